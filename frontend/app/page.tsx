@@ -1,8 +1,8 @@
 "use client";
 
 import { useState, useEffect } from "react";
-import { open } from "@tauri-apps/plugin-dialog";
 import * as api from "../lib/tauri";
+import { submitStatusMessage } from "../lib/submitStatus";
 
 type Tab = "jobs" | "upload" | "settings";
 
@@ -32,6 +32,7 @@ export default function Home() {
   // Upload state
   const [selectedFile, setSelectedFile] = useState<string | null>(null);
   const [selectedTool, setSelectedTool] = useState("");
+  const [tools, setTools] = useState<api.ToolInfo[]>([]);
   const [uploadStatus, setUploadStatus] = useState("");
   const [uploading, setUploading] = useState(false);
 
@@ -57,9 +58,17 @@ export default function Home() {
     total: number;
   } | null>(null);
 
+  // Downloads interrupted by the last quit, offered once connected
+  const [pendingDownloads, setPendingDownloads] = useState<
+    api.PendingDownload[]
+  >([]);
+
   // Showcase mode state
   const [isShowcaseMode, setIsShowcaseMode] = useState(false);
 
+  // Read-only mode; the backend refuses mutating commands regardless
+  const [isReadOnly, setIsReadOnly] = useState(false);
+
   // Toast notification state
   const [toast, setToast] = useState<{
     message: string;
@@ -94,6 +103,8 @@ export default function Home() {
       try {
         const showcaseMode = await api.getShowcaseMode();
         setIsShowcaseMode(showcaseMode);
+        const mode = await api.getAppMode();
+        setIsReadOnly(mode.read_only);
       } catch (err) {
         console.error("Failed to check showcase mode:", err);
       }
@@ -101,6 +112,22 @@ export default function Home() {
     checkShowcaseMode();
   }, []);
 
+  // Tool list for the submit form; the built-in catalog until connected
+  useEffect(() => {
+    const loadTools = async () => {
+      try {
+        const list = await api.listTools();
+        setTools(list.tools);
+        if (list.error) {
+          console.error(list.error);
+        }
+      } catch (err) {
+        console.error("Failed to list tools:", err);
+      }
+    };
+    loadTools();
+  }, [isConnected]);
+
   // Load and apply saved zoom level
   useEffect(() => {
     const loadZoom = async () => {
@@ -163,21 +190,31 @@ export default function Home() {
     const setupDownloadListeners = async () => {
       const { listen } = await import("@tauri-apps/api/event");
 
-      const unlistenProgress = await listen<{
-        filename: string;
-        downloaded: number;
-        total: number;
-      }>("download-progress", (event) => {
-        setJobDownloadProgress(event.payload);
-      });
+      const unlistenProgress = await listen<api.DownloadProgress>(
+        "download-progress",
+        (event) => {
+          setJobDownloadProgress(event.payload);
+        }
+      );
 
       const unlistenComplete = await listen("download-complete", () => {
         setJobDownloadProgress(null);
       });
 
+      const unlistenPending = await listen<api.PendingDownload[]>(
+        "pending-downloads-found",
+        (event) => {
+          setPendingDownloads(event.payload);
+        }
+      );
+
+      // Pick up downloads that were already running before a reload
+      await api.frontendReady();
+
       return () => {
         unlistenProgress();
         unlistenComplete();
+        unlistenPending();
       };
     };
 
@@ -191,6 +228,83 @@ export default function Home() {
     };
   }, []);
 
+  // Offer to resume interrupted downloads once we can actually run them
+  useEffect(() => {
+    if (!isConnected || pendingDownloads.length === 0) return;
+
+    const missing = pendingDownloads.filter((d) => d.output_dir_missing);
+    let message = `${pendingDownloads.length} download(s) were interrupted last time. Resume them now?`;
+    if (missing.length > 0) {
+      message += `\n\n${missing.length} of them will be skipped because the output folder no longer exists.`;
+    }
+    setPendingDownloads([]);
+
+    if (window.confirm(message)) {
+      api
+        .resumePendingDownloads()
+        .then((count) => showToast(`Resuming ${count} download(s)`, "info"))
+        .catch((err) => showToast(`Failed to resume downloads: ${err}`, "error"));
+    } else {
+      api.discardPendingDownloads().catch(() => {});
+    }
+  }, [isConnected, pendingDownloads]);
+
+  // Follow the background monitor's job list updates instead of polling
+  useEffect(() => {
+    if (!isConnected) return;
+    let lastSeq = 0;
+
+    const setupJobListeners = async () => {
+      const { listen } = await import("@tauri-apps/api/event");
+
+      const unlistenFull = await listen<api.JobsUpdate>(
+        "jobs-refreshed",
+        (event) => {
+          if (event.payload.kind !== "full") return;
+          lastSeq = event.payload.seq;
+          setJobs(event.payload.jobs);
+        }
+      );
+
+      const unlistenDiff = await listen<api.JobsDiff>("jobs-diff", (event) => {
+        const diff = event.payload;
+        if (lastSeq !== 0 && diff.seq !== lastSeq + 1) {
+          // Missed an update; the full list arrives as jobs-refreshed
+          api.refreshJobsDiff(true).catch(() => {});
+          return;
+        }
+        lastSeq = diff.seq;
+        setJobs((current) => {
+          const replaced = new Map(
+            [...diff.added, ...diff.changed.map((c) => c.job)].map((j) => [
+              j.job_id,
+              j,
+            ])
+          );
+          return current
+            .filter(
+              (j) => !diff.removed.includes(j.job_id) && !replaced.has(j.job_id)
+            )
+            .concat([...replaced.values()]);
+        });
+      });
+
+      return () => {
+        unlistenFull();
+        unlistenDiff();
+      };
+    };
+
+    let cleanup: (() => void) | undefined;
+    setupJobListeners().then((fn) => {
+      cleanup = fn;
+    });
+
+    return () => {
+      if (cleanup) cleanup();
+    };
+  }, [isConnected]);
+
   // Auto-refresh jobs
   useEffect(() => {
     if (!autoRefresh || !isConnected) return;
@@ -234,12 +348,10 @@ export default function Home() {
 
   const handleSelectDownloadDir = async () => {
     try {
-      const selected = await open({
-        directory: true,
-        multiple: false,
-        defaultPath: tempDownloadDir || downloadDir,
+      const selected = await api.pickPath("folder", {
+        default_path: tempDownloadDir || downloadDir,
       });
-      if (selected && typeof selected === "string") {
+      if (selected) {
         setTempDownloadDir(selected);
       }
     } catch (err) {
@@ -347,8 +459,12 @@ export default function Home() {
 
   const loadStoredCredentials = async () => {
     try {
-      const creds = await api.loadCredentials();
-      if (creds) {
+      const loaded = await api.loadCredentials();
+      if (loaded.status === "unreadable") {
+        setStatus(`Saved credentials can't be used: ${loaded.error}`);
+      }
+      if (loaded.status === "found") {
+        const creds = loaded.credentials;
         setUsername(creds.username);
         setPassword(creds.password);
         setAppKey(creds.app_key);
@@ -372,7 +488,7 @@ export default function Home() {
       setIsConnected(true);
       await refreshJobs();
     } catch (err: any) {
-      setStatus(`Connection failed: ${err}`);
+      setStatus(`Connection failed: ${err?.message ?? err}`);
       setIsConnected(false);
     }
   };
@@ -391,11 +507,10 @@ export default function Home() {
 
   const handleSelectFile = async () => {
     try {
-      const file = await open({
-        multiple: false,
+      const file = await api.pickPath("file", {
         filters: [{ name: "ZIP files", extensions: ["zip"] }],
       });
-      if (file && typeof file === "string") {
+      if (file) {
         setSelectedFile(file);
       }
     } catch (err) {
@@ -412,8 +527,10 @@ export default function Home() {
     setUploading(true);
     setUploadStatus("Submitting job...");
     try {
-      const jobId = await api.submitJob(selectedFile, selectedTool);
-      setUploadStatus(`Job submitted: ${jobId}`);
+      const result = await api.submitJob(selectedFile, selectedTool, {
+        queue_if_offline: true,
+      });
+      setUploadStatus(submitStatusMessage(result));
       setSelectedFile(null);
       setSelectedTool("");
       await refreshJobs();
@@ -426,10 +543,17 @@ export default function Home() {
 
   const handleDownload = async (jobUrl: string) => {
     try {
-      const dir = await api.getDownloadDir();
-      const zipPath = await api.downloadResults(jobUrl, dir);
-      const filename = zipPath.split("/").pop() || "archive.zip";
-      showToast(`Downloaded ${filename} to Downloads`, "success");
+      // Let the download rules choose the folder
+      let result = await api.downloadResults(jobUrl, null);
+      if (result.status === "confirmation_required") {
+        const gb = (result.total_bytes / 1024 ** 3).toFixed(1);
+        if (!window.confirm(`These results are ${gb} GB. Download anyway?`)) {
+          return;
+        }
+        result = await api.downloadResults(jobUrl, null, true);
+      }
+      if (result.status !== "completed") return;
+      showToast(`Downloaded to ${result.path}`, "success");
     } catch (err: any) {
       showToast(`Download failed: ${err}`, "error");
     }
@@ -982,6 +1106,12 @@ export default function Home() {
           <div>
             <h2 className="text-2xl font-bold mb-6">Submit Job</h2>
 
+            {isReadOnly && (
+              <div className="alert alert-warning mb-6">
+                <span>Read-only mode is active. Job submission is disabled.</span>
+              </div>
+            )}
+
             <div className="card bg-base-100 shadow-xl">
               <div className="card-body space-y-6">
                 <div className="form-control">
@@ -1011,55 +1141,22 @@ export default function Home() {
                     className="select select-bordered w-full"
                   >
                     <option value="">Select a tool...</option>
-                    <option value="AMICA_EXPANSE">AMICA_EXPANSE</option>
-                    <option value="BLUEPYOPT_EXPANSE">BLUEPYOPT_EXPANSE</option>
-                    <option value="BLUEPYOPT_EXPANSE1143">
-                      BLUEPYOPT_EXPANSE1143
-                    </option>
-                    <option value="CORENEURON_EXPANSE">
-                      CORENEURON_EXPANSE
-                    </option>
-                    <option value="EEGLAB_EXPANSE">EEGLAB_EXPANSE</option>
-                    <option value="GPU_PY_EXPANSE">GPU_PY_EXPANSE</option>
-                    <option value="HNN_EXPANSE">HNN_EXPANSE</option>
-                    <option value="HNN_GUI_EXPANSE">HNN_GUI_EXPANSE</option>
-                    <option value="MATLAB_EXPANSE">MATLAB_EXPANSE</option>
-                    <option value="MRTRIX_EXPANSE">MRTRIX_EXPANSE</option>
-                    <option value="NEMAR_EXPANSE">NEMAR_EXPANSE</option>
-                    <option value="NEURON_EXPANSE">NEURON_EXPANSE</option>
-                    <option value="NIC_CONVERTER_EXPANSE">
-                      NIC_CONVERTER_EXPANSE
-                    </option>
-                    <option value="NIC_CORRELATOR_EXPANSE">
-                      NIC_CORRELATOR_EXPANSE
-                    </option>
-                    <option value="NIC_TDA_EXPANSE">NIC_TDA_EXPANSE</option>
-                    <option value="OSBv2_EXPANSE_0_7_3">
-                      OSBv2_EXPANSE_0_7_3
-                    </option>
-                    <option value="PYTORCH_PY_EXPANSE">
-                      PYTORCH_PY_EXPANSE
-                    </option>
-                    <option value="PY_CRI">PY_CRI</option>
-                    <option value="PY_EXPANSE">PY_EXPANSE</option>
-                    <option value="SINGULARITY_PGENESIS24_EXPANSE">
-                      SINGULARITY_PGENESIS24_EXPANSE
-                    </option>
-                    <option value="SPIKEINTERFACE_EXPANSE">
-                      SPIKEINTERFACE_EXPANSE
-                    </option>
-                    <option value="TENSORFLOW_PY_EXPANSE">
-                      TENSORFLOW_PY_EXPANSE
-                    </option>
-                    <option value="TENSORFLOW_PY_NSGOSG">
-                      TENSORFLOW_PY_NSGOSG
-                    </option>
+                    {tools.map((tool) => (
+                      <option
+                        key={tool.tool_id}
+                        value={tool.tool_id}
+                        title={tool.description ?? undefined}
+                      >
+                        {tool.tool_id}
+                        {tool.gpu ? " (GPU)" : ""}
+                      </option>
+                    ))}
                   </select>
                 </div>
 
                 <button
                   onClick={handleSubmitJob}
-                  disabled={uploading}
+                  disabled={uploading || isReadOnly}
                   className="btn btn-primary w-full"
                 >
                   {uploading && (
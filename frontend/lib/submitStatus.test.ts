@@ -0,0 +1,35 @@
+import { test } from 'node:test';
+import assert from 'node:assert/strict';
+import { submitStatusMessage } from './submitStatus.ts';
+
+test('a confirmed submission names the job', () => {
+  const message = submitStatusMessage({
+    status: 'submitted',
+    job_id: 'NGBW-JOB-1',
+    warning: null,
+    receipt_warning: null,
+    lint: [],
+    param_sources: {},
+  });
+  assert.equal(message, 'Job submitted: NGBW-JOB-1');
+});
+
+test('a queued submission is blamed on the connection', () => {
+  const message = submitStatusMessage({ status: 'queued', id: 'q1' });
+  assert.match(message, /^No connection/);
+});
+
+test('an unconfirmed submission points at the job list instead', () => {
+  for (const job_id of ['NGBW-JOB-2', null]) {
+    const message = submitStatusMessage({
+      status: 'submitted_but_unconfirmed',
+      id: 'u1',
+      job_id,
+      job_url: null,
+      error: 'unreadable response',
+    });
+    assert.doesNotMatch(message, /No connection/);
+    assert.match(message, /check the job list/);
+    if (job_id) assert.match(message, /NGBW-JOB-2/);
+  }
+});
@@ -0,0 +1,16 @@
+import type { SubmitResponse } from './tauri';
+
+// What the submit form says once submit_job returns
+export function submitStatusMessage(result: SubmitResponse): string {
+  switch (result.status) {
+    case 'submitted':
+      return `Job submitted: ${result.job_id}`;
+    case 'queued':
+      return 'No connection - the job will be submitted when NSG is reachable again';
+    case 'submitted_but_unconfirmed':
+      // NSG may have taken it; sending it again could run it twice
+      return result.job_id
+        ? `NSG received job ${result.job_id} but didn't confirm it yet - check the job list before submitting again`
+        : "NSG may have received the job but didn't confirm it - check the job list before submitting again";
+  }
+}
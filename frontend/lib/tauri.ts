@@ -14,6 +14,14 @@ export interface JobSummary {
   failed: boolean;
   date_submitted: string | null;
   date_completed: string | null;
+  compute_resource: string | null;
+  tool_version: string | null;
+  project_id: string | null;
+  // Reported by NSG, or estimated from date_completed and the retention setting
+  results_expire_at: string | null;
+  // Set on jobs of the saved list returned before the first listing of the
+  // session; a jobs-refreshed event replaces them
+  stale?: boolean;
 }
 
 export interface JobDetails {
@@ -23,59 +31,1828 @@ export interface JobDetails {
   date_submitted: string | null;
   self_uri: string;
   results_uri: string | null;
+  compute_resource: string | null;
+  tool_version: string | null;
+  // Whether NSG was asked to email on completion; null if the record is silent
+  notification_requested: boolean | null;
+  notification_email: string | null;
+  // Best-effort, from the job messages; null when nothing was recognized
+  scheduler_info: SchedulerInfo | null;
+  queue_note: string | null;
+  results_expire_at: string | null;
+}
+
+export interface SchedulerInfo {
+  resource: string | null;
+  requested_nodes: number | null;
+  requested_cores: number | null;
+  requested_runtime: string | null;
 }
 
 export interface DownloadInfo {
   filename: string;
   path: string;
-  size: number;
+  size: number;
+}
+
+// 'unreadable' means a file is there but can't be used; checkCredentialsFile
+// says why
+export type LoadedCredentials =
+  | { status: 'found'; credentials: Credentials }
+  | { status: 'missing'; path: string }
+  | { status: 'unreadable'; path: string; error: string };
+
+// From the configured backend: the credentials file or the OS credential
+// store
+export async function loadCredentials(): Promise<LoadedCredentials> {
+  return await invoke<LoadedCredentials>('load_credentials');
+}
+
+// The OS credential store: macOS Keychain, Windows Credential Manager, or the
+// Secret Service on Linux
+export type CredentialBackend = 'file' | 'keychain';
+
+export async function saveCredentialsSecure(credentials: Credentials): Promise<void> {
+  return await invoke<void>('save_credentials_secure', { credentials });
+}
+
+export async function loadCredentialsSecure(): Promise<LoadedCredentials> {
+  return await invoke<LoadedCredentials>('load_credentials_secure');
+}
+
+export async function getCredentialBackend(): Promise<CredentialBackend> {
+  return await invoke<CredentialBackend>('get_credential_backend');
+}
+
+// Nothing is moved; saveCredentialsSecure fills the keychain
+export async function setCredentialBackend(backend: CredentialBackend): Promise<void> {
+  return await invoke<void>('set_credential_backend', { backend });
+}
+
+// Saved credential profiles, e.g. a lab account and a personal one
+export interface ProfileInfo {
+  id: string;
+  name: string;
+  username: string;
+  created_at: string;
+  // Password and app key are in the OS credential store
+  keychain: boolean;
+  active: boolean;
+}
+
+export async function listProfiles(): Promise<ProfileInfo[]> {
+  return await invoke<ProfileInfo[]>('list_profiles');
+}
+
+// keychain defaults to the credential backend preference
+export async function addProfile(
+  name: string,
+  username: string,
+  password: string,
+  appKey: string,
+  keychain?: boolean
+): Promise<ProfileInfo> {
+  return await invoke<ProfileInfo>('add_profile', {
+    name,
+    username,
+    password,
+    appKey,
+    keychain,
+  });
+}
+
+// Connects with the profile's credentials, replacing the current session,
+// and moves to its account's state
+export async function switchProfile(id: string): Promise<ProfileInfo> {
+  return await invoke<ProfileInfo>('switch_profile', { id });
+}
+
+export async function deleteProfile(id: string): Promise<void> {
+  return await invoke<void>('delete_profile', { id });
+}
+
+// Never carries the password or app key
+export interface CredentialsFileCheck {
+  path: string;
+  exists: boolean;
+  read_error: string | null;
+  parsed: boolean;
+  parse_error: string | null;
+  fields: { name: 'username' | 'password' | 'app_key'; present: boolean }[];
+  // e.g. "0644"; null off Unix
+  permissions: string | null;
+  too_permissive: boolean;
+  warnings: string[];
+  can_fix_permissions: boolean;
+}
+
+export async function checkCredentialsFile(): Promise<CredentialsFileCheck> {
+  return await invoke<CredentialsFileCheck>('check_credentials_file');
+}
+
+// chmod 0600 on the credentials file (macOS and Linux)
+export async function fixCredentialsPermissions(): Promise<CredentialsFileCheck> {
+  return await invoke<CredentialsFileCheck>('fix_credentials_permissions');
+}
+
+export type ConnectPhase =
+  | 'building_client'
+  | 'resolving_host'
+  | 'tls_handshake'
+  | 'authenticating';
+
+// Rejection value of connect(); `connect-progress` events carry { phase }.
+// `message` is already localized; `key` and `params` allow doing it here.
+// The credential a rejected connect most likely got wrong; 'password'
+// covers the username too
+export type CredentialCulprit = 'password' | 'app_key' | 'unknown';
+
+export interface ConnectError {
+  phase: ConnectPhase;
+  // Set when the server rejected the credentials
+  culprit: CredentialCulprit | null;
+  key: string;
+  params: Record<string, string>;
+  message: string;
+}
+
+export async function connect(
+  username: string,
+  password: string,
+  appKey: string
+): Promise<string> {
+  return await invoke<string>('connect', {
+    username,
+    password,
+    appKey,
+  });
+}
+
+// Clears the session; emits `disconnected`
+export async function disconnect(): Promise<void> {
+  return await invoke<void>('disconnect');
+}
+
+// NSG requests currently running; also emitted as `network-activity` when
+// the app goes from idle to busy or back
+export interface NetworkActivity {
+  in_flight: number;
+  // What the most recent request does, e.g. "list jobs"
+  newest: string | null;
+}
+
+export async function getNetworkActivity(): Promise<NetworkActivity> {
+  return await invoke<NetworkActivity>('get_network_activity');
+}
+
+// Offset between the local clock and NSG's, sampled from response headers.
+// Once applied, relative times use the corrected clock; `clock-skew-detected`
+// carries this the first time it happens.
+export interface ClockSkew {
+  // Positive when the local clock is slow
+  offset_secs: number;
+  applied: boolean;
+  samples: number;
+}
+
+export async function getClockSkew(): Promise<ClockSkew> {
+  return await invoke<ClockSkew>('get_clock_skew');
+}
+
+// Commands whose credentials changed underneath them (a connect or
+// disconnect while they ran) reject with an error starting "SessionChanged";
+// such results belong to the previous session and should just be dropped
+export function isSessionChanged(error: unknown): boolean {
+  return String(error).startsWith('SessionChanged');
+}
+
+// A state file stayed locked by another copy of the app for too long; the
+// action can be retried once that copy is done with it
+export function isStateLocked(error: unknown): boolean {
+  return String(error).startsWith('StateLocked');
+}
+
+// Deleting all app data. Downloaded result archives are left alone.
+export const PURGE_PHRASE = 'DELETE EVERYTHING';
+
+export type PurgeOutcome = 'removed' | 'not_present' | 'failed';
+
+export interface PurgeItem {
+  item: string;
+  outcome: PurgeOutcome;
+  error: string | null;
+}
+
+export interface PurgeReport {
+  items: PurgeItem[];
+}
+
+// Only runs when confirmPhrase is exactly PURGE_PHRASE; emits `app-data-purged`
+export async function purgeAppData(confirmPhrase: string): Promise<PurgeReport> {
+  return await invoke<PurgeReport>('purge_app_data', { confirmPhrase });
+}
+
+// Auto-connect: the outcome arrives as a `connected` or
+// `auto-connect-failed` event
+export interface AutoConnectStart {
+  attempting: boolean;
+  username: string | null;
+}
+
+export interface AutoConnectFailed {
+  username: string;
+  reason: 'bad_credentials' | 'network' | 'other';
+  phase: ConnectPhase;
+  message: string;
+}
+
+export async function autoConnect(): Promise<AutoConnectStart> {
+  return await invoke<AutoConnectStart>('auto_connect');
+}
+
+export type AppKeyProblem =
+  | 'empty'
+  | 'whitespace'
+  | 'too_short'
+  | 'too_long'
+  | 'invalid_characters'
+  | 'looks_like_url'
+  | 'same_as_password';
+
+export interface AppKeyFormat {
+  valid: boolean;
+  problems: AppKeyProblem[];
+}
+
+// Local format check of an app key; no network call
+export async function validateAppKeyFormat(
+  key: string,
+  password?: string
+): Promise<AppKeyFormat> {
+  return await invoke<AppKeyFormat>('validate_app_key_format', {
+    key,
+    password: password ?? null,
+  });
+}
+
+export async function getAutoConnect(): Promise<boolean> {
+  return await invoke<boolean>('get_auto_connect');
+}
+
+export async function setAutoConnect(enabled: boolean): Promise<void> {
+  return await invoke<void>('set_auto_connect', { enabled });
+}
+
+export async function deleteSavedCredentials(): Promise<void> {
+  return await invoke<void>('delete_saved_credentials');
+}
+
+// Pass a project id to list only the jobs in that project
+export async function listJobs(
+  projectId?: string,
+  expiringSoon?: boolean
+): Promise<JobSummary[]> {
+  return await invoke<JobSummary[]>('list_jobs', { projectId, expiringSoon });
+}
+
+// Incremental job list updates. The monitor emits `jobs-refreshed` with the
+// whole list first, then `jobs-diff` deltas; each carries a seq one higher
+// than the last. On a gap, call refreshJobsDiff(true) for a new full list.
+export interface ChangedJob {
+  job: JobSummary;
+  fields: string[];
+}
+
+export interface JobsDiff {
+  seq: number;
+  added: JobSummary[];
+  removed: string[];
+  changed: ChangedJob[];
+}
+
+export type JobsUpdate =
+  | { kind: 'full'; seq: number; jobs: JobSummary[] }
+  | ({ kind: 'diff' } & JobsDiff)
+  | { kind: 'unchanged'; seq: number };
+
+export async function refreshJobsDiff(full = false): Promise<JobsUpdate> {
+  return await invoke<JobsUpdate>('refresh_jobs_diff', { full });
+}
+
+// Discard the saved job list and rebuild it from a fresh listing; the
+// result is a full update
+export async function rebuildJobCache(): Promise<JobsUpdate> {
+  return await invoke<JobsUpdate>('rebuild_job_cache');
+}
+
+// One window of the job list for a virtualized table, from the last listing
+// (or the disk cache before one, with stale set). Never contacts the server.
+// Jobs without the sort field come last in either direction; ties go by job
+// ID. data_version matches the seq of jobs-refreshed / jobs-diff: refetch
+// visible windows when it changes.
+export type JobSortField =
+  | 'date_submitted'
+  | 'date_completed'
+  | 'results_expire_at'
+  | 'tool'
+  | 'stage'
+  | 'job_id';
+
+export interface JobSort {
+  field: JobSortField;
+  direction: 'asc' | 'desc';
+}
+
+export interface JobFilter {
+  tool?: string;
+  stages?: string[];
+  project_id?: string;
+  failed?: boolean;
+  expiring_soon?: boolean;
+  // Part of the job ID or tool
+  text?: string;
+}
+
+export interface JobWindow {
+  offset: number;
+  total: number;
+  jobs: JobSummary[];
+  data_version: number;
+  stale: boolean;
+}
+
+// limit is capped at 1000; sort defaults to date_submitted descending
+export async function queryJobs(
+  window: { offset: number; limit: number },
+  sort?: JobSort,
+  filter?: JobFilter
+): Promise<JobWindow> {
+  return await invoke<JobWindow>('query_jobs', { window, sort, filter });
+}
+
+// Diagnostics: what the job list snapshot holds in memory. Jobs past
+// resident_limit are kept as fingerprints and read from the disk cache.
+export interface JobSnapshotStats {
+  resident_jobs: number;
+  resident_limit: number;
+  older_jobs: number;
+  interned_strings: number;
+  approx_bytes: number;
+}
+
+export interface JobIndexStats {
+  indexed_jobs: number;
+  cached_orders: number;
+}
+
+export interface MemoryStats {
+  job_snapshot: JobSnapshotStats;
+  job_index: JobIndexStats;
+  // Monitor refreshes that found the job listing unchanged
+  skipped_unchanged: number;
+}
+
+export async function getMemoryStats(): Promise<MemoryStats> {
+  return await invoke<MemoryStats>('get_memory_stats');
+}
+
+export async function getJobStatus(jobUrl: string): Promise<JobDetails> {
+  return await invoke<JobDetails>('get_job_status', { jobUrl });
+}
+
+// A tool to offer for submission. listed is false when the server's list
+// couldn't be fetched and the built-in catalog is used instead.
+export interface ToolInfo {
+  tool_id: string;
+  name: string;
+  description: string | null;
+  gpu: boolean;
+  listed: boolean;
+}
+
+export interface ToolList {
+  tools: ToolInfo[];
+  from_server: boolean;
+  error: string | null;
+}
+
+export async function listTools(): Promise<ToolList> {
+  return await invoke<ToolList>('list_tools');
+}
+
+// Kill a queued or running job; the job list is refreshed right after
+export async function cancelJob(jobUrl: string): Promise<void> {
+  return await invoke<void>('cancel_job', { jobUrl });
+}
+
+// Payload of `job-deleted`
+export interface JobDeleted {
+  job_id: string;
+  job_url: string;
+}
+
+// Remove a finished job and its results from NSG. Jobs still queued or
+// running are refused unless force is set, which cancels them.
+export async function deleteJob(jobUrl: string, force?: boolean): Promise<void> {
+  return await invoke<void>('delete_job', { jobUrl, force });
+}
+
+// Per-stage timing of a job. Boundaries come from NSG's job messages and
+// dates; `estimated` segments have a boundary from the app's own polling.
+// Execution is the SUBMITTED stage, which includes the cluster's queue.
+export interface StageSegment {
+  stage: string;
+  entered_at: string;
+  duration_secs: number | null;
+  estimated: boolean;
+}
+
+export interface JobTiming {
+  job_id: string;
+  stage: string;
+  segments: StageSegment[];
+  queue_wait_secs: number | null;
+  staging_secs: number | null;
+  execution_secs: number | null;
+  total_secs: number | null;
+  incomplete: boolean;
+}
+
+export async function getJobTiming(jobUrl: string): Promise<JobTiming> {
+  return await invoke<JobTiming>('get_job_timing', { jobUrl });
+}
+
+// Image results for the gallery; `thumbnail` says whether
+// getResultThumbnail can make one (PNG, JPEG, GIF, BMP, TIFF or WebP up to
+// 20 MB)
+export interface ImageResult {
+  filename: string;
+  size: number;
+  thumbnail: boolean;
+}
+
+export async function listImageResults(jobUrl: string): Promise<ImageResult[]> {
+  return await invoke<ImageResult[]>('list_image_results', { jobUrl });
+}
+
+export type ThumbnailResponse =
+  // data is a base64 PNG at most maxDim pixels on the long edge
+  | {
+      status: 'ready';
+      data: string;
+      width: number;
+      height: number;
+      original_width: number;
+      original_height: number;
+      cached: boolean;
+    }
+  | { status: 'unsupported'; filename: string }
+  | { status: 'too_large'; filename: string; size: number; max_bytes: number };
+
+export async function getResultThumbnail(
+  jobUrl: string,
+  filename: string,
+  maxDim: number
+): Promise<ThumbnailResponse> {
+  return await invoke<ThumbnailResponse>('get_result_thumbnail', { jobUrl, filename, maxDim });
+}
+
+// Disk space for cached thumbnails, in bytes; the least recently used go
+// first once it's exceeded
+export async function getThumbnailCacheLimit(): Promise<number> {
+  return await invoke<number>('get_thumbnail_cache_limit');
+}
+
+export async function setThumbnailCacheLimit(bytes: number): Promise<void> {
+  return await invoke<void>('set_thumbnail_cache_limit', { bytes });
+}
+
+// Writes a self-contained HTML report of one job; resolves to the path
+// written (".html" is added when `path` has no extension)
+export async function exportJobReport(jobUrl: string, path: string): Promise<string> {
+  return await invoke<string>('export_job_report', { jobUrl, path });
+}
+
+// A resource request that looks like a mistake, e.g. more runtime than the
+// tool allows; key and params are for localizing it in the frontend
+export interface LintFinding {
+  rule: string;
+  severity: 'info' | 'warning';
+  parameter: string;
+  key: string;
+  params: Record<string, string>;
+  message: string;
+}
+
+// Where a submitted field's value came from; later layers win:
+// app < lab < user < explicit
+export type ParamSource = 'app' | 'lab' | 'user' | 'explicit';
+
+// What a submission of a tool gets without explicit arguments, for
+// pre-filling the submit form. lab_error says why the lab file was ignored.
+export interface EffectiveDefaults {
+  tool: string;
+  params: Record<string, string>;
+  sources: Record<string, ParamSource>;
+  lab_file: string | null;
+  lab_error: string | null;
+}
+
+export async function getEffectiveDefaults(tool: string): Promise<EffectiveDefaults> {
+  return await invoke<EffectiveDefaults>('get_effective_defaults', { tool });
+}
+
+// The lab's shared lab_defaults.json. NSG_GUI_LAB_DEFAULTS, when set, wins
+// over the saved path; an empty path turns lab defaults off.
+export async function getLabDefaultsPath(): Promise<string | null> {
+  return await invoke<string | null>('get_lab_defaults_path');
+}
+
+export async function setLabDefaultsPath(path: string): Promise<void> {
+  return await invoke<void>('set_lab_defaults_path', { path });
+}
+
+// Payload of `lab-defaults-invalid`, sent once per version of a file that
+// can't be used
+export interface LabDefaultsWarning {
+  path: string;
+  message: string;
+}
+
+export type SubmitResponse =
+  // warning is advisory, e.g. most recent jobs for the tool failed;
+  // receipt_warning is set when a submission receipt couldn't be written
+  | {
+      status: 'submitted';
+      job_id: string;
+      warning: string | null;
+      receipt_warning: string | null;
+      lint: LintFinding[];
+      param_sources: Record<string, ParamSource>;
+    }
+  | { status: 'queued'; id: string }
+  // Accepted by NSG, but its answer couldn't be read; followed by
+  // `submission-confirmed` once the job's status can be fetched
+  | {
+      status: 'submitted_but_unconfirmed';
+      id: string;
+      job_id: string | null;
+      job_url: string | null;
+      error: string;
+    };
+
+export interface SubmitOptions {
+  params?: Record<string, string>;
+  queue_if_offline?: boolean;
+  project_id?: string;
+  // false turns off NSG's completion email for this job
+  notify?: boolean;
+  // Overrides the default notification address
+  notify_email?: string;
+  // Submit even though an input contains the password or app key
+  ignore_secret_scan?: boolean;
+}
+
+// Tool parameters by vparam name, e.g. { runtime_: '2', number_nodes_: '1' };
+// names with their own prefix (`metadata.`, `vparam.`) are sent as they are
+export type ToolParameters = Record<string, string>;
+
+export async function submitJob(
+  filePath: string,
+  tool: string,
+  options: SubmitOptions = {},
+  parameters?: ToolParameters
+): Promise<SubmitResponse> {
+  return await invoke<SubmitResponse>('submit_job', {
+    filePath,
+    tool,
+    options,
+    parameters,
+  });
+}
+
+// A file sent under a specific input parameter, e.g. `input.treefile_`
+export interface InputBinding {
+  param_name: string;
+  file_path: string;
+}
+
+// For tools with several named inputs; submitJob sends `input.infile_`
+export async function submitJobInputs(
+  inputs: InputBinding[],
+  tool: string,
+  options: SubmitOptions = {},
+  parameters?: ToolParameters
+): Promise<SubmitResponse> {
+  return await invoke<SubmitResponse>('submit_job_inputs', {
+    inputs,
+    tool,
+    options,
+    parameters,
+  });
+}
+
+export interface ReviewedField {
+  value: string;
+  source: ParamSource;
+}
+
+export interface ReviewedInput {
+  param_name: string;
+  file_path: string;
+  size: number;
+  sha256: string;
+}
+
+// A resolved submission awaiting submitPrepared; metadata keys have no
+// "metadata." prefix. estimated_upload_secs is null until an upload has
+// been timed.
+export interface SubmissionReview {
+  plan_id: string;
+  expires_in_secs: number;
+  tool: string;
+  params: Record<string, ReviewedField>;
+  metadata: Record<string, ReviewedField>;
+  inputs: ReviewedInput[];
+  upload_bytes: number;
+  estimated_upload_secs: number | null;
+  lint: LintFinding[];
+}
+
+// Resolves a submission for review without sending it
+export async function prepareSubmission(
+  inputs: InputBinding[],
+  tool: string,
+  params: Record<string, string> = {},
+  metadata: Record<string, string> = {}
+): Promise<SubmissionReview> {
+  return await invoke<SubmissionReview>('prepare_submission', {
+    inputs,
+    tool,
+    params,
+    metadata,
+  });
+}
+
+// Sends a reviewed plan as it is. Fails if the plan expired, was already
+// used, or an input changed since the review.
+export async function submitPrepared(planId: string): Promise<SubmitResponse> {
+  return await invoke<SubmitResponse>('submit_prepared', { planId });
+}
+
+// Zips a folder (under a top-level directory named after it, skipping the
+// exclude patterns) and submits it. Folders over the warning threshold come
+// back as confirmation_required until called with confirmed = true. Never
+// queued offline. Progress arrives as zip-progress, then upload-progress.
+export interface ExcludedEntry {
+  path: string;
+  pattern: string;
+  is_dir: boolean;
+}
+
+export interface SkippedEntry {
+  path: string;
+  reason: string;
+}
+
+export type FolderSubmitResponse =
+  | {
+      status: 'confirmation_required';
+      estimated_bytes: number;
+      threshold_bytes: number;
+      file_count: number;
+      excluded: ExcludedEntry[];
+    }
+  | {
+      status: 'submitted';
+      job_id: string;
+      warning: string | null;
+      file_count: number;
+      archive_bytes: number;
+      excluded: ExcludedEntry[];
+      skipped: SkippedEntry[];
+      lint: LintFinding[];
+      param_sources: Record<string, ParamSource>;
+    }
+  | {
+      status: 'submitted_but_unconfirmed';
+      id: string;
+      job_id: string | null;
+      job_url: string | null;
+      error: string;
+      file_count: number;
+      archive_bytes: number;
+    };
+
+export interface ZipProgress {
+  folder: string;
+  files_done: number;
+  files_total: number;
+  bytes_done: number;
+  bytes_total: number;
+}
+
+// rate and projection are null for the first second; the estimate and
+// drift are null with no timed upload to go on. A positive drift_secs means
+// the upload is running behind the estimate.
+export interface UploadProgress {
+  folder: string;
+  bytes_sent: number;
+  bytes_total: number;
+  rate_bytes_per_sec: number | null;
+  estimated_total_secs: number | null;
+  projected_total_secs: number | null;
+  drift_secs: number | null;
+}
+
+export async function submitFolder(
+  folder: string,
+  tool: string,
+  options: SubmitOptions = {},
+  confirmed = false
+): Promise<FolderSubmitResponse> {
+  return await invoke<FolderSubmitResponse>('submit_folder', {
+    folder,
+    tool,
+    options,
+    confirmed,
+  });
+}
+
+// Parameter sweeps: one job per entry, each entry's values sent as vparams
+// (keys containing a dot are sent as they are) and the job named from
+// nameTemplate, e.g. "gNa-{gna}_gK-{gk}". All jobs go into a new project.
+// Failures are recorded per entry; the sweep stops after
+// maxConsecutiveFailures in a row (default: the
+// sweep_max_consecutive_failures preference, 3). sweep-progress follows
+// each entry. The manifest is written beside the input.
+export type SweepEntryStatus = 'submitted' | 'failed' | 'skipped';
+
+export interface SweepEntry {
+  index: number;
+  params: Record<string, string>;
+  job_name: string;
+  status: SweepEntryStatus;
+  job_id: string | null;
+  error: string | null;
+}
+
+export interface SweepProgress {
+  sweep_id: string;
+  total: number;
+  entry: SweepEntry;
+}
+
+export interface SweepResult {
+  sweep_id: string;
+  created_at: string;
+  tool: string;
+  base_input: string;
+  name_template: string;
+  shared_params: Record<string, string>;
+  project_id: string;
+  project_name: string;
+  entries: SweepEntry[];
+  stopped_early: boolean;
+  manifest_path: string;
+  manifest_warning: string | null;
+}
+
+export async function submitSweep(
+  baseInput: string,
+  tool: string,
+  sweep: Record<string, string>[],
+  nameTemplate: string,
+  options: SubmitOptions = {},
+  projectName?: string,
+  maxConsecutiveFailures?: number
+): Promise<SweepResult> {
+  return await invoke<SweepResult>('submit_sweep', {
+    baseInput,
+    tool,
+    sweep,
+    nameTemplate,
+    options,
+    projectName,
+    maxConsecutiveFailures,
+  });
+}
+
+// exclude_patterns add to .git, __pycache__, *.pyc and .DS_Store;
+// warn_bytes = 0 never asks for confirmation
+export interface FolderSubmitSettings {
+  exclude_patterns: string[];
+  warn_bytes: number;
+}
+
+export async function getFolderSubmitSettings(): Promise<FolderSubmitSettings> {
+  return await invoke<FolderSubmitSettings>('get_folder_submit_settings');
+}
+
+export async function setFolderSubmitSettings(
+  settings: FolderSubmitSettings
+): Promise<void> {
+  return await invoke<void>('set_folder_submit_settings', { settings });
+}
+
+// Before each submission the inputs, and the entries of zip inputs, are
+// searched for the password and app key; a hit fails the submission with
+// the file and line. On by default.
+export async function getSecretScanEnabled(): Promise<boolean> {
+  return await invoke<boolean>('get_secret_scan_enabled');
+}
+
+export async function setSecretScanEnabled(enabled: boolean): Promise<void> {
+  return await invoke<void>('set_secret_scan_enabled', { enabled });
+}
+
+// Dry run: every check short of creating a job. serverValidate also sends
+// the files to NSG's validate endpoint.
+export type CheckStatus = 'pass' | 'warn' | 'fail';
+
+export interface DryRunCheck {
+  name: string;
+  status: CheckStatus;
+  detail: string;
+}
+
+// File fields have file_name and size but no value; contents aren't shown
+export interface FormField {
+  name: string;
+  value: string | null;
+  file_name: string | null;
+  size: number | null;
+}
+
+export interface DryRunReport {
+  verdict: CheckStatus;
+  checks: DryRunCheck[];
+  fields: FormField[];
+  upload_bytes: number;
+  estimated_upload_secs: number | null;
+  lint: LintFinding[];
+  param_sources: Record<string, ParamSource>;
+}
+
+// Recent outcomes for a tool; warning is set when most of its last few
+// finished jobs failed. Never a reason to block a submission.
+export interface ToolHealth {
+  tool: string;
+  recent_jobs: number;
+  failed_jobs: number;
+  window_days: number;
+  warning: string | null;
+}
+
+export async function getToolHealth(tool: string): Promise<ToolHealth> {
+  return await invoke<ToolHealth>('get_tool_health', { tool });
+}
+
+// `results-expiring-soon` carries ExpiringJob[]: completed jobs that were
+// never downloaded and are within warning_days of NSG deleting the results.
+// Each job is reported once per session.
+export interface ExpiringJob {
+  job_id: string;
+  url: string;
+  tool: string | null;
+  results_expire_at: string;
+}
+
+export interface ExpirySettings {
+  // Used to estimate the expiry when the job record doesn't give one
+  retention_days: number;
+  warning_days: number;
+}
+
+export async function getExpirySettings(): Promise<ExpirySettings> {
+  return await invoke<ExpirySettings>('get_expiry_settings');
+}
+
+export async function setExpirySettings(settings: ExpirySettings): Promise<void> {
+  await invoke('set_expiry_settings', { settings });
+}
+
+// Sent as `download-notification` when a download finishes while the main
+// window isn't in use; show it as a desktop notification and call
+// downloadNotificationClicked when it's clicked
+export interface DownloadNotification {
+  history_id: string;
+  job_id: string;
+  title: string;
+  body: string;
+}
+
+export type NotificationClickAction = 'reveal_in_app' | 'open_folder';
+
+// Local times as "HH:MM"; a start after the end spans midnight
+export interface QuietHours {
+  start: string;
+  end: string;
+}
+
+export interface NotificationSettings {
+  enabled: boolean;
+  click_action: NotificationClickAction;
+  // No OS notifications in this window; null for none
+  quiet_hours: QuietHours | null;
+}
+
+export async function getNotificationSettings(): Promise<NotificationSettings> {
+  return await invoke<NotificationSettings>('get_notification_settings');
+}
+
+export async function setNotificationSettings(settings: NotificationSettings): Promise<void> {
+  await invoke('set_notification_settings', { settings });
+}
+
+// Focuses the window, then emits `reveal-download` with { history_id } or
+// opens the file manager, per the click action
+export async function downloadNotificationClicked(historyId: string): Promise<void> {
+  await invoke('download_notification_clicked', { historyId });
+}
+
+export type AnnouncementSeverity = 'info' | 'warning' | 'critical';
+
+// New warning and critical entries also arrive as `service-announcement`
+export interface Announcement {
+  id: string;
+  title: string;
+  body: string;
+  severity: AnnouncementSeverity;
+  starts_at: string | null;
+  ends_at: string | null;
+  dismissed: boolean;
+}
+
+// Empty when the feed can't be reached
+export async function getServiceAnnouncements(): Promise<Announcement[]> {
+  return await invoke<Announcement[]>('get_service_announcements');
+}
+
+export async function dismissAnnouncement(id: string): Promise<void> {
+  await invoke('dismiss_announcement', { id });
+}
+
+export async function getAnnouncementsUrl(): Promise<string> {
+  return await invoke<string>('get_announcements_url');
+}
+
+// An empty URL restores the default feed
+export async function setAnnouncementsUrl(url: string): Promise<void> {
+  await invoke('set_announcements_url', { url });
+}
+
+// Failures of background work (the job monitor, the submission queue,
+// cleanup sweeps). Each source and kind is sent as `background-error` at
+// most every 30 seconds with a running count, and `background-error-resolved`
+// follows once the source works again. Commands still reject directly.
+export type BackgroundErrorKind = 'network' | 'auth' | 'server' | 'other';
+
+export interface BackgroundError {
+  source: string;
+  kind: BackgroundErrorKind;
+  message: string;
+  count: number;
+  first_seen: string;
+  last_seen: string;
+}
+
+export interface BackgroundErrorResolved {
+  source: string;
+  kind: BackgroundErrorKind;
+}
+
+// Unresolved errors seen in the last five minutes, for a reopened window
+export async function getActiveBackgroundErrors(): Promise<BackgroundError[]> {
+  return await invoke<BackgroundError[]>('get_active_background_errors');
+}
+
+export type ServiceHealth = 'disconnected' | 'unknown' | 'online' | 'offline';
+
+// For the status bar. Updates arrive as `session-status`, at most once a
+// second and only when something changed.
+export interface SessionStatus {
+  username: string | null;
+  jobs_running: number;
+  downloads_active: number;
+  last_refresh_at: string | null;
+  health: ServiceHealth;
+  // Most severe announcement in effect that isn't dismissed
+  announcement: AnnouncementSeverity | null;
+}
+
+export async function getSessionStatus(): Promise<SessionStatus> {
+  return await invoke<SessionStatus>('get_session_status');
+}
+
+export async function dryRunSubmit(
+  inputs: InputBinding[],
+  tool: string,
+  options: SubmitOptions = {},
+  serverValidate = false
+): Promise<DryRunReport> {
+  return await invoke<DryRunReport>('dry_run_submit', {
+    inputs,
+    tool,
+    options,
+    serverValidate,
+  });
+}
+
+// Default address for NSG's completion emails; null means the account's.
+// An invalid stored address is reported after connecting as a
+// `notification-email-invalid` event.
+export async function getDefaultNotificationEmail(): Promise<string | null> {
+  return await invoke<string | null>('get_default_notification_email');
+}
+
+export async function setDefaultNotificationEmail(
+  email: string | null
+): Promise<void> {
+  return await invoke<void>('set_default_notification_email', { email });
+}
+
+// Local projects; deleting one never deletes its jobs
+export interface Project {
+  id: string;
+  name: string;
+  created_at: string;
+}
+
+export interface ProjectSummary extends Project {
+  job_count: number;
+}
+
+export async function listProjects(): Promise<ProjectSummary[]> {
+  return await invoke<ProjectSummary[]>('list_projects');
+}
+
+export async function createProject(name: string): Promise<Project> {
+  return await invoke<Project>('create_project', { name });
+}
+
+export async function renameProject(id: string, name: string): Promise<Project> {
+  return await invoke<Project>('rename_project', { id, name });
+}
+
+// Returned by local deletes and sent as `local-delete`; pass the token to
+// undoLocalDelete before expires_at to restore what was deleted.
+// `local-delete-undone` carries { token, kind } once it's restored.
+export interface LocalDelete {
+  token: string;
+  kind: 'project' | 'download_history' | 'annotations';
+  count: number;
+  expires_at: string;
+}
+
+export async function undoLocalDelete(token: string): Promise<void> {
+  await invoke('undo_local_delete', { token });
+}
+
+export async function deleteProject(
+  id: string,
+  reassignTo?: string
+): Promise<LocalDelete> {
+  return await invoke<LocalDelete>('delete_project', { id, reassignTo });
+}
+
+export async function assignJobToProject(
+  jobId: string,
+  projectId: string | null
+): Promise<void> {
+  return await invoke<void>('assign_job_to_project', { jobId, projectId });
+}
+
+// Local metadata of a job: submitted from the app, or adopted after being
+// submitted elsewhere. Adopting again merges tags and keeps the old note
+// unless a new one is given.
+export interface StageSighting {
+  stage: string;
+  seen_at: string;
+}
+
+export interface LocalJob {
+  job_id: string;
+  job_url: string;
+  tool: string | null;
+  origin: 'submitted' | 'adopted';
+  added_at: string;
+  tags: string[];
+  note: string | null;
+  // When tags and note last changed; missing in older entries
+  tags_modified_at?: string;
+  note_modified_at?: string;
+  // Submitted form fields, for jobs submitted from the app
+  params?: Record<string, string>;
+  stages: StageSighting[];
+  // The job dropped out of the server listing, e.g. deleted in the portal
+  server_deleted: boolean;
+  server_deleted_at?: string;
+}
+
+// Moving tags, notes and projects between devices through a bundle file.
+// 'theirs' takes the bundle's side of a difference, 'mine' keeps this
+// device's, 'merge' unions tags and keeps the newer note. Nothing is ever
+// removed, and the files are backed up first.
+export type ImportStrategy = 'theirs' | 'mine' | 'merge';
+
+export interface MetadataExport {
+  path: string;
+  jobs: number;
+  projects: number;
+}
+
+export interface JobImportChange {
+  job_id: string;
+  added: boolean;
+  tags_added: string[];
+  tags_removed: string[];
+  note_changed: boolean;
+  // Project the job was put in
+  project: string | null;
+  conflicts: string[];
+}
+
+export interface MetadataImportReport {
+  strategy: ImportStrategy;
+  bundle_username: string | null;
+  bundle_exported_at: string;
+  backup_dir: string;
+  // Only the jobs that changed
+  jobs: JobImportChange[];
+  projects_created: string[];
+  unchanged_jobs: number;
+}
+
+export async function exportLocalMetadata(path: string): Promise<MetadataExport> {
+  return await invoke<MetadataExport>('export_local_metadata', { path });
+}
+
+// Refuses a bundle from another account unless `force` is set
+export async function importLocalMetadata(
+  path: string,
+  strategy: ImportStrategy,
+  force = false
+): Promise<MetadataImportReport> {
+  return await invoke<MetadataImportReport>('import_local_metadata', { path, strategy, force });
+}
+
+// Payload of the `jobs-removed-on-server` event, sent once per job
+export interface JobsRemovedOnServer {
+  job_ids: string[];
+  detected_at: string;
+}
+
+export async function adoptJob(
+  jobUrl: string,
+  projectId?: string,
+  tags?: string[],
+  note?: string
+): Promise<LocalJob | null> {
+  return await invoke<LocalJob | null>('adopt_job', { jobUrl, projectId, tags, note });
+}
+
+// Returns how many jobs were adopted
+export async function adoptUntrackedJobs(): Promise<number> {
+  return await invoke<number>('adopt_untracked_jobs');
+}
+
+// serverDeleted: true for only the jobs deleted on the server, false for
+// only the others; all when omitted
+export async function listLocalJobs(serverDeleted?: boolean): Promise<LocalJob[]> {
+  return await invoke<LocalJob[]>('list_local_jobs', { serverDeleted });
+}
+
+// Forgets jobs deleted on the server at least `olderThanDays` ago; their
+// download history stays. Returns how many were removed.
+export async function purgeServerDeletedMetadata(olderThanDays: number): Promise<number> {
+  return await invoke<number>('purge_server_deleted_metadata', { olderThanDays });
+}
+
+export async function clearJobAnnotations(jobId: string): Promise<LocalDelete> {
+  return await invoke<LocalDelete>('clear_job_annotations', { jobId });
+}
+
+// Submissions waiting for connectivity; the outcome of each arrives as a
+// `queued-submission-sent` or `queued-submission-failed` event
+export interface PendingSubmission {
+  id: string;
+  inputs: InputBinding[];
+  // Set instead of inputs on entries queued by older versions
+  file_path?: string;
+  tool: string;
+  params: Record<string, string>;
+  queued_at: string;
+  last_error: string | null;
+}
+
+export async function listPendingSubmissions(): Promise<PendingSubmission[]> {
+  return await invoke<PendingSubmission[]>('list_pending_submissions');
+}
+
+export async function cancelPendingSubmission(id: string): Promise<void> {
+  return await invoke<void>('cancel_pending_submission', { id });
+}
+
+// Submissions NSG accepted without a readable answer. Resubmitting the same
+// tool, fields and inputs is refused until each is confirmed, arriving as a
+// `submission-confirmed` event, or dismissed.
+export interface UnconfirmedSubmission {
+  id: string;
+  tool: string;
+  params: Record<string, string>;
+  inputs: InputBinding[];
+  project_id: string | null;
+  submitted_at: string;
+  // As NSG sent it; empty in showcase mode
+  raw_response: string;
+  error: string;
+  job_id: string | null;
+  job_url: string | null;
+  attempts: number;
+  last_error: string | null;
+}
+
+// Payload of `submission-confirmed`
+export interface SubmissionConfirmed {
+  id: string;
+  job_id: string;
+  job_url: string;
+  tool: string;
+}
+
+export async function listUnconfirmedSubmissions(): Promise<UnconfirmedSubmission[]> {
+  return await invoke<UnconfirmedSubmission[]>('list_unconfirmed_submissions');
+}
+
+export async function dismissUnconfirmedSubmission(id: string): Promise<void> {
+  return await invoke<void>('dismiss_unconfirmed_submission', { id });
+}
+
+export interface ResultFile {
+  filename: string;
+  size: number;
+}
+
+// A result file the download patterns left out; pattern is null when it
+// matched no include pattern
+export interface ExcludedFile {
+  filename: string;
+  size: number;
+  pattern: string | null;
+}
+
+export type DownloadResponse =
+  | {
+      status: 'completed';
+      path: string;
+      purged: boolean;
+      excluded: ExcludedFile[];
+      // Entry names in the archive; relative paths with preserveTree
+      files: string[];
+    }
+  | {
+      status: 'confirmation_required';
+      total_bytes: number;
+      threshold_bytes: number;
+      threshold_source: ThresholdSource;
+      threshold_reason: string;
+      files: ResultFile[];
+    };
+
+export type ThresholdSource = 'fixed' | 'adaptive';
+
+export interface SizeStats {
+  tool: string;
+  samples: number;
+  median_bytes: number | null;
+  p90_bytes: number | null;
+}
+
+// Without an outputDir the remembered folders and download rules pick one;
+// the folder used, and how, is reported in the `download-complete` event.
+// An explicit outputDir is remembered for the job and its project.
+export async function downloadResults(
+  jobUrl: string,
+  outputDir: string | null,
+  confirmed = false,
+  deleteAfterDownload?: boolean,
+  // Keep the results' directory tree in the archive instead of flattening
+  preserveTree?: boolean,
+  // Filenames from listResultFiles to fetch instead of applying the download
+  // patterns; the rest are reported as excluded and never deleted
+  files?: string[]
+): Promise<DownloadResponse> {
+  return await invoke<DownloadResponse>('download_results', {
+    jobUrl,
+    outputDir,
+    confirmed,
+    deleteAfterDownload,
+    preserveTree,
+    files,
+  });
+}
+
+// Every result file of a job, for picking which ones downloadResults fetches
+export interface ResultFileEntry {
+  filename: string;
+  size: number;
+  download_uri: string;
+}
+
+export async function listResultFiles(jobUrl: string): Promise<ResultFileEntry[]> {
+  return await invoke<ResultFileEntry[]>('list_result_files', { jobUrl });
+}
+
+// Downloads the newest file matching a glob from a running job's working
+// directory or from its results, with download-progress events. A null
+// pattern uses the tool's default checkpoint pattern. no_match is not an
+// error: poll again later. Without an outputDir the download folder is used.
+export type ListingSource = 'workingdir' | 'results';
+
+export type LatestMatch =
+  | {
+      status: 'downloaded';
+      job_id: string;
+      path: string;
+      filename: string;
+      size: number;
+      modified: string | null;
+      source: ListingSource;
+      matched: number;
+    }
+  | {
+      status: 'no_match';
+      job_id: string;
+      pattern: string;
+      source: ListingSource;
+      reason: string | null;
+    };
+
+export async function downloadLatestMatching(
+  jobUrl: string,
+  pattern: string | null,
+  source: ListingSource,
+  outputDir: string | null = null
+): Promise<LatestMatch> {
+  return await invoke<LatestMatch>('download_latest_matching', {
+    jobUrl,
+    pattern,
+    source,
+    outputDir,
+  });
+}
+
+// Tool ID -> default checkpoint pattern
+export async function getCheckpointPatterns(): Promise<Record<string, string>> {
+  return await invoke<Record<string, string>>('get_checkpoint_patterns');
+}
+
+// A null or empty pattern removes the tool's default
+export async function setCheckpointPattern(
+  tool: string,
+  pattern: string | null
+): Promise<void> {
+  return await invoke<void>('set_checkpoint_pattern', { tool, pattern });
+}
+
+// Ordered rules for picking a download folder by tool and/or project. Targets
+// may contain {tool} and {project}; the first match wins, falling back to the
+// download folder.
+// Glob patterns applied to every download. A non-empty include list is an
+// allowlist; excludes win over includes.
+export interface DownloadPatterns {
+  include: string[];
+  exclude: string[];
+}
+
+export async function getDownloadPatterns(): Promise<DownloadPatterns> {
+  return await invoke<DownloadPatterns>('get_download_patterns');
+}
+
+// Rejects with the parse error if a pattern is invalid
+export async function setDownloadPatterns(
+  patterns: DownloadPatterns
+): Promise<void> {
+  return await invoke<void>('set_download_patterns', { patterns });
+}
+
+export interface DownloadSelection {
+  included: ResultFile[];
+  excluded: ExcludedFile[];
+  total_bytes: number;
+}
+
+export async function previewDownloadSelection(
+  jobUrl: string
+): Promise<DownloadSelection> {
+  return await invoke<DownloadSelection>('preview_download_selection', {
+    jobUrl,
+  });
+}
+
+// Windowed reading of a large result file with Range requests. Handles
+// close after 10 idle minutes and on disconnect; at most 8 are open.
+export interface StreamInfo {
+  handle: number;
+  filename: string;
+  size: number;
+}
+
+export interface StreamChunk {
+  offset: number;
+  length: number;
+  text: string;
+  eof: boolean;
+}
+
+export async function openResultStream(jobUrl: string, filename: string): Promise<StreamInfo> {
+  return await invoke<StreamInfo>('open_result_stream', { jobUrl, filename });
+}
+
+// length is capped at 4 MB
+export async function readStreamChunk(
+  handle: number,
+  offset: number,
+  length: number
+): Promise<StreamChunk> {
+  return await invoke<StreamChunk>('read_stream_chunk', { handle, offset, length });
+}
+
+// Byte offset of the next match at or after fromOffset, or null
+export async function searchStream(
+  handle: number,
+  pattern: string,
+  fromOffset: number
+): Promise<number | null> {
+  return await invoke<number | null>('search_stream', { handle, pattern, fromOffset });
+}
+
+export async function closeResultStream(handle: number): Promise<boolean> {
+  return await invoke<boolean>('close_result_stream', { handle });
+}
+
+// How result archives are compressed. "auto" stores files that are already
+// compressed (gz, npz, mp4, ...) and deflates the rest. download-complete
+// reports compressed_bytes and uncompressed_bytes for the result entries.
+export type ZipCompression = 'store' | 'fast' | 'default' | 'best' | 'auto';
+
+export async function getZipCompression(): Promise<ZipCompression> {
+  return await invoke<ZipCompression>('get_zip_compression');
+}
+
+export async function setZipCompression(
+  compression: ZipCompression
+): Promise<void> {
+  return await invoke<void>('set_zip_compression', { compression });
+}
+
+export interface DownloadRule {
+  tool: string | null;
+  project_id: string | null;
+  directory: string;
+}
+
+export async function getDownloadRules(): Promise<DownloadRule[]> {
+  return await invoke<DownloadRule[]>('get_download_rules');
+}
+
+export async function setDownloadRules(rules: DownloadRule[]): Promise<void> {
+  return await invoke<void>('set_download_rules', { rules });
+}
+
+export type DirSource = 'explicit' | 'job' | 'project' | 'rule' | 'default';
+
+// One level of the folder lookup; 'missing' means a remembered folder was
+// gone and has been forgotten
+export interface ResolutionStep {
+  source: DirSource;
+  directory: string | null;
+  outcome: 'used' | 'not_set' | 'missing';
+}
+
+export interface ResolvedDir {
+  directory: string;
+  source: DirSource;
+  chain: ResolutionStep[];
+}
+
+export interface DownloadComplete {
+  job_id: string;
+  output_dir: string;
+  output_dir_chain: ResolutionStep[];
+  compressed_bytes: number;
+  uncompressed_bytes: number;
+  // The listing, fetched again before the archive was finalized, had
+  // changed: late_files were fetched then, vanished_files kept as they were
+  listing_changed: boolean;
+  late_files: string[];
+  vanished_files: string[];
+}
+
+// Most specific folder for the download dialog: job > project > rules > default
+export async function getLastOutputDir(jobId: string, tool?: string): Promise<ResolvedDir> {
+  return await invoke<ResolvedDir>('get_last_output_dir', { jobId, tool });
+}
+
+// Download bandwidth limit in kilobits per second (0 = unlimited)
+export async function getDownloadRateLimit(): Promise<number> {
+  return await invoke<number>('get_download_rate_limit');
+}
+
+export async function setDownloadRateLimit(kbps: number): Promise<void> {
+  return await invoke<void>('set_download_rate_limit', { kbps });
+}
+
+export async function getDeleteAfterDownload(): Promise<boolean> {
+  return await invoke<boolean>('get_delete_after_download');
+}
+
+export async function setDeleteAfterDownload(enabled: boolean): Promise<void> {
+  return await invoke<void>('set_delete_after_download', { enabled });
+}
+
+// Seconds a download waits after its job completed, so late output files
+// are there (0 = none, at most 600)
+export async function getSettleDelaySecs(): Promise<number> {
+  return await invoke<number>('get_settle_delay_secs');
+}
+
+export async function setSettleDelaySecs(secs: number): Promise<void> {
+  return await invoke<void>('set_settle_delay_secs', { secs });
+}
+
+// Result files of one job downloaded at the same time (1-8, default 3)
+export async function getDownloadConcurrency(): Promise<number> {
+  return await invoke<number>('get_download_concurrency');
+}
+
+export async function setDownloadConcurrency(files: number): Promise<void> {
+  return await invoke<void>('set_download_concurrency', { files });
+}
+
+export interface DownloadHistoryEntry {
+  id: string;
+  job_id: string;
+  job_url: string;
+  archive_path: string;
+  file_count: number;
+  total_bytes: number;
+  downloaded_at: string;
+  purged_on_server: boolean;
+  tool: string | null;
+  archive_bytes: number | null;
+  // Never removed by archive cleanup
+  pinned: boolean;
+  // The archive was already gone when cleanup looked for it
+  archive_missing: boolean;
+  // When archive cleanup moved the archive to the OS trash
+  archive_trashed_at: string | null;
+}
+
+export async function getDownloadHistory(): Promise<DownloadHistoryEntry[]> {
+  return await invoke<DownloadHistoryEntry[]>('get_download_history');
+}
+
+// The archives themselves are left alone
+export async function removeDownloadHistoryEntries(ids: string[]): Promise<LocalDelete> {
+  return await invoke<LocalDelete>('remove_download_history_entries', { ids });
+}
+
+// Pinned archives are never cleaned up; pass false to unpin
+export async function pinHistoryEntry(id: string, pinned = true): Promise<void> {
+  await invoke('pin_history_entry', { id, pinned });
+}
+
+// Automatic cleanup of old downloaded archives. Only archives in the
+// download history are touched, and they go to the OS trash. A null limit
+// is no limit.
+export interface ArchiveCleanupSettings {
+  enabled: boolean;
+  keep_days: number | null;
+  max_gb: number | null;
+}
+
+export interface CleanupCandidate {
+  path: string;
+  entry_ids: string[];
+  job_ids: string[];
+  downloaded_at: string;
+  bytes: number;
+  reason: 'age' | 'over_budget';
+  trashed: boolean;
+  // Why it couldn't be moved to the trash
+  error: string | null;
 }
 
-export async function loadCredentials(): Promise<Credentials | null> {
-  return await invoke<Credentials | null>('load_credentials');
+// Also emitted as `archive-cleanup-report` after every run
+export interface ArchiveCleanupReport {
+  dry_run: boolean;
+  // False for the periodic run
+  manual: boolean;
+  ran_at: string;
+  settings: ArchiveCleanupSettings;
+  archives: number;
+  total_bytes: number;
+  candidates: CleanupCandidate[];
+  freed_bytes: number;
+  // Archives already gone, now marked missing
+  marked_missing: string[];
+  pinned_exempt: number;
 }
 
-export async function connect(
-  username: string,
-  password: string,
-  appKey: string
-): Promise<string> {
-  return await invoke<string>('connect', {
-    username,
-    password,
-    appKey,
-  });
+export async function getArchiveCleanupSettings(): Promise<ArchiveCleanupSettings> {
+  return await invoke<ArchiveCleanupSettings>('get_archive_cleanup_settings');
 }
 
-export async function listJobs(): Promise<JobSummary[]> {
-  return await invoke<JobSummary[]>('list_jobs');
+export async function setArchiveCleanupSettings(settings: ArchiveCleanupSettings): Promise<void> {
+  await invoke('set_archive_cleanup_settings', { settings });
 }
 
-export async function getJobStatus(jobUrl: string): Promise<JobDetails> {
-  return await invoke<JobDetails>('get_job_status', { jobUrl });
+// Runs even when the periodic cleanup is off; a dry run only reports
+export async function runArchiveCleanup(dryRun: boolean): Promise<ArchiveCleanupReport> {
+  return await invoke<ArchiveCleanupReport>('run_archive_cleanup', { dryRun });
 }
 
-export async function submitJob(
-  filePath: string,
-  tool: string
-): Promise<string> {
-  return await invoke<string>('submit_job', { filePath, tool });
+// Checks a results archive against its checksums.sha256 entry
+export interface ArchiveVerification {
+  matched: string[];
+  mismatched: string[];
+  missing: string[];
+  unlisted: string[];
+  ok: boolean;
 }
 
-export async function downloadResults(
+export async function verifyArchive(path: string): Promise<ArchiveVerification> {
+  return await invoke<ArchiveVerification>('verify_archive', { path });
+}
+
+export type FileVerdict =
+  | 'match'
+  | 'size_mismatch'
+  | 'hash_mismatch'
+  | 'only_on_server'
+  | 'excluded'
+  | 'only_in_archive';
+
+export interface FileCheck {
+  name: string;
+  verdict: FileVerdict;
+  reference_size: number | null;
+  archive_size: number | null;
+}
+
+// reference is 'manifest' when the server had purged the job and the
+// archive's job_info.json was used instead; fallback_reason says why
+export interface ServerVerification {
+  reference: 'server' | 'manifest';
+  fallback_reason: string | null;
+  hashes_checked: boolean;
+  files: FileCheck[];
+  status: 'verified' | 'incomplete' | 'mismatched';
+}
+
+export async function verifyAgainstServer(
   jobUrl: string,
+  archivePath: string
+): Promise<ServerVerification> {
+  return await invoke<ServerVerification>('verify_against_server', { jobUrl, archivePath });
+}
+
+export interface ArchiveEntry {
+  name: string;
+  compressed_size: number;
+  size: number;
+  is_dir: boolean;
+}
+
+// Contents of job_info.json inside a results archive
+export interface JobInfoManifest {
+  job_id: string;
+  job_url: string;
+  tool: string | null;
+  job_stage: string;
+  failed: boolean;
+  date_submitted: string | null;
+  date_completed: string | null;
+  compute_resource: string | null;
+  tool_version: string | null;
+  metadata: Record<string, string>;
+  downloaded_at: string;
+  files: { filename: string; size: number }[];
+  checksums: Record<string, string>;
+}
+
+export interface ArchiveContents {
+  entries: ArchiveEntry[];
+  has_job_info: boolean;
+  job_info: JobInfoManifest | null;
+}
+
+export async function listArchiveContents(path: string): Promise<ArchiveContents> {
+  return await invoke<ArchiveContents>('list_archive_contents', { path });
+}
+
+// Extracts a single entry and returns the path it was written to
+export async function extractArchiveEntry(
+  path: string,
+  entry: string,
   outputDir: string
 ): Promise<string> {
-  return await invoke<string>('download_results', {
-    jobUrl,
-    outputDir,
-  });
+  return await invoke<string>('extract_archive_entry', { path, entry, outputDir });
+}
+
+// Largest file submit_job accepts; checked before uploading or queueing
+export async function getMaxSubmitBytes(): Promise<number> {
+  return await invoke<number>('get_max_submit_bytes');
+}
+
+export async function setMaxSubmitBytes(bytes: number): Promise<void> {
+  return await invoke<void>('set_max_submit_bytes', { bytes });
+}
+
+// Written as `<input>.nsg-receipt.json` beside each input when enabled
+export interface SubmissionReceipt {
+  job_id: string;
+  job_url: string;
+  tool: string;
+  params: Record<string, string>;
+  metadata: Record<string, string>;
+  submitted_at: string;
+  param_name: string;
+  input_file: string;
+  input_sha256: string;
+}
+
+export async function getWriteSubmissionReceipt(): Promise<boolean> {
+  return await invoke<boolean>('get_write_submission_receipt');
+}
+
+export async function setWriteSubmissionReceipt(enabled: boolean): Promise<void> {
+  return await invoke<void>('set_write_submission_receipt', { enabled });
+}
+
+// path may be the input file or its receipt; null when there is no receipt
+export async function readSubmissionReceipt(
+  path: string
+): Promise<SubmissionReceipt | null> {
+  return await invoke<SubmissionReceipt | null>('read_submission_receipt', { path });
+}
+
+export async function getMaxDownloadWarnBytes(): Promise<number> {
+  return await invoke<number>('get_max_download_warn_bytes');
+}
+
+export async function setMaxDownloadWarnBytes(bytes: number): Promise<void> {
+  return await invoke<void>('set_max_download_warn_bytes', { bytes });
+}
+
+// Sizes of this tool's earlier downloads, from the download history
+export async function getDownloadSizeStats(tool: string): Promise<SizeStats> {
+  return await invoke<SizeStats>('get_download_size_stats', { tool });
+}
+
+export async function getAdaptiveDownloadWarning(): Promise<boolean> {
+  return await invoke<boolean>('get_adaptive_download_warning');
+}
+
+export async function setAdaptiveDownloadWarning(enabled: boolean): Promise<void> {
+  return await invoke<void>('set_adaptive_download_warning', { enabled });
+}
+
+// null means the system temp directory
+export async function getStagingDir(): Promise<string | null> {
+  return await invoke<string | null>('get_staging_dir');
+}
+
+// Must be an existing, writable folder; null goes back to the system temp
+// directory. Running downloads keep the folder they started with.
+export async function setStagingDir(dir: string | null): Promise<void> {
+  return await invoke<void>('set_staging_dir', { dir });
 }
 
 export async function getDownloadDir(): Promise<string> {
   return await invoke<string>('get_download_dir');
 }
 
+// File dialogs. Commands only accept paths under the home, download or
+// staging folders, or ones picked here during this session; anything else
+// fails with a "Path not allowed" error. The dialog plugin's own open() and
+// save() don't register what they return, so use this instead.
+export type PickKind = 'file' | 'folder' | 'save';
+
+export interface PickOptions {
+  title?: string;
+  default_path?: string;
+  file_name?: string;
+  filters?: { name: string; extensions: string[] }[];
+}
+
+// null when the dialog was cancelled
+export async function pickPath(kind: PickKind, options?: PickOptions): Promise<string | null> {
+  return await invoke<string | null>('pick_path', { kind, options });
+}
+
 export async function setDownloadDir(dir: string): Promise<void> {
   return await invoke<void>('set_download_dir', { dir });
 }
@@ -101,20 +1878,142 @@ export async function resetZoom(): Promise<number> {
   return await invoke<number>('reset_zoom');
 }
 
+// Preference setters save in the background after a short debounce; this
+// writes anything pending right away, e.g. before relaunching
+export async function flushPreferences(): Promise<void> {
+  return await invoke<void>('flush_preferences');
+}
+
+// Values of the wrong type found in preferences.json at startup, also
+// emitted as `preferences-repaired`. repaired is null when the key was reset
+// to its default.
+export interface PreferenceRepair {
+  key: string;
+  found: unknown;
+  repaired: unknown | null;
+}
+
+// State files that didn't parse at startup are renamed to
+// `<name>.corrupt-<timestamp>` and reported once as `state-files-repaired`
+export interface QuarantinedFile {
+  name: string;
+  quarantined_as: string;
+  error: string;
+}
+
+export interface StateFileStatus {
+  name: string;
+  size: number | null;
+  modified: string | null;
+  status: 'ok' | 'missing' | 'corrupt';
+  error: string | null;
+  // Schema version on disk; 0 before versioning
+  version: number | null;
+  // Written by a newer release and left untouched
+  read_only: boolean;
+}
+
+// Payload of `state-version-mismatch`, sent once per state file written by
+// a newer version of the app; the file is read but not written
+export interface VersionMismatch {
+  file: string;
+  version: number;
+  supported: number;
+}
+
+export interface StateFileReport {
+  files: StateFileStatus[];
+  quarantined: QuarantinedFile[];
+  quarantined_copies: StateFileStatus[];
+}
+
+export async function getStateFileReport(): Promise<StateFileReport> {
+  return await invoke<StateFileReport>('get_state_file_report');
+}
+
+export async function getPreferenceRepairs(): Promise<PreferenceRepair[]> {
+  return await invoke<PreferenceRepair[]>('get_preference_repairs');
+}
+
 // Theme functions
+export type Theme = 'light' | 'dark' | 'system' | 'high-contrast';
+
 export async function getTheme(): Promise<string> {
   return await invoke<string>('get_theme');
 }
 
+// Anything but a Theme is rejected
 export async function setTheme(theme: string): Promise<void> {
   return await invoke<void>('set_theme', { theme });
 }
 
+// Locale of backend-generated messages ("en" or "es")
+export async function getLocale(): Promise<string> {
+  return await invoke<string>('get_locale');
+}
+
+export async function setLocale(locale: string): Promise<string> {
+  return await invoke<string>('set_locale', { locale });
+}
+
 // Showcase mode
 export async function getShowcaseMode(): Promise<boolean> {
   return await invoke<boolean>('get_showcase_mode');
 }
 
+// Read-only mode: mutating commands reject with an error starting "ReadOnly"
+export interface AppMode {
+  read_only: boolean;
+  // account_mismatch: connected as someone other than the owner of the
+  // metadata on screen, until resolveAccountMismatch
+  read_only_source: 'environment' | 'preference' | 'account_mismatch' | null;
+  showcase: boolean;
+}
+
+export async function getAppMode(): Promise<AppMode> {
+  return await invoke<AppMode>('get_app_mode');
+}
+
+// Per-account state. `account-mismatch` (an AccountMismatch) is emitted on
+// connecting as someone other than the active namespace's owner;
+// `account-namespace-changed` after switching, when every list should be
+// reloaded.
+export type MismatchAction = 'switch' | 'stay_read_only';
+
+export interface AccountMismatch {
+  current_user: string;
+  connecting_user: string;
+  namespace_exists: boolean;
+  choice: MismatchAction | null;
+}
+
+export interface AccountStatus {
+  active_user: string | null;
+  mismatch: AccountMismatch | null;
+}
+
+export async function getAccountStatus(): Promise<AccountStatus> {
+  return await invoke<AccountStatus>('get_account_status');
+}
+
+export async function resolveAccountMismatch(action: MismatchAction): Promise<AccountStatus> {
+  return await invoke<AccountStatus>('resolve_account_mismatch', { action });
+}
+
+// Turning read-only off requires the account password when one is stored
+export async function setReadOnly(
+  enabled: boolean,
+  password?: string
+): Promise<AppMode> {
+  return await invoke<AppMode>('set_read_only', { enabled, password });
+}
+
+// Writes the fake -> real ID mapping of this showcase session; returns the
+// number of job IDs exported
+export async function exportAnonymizationMap(path: string): Promise<number> {
+  return await invoke<number>('export_anonymization_map', { path });
+}
+
 // Auto-refresh functions
 export async function getAutoRefresh(): Promise<boolean> {
   return await invoke<boolean>('get_auto_refresh');
@@ -132,6 +2031,283 @@ export async function setAutoRefreshInterval(interval: number): Promise<void> {
   return await invoke<void>('set_auto_refresh_interval', { interval });
 }
 
+// OS accessibility settings; null where the platform doesn't tell. Changes
+// while the app runs arrive as accessibility-hints-changed.
+export interface AccessibilityHints {
+  high_contrast: boolean | null;
+  reduce_motion: boolean | null;
+}
+
+export async function getAccessibilityHints(): Promise<AccessibilityHints> {
+  return await invoke<AccessibilityHints>('get_accessibility_hints');
+}
+
+// About dialog
+export interface LicenseText {
+  file: string;
+  text: string;
+}
+
+export interface LicensedPackage {
+  name: string;
+  version: string;
+  license: string | null;
+  repository: string | null;
+  license_texts: LicenseText[];
+}
+
+export interface LicenseReport {
+  target: string;
+  packages: LicensedPackage[];
+}
+
+export interface AppInfo {
+  name: string;
+  version: string;
+  identifier: string;
+  authors: string[];
+  tauri_version: string;
+  build_target: string;
+  dependency_count: number;
+  license_summary: Record<string, number>;
+  data_mode: DataMode;
+  // Where preferences and state files are kept
+  data_root: string | null;
+  // Every open window, as it is now
+  windows: WindowGeometry[];
+}
+
+// Physical pixels
+export interface Rect {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export interface WindowGeometry {
+  label: string;
+  outer: Rect;
+  scale_factor: number;
+  maximized: boolean;
+  fullscreen: boolean;
+  monitor: string | null;
+  work_area: Rect | null;
+  // What the startup check changed after the saved geometry was restored
+  corrections: string[];
+}
+
+// 'portable' keeps all data in data/ next to the executable
+export type DataMode = 'installed' | 'portable';
+
+// Sent as `data-location-conflict` at startup when the location not in use
+// also holds app data
+export interface DataLocationConflict {
+  mode: DataMode;
+  active_root: string;
+  other_root: string;
+  // copy_other is refused when this is true
+  active_has_data: boolean;
+}
+
+export async function getDataLocationConflict(): Promise<DataLocationConflict | null> {
+  return await invoke<DataLocationConflict | null>('get_data_location_conflict');
+}
+
+// keep_active stops asking about the other location; copy_other copies its
+// data into the empty active location and restarts the app
+export async function resolveDataLocationConflict(
+  choice: 'keep_active' | 'copy_other'
+): Promise<void> {
+  await invoke('resolve_data_location_conflict', { choice });
+}
+
+export async function getAppInfo(): Promise<AppInfo> {
+  return await invoke<AppInfo>('get_app_info');
+}
+
+// Recenter the main window on the primary monitor at its default size, for
+// a window restored off screen
+export async function resetWindowGeometry(): Promise<WindowGeometry> {
+  return await invoke<WindowGeometry>('reset_window_geometry');
+}
+
+export async function getLicenseReport(): Promise<LicenseReport> {
+  return await invoke<LicenseReport>('get_license_report');
+}
+
+// Files dropped on the window, checked before the submit dialog opens
+export type DroppedKind =
+  | 'zip_archive'
+  | 'python_script'
+  | 'hoc_file'
+  | 'folder'
+  | 'unknown';
+
+export interface DroppedPath {
+  path: string;
+  exists: boolean;
+  is_file: boolean;
+  is_dir: boolean;
+  size: number | null;
+  // Only part of a large folder or archive was looked through
+  size_estimated: boolean;
+  kind: DroppedKind;
+  // Most likely first
+  tools: string[];
+  warnings: string[];
+  error: string | null;
+}
+
+export async function inspectDroppedPaths(
+  paths: string[]
+): Promise<DroppedPath[]> {
+  return await invoke<DroppedPath[]>('inspect_dropped_paths', { paths });
+}
+
+// Activity feed for the notification center. Items are sent as `activity`
+// when added and again, same id, each time they change.
+export type ActivityKind =
+  | 'download'
+  | 'submission'
+  | 'queued_submission'
+  | 'job_status'
+  | 'background_error'
+  | 'announcement';
+
+export type ActivityStatus =
+  | 'running'
+  | 'succeeded'
+  | 'failed'
+  | 'attention'
+  | 'info'
+  | 'interrupted';
+
+export interface ActivityItem {
+  id: string;
+  kind: ActivityKind;
+  status: ActivityStatus;
+  timestamp: string;
+  updated_at: string;
+  summary: string;
+  detail: string | null;
+  job_id: string | null;
+  // 0 to 1 while running, when known
+  progress: number | null;
+}
+
+// Newest first; limit defaults to 100
+export async function getActivityFeed(
+  limit?: number,
+  kinds?: ActivityKind[]
+): Promise<ActivityItem[]> {
+  return await invoke<ActivityItem[]>('get_activity_feed', { limit, kinds });
+}
+
+// Background job monitor; stage changes arrive as `job-status-changed`
+export interface MonitorSettings {
+  poll_interval_secs: number;
+  adaptive: boolean;
+}
+
+export interface JobStatusChange {
+  job_id: string;
+  url: string;
+  previous_stage: string | null;
+  job_stage: string | null;
+  failed: boolean;
+  scheduler_info: SchedulerInfo | null;
+  queue_note: string | null;
+}
+
+export async function getMonitorSettings(): Promise<MonitorSettings> {
+  return await invoke<MonitorSettings>('get_monitor_settings');
+}
+
+export async function setMonitorSettings(
+  settings: MonitorSettings
+): Promise<void> {
+  return await invoke<void>('set_monitor_settings', { settings });
+}
+
+// Download progress events, re-sent for running downloads by frontendReady
+export interface DownloadProgress {
+  job_id: string;
+  filename: string;
+  downloaded: number;
+  total: number;
+  speed: number;
+}
+
+export async function frontendReady(): Promise<number> {
+  return await invoke<number>('frontend_ready');
+}
+
+// Downloads left over from the previous run (`pending-downloads-found`)
+export interface PendingDownload {
+  id: string;
+  job_url: string;
+  output_dir: string | null;
+  delete_after_download: boolean;
+  status: 'downloading' | 'failed';
+  error: string | null;
+  added_at: string;
+  output_dir_missing: boolean;
+}
+
+export async function resumePendingDownloads(): Promise<number> {
+  return await invoke<number>('resume_pending_downloads');
+}
+
+export async function discardPendingDownloads(): Promise<number> {
+  return await invoke<number>('discard_pending_downloads');
+}
+
+// The last run crashed (`previous-session-crashed`, sent after
+// frontendReady). log_path is null when the crash log couldn't be written.
+export interface CrashMarker {
+  crashed_at: string;
+  message: string;
+  location: string | null;
+  thread: string | null;
+  log_path: string | null;
+}
+
+export async function getPreviousCrash(): Promise<CrashMarker | null> {
+  return await invoke<CrashMarker | null>('get_previous_crash');
+}
+
+export async function dismissPreviousCrash(): Promise<void> {
+  return await invoke<void>('dismiss_previous_crash');
+}
+
+// Shows the crash log in the system file manager
+export async function openCrashLog(): Promise<void> {
+  return await invoke<void>('open_crash_log');
+}
+
+// Debug builds only. 'app' ends the app through a main-thread panic;
+// 'download' panics inside a guarded download task and rejects.
+export async function triggerTestPanic(kind: 'app' | 'download' = 'app'): Promise<void> {
+  return await invoke<void>('trigger_test_panic', { kind });
+}
+
+// Job windows; the page reads its job from `window.__NSG_JOB_CONTEXT__`
+export interface JobWindowContext {
+  job_url: string;
+  zoom: number;
+  theme: string;
+}
+
+export function getJobWindowContext(): JobWindowContext | null {
+  if (typeof window === 'undefined') return null;
+  return (window as any).__NSG_JOB_CONTEXT__ ?? null;
+}
+
+export async function openJobWindow(jobUrl: string): Promise<string> {
+  return await invoke<string>('open_job_window', { jobUrl });
+}
+
 // Updater functions
 export interface UpdateInfo {
   version: string;
@@ -181,10 +2357,37 @@ export async function installUpdate(
       }
     });
 
-    // Restart the app after update
+    // Restart the app after update; relaunching skips the exit handler
+    await flushPreferences();
     const { relaunch } = await import('@tauri-apps/plugin-process');
     await relaunch();
   } catch (error) {
     throw new Error(`Failed to install update: ${error}`);
   }
 }
+
+// Generated fake accounts, the same for the same seed. Only in builds with
+// the `fixtures` feature; the exported JSON has this shape.
+export interface FixtureAccount {
+  seed: number;
+  username: string;
+  jobs: FixtureJob[];
+}
+
+export interface FixtureJob {
+  job_id: string;
+  url: string;
+  tool: string;
+  job_stage: string;
+  failed: boolean;
+  failure: 'rejected_at_submission' | 'crashed_while_running' | 'completed_with_error' | null;
+  date_submitted: string;
+  date_completed: string | null;
+  messages: { timestamp: string; stage: string; text: string }[];
+  results: { filename: string; size: number }[];
+}
+
+// Writes the account for `seed` to `path`; returns the number of jobs
+export async function exportFixtureAccount(seed: number, path: string): Promise<number> {
+  return await invoke<number>('export_fixture_account', { seed, path });
+}
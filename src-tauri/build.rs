@@ -1,3 +1,189 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
 fn main() {
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    write_license_manifest();
+
     tauri_build::build()
 }
+
+/// Collect the license metadata and license texts of every crate linked into
+/// the app and embed them (gzip-compressed) for the About dialog.
+///
+/// The build fails if any dependency declares neither `license` nor
+/// `license-file`, so we never ship an incomplete report.
+fn write_license_manifest() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let target = std::env::var("TARGET").unwrap();
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+
+    let output = Command::new(cargo)
+        .arg("metadata")
+        .arg("--format-version=1")
+        .arg("--locked")
+        .arg(format!("--filter-platform={}", target))
+        .arg("--manifest-path")
+        .arg(manifest_dir.join("Cargo.toml"))
+        .output()
+        .expect("Failed to run cargo metadata");
+
+    if !output.status.success() {
+        panic!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let metadata: Value =
+        serde_json::from_slice(&output.stdout).expect("Failed to parse cargo metadata");
+
+    let packages: HashMap<&str, &Value> = metadata["packages"]
+        .as_array()
+        .expect("cargo metadata has no packages")
+        .iter()
+        .map(|p| (p["id"].as_str().unwrap(), p))
+        .collect();
+
+    let nodes: HashMap<&str, &Value> = metadata["resolve"]["nodes"]
+        .as_array()
+        .expect("cargo metadata has no resolve graph")
+        .iter()
+        .map(|n| (n["id"].as_str().unwrap(), n))
+        .collect();
+
+    let root = metadata["resolve"]["root"]
+        .as_str()
+        .expect("cargo metadata has no root package");
+
+    // Walk only normal (non-build, non-dev) dependencies: those are the ones
+    // that end up in the shipped binary.
+    let mut linked = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(id) = stack.pop() {
+        for dep in nodes[id]["deps"].as_array().into_iter().flatten() {
+            let is_normal = dep["dep_kinds"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|k| k["kind"].is_null());
+            let dep_id = dep["pkg"].as_str().unwrap();
+            if is_normal && linked.insert(dep_id) {
+                stack.push(dep_id);
+            }
+        }
+    }
+
+    let mut texts: Vec<String> = Vec::new();
+    let mut text_ids: HashMap<String, usize> = HashMap::new();
+    let mut entries = BTreeMap::new();
+    let mut unknown = Vec::new();
+
+    for id in linked {
+        let package = packages[id];
+        let name = package["name"].as_str().unwrap();
+        let version = package["version"].as_str().unwrap();
+        let license = package["license"].as_str();
+        let license_file = package["license_file"].as_str();
+
+        if license.is_none() && license_file.is_none() {
+            unknown.push(format!("{} {}", name, version));
+            continue;
+        }
+
+        let package_dir = Path::new(package["manifest_path"].as_str().unwrap())
+            .parent()
+            .unwrap()
+            .to_path_buf();
+
+        let mut files = Vec::new();
+        for path in license_files(&package_dir, license_file) {
+            let Ok(text) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let next_id = texts.len();
+            let text_id = *text_ids.entry(text.clone()).or_insert_with(|| {
+                texts.push(text);
+                next_id
+            });
+            files.push(json!({
+                "name": path.file_name().unwrap().to_string_lossy(),
+                "text": text_id,
+            }));
+        }
+
+        entries.insert(
+            (name.to_string(), version.to_string()),
+            json!({
+                "name": name,
+                "version": version,
+                "license": license,
+                "repository": package["repository"],
+                "license_files": files,
+            }),
+        );
+    }
+
+    if !unknown.is_empty() {
+        unknown.sort();
+        panic!(
+            "The following dependencies have no license metadata; refusing to build an incomplete license report:\n  {}",
+            unknown.join("\n  ")
+        );
+    }
+
+    let manifest = json!({
+        "target": target,
+        "packages": entries.into_values().collect::<Vec<_>>(),
+        "texts": texts,
+    });
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(manifest.to_string().as_bytes())
+        .expect("Failed to compress license manifest");
+    let compressed = encoder.finish().expect("Failed to compress license manifest");
+
+    std::fs::write(out_dir.join("licenses.json.gz"), compressed)
+        .expect("Failed to write license manifest");
+}
+
+/// License texts shipped alongside a crate: the declared `license-file` plus
+/// any LICENSE*/LICENCE*/COPYING*/NOTICE* files in the package root.
+fn license_files(package_dir: &Path, license_file: Option<&str>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    if let Some(file) = license_file {
+        files.push(package_dir.join(file));
+    }
+
+    if let Ok(dir) = std::fs::read_dir(package_dir) {
+        let mut found: Vec<PathBuf> = dir
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                let name = p
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_uppercase())
+                    .unwrap_or_default();
+                ["LICENSE", "LICENCE", "COPYING", "NOTICE", "UNLICENSE"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+            })
+            .filter(|p| !files.contains(p))
+            .collect();
+        found.sort();
+        files.extend(found);
+    }
+
+    files
+}
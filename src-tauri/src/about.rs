@@ -0,0 +1,148 @@
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use tauri::Manager;
+
+// Generated by build.rs from `cargo metadata`
+static LICENSE_MANIFEST_GZ: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/licenses.json.gz"));
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LicenseManifest {
+    target: String,
+    packages: Vec<ManifestPackage>,
+    texts: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestPackage {
+    name: String,
+    version: String,
+    license: Option<String>,
+    repository: Option<String>,
+    license_files: Vec<ManifestLicenseFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestLicenseFile {
+    name: String,
+    text: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LicenseReport {
+    target: String,
+    packages: Vec<LicensedPackage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LicensedPackage {
+    name: String,
+    version: String,
+    license: Option<String>,
+    repository: Option<String>,
+    license_texts: Vec<LicenseText>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LicenseText {
+    file: String,
+    text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppInfo {
+    name: String,
+    version: String,
+    identifier: String,
+    authors: Vec<String>,
+    tauri_version: String,
+    build_target: String,
+    dependency_count: usize,
+    // License expression -> number of dependencies using it
+    license_summary: BTreeMap<String, usize>,
+    data_mode: crate::portable::DataMode,
+    // Where preferences and state files are kept
+    data_root: Option<String>,
+    // Every open window, as it is now
+    windows: Vec<crate::window_geometry::WindowGeometry>,
+}
+
+fn load_manifest() -> Result<LicenseManifest, String> {
+    let mut json = String::new();
+    GzDecoder::new(LICENSE_MANIFEST_GZ)
+        .read_to_string(&mut json)
+        .map_err(|e| format!("Failed to decompress license manifest: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse license manifest: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_license_report() -> Result<LicenseReport, String> {
+    let manifest = load_manifest()?;
+    let texts = manifest.texts;
+
+    let packages = manifest
+        .packages
+        .into_iter()
+        .map(|p| LicensedPackage {
+            name: p.name,
+            version: p.version,
+            license: p.license,
+            repository: p.repository,
+            license_texts: p
+                .license_files
+                .into_iter()
+                .filter_map(|f| {
+                    texts.get(f.text).map(|text| LicenseText {
+                        file: f.name,
+                        text: text.clone(),
+                    })
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(LicenseReport {
+        target: manifest.target,
+        packages,
+    })
+}
+
+#[tauri::command]
+pub async fn get_app_info(app: tauri::AppHandle) -> Result<AppInfo, String> {
+    let manifest = load_manifest()?;
+
+    let mut license_summary = BTreeMap::new();
+    for package in &manifest.packages {
+        let license = package
+            .license
+            .clone()
+            .unwrap_or_else(|| "See license file".to_string());
+        *license_summary.entry(license).or_insert(0) += 1;
+    }
+
+    let package_info = app.package_info();
+
+    Ok(AppInfo {
+        name: package_info.name.clone(),
+        version: package_info.version.to_string(),
+        identifier: app.config().identifier.clone(),
+        authors: env!("CARGO_PKG_AUTHORS")
+            .split(':')
+            .map(|s| s.to_string())
+            .collect(),
+        tauri_version: tauri::VERSION.to_string(),
+        build_target: manifest.target,
+        dependency_count: manifest.packages.len(),
+        license_summary,
+        data_mode: crate::portable::mode(),
+        data_root: crate::portable::app_data_root(&app)
+            .ok()
+            .map(|root| root.display().to_string()),
+        windows: app
+            .webview_windows()
+            .values()
+            .filter_map(crate::window_geometry::geometry)
+            .collect(),
+    })
+}
@@ -0,0 +1,133 @@
+// OS accessibility settings.
+//
+// The frontend can follow light and dark through CSS, but not the OS's
+// high-contrast and reduce-motion switches everywhere, so the backend reads
+// them: the HighContrast flag and MinAnimate in the Windows registry, the
+// universal access defaults on macOS, and the GNOME a11y and interface
+// settings on Linux. A setting that can't be read (no gsettings, a key the
+// OS version doesn't have) is None rather than a guess. The settings are
+// polled every POLL_INTERVAL and `accessibility-hints-changed` is emitted
+// when one changes while the app runs.
+
+use serde::Serialize;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::Emitter;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Payload of `accessibility-hints-changed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AccessibilityHints {
+    // Windows high contrast, macOS increase contrast, GNOME high contrast
+    pub high_contrast: Option<bool>,
+    pub reduce_motion: Option<bool>,
+}
+
+static LAST: Mutex<Option<AccessibilityHints>> = Mutex::new(None);
+
+/// Trimmed standard output of a command that succeeded
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let mut command = Command::new(program);
+    command.args(args);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW, so polling doesn't flash a console
+        command.creation_flags(0x0800_0000);
+    }
+    let out = command.output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn registry_value(key: &str, name: &str) -> Option<String> {
+    // "    Flags    REG_SZ    126"
+    output("reg", &["query", key, "/v", name])?
+        .lines()
+        .find(|line| line.trim_start().starts_with(name))
+        .and_then(|line| line.split_whitespace().nth(2))
+        .map(|v| v.to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn read() -> AccessibilityHints {
+    // HCF_HIGHCONTRASTON
+    let high_contrast = registry_value(r"HKCU\Control Panel\Accessibility\HighContrast", "Flags")
+        .and_then(|flags| flags.parse::<u32>().ok())
+        .map(|flags| flags & 1 != 0);
+    let reduce_motion =
+        registry_value(r"HKCU\Control Panel\Desktop\WindowMetrics", "MinAnimate").map(|v| v == "0");
+    AccessibilityHints {
+        high_contrast,
+        reduce_motion,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn read() -> AccessibilityHints {
+    let flag = |key: &str| {
+        output("defaults", &["read", "com.apple.universalaccess", key]).and_then(|v| {
+            match v.as_str() {
+                "1" => Some(true),
+                "0" => Some(false),
+                _ => None,
+            }
+        })
+    };
+    AccessibilityHints {
+        high_contrast: flag("increaseContrast"),
+        reduce_motion: flag("reduceMotion"),
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn read() -> AccessibilityHints {
+    let setting = |schema: &str, key: &str| output("gsettings", &["get", schema, key]);
+    let flag = |schema: &str, key: &str| {
+        setting(schema, key).and_then(|v| match v.as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        })
+    };
+    // Older GNOME only says so through the theme name
+    let high_contrast = flag("org.gnome.desktop.a11y.interface", "high-contrast").or_else(|| {
+        setting("org.gnome.desktop.interface", "gtk-theme")
+            .filter(|theme| theme.contains("HighContrast"))
+            .map(|_| true)
+    });
+    let reduce_motion = flag("org.gnome.desktop.interface", "enable-animations").map(|on| !on);
+    AccessibilityHints {
+        high_contrast,
+        reduce_motion,
+    }
+}
+
+/// Poll the OS settings and emit a change. Called once at startup.
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Ok(hints) = tokio::task::spawn_blocking(read).await {
+                let previous = LAST.lock().unwrap().replace(hints);
+                if previous.is_some_and(|p| p != hints) {
+                    let _ = app.emit(crate::events::ACCESSIBILITY_HINTS_CHANGED, hints);
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_accessibility_hints() -> Result<AccessibilityHints, String> {
+    let hints = tokio::task::spawn_blocking(read)
+        .await
+        .map_err(|e| format!("Task error: {}", e))?;
+    *LAST.lock().unwrap() = Some(hints);
+    Ok(hints)
+}
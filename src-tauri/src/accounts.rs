@@ -0,0 +1,325 @@
+// Per-account state.
+//
+// History, local job metadata, projects, the download and submission queues
+// and the job cache belong to one NSG account. Each account's files live in
+// a namespace folder holding an account.json with its username: the app
+// data folder itself for the first account that connected (which is how an
+// install from before namespacing keeps its data), users/<name>/ for any
+// other. accounts.json in the app data folder remembers which namespace was
+// last active. Connecting as someone other than the active namespace's owner
+// emits `account-mismatch` and keeps the app read-only, with the monitor and
+// download history not recording anything, until
+// resolve_account_mismatch either switches to the connecting account's
+// namespace (created if new) or acknowledges staying read-only on the
+// current one. Preferences, thumbnails and crash logs stay shared.
+
+use crate::anonymize::{self, anonymize_username, Anonymize};
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{Emitter, State};
+
+pub const ACCOUNTS_FILE: &str = "accounts.json";
+const OWNER_FILE: &str = "account.json";
+const USERS_DIR: &str = "users";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Owner {
+    username: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct ActiveFile {
+    active: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Accounts {
+    // Owner of the app data folder's own state files
+    root_owner: Option<String>,
+    // Owner of the namespace in use; None before anyone connected
+    active: Option<String>,
+    mismatch: Option<AccountMismatch>,
+}
+
+// None until first used
+static ACCOUNTS: Mutex<Option<Accounts>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchAction {
+    // Use the connecting account's own namespace
+    Switch,
+    // Keep showing the current namespace, read-only
+    StayReadOnly,
+}
+
+/// Payload of `account-mismatch`
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountMismatch {
+    // Owner of the metadata on screen
+    pub current_user: String,
+    pub connecting_user: String,
+    // Whether the connecting account already has a namespace here
+    pub namespace_exists: bool,
+    // StayReadOnly once acknowledged
+    pub choice: Option<MismatchAction>,
+}
+
+impl Anonymize for AccountMismatch {
+    fn anonymize(self) -> Self {
+        Self {
+            current_user: anonymize_username(&self.current_user),
+            connecting_user: anonymize_username(&self.connecting_user),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountStatus {
+    pub active_user: Option<String>,
+    pub mismatch: Option<AccountMismatch>,
+}
+
+impl Anonymize for AccountStatus {
+    fn anonymize(self) -> Self {
+        Self {
+            active_user: self.active_user.map(|u| anonymize_username(&u)),
+            mismatch: self.mismatch.anonymize(),
+        }
+    }
+}
+
+fn read_json<T: for<'de> Deserialize<'de> + Default>(path: &Path) -> T {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let content = serde_json::to_vec_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    let _lock = crate::state_file::lock(path)?;
+    crate::state_file::write_atomic(path, &content)
+}
+
+/// Folder name for a username; anything outside [A-Za-z0-9._-] is hex
+/// encoded so names can't reach outside users/
+fn dir_name(username: &str) -> String {
+    let plain = !username.is_empty()
+        && !username.starts_with('.')
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if plain {
+        username.to_string()
+    } else {
+        let hex: String = username.bytes().map(|b| format!("{:02x}", b)).collect();
+        format!("x-{}", hex)
+    }
+}
+
+fn with_accounts<R>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&Path, &mut Accounts) -> R,
+) -> Result<R, String> {
+    let root = crate::portable::app_data_root(app)?;
+    let mut accounts = ACCOUNTS.lock().unwrap();
+    let accounts = accounts.get_or_insert_with(|| {
+        let root_owner = read_json::<Option<Owner>>(&root.join(OWNER_FILE)).map(|o| o.username);
+        let active = read_json::<ActiveFile>(&root.join(ACCOUNTS_FILE))
+            .active
+            .or_else(|| root_owner.clone());
+        Accounts {
+            root_owner,
+            active,
+            mismatch: None,
+        }
+    });
+    Ok(f(&root, accounts))
+}
+
+fn namespace_dir(root: &Path, accounts: &Accounts, username: &str) -> PathBuf {
+    if accounts.root_owner.as_deref() == Some(username) {
+        root.to_path_buf()
+    } else {
+        root.join(USERS_DIR).join(dir_name(username))
+    }
+}
+
+/// Folder of the active account's state files
+pub fn user_root(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    with_accounts(app, |root, accounts| match &accounts.active {
+        Some(active) => namespace_dir(root, accounts, active),
+        None => root.to_path_buf(),
+    })
+}
+
+/// Owner of the state files in use; None before anyone connected
+pub fn active_username(app: &tauri::AppHandle) -> Option<String> {
+    with_accounts(app, |_, accounts| accounts.active.clone())
+        .ok()
+        .flatten()
+}
+
+/// Whether state belonging to the active account must be left alone,
+/// because the session is someone else's
+pub fn is_mismatched() -> bool {
+    ACCOUNTS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|a| a.mismatch.is_some())
+}
+
+fn status(accounts: &Accounts) -> AccountStatus {
+    AccountStatus {
+        active_user: accounts.active.clone(),
+        mismatch: accounts.mismatch.clone(),
+    }
+}
+
+/// Compare a newly connected account with the active namespace. The first
+/// account to connect takes over the app data folder's existing state.
+pub fn on_connect(app: &tauri::AppHandle, username: &str) {
+    let result = with_accounts(app, |root, accounts| {
+        if accounts.active.is_none() {
+            let owner = Owner {
+                username: username.to_string(),
+            };
+            write_json(&root.join(OWNER_FILE), &owner)?;
+            accounts.root_owner = Some(username.to_string());
+            accounts.active = Some(username.to_string());
+            accounts.mismatch = None;
+            return Ok(None);
+        }
+        if accounts.active.as_deref() == Some(username) {
+            accounts.mismatch = None;
+            return Ok(None);
+        }
+        let mismatch = AccountMismatch {
+            current_user: accounts.active.clone().unwrap_or_default(),
+            connecting_user: username.to_string(),
+            namespace_exists: namespace_dir(root, accounts, username)
+                .join(OWNER_FILE)
+                .exists(),
+            choice: None,
+        };
+        accounts.mismatch = Some(mismatch.clone());
+        Ok::<_, String>(Some(mismatch))
+    })
+    .and_then(|r| r);
+
+    match result {
+        Ok(Some(mismatch)) => {
+            anonymize::emit(app, crate::events::ACCOUNT_MISMATCH, mismatch);
+            crate::mode::changed(app);
+        }
+        Ok(None) => {}
+        Err(e) => crate::background_errors::report(
+            app,
+            "accounts",
+            crate::background_errors::ErrorKind::Other,
+            format!("Failed to check the account's state: {}", e),
+        ),
+    }
+}
+
+/// Forget an unresolved mismatch once its session is gone
+pub fn on_disconnect(app: &tauri::AppHandle) {
+    let had_mismatch = ACCOUNTS
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|a| a.mismatch.take())
+        .is_some();
+    if had_mismatch {
+        crate::mode::changed(app);
+    }
+}
+
+fn switch_to(root: &Path, accounts: &mut Accounts, username: &str) -> Result<(), String> {
+    let dir = namespace_dir(root, accounts, username);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let owner_path = dir.join(OWNER_FILE);
+    if !owner_path.exists() {
+        write_json(
+            &owner_path,
+            &Owner {
+                username: username.to_string(),
+            },
+        )?;
+    }
+    let active = ActiveFile {
+        active: Some(username.to_string()),
+    };
+    write_json(&root.join(ACCOUNTS_FILE), &active)?;
+    accounts.active = Some(username.to_string());
+    accounts.mismatch = None;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_account_status(app: tauri::AppHandle) -> Result<AccountStatus, String> {
+    let status = with_accounts(&app, |_, accounts| status(accounts))?;
+    Ok(anonymize::outgoing(&app, status))
+}
+
+/// Answer `account-mismatch`: switch to the connected account's own state,
+/// or stay read-only on the current one until an account that owns it
+/// connects again
+#[tauri::command]
+pub async fn resolve_account_mismatch(
+    action: MismatchAction,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<AccountStatus, String> {
+    let session = state.session.require()?;
+    let username = session.credentials.username.clone();
+
+    let status = with_accounts(&app, |root, accounts| {
+        let Some(mismatch) = accounts.mismatch.as_mut() else {
+            return Err("There's no account mismatch to resolve".to_string());
+        };
+        if mismatch.connecting_user != username {
+            return Err("The mismatch belongs to an earlier session".to_string());
+        }
+        match action {
+            MismatchAction::Switch => switch_to(root, accounts, &username)?,
+            MismatchAction::StayReadOnly => mismatch.choice = Some(MismatchAction::StayReadOnly),
+        }
+        Ok(status(accounts))
+    })??;
+
+    if action == MismatchAction::Switch {
+        // The queue's record of what's pending came from the other namespace
+        crate::queue::load(&app);
+        crate::activity_feed::reset();
+        let _ = app.emit(crate::events::ACCOUNT_NAMESPACE_CHANGED, ());
+    }
+    crate::mode::changed(&app);
+    Ok(anonymize::outgoing(&app, status))
+}
+
+/// Delete every other account's namespace and the record of who owns what,
+/// returning whether there was any
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let root = crate::portable::app_data_root(app)?;
+    *ACCOUNTS.lock().unwrap() = None;
+
+    let mut removed = false;
+    for name in [OWNER_FILE, ACCOUNTS_FILE] {
+        removed |= crate::paths::remove_file_if_exists(&root.join(name))?;
+    }
+    let users = root.join(USERS_DIR);
+    if users.exists() {
+        std::fs::remove_dir_all(&users)
+            .map_err(|e| format!("Failed to remove {}: {}", users.display(), e))?;
+        removed = true;
+    }
+    Ok(removed)
+}
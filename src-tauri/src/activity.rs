@@ -0,0 +1,67 @@
+// Network activity indicator.
+//
+// Every NSG request made through NsgApi holds an `InFlight` guard while it
+// runs, so the count covers all commands and background workers without
+// each of them doing anything. `network-activity` is emitted when the count
+// goes from zero to nonzero or back, not on every request. Descriptions are
+// fixed strings like "list jobs", never URLs, so they can't leak usernames
+// or job IDs in showcase mode.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tauri::Emitter;
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+// What the most recently started request does
+static NEWEST: Mutex<Option<&'static str>> = Mutex::new(None);
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkActivity {
+    pub in_flight: usize,
+    pub newest: Option<&'static str>,
+}
+
+fn current() -> NetworkActivity {
+    NetworkActivity {
+        in_flight: IN_FLIGHT.load(Ordering::SeqCst),
+        newest: *NEWEST.lock().unwrap(),
+    }
+}
+
+fn emit() {
+    if let Some(app) = APP.get() {
+        let _ = app.emit(crate::events::NETWORK_ACTIVITY, current());
+    }
+}
+
+/// Counts one request as in flight until dropped
+pub struct InFlight(());
+
+/// Mark a request as started; `kind` says what it does, e.g. "list jobs"
+pub fn begin(kind: &'static str) -> InFlight {
+    *NEWEST.lock().unwrap() = Some(kind);
+    if IN_FLIGHT.fetch_add(1, Ordering::SeqCst) == 0 {
+        emit();
+    }
+    InFlight(())
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if IN_FLIGHT.fetch_sub(1, Ordering::SeqCst) == 1 {
+            emit();
+        }
+    }
+}
+
+/// Let the counter emit events. Called once at startup.
+pub fn init(app: tauri::AppHandle) {
+    let _ = APP.set(app);
+}
+
+#[tauri::command]
+pub async fn get_network_activity() -> Result<NetworkActivity, String> {
+    Ok(current())
+}
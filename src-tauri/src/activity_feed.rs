@@ -0,0 +1,303 @@
+// Activity feed.
+//
+// One list of what the backend has been doing, for the frontend's
+// notification center: downloads, submissions and their uploads, queued
+// submissions going out, jobs finishing, background errors and service
+// announcements. Each occurrence is an ActivityItem, emitted as `activity`
+// whenever it is added or changes. Something ongoing is recorded under a
+// key ("download:<job>") and updated in place until it finishes, so a
+// download is one item whose progress moves rather than a stream of them.
+// The newest MAX_ITEMS are kept and saved to activity.json in the account's
+// folder whenever an item is added or finishes; progress alone isn't
+// saved, and items still running when the app quit come back interrupted.
+// Job IDs are anonymized on the way out; summaries never contain them.
+
+use crate::anonymize::{self, anonymize_job_id, Anonymize};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+pub const ACTIVITY_FILE: &str = "activity.json";
+const MAX_ITEMS: usize = 500;
+// Progress moving less than this isn't worth an event
+const MIN_PROGRESS_STEP: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Download,
+    Submission,
+    QueuedSubmission,
+    JobStatus,
+    BackgroundError,
+    Announcement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityStatus {
+    Running,
+    Succeeded,
+    Failed,
+    // Needs the user, e.g. a download waiting for confirmation
+    Attention,
+    Info,
+    // Was running when the app quit
+    Interrupted,
+}
+
+/// Payload of `activity`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityItem {
+    pub id: String,
+    pub kind: ActivityKind,
+    pub status: ActivityStatus,
+    // When it started
+    pub timestamp: String,
+    pub updated_at: String,
+    pub summary: String,
+    pub detail: Option<String>,
+    pub job_id: Option<String>,
+    // 0 to 1, for something running
+    pub progress: Option<f64>,
+    // Set while it can still be updated in place
+    #[serde(skip)]
+    key: Option<String>,
+}
+
+impl Anonymize for ActivityItem {
+    fn anonymize(self) -> Self {
+        // Error details can quote the job's ID
+        let detail = match (&self.detail, &self.job_id) {
+            (Some(detail), Some(id)) => Some(detail.replace(id.as_str(), &anonymize_job_id(id))),
+            _ => self.detail.clone(),
+        };
+        Self {
+            job_id: self.job_id.map(|id| anonymize_job_id(&id)),
+            detail,
+            ..self
+        }
+    }
+}
+
+/// What to record; fields left None keep their current value on update
+#[derive(Debug, Clone)]
+pub struct Activity {
+    pub kind: ActivityKind,
+    pub status: ActivityStatus,
+    pub summary: String,
+    pub detail: Option<String>,
+    pub job_id: Option<String>,
+}
+
+impl Activity {
+    pub fn new(kind: ActivityKind, status: ActivityStatus, summary: impl Into<String>) -> Self {
+        Self {
+            kind,
+            status,
+            summary: summary.into(),
+            detail: None,
+            job_id: None,
+        }
+    }
+
+    pub fn detail(self, detail: impl Into<String>) -> Self {
+        Self {
+            detail: Some(detail.into()),
+            ..self
+        }
+    }
+
+    pub fn job(self, job_id: &str) -> Self {
+        Self {
+            job_id: Some(job_id.to_string()),
+            ..self
+        }
+    }
+}
+
+#[derive(Default)]
+struct Feed {
+    // Oldest first
+    items: VecDeque<ActivityItem>,
+    next_id: u64,
+}
+
+// None until first used
+static FEED: Mutex<Option<Feed>> = Mutex::new(None);
+
+fn feed_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::accounts::user_root(app)?.join(ACTIVITY_FILE))
+}
+
+fn load(app: &tauri::AppHandle) -> Feed {
+    let items: VecDeque<ActivityItem> = feed_path(app)
+        .ok()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default();
+    let items: VecDeque<ActivityItem> = items
+        .into_iter()
+        .map(|item| match item.status {
+            ActivityStatus::Running => ActivityItem {
+                status: ActivityStatus::Interrupted,
+                progress: None,
+                ..item
+            },
+            _ => item,
+        })
+        .collect();
+    let next_id = items
+        .iter()
+        .filter_map(|item| item.id.strip_prefix("act-")?.parse::<u64>().ok())
+        .max()
+        .map_or(1, |id| id + 1);
+    Feed { items, next_id }
+}
+
+fn save(app: &tauri::AppHandle, feed: &Feed) {
+    let result = feed_path(app).and_then(|path| {
+        let content = serde_json::to_vec(&feed.items)
+            .map_err(|e| format!("Failed to serialize the activity feed: {}", e))?;
+        crate::state_file::write_atomic(&path, &content)
+    });
+    // Not reported: a background error is itself recorded in the feed.
+    // The items stay in memory and the next save tries again.
+    let _ = result;
+}
+
+fn with_feed<R>(app: &tauri::AppHandle, f: impl FnOnce(&mut Feed) -> R) -> R {
+    let mut feed = FEED.lock().unwrap();
+    f(feed.get_or_insert_with(|| load(app)))
+}
+
+fn emit(app: &tauri::AppHandle, item: Option<ActivityItem>) {
+    if let Some(item) = item {
+        anonymize::emit(app, crate::events::ACTIVITY, item);
+    }
+}
+
+static NEXT_KEY: AtomicU64 = AtomicU64::new(1);
+
+/// A key for something with no natural one, like a submission before it
+/// has a job ID
+pub fn unique_key(prefix: &str) -> String {
+    format!("{}:{}", prefix, NEXT_KEY.fetch_add(1, Ordering::SeqCst))
+}
+
+/// Add an item, or with `key`, update the one still open under that key
+pub fn record(app: &tauri::AppHandle, key: Option<String>, activity: Activity) {
+    let now = crate::clock::now().to_rfc3339();
+    let item = with_feed(app, |feed| {
+        let open = key
+            .as_ref()
+            .and_then(|key| feed.items.iter_mut().find(|i| i.key.as_ref() == Some(key)));
+        let item = match open {
+            Some(item) => {
+                item.status = activity.status;
+                item.summary = activity.summary;
+                item.detail = activity.detail.or(item.detail.take());
+                item.job_id = activity.job_id.or(item.job_id.take());
+                item.updated_at = now;
+                item.clone()
+            }
+            None => {
+                let item = ActivityItem {
+                    id: format!("act-{}", feed.next_id),
+                    kind: activity.kind,
+                    status: activity.status,
+                    timestamp: now.clone(),
+                    updated_at: now,
+                    summary: activity.summary,
+                    detail: activity.detail,
+                    job_id: activity.job_id,
+                    progress: None,
+                    key,
+                };
+                feed.next_id += 1;
+                feed.items.push_back(item.clone());
+                while feed.items.len() > MAX_ITEMS {
+                    feed.items.pop_front();
+                }
+                item
+            }
+        };
+        save(app, feed);
+        item
+    });
+    emit(app, Some(item));
+}
+
+/// Move the progress of the item open under `key`; not saved
+pub fn progress(app: &tauri::AppHandle, key: &str, fraction: f64) {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let item = with_feed(app, |feed| {
+        let item = feed
+            .items
+            .iter_mut()
+            .find(|i| i.key.as_deref() == Some(key))?;
+        let moved = item
+            .progress
+            .is_none_or(|p| (fraction - p).abs() >= MIN_PROGRESS_STEP || fraction >= 1.0);
+        if !moved || item.progress == Some(fraction) {
+            return None;
+        }
+        item.progress = Some(fraction);
+        item.updated_at = crate::clock::now().to_rfc3339();
+        Some(item.clone())
+    });
+    emit(app, item);
+}
+
+/// Close the item open under `key`; it won't be updated again
+pub fn finish(app: &tauri::AppHandle, key: &str, activity: Activity) {
+    record(app, Some(key.to_string()), activity);
+    let finished = with_feed(app, |feed| {
+        let item = feed
+            .items
+            .iter_mut()
+            .find(|i| i.key.as_deref() == Some(key))?;
+        item.key = None;
+        if item.status != ActivityStatus::Running {
+            item.progress = None;
+        }
+        Some(())
+    });
+    if finished.is_some() {
+        with_feed(app, |feed| save(app, feed));
+    }
+}
+
+/// Forget the in-memory feed, e.g. after switching accounts
+pub fn reset() {
+    *FEED.lock().unwrap() = None;
+}
+
+/// Delete the saved feed, returning whether there was one
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    reset();
+    crate::paths::remove_file_if_exists(&feed_path(app)?)
+}
+
+/// The newest `limit` items (default 100), newest first, optionally only
+/// of some kinds
+#[tauri::command]
+pub async fn get_activity_feed(
+    app: tauri::AppHandle,
+    limit: Option<usize>,
+    kinds: Option<Vec<ActivityKind>>,
+) -> Result<Vec<ActivityItem>, String> {
+    let limit = limit.unwrap_or(100).min(MAX_ITEMS);
+    let items: Vec<ActivityItem> = with_feed(&app, |feed| {
+        feed.items
+            .iter()
+            .rev()
+            .filter(|i| kinds.as_ref().is_none_or(|kinds| kinds.contains(&i.kind)))
+            .take(limit)
+            .cloned()
+            .collect()
+    });
+    Ok(anonymize::outgoing(&app, items))
+}
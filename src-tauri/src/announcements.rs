@@ -0,0 +1,270 @@
+// Service announcements.
+//
+// NSG maintenance is otherwise only noticed when jobs start failing. The
+// feed at the `announcements_url` preference (by default the one published
+// with this app, since NSG has no machine-readable feed) is a JSON document:
+//
+//     {"announcements": [{"id", "title", "body", "severity",
+//                         "starts_at", "ends_at"}]}
+//
+// It's fetched with If-None-Match and cached in memory. The monitor checks
+// it at most every CHECK_INTERVAL while online and emits
+// `service-announcement` once per high-severity entry that isn't dismissed.
+// A missing or broken feed is never reported: there simply are no
+// announcements.
+
+use crate::activity_feed::{Activity, ActivityKind, ActivityStatus};
+use crate::i18n::Msg;
+use crate::preferences;
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+pub const URL_KEY: &str = "announcements_url";
+pub const DISMISSED_KEY: &str = "dismissed_announcements";
+const DEFAULT_URL: &str =
+    "https://raw.githubusercontent.com/sdraeger/nsg-gui/main/announcements.json";
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+// Dismissed IDs kept; the oldest are forgotten first
+const MAX_DISMISSED: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Announcement {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+    // RFC 3339; either end may be open
+    #[serde(default)]
+    pub starts_at: Option<String>,
+    #[serde(default)]
+    pub ends_at: Option<String>,
+    #[serde(default, skip_deserializing)]
+    pub dismissed: bool,
+}
+
+fn default_severity() -> Severity {
+    Severity::Info
+}
+
+#[derive(Deserialize)]
+struct Feed {
+    #[serde(default)]
+    announcements: Vec<Announcement>,
+}
+
+struct Cache {
+    url: String,
+    etag: Option<String>,
+    announcements: Vec<Announcement>,
+    fetched_at: Option<Instant>,
+    // High-severity IDs already emitted this run
+    announced: BTreeSet<String>,
+}
+
+static CACHE: Mutex<Cache> = Mutex::new(Cache {
+    url: String::new(),
+    etag: None,
+    announcements: Vec::new(),
+    fetched_at: None,
+    announced: BTreeSet::new(),
+});
+
+fn feed_url(app: &tauri::AppHandle) -> String {
+    preferences::of(app)
+        .get(URL_KEY)
+        .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| DEFAULT_URL.to_string())
+}
+
+fn dismissed(app: &tauri::AppHandle) -> Vec<String> {
+    preferences::of(app)
+        .get(DISMISSED_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// Whether an announcement hasn't ended yet
+fn is_current(announcement: &Announcement, now: chrono::DateTime<chrono::Utc>) -> bool {
+    announcement
+        .ends_at
+        .as_deref()
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .is_none_or(|end| end >= now)
+}
+
+/// Fetch the feed, or None when it's unchanged or unavailable
+fn fetch(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    etag: Option<&str>,
+) -> Option<(Option<String>, Vec<Announcement>)> {
+    let mut request = client.get(url).timeout(FETCH_TIMEOUT);
+    if let Some(etag) = etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+
+    let response = request.send().ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let feed: Feed = response.json().ok()?;
+    Some((etag, feed.announcements))
+}
+
+/// Bring the cache up to date. Called with `force` from the command, and
+/// from the monitor, which only refetches every CHECK_INTERVAL.
+async fn refresh(app: &tauri::AppHandle, force: bool) {
+    let url = feed_url(app);
+    let etag = {
+        let cache = CACHE.lock().unwrap();
+        let fresh = cache.url == url
+            && cache
+                .fetched_at
+                .is_some_and(|t| t.elapsed() < CHECK_INTERVAL);
+        if fresh && !force {
+            return;
+        }
+        (cache.url == url).then(|| cache.etag.clone()).flatten()
+    };
+
+    let fetch_url = url.clone();
+    let fetched = match app.state::<AppState>().client.client().await {
+        Ok(client) => {
+            tokio::task::spawn_blocking(move || fetch(&client, &fetch_url, etag.as_deref()))
+                .await
+                .ok()
+                .flatten()
+        }
+        Err(_) => None,
+    };
+
+    let mut cache = CACHE.lock().unwrap();
+    if cache.url != url {
+        cache.url = url;
+        cache.etag = None;
+        cache.announcements.clear();
+    }
+    if let Some((etag, announcements)) = fetched {
+        cache.etag = etag;
+        cache.announcements = announcements;
+    }
+    cache.fetched_at = Some(Instant::now());
+}
+
+fn current_announcements(app: &tauri::AppHandle) -> Vec<Announcement> {
+    let dismissed = dismissed(app);
+    let now = crate::clock::now();
+    CACHE
+        .lock()
+        .unwrap()
+        .announcements
+        .iter()
+        .filter(|a| is_current(a, now))
+        .map(|a| Announcement {
+            dismissed: dismissed.contains(&a.id),
+            ..a.clone()
+        })
+        .collect()
+}
+
+/// Refresh when due and emit `service-announcement` for each new
+/// high-severity entry. Called by the monitor while online.
+pub async fn check(app: &tauri::AppHandle) {
+    refresh(app, false).await;
+
+    let current = current_announcements(app);
+    let new: Vec<Announcement> = {
+        let mut cache = CACHE.lock().unwrap();
+        current
+            .into_iter()
+            .filter(|a| a.severity >= Severity::Warning && !a.dismissed)
+            .filter(|a| cache.announced.insert(a.id.clone()))
+            .collect()
+    };
+    for announcement in new {
+        crate::activity_feed::record(
+            app,
+            None,
+            Activity::new(
+                ActivityKind::Announcement,
+                ActivityStatus::Info,
+                announcement.title.clone(),
+            )
+            .detail(announcement.body.clone()),
+        );
+        let _ = app.emit(crate::events::SERVICE_ANNOUNCEMENT, announcement);
+    }
+    crate::session_status::changed(app);
+}
+
+/// Severity of the most severe announcement in effect that isn't dismissed
+pub fn highest_severity(app: &tauri::AppHandle) -> Option<Severity> {
+    current_announcements(app)
+        .into_iter()
+        .filter(|a| !a.dismissed)
+        .map(|a| a.severity)
+        .max()
+}
+
+/// Announcements that haven't ended, dismissed ones included and marked.
+/// Empty when the feed can't be reached.
+#[tauri::command]
+pub async fn get_service_announcements(app: tauri::AppHandle) -> Result<Vec<Announcement>, String> {
+    refresh(&app, true).await;
+    Ok(current_announcements(&app))
+}
+
+#[tauri::command]
+pub async fn dismiss_announcement(app: tauri::AppHandle, id: String) -> Result<(), String> {
+    let mut ids = dismissed(&app);
+    if !ids.contains(&id) {
+        ids.push(id);
+    }
+    let excess = ids.len().saturating_sub(MAX_DISMISSED);
+    ids.drain(..excess);
+
+    preferences::of(&app).set(DISMISSED_KEY, json!(ids));
+    crate::session_status::changed(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_announcements_url(app: tauri::AppHandle) -> Result<String, String> {
+    Ok(feed_url(&app))
+}
+
+/// Set the feed URL; an empty one restores the default
+#[tauri::command]
+pub async fn set_announcements_url(app: tauri::AppHandle, url: String) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeNotificationSettings)?;
+    let url = url.trim();
+    if !url.is_empty() && reqwest::Url::parse(url).is_err() {
+        return Err(format!("Not a valid URL: {}", url));
+    }
+
+    preferences::of(&app).set(URL_KEY, json!(url));
+    Ok(())
+}
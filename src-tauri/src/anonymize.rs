@@ -0,0 +1,480 @@
+// Showcase mode anonymization.
+//
+// Every fake ID handed out during a session is recorded in a session-scoped
+// map, which keeps fake IDs stable, guarantees that two different real IDs
+// never share a fake one, and lets the presenter export the mapping later.
+//
+// Data keeps its real IDs everywhere inside the app. Types that carry them
+// implement `Anonymize`, and are anonymized in one place on the way out:
+// `outgoing` for command results and `emit` for events. Debug builds check
+// what goes out for real job IDs and the connected username.
+
+use crate::AppState;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use tauri::{Emitter, Manager};
+
+const FAKE_JOB_PREFIX: &str = "NGBW-JOB-";
+const FAKE_USERNAME: &str = "demo_user";
+
+#[derive(Default)]
+struct SessionMap {
+    // real job ID -> fake job ID
+    job_ids: HashMap<String, String>,
+    // fake job ID -> real job ID, used to detect collisions
+    fake_job_ids: HashMap<String, String>,
+    // fake URL -> real URL
+    urls: BTreeMap<String, String>,
+}
+
+static SESSION_MAP: Mutex<Option<SessionMap>> = Mutex::new(None);
+
+fn with_session_map<T>(f: impl FnOnce(&mut SessionMap) -> T) -> T {
+    let mut guard = SESSION_MAP.lock().unwrap();
+    f(guard.get_or_insert_with(SessionMap::default))
+}
+
+#[cfg(test)]
+thread_local! {
+    // Showcase mode for the current test thread, without the variable
+    static TEST_SHOWCASE: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+pub fn is_showcase_mode() -> bool {
+    #[cfg(test)]
+    if TEST_SHOWCASE.with(|s| s.get()) {
+        return true;
+    }
+    std::env::var("SHOWCASE_MODE").unwrap_or_default() == "1"
+}
+
+/// Run `f` with showcase mode on for this thread
+#[cfg(test)]
+pub fn in_showcase_mode<T>(f: impl FnOnce() -> T) -> T {
+    TEST_SHOWCASE.with(|s| s.set(true));
+    let result = f();
+    TEST_SHOWCASE.with(|s| s.set(false));
+    result
+}
+
+/// Fail if `value`, serialized as the frontend gets it, holds a real job
+/// ID or `username`. Call within in_showcase_mode.
+#[cfg(test)]
+pub fn assert_anonymized(value: &impl Serialize, username: &str) {
+    let value = serde_json::to_value(value).expect("value serializes");
+    let leaks = leaked_identifiers(&value, Some(username));
+    assert!(
+        leaks.is_empty(),
+        "real identifiers reach the frontend: {:?}",
+        leaks
+    );
+}
+
+pub fn anonymize_username(username: &str) -> String {
+    if is_showcase_mode() {
+        FAKE_USERNAME.to_string()
+    } else {
+        username.to_string()
+    }
+}
+
+// Consistent but unique fake suffix based on a hash of the original. `salt`
+// is bumped to move off a suffix that is already taken by another real ID.
+fn fake_job_id(job_id: &str, salt: u64) -> String {
+    let mut hash: u64 = salt;
+    for byte in job_id.bytes() {
+        hash = hash.wrapping_mul(31).wrapping_add(byte as u64);
+    }
+
+    // Convert hash to base36 string (using 0-9, A-Z)
+    let chars = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut suffix = String::new();
+    let mut h = hash;
+    for _ in 0..12 {
+        suffix.push(chars.chars().nth((h % 36) as usize).unwrap());
+        h /= 36;
+    }
+
+    format!("{}{}", FAKE_JOB_PREFIX, suffix)
+}
+
+impl SessionMap {
+    fn job_id(&mut self, job_id: &str) -> String {
+        if let Some(fake) = self.job_ids.get(job_id) {
+            return fake.clone();
+        }
+
+        let mut salt = 0;
+        let mut fake = fake_job_id(job_id, salt);
+        while self.fake_job_ids.contains_key(&fake) {
+            salt += 1;
+            fake = fake_job_id(job_id, salt);
+        }
+
+        self.job_ids.insert(job_id.to_string(), fake.clone());
+        self.fake_job_ids.insert(fake.clone(), job_id.to_string());
+        fake
+    }
+
+    fn url(&mut self, url: &str) -> String {
+        // NSG URLs format: https://nsgr.sdsc.edu:8443/cipresrest/v1/job/USERNAME/JOBID[/output...]
+        let mut parts: Vec<String> = url.split('/').map(|s| s.to_string()).collect();
+        if parts.len() < 2 {
+            return url.to_string();
+        }
+
+        let (user_idx, job_idx) = match parts.iter().position(|p| p == "job") {
+            Some(i) if i + 2 < parts.len() => (i + 1, i + 2),
+            // Not a recognizable job URL: assume it ends in USERNAME/JOBID
+            _ => (parts.len() - 2, parts.len() - 1),
+        };
+        parts[user_idx] = FAKE_USERNAME.to_string();
+        parts[job_idx] = self.job_id(&parts[job_idx]);
+
+        let fake = parts.join("/");
+        self.urls.insert(fake.clone(), url.to_string());
+        fake
+    }
+
+    // Whether `s` contains `username` or a job ID that wasn't handed out as
+    // a fake; real NSG job IDs start with the same prefix
+    fn is_leak(&self, s: &str, username: Option<&str>) -> bool {
+        if username.is_some_and(|u| !u.is_empty() && s.contains(u)) {
+            return true;
+        }
+        s.match_indices(FAKE_JOB_PREFIX).any(|(start, _)| {
+            let id: String = s[start..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+                .collect();
+            !self.fake_job_ids.contains_key(&id)
+        })
+    }
+}
+
+pub fn anonymize_job_id(job_id: &str) -> String {
+    if !is_showcase_mode() {
+        return job_id.to_string();
+    }
+    with_session_map(|map| map.job_id(job_id))
+}
+
+pub fn anonymize_url(url: &str) -> String {
+    if !is_showcase_mode() {
+        return url.to_string();
+    }
+    with_session_map(|map| map.url(url))
+}
+
+/// The real job ID behind one handed out by `anonymize_job_id`. IDs that
+/// aren't known fakes are returned unchanged.
+pub fn real_job_id(job_id: &str) -> String {
+    if !is_showcase_mode() {
+        return job_id.to_string();
+    }
+
+    with_session_map(|map| {
+        map.fake_job_ids
+            .get(job_id)
+            .cloned()
+            .unwrap_or_else(|| job_id.to_string())
+    })
+}
+
+/// The real URL behind one handed out by `anonymize_url`. URLs that aren't
+/// known fakes are returned unchanged.
+pub fn real_url(url: &str) -> String {
+    if !is_showcase_mode() {
+        return url.to_string();
+    }
+
+    with_session_map(|map| {
+        map.urls
+            .get(url)
+            .cloned()
+            .unwrap_or_else(|| url.to_string())
+    })
+}
+
+/// A value carrying real job IDs, URLs or usernames
+pub trait Anonymize {
+    /// The value as it may be shown; unchanged outside showcase mode
+    fn anonymize(self) -> Self;
+}
+
+impl<T: Anonymize> Anonymize for Vec<T> {
+    fn anonymize(self) -> Self {
+        self.into_iter().map(Anonymize::anonymize).collect()
+    }
+}
+
+impl<T: Anonymize> Anonymize for Option<T> {
+    fn anonymize(self) -> Self {
+        self.map(Anonymize::anonymize)
+    }
+}
+
+/// Strings in `value` that still contain a real job ID, or `username`.
+/// Always empty outside showcase mode.
+pub fn leaked_identifiers(value: &Value, username: Option<&str>) -> Vec<String> {
+    if !is_showcase_mode() {
+        return Vec::new();
+    }
+
+    with_session_map(|map| leaks(value, |s| map.is_leak(s, username)))
+}
+
+// Every string in `value`, keys included, that `is_leak` flags
+fn leaks(value: &Value, is_leak: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut leaks = Vec::new();
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::String(s) if is_leak(s) => leaks.push(s.clone()),
+            Value::Array(items) => pending.extend(items),
+            Value::Object(fields) => {
+                for (key, field) in fields {
+                    if is_leak(key) {
+                        leaks.push(key.clone());
+                    }
+                    pending.push(field);
+                }
+            }
+            _ => {}
+        }
+    }
+    leaks
+}
+
+#[cfg(debug_assertions)]
+fn report_leaks(app: &tauri::AppHandle, what: &str, value: &impl Serialize) {
+    let Ok(value) = serde_json::to_value(value) else {
+        return;
+    };
+    let username = app
+        .state::<AppState>()
+        .session
+        .snapshot()
+        .map(|s| s.credentials.username);
+    // The count only: the message is shown, and the identifiers are what
+    // mustn't be
+    let leaks = leaked_identifiers(&value, username.as_deref()).len();
+    if leaks > 0 {
+        crate::background_errors::report(
+            app,
+            "showcase_mode",
+            crate::background_errors::ErrorKind::Other,
+            format!("{} leaks real identifiers ({})", what, leaks),
+        );
+    }
+}
+
+/// Anonymize a command result before it's returned to the webview
+pub fn outgoing<T: Anonymize + Serialize>(app: &tauri::AppHandle, value: T) -> T {
+    let value = value.anonymize();
+    #[cfg(debug_assertions)]
+    report_leaks(app, "command result", &value);
+    #[cfg(not(debug_assertions))]
+    let _ = app;
+    value
+}
+
+/// Emit an event with its payload anonymized
+pub fn emit<T: Anonymize + Serialize + Clone>(app: &tauri::AppHandle, event: &str, payload: T) {
+    let payload = payload.anonymize();
+    #[cfg(debug_assertions)]
+    report_leaks(app, event, &payload);
+    let _ = app.emit(event, payload);
+}
+
+#[allow(dead_code)]
+pub fn anonymize_app_key(key: &str) -> String {
+    if is_showcase_mode() {
+        "DEMO-APP-KEY-".to_string() + &"X".repeat(32)
+    } else {
+        key.to_string()
+    }
+}
+
+#[derive(Serialize)]
+struct AnonymizationMapExport {
+    exported_at: String,
+    // fake -> real
+    job_ids: BTreeMap<String, String>,
+    urls: BTreeMap<String, String>,
+}
+
+/// Write the fake -> real mapping of this session to `path`, readable only
+/// by the current user.
+#[tauri::command]
+pub async fn export_anonymization_map(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<usize, String> {
+    if !is_showcase_mode() {
+        return Err("The anonymization map is only available in showcase mode".to_string());
+    }
+    let path = crate::path_access::authorize_str(&app, &path)?;
+
+    let export = with_session_map(|map| AnonymizationMapExport {
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        job_ids: map
+            .fake_job_ids
+            .iter()
+            .map(|(fake, real)| (fake.clone(), real.clone()))
+            .collect(),
+        urls: map.urls.clone(),
+    });
+    let count = export.job_ids.len();
+
+    let content = serde_json::to_string_pretty(&export)
+        .map_err(|e| format!("Failed to serialize anonymization map: {}", e))?;
+
+    write_private_file(&path, content.as_bytes())
+        .map_err(|e| format!("Failed to write anonymization map: {}", e))?;
+
+    Ok(count)
+}
+
+fn write_private_file(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let mut file = options.open(path)?;
+        // mode() only applies to newly created files
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        file.write_all(contents)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let mut file = options.open(path)?;
+        file.write_all(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const URL: &str = "https://nsgr.sdsc.edu:8443/cipresrest/v1/job/alice/NGBW-JOB-REAL1";
+
+    #[test]
+    fn fake_ids_are_stable_per_real_id() {
+        let mut map = SessionMap::default();
+        let first = map.job_id("NGBW-JOB-A");
+        assert!(first.starts_with(FAKE_JOB_PREFIX));
+        assert_eq!(map.job_id("NGBW-JOB-A"), first);
+        assert_ne!(map.job_id("NGBW-JOB-B"), first);
+        assert_eq!(map.fake_job_ids[&first], "NGBW-JOB-A");
+    }
+
+    #[test]
+    fn a_taken_fake_id_is_not_handed_out_again() {
+        let mut map = SessionMap::default();
+        // Another real ID already holds the fake this one hashes to
+        let taken = fake_job_id("NGBW-JOB-A", 0);
+        map.fake_job_ids
+            .insert(taken.clone(), "NGBW-JOB-OTHER".to_string());
+
+        let fake = map.job_id("NGBW-JOB-A");
+        assert_ne!(fake, taken);
+        assert_eq!(fake, fake_job_id("NGBW-JOB-A", 1));
+        assert_eq!(map.fake_job_ids[&taken], "NGBW-JOB-OTHER");
+        assert_eq!(map.fake_job_ids[&fake], "NGBW-JOB-A");
+    }
+
+    #[test]
+    fn urls_lose_the_username_and_real_id_and_map_back() {
+        let mut map = SessionMap::default();
+        let fake = map.url(&format!("{}/output", URL));
+        let fake_id = map.job_id("NGBW-JOB-REAL1");
+        assert_eq!(
+            fake,
+            format!(
+                "https://nsgr.sdsc.edu:8443/cipresrest/v1/job/{}/{}/output",
+                FAKE_USERNAME, fake_id
+            )
+        );
+        assert_eq!(map.urls[&fake], format!("{}/output", URL));
+        assert_eq!(map.url("nourl"), "nourl");
+    }
+
+    #[test]
+    fn real_ids_and_the_username_are_leaks() {
+        let mut map = SessionMap::default();
+        let fake = map.job_id("NGBW-JOB-REAL1");
+        let payload = json!({
+            "job_id": fake,
+            "jobs": [{ "url": URL }, { "note": "started by alice" }],
+            "NGBW-JOB-REAL2": "as a key",
+            "count": 3,
+        });
+        let mut found = leaks(&payload, |s| map.is_leak(s, Some("alice")));
+        found.sort();
+        assert_eq!(found, ["NGBW-JOB-REAL2", URL, "started by alice"]);
+    }
+
+    #[test]
+    fn job_summaries_and_details_go_out_anonymized() {
+        in_showcase_mode(|| {
+            let summary = crate::JobSummary {
+                job_id: "NGBW-JOB-REAL1".to_string(),
+                url: URL.to_string(),
+                tool: Some("PY_EXPANSE".to_string()),
+                ..Default::default()
+            };
+            assert_anonymized(&vec![summary.clone()].anonymize(), "alice");
+
+            let details = crate::JobDetails {
+                job_id: "NGBW-JOB-REAL1".to_string(),
+                job_stage: "COMPLETED".to_string(),
+                failed: false,
+                date_submitted: None,
+                self_uri: URL.to_string(),
+                results_uri: Some(format!("{}/output", URL)),
+                compute_resource: None,
+                tool_version: None,
+                notification_requested: None,
+                notification_email: None,
+                scheduler_info: None,
+                queue_note: None,
+                results_expire_at: None,
+            };
+            let details = details.anonymize();
+            assert_anonymized(&details, "alice");
+            // The fakes map back for the commands they are passed to
+            assert_eq!(real_url(&details.self_uri), URL);
+            assert_eq!(real_job_id(&details.job_id), "NGBW-JOB-REAL1");
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "real identifiers")]
+    fn a_payload_that_skipped_anonymizing_is_caught() {
+        let summary = crate::JobSummary {
+            job_id: "NGBW-JOB-REAL3".to_string(),
+            url: URL.replace("REAL1", "REAL3"),
+            ..Default::default()
+        };
+        in_showcase_mode(|| assert_anonymized(&summary, "alice"));
+    }
+
+    #[test]
+    fn handed_out_fakes_are_not_leaks() {
+        let mut map = SessionMap::default();
+        let fake_url = map.url(URL);
+        let payload = json!({ "url": fake_url, "user": FAKE_USERNAME });
+        assert!(leaks(&payload, |s| map.is_leak(s, Some("alice"))).is_empty());
+        assert!(leaks(&payload, |s| map.is_leak(s, Some(""))).is_empty());
+    }
+}
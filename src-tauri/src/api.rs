@@ -0,0 +1,1350 @@
+// Direct CIPRES REST calls for the parts of the API that NsgClient doesn't
+// expose. Responses are parsed with nsg-cli's own XML parsers so the models
+// stay identical to what NsgClient returns.
+
+use crate::i18n::{t, Msg};
+use crate::inputs::InputBinding;
+use crate::xml::{
+    parse_file_dates, parse_job_extras, parse_listing_paging, parse_tool_ids, parse_tool_list,
+    recover_job_handle, JobExtras, ListedTool, RecoveredHandle,
+};
+use crate::{activity, clock};
+use anyhow::{Context, Result};
+use nsg_cli::models::{
+    parse_job_list, parse_job_status, parse_output_files, JobStatus, JobSummary, OutputFile,
+};
+use nsg_cli::Credentials;
+use rayon::prelude::*;
+use reqwest::blocking::{multipart, Client, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE};
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub const NSG_BASE_URL: &str = "https://nsgr.sdsc.edu:8443/cipresrest/v1";
+// Set only by headless mode, to point requests at the demo backend
+static BASE_URL: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// The client-wide timeout covers the whole body, which is far too short for
+// multi-GB result files and job archives
+const FILE_TRANSFER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(24 * 3600);
+// Download reads start at the smallest chunk and grow while the link keeps
+// filling them quickly, so a fast link isn't held back by per-chunk work
+// and a slow one still reports progress often
+const MIN_DOWNLOAD_CHUNK: usize = 64 * 1024;
+const MAX_DOWNLOAD_CHUNK: usize = 4 * 1024 * 1024;
+// A chunk that takes less than this to fill and handle doubles; one that
+// takes more than SLOW_CHUNK halves
+const FAST_CHUNK: std::time::Duration = std::time::Duration::from_millis(20);
+const SLOW_CHUNK: std::time::Duration = std::time::Duration::from_millis(250);
+// A paged file listing followed further than this is treated as broken
+const MAX_LISTING_PAGES: usize = 200;
+
+/// Why an authenticated probe failed, so the UI can offer the right fix
+#[derive(Debug)]
+pub enum ProbeError {
+    // The server answered and rejected the credentials
+    Unauthorized(String, CredentialCulprit),
+    // We never got an answer (DNS, TLS, timeout, offline)
+    Network(String),
+    Other(String),
+}
+
+impl std::fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeError::Unauthorized(msg, _)
+            | ProbeError::Network(msg)
+            | ProbeError::Other(msg) => {
+                write!(f, "{}", msg)
+            }
+        }
+    }
+}
+
+/// The credential a rejected probe most likely got wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialCulprit {
+    // The username or the password; basic auth can't tell them apart
+    Password,
+    AppKey,
+    Unknown,
+}
+
+/// Something about an app key that can't be right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppKeyProblem {
+    Empty,
+    // Spaces, tabs or line breaks, usually from copying too much
+    Whitespace,
+    TooShort,
+    TooLong,
+    // Anything but letters, digits, '-', '_' and '.'
+    InvalidCharacters,
+    // The key's page URL was pasted instead of the key
+    LooksLikeUrl,
+    SameAsPassword,
+}
+
+// NSG keys are the application name, a dash and 32 hex digits
+const APP_KEY_MIN_LEN: usize = 16;
+const APP_KEY_MAX_LEN: usize = 128;
+
+/// Check an app key's format without asking the server. The password, when
+/// given, catches it being pasted into the app key field.
+pub fn app_key_problems(key: &str, password: Option<&str>) -> Vec<AppKeyProblem> {
+    let mut problems = Vec::new();
+    if key.trim().is_empty() {
+        problems.push(AppKeyProblem::Empty);
+        return problems;
+    }
+    if key.chars().any(char::is_whitespace) {
+        problems.push(AppKeyProblem::Whitespace);
+    }
+
+    let trimmed = key.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("www.") {
+        problems.push(AppKeyProblem::LooksLikeUrl);
+    } else if !trimmed
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        problems.push(AppKeyProblem::InvalidCharacters);
+    }
+    if trimmed.len() < APP_KEY_MIN_LEN {
+        problems.push(AppKeyProblem::TooShort);
+    } else if trimmed.len() > APP_KEY_MAX_LEN {
+        problems.push(AppKeyProblem::TooLong);
+    }
+    if password.is_some_and(|p| !p.is_empty() && p.trim() == trimmed) {
+        problems.push(AppKeyProblem::SameAsPassword);
+    }
+    problems
+}
+
+/// Where requests go: NSG_BASE_URL unless set_base_url was called
+pub fn base_url() -> &'static str {
+    BASE_URL.get().map_or(NSG_BASE_URL, String::as_str)
+}
+
+/// Send every request to `url` instead of NSG, before any is made
+#[cfg(feature = "headless-invoke")]
+pub fn set_base_url(url: String) {
+    let _ = BASE_URL.set(url);
+}
+
+/// Blame a field from the message of NSG's error document
+fn culprit_of_message(body: &str) -> Option<CredentialCulprit> {
+    let lower = body.to_ascii_lowercase();
+    if ["appkey", "app key", "application id", "application key"]
+        .iter()
+        .any(|w| lower.contains(w))
+    {
+        Some(CredentialCulprit::AppKey)
+    } else if ["password", "username", "user name"]
+        .iter()
+        .any(|w| lower.contains(w))
+    {
+        Some(CredentialCulprit::Password)
+    } else {
+        None
+    }
+}
+
+/// Blame a field for a rejected login: from the server's message, then from
+/// the key's format. No endpoint refuses a bad key on its own (the tool list
+/// is public), so a well-formed key is never cleared and the password never
+/// blamed without the server saying so.
+fn rejection_culprit(body: &str, credentials: &Credentials) -> CredentialCulprit {
+    if let Some(culprit) = culprit_of_message(body) {
+        return culprit;
+    }
+    if app_key_problems(&credentials.app_key, Some(&credentials.password)).is_empty() {
+        CredentialCulprit::Unknown
+    } else {
+        CredentialCulprit::AppKey
+    }
+}
+
+/// Read a listing starting at `url`, fetching each page with `fetch` and
+/// following next-page links; see NsgApi::dated_listing
+fn follow_listing(
+    url: &str,
+    mut fetch: impl FnMut(&str) -> Result<String>,
+) -> Result<(Vec<OutputFile>, BTreeMap<String, String>)> {
+    let mut files = Vec::new();
+    let mut dates = BTreeMap::new();
+    let mut seen = HashSet::new();
+    let mut visited = HashSet::from([url.to_string()]);
+    let mut declared_total = None;
+    let mut page_url = url.to_string();
+
+    for _ in 0..MAX_LISTING_PAGES {
+        let body = fetch(&page_url)?;
+        let paging = parse_listing_paging(&body);
+        for file in parse_output_files(&body)? {
+            if seen.insert(file.download_uri.clone()) {
+                files.push(file);
+            }
+        }
+        dates.extend(parse_file_dates(&body));
+        declared_total = declared_total.max(paging.total);
+
+        // A next link seen before would loop forever
+        match paging.next_url.filter(|next| visited.insert(next.clone())) {
+            Some(next) => page_url = next,
+            None => {
+                if let Some(total) = declared_total.filter(|t| files.len() < *t) {
+                    anyhow::bail!(
+                        "Incomplete file listing: the server reported {} files but sent {}",
+                        total,
+                        files.len()
+                    );
+                }
+                if paging.truncated {
+                    anyhow::bail!(
+                        "Incomplete file listing: the server cut it short after {} files",
+                        files.len()
+                    );
+                }
+                return Ok((files, dates));
+            }
+        }
+    }
+
+    anyhow::bail!(
+        "The file listing runs past {} pages; stopped at {} files rather than use an incomplete list",
+        MAX_LISTING_PAGES,
+        files.len()
+    )
+}
+
+/// Observable stages of establishing a session, in order
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectPhase {
+    BuildingClient,
+    ResolvingHost,
+    TlsHandshake,
+    Authenticating,
+}
+
+impl ConnectPhase {
+    /// Human-readable name in the current locale
+    pub fn label(&self) -> String {
+        t(
+            match self {
+                ConnectPhase::BuildingClient => Msg::PhaseBuildingClient,
+                ConnectPhase::ResolvingHost => Msg::PhaseResolvingHost,
+                ConnectPhase::TlsHandshake => Msg::PhaseTlsHandshake,
+                ConnectPhase::Authenticating => Msg::PhaseAuthenticating,
+            },
+            &[],
+        )
+    }
+}
+
+/// Whether a request failed because the server couldn't be reached at all,
+/// as opposed to answering with an error
+pub fn is_network_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|r| r.is_connect() || r.is_timeout())
+    })
+}
+
+/// A job status together with the fields nsg-cli doesn't parse
+pub struct JobRecord {
+    pub status: JobStatus,
+    pub extras: JobExtras,
+}
+
+/// Outcome of a submission the server accepted
+pub enum Submitted {
+    Confirmed(JobStatus),
+    // Accepted, but the response didn't parse
+    Unconfirmed(UnreadSubmission),
+}
+
+/// A successful submission response that couldn't be read as a job status
+pub struct UnreadSubmission {
+    pub raw: String,
+    // Whatever identifies the job in `raw`
+    pub handle: RecoveredHandle,
+    pub error: String,
+}
+
+/// Send a request, counting it as in flight for the activity indicator and
+/// sampling the server's clock. Every NSG request goes through here.
+fn execute(request: RequestBuilder, kind: &'static str) -> reqwest::Result<Response> {
+    let _in_flight = activity::begin(kind);
+    let response = request.send()?;
+    if let Some(date) = response
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|v| v.to_str().ok())
+    {
+        clock::observe(date);
+    }
+    Ok(response)
+}
+
+/// Read until `buffer` is full or the body ends, returning the bytes read.
+/// A single read returns whatever the socket has, which is too little to
+/// judge the link by.
+fn read_chunk(body: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match body.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// The chunk size after `filled` bytes of a `chunk`-sized read took `took`
+fn next_chunk(chunk: usize, filled: usize, took: std::time::Duration) -> usize {
+    if filled == chunk && took < FAST_CHUNK && chunk < MAX_DOWNLOAD_CHUNK {
+        chunk * 2
+    } else if took > SLOW_CHUNK && chunk > MIN_DOWNLOAD_CHUNK {
+        chunk / 2
+    } else {
+        chunk
+    }
+}
+
+/// Identifies one version of the job listing, for job_listing to tell
+/// whether it changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingVersion {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    // SHA-256 of the body, for a server that sends neither header or
+    // ignores them
+    pub body_hash: [u8; 32],
+}
+
+/// The version of a job listing response, or None when it is the same as
+/// `previous`: a 304 to the conditional request, or the body it had
+fn listing_version(
+    previous: Option<&ListingVersion>,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Option<ListingVersion>> {
+    if previous.is_some() && status == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        anyhow::bail!("Failed to list jobs: HTTP {}", status);
+    }
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let version = ListingVersion {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+        body_hash: Sha256::digest(body.as_bytes()).into(),
+    };
+    if previous.is_some_and(|p| p.body_hash == version.body_hash) {
+        return Ok(None);
+    }
+    Ok(Some(version))
+}
+
+/// Called with the number of input bytes sent so far, across all files
+pub type UploadProgress = Arc<dyn Fn(u64) + Send + Sync>;
+
+/// Counts the bytes read out of an input file as it's uploaded
+struct CountingReader {
+    file: File,
+    sent: Arc<AtomicU64>,
+    progress: UploadProgress,
+}
+
+impl Read for CountingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.file.read(buf)?;
+        let sent = self.sent.fetch_add(n as u64, Ordering::SeqCst) + n as u64;
+        (self.progress)(sent);
+        Ok(n)
+    }
+}
+
+/// A streamed multipart part for one input file
+fn file_part(
+    path: &Path,
+    progress: Option<(&Arc<AtomicU64>, &UploadProgress)>,
+) -> Result<multipart::Part> {
+    let file_name = match path.file_name() {
+        Some(name) => name
+            .to_str()
+            .with_context(|| format!("File name is not valid UTF-8: {}", path.display()))?
+            .to_string(),
+        None => anyhow::bail!("Not a file: {}", path.display()),
+    };
+
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let length = file
+        .metadata()
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .len();
+    let part = match progress {
+        Some((sent, progress)) => multipart::Part::reader_with_length(
+            CountingReader {
+                file,
+                sent: sent.clone(),
+                progress: progress.clone(),
+            },
+            length,
+        ),
+        None => multipart::Part::reader_with_length(file, length),
+    };
+    Ok(part
+        .file_name(file_name)
+        .mime_str("application/octet-stream")?)
+}
+
+#[derive(Clone)]
+pub struct NsgApi {
+    client: Client,
+    credentials: Credentials,
+}
+
+fn build_client() -> Result<Client> {
+    Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .context("Failed to create HTTP client")
+}
+
+/// The app-wide HTTP client, kept in AppState so every request reuses its
+/// open connections and TLS sessions instead of setting up new ones.
+/// Credentials go with every request, so accounts can share it. It's built
+/// on first use and never dropped: clones share its connection pool, and
+/// the last drop of a blocking client panics inside the async runtime.
+#[derive(Default)]
+pub struct ApiClient {
+    client: tokio::sync::Mutex<Option<Client>>,
+}
+
+impl ApiClient {
+    /// The shared client, building it if this is the first use
+    pub async fn client(&self) -> std::result::Result<Client, String> {
+        let mut client = self.client.lock().await;
+        if let Some(client) = &*client {
+            return Ok(client.clone());
+        }
+        // Building a blocking client waits on a thread of its own
+        let built = tokio::task::spawn_blocking(build_client)
+            .await
+            .map_err(|e| format!("Task error: {}", e))?
+            .map_err(|e| format!("{:#}", e))?;
+        Ok(client.insert(built).clone())
+    }
+
+    /// An API handle for `credentials` on the shared client
+    pub async fn api(&self, credentials: Credentials) -> std::result::Result<NsgApi, String> {
+        Ok(NsgApi {
+            client: self.client().await?,
+            credentials,
+        })
+    }
+
+    /// Same as `api`, for threads outside the async runtime
+    pub fn blocking_api(&self, credentials: Credentials) -> std::result::Result<NsgApi, String> {
+        let mut client = self.client.blocking_lock();
+        let client = match &*client {
+            Some(client) => client.clone(),
+            None => client
+                .insert(build_client().map_err(|e| format!("{:#}", e))?)
+                .clone(),
+        };
+        Ok(NsgApi {
+            client,
+            credentials,
+        })
+    }
+}
+
+impl NsgApi {
+    /// Build an authenticated request. Accepts either a full URL returned by
+    /// the API or a path relative to the base URL.
+    fn request(&self, method: Method, url_or_path: &str) -> RequestBuilder {
+        let url = if url_or_path.starts_with("http") {
+            url_or_path.to_string()
+        } else {
+            format!("{}{}", base_url(), url_or_path)
+        };
+
+        self.client
+            .request(method, url)
+            .basic_auth(&self.credentials.username, Some(&self.credentials.password))
+            .header("cipres-appkey", &self.credentials.app_key)
+    }
+
+    fn send(&self, method: Method, url_or_path: &str, what: &'static str) -> Result<Response> {
+        let response = execute(self.request(method, url_or_path), what)
+            .with_context(|| format!("Failed to {}", what))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to {}: HTTP {}", what, response.status());
+        }
+
+        Ok(response)
+    }
+
+    /// Connect step by step, reporting each phase as it begins, so a slow or
+    /// failing connection can be pinned to DNS, TLS, or authentication.
+    /// `ConnectPhase::BuildingClient` is the caller's, getting `client`.
+    pub fn connect_staged(
+        client: Client,
+        credentials: Credentials,
+        mut on_phase: impl FnMut(ConnectPhase),
+    ) -> std::result::Result<Self, (ConnectPhase, ProbeError)> {
+        let api = Self {
+            client,
+            credentials,
+        };
+
+        on_phase(ConnectPhase::ResolvingHost);
+        let url = reqwest::Url::parse(base_url()).map_err(|e| {
+            (
+                ConnectPhase::ResolvingHost,
+                ProbeError::Other(format!("Invalid API URL {}: {}", base_url(), e)),
+            )
+        })?;
+        let host = url.host_str().unwrap_or_default();
+        let port = url.port_or_known_default().unwrap_or(443);
+        (host, port).to_socket_addrs().map_err(|e| {
+            (
+                ConnectPhase::ResolvingHost,
+                ProbeError::Network(format!("Could not resolve {}: {}", host, e)),
+            )
+        })?;
+
+        // Any HTTP answer at all means TCP and TLS are up
+        on_phase(ConnectPhase::TlsHandshake);
+        execute(api.client.head(base_url()), "connect").map_err(|e| {
+            (
+                ConnectPhase::TlsHandshake,
+                ProbeError::Network(format!("Could not establish a secure connection: {}", e)),
+            )
+        })?;
+
+        on_phase(ConnectPhase::Authenticating);
+        api.probe_auth()
+            .map_err(|e| (ConnectPhase::Authenticating, e))?;
+
+        Ok(api)
+    }
+
+    /// Same request as NsgClient::test_connection, with the failure classified
+    pub fn probe_auth(&self) -> Result<(), ProbeError> {
+        let path = format!("/job/{}", self.credentials.username);
+        let response = execute(self.request(Method::GET, &path), "check credentials")
+            .map_err(|e| ProbeError::Network(format!("Failed to connect to NSG API: {}", e)))?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            let body = response.text().unwrap_or_default();
+            let culprit = rejection_culprit(&body, &self.credentials);
+            let hint = match culprit {
+                CredentialCulprit::Password => "Check your username and password",
+                CredentialCulprit::AppKey => "Check your app key",
+                CredentialCulprit::Unknown => "Check your credentials",
+            };
+            return Err(ProbeError::Unauthorized(
+                format!("Authentication failed: HTTP {} - {}", status, hint),
+                culprit,
+            ));
+        }
+        if !status.is_success() {
+            return Err(ProbeError::Other(format!(
+                "Unexpected response: HTTP {}",
+                status
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn job_record(&self, job_url: &str) -> Result<JobRecord> {
+        let body = self
+            .send(Method::GET, job_url, "fetch job status")?
+            .text()?;
+        let status = parse_job_status(&body)?;
+        Ok(JobRecord {
+            status: JobStatus {
+                date_submitted: clock::normalize_server_time(status.date_submitted),
+                date_completed: clock::normalize_server_time(status.date_completed),
+                ..status
+            },
+            extras: parse_job_extras(&body),
+        })
+    }
+
+    /// List all jobs with their detailed status, like NsgClient::list_jobs.
+    /// Jobs whose status can't be fetched keep their basic listing fields and
+    /// no extras.
+    pub fn list_jobs(&self) -> Result<Vec<(JobSummary, Option<JobExtras>)>> {
+        let (basic_jobs, _) = self
+            .job_listing(None)?
+            .context("Failed to list jobs: the server sent no listing")?;
+        Ok(self.job_records(basic_jobs))
+    }
+
+    /// The bare job listing, or None when it is the same as `previous`:
+    /// the server answered 304 to a conditional request, or sent a body
+    /// identical to the last one
+    pub fn job_listing(
+        &self,
+        previous: Option<&ListingVersion>,
+    ) -> Result<Option<(Vec<JobSummary>, ListingVersion)>> {
+        let path = format!("/job/{}", self.credentials.username);
+        let mut request = self.request(Method::GET, &path);
+        if let Some(previous) = previous {
+            if let Some(etag) = &previous.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(modified) = &previous.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, modified);
+            }
+        }
+        let response = execute(request, "list jobs").context("Failed to list jobs")?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text()?;
+        let Some(version) = listing_version(previous, status, &headers, &body)? else {
+            return Ok(None);
+        };
+        let jobs = parse_job_list(&body)?
+            .into_iter()
+            .map(|job| JobSummary {
+                date_submitted: clock::normalize_server_time(job.date_submitted),
+                date_completed: clock::normalize_server_time(job.date_completed),
+                ..job
+            })
+            .collect();
+        Ok(Some((jobs, version)))
+    }
+
+    /// The detailed status of each job in a listing
+    pub fn job_records(&self, basic_jobs: Vec<JobSummary>) -> Vec<(JobSummary, Option<JobExtras>)> {
+        basic_jobs
+            .into_par_iter()
+            .map(|basic| match self.job_record(&basic.url) {
+                Ok(record) => (
+                    JobSummary {
+                        job_id: record.status.job_id,
+                        url: basic.url,
+                        tool: record.status.tool_id,
+                        job_stage: Some(record.status.job_stage),
+                        failed: record.status.failed,
+                        date_submitted: record.status.date_submitted,
+                        date_completed: record.status.date_completed,
+                    },
+                    Some(record.extras),
+                ),
+                Err(_) => (basic, None),
+            })
+            .collect()
+    }
+
+    /// List the output files of a finished job
+    pub fn output_files(&self, status: &JobStatus) -> Result<Vec<OutputFile>> {
+        let results_url = status
+            .results_uri
+            .clone()
+            .context("Job has no results URL - may not be completed yet")?;
+
+        self.dated_listing(&results_url, "fetch results list")
+            .map(|(files, _)| files)
+    }
+
+    /// List a results or working directory URL, with each file's
+    /// modification time where the server gives one. A listing split into
+    /// pages is followed to the end, up to MAX_LISTING_PAGES; one that stops
+    /// short of the total it declares, or is flagged truncated, is an error
+    /// rather than a silently incomplete list.
+    pub fn dated_listing(
+        &self,
+        url: &str,
+        action: &'static str,
+    ) -> Result<(Vec<OutputFile>, BTreeMap<String, String>)> {
+        follow_listing(url, |page_url| {
+            Ok(self.send(Method::GET, page_url, action)?.text()?)
+        })
+    }
+
+    /// Delete a job and its results on the server
+    pub fn delete_job(&self, job_url: &str) -> Result<()> {
+        self.send(Method::DELETE, job_url, "delete job")?;
+        Ok(())
+    }
+
+    /// Stop a job that hasn't finished. NSG has no separate cancel; the
+    /// DELETE that removes a finished job kills a queued or running one.
+    pub fn cancel_job(&self, job_url: &str) -> Result<()> {
+        self.send(Method::DELETE, job_url, "cancel job")?;
+        Ok(())
+    }
+
+    /// The form for a submission: the tool, each input file under its
+    /// parameter name, and `params` as extra fields, including the
+    /// `metadata.*` email options. Files are read from their paths as OsStr
+    /// and streamed into the request body, so memory use doesn't grow with
+    /// their size. File names are sent as raw UTF-8 in the multipart header
+    /// rather than percent-encoded, which the server would store literally.
+    fn submission_form(
+        inputs: &[InputBinding],
+        tool: &str,
+        params: &BTreeMap<String, String>,
+        progress: Option<&UploadProgress>,
+    ) -> Result<multipart::Form> {
+        let mut form = multipart::Form::new()
+            .percent_encode_noop()
+            .text("tool", tool.to_string());
+        let sent = Arc::new(AtomicU64::new(0));
+        for input in inputs {
+            let part = file_part(&input.file_path, progress.map(|p| (&sent, p)))
+                .with_context(|| format!("Input {}", input.param_name))?;
+            form = form.part(input.param_name.clone(), part);
+        }
+        for (name, value) in params {
+            form = form.text(name.clone(), value.clone());
+        }
+        Ok(form)
+    }
+
+    /// Submit a job with its input files
+    pub fn submit_job(
+        &self,
+        inputs: &[InputBinding],
+        tool: &str,
+        params: &BTreeMap<String, String>,
+    ) -> Result<Submitted> {
+        self.submit_job_with_progress(inputs, tool, params, None)
+    }
+
+    /// submit_job, reporting how much of the inputs has been sent
+    pub fn submit_job_with_progress(
+        &self,
+        inputs: &[InputBinding],
+        tool: &str,
+        params: &BTreeMap<String, String>,
+        progress: Option<&UploadProgress>,
+    ) -> Result<Submitted> {
+        let form = Self::submission_form(inputs, tool, params, progress)?;
+        let path = format!("/job/{}", self.credentials.username);
+        let request = self
+            .request(Method::POST, &path)
+            .multipart(form)
+            .timeout(FILE_TRANSFER_TIMEOUT);
+        let response = execute(request, "submit job").context("Failed to submit job")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().unwrap_or_default();
+            anyhow::bail!("Failed to submit job: HTTP {}\nResponse: {}", status, body);
+        }
+
+        // The server took the job; from here on a failure means we don't
+        // know its handle, not that it doesn't exist
+        let body = match response.text() {
+            Ok(body) => body,
+            Err(e) => {
+                return Ok(Submitted::Unconfirmed(UnreadSubmission {
+                    raw: String::new(),
+                    handle: RecoveredHandle::default(),
+                    error: format!("Failed to read the submission response: {}", e),
+                }))
+            }
+        };
+        match parse_job_status(&body) {
+            Ok(status) => Ok(Submitted::Confirmed(status)),
+            Err(e) => Ok(Submitted::Unconfirmed(UnreadSubmission {
+                handle: recover_job_handle(&body),
+                raw: body,
+                error: format!("Failed to parse the submission response: {}", e),
+            })),
+        }
+    }
+
+    /// Have the server check a submission without creating a job. The files
+    /// are uploaded like for a real submission. Returns the server's
+    /// complaint if it rejects the submission.
+    pub fn validate_job(
+        &self,
+        inputs: &[InputBinding],
+        tool: &str,
+        params: &BTreeMap<String, String>,
+    ) -> Result<Option<String>> {
+        let form = Self::submission_form(inputs, tool, params, None)?;
+        let path = format!("/job/{}/validate", self.credentials.username);
+        let request = self
+            .request(Method::POST, &path)
+            .multipart(form)
+            .timeout(FILE_TRANSFER_TIMEOUT);
+        let response = execute(request, "validate job").context("Failed to validate job")?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(None);
+        }
+        if status.is_client_error() {
+            let body = response.text().unwrap_or_default();
+            return Ok(Some(format!("HTTP {}: {}", status, body.trim())));
+        }
+        anyhow::bail!("Failed to validate job: HTTP {}", status)
+    }
+
+    /// IDs of the tools the server offers
+    pub fn tool_ids(&self) -> Result<Vec<String>> {
+        let body = self.send(Method::GET, "/tool", "list tools")?.text()?;
+        Ok(parse_tool_ids(&body))
+    }
+
+    /// The tools the server offers, with their names
+    pub fn tools(&self) -> Result<Vec<ListedTool>> {
+        let body = self.send(Method::GET, "/tool", "list tools")?.text()?;
+        Ok(parse_tool_list(&body))
+    }
+
+    /// Stream one result file to `dest`, calling `on_chunk` with every chunk
+    /// after it has been written, along with the number of bytes now on disk.
+    /// Returns the final size of `dest`.
+    ///
+    /// A partial `dest` left by an interrupted download is continued with a
+    /// Range request; if the server ignores the range the file is restarted.
+    pub fn download_file(
+        &self,
+        file: &OutputFile,
+        dest: &Path,
+        mut on_chunk: impl FnMut(&[u8], u64),
+    ) -> Result<u64> {
+        let existing = std::fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+        if file.size > 0 && existing == file.size {
+            return Ok(existing);
+        }
+        let resume_from = if existing < file.size { existing } else { 0 };
+
+        let mut request = self
+            .request(Method::GET, &file.download_uri)
+            .timeout(FILE_TRANSFER_TIMEOUT);
+        if resume_from > 0 {
+            request = request.header(RANGE, format!("bytes={}-", resume_from));
+        }
+        // The body is streamed after execute returns; keep counting it
+        let _transfer = activity::begin("download result file");
+        let mut response = execute(request, "download result file")
+            .with_context(|| format!("Failed to download {}", file.filename))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Failed to download {}: HTTP {}",
+                file.filename,
+                response.status()
+            );
+        }
+
+        let (mut out, mut written) =
+            if resume_from > 0 && response.status() == StatusCode::PARTIAL_CONTENT {
+                let out = std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(dest)
+                    .with_context(|| format!("Failed to open {}", dest.display()))?;
+                (out, resume_from)
+            } else {
+                let out = std::fs::File::create(dest)
+                    .with_context(|| format!("Failed to create {}", dest.display()))?;
+                (out, 0)
+            };
+
+        let mut chunk = MIN_DOWNLOAD_CHUNK;
+        let mut buffer = vec![0u8; chunk];
+        loop {
+            let started = std::time::Instant::now();
+            let n = read_chunk(&mut response, &mut buffer[..chunk])
+                .with_context(|| format!("Failed to read from {}", file.filename))?;
+            if n == 0 {
+                break;
+            }
+
+            out.write_all(&buffer[..n])
+                .with_context(|| format!("Failed to write to {}", dest.display()))?;
+            written += n as u64;
+            on_chunk(&buffer[..n], written);
+
+            // Timed including on_chunk, so a rate limit keeps chunks small
+            chunk = next_chunk(chunk, n, started.elapsed());
+            if buffer.len() < chunk {
+                buffer.resize(chunk, 0);
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Up to `length` bytes of a result file starting at `offset`, fetched
+    /// with a Range request. Fails rather than fetching the whole file when
+    /// the server ignores the range.
+    pub fn read_range(&self, file: &OutputFile, offset: u64, length: u64) -> Result<Vec<u8>> {
+        if length == 0 || offset >= file.size {
+            return Ok(Vec::new());
+        }
+        let last = (offset + length).min(file.size) - 1;
+
+        let request = self
+            .request(Method::GET, &file.download_uri)
+            .header(RANGE, format!("bytes={}-{}", offset, last));
+        let response = execute(request, "read result file")
+            .with_context(|| format!("Failed to read {}", file.filename))?;
+
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            anyhow::bail!(
+                "Failed to read part of {}: HTTP {} (the server may not support ranged reads)",
+                file.filename,
+                response.status()
+            );
+        }
+
+        let mut data = Vec::with_capacity((last - offset + 1) as usize);
+        response
+            .take(last - offset + 1)
+            .read_to_end(&mut data)
+            .with_context(|| format!("Failed to read from {}", file.filename))?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch;
+
+    const KEY: &str = "NSG_GUI-0123456789abcdef0123456789abcdef";
+
+    // A job listing as NSG sends it
+    fn job_list(seed: u64) -> String {
+        crate::fixtures::generate(seed).job_list_xml()
+    }
+
+    fn headers(pairs: &[(reqwest::header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn a_first_listing_takes_its_version_from_the_headers() {
+        let version = listing_version(
+            None,
+            StatusCode::OK,
+            &headers(&[
+                (ETAG, "\"v1\""),
+                (LAST_MODIFIED, "Tue, 13 Oct 2026 08:00:00 GMT"),
+            ]),
+            &job_list(1),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(version.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(
+            version.last_modified.as_deref(),
+            Some("Tue, 13 Oct 2026 08:00:00 GMT")
+        );
+    }
+
+    #[test]
+    fn not_modified_means_the_listing_is_unchanged() {
+        let previous = listing_version(
+            None,
+            StatusCode::OK,
+            &headers(&[(ETAG, "\"v1\"")]),
+            &job_list(1),
+        )
+        .unwrap()
+        .unwrap();
+        let answer = listing_version(
+            Some(&previous),
+            StatusCode::NOT_MODIFIED,
+            &headers(&[(ETAG, "\"v1\"")]),
+            "",
+        );
+        assert!(answer.unwrap().is_none());
+    }
+
+    #[test]
+    fn not_modified_without_a_conditional_request_is_an_error() {
+        assert!(listing_version(None, StatusCode::NOT_MODIFIED, &HeaderMap::new(), "").is_err());
+        assert!(listing_version(None, StatusCode::BAD_GATEWAY, &HeaderMap::new(), "").is_err());
+    }
+
+    #[test]
+    fn without_headers_an_identical_body_is_unchanged() {
+        let listing = job_list(1);
+        let previous = listing_version(None, StatusCode::OK, &HeaderMap::new(), &listing)
+            .unwrap()
+            .unwrap();
+        assert_eq!(previous.etag, None);
+        assert_eq!(previous.last_modified, None);
+
+        let same = listing_version(Some(&previous), StatusCode::OK, &HeaderMap::new(), &listing);
+        assert!(same.unwrap().is_none());
+
+        let changed = job_list(2);
+        let next = listing_version(Some(&previous), StatusCode::OK, &HeaderMap::new(), &changed)
+            .unwrap()
+            .unwrap();
+        assert_ne!(next.body_hash, previous.body_hash);
+    }
+
+    #[test]
+    fn the_body_hash_is_the_bodys_sha256() {
+        let version = listing_version(None, StatusCode::OK, &HeaderMap::new(), "abc")
+            .unwrap()
+            .unwrap();
+        let hex: String = version
+            .body_hash
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(
+            hex,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn a_well_formed_key_has_no_problems() {
+        assert!(app_key_problems(KEY, None).is_empty());
+        assert!(app_key_problems(KEY, Some("hunter2")).is_empty());
+        assert!(app_key_problems("key.with_dots-1234", Some("")).is_empty());
+    }
+
+    #[test]
+    fn an_empty_key_is_only_empty() {
+        assert_eq!(app_key_problems("", None), [AppKeyProblem::Empty]);
+        assert_eq!(app_key_problems(" \n", None), [AppKeyProblem::Empty]);
+    }
+
+    #[test]
+    fn paste_errors_are_named() {
+        assert_eq!(
+            app_key_problems(&format!(" {}\n", KEY), None),
+            [AppKeyProblem::Whitespace]
+        );
+        assert_eq!(
+            app_key_problems("https://nsgr.sdsc.edu/portal/appkey", None),
+            [AppKeyProblem::LooksLikeUrl]
+        );
+        assert_eq!(
+            app_key_problems("key!with$symbols-000", None),
+            [AppKeyProblem::InvalidCharacters]
+        );
+        assert_eq!(
+            app_key_problems(KEY, Some(KEY)),
+            [AppKeyProblem::SameAsPassword]
+        );
+    }
+
+    #[test]
+    fn implausible_lengths_are_problems() {
+        assert_eq!(app_key_problems("abc123", None), [AppKeyProblem::TooShort]);
+        assert_eq!(
+            app_key_problems(&"a".repeat(APP_KEY_MAX_LEN + 1), None),
+            [AppKeyProblem::TooLong]
+        );
+        assert!(app_key_problems(&"a".repeat(APP_KEY_MIN_LEN), None).is_empty());
+    }
+
+    #[test]
+    fn several_problems_are_all_reported() {
+        assert_eq!(
+            app_key_problems("www.x y", Some("www.x y")),
+            [
+                AppKeyProblem::Whitespace,
+                AppKeyProblem::LooksLikeUrl,
+                AppKeyProblem::TooShort,
+                AppKeyProblem::SameAsPassword,
+            ]
+        );
+    }
+
+    #[test]
+    fn the_error_message_names_the_field_when_it_can() {
+        assert_eq!(
+            culprit_of_message("<error><displayMessage>Invalid AppKey</displayMessage></error>"),
+            Some(CredentialCulprit::AppKey)
+        );
+        assert_eq!(
+            culprit_of_message("Incorrect user name or password"),
+            Some(CredentialCulprit::Password)
+        );
+        assert_eq!(culprit_of_message("<error>Unauthorized</error>"), None);
+        assert_eq!(culprit_of_message(""), None);
+    }
+
+    #[test]
+    fn a_rejected_login_blames_only_what_it_can_tell() {
+        use CredentialCulprit::*;
+        let credentials = |password: &str, app_key: &str| {
+            Credentials::new("user".into(), password.into(), app_key.into())
+        };
+        let generic = "<error><displayMessage>Authentication Error</displayMessage></error>";
+        let names_password =
+            "<error><displayMessage>Invalid user name or password</displayMessage></error>";
+        let names_key = "<error><displayMessage>Invalid application ID</displayMessage></error>";
+        let cases = [
+            // Bad password, good key
+            (credentials("wrong", KEY), names_password, Password),
+            (credentials("wrong", KEY), generic, Unknown),
+            // Bad key that looks fine: only the server can say so
+            (
+                credentials("secret", "NSG_GUI-ffffffffffffffffffffffffffffffff"),
+                names_key,
+                AppKey,
+            ),
+            (
+                credentials("secret", "NSG_GUI-ffffffffffffffffffffffffffffffff"),
+                generic,
+                Unknown,
+            ),
+            // Malformed key
+            (credentials("secret", "not a key"), generic, AppKey),
+            (credentials("secret", "not a key"), names_key, AppKey),
+            // Both bad
+            (credentials("wrong", "not a key"), generic, AppKey),
+            (
+                credentials("wrong", "NSG_GUI-ffffffffffffffffffffffffffffffff"),
+                generic,
+                Unknown,
+            ),
+            (
+                credentials("wrong", "NSG_GUI-ffffffffffffffffffffffffffffffff"),
+                names_key,
+                AppKey,
+            ),
+            (
+                credentials("wrong", "NSG_GUI-ffffffffffffffffffffffffffffffff"),
+                names_password,
+                Password,
+            ),
+        ];
+        for (credentials, body, expected) in cases {
+            assert_eq!(
+                rejection_culprit(body, &credentials),
+                expected,
+                "key {:?}, password {:?}, body {}",
+                credentials.app_key,
+                credentials.password,
+                body
+            );
+        }
+    }
+
+    // A results page with `files` and whatever paging `extra` adds
+    fn page(files: &[&str], extra: &str) -> String {
+        let files: String = files
+            .iter()
+            .map(|name| {
+                format!(
+                    "<jobfile><filename>{0}</filename><length>1</length>\
+                     <downloadUri><url>https://example.org/{0}</url></downloadUri></jobfile>",
+                    name
+                )
+            })
+            .collect();
+        format!("<results><jobfiles>{}</jobfiles>{}</results>", files, extra)
+    }
+
+    fn listing(pages: &[(&str, String)]) -> Result<Vec<String>> {
+        let pages: BTreeMap<&str, &String> = pages.iter().map(|(u, b)| (*u, b)).collect();
+        let (files, _) = follow_listing("p1", |url| {
+            pages
+                .get(url)
+                .map(|body| body.to_string())
+                .context("no such page")
+        })?;
+        Ok(files.into_iter().map(|f| f.filename).collect())
+    }
+
+    #[test]
+    fn a_fixture_results_listing_reads_back_whole() {
+        for job in crate::fixtures::generate(1).jobs {
+            let (files, _) = follow_listing(&job.results_url(), |_| Ok(job.results_xml())).unwrap();
+            let read: Vec<_> = files
+                .iter()
+                .map(|f| (f.filename.as_str(), f.size))
+                .collect();
+            let expected: Vec<_> = job
+                .results
+                .iter()
+                .map(|f| (f.filename.as_str(), f.size))
+                .collect();
+            assert_eq!(read, expected, "{}", job.job_id);
+        }
+    }
+
+    #[test]
+    fn pages_are_followed_to_the_end() {
+        let files = listing(&[
+            ("p1", page(&["a", "b"], "<next>p2</next><total>4</total>")),
+            ("p2", page(&["b", "c"], "<link rel=\"next\" href=\"p3\"/>")),
+            ("p3", page(&["d"], "")),
+        ])
+        .unwrap();
+        assert_eq!(files, ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn an_incomplete_listing_is_an_error() {
+        let short = listing(&[("p1", page(&["a"], "<total>3</total>"))]).unwrap_err();
+        assert!(
+            short.to_string().contains("reported 3 files but sent 1"),
+            "{}",
+            short
+        );
+
+        let cut = listing(&[("p1", page(&["a"], "<truncated>true</truncated>"))]).unwrap_err();
+        assert!(cut.to_string().contains("cut it short"), "{}", cut);
+
+        assert!(listing(&[("p1", page(&["a"], "<next>missing</next>"))]).is_err());
+    }
+
+    #[test]
+    fn a_next_link_seen_before_ends_the_listing() {
+        let files = listing(&[
+            ("p1", page(&["a"], "<next>p2</next>")),
+            ("p2", page(&["b"], "<next>p1</next>")),
+        ])
+        .unwrap();
+        assert_eq!(files, ["a", "b"]);
+    }
+
+    #[test]
+    fn an_endless_listing_stops_at_the_page_limit() {
+        let mut fetched = 0;
+        let error = follow_listing("p0", |url| {
+            fetched += 1;
+            let n: usize = url[1..].parse().unwrap();
+            Ok(page(&[url], &format!("<next>p{}</next>", n + 1)))
+        })
+        .unwrap_err();
+        assert_eq!(fetched, MAX_LISTING_PAGES);
+        assert!(error.to_string().contains("runs past"), "{}", error);
+    }
+
+    #[test]
+    fn chunks_grow_while_filled_quickly_up_to_the_maximum() {
+        let fast = std::time::Duration::from_millis(1);
+        let mut chunk = MIN_DOWNLOAD_CHUNK;
+        for _ in 0..20 {
+            chunk = next_chunk(chunk, chunk, fast);
+        }
+        assert_eq!(chunk, MAX_DOWNLOAD_CHUNK);
+        assert_eq!(
+            next_chunk(MIN_DOWNLOAD_CHUNK, MIN_DOWNLOAD_CHUNK, fast),
+            128 * 1024
+        );
+    }
+
+    #[test]
+    fn chunks_shrink_when_slow_down_to_the_minimum() {
+        let slow = std::time::Duration::from_secs(1);
+        let mut chunk = MAX_DOWNLOAD_CHUNK;
+        for _ in 0..20 {
+            chunk = next_chunk(chunk, chunk, slow);
+        }
+        assert_eq!(chunk, MIN_DOWNLOAD_CHUNK);
+    }
+
+    #[test]
+    fn a_short_or_steady_read_keeps_the_chunk() {
+        let chunk = 256 * 1024;
+        let fast = std::time::Duration::from_millis(1);
+        let steady = std::time::Duration::from_millis(100);
+        assert_eq!(next_chunk(chunk, chunk - 1, fast), chunk);
+        assert_eq!(next_chunk(chunk, chunk, steady), chunk);
+    }
+
+    #[test]
+    fn reading_a_chunk_fills_it_across_short_reads() {
+        // Hands out at most 3 bytes per read, like a socket with little data
+        struct Trickle(std::io::Cursor<Vec<u8>>);
+        impl Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(3);
+                self.0.read(&mut buf[..len])
+            }
+        }
+        let mut body = Trickle(std::io::Cursor::new((0..10).collect()));
+        let mut buffer = [0u8; 8];
+        assert_eq!(read_chunk(&mut body, &mut buffer).unwrap(), 8);
+        assert_eq!(buffer, [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(read_chunk(&mut body, &mut buffer).unwrap(), 2);
+        assert_eq!(read_chunk(&mut body, &mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn input_files_are_streamed_into_the_form() {
+        const LENGTH: u64 = 1 << 30;
+        const BUFFER: usize = 64 * 1024;
+
+        // Sparse, so the test costs no disk space
+        let dir = scratch("api", "streamed");
+        let path = dir.join("large.nex");
+        File::create(&path).unwrap().set_len(LENGTH).unwrap();
+
+        let sent = Arc::new(AtomicU64::new(0));
+        let progress: UploadProgress = {
+            let sent = sent.clone();
+            Arc::new(move |n| sent.store(n, Ordering::SeqCst))
+        };
+        let form = NsgApi::submission_form(
+            &[InputBinding::primary(path)],
+            "RAXMLHPC8_REST_XSEDE",
+            &BTreeMap::new(),
+            Some(&progress),
+        )
+        .unwrap();
+        // Building the form reads nothing
+        assert_eq!(sent.load(Ordering::SeqCst), 0);
+
+        // The file is only read as the body is, one buffer at a time
+        let mut body = form.into_reader();
+        let mut buf = vec![0; BUFFER];
+        let mut consumed = 0u64;
+        loop {
+            let n = body.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            consumed += n as u64;
+            assert!(sent.load(Ordering::SeqCst) <= consumed);
+        }
+        assert_eq!(sent.load(Ordering::SeqCst), LENGTH);
+        assert!(consumed > LENGTH);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+// Browsing result archives already on disk.
+//
+// Lets the download history act as a results browser after NSG has purged
+// the job: an archive written by download_results can be listed, its
+// job_info.json read, and single entries extracted. Entry names are
+// reduced to a bare file name on extraction, as for downloads, so a
+// crafted archive can't write outside the chosen folder. zip64 archives
+// are read transparently.
+
+use crate::download::{local_file_name, JobInfoManifest, JOB_INFO_ENTRY};
+use crate::paths;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub compressed_size: u64,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchiveContents {
+    pub entries: Vec<ArchiveEntry>,
+    pub has_job_info: bool,
+    // Parsed job_info.json; None when missing or unreadable
+    pub job_info: Option<JobInfoManifest>,
+}
+
+pub fn open_archive(path: &Path) -> Result<zip::ZipArchive<File>, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    if !metadata.is_file() {
+        return Err(format!("Not a file: {}", path.display()));
+    }
+    let file = File::open(path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    zip::ZipArchive::new(file).map_err(|e| format!("Failed to read archive: {}", e))
+}
+
+pub fn read_job_info(archive: &mut zip::ZipArchive<File>) -> Option<JobInfoManifest> {
+    let mut entry = archive.by_name(JOB_INFO_ENTRY).ok()?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// List an archive's entries along with its job_info.json, if any
+#[tauri::command]
+pub async fn list_archive_contents(
+    app: tauri::AppHandle,
+    path: PathBuf,
+) -> Result<ArchiveContents, String> {
+    let path = crate::path_access::authorize(&app, &path)?;
+    tokio::task::spawn_blocking(move || {
+        let mut archive = open_archive(&path)?;
+
+        let mut entries = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let entry = archive
+                .by_index_raw(i)
+                .map_err(|e| format!("Failed to read archive entry: {}", e))?;
+            entries.push(ArchiveEntry {
+                name: entry.name().to_string(),
+                compressed_size: entry.compressed_size(),
+                size: entry.size(),
+                is_dir: entry.is_dir(),
+            });
+        }
+
+        let has_job_info = entries.iter().any(|e| e.name == JOB_INFO_ENTRY);
+        let job_info = if has_job_info {
+            read_job_info(&mut archive)
+        } else {
+            None
+        };
+
+        Ok(ArchiveContents {
+            entries,
+            has_job_info,
+            job_info,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+}
+
+/// Extract one entry into `output_dir`, returning the path written. An
+/// existing file is never overwritten.
+#[tauri::command]
+pub async fn extract_archive_entry(
+    app: tauri::AppHandle,
+    path: PathBuf,
+    entry: String,
+    output_dir: PathBuf,
+) -> Result<String, String> {
+    let path = crate::path_access::authorize(&app, &path)?;
+    let output_dir = crate::path_access::authorize(&app, &output_dir)?;
+    tokio::task::spawn_blocking(move || {
+        let mut archive = open_archive(&path)?;
+        let mut source = archive
+            .by_name(&entry)
+            .map_err(|_| format!("Archive has no entry {}", entry))?;
+        if source.is_dir() {
+            return Err(format!("{} is a directory", entry));
+        }
+
+        let dest = output_dir.join(local_file_name(&entry)?);
+        std::fs::create_dir_all(&output_dir)
+            .map_err(|e| format!("Failed to create output dir: {}", e))?;
+        let mut out = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&dest)
+            .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+
+        if let Err(e) = std::io::copy(&mut source, &mut out) {
+            drop(out);
+            let _ = std::fs::remove_file(&dest);
+            return Err(format!("Failed to extract {}: {}", entry, e));
+        }
+
+        paths::to_ipc_string(&dest)
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+}
@@ -0,0 +1,383 @@
+// Cleaning up old downloaded archives.
+//
+// Off unless `auto_cleanup_enabled` is set. Only archives recorded in the
+// download history are considered, never anything else in the download
+// folder. An archive is a candidate when it was downloaded more than
+// `keep_archives_days` ago, or, oldest first, while the archives still on
+// disk add up to more than `keep_archives_max_gb`; either limit can be left
+// unset. Pinned history entries are always kept. Candidates are moved to
+// the OS trash, from where they can be restored, and never deleted
+// outright: an archive that can't be trashed is left alone and reported.
+// An archive the user already removed is only marked missing in the
+// history. The check runs every CLEANUP_INTERVAL and on run_archive_cleanup,
+// which can also just report what it would do. Every run emits
+// `archive-cleanup-report`, and runs that trashed or marked anything are
+// added to the audit log.
+
+use crate::anonymize::{self, anonymize_job_id, Anonymize};
+use crate::background_errors::{self, ErrorKind};
+use crate::history::{self, DownloadHistoryEntry};
+use crate::i18n::Msg;
+use crate::preferences;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+pub const ENABLED_KEY: &str = "auto_cleanup_enabled";
+pub const KEEP_DAYS_KEY: &str = "keep_archives_days";
+pub const MAX_GB_KEY: &str = "keep_archives_max_gb";
+
+// Name of the scheduled cleanup in background errors
+const SOURCE: &str = "archive_cleanup";
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+// Give the app time to settle before the first scheduled run
+const FIRST_RUN_DELAY: Duration = Duration::from_secs(5 * 60);
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveCleanupSettings {
+    pub enabled: bool,
+    // None keeps archives of any age
+    pub keep_days: Option<u64>,
+    // None keeps archives of any total size
+    pub max_gb: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupReason {
+    Age,
+    OverBudget,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupCandidate {
+    pub path: String,
+    // Every history entry recording this archive
+    pub entry_ids: Vec<String>,
+    pub job_ids: Vec<String>,
+    pub downloaded_at: String,
+    pub bytes: u64,
+    pub reason: CleanupReason,
+    pub trashed: bool,
+    // Why it couldn't be moved to the trash
+    pub error: Option<String>,
+}
+
+/// Payload of `archive-cleanup-report`, and what run_archive_cleanup
+/// returns
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveCleanupReport {
+    pub dry_run: bool,
+    // False for the periodic run
+    pub manual: bool,
+    pub ran_at: String,
+    pub settings: ArchiveCleanupSettings,
+    // Archives still on disk before the run
+    pub archives: usize,
+    pub total_bytes: u64,
+    pub candidates: Vec<CleanupCandidate>,
+    // Actually moved to the trash; 0 for a dry run
+    pub freed_bytes: u64,
+    // Archives already gone, now marked missing
+    pub marked_missing: Vec<String>,
+    // Kept only because they are pinned
+    pub pinned_exempt: usize,
+}
+
+impl Anonymize for CleanupCandidate {
+    fn anonymize(self) -> Self {
+        Self {
+            job_ids: self.job_ids.iter().map(|id| anonymize_job_id(id)).collect(),
+            ..self
+        }
+    }
+}
+
+impl Anonymize for ArchiveCleanupReport {
+    fn anonymize(self) -> Self {
+        Self {
+            candidates: self.candidates.anonymize(),
+            ..self
+        }
+    }
+}
+
+/// The cleanup preferences; a limit that is unset or 0 is none
+pub fn settings(app: &tauri::AppHandle) -> ArchiveCleanupSettings {
+    let prefs = preferences::of(app);
+    ArchiveCleanupSettings {
+        enabled: prefs
+            .get(ENABLED_KEY)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        keep_days: prefs
+            .get(KEEP_DAYS_KEY)
+            .and_then(|v| v.as_u64())
+            .filter(|d| *d > 0),
+        max_gb: prefs
+            .get(MAX_GB_KEY)
+            .and_then(|v| v.as_f64())
+            .filter(|gb| *gb > 0.0),
+    }
+}
+
+/// One archive on disk and the history entries recording it
+struct Archive {
+    path: String,
+    entries: Vec<DownloadHistoryEntry>,
+    bytes: u64,
+    // Of the newest entry, so a re-download counts from then
+    downloaded_at: chrono::DateTime<chrono::Utc>,
+    pinned: bool,
+}
+
+/// Group the entries by archive, oldest first, splitting off the paths
+/// that are no longer there
+fn archives(entries: Vec<DownloadHistoryEntry>) -> (Vec<Archive>, Vec<String>) {
+    let mut by_path: BTreeMap<String, Vec<DownloadHistoryEntry>> = BTreeMap::new();
+    for entry in entries.into_iter().filter(|e| e.has_archive()) {
+        by_path
+            .entry(entry.archive_path.clone())
+            .or_default()
+            .push(entry);
+    }
+
+    let mut archives = Vec::new();
+    let mut missing = Vec::new();
+    for (path, entries) in by_path {
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                missing.push(path);
+                continue;
+            }
+            // Unreadable or not a file: not ours to judge
+            _ => continue,
+        };
+        let downloaded_at = entries
+            .iter()
+            .filter_map(|e| chrono::DateTime::parse_from_rfc3339(&e.downloaded_at).ok())
+            .map(|d| d.with_timezone(&chrono::Utc))
+            .max();
+        let Some(downloaded_at) = downloaded_at else {
+            continue;
+        };
+        archives.push(Archive {
+            pinned: entries.iter().any(|e| e.pinned),
+            path,
+            entries,
+            bytes: metadata.len(),
+            downloaded_at,
+        });
+    }
+    archives.sort_by_key(|a| a.downloaded_at);
+    (archives, missing)
+}
+
+/// The archives to remove and why, oldest first, and how many were kept
+/// only for being pinned
+fn select(
+    archives: &[Archive],
+    settings: &ArchiveCleanupSettings,
+    now: chrono::DateTime<chrono::Utc>,
+) -> (Vec<(usize, CleanupReason)>, usize) {
+    let mut selected = Vec::new();
+    let mut pinned_exempt = 0;
+    // Too many days to subtract is no age limit at all
+    let cutoff = settings
+        .keep_days
+        .and_then(|days| chrono::Duration::try_days(i64::try_from(days).ok()?))
+        .and_then(|keep| now.checked_sub_signed(keep));
+    let budget = settings.max_gb.map(|gb| (gb * BYTES_PER_GB) as u64);
+    let mut remaining: u64 = archives.iter().map(|a| a.bytes).sum();
+
+    for (i, archive) in archives.iter().enumerate() {
+        let reason = if cutoff.is_some_and(|cutoff| archive.downloaded_at < cutoff) {
+            Some(CleanupReason::Age)
+        } else if budget.is_some_and(|budget| remaining > budget) {
+            Some(CleanupReason::OverBudget)
+        } else {
+            None
+        };
+        let Some(reason) = reason else {
+            continue;
+        };
+        if archive.pinned {
+            pinned_exempt += 1;
+            continue;
+        }
+        remaining = remaining.saturating_sub(archive.bytes);
+        selected.push((i, reason));
+    }
+    (selected, pinned_exempt)
+}
+
+fn move_to_os_trash(path: &Path) -> Result<(), String> {
+    trash::delete(path).map_err(|e| e.to_string())
+}
+
+/// Look through the history and, unless `dry_run`, trash the candidates
+fn run(
+    app: &tauri::AppHandle,
+    dry_run: bool,
+    manual: bool,
+) -> Result<ArchiveCleanupReport, String> {
+    let settings = settings(app);
+    let now = crate::clock::now();
+    let (archives, missing) = archives(history::load(app)?);
+    let (selected, pinned_exempt) = select(&archives, &settings, now);
+
+    let mut candidates = Vec::new();
+    let mut trashed = Vec::new();
+    let mut freed_bytes = 0;
+    for (i, reason) in selected {
+        let archive = &archives[i];
+        let mut candidate = CleanupCandidate {
+            path: archive.path.clone(),
+            entry_ids: archive.entries.iter().map(|e| e.id.clone()).collect(),
+            job_ids: archive.entries.iter().map(|e| e.job_id.clone()).collect(),
+            downloaded_at: archive.downloaded_at.to_rfc3339(),
+            bytes: archive.bytes,
+            reason,
+            trashed: false,
+            error: None,
+        };
+        if !dry_run {
+            let path = Path::new(&archive.path);
+            match move_to_os_trash(path) {
+                Ok(()) if path.exists() => {
+                    candidate.error = Some("It is still there after moving it to the trash".into())
+                }
+                Ok(()) => {
+                    candidate.trashed = true;
+                    freed_bytes += archive.bytes;
+                    trashed.push(archive.path.clone());
+                }
+                Err(e) => candidate.error = Some(format!("Failed to move it to the trash: {}", e)),
+            }
+        }
+        candidates.push(candidate);
+    }
+
+    let ran_at = now.to_rfc3339();
+    if !dry_run {
+        history::mark_archives(app, &missing, &trashed, &ran_at)?;
+    }
+    let report = ArchiveCleanupReport {
+        dry_run,
+        manual,
+        ran_at,
+        settings,
+        archives: archives.len(),
+        total_bytes: archives.iter().map(|a| a.bytes).sum(),
+        candidates,
+        freed_bytes,
+        marked_missing: if dry_run { Vec::new() } else { missing },
+        pinned_exempt,
+    };
+    if !dry_run && (!trashed.is_empty() || !report.marked_missing.is_empty()) {
+        crate::audit_log::append(
+            app,
+            "archive_cleanup",
+            json!({
+                "manual": manual,
+                "trashed": report.candidates.iter().filter(|c| c.trashed).map(|c| json!({
+                    "path": c.path,
+                    "bytes": c.bytes,
+                    "reason": c.reason,
+                    "job_ids": c.job_ids,
+                })).collect::<Vec<_>>(),
+                "failed": report.candidates.iter().filter(|c| c.error.is_some()).map(|c| &c.path).collect::<Vec<_>>(),
+                "marked_missing": report.marked_missing,
+                "freed_bytes": freed_bytes,
+            }),
+        );
+    }
+    anonymize::emit(app, crate::events::ARCHIVE_CLEANUP_REPORT, report.clone());
+    Ok(report)
+}
+
+/// Run the cleanup every CLEANUP_INTERVAL while it is enabled. Called once
+/// at startup.
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(FIRST_RUN_DELAY).await;
+        loop {
+            let enabled = settings(&app).enabled
+                && !crate::mode::is_read_only(&app)
+                && !crate::accounts::is_mismatched();
+            if enabled {
+                let run_app = app.clone();
+                let error =
+                    match tokio::task::spawn_blocking(move || run(&run_app, false, false)).await {
+                        Ok(Ok(_)) => None,
+                        Ok(Err(e)) => Some(e),
+                        Err(e) => Some(format!("Task error: {}", e)),
+                    };
+                match error {
+                    None => background_errors::resolved(&app, SOURCE),
+                    Some(e) => background_errors::report(
+                        &app,
+                        SOURCE,
+                        ErrorKind::Other,
+                        format!("Archive cleanup failed: {}", e),
+                    ),
+                }
+            }
+            tokio::time::sleep(CLEANUP_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_archive_cleanup_settings(
+    app: tauri::AppHandle,
+) -> Result<ArchiveCleanupSettings, String> {
+    Ok(settings(&app))
+}
+
+#[tauri::command]
+pub async fn set_archive_cleanup_settings(
+    app: tauri::AppHandle,
+    settings: ArchiveCleanupSettings,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+    if settings
+        .max_gb
+        .is_some_and(|gb| !gb.is_finite() || gb <= 0.0)
+    {
+        return Err("The size budget must be a positive number of GB".to_string());
+    }
+    if settings.keep_days == Some(0) {
+        return Err("Archives must be kept for at least one day".to_string());
+    }
+    let prefs = preferences::of(&app);
+    // 0 stands for no limit
+    prefs.set_many([
+        (ENABLED_KEY, json!(settings.enabled)),
+        (KEEP_DAYS_KEY, json!(settings.keep_days.unwrap_or(0))),
+        (MAX_GB_KEY, json!(settings.max_gb.unwrap_or(0.0))),
+    ]);
+    Ok(())
+}
+
+/// Clean up now, whether or not the periodic cleanup is enabled. With
+/// `dry_run` nothing is moved or marked; the report lists what would be.
+#[tauri::command]
+pub async fn run_archive_cleanup(
+    app: tauri::AppHandle,
+    dry_run: bool,
+) -> Result<ArchiveCleanupReport, String> {
+    if !dry_run {
+        crate::mode::ensure_writable(&app, Msg::ActionCleanUpArchives)?;
+    }
+    let run_app = app.clone();
+    let report = tokio::task::spawn_blocking(move || run(&run_app, dry_run, true))
+        .await
+        .map_err(|e| format!("Task error: {}", e))??;
+    Ok(anonymize::outgoing(&app, report))
+}
@@ -0,0 +1,77 @@
+// Audit log of what the app did to the user's files on its own.
+//
+// One JSON object per line in audit.jsonl in the account's folder: when,
+// what, and the details of it. Lines are only appended; once the file
+// passes MAX_BYTES it is renamed to audit.jsonl.old, replacing the one
+// before, and a new file is started. Writing is best effort and never
+// fails the action being logged.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const AUDIT_FILE: &str = "audit.jsonl";
+const MAX_BYTES: u64 = 1024 * 1024;
+
+// Appends from different threads don't interleave
+static WRITE: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize)]
+struct AuditLine<'a> {
+    at: String,
+    action: &'a str,
+    details: Value,
+}
+
+fn audit_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::accounts::user_root(app)?.join(AUDIT_FILE))
+}
+
+fn old_path(path: &std::path::Path) -> PathBuf {
+    path.with_extension("jsonl.old")
+}
+
+fn write(app: &tauri::AppHandle, action: &str, details: Value) -> Result<(), String> {
+    let path = audit_path(app)?;
+    let line = serde_json::to_string(&AuditLine {
+        at: crate::clock::now().to_rfc3339(),
+        action,
+        details,
+    })
+    .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+
+    let _guard = WRITE.lock().unwrap();
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_BYTES) {
+        std::fs::rename(&path, old_path(&path))
+            .map_err(|e| format!("Failed to rotate the audit log: {}", e))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open the audit log: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write the audit log: {}", e))
+}
+
+/// Append `action` with its details
+pub fn append(app: &tauri::AppHandle, action: &str, details: Value) {
+    if let Err(e) = write(app, action, details) {
+        crate::background_errors::report(
+            app,
+            "audit_log",
+            crate::background_errors::ErrorKind::Other,
+            e,
+        );
+    }
+}
+
+/// Delete the audit log and its rotated copy, returning whether there was
+/// either
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = audit_path(app)?;
+    let _guard = WRITE.lock().unwrap();
+    let old = crate::paths::remove_file_if_exists(&old_path(&path))?;
+    Ok(crate::paths::remove_file_if_exists(&path)? || old)
+}
@@ -0,0 +1,208 @@
+// Errors of background work.
+//
+// When the network drops, every background task fails on every cycle.
+// Instead of each one surfacing its own error, they report here, keyed by
+// source and kind. A key goes out as `background-error` at most once per
+// EMIT_INTERVAL, carrying how often it happened since it was first seen.
+// An occurrence more than WINDOW after the previous one starts a new
+// count. When a source succeeds again its errors are dropped and
+// `background-error-resolved` is emitted for each. Commands the user runs
+// keep returning their errors directly, and nothing here touches them.
+
+use crate::activity_feed::{Activity, ActivityKind, ActivityStatus};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::Emitter;
+
+const EMIT_INTERVAL: Duration = Duration::from_secs(30);
+const WINDOW: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    // The server couldn't be reached
+    Network,
+    // The server refused the credentials
+    Auth,
+    // The server answered with an error of its own
+    Server,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn of(e: &anyhow::Error) -> Self {
+        if crate::api::is_network_error(e) {
+            return ErrorKind::Network;
+        }
+        Self::of_message(&format!("{:#}", e))
+    }
+
+    pub fn of_message(message: &str) -> Self {
+        if message.contains("HTTP 401") || message.contains("HTTP 403") {
+            ErrorKind::Auth
+        } else if message.contains("HTTP 5") {
+            ErrorKind::Server
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+/// Payload of `background-error`, and one entry of
+/// get_active_background_errors
+#[derive(Debug, Clone, Serialize)]
+pub struct BackgroundError {
+    // Which background task failed, e.g. "job_monitor"
+    pub source: &'static str,
+    pub kind: ErrorKind,
+    // The latest message
+    pub message: String,
+    // Occurrences since first_seen
+    pub count: u64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BackgroundErrorResolved {
+    source: &'static str,
+    kind: ErrorKind,
+}
+
+struct Tracked {
+    error: BackgroundError,
+    last_seen: chrono::DateTime<chrono::Utc>,
+    last_emitted: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+static ACTIVE: Mutex<BTreeMap<(&'static str, ErrorKind), Tracked>> = Mutex::new(BTreeMap::new());
+
+fn chrono_duration(d: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(d).unwrap_or_default()
+}
+
+/// Record a failure of a background task, emitting it unless the same
+/// kind from the same source went out within EMIT_INTERVAL
+pub fn report(app: &tauri::AppHandle, source: &'static str, kind: ErrorKind, message: String) {
+    let now = crate::clock::now();
+    let stamp = now.to_rfc3339();
+
+    let emit = {
+        let mut active = ACTIVE.lock().unwrap();
+        let tracked = active.entry((source, kind)).or_insert_with(|| Tracked {
+            error: BackgroundError {
+                source,
+                kind,
+                message: message.clone(),
+                count: 0,
+                first_seen: stamp.clone(),
+                last_seen: stamp.clone(),
+            },
+            last_seen: now,
+            last_emitted: None,
+        });
+        if now - tracked.last_seen > chrono_duration(WINDOW) {
+            tracked.error.count = 0;
+            tracked.error.first_seen = stamp.clone();
+        }
+        tracked.error.count += 1;
+        tracked.error.message = message;
+        tracked.error.last_seen = stamp;
+        tracked.last_seen = now;
+
+        let due = tracked
+            .last_emitted
+            .is_none_or(|t| now - t >= chrono_duration(EMIT_INTERVAL));
+        if due {
+            tracked.last_emitted = Some(now);
+        }
+        due.then(|| tracked.error.clone())
+    };
+
+    if let Some(error) = emit {
+        crate::activity_feed::record(
+            app,
+            Some(activity_key(source)),
+            Activity::new(
+                ActivityKind::BackgroundError,
+                ActivityStatus::Failed,
+                format!("Background task {} is failing", source),
+            )
+            .detail(error.message.clone()),
+        );
+        let _ = app.emit(crate::events::BACKGROUND_ERROR, error);
+    }
+}
+
+/// Activity feed key of a source's errors
+fn activity_key(source: &str) -> String {
+    format!("error:{}", source)
+}
+
+/// Report a failure, classifying the error
+pub fn report_error(app: &tauri::AppHandle, source: &'static str, e: &anyhow::Error) {
+    report(app, source, ErrorKind::of(e), format!("{:#}", e));
+}
+
+/// A background task succeeded: its errors are over
+pub fn resolved(app: &tauri::AppHandle, source: &'static str) {
+    let cleared: Vec<ErrorKind> = {
+        let mut active = ACTIVE.lock().unwrap();
+        let kinds: Vec<ErrorKind> = active
+            .keys()
+            .filter(|(s, _)| *s == source)
+            .map(|(_, kind)| *kind)
+            .collect();
+        for kind in &kinds {
+            active.remove(&(source, *kind));
+        }
+        kinds
+    };
+
+    if !cleared.is_empty() {
+        crate::activity_feed::finish(
+            app,
+            &activity_key(source),
+            Activity::new(
+                ActivityKind::BackgroundError,
+                ActivityStatus::Succeeded,
+                format!("Background task {} recovered", source),
+            ),
+        );
+    }
+    for kind in cleared {
+        let _ = app.emit(
+            crate::events::BACKGROUND_ERROR_RESOLVED,
+            BackgroundErrorResolved { source, kind },
+        );
+    }
+}
+
+/// Resolve every error, e.g. after the session changed
+pub fn resolve_all(app: &tauri::AppHandle) {
+    let cleared: Vec<(&'static str, ErrorKind)> = std::mem::take(&mut *ACTIVE.lock().unwrap())
+        .into_keys()
+        .collect();
+    for (source, kind) in cleared {
+        let _ = app.emit(
+            crate::events::BACKGROUND_ERROR_RESOLVED,
+            BackgroundErrorResolved { source, kind },
+        );
+    }
+}
+
+/// Errors seen within WINDOW that haven't been resolved, for a window that
+/// opens after they were emitted
+#[tauri::command]
+pub async fn get_active_background_errors() -> Result<Vec<BackgroundError>, String> {
+    let now = crate::clock::now();
+    Ok(ACTIVE
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|t| now - t.last_seen <= chrono_duration(WINDOW))
+        .map(|t| t.error.clone())
+        .collect())
+}
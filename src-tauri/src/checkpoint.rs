@@ -0,0 +1,284 @@
+// Fetching the newest file that matches a pattern.
+//
+// Long NEURON runs write periodic checkpoints into their working directory.
+// `download_latest_matching` lists the working directory or the results,
+// keeps the files matching a glob, and downloads the one with the latest
+// modification time; listings without times fall back to the greatest
+// name, which works for numbered checkpoints. No match yet is a result, not
+// an error, so the UI can poll. The `checkpoint_patterns` preference holds
+// a default pattern per tool, used when the call doesn't give one.
+
+use crate::anonymize::{self, anonymize_job_id, real_url, Anonymize};
+use crate::api::NsgApi;
+use crate::download::{local_file_name, DownloadProgress};
+use crate::i18n::Msg;
+use crate::monitor::parse_date;
+use crate::{patterns, preferences, progress, throttle, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Instant;
+use tauri::State;
+
+pub const PATTERNS_KEY: &str = "checkpoint_patterns";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListingSource {
+    // Files of a running job
+    WorkingDir,
+    Results,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LatestMatch {
+    Downloaded {
+        job_id: String,
+        path: String,
+        filename: String,
+        size: u64,
+        // RFC 3339; None when the listing has no times
+        modified: Option<String>,
+        source: ListingSource,
+        // How many files matched, the downloaded one included
+        matched: usize,
+    },
+    // Nothing matches yet; poll again later
+    NoMatch {
+        job_id: String,
+        pattern: String,
+        source: ListingSource,
+        // Set when the source can't be listed yet, e.g. results of a
+        // running job
+        reason: Option<String>,
+    },
+}
+
+impl Anonymize for LatestMatch {
+    fn anonymize(self) -> Self {
+        match self {
+            LatestMatch::Downloaded {
+                job_id,
+                path,
+                filename,
+                size,
+                modified,
+                source,
+                matched,
+            } => LatestMatch::Downloaded {
+                job_id: anonymize_job_id(&job_id),
+                path,
+                filename,
+                size,
+                modified,
+                source,
+                matched,
+            },
+            LatestMatch::NoMatch {
+                job_id,
+                pattern,
+                source,
+                reason,
+            } => LatestMatch::NoMatch {
+                job_id: anonymize_job_id(&job_id),
+                pattern,
+                source,
+                reason,
+            },
+        }
+    }
+}
+
+fn load(app: &tauri::AppHandle) -> BTreeMap<String, String> {
+    preferences::of(app)
+        .get(PATTERNS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn latest_matching(
+    app: &tauri::AppHandle,
+    api: &NsgApi,
+    job_url: &str,
+    pattern: Option<String>,
+    source: ListingSource,
+    output_dir: Option<PathBuf>,
+) -> Result<LatestMatch, String> {
+    let record = api
+        .job_record(job_url)
+        .map_err(|e| format!("Failed to get job status: {}", e))?;
+    let job_id = record.status.job_id.clone();
+
+    let pattern = pattern
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .or_else(|| {
+            let tool = record.status.tool_id.as_deref()?;
+            load(app).get(tool).cloned()
+        })
+        .ok_or("No pattern given and the job's tool has no default checkpoint pattern")?;
+    let (_, matcher) = patterns::compile(std::slice::from_ref(&pattern))?
+        .pop()
+        .ok_or("Empty pattern")?;
+
+    let no_match = |reason: Option<String>| LatestMatch::NoMatch {
+        job_id: job_id.clone(),
+        pattern: pattern.clone(),
+        source,
+        reason,
+    };
+
+    let url = match source {
+        ListingSource::WorkingDir => record.extras.working_dir_uri.clone(),
+        ListingSource::Results => record.status.results_uri.clone(),
+    };
+    let Some(url) = url else {
+        let reason = match source {
+            ListingSource::WorkingDir => "The job has no working directory (it isn't running)",
+            ListingSource::Results => "The job has no results yet",
+        };
+        return Ok(no_match(Some(reason.to_string())));
+    };
+    let (files, dates) = api
+        .dated_listing(&url, "list job files")
+        .map_err(|e| e.to_string())?;
+
+    let matching: Vec<_> = files
+        .into_iter()
+        .filter(|f| matcher.is_match(f.filename.replace('\\', "/")))
+        .collect();
+    let matched = matching.len();
+    // A dated file beats an undated one; then the name decides
+    let Some(file) = matching.into_iter().max_by_key(|f| {
+        (
+            parse_date(dates.get(&f.filename).map(|d| d.as_str())),
+            f.filename.clone(),
+        )
+    }) else {
+        return Ok(no_match(None));
+    };
+
+    let output_dir = match output_dir {
+        Some(dir) => dir,
+        None => crate::default_download_dir(app)?,
+    };
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| format!("Failed to create output dir: {}", e))?;
+    let name = local_file_name(&file.filename)?;
+    let dest = output_dir.join(&name);
+    // Checkpoints are rewritten under the same name, so a leftover partial
+    // file must not be resumed
+    let part = output_dir.join(format!("{}.part", name));
+    let _ = std::fs::remove_file(&part);
+
+    let started = Instant::now();
+    let mut fetched = 0u64;
+    let gate = progress::Gate::new();
+    // The newest update the gate held back, sent once the file is done
+    let mut held = None;
+    let size = api
+        .download_file(&file, &part, |chunk, downloaded| {
+            throttle::acquire(chunk.len());
+            fetched += chunk.len() as u64;
+            let elapsed = started.elapsed().as_secs_f64();
+            let speed = if elapsed > 0.0 {
+                (fetched as f64 / elapsed) as u64
+            } else {
+                0
+            };
+            let update = DownloadProgress {
+                job_id: job_id.clone(),
+                filename: file.filename.clone(),
+                downloaded,
+                total: file.size,
+                speed,
+            };
+            if gate.due(file.size > 0 && downloaded >= file.size) {
+                held = None;
+                anonymize::emit(app, crate::events::DOWNLOAD_PROGRESS, update);
+            } else {
+                held = Some(update);
+            }
+        })
+        .map_err(|e| format!("Failed to download {}: {}", file.filename, e))?;
+    if let Some(update) = held {
+        anonymize::emit(app, crate::events::DOWNLOAD_PROGRESS, update);
+    }
+    std::fs::rename(&part, &dest)
+        .map_err(|e| format!("Failed to move {} into place: {}", name, e))?;
+
+    Ok(LatestMatch::Downloaded {
+        job_id: job_id.clone(),
+        path: crate::paths::to_ipc_string(&dest)?,
+        modified: dates.get(&file.filename).cloned(),
+        filename: file.filename,
+        size,
+        source,
+        matched,
+    })
+}
+
+/// Download the newest file matching `pattern` (or the tool's default
+/// checkpoint pattern) from the job's working directory or results
+#[tauri::command]
+pub async fn download_latest_matching(
+    job_url: String,
+    pattern: Option<String>,
+    source: ListingSource,
+    output_dir: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<LatestMatch, String> {
+    let session = state.session.require()?;
+    let output_dir = match output_dir {
+        Some(dir) => Some(crate::path_access::authorize_str(&app, &dir)?),
+        None => None,
+    };
+
+    let api = state.client.api(session.credentials).await?;
+    let url = real_url(&job_url);
+    let task_app = app.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        latest_matching(&task_app, &api, &url, pattern, source, output_dir)
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))??;
+    state.session.ensure_current(session.generation)?;
+    Ok(anonymize::outgoing(&app, result))
+}
+
+/// Default checkpoint pattern of each tool
+#[tauri::command]
+pub async fn get_checkpoint_patterns(
+    app: tauri::AppHandle,
+) -> Result<BTreeMap<String, String>, String> {
+    Ok(load(&app))
+}
+
+/// Set a tool's default checkpoint pattern; None or empty removes it
+#[tauri::command]
+pub async fn set_checkpoint_pattern(
+    app: tauri::AppHandle,
+    tool: String,
+    pattern: Option<String>,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+
+    let mut defaults = load(&app);
+    match pattern
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+    {
+        Some(pattern) => {
+            patterns::compile(std::slice::from_ref(&pattern))?;
+            defaults.insert(tool, pattern);
+        }
+        None => {
+            defaults.remove(&tool);
+        }
+    }
+    preferences::of(&app).set(PATTERNS_KEY, json!(defaults));
+    Ok(())
+}
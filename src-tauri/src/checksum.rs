@@ -0,0 +1,163 @@
+// SHA-256 checksums for result archives.
+//
+// Hashes are computed on the data as it is copied into the zip, so they
+// cost no extra read pass. They are stored as a `checksums.sha256` entry in
+// the usual `sha256sum` format and repeated in job_info.json.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+
+/// Name of the checksum entry stored in every archive
+pub const CHECKSUMS_ENTRY: &str = "checksums.sha256";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hex SHA-256 of a file's contents
+pub fn file_sha256(path: &std::path::Path) -> Result<String, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    copy_hashed(&mut file, &mut std::io::sink())
+        .map(|(_, hash)| hash)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Copy `reader` to `writer`, returning the number of bytes and their hex
+/// SHA-256
+pub fn copy_hashed(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+) -> std::io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut total = 0u64;
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        writer.write_all(&buffer[..n])?;
+        total += n as u64;
+    }
+    Ok((total, to_hex(&hasher.finalize())))
+}
+
+/// Render checksums as `<hash>  <name>` lines
+pub fn format_manifest(checksums: &BTreeMap<String, String>) -> String {
+    checksums
+        .iter()
+        .map(|(name, hash)| format!("{}  {}\n", hash, name))
+        .collect()
+}
+
+/// Parse `sha256sum` output, accepting the binary-mode `*name` marker
+fn parse_manifest(content: &str) -> BTreeMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (hash, name) = line.split_once(char::is_whitespace)?;
+            let name = name.trim_start();
+            let name = name.strip_prefix('*').unwrap_or(name);
+            if hash.len() != 64 || name.is_empty() {
+                return None;
+            }
+            Some((name.to_string(), hash.to_lowercase()))
+        })
+        .collect()
+}
+
+/// The checksum manifest stored in an archive
+pub fn read_manifest(
+    archive: &mut zip::ZipArchive<File>,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut entry = archive
+        .by_name(CHECKSUMS_ENTRY)
+        .map_err(|_| format!("Archive has no {} entry", CHECKSUMS_ENTRY))?;
+    let mut content = String::new();
+    entry
+        .read_to_string(&mut content)
+        .map_err(|e| format!("Failed to read {}: {}", CHECKSUMS_ENTRY, e))?;
+    Ok(parse_manifest(&content))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveVerification {
+    pub matched: Vec<String>,
+    pub mismatched: Vec<String>,
+    // Listed in the checksum manifest but not in the archive
+    pub missing: Vec<String>,
+    // In the archive but not covered by the manifest
+    pub unlisted: Vec<String>,
+    pub ok: bool,
+}
+
+/// Recompute the hashes of an archive produced by download_results and
+/// compare them with its checksum manifest
+#[tauri::command]
+pub async fn verify_archive(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<ArchiveVerification, String> {
+    let path = crate::path_access::authorize_str(&app, &path)?;
+    tokio::task::spawn_blocking(move || {
+        let file = File::open(&path).map_err(|e| format!("Failed to open archive: {}", e))?;
+        let mut archive =
+            zip::ZipArchive::new(file).map_err(|e| format!("Failed to read archive: {}", e))?;
+
+        let expected = read_manifest(&mut archive)?;
+
+        let mut matched = Vec::new();
+        let mut mismatched = Vec::new();
+        let mut unlisted = Vec::new();
+        let mut seen = Vec::new();
+
+        for i in 0..archive.len() {
+            let mut entry = archive
+                .by_index(i)
+                .map_err(|e| format!("Failed to read archive entry: {}", e))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name().to_string();
+            if name == CHECKSUMS_ENTRY || name == crate::download::JOB_INFO_ENTRY {
+                continue;
+            }
+
+            let Some(expected_hash) = expected.get(&name) else {
+                unlisted.push(name);
+                continue;
+            };
+
+            let (_, hash) = copy_hashed(&mut entry, &mut std::io::sink())
+                .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            if &hash == expected_hash {
+                matched.push(name.clone());
+            } else {
+                mismatched.push(name.clone());
+            }
+            seen.push(name);
+        }
+
+        let missing: Vec<String> = expected
+            .keys()
+            .filter(|name| !seen.contains(name))
+            .cloned()
+            .collect();
+
+        let ok = mismatched.is_empty() && missing.is_empty();
+        Ok(ArchiveVerification {
+            matched,
+            mismatched,
+            missing,
+            unlisted,
+            ok,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+}
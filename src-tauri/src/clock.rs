@@ -0,0 +1,344 @@
+// Local clock correction.
+//
+// Durations and relative times ("submitted 5 minutes ago", "expires in 2
+// days") are computed against the local clock, which may be off. Every NSG
+// response carries a Date header; the offset between it and the local time
+// is sampled from normal responses, never from extra requests. Once the
+// median of the recent samples is beyond SKEW_THRESHOLD_SECS, it's applied
+// by `now`, and `clock-skew-detected` is emitted once with the offset.
+//
+// Timestamps from the server are read by `parse_server_time` into UTC
+// instants, and all arithmetic is done on those; local time is for display
+// only, in the frontend. NSG normally sends an offset, but a timestamp
+// without one is taken as the server's own time in San Diego, Pacific time
+// with US daylight saving, rather than as UTC or the user's time zone. A
+// leap second (23:59:60) parses as the last second of the minute. Durations
+// between two timestamps go through `between`, which never goes negative.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use tauri::Emitter;
+
+// Smaller offsets are within what network latency and the header's
+// one-second resolution explain
+const SKEW_THRESHOLD_SECS: i64 = 60;
+// Samples kept; at least MIN_SAMPLES are needed before any offset is used
+const MAX_SAMPLES: usize = 7;
+const MIN_SAMPLES: usize = 3;
+
+struct Skew {
+    // Server minus local time, in seconds, oldest first
+    samples: VecDeque<i64>,
+    // Offset applied by now(); zero while within the threshold
+    applied: i64,
+    reported: bool,
+}
+
+static SKEW: Mutex<Skew> = Mutex::new(Skew {
+    samples: VecDeque::new(),
+    applied: 0,
+    reported: false,
+});
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockSkew {
+    // Seconds to add to the local clock to get the server's; positive when
+    // the local clock is slow
+    pub offset_secs: i64,
+    pub applied: bool,
+    pub samples: usize,
+}
+
+fn median(samples: &VecDeque<i64>) -> i64 {
+    let mut sorted: Vec<i64> = samples.iter().copied().collect();
+    sorted.sort_unstable();
+    sorted[sorted.len() / 2]
+}
+
+/// Record the Date header of a response that just arrived
+pub fn observe(date_header: &str) {
+    let Ok(server) = chrono::DateTime::parse_from_rfc2822(date_header.trim()) else {
+        return;
+    };
+    let offset = (server.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_seconds();
+
+    let mut skew = SKEW.lock().unwrap();
+    skew.samples.push_back(offset);
+    if skew.samples.len() > MAX_SAMPLES {
+        skew.samples.pop_front();
+    }
+    if skew.samples.len() < MIN_SAMPLES {
+        return;
+    }
+
+    let measured = median(&skew.samples);
+    skew.applied = if measured.abs() > SKEW_THRESHOLD_SECS {
+        measured
+    } else {
+        0
+    };
+    let report = skew.applied != 0 && !skew.reported;
+    skew.reported |= report;
+    let status = current(&skew);
+    drop(skew);
+
+    if report {
+        if let Some(app) = APP.get() {
+            let _ = app.emit(crate::events::CLOCK_SKEW_DETECTED, status);
+        }
+    }
+}
+
+fn current(skew: &Skew) -> ClockSkew {
+    ClockSkew {
+        offset_secs: if skew.samples.is_empty() {
+            0
+        } else {
+            median(&skew.samples)
+        },
+        applied: skew.applied != 0,
+        samples: skew.samples.len(),
+    }
+}
+
+/// The current time, corrected by the measured skew
+pub fn now() -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now() + chrono::Duration::seconds(SKEW.lock().unwrap().applied)
+}
+
+/// Time elapsed since `t`, never negative
+pub fn since(t: chrono::DateTime<chrono::Utc>) -> chrono::Duration {
+    (now() - t).max(chrono::Duration::zero())
+}
+
+/// Time from `start` to `end`, zero if `end` comes first
+pub fn between(
+    start: chrono::DateTime<chrono::Utc>,
+    end: chrono::DateTime<chrono::Utc>,
+) -> chrono::Duration {
+    (end - start).max(chrono::Duration::zero())
+}
+
+// Formats with an offset tried after RFC 3339, and without one
+const OFFSET_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f%:z", "%Y-%m-%dT%H:%M:%S%.f%z"];
+const NAIVE_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+/// The server's UTC offset at a local time in its zone. US daylight saving
+/// runs from 2:00 on the second Sunday of March to 2:00 on the first Sunday
+/// of November; the repeated hour in November is read as the first one, and
+/// the skipped one in March as daylight time.
+fn server_offset(local: chrono::NaiveDateTime) -> chrono::FixedOffset {
+    use chrono::{Datelike, NaiveDate, Weekday};
+    let switch = |month, nth| {
+        NaiveDate::from_weekday_of_month_opt(local.year(), month, Weekday::Sun, nth)
+            .and_then(|d| d.and_hms_opt(2, 0, 0))
+    };
+    let daylight = match (switch(3, 2), switch(11, 1)) {
+        (Some(start), Some(end)) => local >= start && local < end,
+        _ => false,
+    };
+    let hours = if daylight { -7 } else { -8 };
+    chrono::FixedOffset::east_opt(hours * 3600).expect("offset is in range")
+}
+
+/// An instant from a server timestamp, with or without a UTC offset
+pub fn parse_server_time(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let value = value.trim();
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(t.with_timezone(&chrono::Utc));
+    }
+    for format in OFFSET_FORMATS {
+        if let Ok(t) = chrono::DateTime::parse_from_str(value, format) {
+            return Some(t.with_timezone(&chrono::Utc));
+        }
+    }
+    NAIVE_FORMATS.iter().find_map(|format| {
+        let local = chrono::NaiveDateTime::parse_from_str(value, format).ok()?;
+        let t = local.and_local_timezone(server_offset(local)).single()?;
+        Some(t.with_timezone(&chrono::Utc))
+    })
+}
+
+/// A server timestamp as RFC 3339 in UTC; one that can't be read is kept
+/// as it was
+pub fn normalize_server_time(value: Option<String>) -> Option<String> {
+    value.map(|v| parse_server_time(&v).map_or(v, |t| t.to_rfc3339()))
+}
+
+/// Let skew detection emit its event. Called once at startup.
+pub fn init(app: tauri::AppHandle) {
+    let _ = APP.set(app);
+}
+
+#[tauri::command]
+pub async fn get_clock_skew() -> Result<ClockSkew, String> {
+    Ok(current(&SKEW.lock().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> chrono::DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()
+    }
+
+    #[test]
+    fn daylight_saving_starts_at_two_on_the_second_sunday_of_march() {
+        // 8 March 2026 is the second Sunday
+        assert_eq!(
+            parse_server_time("2026-03-08T01:59:59"),
+            Some(utc(2026, 3, 8, 9, 59, 59))
+        );
+        assert_eq!(
+            parse_server_time("2026-03-08T03:00:00"),
+            Some(utc(2026, 3, 8, 10, 0, 0))
+        );
+        // The skipped hour is read as daylight time
+        assert_eq!(
+            parse_server_time("2026-03-08T02:30:00"),
+            Some(utc(2026, 3, 8, 9, 30, 0))
+        );
+        assert_eq!(
+            parse_server_time("2026-03-01T02:30:00"),
+            Some(utc(2026, 3, 1, 10, 30, 0))
+        );
+    }
+
+    #[test]
+    fn daylight_saving_ends_at_two_on_the_first_sunday_of_november() {
+        // 1 November 2026 is the first Sunday; the repeated hour is the first one
+        assert_eq!(
+            parse_server_time("2026-11-01T01:30:00"),
+            Some(utc(2026, 11, 1, 8, 30, 0))
+        );
+        assert_eq!(
+            parse_server_time("2026-11-01T02:00:00"),
+            Some(utc(2026, 11, 1, 10, 0, 0))
+        );
+        assert_eq!(
+            parse_server_time("2026-11-08T01:30:00"),
+            Some(utc(2026, 11, 8, 9, 30, 0))
+        );
+    }
+
+    #[test]
+    fn durations_across_a_transition_count_real_time() {
+        let before = parse_server_time("2026-11-01T00:00:00").unwrap();
+        let after = parse_server_time("2026-11-02T00:00:00").unwrap();
+        assert_eq!(between(before, after).num_hours(), 25);
+
+        let before = parse_server_time("2026-03-08T00:00:00").unwrap();
+        let after = parse_server_time("2026-03-09T00:00:00").unwrap();
+        assert_eq!(between(before, after).num_hours(), 23);
+    }
+
+    #[test]
+    fn late_evening_pacific_time_falls_in_the_next_utc_month() {
+        assert_eq!(
+            parse_server_time("2026-01-31T23:30:00"),
+            Some(utc(2026, 2, 1, 7, 30, 0))
+        );
+        assert_eq!(
+            parse_server_time("2026-12-31T20:00:00"),
+            Some(utc(2027, 1, 1, 4, 0, 0))
+        );
+        assert_eq!(
+            parse_server_time("2026-02-28T12:00:00-08:00"),
+            Some(utc(2026, 2, 28, 20, 0, 0))
+        );
+    }
+
+    #[test]
+    fn a_leap_second_is_the_last_second_of_its_minute() {
+        let t = parse_server_time("2016-12-31T23:59:60Z").unwrap();
+        assert_eq!(t.timestamp(), utc(2016, 12, 31, 23, 59, 59).timestamp());
+        let next = utc(2017, 1, 1, 0, 0, 0);
+        assert!(t < next);
+        assert_eq!(between(t, next).num_seconds(), 0);
+    }
+
+    #[test]
+    fn durations_never_go_negative() {
+        let early = utc(2026, 1, 2, 3, 4, 5);
+        let late = utc(2026, 1, 2, 4, 4, 5);
+        assert_eq!(between(early, late).num_seconds(), 3600);
+        assert_eq!(between(late, early), chrono::Duration::zero());
+        assert_eq!(between(early, early), chrono::Duration::zero());
+        assert_eq!(
+            since(now() + chrono::Duration::hours(1)),
+            chrono::Duration::zero()
+        );
+    }
+
+    #[test]
+    fn normalizing_writes_utc_and_keeps_what_cannot_be_read() {
+        assert_eq!(
+            normalize_server_time(Some("2026-01-02 03:04:05".to_string())),
+            Some("2026-01-02T11:04:05+00:00".to_string())
+        );
+        assert_eq!(
+            normalize_server_time(Some("last Tuesday".to_string())),
+            Some("last Tuesday".to_string())
+        );
+        assert_eq!(normalize_server_time(None), None);
+    }
+
+    #[test]
+    fn the_median_is_the_middle_sample() {
+        assert_eq!(median(&VecDeque::from([90, -5, 120])), 90);
+        assert_eq!(median(&VecDeque::from([3, 1, 4, 2])), 3);
+    }
+
+    #[test]
+    fn timestamps_with_an_offset_are_read_as_that_instant() {
+        let expected = utc(2026, 1, 2, 11, 4, 5);
+        for value in [
+            "2026-01-02T03:04:05-08:00",
+            "2026-01-02T11:04:05Z",
+            "2026-01-02 12:04:05+01:00",
+            "2026-01-02T03:04:05-0800",
+            "  2026-01-02T11:04:05+00:00\n",
+        ] {
+            assert_eq!(parse_server_time(value), Some(expected), "{}", value);
+        }
+        assert_eq!(
+            parse_server_time("2026-01-02T11:04:05.250Z")
+                .unwrap()
+                .timestamp_millis(),
+            expected.timestamp_millis() + 250
+        );
+    }
+
+    #[test]
+    fn timestamps_without_an_offset_are_pacific_time() {
+        // Standard time in January, daylight time in July
+        assert_eq!(
+            parse_server_time("2026-01-02T03:04:05"),
+            Some(utc(2026, 1, 2, 11, 4, 5))
+        );
+        assert_eq!(
+            parse_server_time("2026-07-02 03:04:05"),
+            Some(utc(2026, 7, 2, 10, 4, 5))
+        );
+    }
+
+    #[test]
+    fn malformed_timestamps_are_none() {
+        for value in [
+            "",
+            "yesterday",
+            "2026-13-01T00:00:00Z",
+            "2026-02-30T00:00:00",
+            "2026-01-02",
+            "2026-01-02T25:00:00-08:00",
+            "2026-01-02T03:04:05+99:00",
+            "1767352245",
+        ] {
+            assert_eq!(parse_server_time(value), None, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+// Compression of downloaded result archives.
+//
+// Many NSG outputs are already compressed (npz, gz, mp4 renders), and
+// deflating them again costs minutes of CPU for next to no saving. The
+// `zip_compression` preference picks a fixed level, or "auto", which stores
+// files with a known compressed extension as they are and deflates the rest.
+// Every mode produces a standard zip: Stored or Deflated entries only.
+
+use crate::i18n::Msg;
+use crate::preferences;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+const COMPRESSION_KEY: &str = "zip_compression";
+
+// Lowercase extensions of formats that don't shrink any further
+const ALREADY_COMPRESSED: &[&str] = &[
+    "gz", "tgz", "bz2", "tbz2", "xz", "txz", "zst", "lz4", "zip", "7z", "rar", "npz", "jar", "mp4",
+    "m4v", "mkv", "mov", "avi", "webm", "mp3", "ogg", "png", "jpg", "jpeg", "gif", "webp", "pdf",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZipCompression {
+    Store,
+    Fast,
+    #[default]
+    Default,
+    Best,
+    Auto,
+}
+
+fn is_already_compressed(name: &str) -> bool {
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ALREADY_COMPRESSED.contains(&ext.to_lowercase().as_str()))
+}
+
+impl ZipCompression {
+    pub const ALL: &'static [&'static str] = &["store", "fast", "default", "best", "auto"];
+
+    /// `base` with the compression for an entry called `name`
+    pub fn options_for(self, name: &str, base: SimpleFileOptions) -> SimpleFileOptions {
+        let deflated = base.compression_method(CompressionMethod::Deflated);
+        match self {
+            ZipCompression::Store => base.compression_method(CompressionMethod::Stored),
+            ZipCompression::Fast => deflated.compression_level(Some(1)),
+            ZipCompression::Default => deflated.compression_level(None),
+            ZipCompression::Best => deflated.compression_level(Some(9)),
+            ZipCompression::Auto if is_already_compressed(name) => {
+                base.compression_method(CompressionMethod::Stored)
+            }
+            ZipCompression::Auto => deflated.compression_level(None),
+        }
+    }
+}
+
+pub fn load(app: &tauri::AppHandle) -> ZipCompression {
+    preferences::of(app)
+        .get(COMPRESSION_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+pub async fn get_zip_compression(app: tauri::AppHandle) -> Result<ZipCompression, String> {
+    Ok(load(&app))
+}
+
+#[tauri::command]
+pub async fn set_zip_compression(
+    app: tauri::AppHandle,
+    compression: ZipCompression,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+
+    preferences::of(&app).set(COMPRESSION_KEY, json!(compression));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch;
+    use std::io::{Cursor, Read, Write};
+
+    // The method each entry ends up with when written with `mode`
+    fn methods(mode: ZipCompression, names: &[&str]) -> Vec<CompressionMethod> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for name in names {
+            zip.start_file(*name, mode.options_for(name, SimpleFileOptions::default()))
+                .unwrap();
+            zip.write_all(&[b'a'; 256]).unwrap();
+        }
+        let mut archive = zip::ZipArchive::new(zip.finish().unwrap()).unwrap();
+        (0..names.len())
+            .map(|i| archive.by_index(i).unwrap().compression())
+            .collect()
+    }
+
+    #[test]
+    fn auto_stores_what_is_already_compressed() {
+        assert_eq!(
+            methods(
+                ZipCompression::Auto,
+                &["data.NPZ", "render.mp4", "log.txt", "noext"]
+            ),
+            [
+                CompressionMethod::Stored,
+                CompressionMethod::Stored,
+                CompressionMethod::Deflated,
+                CompressionMethod::Deflated,
+            ]
+        );
+    }
+
+    #[test]
+    fn fixed_modes_apply_to_every_entry() {
+        assert_eq!(
+            methods(ZipCompression::Store, &["log.txt", "data.gz"]),
+            [CompressionMethod::Stored, CompressionMethod::Stored]
+        );
+        for mode in [
+            ZipCompression::Fast,
+            ZipCompression::Default,
+            ZipCompression::Best,
+        ] {
+            assert_eq!(
+                methods(mode, &["log.txt", "data.gz"]),
+                [CompressionMethod::Deflated, CompressionMethod::Deflated]
+            );
+        }
+    }
+
+    // Entries of a results folder: text that deflates well, a real gzip and
+    // bytes that don't compress at all
+    fn payloads() -> Vec<(&'static str, Vec<u8>)> {
+        let text = (0..2000)
+            .map(|i| format!("step {} lnL -1234.{}\n", i, i % 7))
+            .collect::<String>()
+            .into_bytes();
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&text).unwrap();
+        let gz = gz.finish().unwrap();
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let noise = (0..64 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        vec![
+            ("out/infile.tree.log", text),
+            ("out/trees.gz", gz),
+            ("out/arrays.npz", noise),
+            ("empty.txt", Vec::new()),
+        ]
+    }
+
+    #[test]
+    fn every_mode_reads_back_byte_for_byte() {
+        let payloads = payloads();
+        let unzip = std::process::Command::new("unzip")
+            .arg("-v")
+            .output()
+            .is_ok();
+        for (mode, name) in [
+            ZipCompression::Store,
+            ZipCompression::Fast,
+            ZipCompression::Default,
+            ZipCompression::Best,
+            ZipCompression::Auto,
+        ]
+        .into_iter()
+        .zip(ZipCompression::ALL)
+        {
+            let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            for (entry, content) in &payloads {
+                zip.start_file(
+                    *entry,
+                    mode.options_for(entry, SimpleFileOptions::default()),
+                )
+                .unwrap();
+                zip.write_all(content).unwrap();
+            }
+            let bytes = zip.finish().unwrap().into_inner();
+
+            let mut archive = zip::ZipArchive::new(Cursor::new(&bytes)).unwrap();
+            assert_eq!(archive.len(), payloads.len());
+            for (i, (entry, content)) in payloads.iter().enumerate() {
+                let mut file = archive.by_index(i).unwrap();
+                assert_eq!(file.name(), *entry);
+                let mut read = Vec::new();
+                // Fails on a CRC mismatch
+                file.read_to_end(&mut read).unwrap();
+                assert!(read == *content, "{} in {} mode", entry, name);
+            }
+
+            // And with the unzip tool, where it is installed
+            if unzip {
+                let path = scratch("compression", name).join("results.zip");
+                std::fs::write(&path, &bytes).unwrap();
+                let test = std::process::Command::new("unzip")
+                    .arg("-tq")
+                    .arg(&path)
+                    .output()
+                    .unwrap();
+                assert!(
+                    test.status.success(),
+                    "unzip -t failed in {} mode: {}",
+                    name,
+                    String::from_utf8_lossy(&test.stdout)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_mode_is_listed() {
+        for name in ZipCompression::ALL {
+            assert!(serde_json::from_value::<ZipCompression>(json!(name)).is_ok());
+        }
+    }
+}
@@ -0,0 +1,272 @@
+// Crash reports.
+//
+// A panic hook installed first thing in main() writes the panic message,
+// location and backtrace to crashes/crash-<timestamp>.log in the app data
+// folder and flushes the preferences, the only state that lives in memory
+// between saves (queue, history and the other state files are written on
+// every change). Unless the panic is caught by `catch`, it also leaves
+// crash_marker.json behind; the next launch picks that up and offers the log
+// through `previous-session-crashed`. Downloads run under `catch`, so a
+// panic in one fails that download with an error instead of taking the task
+// down silently.
+
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::cell::Cell;
+use std::panic::{AssertUnwindSafe, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::Emitter;
+
+const CRASH_DIR: &str = "crashes";
+const MARKER_FILE: &str = "crash_marker.json";
+// Older crash logs are deleted
+const MAX_CRASH_LOGS: usize = 10;
+// A flush blocked by a lock the panicking thread held is given up on
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+// Marker left by the previous run, until dismissed
+static PREVIOUS: Mutex<Option<CrashMarker>> = Mutex::new(None);
+
+thread_local! {
+    // Set while `catch` runs, so a caught panic isn't reported as a crash
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Payload of `previous-session-crashed`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashMarker {
+    pub crashed_at: String,
+    pub message: String,
+    // Source location of the panic
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    // None when the log itself couldn't be written
+    pub log_path: Option<String>,
+}
+
+fn crash_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::portable::app_data_root(app)?.join(CRASH_DIR))
+}
+
+fn marker_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::portable::app_data_root(app)?.join(MARKER_FILE))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Install the panic hook. Reports are written once `attach` has given it an
+/// app handle; before that only the default hook runs.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        if let Some(app) = APP.get() {
+            report(app, info);
+        }
+    }));
+}
+
+pub fn attach(app: tauri::AppHandle) {
+    let _ = APP.set(app);
+}
+
+fn report(app: &tauri::AppHandle, info: &PanicHookInfo) {
+    let crashed_at = chrono::Utc::now();
+    let message = panic_message(info.payload());
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+    let thread = std::thread::current().name().map(|n| n.to_string());
+    let backtrace = std::backtrace::Backtrace::force_capture();
+
+    let log = format!(
+        "NSG GUI {} crashed at {}\nThread: {}\nLocation: {}\nMessage: {}\n\nBacktrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        crashed_at.to_rfc3339(),
+        thread.as_deref().unwrap_or("unnamed"),
+        location.as_deref().unwrap_or("unknown"),
+        message,
+        backtrace
+    );
+    let log_path = write_log(app, &crashed_at, &log).ok();
+
+    if CATCHING.with(|c| c.get()) {
+        return;
+    }
+
+    let marker = CrashMarker {
+        crashed_at: crashed_at.to_rfc3339(),
+        message,
+        location,
+        thread,
+        log_path: log_path.map(|p| p.display().to_string()),
+    };
+    // Nothing can report a failure from here
+    let _ = write_marker(app, &marker);
+    flush_preferences(app);
+}
+
+fn write_log(
+    app: &tauri::AppHandle,
+    crashed_at: &chrono::DateTime<chrono::Utc>,
+    log: &str,
+) -> Result<PathBuf, String> {
+    let dir = crash_dir(app)?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!(
+        "crash-{}.log",
+        crashed_at.format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    std::fs::write(&path, log).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    prune_logs(&dir);
+    Ok(path)
+}
+
+/// Keep only the newest MAX_CRASH_LOGS logs; their names sort by time
+fn prune_logs(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut logs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("crash-") && n.ends_with(".log"))
+        })
+        .collect();
+    logs.sort();
+    let excess = logs.len().saturating_sub(MAX_CRASH_LOGS);
+    for path in logs.into_iter().take(excess) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn write_marker(app: &tauri::AppHandle, marker: &CrashMarker) -> Result<(), String> {
+    let path = marker_path(app)?;
+    let content = serde_json::to_vec_pretty(marker)
+        .map_err(|e| format!("Failed to serialize crash marker: {}", e))?;
+    crate::state_file::write_atomic(&path, &content)
+}
+
+/// Flush the preferences the way a normal exit does, from another thread so
+/// a lock held by the panicking one can't hang the crash
+fn flush_preferences(app: &tauri::AppHandle) {
+    let (done, wait) = std::sync::mpsc::channel();
+    let flush_app = app.clone();
+    std::thread::spawn(move || {
+        let _ = done.send(crate::preferences::of(&flush_app).flush());
+    });
+    // Give up after FLUSH_TIMEOUT, whatever the outcome
+    let _ = wait.recv_timeout(FLUSH_TIMEOUT);
+}
+
+/// Run `f`, turning a panic into an error naming what was being done
+pub fn catch<T>(what: &str, f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    let was_catching = CATCHING.with(|c| c.replace(true));
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|c| c.set(was_catching));
+
+    result.unwrap_or_else(|payload| {
+        Err(format!(
+            "Internal error while {}: {}. Details are in the crash log.",
+            what,
+            panic_message(payload.as_ref())
+        ))
+    })
+}
+
+/// Pick up the marker left by a crashed previous run
+pub fn check(app: &tauri::AppHandle) {
+    let Ok(path) = marker_path(app) else {
+        return;
+    };
+    let Ok(content) = std::fs::read(&path) else {
+        return;
+    };
+    let _ = std::fs::remove_file(&path);
+    // An unreadable marker is dropped with the rest
+    if let Ok(marker) = serde_json::from_slice::<CrashMarker>(&content) {
+        *PREVIOUS.lock().unwrap() = Some(marker);
+    }
+}
+
+/// Emit `previous-session-crashed` if the last run crashed and it hasn't
+/// been dismissed
+pub fn announce(app: &tauri::AppHandle) {
+    if let Some(marker) = PREVIOUS.lock().unwrap().clone() {
+        let _ = app.emit(crate::events::PREVIOUS_SESSION_CRASHED, marker);
+    }
+}
+
+#[tauri::command]
+pub async fn get_previous_crash() -> Result<Option<CrashMarker>, String> {
+    Ok(PREVIOUS.lock().unwrap().clone())
+}
+
+/// Stop offering the previous crash; the log itself is kept
+#[tauri::command]
+pub async fn dismiss_previous_crash() -> Result<(), String> {
+    *PREVIOUS.lock().unwrap() = None;
+    Ok(())
+}
+
+/// Show the previous crash's log in the system file manager
+#[tauri::command]
+pub async fn open_crash_log() -> Result<(), String> {
+    let log_path = PREVIOUS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|m| m.log_path.clone())
+        .ok_or("No crash log to open")?;
+    crate::notify::open_folder(Path::new(&log_path))
+}
+
+/// Debug builds only: panic on purpose to exercise crash reporting. "app"
+/// (the default) panics on the main thread, which ends the app; "download"
+/// panics inside a guarded download task and comes back as an error.
+#[tauri::command]
+pub async fn trigger_test_panic(app: tauri::AppHandle, kind: Option<String>) -> Result<(), String> {
+    if !cfg!(debug_assertions) {
+        return Err("trigger_test_panic is only available in debug builds".to_string());
+    }
+    match kind.as_deref().unwrap_or("app") {
+        "app" => app
+            .run_on_main_thread(|| panic!("Test panic from trigger_test_panic"))
+            .map_err(|e| format!("Failed to reach the main thread: {}", e)),
+        "download" => tokio::task::spawn_blocking(|| {
+            catch("downloading", || -> Result<(), String> {
+                panic!("Test panic from trigger_test_panic")
+            })
+        })
+        .await
+        .map_err(|e| format!("Task error: {}", e))?,
+        other => Err(format!("Unknown panic kind: {}", other)),
+    }
+}
+
+/// Delete the crash logs and any unread marker, returning whether there
+/// were any
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    *PREVIOUS.lock().unwrap() = None;
+    let marker = crate::paths::remove_file_if_exists(&marker_path(app)?)?;
+    let dir = crash_dir(app)?;
+    if !dir.exists() {
+        return Ok(marker);
+    }
+    std::fs::remove_dir_all(&dir)
+        .map(|_| true)
+        .map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))
+}
@@ -0,0 +1,200 @@
+// Checks of the saved credentials file.
+//
+// A credentials file that can't be read, doesn't parse or lacks a field
+// used to look the same as having none. check_credentials_file says which
+// it is, along with the file's permission bits; on Unix a file other users
+// can read is flagged, and fix_credentials_permissions makes it owner-only
+// (0600). Only load_credentials, which the login form needs, returns the
+// password or app key; parse errors are reduced to their position so a
+// value can't leak through them either.
+
+use nsg_cli::Credentials;
+use serde::Serialize;
+use std::path::Path;
+
+const FIELDS: &[&str] = &["username", "password", "app_key"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldStatus {
+    pub name: &'static str,
+    pub present: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialsFileCheck {
+    pub path: String,
+    pub exists: bool,
+    // Why the file couldn't be read, e.g. permission denied
+    pub read_error: Option<String>,
+    pub parsed: bool,
+    pub parse_error: Option<String>,
+    // Whether each field is there as a non-empty string
+    pub fields: Vec<FieldStatus>,
+    // e.g. "0644"; None off Unix
+    pub permissions: Option<String>,
+    // Readable by the group or everyone
+    pub too_permissive: bool,
+    pub warnings: Vec<String>,
+    // fix_credentials_permissions would help
+    pub can_fix_permissions: bool,
+}
+
+/// What load_credentials found
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoadedCredentials {
+    Found { credentials: Credentials },
+    Missing { path: String },
+    // There is a file, but it can't be used; check_credentials_file says why
+    Unreadable { path: String, error: String },
+}
+
+/// A parse error without the text it choked on
+fn describe_parse_error(e: &serde_json::Error) -> String {
+    match e.classify() {
+        serde_json::error::Category::Data => {
+            format!(
+                "a field is missing or has the wrong type (line {})",
+                e.line()
+            )
+        }
+        _ => format!("not valid JSON (line {}, column {})", e.line(), e.column()),
+    }
+}
+
+/// The saved credentials, telling a missing file apart from a broken one
+pub fn load() -> LoadedCredentials {
+    let path = crate::portable::credentials_path();
+    let display = path.display().to_string();
+    if !path.exists() {
+        return LoadedCredentials::Missing { path: display };
+    }
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) => {
+            return LoadedCredentials::Unreadable {
+                path: display,
+                error: format!("Failed to read the file: {}", e),
+            }
+        }
+    };
+    match serde_json::from_str::<Credentials>(&content) {
+        Ok(credentials) => LoadedCredentials::Found { credentials },
+        Err(e) => LoadedCredentials::Unreadable {
+            path: display,
+            error: format!("The file is {}", describe_parse_error(&e)),
+        },
+    }
+}
+
+#[cfg(unix)]
+fn mode_bits(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .ok()
+        .map(|m| m.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn mode_bits(_path: &Path) -> Option<u32> {
+    None
+}
+
+fn check(path: &Path) -> CredentialsFileCheck {
+    let mut report = CredentialsFileCheck {
+        path: path.display().to_string(),
+        exists: path.exists(),
+        read_error: None,
+        parsed: false,
+        parse_error: None,
+        fields: FIELDS
+            .iter()
+            .map(|&name| FieldStatus {
+                name,
+                present: false,
+            })
+            .collect(),
+        permissions: None,
+        too_permissive: false,
+        warnings: Vec::new(),
+        can_fix_permissions: false,
+    };
+    if !report.exists {
+        return report;
+    }
+
+    if let Some(mode) = mode_bits(path) {
+        report.permissions = Some(format!("{:04o}", mode));
+        report.too_permissive = mode & 0o077 != 0;
+        if report.too_permissive {
+            report.warnings.push(format!(
+                "Other users on this computer can read the file (mode {:04o}); it should be 0600",
+                mode
+            ));
+            report.can_fix_permissions = true;
+        }
+    }
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            report.read_error = Some(e.to_string());
+            return report;
+        }
+    };
+    let value = match serde_json::from_str::<serde_json::Value>(&content) {
+        Ok(value) => value,
+        Err(e) => {
+            report.parse_error = Some(describe_parse_error(&e));
+            return report;
+        }
+    };
+    for field in &mut report.fields {
+        field.present = value
+            .get(field.name)
+            .and_then(|v| v.as_str())
+            .is_some_and(|s| !s.is_empty());
+    }
+    report.parsed = serde_json::from_value::<Credentials>(value).is_ok();
+    if !report.parsed {
+        report.parse_error = Some("a field is missing or has the wrong type".to_string());
+    }
+    report
+}
+
+#[tauri::command]
+pub async fn check_credentials_file() -> Result<CredentialsFileCheck, String> {
+    let path = crate::portable::credentials_path();
+    tokio::task::spawn_blocking(move || check(&path))
+        .await
+        .map_err(|e| format!("Task error: {}", e))
+}
+
+#[cfg(unix)]
+pub fn set_owner_only(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).map_err(|e| {
+        format!(
+            "Failed to change the permissions of {}: {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+#[cfg(not(unix))]
+pub fn set_owner_only(_path: &Path) -> Result<(), String> {
+    Err("Fixing file permissions is only supported on macOS and Linux".to_string())
+}
+
+/// Make the credentials file readable by its owner only
+#[tauri::command]
+pub async fn fix_credentials_permissions() -> Result<CredentialsFileCheck, String> {
+    let path = crate::portable::credentials_path();
+    tokio::task::spawn_blocking(move || {
+        set_owner_only(&path)?;
+        Ok(check(&path))
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+}
@@ -0,0 +1,475 @@
+// Demo-data backend for headless mode.
+//
+// Serves the fixtures account for the seed in NSG_GUI_DEMO_SEED (1 when
+// unset) over plain HTTP on a local port, answering the requests api.rs
+// makes the way NSG would, so headless commands run exactly the GUI's code
+// without an NSG account. Log in as the account's user (`fixture_0001` for
+// seed 1) with any password and a well-formed app key. Submitted jobs are
+// added in QUEUE and stay there; deleted ones are gone until restart.
+// Result files are the listed size, filled with a repeating pattern, and
+// Range requests are honoured.
+
+use crate::fixtures::{self, FixtureAccount, FixtureJob};
+use base64::Engine;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+pub const SEED_ENV: &str = "NSG_GUI_DEMO_SEED";
+const DEFAULT_SEED: u64 = 1;
+// What every result file is made of
+const FILE_PATTERN: &[u8] = b"NSG demo result data\n";
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The user of a Basic authorization header
+    fn user(&self) -> Option<String> {
+        let encoded = self.header("authorization")?.strip_prefix("Basic ")?;
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        Some(decoded.split_once(':')?.0.to_string())
+    }
+}
+
+struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Body,
+}
+
+enum Body {
+    Text(String),
+    // `len` bytes of FILE_PATTERN starting at `offset`
+    File { offset: u64, len: u64 },
+}
+
+impl Response {
+    fn xml(body: String) -> Self {
+        Self {
+            status: "200 OK",
+            headers: vec![("Content-Type", "application/xml".to_string())],
+            body: Body::Text(body),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type", "application/xml".to_string())],
+            body: Body::Text(format!(
+                "<error><displayMessage>{}</displayMessage></error>",
+                message
+            )),
+        }
+    }
+}
+
+/// Start serving the demo account on a free local port and send every API
+/// request there
+pub fn start() -> Result<(), String> {
+    let seed = std::env::var(SEED_ENV)
+        .ok()
+        .map(|s| {
+            s.parse()
+                .map_err(|_| format!("{} must be a number, not {:?}", SEED_ENV, s))
+        })
+        .transpose()?
+        .unwrap_or(DEFAULT_SEED);
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to start the demo backend: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start the demo backend: {}", e))?;
+    // Before generating, so the account's job URLs point here
+    crate::api::set_base_url(format!("http://{}", addr));
+    serve(listener, fixtures::generate(seed));
+    Ok(())
+}
+
+/// Answer requests on `listener` from `account` in the background
+fn serve(listener: TcpListener, account: FixtureAccount) {
+    let account = Arc::new(Mutex::new(account));
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let account = account.clone();
+            std::thread::spawn(move || {
+                let _ = handle(stream, &account);
+            });
+        }
+    });
+}
+
+/// One request per connection, which api.rs's client copes with
+fn handle(stream: TcpStream, account: &Mutex<FixtureAccount>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = read_request(&mut reader)?;
+    let response = route(&request, &mut account.lock().unwrap());
+    write_response(stream, &request, response)
+}
+
+fn invalid(message: impl ToString) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_request(reader: &mut impl BufRead) -> std::io::Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts
+        .next()
+        .ok_or_else(|| invalid("empty request"))?
+        .to_string();
+    let target = parts.next().ok_or_else(|| invalid("no request target"))?;
+    // Everything after the host; the query isn't used
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+
+    if request
+        .header("transfer-encoding")
+        .is_some_and(|v| v.eq_ignore_ascii_case("chunked"))
+    {
+        request.body = read_chunked(reader)?;
+    } else if let Some(length) = request.header("content-length") {
+        let length: usize = length.parse().map_err(invalid)?;
+        request.body = vec![0; length];
+        reader.read_exact(&mut request.body)?;
+    }
+    Ok(request)
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size, 16).map_err(invalid)?;
+        if size == 0 {
+            // Trailers, up to the blank line
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+}
+
+/// The value of text field `name` in a multipart form
+fn form_field(body: &[u8], name: &str) -> Option<String> {
+    let body = String::from_utf8_lossy(body);
+    let start = body.find(&format!("name=\"{}\"", name))?;
+    let value = &body[start..];
+    let value = &value[value.find("\r\n\r\n")? + 4..];
+    Some(value[..value.find("\r\n")?].to_string())
+}
+
+fn route(request: &Request, account: &mut FixtureAccount) -> Response {
+    if request.method == "HEAD" {
+        return Response::xml(String::new());
+    }
+    let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+    if segments == ["tool"] {
+        return Response::xml(fixtures::tool_list_xml());
+    }
+    let ["job", user, rest @ ..] = segments.as_slice() else {
+        return Response::error("404 Not Found", "No such resource");
+    };
+    if request.user().as_deref() != Some(*user) || *user != account.username {
+        return Response::error("401 Unauthorized", "Authentication Error: invalid username");
+    }
+    if request.header("cipres-appkey").is_none_or(str::is_empty) {
+        return Response::error("401 Unauthorized", "Authentication Error: missing app key");
+    }
+
+    let find =
+        |account: &FixtureAccount, id: &str| account.jobs.iter().position(|j| j.job_id == id);
+    match (request.method.as_str(), rest) {
+        ("GET", []) => Response::xml(account.job_list_xml()),
+        ("POST", []) => {
+            let Some(tool) = form_field(&request.body, "tool") else {
+                return Response::error("400 Bad Request", "No tool given");
+            };
+            let job = fixtures::submitted_job(&account.username, &tool, account.jobs.len() as u64);
+            let body = job.status_xml();
+            account.jobs.push(job);
+            Response::xml(body)
+        }
+        ("POST", ["validate"]) => match form_field(&request.body, "tool") {
+            Some(_) => Response::xml("<validationResults/>".to_string()),
+            None => Response::error("400 Bad Request", "No tool given"),
+        },
+        (method, [id, file @ ..]) => {
+            let Some(index) = find(account, id) else {
+                return Response::error("404 Not Found", "No such job");
+            };
+            let job = &account.jobs[index];
+            match (method, file) {
+                ("GET", []) => Response::xml(job.status_xml()),
+                ("DELETE", []) => {
+                    account.jobs.remove(index);
+                    Response::xml(String::new())
+                }
+                ("GET", ["output"]) => Response::xml(job.results_xml()),
+                ("GET", ["output", n]) => {
+                    match n.parse::<usize>().ok().and_then(|n| job.results.get(n)) {
+                        Some(file) => file_response(request, job, file.size),
+                        None => Response::error("404 Not Found", "No such file"),
+                    }
+                }
+                _ => Response::error("404 Not Found", "No such resource"),
+            }
+        }
+        _ => Response::error("404 Not Found", "No such resource"),
+    }
+}
+
+/// A result file of `size` bytes, or the part of it a Range header asks for
+fn file_response(request: &Request, job: &FixtureJob, size: u64) -> Response {
+    let range = request
+        .header("range")
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.split_once('-'))
+        .and_then(|(first, last)| {
+            let first: u64 = first.parse().ok()?;
+            let last = if last.is_empty() {
+                size.checked_sub(1)?
+            } else {
+                last.parse().ok()?
+            };
+            (first <= last && last < size).then_some((first, last))
+        });
+    let disposition = (
+        "Content-Disposition",
+        format!("attachment; filename=\"{}\"", job.job_id),
+    );
+    match range {
+        Some((first, last)) => Response {
+            status: "206 Partial Content",
+            headers: vec![
+                disposition,
+                (
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", first, last, size),
+                ),
+            ],
+            body: Body::File {
+                offset: first,
+                len: last - first + 1,
+            },
+        },
+        None => Response {
+            status: "200 OK",
+            headers: vec![disposition],
+            body: Body::File {
+                offset: 0,
+                len: size,
+            },
+        },
+    }
+}
+
+fn write_response(stream: TcpStream, request: &Request, response: Response) -> std::io::Result<()> {
+    let mut out = std::io::BufWriter::new(stream);
+    let len = match &response.body {
+        Body::Text(text) => text.len() as u64,
+        Body::File { len, .. } => *len,
+    };
+    write!(out, "HTTP/1.1 {}\r\n", response.status)?;
+    write!(
+        out,
+        "Date: {}\r\n",
+        chrono::Utc::now().format("%a, %d %b %Y %H:%M:%S GMT")
+    )?;
+    for (name, value) in &response.headers {
+        write!(out, "{}: {}\r\n", name, value)?;
+    }
+    write!(out, "Content-Length: {}\r\nConnection: close\r\n\r\n", len)?;
+    if request.method == "HEAD" {
+        return out.flush();
+    }
+
+    match response.body {
+        Body::Text(text) => out.write_all(text.as_bytes())?,
+        Body::File { offset, len } => {
+            let mut at = offset;
+            let end = offset + len;
+            while at < end {
+                let start = (at % FILE_PATTERN.len() as u64) as usize;
+                let n = ((FILE_PATTERN.len() - start) as u64).min(end - at) as usize;
+                out.write_all(&FILE_PATTERN[start..start + n])?;
+                at += n as u64;
+            }
+        }
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiClient;
+    use nsg_cli::models::{parse_job_list, parse_job_status, OutputFile};
+    use nsg_cli::Credentials;
+
+    const KEY: &str = "NSG_GUI-0123456789abcdef0123456789abcdef";
+
+    // A server for the account of `seed`, and the account's URL on it
+    fn server(seed: u64) -> (String, FixtureAccount) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let mut account = fixtures::generate(seed);
+        // Requests are routed by path, so only the host needs changing
+        for job in &mut account.jobs {
+            job.url = format!("{}/job/{}/{}", base, account.username, job.job_id);
+        }
+        serve(listener, account.clone());
+        (format!("{}/job/{}", base, account.username), account)
+    }
+
+    fn credentials(username: &str) -> Credentials {
+        Credentials {
+            username: username.to_string(),
+            password: "anything".to_string(),
+            app_key: KEY.to_string(),
+        }
+    }
+
+    fn get(url: &str, username: &str) -> reqwest::blocking::Response {
+        reqwest::blocking::Client::new()
+            .get(url)
+            .basic_auth(username, Some("anything"))
+            .header("cipres-appkey", KEY)
+            .send()
+            .unwrap()
+    }
+
+    #[test]
+    fn the_account_is_served_as_nsg_would() {
+        let (url, account) = server(2);
+        let listed = parse_job_list(&get(&url, &account.username).text().unwrap()).unwrap();
+        assert_eq!(listed.len(), account.jobs.len());
+
+        let api = ApiClient::default()
+            .blocking_api(credentials(&account.username))
+            .unwrap();
+        let job = account.jobs.iter().find(|j| j.results.len() > 1).unwrap();
+        let record = api.job_record(&job.url).unwrap();
+        assert_eq!(record.status.job_id, job.job_id);
+        let files = api.output_files(&record.status).unwrap();
+        assert_eq!(files.len(), job.results.len());
+        assert_eq!(files[0].filename, job.results[0].filename);
+    }
+
+    #[test]
+    fn other_users_are_refused() {
+        let (url, account) = server(3);
+        let response = get(&url.replace(&account.username, "alice"), "alice");
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            get(&url, "alice").status(),
+            reqwest::StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn files_have_their_size_and_honour_ranges() {
+        let (_, account) = server(4);
+        let api = ApiClient::default()
+            .blocking_api(credentials(&account.username))
+            .unwrap();
+        let job = account
+            .jobs
+            .iter()
+            .find(|j| j.results.iter().any(|f| f.filename == "STDOUT"))
+            .unwrap();
+        let (n, stdout) = job
+            .results
+            .iter()
+            .enumerate()
+            .find(|(_, f)| f.filename == "STDOUT")
+            .unwrap();
+        let file = OutputFile {
+            filename: stdout.filename.clone(),
+            download_uri: format!("{}/{}", job.results_url(), n),
+            size: stdout.size,
+        };
+        let body = get(&file.download_uri, &account.username).bytes().unwrap();
+        assert_eq!(body.len() as u64, stdout.size);
+        assert_eq!(&body[..FILE_PATTERN.len()], FILE_PATTERN);
+
+        let part = api.read_range(&file, 5, 30).unwrap();
+        assert_eq!(part, body[5..35]);
+    }
+
+    #[test]
+    fn submitted_jobs_are_listed_and_deleted_ones_are_not() {
+        let (url, account) = server(5);
+        let client = reqwest::blocking::Client::new();
+        let form = reqwest::blocking::multipart::Form::new().text("tool", "PY_EXPANSE");
+        let response = client
+            .post(&url)
+            .basic_auth(&account.username, Some("anything"))
+            .header("cipres-appkey", KEY)
+            .multipart(form)
+            .send()
+            .unwrap();
+        let status = parse_job_status(&response.text().unwrap()).unwrap();
+        assert_eq!(status.tool_id.as_deref(), Some("PY_EXPANSE"));
+        let listed = parse_job_list(&get(&url, &account.username).text().unwrap()).unwrap();
+        assert_eq!(listed.len(), account.jobs.len() + 1);
+
+        let api = ApiClient::default()
+            .blocking_api(credentials(&account.username))
+            .unwrap();
+        // The job's own URL names the real base, which this server isn't on
+        api.delete_job(&format!("{}/{}", url, status.job_id))
+            .unwrap();
+        let listed = parse_job_list(&get(&url, &account.username).text().unwrap()).unwrap();
+        assert_eq!(listed.len(), account.jobs.len());
+    }
+
+    #[test]
+    fn chunked_bodies_are_read_whole() {
+        let mut body: &[u8] = b"4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\n\r\n";
+        assert_eq!(read_chunked(&mut body).unwrap(), b"Wikipedia");
+    }
+}
@@ -0,0 +1,1644 @@
+use crate::activity_feed::{self, Activity, ActivityKind, ActivityStatus};
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, Anonymize};
+use crate::api::NsgApi;
+use crate::background_errors::{self, ErrorKind};
+use crate::checksum::{self, CHECKSUMS_ENTRY};
+use crate::compression;
+use crate::history::{self, DownloadHistoryEntry};
+use crate::i18n::Msg;
+use crate::mode;
+use crate::monitor::parse_date;
+use crate::notify;
+use crate::path_access;
+use crate::paths;
+use crate::patterns::{self, ExcludedFile};
+use crate::preferences;
+use crate::progress;
+use crate::queue::{self, QueueStatus};
+use crate::resume;
+use crate::rules::{self, DirSource, ResolutionStep};
+use crate::size_stats::{self, ThresholdSource, WarnThreshold};
+use crate::staging;
+use crate::throttle;
+use crate::AppState;
+use nsg_cli::models::{JobStatus, OutputFile};
+use nsg_cli::Credentials;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+pub const DEFAULT_MAX_DOWNLOAD_WARN_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+// Name of the follow-up work of a download in background errors; the
+// download itself fails through its result
+const SOURCE: &str = "download";
+
+// Seconds a download waits after the job completed, for NSG to finish
+// writing late output files
+const SETTLE_DELAY_KEY: &str = "settle_delay_secs";
+pub const MAX_SETTLE_DELAY_SECS: u64 = 600;
+
+// Tries per result file when the transfer breaks off, each continuing
+// where the last one stopped
+const DOWNLOAD_ATTEMPTS: u32 = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+// Result files of one job fetched at the same time
+const CONCURRENCY_KEY: &str = "download_concurrency";
+pub const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 3;
+pub const MAX_DOWNLOAD_CONCURRENCY: usize = 8;
+
+/// Payload of `download-progress`
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub job_id: String,
+    pub filename: String,
+    pub downloaded: u64,
+    pub total: u64,
+    // Bytes per second
+    pub speed: u64,
+}
+
+impl Anonymize for DownloadProgress {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            ..self
+        }
+    }
+}
+
+// Latest progress of every file of every running download, keyed by
+// transfer ID and entry name so neither two downloads of the same job nor
+// files fetched in parallel overwrite each other, so a reloaded frontend
+// can be brought back up to date
+static ACTIVE_TRANSFERS: Mutex<BTreeMap<(u64, String), DownloadProgress>> =
+    Mutex::new(BTreeMap::new());
+static NEXT_TRANSFER_ID: AtomicU64 = AtomicU64::new(0);
+
+// Jobs whose results are being fetched. Each job has one staging folder
+// and one archive name, so a second download of it would cut back the
+// partial files the first is writing and delete its folder when done.
+static DOWNLOADING_JOBS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// A job's claim on its staging folder, released when dropped
+struct JobClaim(String);
+
+impl JobClaim {
+    fn take(job_id: &str) -> Result<Self, String> {
+        if !DOWNLOADING_JOBS.lock().unwrap().insert(job_id.to_string()) {
+            return Err(format!(
+                "The results of {} are already being downloaded",
+                job_id
+            ));
+        }
+        Ok(Self(job_id.to_string()))
+    }
+}
+
+impl Drop for JobClaim {
+    fn drop(&mut self) {
+        DOWNLOADING_JOBS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Payload of `download-complete`
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadComplete {
+    pub job_id: String,
+    pub output_dir: String,
+    // How output_dir was picked, most specific level first
+    pub output_dir_chain: Vec<ResolutionStep>,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+    // The results listing, fetched again before finalizing, had changed
+    pub listing_changed: bool,
+    // Listed only the second time, and fetched then
+    pub late_files: Vec<String>,
+    // Listed the first time but gone the second; kept in the archive
+    pub vanished_files: Vec<String>,
+}
+
+impl Anonymize for DownloadComplete {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            ..self
+        }
+    }
+}
+
+// Downloads running right now, counted from their start rather than their
+// first progress report
+static ACTIVE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn active_downloads() -> usize {
+    ACTIVE_COUNT.load(Ordering::SeqCst)
+}
+
+/// Tracks one running download and forgets it when dropped, whether the
+/// download finished or failed part-way
+struct ActiveTransfer {
+    id: u64,
+    job_id: String,
+    app: tauri::AppHandle,
+    // Advertised size of every file being downloaded
+    total_bytes: u64,
+    // Entry name -> bytes on disk, for the progress of the whole download
+    // while several files are fetched at once
+    downloaded: Mutex<BTreeMap<String, u64>>,
+}
+
+impl ActiveTransfer {
+    fn new(app: &tauri::AppHandle, job_id: &str, total_bytes: u64) -> Self {
+        ACTIVE_COUNT.fetch_add(1, Ordering::SeqCst);
+        crate::session_status::changed(app);
+        Self {
+            id: NEXT_TRANSFER_ID.fetch_add(1, Ordering::SeqCst),
+            job_id: job_id.to_string(),
+            app: app.clone(),
+            total_bytes,
+            downloaded: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn report(&self, app: &tauri::AppHandle, name: &str, progress: DownloadProgress) {
+        ACTIVE_TRANSFERS
+            .lock()
+            .unwrap()
+            .insert((self.id, name.to_string()), progress.clone());
+        let downloaded: u64 = {
+            let mut files = self.downloaded.lock().unwrap();
+            files.insert(name.to_string(), progress.downloaded);
+            files.values().sum()
+        };
+        if self.total_bytes > 0 {
+            let fraction = downloaded as f64 / self.total_bytes as f64;
+            activity_feed::progress(app, &activity_key(&self.job_id), fraction);
+        }
+        anonymize::emit(app, crate::events::DOWNLOAD_PROGRESS, progress);
+    }
+}
+
+impl Drop for ActiveTransfer {
+    fn drop(&mut self) {
+        ACTIVE_TRANSFERS
+            .lock()
+            .unwrap()
+            .retain(|(id, _), _| *id != self.id);
+        ACTIVE_COUNT.fetch_sub(1, Ordering::SeqCst);
+        crate::session_status::changed(&self.app);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultFile {
+    pub filename: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DownloadResponse {
+    Completed {
+        path: String,
+        // Results were deleted on the server after the archive was verified
+        purged: bool,
+        // Left out by the download patterns
+        excluded: Vec<ExcludedFile>,
+        // Entry names in the archive; relative paths when the tree was
+        // preserved
+        files: Vec<String>,
+    },
+    // Returned instead of downloading when the result set is larger than
+    // the warning threshold; call again with `confirmed: true` to proceed.
+    ConfirmationRequired {
+        total_bytes: u64,
+        threshold_bytes: u64,
+        // Whether the tool's own download sizes set the threshold, and how
+        threshold_source: ThresholdSource,
+        threshold_reason: String,
+        files: Vec<ResultFile>,
+    },
+}
+
+/// Name of the manifest entry stored alongside the results in every archive
+pub const JOB_INFO_ENTRY: &str = "job_info.json";
+
+/// Contents of job_info.json: what the job was and what the archive holds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobInfoManifest {
+    pub job_id: String,
+    pub job_url: String,
+    pub tool: Option<String>,
+    pub job_stage: String,
+    pub failed: bool,
+    pub date_submitted: Option<String>,
+    pub date_completed: Option<String>,
+    pub compute_resource: Option<String>,
+    pub tool_version: Option<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    pub downloaded_at: String,
+    pub files: Vec<ResultFile>,
+    // Result files the download patterns left out
+    #[serde(default)]
+    pub excluded: Vec<ExcludedFile>,
+    // File name -> hex SHA-256, same as checksums.sha256
+    #[serde(default)]
+    pub checksums: BTreeMap<String, String>,
+    // Entries are relative paths (see result_path) instead of bare names
+    #[serde(default)]
+    pub preserve_tree: bool,
+    // Result files that only appeared when the listing was fetched again
+    // at the end of the download
+    #[serde(default)]
+    pub late_files: Vec<String>,
+    // Result files downloaded but no longer listed at the end
+    #[serde(default)]
+    pub vanished_files: Vec<String>,
+    // File name -> entry name, for the files stored under another name
+    // than entry_name gives because that one was taken
+    #[serde(default)]
+    pub renamed: BTreeMap<String, String>,
+}
+
+/// A result file fetched into the staging directory
+struct DownloadedFile {
+    path: PathBuf,
+    // Entry name in the archive, as returned by entry_name
+    name: String,
+    // Size advertised by the results listing
+    size: u64,
+}
+
+/// Fetches the result files of one download into its staging directory
+struct Fetcher<'a> {
+    app: &'a tauri::AppHandle,
+    api: &'a NsgApi,
+    transfer: &'a ActiveTransfer,
+    offsets: &'a resume::Offsets,
+    temp_dir: &'a Path,
+    // Files fetched at the same time
+    workers: usize,
+}
+
+impl Fetcher<'_> {
+    /// Fetch `files` under the entry names in `names`, up to `workers` at a
+    /// time, returning them in the same order. After a failure no further
+    /// files are started; the first error in listing order is returned once
+    /// the running ones have stopped.
+    fn fetch_all(
+        &self,
+        files: &[OutputFile],
+        names: &[String],
+    ) -> Result<Vec<DownloadedFile>, String> {
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let results: Mutex<Vec<Option<Result<DownloadedFile, String>>>> =
+            Mutex::new(files.iter().map(|_| None).collect());
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.workers.min(files.len()) {
+                scope.spawn(|| {
+                    while !failed.load(Ordering::SeqCst) {
+                        let at = next.fetch_add(1, Ordering::SeqCst);
+                        let (Some(file), Some(name)) = (files.get(at), names.get(at)) else {
+                            break;
+                        };
+                        let result = fetch_file(self, file, name);
+                        if result.is_err() {
+                            failed.store(true, Ordering::SeqCst);
+                        }
+                        results.lock().unwrap()[at] = Some(result);
+                    }
+                });
+            }
+        });
+
+        let results = results.into_inner().unwrap();
+        if let Some(Err(e)) = results.iter().flatten().find(|r| r.is_err()) {
+            return Err(e.clone());
+        }
+        Ok(results.into_iter().flatten().flatten().collect())
+    }
+}
+
+/// Fetch one result file into the staging directory, emitting throttled
+/// progress and always the final byte count. A transfer that breaks off is
+/// resumed up to DOWNLOAD_ATTEMPTS times, as long as each attempt gets
+/// further than the last.
+fn fetch_file(fetcher: &Fetcher, file: &OutputFile, name: &str) -> Result<DownloadedFile, String> {
+    let Fetcher {
+        app,
+        api,
+        transfer,
+        offsets,
+        temp_dir,
+        ..
+    } = *fetcher;
+    let dest = temp_dir.join(name);
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create temp dir: {}", e))?;
+    }
+    let mut on_disk = offsets.prepare(name, file, &dest)?;
+    let started = Instant::now();
+    // Only this session's bytes count towards the speed; a resumed file
+    // starts part-way
+    let mut fetched = 0u64;
+    let gate = progress::Gate::new();
+    // The newest update the gate held back, sent once the file is done
+    let mut held = None;
+
+    let mut attempt = 1;
+    loop {
+        let before = on_disk;
+        let result = api.download_file(file, &dest, |chunk, downloaded| {
+            on_disk = downloaded;
+            offsets.record(name, downloaded, false);
+            throttle::acquire(chunk.len());
+            fetched += chunk.len() as u64;
+
+            // Effective rate, so a throttled transfer visibly slows down
+            let elapsed = started.elapsed().as_secs_f64();
+            let speed = if elapsed > 0.0 {
+                (fetched as f64 / elapsed) as u64
+            } else {
+                0
+            };
+
+            let update = DownloadProgress {
+                job_id: transfer.job_id.clone(),
+                filename: file.filename.clone(),
+                downloaded,
+                total: file.size,
+                speed,
+            };
+            if gate.due(file.size > 0 && downloaded >= file.size) {
+                held = None;
+                transfer.report(app, name, update);
+            } else {
+                held = Some(update);
+            }
+        });
+        match result {
+            Ok(size) => {
+                offsets.record(name, size, true);
+                break;
+            }
+            Err(e) => {
+                offsets.record(name, on_disk, true);
+                let resumable = crate::api::is_network_error(&e) || on_disk > before;
+                if !resumable || attempt >= DOWNLOAD_ATTEMPTS {
+                    return Err(format!("Failed to download results: {}", e));
+                }
+                std::thread::sleep(RETRY_DELAY * attempt);
+                attempt += 1;
+            }
+        }
+    }
+    if let Some(update) = held {
+        transfer.report(app, name, update);
+    }
+
+    Ok(DownloadedFile {
+        path: dest,
+        name: name.to_string(),
+        size: file.size,
+    })
+}
+
+/// What changed in the results listing while the files were downloading
+#[derive(Default)]
+struct Relisting {
+    // Newly listed files the download patterns let through
+    added: Vec<OutputFile>,
+    // Newly listed files the download patterns left out
+    excluded: Vec<ExcludedFile>,
+    vanished: Vec<String>,
+}
+
+impl Relisting {
+    /// Whether the second pass found anything, reported as `listing_changed`
+    fn changed(&self) -> bool {
+        !self.added.is_empty() || !self.excluded.is_empty() || !self.vanished.is_empty()
+    }
+}
+
+/// Split a listing into the files picked by name and the rest. Every name
+/// has to be in the listing.
+fn pick(
+    listing: Vec<OutputFile>,
+    names: &[String],
+) -> Result<(Vec<OutputFile>, Vec<ExcludedFile>), String> {
+    let missing: Vec<&str> = names
+        .iter()
+        .filter(|name| !listing.iter().any(|f| f.filename == **name))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Not among the job's result files: {}",
+            missing.join(", ")
+        ));
+    }
+    Ok(split_picked(listing, names))
+}
+
+fn split_picked(
+    listing: Vec<OutputFile>,
+    names: &[String],
+) -> (Vec<OutputFile>, Vec<ExcludedFile>) {
+    let (picked, rest): (Vec<_>, Vec<_>) = listing
+        .into_iter()
+        .partition(|f| names.contains(&f.filename));
+    let rest = rest
+        .into_iter()
+        .map(|f| ExcludedFile {
+            filename: f.filename,
+            size: f.size,
+            pattern: None,
+        })
+        .collect();
+    (picked, rest)
+}
+
+/// Fetch the listing again and compare it with the one downloaded. Best
+/// effort: when it can't be fetched the download goes ahead as it is.
+/// With `picked`, files that appeared meanwhile are only added when named.
+fn relist(
+    app: &tauri::AppHandle,
+    api: &NsgApi,
+    status: &JobStatus,
+    downloaded: &[OutputFile],
+    excluded: &[ExcludedFile],
+    picked: Option<&[String]>,
+) -> Relisting {
+    let listing = match api.output_files(status) {
+        Ok(listing) => listing,
+        Err(e) => {
+            background_errors::report(
+                app,
+                SOURCE,
+                ErrorKind::of(&e),
+                format!(
+                    "Failed to list results again for {}: {:#}",
+                    status.job_id, e
+                ),
+            );
+            return Relisting::default();
+        }
+    };
+    compare_listing(listing, downloaded, excluded, |new_files| match picked {
+        Some(names) => split_picked(new_files, names),
+        None => patterns::select(app, new_files).unwrap_or_else(|e| {
+            background_errors::report(
+                app,
+                SOURCE,
+                ErrorKind::Other,
+                format!("Failed to apply download patterns: {}", e),
+            );
+            (Vec::new(), Vec::new())
+        }),
+    })
+}
+
+/// Compare the listing fetched again with what the first pass downloaded
+/// and left out. Files listed for the first time go through `split`, which
+/// applies the download patterns (or the picked names).
+fn compare_listing(
+    listing: Vec<OutputFile>,
+    downloaded: &[OutputFile],
+    excluded: &[ExcludedFile],
+    split: impl FnOnce(Vec<OutputFile>) -> (Vec<OutputFile>, Vec<ExcludedFile>),
+) -> Relisting {
+    let known: HashSet<&str> = downloaded
+        .iter()
+        .map(|f| f.filename.as_str())
+        .chain(excluded.iter().map(|f| f.filename.as_str()))
+        .collect();
+    let listed: HashSet<&str> = listing.iter().map(|f| f.filename.as_str()).collect();
+    let vanished = downloaded
+        .iter()
+        .filter(|f| !listed.contains(f.filename.as_str()))
+        .map(|f| f.filename.clone())
+        .collect();
+
+    let new_files: Vec<OutputFile> = listing
+        .iter()
+        .filter(|f| !known.contains(f.filename.as_str()))
+        .cloned()
+        .collect();
+    let (added, excluded) = split(new_files);
+    Relisting {
+        added,
+        excluded,
+        vanished,
+    }
+}
+
+fn download_concurrency(app: &tauri::AppHandle) -> usize {
+    preferences::of(app)
+        .get(CONCURRENCY_KEY)
+        .and_then(|v| v.as_u64())
+        .map_or(DEFAULT_DOWNLOAD_CONCURRENCY, |n| n as usize)
+        .clamp(1, MAX_DOWNLOAD_CONCURRENCY)
+}
+
+fn settle_delay_secs(app: &tauri::AppHandle) -> u64 {
+    preferences::of(app)
+        .get(SETTLE_DELAY_KEY)
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0)
+        .min(MAX_SETTLE_DELAY_SECS)
+}
+
+/// Wait out what's left of the settle delay after the job completed
+fn settle(app: &tauri::AppHandle, status: &JobStatus) {
+    let delay = settle_delay_secs(app);
+    if delay == 0 || status.job_stage != "COMPLETED" {
+        return;
+    }
+    let Some(completed) = parse_date(status.date_completed.as_deref()) else {
+        return;
+    };
+    let ready = completed + chrono::Duration::seconds(delay as i64);
+    if let Ok(wait) = (ready - crate::clock::now()).to_std() {
+        std::thread::sleep(wait);
+    }
+}
+
+/// The name a listed result file is stored under locally. Only the final
+/// path component is kept, so server-supplied names can't escape the
+/// staging directory.
+pub fn local_file_name(filename: &str) -> Result<String, String> {
+    Path::new(&filename.replace('\\', "/"))
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .filter(|n| n != "..")
+        .ok_or_else(|| format!("Invalid result file name: {}", filename))
+}
+
+/// The relative path a listed result file is stored under when the tree is
+/// preserved, with `/` separators. Empty and `.` components are dropped;
+/// `..`, drive letters and absolute paths would escape the target, so such
+/// names are refused rather than rewritten.
+pub fn result_path(filename: &str) -> Result<String, String> {
+    let normalized = filename.replace('\\', "/");
+    let invalid = || format!("Invalid result file path: {}", filename);
+    if normalized.starts_with('/') {
+        return Err(invalid());
+    }
+
+    let mut parts = Vec::new();
+    for part in normalized.split('/') {
+        match part {
+            "" | "." => continue,
+            ".." => return Err(invalid()),
+            _ if part.contains(':') => return Err(invalid()),
+            _ => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        return Err(invalid());
+    }
+    Ok(parts.join("/"))
+}
+
+/// The archive entry name of a listed result file
+pub fn entry_name(filename: &str, preserve_tree: bool) -> Result<String, String> {
+    if preserve_tree {
+        result_path(filename)
+    } else {
+        local_file_name(filename)
+    }
+}
+
+/// Entry names for `files` that are unique among themselves and `taken`,
+/// which collects them. Flattening the tree can give two result files the
+/// same name, and the staging folder may be on a case-insensitive volume,
+/// so a name already taken in any case gets " (2)", " (3)" and so on before
+/// its extension.
+fn unique_entry_names(
+    files: &[OutputFile],
+    preserve_tree: bool,
+    taken: &mut BTreeSet<String>,
+) -> Result<Vec<String>, String> {
+    let mut names = Vec::with_capacity(files.len());
+    for file in files {
+        let base = entry_name(&file.filename, preserve_tree)?;
+        let mut name = base.clone();
+        let mut n = 2;
+        while !taken.insert(name.to_lowercase()) {
+            name = numbered(&base, n);
+            n += 1;
+        }
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// Names a result file can't take in the staging folder or the archive
+fn reserved_names() -> BTreeSet<String> {
+    [CHECKSUMS_ENTRY, JOB_INFO_ENTRY, resume::OFFSETS_FILE]
+        .into_iter()
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// "dir/out.txt" -> "dir/out (2).txt"
+fn numbered(name: &str, n: usize) -> String {
+    let (dir, base) = match name.rsplit_once('/') {
+        Some((dir, base)) => (Some(dir), base),
+        None => (None, name),
+    };
+    let base = match base.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, n, ext),
+        _ => format!("{} ({})", base, n),
+    };
+    match dir {
+        Some(dir) => format!("{}/{}", dir, base),
+        None => base,
+    }
+}
+
+#[cfg(unix)]
+type VolumeId = u64;
+#[cfg(not(unix))]
+type VolumeId = String;
+
+/// Which volume an existing path is on
+#[cfg(unix)]
+fn volume_id(path: &Path) -> Result<VolumeId, String> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path)
+        .map(|m| m.dev())
+        .map_err(|e| format!("Failed to check free disk space: {}", e))
+}
+
+/// Which volume an existing path is on: its drive or share
+#[cfg(not(unix))]
+fn volume_id(path: &Path) -> Result<VolumeId, String> {
+    let path =
+        std::path::absolute(path).map_err(|e| format!("Failed to check free disk space: {}", e))?;
+    Ok(path
+        .components()
+        .next()
+        .map(|c| c.as_os_str().to_string_lossy().to_uppercase())
+        .unwrap_or_default())
+}
+
+/// What a download is about to fetch, checked before any bytes are written.
+struct Preflight {
+    files: Vec<ResultFile>,
+    total_bytes: u64,
+}
+
+impl Preflight {
+    fn new(files: Vec<ResultFile>) -> Self {
+        let total_bytes = files.iter().map(|f| f.size).sum();
+        Self { files, total_bytes }
+    }
+
+    /// Hard failure: a volume can't hold what goes on it. The results are
+    /// staged in `temp_dir` and then zipped into `output_dir`, both about
+    /// the size of the results, so a volume holding both needs room for
+    /// them twice until the staging folder is removed.
+    fn check_free_space(&self, output_dir: &Path, temp_dir: &Path) -> Result<(), String> {
+        // (closest existing folder, its volume, bytes going onto it)
+        let mut volumes: Vec<(&Path, VolumeId, u64)> = Vec::new();
+        for dir in [temp_dir, output_dir] {
+            // The folder may not exist yet - measure the closest existing
+            // ancestor, which is on the volume it will be created on.
+            let existing = dir
+                .ancestors()
+                .find(|p| p.exists())
+                .ok_or_else(|| format!("Invalid output directory: {}", dir.display()))?;
+            let volume = volume_id(existing)?;
+            match volumes.iter_mut().find(|(_, v, _)| *v == volume) {
+                Some((_, _, needed)) => *needed += self.total_bytes,
+                None => volumes.push((existing, volume, self.total_bytes)),
+            }
+        }
+
+        for (existing, _, needed) in volumes {
+            let available = fs2::available_space(existing)
+                .map_err(|e| format!("Failed to check free disk space: {}", e))?;
+            if needed > available {
+                return Err(format!(
+                    "Not enough disk space: results need {} bytes but only {} bytes are free on {}",
+                    needed,
+                    available,
+                    existing.display()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Soft gate: large result sets need explicit confirmation. A threshold
+    /// of 0 disables the gate.
+    fn confirmation(&self, threshold: WarnThreshold, confirmed: bool) -> Option<DownloadResponse> {
+        if confirmed || threshold.bytes == 0 || self.total_bytes <= threshold.bytes {
+            return None;
+        }
+
+        Some(DownloadResponse::ConfirmationRequired {
+            total_bytes: self.total_bytes,
+            threshold_bytes: threshold.bytes,
+            threshold_source: threshold.source,
+            threshold_reason: threshold.reason,
+            files: self.files.clone(),
+        })
+    }
+}
+
+fn max_download_warn_bytes(app: &tauri::AppHandle) -> Result<u64, String> {
+    let prefs = preferences::of(app);
+
+    Ok(prefs
+        .get("max_download_warn_bytes")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_MAX_DOWNLOAD_WARN_BYTES))
+}
+
+fn default_delete_after_download(app: &tauri::AppHandle) -> Result<bool, String> {
+    let prefs = preferences::of(app);
+
+    Ok(prefs
+        .get("delete_after_download")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false))
+}
+
+/// Compressed and uncompressed size of the result entries in an archive
+fn archive_totals(zip_path: &Path) -> Result<(u64, u64), String> {
+    let file = File::open(zip_path).map_err(|e| format!("Failed to reopen zip file: {}", e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read back zip file: {}", e))?;
+
+    let (mut compressed, mut uncompressed) = (0, 0);
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(i)
+            .map_err(|e| format!("Failed to read back zip file: {}", e))?;
+        if entry.name() == JOB_INFO_ENTRY || entry.name() == CHECKSUMS_ENTRY {
+            continue;
+        }
+        compressed += entry.compressed_size();
+        uncompressed += entry.size();
+    }
+    Ok((compressed, uncompressed))
+}
+
+/// Check that the finalized archive holds exactly the files of the results
+/// listing. Anything short of that means we must not purge the server copy.
+fn verify_archive_entries(zip_path: &Path, expected: usize) -> Result<(), String> {
+    let file = File::open(zip_path).map_err(|e| format!("Failed to reopen zip file: {}", e))?;
+    let archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read back zip file: {}", e))?;
+
+    let result_entries = archive
+        .file_names()
+        .filter(|name| *name != JOB_INFO_ENTRY && *name != CHECKSUMS_ENTRY)
+        .count();
+    if result_entries != expected {
+        return Err(format!(
+            "Archive has {} result entries but the results listing has {}",
+            result_entries, expected
+        ));
+    }
+
+    Ok(())
+}
+
+/// What to download and how; also the persisted form of a queue entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadRequest {
+    pub job_url: String,
+    // None: picked by the download rules once the job's tool is known
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub confirmed: bool,
+    #[serde(default)]
+    pub delete_after_download: bool,
+    // Keep the results' directory structure instead of flattening it
+    #[serde(default)]
+    pub preserve_tree: bool,
+    // Only these result files, by filename; the download patterns are used
+    // when None
+    #[serde(default)]
+    pub files: Option<Vec<String>>,
+}
+
+/// Activity feed key of a job's download
+fn activity_key(job_id: &str) -> String {
+    format!("download:{}", job_id)
+}
+
+// Extract job ID from URL (e.g., "https://example.com/jobs/NGBW-JOB-123" -> "NGBW-JOB-123")
+fn job_id_from_url(job_url: &str) -> Option<String> {
+    job_url
+        .split('/')
+        .next_back()
+        .filter(|id| !id.is_empty())
+        .map(|id| id.to_string())
+}
+
+/// Every staging folder the files of a job may have been collected in
+pub fn temp_dirs_for(app: &tauri::AppHandle, job_url: &str) -> Vec<PathBuf> {
+    job_id_from_url(job_url)
+        .map(|id| staging::download_dirs(app, &id))
+        .unwrap_or_default()
+}
+
+fn run_download(
+    app: &tauri::AppHandle,
+    creds: Credentials,
+    request: &DownloadRequest,
+) -> Result<DownloadResponse, String> {
+    let job_url = request.job_url.as_str();
+    let job_id = job_id_from_url(job_url).ok_or("Invalid job URL")?;
+
+    // Check the expected size before downloading anything
+    let api = app
+        .state::<AppState>()
+        .client
+        .blocking_api(creds)
+        .map_err(|e| format!("Failed to create client: {}", e))?;
+    let record = api
+        .job_record(job_url)
+        .map_err(|e| format!("Failed to get job status: {}", e))?;
+    settle(app, &record.status);
+    let listing = api
+        .output_files(&record.status)
+        .map_err(|e| format!("Failed to list results: {}", e))?;
+    let (listing, mut excluded) = match &request.files {
+        Some(names) if names.is_empty() => return Err("No result files were picked".to_string()),
+        Some(names) => pick(listing, names)?,
+        None => patterns::select(app, listing)?,
+    };
+    if listing.is_empty() && !excluded.is_empty() {
+        return Err("Every result file is excluded by the download patterns".to_string());
+    }
+    let mut preflight = Preflight::new(
+        listing
+            .iter()
+            .map(|f| ResultFile {
+                filename: f.filename.clone(),
+                size: f.size,
+            })
+            .collect(),
+    );
+
+    let resolved = rules::resolve(
+        app,
+        request.output_dir.as_deref(),
+        record.status.tool_id.as_deref(),
+        &job_id,
+    )?;
+    let output_dir = resolved.directory.clone();
+    // Fixed here, so a later change of the setting doesn't move this download
+    let temp_dir = staging::staging_path(app, &format!("{}{}", staging::DOWNLOAD_PREFIX, job_id));
+    preflight.check_free_space(&output_dir, &temp_dir)?;
+    let threshold = size_stats::threshold(
+        app,
+        record.status.tool_id.as_deref(),
+        max_download_warn_bytes(app)?,
+    );
+    if let Some(response) = preflight.confirmation(threshold, request.confirmed) {
+        return Ok(response);
+    }
+
+    // Held until the staging folder is gone
+    let _claim = JobClaim::take(&job_id)?;
+
+    // Create temporary directory for downloads
+    // Partial files are kept on failure so a retry can resume them
+    std::fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp dir: {}", e))?;
+
+    // Download files to temp directory, emitting progress per chunk
+    let transfer = ActiveTransfer::new(app, &job_id, preflight.total_bytes);
+    let offsets = resume::Offsets::open(&temp_dir);
+    let fetcher = Fetcher {
+        app,
+        api: &api,
+        transfer: &transfer,
+        offsets: &offsets,
+        temp_dir: &temp_dir,
+        workers: download_concurrency(app),
+    };
+    let mut taken = reserved_names();
+    let names = unique_entry_names(&listing, request.preserve_tree, &mut taken)?;
+    let mut files = fetcher.fetch_all(&listing, &names)?;
+
+    // NSG sometimes writes the last files after the results are first
+    // listed; pick up whatever appeared meanwhile
+    let relisting = relist(
+        app,
+        &api,
+        &record.status,
+        &listing,
+        &excluded,
+        request.files.as_deref(),
+    );
+    let late_files: Vec<String> = relisting.added.iter().map(|f| f.filename.clone()).collect();
+    let late_names = unique_entry_names(&relisting.added, request.preserve_tree, &mut taken)?;
+    files.extend(fetcher.fetch_all(&relisting.added, &late_names)?);
+    let mut renamed = BTreeMap::new();
+    for (file, name) in listing
+        .iter()
+        .chain(&relisting.added)
+        .zip(names.iter().chain(&late_names))
+    {
+        if entry_name(&file.filename, request.preserve_tree)? != *name {
+            renamed.insert(file.filename.clone(), name.clone());
+        }
+    }
+    for file in &relisting.added {
+        preflight.files.push(ResultFile {
+            filename: file.filename.clone(),
+            size: file.size,
+        });
+        preflight.total_bytes += file.size;
+    }
+    let listing_changed = relisting.changed();
+    excluded.extend(relisting.excluded);
+    let vanished_files = relisting.vanished;
+
+    // Every listed file must have arrived with its advertised size
+    let sizes_verified = files.len() == preflight.files.len()
+        && files.iter().all(|f| {
+            std::fs::metadata(&f.path)
+                .map(|m| m.len() == f.size)
+                .unwrap_or(false)
+        });
+
+    // Create output zip file
+    let zip_filename = format!("nsg_results_{}.zip", job_id);
+    let zip_path = output_dir.join(&zip_filename);
+
+    // Ensure output directory exists
+    if let Some(parent) = zip_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create output dir: {}", e))?;
+    }
+
+    // Create zip archive
+    let zip_file =
+        File::create(&zip_path).map_err(|e| format!("Failed to create zip file: {}", e))?;
+    let mut zip = ZipWriter::new(zip_file);
+    let compression = compression::load(app);
+    let options = SimpleFileOptions::default().unix_permissions(0o755);
+
+    // Add each downloaded file to the zip
+    let mut checksums = BTreeMap::new();
+    let entry_names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
+    for file_info in files {
+        let file_name = &file_info.name;
+        let mut source = File::open(&file_info.path)
+            .map_err(|e| format!("Failed to read file {}: {}", file_name, e))?;
+
+        // Stream into the zip, hashing on the way. Entries of 4 GiB and up
+        // need zip64 headers, which the writer won't add on its own.
+        let size = source.metadata().map(|m| m.len()).unwrap_or(file_info.size);
+        let entry_options = compression
+            .options_for(file_name, options)
+            .large_file(size >= u32::MAX as u64);
+        zip.start_file(file_name.as_str(), entry_options)
+            .map_err(|e| format!("Failed to add file to zip: {}", e))?;
+        let (_, hash) = checksum::copy_hashed(&mut source, &mut zip)
+            .map_err(|e| format!("Failed to write file to zip: {}", e))?;
+        checksums.insert(file_name.clone(), hash);
+    }
+
+    zip.start_file(
+        CHECKSUMS_ENTRY,
+        compression.options_for(CHECKSUMS_ENTRY, options),
+    )
+    .map_err(|e| format!("Failed to add file to zip: {}", e))?;
+    zip.write_all(checksum::format_manifest(&checksums).as_bytes())
+        .map_err(|e| format!("Failed to write file to zip: {}", e))?;
+
+    // Describe the job and the archive contents
+    let manifest = JobInfoManifest {
+        job_id: record.status.job_id.clone(),
+        job_url: job_url.to_string(),
+        tool: record.status.tool_id.clone(),
+        job_stage: record.status.job_stage.clone(),
+        failed: record.status.failed,
+        date_submitted: record.status.date_submitted.clone(),
+        date_completed: record.status.date_completed.clone(),
+        compute_resource: record.extras.compute_resource.clone(),
+        tool_version: record.extras.tool_version.clone(),
+        metadata: record.extras.metadata.clone(),
+        downloaded_at: chrono::Utc::now().to_rfc3339(),
+        files: preflight.files.clone(),
+        excluded: excluded.clone(),
+        checksums,
+        preserve_tree: request.preserve_tree,
+        late_files: late_files.clone(),
+        vanished_files: vanished_files.clone(),
+        renamed,
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize job info: {}", e))?;
+    zip.start_file(
+        JOB_INFO_ENTRY,
+        compression.options_for(JOB_INFO_ENTRY, options),
+    )
+    .map_err(|e| format!("Failed to add file to zip: {}", e))?;
+    zip.write_all(&manifest_json)
+        .map_err(|e| format!("Failed to write file to zip: {}", e))?;
+
+    // Finalize zip
+    zip.finish()
+        .map_err(|e| format!("Failed to finalize zip: {}", e))?;
+
+    // Clean up temp directory
+    std::fs::remove_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to clean up temp dir: {}", e))?;
+
+    // Only purge the server copy once the local archive is known-good
+    // A job with files left out, or whose sizes or archive don't check out,
+    // keeps its server copy; `purged` tells the caller
+    let mut purged = false;
+    if request.delete_after_download
+        && excluded.is_empty()
+        && sizes_verified
+        && verify_archive_entries(&zip_path, preflight.files.len()).is_ok()
+    {
+        match api.delete_job(job_url) {
+            Ok(()) => {
+                purged = true;
+                app.state::<AppState>().monitor.expect_change();
+            }
+            Err(e) => background_errors::report(
+                app,
+                SOURCE,
+                ErrorKind::of(&e),
+                format!("Failed to delete results on server: {:#}", e),
+            ),
+        }
+    }
+
+    let zip_path_str = paths::to_ipc_string(&zip_path)?;
+    let history_id = history::new_entry_id();
+    let archive_bytes = std::fs::metadata(&zip_path).ok().map(|m| m.len());
+    if let Err(e) = history::append(
+        app,
+        DownloadHistoryEntry {
+            id: history_id.clone(),
+            job_id: job_id.clone(),
+            job_url: job_url.to_string(),
+            archive_path: zip_path_str.clone(),
+            file_count: preflight.files.len(),
+            total_bytes: preflight.total_bytes,
+            downloaded_at: chrono::Utc::now().to_rfc3339(),
+            purged_on_server: purged,
+            tool: record.status.tool_id.clone(),
+            archive_bytes,
+            deleted: None,
+            pinned: false,
+            archive_missing: false,
+            archive_trashed_at: None,
+        },
+    ) {
+        background_errors::report(
+            app,
+            SOURCE,
+            ErrorKind::Other,
+            format!("Failed to record download history: {}", e),
+        );
+    }
+
+    if resolved.source == DirSource::Explicit {
+        rules::remember(app, &job_id, &output_dir);
+    }
+
+    // Emit completion event, with what compression saved
+    let (compressed_bytes, uncompressed_bytes) = archive_totals(&zip_path)?;
+    drop(transfer);
+    anonymize::emit(
+        app,
+        crate::events::DOWNLOAD_COMPLETE,
+        DownloadComplete {
+            job_id: job_id.clone(),
+            output_dir: paths::to_ipc_string(&output_dir)?,
+            output_dir_chain: resolved.chain,
+            compressed_bytes,
+            uncompressed_bytes,
+            listing_changed,
+            late_files,
+            vanished_files,
+        },
+    );
+    notify::download_complete(
+        app,
+        &history_id,
+        &job_id,
+        record
+            .extras
+            .metadata
+            .get("clientJobName")
+            .map(|s| s.as_str()),
+        archive_bytes.unwrap_or(compressed_bytes),
+    );
+
+    Ok(DownloadResponse::Completed {
+        path: zip_path_str,
+        purged,
+        excluded,
+        files: entry_names,
+    })
+}
+
+/// Run a download while keeping it in the persisted queue, so an interrupted
+/// one can be offered again on the next launch
+pub fn run_queued(
+    app: &tauri::AppHandle,
+    creds: Credentials,
+    request: DownloadRequest,
+) -> Result<DownloadResponse, String> {
+    let queue_id = queue::add(app, &request);
+    if let Err(e) = &queue_id {
+        background_errors::report(
+            app,
+            SOURCE,
+            ErrorKind::Other,
+            format!("Failed to record queued download: {}", e),
+        );
+    }
+
+    let job_id = job_id_from_url(&request.job_url).unwrap_or_default();
+    let key = activity_key(&job_id);
+    activity_feed::record(
+        app,
+        Some(key.clone()),
+        Activity::new(
+            ActivityKind::Download,
+            ActivityStatus::Running,
+            "Downloading results",
+        )
+        .job(&job_id),
+    );
+
+    let result = crate::crash::catch("downloading", || run_download(app, creds, &request));
+
+    let outcome = match &result {
+        Ok(DownloadResponse::Completed { files, .. }) => Activity::new(
+            ActivityKind::Download,
+            ActivityStatus::Succeeded,
+            format!("Downloaded {} result files", files.len()),
+        ),
+        Ok(DownloadResponse::ConfirmationRequired { .. }) => Activity::new(
+            ActivityKind::Download,
+            ActivityStatus::Attention,
+            "Download waiting for confirmation of its size",
+        ),
+        Err(e) => Activity::new(
+            ActivityKind::Download,
+            ActivityStatus::Failed,
+            "Download failed",
+        )
+        .detail(e.clone()),
+    };
+    activity_feed::finish(app, &key, outcome);
+
+    if let Ok(id) = &queue_id {
+        let update = match &result {
+            Ok(_) => queue::remove(app, id),
+            Err(e) => queue::set_status(app, id, QueueStatus::Failed, Some(e.clone())),
+        };
+        if let Err(e) = update {
+            background_errors::report(
+                app,
+                SOURCE,
+                ErrorKind::Other,
+                format!("Failed to update download queue: {}", e),
+            );
+        }
+    }
+
+    result
+}
+
+/// One result file, as listed before downloading
+#[derive(Debug, Clone, Serialize)]
+pub struct ListedResultFile {
+    pub filename: String,
+    pub size: u64,
+    pub download_uri: String,
+}
+
+impl Anonymize for ListedResultFile {
+    fn anonymize(self) -> Self {
+        Self {
+            download_uri: anonymize_url(&self.download_uri),
+            ..self
+        }
+    }
+}
+
+/// Every result file of a job, regardless of the download patterns, for
+/// picking which ones download_results fetches
+#[tauri::command]
+pub async fn list_result_files(
+    job_url: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<ListedResultFile>, String> {
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let job_url = anonymize::real_url(&job_url);
+    let listing = tokio::task::spawn_blocking(move || {
+        let record = api.job_record(&job_url)?;
+        api.output_files(&record.status)
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+    .map_err(|e| format!("Failed to list results: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    let files = listing
+        .into_iter()
+        .map(|f| ListedResultFile {
+            filename: f.filename,
+            size: f.size,
+            download_uri: f.download_uri,
+        })
+        .collect::<Vec<_>>();
+    Ok(anonymize::outgoing(&app, files))
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn download_results(
+    job_url: String,
+    output_dir: Option<String>,
+    confirmed: Option<bool>,
+    delete_after_download: Option<bool>,
+    preserve_tree: Option<bool>,
+    // Filenames from list_result_files; None applies the download patterns
+    files: Option<Vec<String>>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<DownloadResponse, String> {
+    let creds = state.session.require()?.credentials;
+    let output_dir = match output_dir {
+        Some(dir) => Some(paths::to_ipc_string(&path_access::authorize_str(
+            &app, &dir,
+        )?)?),
+        None => None,
+    };
+
+    // The per-call flag always wins over the preference. In read-only mode
+    // an explicit request to delete is refused, the preference just ignored.
+    let delete_after_download = match delete_after_download {
+        Some(true) => {
+            mode::ensure_writable(&app, Msg::ActionDeleteAfterDownload)?;
+            true
+        }
+        Some(false) => false,
+        None => !mode::is_read_only(&app) && default_delete_after_download(&app)?,
+    };
+
+    let request = DownloadRequest {
+        job_url: anonymize::real_url(&job_url),
+        output_dir,
+        confirmed: confirmed.unwrap_or(false),
+        delete_after_download,
+        preserve_tree: preserve_tree.unwrap_or(false),
+        files,
+    };
+
+    tokio::task::spawn_blocking(move || run_queued(&app, creds, request))
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_max_download_warn_bytes(app: tauri::AppHandle) -> Result<u64, String> {
+    max_download_warn_bytes(&app)
+}
+
+#[tauri::command]
+pub async fn get_delete_after_download(app: tauri::AppHandle) -> Result<bool, String> {
+    default_delete_after_download(&app)
+}
+
+#[tauri::command]
+pub async fn set_delete_after_download(app: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+
+    let prefs = preferences::of(&app);
+
+    prefs.set("delete_after_download", json!(enabled));
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_max_download_warn_bytes(app: tauri::AppHandle, bytes: u64) -> Result<(), String> {
+    mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+
+    let prefs = preferences::of(&app);
+
+    prefs.set("max_download_warn_bytes", json!(bytes));
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_settle_delay_secs(app: tauri::AppHandle) -> Result<u64, String> {
+    Ok(settle_delay_secs(&app))
+}
+
+/// Seconds to wait after a job completed before downloading its results;
+/// 0 downloads at once
+#[tauri::command]
+pub async fn set_settle_delay_secs(app: tauri::AppHandle, secs: u64) -> Result<(), String> {
+    mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+    if secs > MAX_SETTLE_DELAY_SECS {
+        return Err(format!(
+            "The settle delay can be at most {} seconds",
+            MAX_SETTLE_DELAY_SECS
+        ));
+    }
+    preferences::of(&app).set(SETTLE_DELAY_KEY, json!(secs));
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_download_concurrency(app: tauri::AppHandle) -> Result<usize, String> {
+    Ok(download_concurrency(&app))
+}
+
+/// Number of result files a download fetches at the same time
+#[tauri::command]
+pub async fn set_download_concurrency(app: tauri::AppHandle, files: usize) -> Result<(), String> {
+    mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+    if !(1..=MAX_DOWNLOAD_CONCURRENCY).contains(&files) {
+        return Err(format!(
+            "Between 1 and {} files can be downloaded at a time",
+            MAX_DOWNLOAD_CONCURRENCY
+        ));
+    }
+    preferences::of(&app).set(CONCURRENCY_KEY, json!(files));
+    Ok(())
+}
+
+/// Called by the frontend once its listeners are registered, e.g. after a
+/// reload. Re-emits the latest progress of every file of every running
+/// download so the UI picks them all up again, returning how many, offers
+/// downloads interrupted by the last quit and reports a crash of the last
+/// run.
+#[tauri::command]
+pub async fn frontend_ready(app: tauri::AppHandle) -> Result<usize, String> {
+    let active: Vec<DownloadProgress> =
+        ACTIVE_TRANSFERS.lock().unwrap().values().cloned().collect();
+    for progress in &active {
+        anonymize::emit(&app, crate::events::DOWNLOAD_PROGRESS, progress.clone());
+    }
+    queue::announce_pending(&app);
+    crate::crash::announce(&app);
+    Ok(active.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(names: &[&str]) -> Vec<OutputFile> {
+        names
+            .iter()
+            .map(|name| OutputFile {
+                filename: name.to_string(),
+                download_uri: format!("https://example.org/{}", name),
+                size: 1,
+            })
+            .collect()
+    }
+
+    fn needing(total_bytes: u64) -> Preflight {
+        Preflight {
+            files: Vec::new(),
+            total_bytes,
+        }
+    }
+
+    #[test]
+    fn staging_and_archive_on_one_volume_need_room_twice() {
+        let dir = std::env::temp_dir();
+        let free = fs2::available_space(&dir).unwrap();
+        // Fits once, not twice
+        let preflight = needing(free / 10 * 6);
+        assert!(preflight
+            .check_free_space(&dir, &dir.join("staging"))
+            .is_err());
+        assert!(needing(free / 10 * 4)
+            .check_free_space(&dir, &dir.join("staging"))
+            .is_ok());
+    }
+
+    #[test]
+    fn flattened_names_are_made_unique() {
+        let files = listed(&["a/out.txt", "b/out.txt", "c/OUT.txt", "d/out.txt"]);
+        let names = unique_entry_names(&files, false, &mut reserved_names()).unwrap();
+        assert_eq!(
+            names,
+            ["out.txt", "out (2).txt", "OUT (3).txt", "out (4).txt"]
+        );
+    }
+
+    #[test]
+    fn a_name_taken_by_the_archive_is_numbered() {
+        let files = listed(&["run/job_info.json", "checksums.sha256"]);
+        let names = unique_entry_names(&files, false, &mut reserved_names()).unwrap();
+        assert_eq!(names, ["job_info (2).json", "checksums (2).sha256"]);
+    }
+
+    #[test]
+    fn late_files_avoid_the_names_already_given() {
+        let mut taken = reserved_names();
+        let first = unique_entry_names(&listed(&["a/log"]), false, &mut taken).unwrap();
+        let late = unique_entry_names(&listed(&["b/log"]), false, &mut taken).unwrap();
+        assert_eq!(first, ["log"]);
+        assert_eq!(late, ["log (2)"]);
+    }
+
+    #[test]
+    fn preserved_paths_only_change_on_a_clash() {
+        let files = listed(&["a/out.txt", "b/out.txt", "a/OUT.txt", ".hidden"]);
+        let names = unique_entry_names(&files, true, &mut reserved_names()).unwrap();
+        assert_eq!(
+            names,
+            ["a/out.txt", "b/out.txt", "a/OUT (2).txt", ".hidden"]
+        );
+    }
+
+    #[test]
+    fn local_names_keep_only_the_last_component() {
+        assert_eq!(local_file_name("out.txt").unwrap(), "out.txt");
+        assert_eq!(local_file_name("run/output/out.txt").unwrap(), "out.txt");
+        assert_eq!(local_file_name("run\\output\\out.txt").unwrap(), "out.txt");
+        assert_eq!(local_file_name("../../etc/passwd").unwrap(), "passwd");
+        assert_eq!(local_file_name("/etc/passwd").unwrap(), "passwd");
+        assert_eq!(
+            local_file_name("C:\\Windows\\evil.dll").unwrap(),
+            "evil.dll"
+        );
+    }
+
+    #[test]
+    fn local_names_that_are_no_file_are_refused() {
+        for name in ["", "..", "run/..", "/"] {
+            assert!(local_file_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn result_paths_are_normalized() {
+        assert_eq!(result_path("run/out.txt").unwrap(), "run/out.txt");
+        assert_eq!(result_path("run\\sub\\out.txt").unwrap(), "run/sub/out.txt");
+        assert_eq!(result_path("./run//out.txt").unwrap(), "run/out.txt");
+        assert_eq!(entry_name("run/out.txt", false).unwrap(), "out.txt");
+        assert_eq!(entry_name("run/out.txt", true).unwrap(), "run/out.txt");
+    }
+
+    #[test]
+    fn result_paths_that_would_escape_are_refused() {
+        for name in [
+            "../out.txt",
+            "run/../../out.txt",
+            "/etc/passwd",
+            "\\\\server\\share\\out.txt",
+            "C:\\Windows\\evil.dll",
+            "C:evil.dll",
+            "run/C:/out.txt",
+            "",
+            "./.",
+        ] {
+            assert!(result_path(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn a_job_is_downloaded_once_at_a_time() {
+        let first = JobClaim::take("NGBW-JOB-CLAIM-1").unwrap();
+        assert!(JobClaim::take("NGBW-JOB-CLAIM-1").is_err());
+        // Other jobs aren't held up
+        let other = JobClaim::take("NGBW-JOB-CLAIM-2").unwrap();
+        drop(first);
+        assert!(JobClaim::take("NGBW-JOB-CLAIM-1").is_ok());
+        drop(other);
+    }
+
+    fn names(files: &[OutputFile]) -> Vec<&str> {
+        files.iter().map(|f| f.filename.as_str()).collect()
+    }
+
+    fn no_patterns(listing: Vec<OutputFile>) -> (Vec<OutputFile>, Vec<ExcludedFile>) {
+        patterns::split(&patterns::DownloadPatterns::default(), listing).unwrap()
+    }
+
+    #[test]
+    fn files_listed_during_the_download_are_added() {
+        let first = listed(&["a.out", "b.out"]);
+        let second = listed(&["a.out", "b.out", "c.out"]);
+
+        let relisting = compare_listing(second, &first, &[], no_patterns);
+
+        assert_eq!(names(&relisting.added), ["c.out"]);
+        assert!(relisting.excluded.is_empty());
+        assert!(relisting.vanished.is_empty());
+        assert!(relisting.changed());
+    }
+
+    #[test]
+    fn files_gone_from_the_listing_are_vanished() {
+        let first = listed(&["a.out", "b.out", "c.out"]);
+        let second = listed(&["a.out"]);
+
+        let relisting = compare_listing(second, &first, &[], no_patterns);
+
+        assert!(relisting.added.is_empty());
+        assert_eq!(relisting.vanished, ["b.out", "c.out"]);
+        assert!(relisting.changed());
+    }
+
+    #[test]
+    fn an_unchanged_listing_is_not_reported_as_changed() {
+        let first = listed(&["a.out", "b.out"]);
+        let excluded = [ExcludedFile {
+            filename: "scratch.chk".to_string(),
+            size: 1,
+            pattern: Some("*.chk".to_string()),
+        }];
+        let second = listed(&["b.out", "scratch.chk", "a.out"]);
+
+        let relisting = compare_listing(second, &first, &excluded, |new| {
+            assert!(new.is_empty(), "known files must not be split again");
+            no_patterns(new)
+        });
+
+        assert!(!relisting.changed());
+    }
+
+    #[test]
+    fn late_files_go_through_the_download_patterns() {
+        let patterns = patterns::DownloadPatterns {
+            include: Vec::new(),
+            exclude: vec!["*.chk".to_string()],
+        };
+        let first = listed(&["a.out"]);
+        let second = listed(&["a.out", "late.out", "late.chk"]);
+
+        let relisting = compare_listing(second, &first, &[], |new| {
+            patterns::split(&patterns, new).unwrap()
+        });
+
+        assert_eq!(names(&relisting.added), ["late.out"]);
+        assert_eq!(relisting.excluded.len(), 1);
+        assert_eq!(relisting.excluded[0].filename, "late.chk");
+        assert_eq!(relisting.excluded[0].pattern.as_deref(), Some("*.chk"));
+        assert!(relisting.changed());
+    }
+
+    #[test]
+    fn only_picked_late_files_are_added() {
+        let picked = ["a.out".to_string(), "wanted.out".to_string()];
+        let first = listed(&["a.out"]);
+        let second = listed(&["a.out", "wanted.out", "other.out"]);
+
+        let relisting = compare_listing(second, &first, &[], |new| split_picked(new, &picked));
+
+        assert_eq!(names(&relisting.added), ["wanted.out"]);
+        assert_eq!(relisting.excluded.len(), 1);
+        assert_eq!(relisting.excluded[0].filename, "other.out");
+        assert_eq!(relisting.excluded[0].pattern, None);
+    }
+
+    #[test]
+    fn a_listing_that_shrinks_and_grows_reports_both() {
+        let first = listed(&["a.out", "b.out"]);
+        let second = listed(&["a.out", "c.out"]);
+
+        let relisting = compare_listing(second, &first, &[], no_patterns);
+
+        assert_eq!(names(&relisting.added), ["c.out"]);
+        assert_eq!(relisting.vanished, ["b.out"]);
+    }
+}
@@ -0,0 +1,317 @@
+// Checking files dropped on the window.
+//
+// The submit dialog opens as soon as something is dropped, so
+// inspect_dropped_paths has to answer before it renders: whether each path
+// exists, what it is, how big, and which tools it plausibly goes with. The
+// tools come from the file extensions inside it (a .hoc or .mod file means
+// NEURON, a .py file the Python tools, ...), filtered by the server's tool
+// list when the app has fetched one. Looking inside is bounded: a zip's
+// central directory and a folder walk both stop after MAX_ENTRIES entries
+// or SCAN_BUDGET, and a folder's size is then flagged as an estimate.
+// Dropped paths reach the backend through the webview's drop event before
+// the frontend hears of them, and are registered as picked there, so they
+// pass path_access like a path chosen in a dialog.
+
+use crate::path_access;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Paths looked at per call
+const MAX_PATHS: usize = 100;
+// Entries looked at inside one archive or folder
+const MAX_ENTRIES: usize = 5000;
+const SCAN_BUDGET: Duration = Duration::from_millis(20);
+
+// File extension -> tools it suggests, most likely first. Tools are listed
+// in this order when several extensions are present.
+const TOOL_HINTS: &[(&str, &[&str])] = &[
+    ("hoc", &["NEURON_EXPANSE", "CORENEURON_EXPANSE"]),
+    ("mod", &["NEURON_EXPANSE", "CORENEURON_EXPANSE"]),
+    (
+        "py",
+        &[
+            "PY_EXPANSE",
+            "GPU_PY_EXPANSE",
+            "PYTORCH_PY_EXPANSE",
+            "TENSORFLOW_PY_EXPANSE",
+        ],
+    ),
+    ("m", &["MATLAB_EXPANSE", "EEGLAB_EXPANSE"]),
+    ("g", &["SINGULARITY_PGENESIS24_EXPANSE"]),
+];
+
+// Folders a folder submit leaves out anyway
+const SKIPPED_DIRS: &[&str] = &[".git", "__pycache__"];
+
+// Tool IDs from the server's last tool list, once fetched
+static CATALOG: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Keep the server's tool list for matching dropped files against
+pub fn remember_catalog(tools: &[String]) {
+    *CATALOG.lock().unwrap() = Some(tools.to_vec());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DroppedKind {
+    ZipArchive,
+    PythonScript,
+    HocFile,
+    Folder,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DroppedPath {
+    pub path: String,
+    pub exists: bool,
+    pub is_file: bool,
+    pub is_dir: bool,
+    // A folder's total, or of the part walked when size_estimated
+    pub size: Option<u64>,
+    pub size_estimated: bool,
+    pub kind: DroppedKind,
+    // Plausible tools, most likely first
+    pub tools: Vec<String>,
+    pub warnings: Vec<String>,
+    // Set when the path can't be used at all
+    pub error: Option<String>,
+}
+
+impl DroppedPath {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            exists: false,
+            is_file: false,
+            is_dir: false,
+            size: None,
+            size_estimated: false,
+            kind: DroppedKind::Unknown,
+            tools: Vec::new(),
+            warnings: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+/// File extensions counted inside an archive or folder
+#[derive(Default)]
+struct Contents {
+    extensions: BTreeMap<String, usize>,
+    bytes: u64,
+    // Stopped at MAX_ENTRIES or SCAN_BUDGET
+    truncated: bool,
+}
+
+impl Contents {
+    fn add(&mut self, name: &str, bytes: u64) {
+        if let Some(ext) = extension(Path::new(name)) {
+            *self.extensions.entry(ext).or_default() += 1;
+        }
+        self.bytes = self.bytes.saturating_add(bytes);
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+fn is_zip(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|_| magic == *b"PK\x03\x04")
+}
+
+fn zip_contents(path: &Path, started: Instant) -> Result<Contents, String> {
+    let file = File::open(path).map_err(|e| format!("Can't read the archive: {}", e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Not a readable zip archive: {}", e))?;
+    let mut contents = Contents::default();
+    for i in 0..archive.len() {
+        if i >= MAX_ENTRIES || started.elapsed() > SCAN_BUDGET {
+            contents.truncated = true;
+            break;
+        }
+        if let Ok(entry) = archive.by_index_raw(i) {
+            contents.add(entry.name(), entry.size());
+        }
+    }
+    Ok(contents)
+}
+
+/// Walk a folder without following symlinks, within the entry and time
+/// bounds
+fn folder_contents(path: &Path, started: Instant) -> Contents {
+    let mut contents = Contents::default();
+    let mut pending = vec![path.to_path_buf()];
+    let mut seen = 0;
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            seen += 1;
+            if seen > MAX_ENTRIES || started.elapsed() > SCAN_BUDGET {
+                contents.truncated = true;
+                return contents;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let name = entry.file_name();
+            if file_type.is_dir() {
+                if !SKIPPED_DIRS.iter().any(|d| name == *d) {
+                    pending.push(entry.path());
+                }
+            } else if file_type.is_file() {
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                contents.add(&name.to_string_lossy(), size);
+            }
+        }
+    }
+    contents
+}
+
+/// Tools suggested by the extensions present, in TOOL_HINTS order
+fn suggested_tools(extensions: &BTreeMap<String, usize>) -> Vec<String> {
+    let catalog = CATALOG.lock().unwrap();
+    let mut tools: Vec<String> = Vec::new();
+    for (ext, hints) in TOOL_HINTS {
+        if !extensions.contains_key(*ext) {
+            continue;
+        }
+        for tool in *hints {
+            let offered = catalog.as_ref().is_none_or(|c| c.iter().any(|t| t == tool));
+            if offered && !tools.iter().any(|t| t == tool) {
+                tools.push(tool.to_string());
+            }
+        }
+    }
+    tools
+}
+
+fn inspect(
+    path: &str,
+    max_submit_bytes: u64,
+    warn_bytes: u64,
+    app: &tauri::AppHandle,
+) -> DroppedPath {
+    let mut result = DroppedPath::new(path);
+    let resolved = match path_access::authorize_str(app, path) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    let metadata = match std::fs::metadata(&resolved) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            result.error = Some(format!("Can't read it: {}", e));
+            return result;
+        }
+    };
+    result.exists = true;
+    result.is_file = metadata.is_file();
+    result.is_dir = metadata.is_dir();
+    let started = Instant::now();
+
+    let contents = if result.is_dir {
+        result.kind = DroppedKind::Folder;
+        let contents = folder_contents(&resolved, started);
+        result.size = Some(contents.bytes);
+        result.size_estimated = contents.truncated;
+        if contents.bytes > warn_bytes {
+            result.warnings.push(format!(
+                "Over {} bytes; submitting it will ask for confirmation before zipping",
+                warn_bytes
+            ));
+        }
+        contents
+    } else if result.is_file {
+        result.size = Some(metadata.len());
+        if metadata.len() > max_submit_bytes {
+            result.warnings.push(format!(
+                "{} bytes, over the {} byte submission limit",
+                metadata.len(),
+                max_submit_bytes
+            ));
+        }
+        match extension(&resolved).as_deref() {
+            _ if is_zip(&resolved) => {
+                result.kind = DroppedKind::ZipArchive;
+                zip_contents(&resolved, started).unwrap_or_else(|e| {
+                    result.warnings.push(e);
+                    Contents::default()
+                })
+            }
+            Some(ext @ ("py" | "hoc")) => {
+                result.kind = if ext == "py" {
+                    DroppedKind::PythonScript
+                } else {
+                    DroppedKind::HocFile
+                };
+                result.warnings.push(
+                    "NSG takes a zip archive; drop the folder holding this script instead"
+                        .to_string(),
+                );
+                let mut contents = Contents::default();
+                contents.add(path, metadata.len());
+                contents
+            }
+            Some("zip") => {
+                result.kind = DroppedKind::ZipArchive;
+                result
+                    .warnings
+                    .push("Named .zip but isn't a zip archive".to_string());
+                Contents::default()
+            }
+            _ => Contents::default(),
+        }
+    } else {
+        result.error = Some("Not a regular file or folder".to_string());
+        return result;
+    };
+
+    if contents.truncated {
+        result
+            .warnings
+            .push("Too many entries to look through; only the first part was checked".to_string());
+    }
+    result.tools = suggested_tools(&contents.extensions);
+    result
+}
+
+/// What each dropped path is and which tools it may be for. A path that
+/// can't be used has `error` set instead of failing the call.
+#[tauri::command]
+pub async fn inspect_dropped_paths(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<Vec<DroppedPath>, String> {
+    if paths.len() > MAX_PATHS {
+        return Err(format!(
+            "At most {} paths can be inspected at once",
+            MAX_PATHS
+        ));
+    }
+    let max_submit_bytes = crate::max_submit_bytes(&app);
+    let warn_bytes = crate::folder_submit::warn_bytes(&app);
+    tokio::task::spawn_blocking(move || {
+        paths
+            .iter()
+            .map(|path| inspect(path, max_submit_bytes, warn_bytes, &app))
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))
+}
@@ -0,0 +1,466 @@
+// Dry-run submissions.
+//
+// dry_run_submit goes through everything submit_job would do short of
+// creating a job, and reports each check as pass, warn or fail along with
+// the exact form fields that would be sent. Nothing is recorded: no job
+// appears in the list, the monitor isn't nudged and the offline queue isn't
+// touched. With `server_validate` the files are also sent to NSG's validate
+// endpoint, which checks the submission without creating a job.
+
+use crate::api::is_network_error;
+use crate::inputs::{self, InputBinding};
+use crate::lab_defaults::{self, ParamSource};
+use crate::param_lint::{self, LintFinding, LintSeverity};
+use crate::{preferences, secret_scan, AppState, SubmitOptions};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+// Bytes per second of recent uploads, as a moving average
+const UPLOAD_RATE_KEY: &str = "upload_bytes_per_sec";
+
+// Uploads smaller or quicker than this say more about latency than speed
+const MIN_RATE_SAMPLE_BYTES: u64 = 1024 * 1024;
+const MIN_RATE_SAMPLE_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// A multipart field as it would be sent; file contents are left out
+#[derive(Debug, Clone, Serialize)]
+pub struct FormField {
+    pub name: String,
+    pub value: Option<String>,
+    pub file_name: Option<String>,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunReport {
+    // The worst status of any check
+    pub verdict: CheckStatus,
+    pub checks: Vec<DryRunCheck>,
+    pub fields: Vec<FormField>,
+    pub upload_bytes: u64,
+    pub estimated_upload_secs: Option<u64>,
+    // Resource requests that look like mistakes
+    pub lint: Vec<LintFinding>,
+    // Which layer each field came from
+    pub param_sources: BTreeMap<String, ParamSource>,
+}
+
+struct Checks(Vec<DryRunCheck>);
+
+impl Checks {
+    fn add(&mut self, name: &'static str, status: CheckStatus, detail: impl Into<String>) {
+        self.0.push(DryRunCheck {
+            name,
+            status,
+            detail: detail.into(),
+        });
+    }
+}
+
+/// Fold a finished upload into the average used for estimates
+pub fn record_upload(app: &tauri::AppHandle, bytes: u64, elapsed: Duration) {
+    if bytes < MIN_RATE_SAMPLE_BYTES || elapsed < MIN_RATE_SAMPLE_TIME {
+        return;
+    }
+    let sample = bytes as f64 / elapsed.as_secs_f64();
+    let prefs = preferences::of(app);
+    let rate = match prefs.get(UPLOAD_RATE_KEY).and_then(|v| v.as_f64()) {
+        Some(previous) => previous * 0.7 + sample * 0.3,
+        None => sample,
+    };
+    prefs.set(UPLOAD_RATE_KEY, json!(rate));
+}
+
+fn upload_rate(app: &tauri::AppHandle) -> Option<f64> {
+    preferences::of(app)
+        .get(UPLOAD_RATE_KEY)
+        .and_then(|v| v.as_f64())
+        .filter(|rate| *rate > 0.0)
+}
+
+/// Seconds `bytes` should take to upload at recent speeds, if any are known
+pub fn estimated_upload_secs(app: &tauri::AppHandle, bytes: u64) -> Option<u64> {
+    upload_rate(app).map(|rate| (bytes as f64 / rate).ceil() as u64)
+}
+
+/// Live rate of an upload in progress, against the estimate made when it
+/// started
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadPace {
+    // None for the first second, which is mostly connection setup
+    pub rate_bytes_per_sec: Option<f64>,
+    pub estimated_total_secs: Option<u64>,
+    // How long the whole upload will take at the live rate
+    pub projected_total_secs: Option<u64>,
+    // Projected minus estimated; positive means running behind
+    pub drift_secs: Option<i64>,
+}
+
+pub struct PaceTracker {
+    started: Instant,
+    bytes_total: u64,
+    estimated_total_secs: Option<u64>,
+}
+
+impl PaceTracker {
+    pub fn start(app: &tauri::AppHandle, bytes_total: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            bytes_total,
+            estimated_total_secs: estimated_upload_secs(app, bytes_total),
+        }
+    }
+
+    pub fn at(&self, bytes_sent: u64) -> UploadPace {
+        let elapsed = self.started.elapsed();
+        let rate = (elapsed >= MIN_RATE_SAMPLE_TIME && bytes_sent > 0)
+            .then(|| bytes_sent as f64 / elapsed.as_secs_f64());
+        let projected = rate.map(|rate| (self.bytes_total as f64 / rate).ceil() as u64);
+        UploadPace {
+            rate_bytes_per_sec: rate,
+            estimated_total_secs: self.estimated_total_secs,
+            projected_total_secs: projected,
+            drift_secs: projected
+                .zip(self.estimated_total_secs)
+                .map(|(projected, estimated)| projected as i64 - estimated as i64),
+        }
+    }
+}
+
+/// Check that a zip input opens and has entries. Other files are sent as
+/// they are and not looked into.
+fn check_archive(path: &Path) -> Result<Option<usize>, String> {
+    let is_zip = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    if !is_zip {
+        return Ok(None);
+    }
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("{} is not a readable zip archive: {}", path.display(), e))?;
+    Ok(Some(archive.len()))
+}
+
+fn form_fields(
+    inputs: &[InputBinding],
+    tool: &str,
+    params: &BTreeMap<String, String>,
+) -> Vec<FormField> {
+    let text = |name: &str, value: &str| FormField {
+        name: name.to_string(),
+        value: Some(value.to_string()),
+        file_name: None,
+        size: None,
+    };
+
+    let mut fields = vec![text("tool", tool)];
+    fields.extend(inputs.iter().map(|input| {
+        FormField {
+            name: input.param_name.clone(),
+            value: None,
+            file_name: input
+                .file_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string()),
+            size: std::fs::metadata(&input.file_path).ok().map(|m| m.len()),
+        }
+    }));
+    fields.extend(params.iter().map(|(name, value)| text(name, value)));
+    fields
+}
+
+fn check_params(checks: &mut Checks, inputs: &[InputBinding], params: &BTreeMap<String, String>) {
+    let input_names: HashSet<&str> = inputs.iter().map(|i| i.param_name.as_str()).collect();
+    if let Some(name) = params.keys().find(|name| name.trim().is_empty()) {
+        checks.add(
+            "parameters",
+            CheckStatus::Fail,
+            format!("Parameter \"{}\" has no name", name),
+        );
+    } else if let Some(name) = params
+        .keys()
+        .find(|name| *name == "tool" || input_names.contains(name.as_str()))
+    {
+        checks.add(
+            "parameters",
+            CheckStatus::Fail,
+            format!(
+                "Parameter {} would clash with the tool or an input file",
+                name
+            ),
+        );
+    } else {
+        // There are no tool schemas in the app to check names and values
+        // against; only the server knows
+        checks.add(
+            "parameters",
+            CheckStatus::Warn,
+            format!(
+                "{} parameters; values are only checked by the server",
+                params.len()
+            ),
+        );
+    }
+}
+
+/// Everything short of submitting: inputs, archives, email options, the
+/// tool, parameters and an upload estimate, plus NSG's own validation if
+/// `server_validate` is set. Nothing about a dry run is recorded anywhere.
+#[tauri::command]
+pub async fn dry_run_submit(
+    inputs: Vec<InputBinding>,
+    tool: String,
+    options: Option<SubmitOptions>,
+    server_validate: Option<bool>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<DryRunReport, String> {
+    let inputs: Vec<InputBinding> = inputs
+        .into_iter()
+        .map(|input| InputBinding {
+            param_name: input.param_name.trim().to_string(),
+            ..input
+        })
+        .collect();
+    inputs::authorize(&app, &inputs)?;
+    let options = options.unwrap_or_default();
+    let params = options.params;
+    let mut checks = Checks(Vec::new());
+
+    let upload_bytes = match inputs::validate(&inputs, crate::max_submit_bytes(&app)) {
+        Ok(bytes) => {
+            checks.add(
+                "inputs",
+                CheckStatus::Pass,
+                format!("{} files, {} bytes", inputs.len(), bytes),
+            );
+            bytes
+        }
+        Err(e) => {
+            checks.add("inputs", CheckStatus::Fail, e);
+            0
+        }
+    };
+
+    for input in &inputs {
+        match check_archive(&input.file_path) {
+            Ok(Some(0)) => checks.add(
+                "archive",
+                CheckStatus::Warn,
+                format!("Input {} is an empty zip archive", input.param_name),
+            ),
+            Ok(Some(entries)) => checks.add(
+                "archive",
+                CheckStatus::Pass,
+                format!(
+                    "Input {}: zip archive with {} entries",
+                    input.param_name, entries
+                ),
+            ),
+            Ok(None) => {}
+            Err(e) => checks.add(
+                "archive",
+                CheckStatus::Fail,
+                format!("Input {}: {}", input.param_name, e),
+            ),
+        }
+    }
+
+    match lab_defaults::file_status(&app) {
+        Some(Ok(path)) => checks.add(
+            "lab_defaults",
+            CheckStatus::Pass,
+            format!("Lab defaults from {}", path),
+        ),
+        Some(Err(e)) => checks.add(
+            "lab_defaults",
+            CheckStatus::Warn,
+            format!("Lab defaults ignored: {}", e),
+        ),
+        None => {}
+    }
+
+    let layered = lab_defaults::layer(
+        &app,
+        &tool,
+        params.clone(),
+        options.notify,
+        options.notify_email.as_deref(),
+    );
+    let (params, param_sources) = match layered {
+        Ok(layered) => {
+            checks.add(
+                "email",
+                CheckStatus::Pass,
+                "Completion email options are valid",
+            );
+            (layered.params, layered.sources)
+        }
+        Err(e) => {
+            checks.add("email", CheckStatus::Fail, e);
+            (params, BTreeMap::new())
+        }
+    };
+
+    check_params(&mut checks, &inputs, &params);
+
+    let lint = match param_lint::lint(&app, &tool, &params) {
+        Ok(findings) => {
+            for finding in &findings {
+                let status = match finding.severity {
+                    LintSeverity::Warning => CheckStatus::Warn,
+                    LintSeverity::Info => CheckStatus::Pass,
+                };
+                checks.add("resources", status, finding.message.message.clone());
+            }
+            findings
+        }
+        Err(e) => {
+            checks.add("resources", CheckStatus::Fail, e);
+            Vec::new()
+        }
+    };
+
+    let estimated_upload_secs = match estimated_upload_secs(&app, upload_bytes) {
+        Some(secs) => {
+            checks.add(
+                "upload_estimate",
+                CheckStatus::Pass,
+                format!("About {} s at recent upload speeds", secs),
+            );
+            Some(secs)
+        }
+        None => {
+            checks.add(
+                "upload_estimate",
+                CheckStatus::Warn,
+                "No recent uploads to estimate the upload time from",
+            );
+            None
+        }
+    };
+
+    let fields = form_fields(&inputs, &tool, &params);
+
+    match state.session.snapshot() {
+        None => checks.add(
+            "tool",
+            CheckStatus::Warn,
+            "Not connected; the tool and server-side checks were skipped",
+        ),
+        Some(session) => {
+            if !options.ignore_secret_scan && secret_scan::enabled(&app) {
+                let scan_inputs = inputs.clone();
+                let scan_creds = session.credentials.clone();
+                let scan = tokio::task::spawn_blocking(move || {
+                    secret_scan::check(&scan_inputs, &scan_creds)
+                })
+                .await
+                .map_err(|e| format!("Task error: {}", e))?;
+                match scan {
+                    Ok(()) => checks.add(
+                        "secrets",
+                        CheckStatus::Pass,
+                        "No input contains the password or app key",
+                    ),
+                    Err(e) => checks.add("secrets", CheckStatus::Fail, e),
+                }
+            }
+
+            // Server validation uploads the files, so not when they are
+            // invalid or hold a secret
+            let inputs_ok = !checks.0.iter().any(|c| {
+                (c.name == "inputs" || c.name == "secrets") && c.status == CheckStatus::Fail
+            });
+            let validate = server_validate.unwrap_or(false) && inputs_ok;
+            let api = state.client.api(session.credentials).await?;
+            let server_tool = tool.clone();
+            let server_inputs = inputs.clone();
+            let server_params = params.clone();
+            let (tools, validation) = tokio::task::spawn_blocking(move || {
+                let tools = api.tool_ids();
+                let validation = validate
+                    .then(|| api.validate_job(&server_inputs, &server_tool, &server_params));
+                (tools, validation)
+            })
+            .await
+            .map_err(|e| format!("Task error: {}", e))?;
+            state.session.ensure_current(session.generation)?;
+
+            if let Ok(ids) = &tools {
+                crate::dropped_paths::remember_catalog(ids);
+            }
+            match tools {
+                Ok(ids) if ids.contains(&tool) => {
+                    checks.add("tool", CheckStatus::Pass, format!("{} is available", tool))
+                }
+                Ok(_) => checks.add(
+                    "tool",
+                    CheckStatus::Fail,
+                    format!("NSG has no tool {}", tool),
+                ),
+                Err(e) if is_network_error(&e) => checks.add(
+                    "tool",
+                    CheckStatus::Warn,
+                    format!("Couldn't reach NSG to check the tool: {}", e),
+                ),
+                Err(e) => checks.add("tool", CheckStatus::Warn, e.to_string()),
+            }
+
+            match validation {
+                None if server_validate.unwrap_or(false) => checks.add(
+                    "server_validation",
+                    CheckStatus::Warn,
+                    "Skipped because the inputs are invalid",
+                ),
+                None => {}
+                Some(Ok(None)) => checks.add(
+                    "server_validation",
+                    CheckStatus::Pass,
+                    "NSG accepted the submission",
+                ),
+                Some(Ok(Some(complaint))) => {
+                    checks.add("server_validation", CheckStatus::Fail, complaint)
+                }
+                Some(Err(e)) => checks.add("server_validation", CheckStatus::Warn, e.to_string()),
+            }
+        }
+    }
+
+    let verdict = checks
+        .0
+        .iter()
+        .map(|c| c.status)
+        .max()
+        .unwrap_or(CheckStatus::Pass);
+    Ok(DryRunReport {
+        verdict,
+        checks: checks.0,
+        fields,
+        upload_bytes,
+        estimated_upload_secs,
+        lint,
+        param_sources,
+    })
+}
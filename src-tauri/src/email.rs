@@ -0,0 +1,155 @@
+// Completion emails sent by NSG itself.
+//
+// CIPRES mails the account address when `metadata.statusEmail` is true, or
+// `metadata.emailAddress` when that is given. The GUI keeps a default
+// address in preferences and applies it to every submission unless the
+// submission overrides or disables it.
+
+use crate::i18n::Msg;
+use crate::preferences;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use tauri::Emitter;
+
+pub const STATUS_EMAIL_FIELD: &str = "metadata.statusEmail";
+pub const EMAIL_ADDRESS_FIELD: &str = "metadata.emailAddress";
+
+const LOCAL_PART_SPECIALS: &str = "!#$%&'*+/=?^_`{|}~.-";
+
+/// Check an address against the practical subset of RFC 5321: a dot-atom
+/// local part and a hostname with a top-level domain
+pub fn validate_email(email: &str) -> Result<(), String> {
+    let invalid = |reason: &str| Err(format!("Invalid email address \"{}\": {}", email, reason));
+
+    if email.len() > 254 {
+        return invalid("longer than 254 characters");
+    }
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return invalid("missing @");
+    };
+
+    if local.is_empty() || local.len() > 64 {
+        return invalid("the part before @ must be 1 to 64 characters");
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return invalid("misplaced dot before @");
+    }
+    if !local
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || LOCAL_PART_SPECIALS.contains(c))
+    {
+        return invalid("unsupported character before @");
+    }
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 {
+        return invalid("the domain needs a top-level domain");
+    }
+    for label in &labels {
+        if label.is_empty() || label.len() > 63 {
+            return invalid("empty or overlong domain label");
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return invalid("domain labels can't start or end with a hyphen");
+        }
+        if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return invalid("unsupported character in the domain");
+        }
+    }
+    let tld = labels[labels.len() - 1];
+    if tld.len() < 2 || !tld.chars().all(|c| c.is_ascii_alphabetic()) {
+        return invalid("invalid top-level domain");
+    }
+
+    Ok(())
+}
+
+fn stored_default(app: &tauri::AppHandle) -> Result<Option<String>, String> {
+    let prefs = preferences::of(app);
+
+    Ok(prefs
+        .get("notification_email")
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .filter(|s| !s.is_empty()))
+}
+
+/// Form fields for a submission. `notify: Some(false)` turns the email off;
+/// otherwise the explicit address, or else a valid default, is used. Without
+/// either NSG falls back to the account's address.
+pub fn submission_fields(
+    app: &tauri::AppHandle,
+    notify: Option<bool>,
+    notify_email: Option<&str>,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut fields = BTreeMap::new();
+    if notify == Some(false) {
+        fields.insert(STATUS_EMAIL_FIELD.to_string(), "false".to_string());
+        return Ok(fields);
+    }
+
+    fields.insert(STATUS_EMAIL_FIELD.to_string(), "true".to_string());
+    let address = match notify_email.map(str::trim).filter(|e| !e.is_empty()) {
+        Some(email) => {
+            validate_email(email)?;
+            Some(email.to_string())
+        }
+        // An invalid default was already flagged at connect time; don't fail
+        // the submission over it
+        None => stored_default(app)?.filter(|e| validate_email(e).is_ok()),
+    };
+    if let Some(address) = address {
+        fields.insert(EMAIL_ADDRESS_FIELD.to_string(), address);
+    }
+
+    Ok(fields)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InvalidNotificationEmail {
+    pub email: String,
+    pub message: String,
+}
+
+/// Emit `notification-email-invalid` if the stored default doesn't validate,
+/// e.g. one saved by an older version. Called after connecting.
+pub fn check_stored_default(app: &tauri::AppHandle) {
+    if let Ok(Some(email)) = stored_default(app) {
+        if let Err(message) = validate_email(&email) {
+            let _ = app.emit(
+                crate::events::NOTIFICATION_EMAIL_INVALID,
+                InvalidNotificationEmail { email, message },
+            );
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_default_notification_email(
+    app: tauri::AppHandle,
+) -> Result<Option<String>, String> {
+    stored_default(&app)
+}
+
+/// Set the default completion email address; `None` or an empty string
+/// clears it, so NSG uses the account's address
+#[tauri::command]
+pub async fn set_default_notification_email(
+    app: tauri::AppHandle,
+    email: Option<String>,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeNotificationSettings)?;
+
+    let email = email
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty());
+    if let Some(email) = &email {
+        validate_email(email)?;
+    }
+
+    let prefs = preferences::of(&app);
+
+    prefs.set("notification_email", json!(email.unwrap_or_default()));
+
+    Ok(())
+}
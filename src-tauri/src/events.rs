@@ -0,0 +1,196 @@
+// Names of every event the backend emits.
+//
+// Emit through these constants rather than string literals, so `ALL` stays
+// the complete list that headless mode forwards; the test below fails on
+// any `emit(` whose event name is a literal missing from it.
+
+pub const ACCESSIBILITY_HINTS_CHANGED: &str = "accessibility-hints-changed";
+pub const ACCOUNT_MISMATCH: &str = "account-mismatch";
+pub const ACCOUNT_NAMESPACE_CHANGED: &str = "account-namespace-changed";
+pub const ACTIVITY: &str = "activity";
+pub const APP_DATA_PURGED: &str = "app-data-purged";
+pub const APP_MODE_CHANGED: &str = "app-mode-changed";
+pub const ARCHIVE_CLEANUP_REPORT: &str = "archive-cleanup-report";
+pub const AUTO_CONNECT_FAILED: &str = "auto-connect-failed";
+pub const BACKGROUND_ERROR: &str = "background-error";
+pub const BACKGROUND_ERROR_RESOLVED: &str = "background-error-resolved";
+pub const CLOCK_SKEW_DETECTED: &str = "clock-skew-detected";
+pub const CONNECT_PROGRESS: &str = "connect-progress";
+pub const CONNECTED: &str = "connected";
+pub const CONNECTIVITY_CHANGED: &str = "connectivity-changed";
+pub const DATA_LOCATION_CONFLICT: &str = "data-location-conflict";
+pub const DISCONNECTED: &str = "disconnected";
+pub const DOWNLOAD_COMPLETE: &str = "download-complete";
+pub const DOWNLOAD_NOTIFICATION: &str = "download-notification";
+pub const DOWNLOAD_PROGRESS: &str = "download-progress";
+pub const JOB_CONTEXT: &str = "job-context";
+pub const JOB_DELETED: &str = "job-deleted";
+pub const JOB_STATUS_CHANGED: &str = "job-status-changed";
+pub const JOBS_DIFF: &str = "jobs-diff";
+pub const JOBS_REFRESHED: &str = "jobs-refreshed";
+pub const JOBS_REMOVED_ON_SERVER: &str = "jobs-removed-on-server";
+pub const LAB_DEFAULTS_INVALID: &str = "lab-defaults-invalid";
+pub const LOCAL_DELETE: &str = "local-delete";
+pub const LOCAL_DELETE_UNDONE: &str = "local-delete-undone";
+pub const MONITOR_STATE_CHANGED: &str = "monitor-state-changed";
+pub const NETWORK_ACTIVITY: &str = "network-activity";
+pub const NOTIFICATION_EMAIL_INVALID: &str = "notification-email-invalid";
+pub const PENDING_DOWNLOADS_FOUND: &str = "pending-downloads-found";
+pub const PREFERENCES_REPAIRED: &str = "preferences-repaired";
+pub const PREVIOUS_SESSION_CRASHED: &str = "previous-session-crashed";
+pub const QUEUED_SUBMISSION_FAILED: &str = "queued-submission-failed";
+pub const QUEUED_SUBMISSION_SENT: &str = "queued-submission-sent";
+pub const RESULTS_EXPIRING_SOON: &str = "results-expiring-soon";
+pub const REVEAL_DOWNLOAD: &str = "reveal-download";
+pub const SERVICE_ANNOUNCEMENT: &str = "service-announcement";
+pub const SESSION_STATUS: &str = "session-status";
+pub const STATE_FILES_REPAIRED: &str = "state-files-repaired";
+pub const STATE_VERSION_MISMATCH: &str = "state-version-mismatch";
+pub const SUBMISSION_CONFIRMED: &str = "submission-confirmed";
+pub const SWEEP_PROGRESS: &str = "sweep-progress";
+pub const UPLOAD_PROGRESS: &str = "upload-progress";
+pub const ZIP_PROGRESS: &str = "zip-progress";
+
+/// Every event name above
+#[cfg(any(test, feature = "headless-invoke"))]
+pub const ALL: &[&str] = &[
+    ACCESSIBILITY_HINTS_CHANGED,
+    ACCOUNT_MISMATCH,
+    ACCOUNT_NAMESPACE_CHANGED,
+    ACTIVITY,
+    APP_DATA_PURGED,
+    APP_MODE_CHANGED,
+    ARCHIVE_CLEANUP_REPORT,
+    AUTO_CONNECT_FAILED,
+    BACKGROUND_ERROR,
+    BACKGROUND_ERROR_RESOLVED,
+    CLOCK_SKEW_DETECTED,
+    CONNECT_PROGRESS,
+    CONNECTED,
+    CONNECTIVITY_CHANGED,
+    DATA_LOCATION_CONFLICT,
+    DISCONNECTED,
+    DOWNLOAD_COMPLETE,
+    DOWNLOAD_NOTIFICATION,
+    DOWNLOAD_PROGRESS,
+    JOB_CONTEXT,
+    JOB_DELETED,
+    JOB_STATUS_CHANGED,
+    JOBS_DIFF,
+    JOBS_REFRESHED,
+    JOBS_REMOVED_ON_SERVER,
+    LAB_DEFAULTS_INVALID,
+    LOCAL_DELETE,
+    LOCAL_DELETE_UNDONE,
+    MONITOR_STATE_CHANGED,
+    NETWORK_ACTIVITY,
+    NOTIFICATION_EMAIL_INVALID,
+    PENDING_DOWNLOADS_FOUND,
+    PREFERENCES_REPAIRED,
+    PREVIOUS_SESSION_CRASHED,
+    QUEUED_SUBMISSION_FAILED,
+    QUEUED_SUBMISSION_SENT,
+    RESULTS_EXPIRING_SOON,
+    REVEAL_DOWNLOAD,
+    SERVICE_ANNOUNCEMENT,
+    SESSION_STATUS,
+    STATE_FILES_REPAIRED,
+    STATE_VERSION_MISMATCH,
+    SUBMISSION_CONFIRMED,
+    SWEEP_PROGRESS,
+    UPLOAD_PROGRESS,
+    ZIP_PROGRESS,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Splits call arguments at top-level commas, stopping at the closing
+    // paren; good enough for the emit calls in this crate
+    fn call_args(source: &str) -> Vec<String> {
+        let mut args = vec![String::new()];
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
+        for c in source.chars() {
+            if in_string {
+                in_string = c != '"' || escaped;
+                escaped = c == '\\' && !escaped;
+            } else {
+                match c {
+                    '"' => in_string = true,
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' | '}' if depth == 0 => break,
+                    ')' | ']' | '}' => depth -= 1,
+                    ',' if depth == 0 => {
+                        args.push(String::new());
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            args.last_mut().unwrap().push(c);
+        }
+        args.into_iter().map(|a| a.trim().to_string()).collect()
+    }
+
+    fn literal(arg: &str) -> Option<&str> {
+        arg.strip_prefix('"')?.strip_suffix('"')
+    }
+
+    #[test]
+    fn every_emitted_literal_is_listed() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut missing = Vec::new();
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|e| e != "rs") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            for (call, skip) in [("emit(", 0), ("emit_to(", 1)] {
+                for (at, _) in source.match_indices(call) {
+                    let preceding = source[..at].chars().next_back();
+                    if preceding.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                        continue;
+                    }
+                    // app.emit(event, ..), anonymize::emit(app, event, ..)
+                    // and window.emit_to(label, event, ..)
+                    let args = call_args(&source[at + call.len()..]);
+                    for arg in args.iter().skip(skip).take(2) {
+                        if let Some(name) = literal(arg) {
+                            if !ALL.contains(&name) {
+                                missing.push(format!("{}: {name}", path.display()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert!(missing.is_empty(), "events missing from ALL: {missing:?}");
+    }
+
+    #[test]
+    fn every_constant_is_in_all() {
+        let source = include_str!("events.rs");
+        let declared: Vec<&str> = source
+            .lines()
+            .filter_map(|l| l.strip_prefix("pub const ")?.split_once(": &str = \""))
+            .map(|(_, rest)| rest.trim_end_matches("\";"))
+            .collect();
+        assert_eq!(declared.len(), ALL.len());
+        for name in declared {
+            assert!(ALL.contains(&name), "{name} is not in ALL");
+        }
+    }
+
+    #[test]
+    fn call_args_respects_nesting_and_strings() {
+        assert_eq!(
+            call_args("app, \"a-b\", json!({ \"x\": f(1, 2) }));"),
+            vec!["app", "\"a-b\"", "json!({ \"x\": f(1, 2) })"]
+        );
+        assert_eq!(call_args("\"a,)\", x)"), vec!["\"a,)\"", "x"]);
+    }
+}
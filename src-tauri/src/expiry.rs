@@ -0,0 +1,179 @@
+// When NSG deletes a job's results.
+//
+// NSG purges results some time after a job completes. When the job record
+// gives an expiration date, that's used; otherwise it's estimated as the
+// completion date plus the `results_retention_days` preference. After each
+// full refresh, completed jobs within `expiry_warning_days` of that date
+// that have no entry in the download history are reported once per session
+// as `results-expiring-soon`.
+
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, Anonymize};
+use crate::i18n::Msg;
+use crate::monitor::parse_date;
+use crate::{clock, history, preferences, JobSummary};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+pub const RETENTION_KEY: &str = "results_retention_days";
+pub const WARNING_KEY: &str = "expiry_warning_days";
+
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+pub const DEFAULT_WARNING_DAYS: u64 = 3;
+pub const MAX_DAYS: u64 = 365;
+
+// Real IDs of jobs already warned about, with the session generation
+static WARNED: Mutex<Option<(u64, HashSet<String>)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ExpirySettings {
+    pub retention_days: u64,
+    pub warning_days: u64,
+}
+
+impl Default for ExpirySettings {
+    fn default() -> Self {
+        Self {
+            retention_days: DEFAULT_RETENTION_DAYS,
+            warning_days: DEFAULT_WARNING_DAYS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiringJob {
+    pub job_id: String,
+    pub url: String,
+    pub tool: Option<String>,
+    pub results_expire_at: String,
+}
+
+impl Anonymize for ExpiringJob {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            url: anonymize_url(&self.url),
+            ..self
+        }
+    }
+}
+
+pub fn load(app: &tauri::AppHandle) -> ExpirySettings {
+    let prefs = preferences::of(app);
+    let days = |key: &str, default: u64| -> u64 {
+        prefs
+            .get(key)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(default)
+            .clamp(1, MAX_DAYS)
+    };
+    ExpirySettings {
+        retention_days: days(RETENTION_KEY, DEFAULT_RETENTION_DAYS),
+        warning_days: days(WARNING_KEY, DEFAULT_WARNING_DAYS),
+    }
+}
+
+/// The reported expiration date, or one estimated from the completion date
+pub fn expires_at(
+    reported: Option<&str>,
+    date_completed: Option<&str>,
+    settings: ExpirySettings,
+) -> Option<String> {
+    if let Some(reported) = reported {
+        return Some(reported.to_string());
+    }
+    let completed = parse_date(date_completed)?;
+    let days = chrono::Duration::days(settings.retention_days as i64);
+    Some((completed + days).to_rfc3339())
+}
+
+/// Completed, not yet expired, and due to expire within the warning window
+pub fn is_expiring_soon(
+    job: &JobSummary,
+    now: chrono::DateTime<chrono::Utc>,
+    settings: ExpirySettings,
+) -> bool {
+    if job.failed || job.job_stage.as_deref() != Some("COMPLETED") {
+        return false;
+    }
+    let Some(expires) = parse_date(job.results_expire_at.as_deref()) else {
+        return false;
+    };
+    let window = chrono::Duration::days(settings.warning_days as i64);
+    expires > now && expires - now <= window
+}
+
+/// Warn about jobs from a fresh listing (real IDs) whose results expire
+/// soon and were never downloaded. Each job is reported once per session.
+pub fn check(app: &tauri::AppHandle, generation: u64, jobs: &[JobSummary]) {
+    let settings = load(app);
+    let now = clock::now();
+
+    let candidates: Vec<&JobSummary> = jobs
+        .iter()
+        .filter(|j| is_expiring_soon(j, now, settings))
+        .collect();
+    if candidates.is_empty() {
+        return;
+    }
+
+    // Without the history every job looks undownloaded; better to skip a
+    // round than to warn about results that are already saved
+    let Ok(history) = history::load(app) else {
+        return;
+    };
+    let downloaded: HashSet<&str> = history.iter().map(|e| e.job_id.as_str()).collect();
+
+    let mut warned = WARNED.lock().unwrap();
+    if warned.as_ref().is_none_or(|(g, _)| *g != generation) {
+        *warned = Some((generation, HashSet::new()));
+    }
+    let Some((_, seen)) = warned.as_mut() else {
+        return;
+    };
+
+    let expiring: Vec<ExpiringJob> = candidates
+        .into_iter()
+        .filter(|j| !downloaded.contains(j.job_id.as_str()))
+        .filter(|j| seen.insert(j.job_id.clone()))
+        .map(|j| ExpiringJob {
+            job_id: j.job_id.clone(),
+            url: j.url.clone(),
+            tool: j.tool.clone(),
+            results_expire_at: j.results_expire_at.clone().unwrap_or_default(),
+        })
+        .collect();
+    drop(warned);
+
+    if !expiring.is_empty() {
+        anonymize::emit(app, crate::events::RESULTS_EXPIRING_SOON, expiring);
+    }
+}
+
+#[tauri::command]
+pub async fn get_expiry_settings(app: tauri::AppHandle) -> Result<ExpirySettings, String> {
+    Ok(load(&app))
+}
+
+#[tauri::command]
+pub async fn set_expiry_settings(
+    app: tauri::AppHandle,
+    settings: ExpirySettings,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeNotificationSettings)?;
+    for (name, days) in [
+        ("Retention", settings.retention_days),
+        ("Warning window", settings.warning_days),
+    ] {
+        if !(1..=MAX_DAYS).contains(&days) {
+            return Err(format!("{} must be between 1 and {} days", name, MAX_DAYS));
+        }
+    }
+
+    preferences::of(&app).set_many([
+        (RETENTION_KEY, json!(settings.retention_days)),
+        (WARNING_KEY, json!(settings.warning_days)),
+    ]);
+    Ok(())
+}
@@ -0,0 +1,531 @@
+// Generated fake accounts for development.
+//
+// Built with the `fixtures` feature, which release bundles don't enable,
+// and into tests. generate(seed) returns the same account for the same seed on any
+// machine: jobs in every stage and across the common tools, each with the
+// message timeline NSG would have sent, results listings with the file
+// names and sizes real jobs produce, and the usual failures (rejected at
+// submission, crashed while running, finished with an error). Stages come
+// from job_query's STAGE_ORDER and timestamps are written the way
+// normalize_server_time leaves them, RFC 3339 in UTC, so the format is
+// defined in one place. Quoting the seed in a bug report is enough to
+// reproduce its data; export_fixture_account writes the account as JSON
+// for building the frontend against. The demo backend serves these accounts
+// and the API tests parse them, so both see the formats defined here.
+
+use crate::job_query::STAGE_ORDER;
+use serde::Serialize;
+
+// Timestamps count back from here rather than from now, so the output
+// doesn't depend on when it is generated
+const EPOCH: &str = "2024-03-01T00:00:00Z";
+const JOBS_PER_STAGE: usize = 3;
+
+// Tool -> files its jobs typically leave besides the standard ones
+const TOOLS: &[(&str, &[&str])] = &[
+    ("NEURON_EXPANSE", &["voltage_trace.dat", "spikes.dat"]),
+    ("CORENEURON_EXPANSE", &["out.dat", "spk0.dat"]),
+    ("PY_EXPANSE", &["results.csv", "figure_1.png"]),
+    ("PYTORCH_PY_EXPANSE", &["model.pt", "training_log.txt"]),
+    ("MATLAB_EXPANSE", &["output.mat", "summary.txt"]),
+    ("EEGLAB_EXPANSE", &["cleaned.set", "cleaned.fdt"]),
+    ("SINGULARITY_PGENESIS24_EXPANSE", &["Vm.out", "network.log"]),
+];
+
+// Every job that ran has these
+const STANDARD_FILES: &[&str] = &["STDOUT", "STDERR", "scheduler_stdout.txt", "output.tar.gz"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FixtureFailure {
+    // Refused before it was queued
+    RejectedAtSubmission,
+    // Stopped while running
+    CrashedWhileRunning,
+    // Reached COMPLETED but failed
+    CompletedWithError,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixtureMessage {
+    pub timestamp: String,
+    pub stage: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixtureFile {
+    pub filename: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixtureJob {
+    pub job_id: String,
+    pub url: String,
+    pub tool: String,
+    pub job_stage: String,
+    pub failed: bool,
+    pub failure: Option<FixtureFailure>,
+    pub date_submitted: String,
+    pub date_completed: Option<String>,
+    pub messages: Vec<FixtureMessage>,
+    // Empty until the job has run
+    pub results: Vec<FixtureFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixtureAccount {
+    pub seed: u64,
+    pub username: String,
+    pub jobs: Vec<FixtureJob>,
+}
+
+/// SplitMix64: small, and the same sequence on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in [low, high)
+    fn between(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next() % (high - low).max(1)
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.next() as usize % items.len()]
+    }
+}
+
+/// A timestamp in the canonical format
+pub fn timestamp(t: chrono::DateTime<chrono::Utc>) -> String {
+    t.to_rfc3339()
+}
+
+fn epoch() -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::parse_from_rfc3339(EPOCH)
+        .expect("EPOCH is RFC 3339")
+        .with_timezone(&chrono::Utc)
+}
+
+/// What NSG says on reaching `stage`
+fn stage_message(stage: &str, tool: &str, rng: &mut Rng) -> String {
+    match stage {
+        "QUEUE" => "Added to cipres run queue.".to_string(),
+        "COMMITTED" => format!("Command rendered successfully: {}", tool.to_lowercase()),
+        "SUBMITTED" => format!(
+            "Submitted to Expanse as job '{}'.",
+            rng.between(10_000_000, 40_000_000)
+        ),
+        "RUNNING" => "Job is running.".to_string(),
+        "COMPLETED" => "Output files retrieved.".to_string(),
+        _ => format!("Entered {}.", stage),
+    }
+}
+
+fn job(
+    rng: &mut Rng,
+    username: &str,
+    stage_index: usize,
+    failure: Option<FixtureFailure>,
+) -> FixtureJob {
+    let (tool, tool_files) = *rng.pick(TOOLS);
+    let job_id = format!("NGBW-JOB-{}-{:016X}{:016X}", tool, rng.next(), rng.next());
+    let submitted = epoch() - chrono::Duration::minutes(rng.between(60, 60 * 24 * 60) as i64);
+
+    let mut at = submitted;
+    let mut messages = Vec::new();
+    for stage in &STAGE_ORDER[..=stage_index] {
+        messages.push(FixtureMessage {
+            timestamp: timestamp(at),
+            stage: stage.to_string(),
+            text: stage_message(stage, tool, rng),
+        });
+        at += chrono::Duration::minutes(rng.between(1, 240) as i64);
+    }
+    let error_text = match failure {
+        Some(FixtureFailure::RejectedAtSubmission) => {
+            Some("Validation error: runtime must be between 0.1 and 48 hours")
+        }
+        Some(FixtureFailure::CrashedWhileRunning) => {
+            Some("Job terminated by the scheduler: exceeded the memory limit")
+        }
+        Some(FixtureFailure::CompletedWithError) => Some("Job finished with a non-zero exit code"),
+        None => None,
+    };
+    if let (Some(text), Some(last)) = (error_text, messages.last()) {
+        messages.push(FixtureMessage {
+            timestamp: timestamp(at),
+            stage: last.stage.clone(),
+            text: text.to_string(),
+        });
+    }
+
+    let stage = STAGE_ORDER[stage_index];
+    let finished = stage == "COMPLETED" || failure.is_some();
+    let ran = STAGE_ORDER[..=stage_index].contains(&"RUNNING");
+    let results = if ran && finished {
+        STANDARD_FILES
+            .iter()
+            .chain(tool_files.iter())
+            .map(|name| FixtureFile {
+                filename: name.to_string(),
+                size: match *name {
+                    "STDOUT" | "STDERR" | "scheduler_stdout.txt" => rng.between(200, 64 * 1024),
+                    "output.tar.gz" => rng.between(1 << 20, 2 << 30),
+                    _ => rng.between(4 * 1024, 256 << 20),
+                },
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    FixtureJob {
+        url: format!("{}/job/{}/{}", crate::api::base_url(), username, job_id),
+        job_id,
+        tool: tool.to_string(),
+        job_stage: stage.to_string(),
+        failed: failure.is_some(),
+        failure,
+        date_submitted: timestamp(submitted),
+        date_completed: finished.then(|| timestamp(at)),
+        messages,
+        results,
+    }
+}
+
+/// The account for `seed`
+pub fn generate(seed: u64) -> FixtureAccount {
+    let mut rng = Rng(seed);
+    let username = format!("fixture_{:04x}", seed & 0xffff);
+    let stage_of = |name: &str| {
+        STAGE_ORDER
+            .iter()
+            .position(|s| *s == name)
+            .expect("stage is in STAGE_ORDER")
+    };
+
+    let mut jobs = Vec::new();
+    for stage_index in 0..STAGE_ORDER.len() {
+        for _ in 0..JOBS_PER_STAGE {
+            jobs.push(job(&mut rng, &username, stage_index, None));
+        }
+    }
+    for (stage, failure) in [
+        ("QUEUE", FixtureFailure::RejectedAtSubmission),
+        ("RUNNING", FixtureFailure::CrashedWhileRunning),
+        ("COMPLETED", FixtureFailure::CompletedWithError),
+    ] {
+        jobs.push(job(&mut rng, &username, stage_of(stage), Some(failure)));
+    }
+    jobs.sort_by(|a, b| a.date_submitted.cmp(&b.date_submitted));
+
+    FixtureAccount {
+        seed,
+        username,
+        jobs,
+    }
+}
+
+#[cfg(any(test, feature = "headless-invoke"))]
+/// A job just submitted to `tool`, as NSG records it; `serial` makes its ID
+pub fn submitted_job(username: &str, tool: &str, serial: u64) -> FixtureJob {
+    use chrono::Timelike;
+    let now = chrono::Utc::now().with_nanosecond(0).unwrap_or_default();
+    let job_id = format!("NGBW-JOB-{}-{:032X}", tool, serial);
+    FixtureJob {
+        url: format!("{}/job/{}/{}", crate::api::base_url(), username, job_id),
+        job_id,
+        tool: tool.to_string(),
+        job_stage: STAGE_ORDER[0].to_string(),
+        failed: false,
+        failure: None,
+        date_submitted: timestamp(now),
+        date_completed: None,
+        messages: vec![FixtureMessage {
+            timestamp: timestamp(now),
+            stage: STAGE_ORDER[0].to_string(),
+            text: stage_message(STAGE_ORDER[0], tool, &mut Rng(serial)),
+        }],
+        results: Vec::new(),
+    }
+}
+
+#[cfg(any(test, feature = "headless-invoke"))]
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(any(test, feature = "headless-invoke"))]
+/// The `<toolList>` NSG sends, with every tool fixture jobs use
+pub fn tool_list_xml() -> String {
+    let tools: String = TOOLS
+        .iter()
+        .map(|(tool, _)| {
+            format!(
+                "<tool><toolId>{0}</toolId><toolName>{0}</toolName></tool>",
+                tool
+            )
+        })
+        .collect();
+    format!("<toolList><tools>{}</tools></toolList>", tools)
+}
+
+#[cfg(any(test, feature = "headless-invoke"))]
+impl FixtureAccount {
+    /// The `<joblist>` NSG sends for the account
+    pub fn job_list_xml(&self) -> String {
+        let jobs: String = self
+            .jobs
+            .iter()
+            .map(|job| format!("<jobstatus>{}</jobstatus>", job.self_uri_xml()))
+            .collect();
+        format!(
+            "<joblist><title>Submitted Jobs</title><jobs>{}</jobs></joblist>",
+            jobs
+        )
+    }
+}
+
+#[cfg(any(test, feature = "headless-invoke"))]
+impl FixtureJob {
+    fn self_uri_xml(&self) -> String {
+        format!(
+            "<selfUri><url>{}</url><rel>jobstatus</rel><title>{}</title></selfUri>",
+            escape(&self.url),
+            escape(&self.job_id)
+        )
+    }
+
+    /// Where the job's results are listed
+    pub fn results_url(&self) -> String {
+        format!("{}/output", self.url)
+    }
+
+    /// The `<jobstatus>` NSG sends for the job
+    pub fn status_xml(&self) -> String {
+        let messages: String = self
+            .messages
+            .iter()
+            .map(|m| {
+                format!(
+                    "<message><timestamp>{}</timestamp><stage>{}</stage><text>{}</text></message>",
+                    escape(&m.timestamp),
+                    escape(&m.stage),
+                    escape(&m.text)
+                )
+            })
+            .collect();
+        format!(
+            "<jobstatus>{}<jobHandle>{}</jobHandle><jobStage>{}</jobStage>\
+             <terminalStage>{}</terminalStage><failed>{}</failed>\
+             <dateSubmitted>{}</dateSubmitted>\
+             <resultsUri><url>{}</url><rel>results</rel><title>Job Results</title></resultsUri>\
+             <messages>{}</messages></jobstatus>",
+            self.self_uri_xml(),
+            escape(&self.job_id),
+            escape(&self.job_stage),
+            self.date_completed.is_some(),
+            self.failed,
+            escape(&self.date_submitted),
+            escape(&self.results_url()),
+            messages
+        )
+    }
+
+    /// The `<results>` listing NSG sends for the job; file `n` downloads
+    /// from `results_url()/n`
+    pub fn results_xml(&self) -> String {
+        let files: String = self
+            .results
+            .iter()
+            .enumerate()
+            .map(|(n, file)| {
+                format!(
+                    "<jobfile><downloadUri><url>{}/{}</url><rel>fileDownload</rel>\
+                     <title>{2}</title></downloadUri><jobHandle>{3}</jobHandle>\
+                     <filename>{2}</filename><length>{4}</length></jobfile>",
+                    escape(&self.results_url()),
+                    n,
+                    escape(&file.filename),
+                    escape(&self.job_id),
+                    file.size
+                )
+            })
+            .collect();
+        format!("<results><jobfiles>{}</jobfiles></results>", files)
+    }
+}
+
+/// Write the account for `seed` to `path` as JSON, returning the number of
+/// jobs in it
+fn export(seed: u64, path: &std::path::Path) -> Result<usize, String> {
+    let account = generate(seed);
+    let content = serde_json::to_vec_pretty(&account)
+        .map_err(|e| format!("Failed to serialize fixture account: {}", e))?;
+    crate::state_file::write_atomic(path, &content)?;
+    Ok(account.jobs.len())
+}
+
+/// `export` to a path the frontend may write to
+#[cfg(feature = "fixtures")]
+#[tauri::command]
+pub async fn export_fixture_account(
+    app: tauri::AppHandle,
+    seed: u64,
+    path: String,
+) -> Result<usize, String> {
+    let path = crate::path_access::authorize_str(&app, &path)?;
+    export(seed, &path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_gives_the_same_account() {
+        let first = serde_json::to_string(&generate(42)).unwrap();
+        assert_eq!(serde_json::to_string(&generate(42)).unwrap(), first);
+        assert_ne!(serde_json::to_string(&generate(43)).unwrap(), first);
+    }
+
+    #[test]
+    fn every_stage_and_failure_is_covered() {
+        let account = generate(7);
+        for stage in STAGE_ORDER {
+            assert!(
+                account
+                    .jobs
+                    .iter()
+                    .any(|j| j.job_stage == *stage && !j.failed),
+                "{}",
+                stage
+            );
+        }
+        for failure in [
+            FixtureFailure::RejectedAtSubmission,
+            FixtureFailure::CrashedWhileRunning,
+            FixtureFailure::CompletedWithError,
+        ] {
+            assert!(account.jobs.iter().any(|j| j.failure == Some(failure)));
+        }
+        assert_eq!(account.jobs.len(), STAGE_ORDER.len() * JOBS_PER_STAGE + 3);
+    }
+
+    #[test]
+    fn stages_and_timestamps_are_in_the_canonical_formats() {
+        for job in generate(1).jobs {
+            assert!(STAGE_ORDER.contains(&job.job_stage.as_str()));
+            let dates = job
+                .messages
+                .iter()
+                .map(|m| m.timestamp.clone())
+                .chain([job.date_submitted.clone()])
+                .chain(job.date_completed.clone());
+            for date in dates {
+                assert_eq!(
+                    crate::clock::normalize_server_time(Some(date.clone())),
+                    Some(date)
+                );
+            }
+            for message in &job.messages {
+                assert!(STAGE_ORDER.contains(&message.stage.as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn messages_follow_the_job_in_order() {
+        for job in generate(3).jobs {
+            let times: Vec<_> = job
+                .messages
+                .iter()
+                .map(|m| crate::clock::parse_server_time(&m.timestamp).unwrap())
+                .collect();
+            assert!(times.windows(2).all(|w| w[0] <= w[1]), "{}", job.job_id);
+            assert_eq!(job.messages[0].stage, STAGE_ORDER[0]);
+            assert_eq!(job.messages[0].timestamp, job.date_submitted);
+            assert!(job.url.ends_with(&job.job_id));
+        }
+    }
+
+    #[test]
+    fn only_finished_jobs_that_ran_have_results() {
+        for job in generate(5).jobs {
+            let finished = job.job_stage == "COMPLETED" || job.failed;
+            assert_eq!(finished, job.date_completed.is_some(), "{}", job.job_id);
+            if job.failure == Some(FixtureFailure::RejectedAtSubmission) || !finished {
+                assert!(job.results.is_empty(), "{}", job.job_id);
+            }
+            if job.job_stage == "COMPLETED" {
+                let names: Vec<_> = job.results.iter().map(|f| f.filename.as_str()).collect();
+                for standard in STANDARD_FILES {
+                    assert!(names.contains(standard), "{}", job.job_id);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_xml_reads_back_as_the_account() {
+        let account = generate(11);
+        let listed = nsg_cli::models::parse_job_list(&account.job_list_xml()).unwrap();
+        assert_eq!(listed.len(), account.jobs.len());
+        for (summary, job) in listed.iter().zip(&account.jobs) {
+            assert_eq!(summary.job_id, job.job_id);
+            assert_eq!(summary.url, job.url);
+
+            let status = nsg_cli::models::parse_job_status(&job.status_xml()).unwrap();
+            assert_eq!(status.job_id, job.job_id);
+            assert_eq!(status.job_stage, job.job_stage);
+            assert_eq!(status.failed, job.failed);
+            assert_eq!(status.tool_id.as_deref(), Some(job.tool.as_str()));
+            assert_eq!(status.self_uri, job.url);
+            assert_eq!(status.results_uri, Some(job.results_url()));
+            assert_eq!(status.messages.len(), job.messages.len());
+
+            let files = nsg_cli::models::parse_output_files(&job.results_xml()).unwrap();
+            let sizes: Vec<_> = files.iter().map(|f| (f.filename.clone(), f.size)).collect();
+            let expected: Vec<_> = job
+                .results
+                .iter()
+                .map(|f| (f.filename.clone(), f.size))
+                .collect();
+            assert_eq!(sizes, expected);
+        }
+        let tools = crate::xml::parse_tool_ids(&tool_list_xml());
+        assert_eq!(tools.len(), TOOLS.len());
+    }
+
+    #[test]
+    fn a_submitted_job_is_queued() {
+        let job = submitted_job("fixture_0001", "PY_EXPANSE", 1);
+        let status = nsg_cli::models::parse_job_status(&job.status_xml()).unwrap();
+        assert_eq!(status.job_stage, STAGE_ORDER[0]);
+        assert_eq!(status.tool_id.as_deref(), Some("PY_EXPANSE"));
+        assert_eq!(
+            crate::clock::normalize_server_time(Some(job.date_submitted.clone())),
+            Some(job.date_submitted)
+        );
+    }
+
+    #[test]
+    fn export_writes_the_account() {
+        let path = crate::test_util::scratch("fixtures", "export").join("account.json");
+        let count = export(9, &path).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["seed"], 9);
+        assert_eq!(written["jobs"].as_array().unwrap().len(), count);
+    }
+}
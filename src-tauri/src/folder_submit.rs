@@ -0,0 +1,514 @@
+// Submitting a folder.
+//
+// NSG takes a single archive, so a folder is zipped first, into the staging
+// folder, and the zip is deleted once the upload is done. Everything lands
+// under one top-level directory named after the folder, which several NSG
+// tools expect. Entries matching an exclude pattern are left out, checked
+// against both their name and their path inside the folder, so "*.pyc" and
+// "data/raw/**" both work. Symlinks are followed; broken links and links
+// back into their own ancestors are skipped and listed. Before zipping, the
+// total size is compared with the `submit_zip_warn_bytes` threshold and the
+// caller has to confirm anything bigger. Progress is reported in two
+// phases: `zip-progress` while archiving, then `upload-progress`, which
+// carries the live rate and how far it has drifted from the estimate.
+
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, Anonymize};
+use crate::api::UploadProgress;
+use crate::i18n::Msg;
+use crate::inputs::InputBinding;
+use crate::lab_defaults::ParamSource;
+use crate::param_lint::LintFinding;
+use crate::{compression, dry_run, patterns, preferences, progress, staging, AppState};
+use crate::{SubmitOptions, SubmitResponse};
+use globset::GlobMatcher;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{Emitter, State};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+pub const EXCLUDE_KEY: &str = "submit_exclude_patterns";
+pub const WARN_BYTES_KEY: &str = "submit_zip_warn_bytes";
+
+// Always applied, before the user's own patterns
+pub const DEFAULT_EXCLUDES: &[&str] = &[".git", "__pycache__", "*.pyc", ".DS_Store"];
+const DEFAULT_WARN_BYTES: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderSubmitSettings {
+    // In addition to DEFAULT_EXCLUDES
+    pub exclude_patterns: Vec<String>,
+    // Folders bigger than this need confirming; 0 never asks
+    pub warn_bytes: u64,
+}
+
+/// An entry left out by an exclude pattern
+#[derive(Debug, Clone, Serialize)]
+pub struct ExcludedEntry {
+    pub path: String,
+    pub pattern: String,
+    // A directory is left out with everything in it
+    pub is_dir: bool,
+}
+
+/// An entry that couldn't be archived
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedEntry {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FolderSubmitResponse {
+    // Nothing was zipped; call again with `confirmed`
+    ConfirmationRequired {
+        estimated_bytes: u64,
+        threshold_bytes: u64,
+        file_count: usize,
+        excluded: Vec<ExcludedEntry>,
+    },
+    Submitted {
+        job_id: String,
+        warning: Option<String>,
+        file_count: usize,
+        archive_bytes: u64,
+        excluded: Vec<ExcludedEntry>,
+        skipped: Vec<SkippedEntry>,
+        lint: Vec<LintFinding>,
+        param_sources: BTreeMap<String, ParamSource>,
+    },
+    // Uploaded, but NSG's answer couldn't be read; see unconfirmed
+    SubmittedButUnconfirmed {
+        id: String,
+        job_id: Option<String>,
+        job_url: Option<String>,
+        error: String,
+        file_count: usize,
+        archive_bytes: u64,
+    },
+}
+
+impl Anonymize for FolderSubmitResponse {
+    fn anonymize(self) -> Self {
+        match self {
+            FolderSubmitResponse::Submitted {
+                job_id,
+                warning,
+                file_count,
+                archive_bytes,
+                excluded,
+                skipped,
+                lint,
+                param_sources,
+            } => FolderSubmitResponse::Submitted {
+                job_id: anonymize_job_id(&job_id),
+                warning,
+                file_count,
+                archive_bytes,
+                excluded,
+                skipped,
+                lint,
+                param_sources,
+            },
+            FolderSubmitResponse::SubmittedButUnconfirmed {
+                id,
+                job_id,
+                job_url,
+                error,
+                file_count,
+                archive_bytes,
+            } => FolderSubmitResponse::SubmittedButUnconfirmed {
+                id,
+                job_id: job_id.map(|id| anonymize_job_id(&id)),
+                job_url: job_url.map(|url| anonymize_url(&url)),
+                error,
+                file_count,
+                archive_bytes,
+            },
+            confirmation => confirmation,
+        }
+    }
+}
+
+/// Payload of `zip-progress`
+#[derive(Debug, Clone, Serialize)]
+struct ZipProgress {
+    folder: String,
+    files_done: usize,
+    files_total: usize,
+    bytes_done: u64,
+    bytes_total: u64,
+}
+
+/// Payload of `upload-progress`
+#[derive(Debug, Clone, Serialize)]
+struct UploadProgressEvent {
+    folder: String,
+    bytes_sent: u64,
+    bytes_total: u64,
+    #[serde(flatten)]
+    pace: dry_run::UploadPace,
+}
+
+fn load(app: &tauri::AppHandle) -> FolderSubmitSettings {
+    let prefs = preferences::of(app);
+    FolderSubmitSettings {
+        exclude_patterns: prefs
+            .get(EXCLUDE_KEY)
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default(),
+        warn_bytes: prefs
+            .get(WARN_BYTES_KEY)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_WARN_BYTES),
+    }
+}
+
+/// Size above which a folder submit asks for confirmation
+pub fn warn_bytes(app: &tauri::AppHandle) -> u64 {
+    load(app).warn_bytes
+}
+
+struct WalkedFile {
+    path: PathBuf,
+    // Inside the folder, with `/` separators
+    relative: String,
+    size: u64,
+}
+
+#[derive(Default)]
+struct Walk {
+    files: Vec<WalkedFile>,
+    excluded: Vec<ExcludedEntry>,
+    skipped: Vec<SkippedEntry>,
+}
+
+impl Walk {
+    fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+}
+
+fn exclusion<'a>(
+    excludes: &'a [(String, GlobMatcher)],
+    name: &str,
+    relative: &str,
+) -> Option<&'a str> {
+    excludes
+        .iter()
+        .find(|(_, m)| m.is_match(name) || m.is_match(relative))
+        .map(|(pattern, _)| pattern.as_str())
+}
+
+/// Collect the files under `dir`. `ancestors` holds the canonical paths of
+/// the directories being walked, so a symlink back into one is caught.
+fn walk_dir(
+    dir: &Path,
+    prefix: &str,
+    excludes: &[(String, GlobMatcher)],
+    ancestors: &mut Vec<PathBuf>,
+    walk: &mut Walk,
+) -> Result<(), String> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .flatten()
+        .collect();
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = format!("{}{}", prefix, name);
+        let path = entry.path();
+        let skip = |walk: &mut Walk, reason: String| {
+            walk.skipped.push(SkippedEntry {
+                path: relative.clone(),
+                reason,
+            })
+        };
+
+        // Follows symlinks; fails for a broken one
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if path.is_symlink() => {
+                skip(walk, format!("Broken symlink: {}", e));
+                continue;
+            }
+            Err(e) => {
+                skip(walk, e.to_string());
+                continue;
+            }
+        };
+
+        if let Some(pattern) = exclusion(excludes, &name, &relative) {
+            walk.excluded.push(ExcludedEntry {
+                path: relative,
+                pattern: pattern.to_string(),
+                is_dir: metadata.is_dir(),
+            });
+            continue;
+        }
+
+        if metadata.is_dir() {
+            let canonical = match path.canonicalize() {
+                Ok(canonical) => canonical,
+                Err(e) => {
+                    skip(walk, e.to_string());
+                    continue;
+                }
+            };
+            if ancestors.contains(&canonical) {
+                skip(
+                    walk,
+                    "Symlink loop: points back into a folder containing it".to_string(),
+                );
+                continue;
+            }
+            ancestors.push(canonical);
+            let result = walk_dir(&path, &format!("{}/", relative), excludes, ancestors, walk);
+            ancestors.pop();
+            if let Err(e) = result {
+                skip(walk, e);
+            }
+        } else if metadata.is_file() {
+            walk.files.push(WalkedFile {
+                path,
+                relative,
+                size: metadata.len(),
+            });
+        } else {
+            skip(walk, "Not a regular file".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn walk(folder: &Path, excludes: &[(String, GlobMatcher)]) -> Result<Walk, String> {
+    let canonical = folder
+        .canonicalize()
+        .map_err(|e| format!("Failed to open {}: {}", folder.display(), e))?;
+    if !canonical.is_dir() {
+        return Err(format!("Not a folder: {}", folder.display()));
+    }
+
+    let mut walk = Walk::default();
+    walk_dir(folder, "", excludes, &mut vec![canonical], &mut walk)?;
+    if walk.files.is_empty() {
+        return Err(format!(
+            "Nothing to submit: {} has no files left after exclusions",
+            folder.display()
+        ));
+    }
+    Ok(walk)
+}
+
+/// Zip the walked files into `zip_path`, under `top_dir/`
+fn write_zip(
+    app: &tauri::AppHandle,
+    folder_label: &str,
+    top_dir: &str,
+    walk: &Walk,
+    zip_path: &Path,
+) -> Result<u64, String> {
+    let zip_file =
+        File::create(zip_path).map_err(|e| format!("Failed to create zip file: {}", e))?;
+    let mut zip = ZipWriter::new(zip_file);
+    let compression = compression::load(app);
+    let options = SimpleFileOptions::default().unix_permissions(0o755);
+
+    let throttle = progress::Gate::new();
+    let bytes_total = walk.total_bytes();
+    let mut bytes_done = 0;
+    for (i, file) in walk.files.iter().enumerate() {
+        let mut source = File::open(&file.path)
+            .map_err(|e| format!("Failed to read {}: {}", file.relative, e))?;
+        let entry_name = format!("{}/{}", top_dir, file.relative);
+        let entry_options = compression
+            .options_for(&entry_name, options)
+            .large_file(file.size >= u32::MAX as u64);
+        zip.start_file(entry_name.as_str(), entry_options)
+            .map_err(|e| format!("Failed to add file to zip: {}", e))?;
+        bytes_done += std::io::copy(&mut source, &mut zip)
+            .map_err(|e| format!("Failed to write {} to zip: {}", file.relative, e))?;
+
+        let files_done = i + 1;
+        if throttle.due(files_done == walk.files.len()) {
+            let _ = app.emit(
+                crate::events::ZIP_PROGRESS,
+                ZipProgress {
+                    folder: folder_label.to_string(),
+                    files_done,
+                    files_total: walk.files.len(),
+                    bytes_done,
+                    bytes_total,
+                },
+            );
+        }
+    }
+
+    zip.finish()
+        .map_err(|e| format!("Failed to finish zip: {}", e))?;
+    std::fs::metadata(zip_path)
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to read zip file: {}", e))
+}
+
+/// Deletes the temporary zip however the submission ends
+struct TempZip(PathBuf);
+
+impl Drop for TempZip {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Zip a folder and submit it as the tool's primary input. Folders bigger
+/// than the warning threshold come back as ConfirmationRequired until
+/// `confirmed` is set. Folder submissions are never queued offline.
+#[tauri::command]
+pub async fn submit_folder(
+    folder: String,
+    tool: String,
+    options: Option<SubmitOptions>,
+    confirmed: Option<bool>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<FolderSubmitResponse, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionSubmitJobs)?;
+    state.session.require()?;
+
+    let settings = load(&app);
+    let mut patterns: Vec<String> = DEFAULT_EXCLUDES.iter().map(|p| p.to_string()).collect();
+    patterns.extend(settings.exclude_patterns);
+    let excludes = patterns::compile(&patterns)?;
+
+    let folder_path = crate::path_access::authorize_str(&app, &folder)?;
+    let walk_path = folder_path.clone();
+    let walk = tokio::task::spawn_blocking(move || walk(&walk_path, &excludes))
+        .await
+        .map_err(|e| format!("Task error: {}", e))??;
+
+    let estimated_bytes = walk.total_bytes();
+    if settings.warn_bytes > 0
+        && estimated_bytes > settings.warn_bytes
+        && !confirmed.unwrap_or(false)
+    {
+        return Ok(FolderSubmitResponse::ConfirmationRequired {
+            estimated_bytes,
+            threshold_bytes: settings.warn_bytes,
+            file_count: walk.files.len(),
+            excluded: walk.excluded,
+        });
+    }
+
+    let top_dir = folder_path
+        .canonicalize()
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "input".to_string());
+    let nanos = chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0);
+    let zip_path = staging::staging_path(&app, &format!("nsg_submit_{}.zip", nanos));
+    let temp_zip = TempZip(zip_path.clone());
+
+    let zip_app = app.clone();
+    let label = folder.clone();
+    let (walk, archive_bytes) = tokio::task::spawn_blocking(move || {
+        let bytes = write_zip(&zip_app, &label, &top_dir, &walk, &zip_path)?;
+        Ok::<_, String>((walk, bytes))
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))??;
+
+    let progress_app = app.clone();
+    let throttle = progress::Gate::new();
+    let pace = dry_run::PaceTracker::start(&app, archive_bytes);
+    let progress: UploadProgress = Arc::new(move |bytes_sent| {
+        if throttle.due(bytes_sent >= archive_bytes) {
+            let _ = progress_app.emit(
+                crate::events::UPLOAD_PROGRESS,
+                UploadProgressEvent {
+                    folder: folder.clone(),
+                    bytes_sent,
+                    bytes_total: archive_bytes,
+                    pace: pace.at(bytes_sent),
+                },
+            );
+        }
+    });
+
+    let inputs = vec![InputBinding::primary(temp_zip.0.clone())];
+    let response =
+        crate::submit_with(inputs, tool, options, Some(progress), true, &app, &state).await?;
+    drop(temp_zip);
+
+    let response = match response {
+        SubmitResponse::Submitted {
+            job_id,
+            warning,
+            lint,
+            param_sources,
+            ..
+        } => FolderSubmitResponse::Submitted {
+            job_id,
+            warning,
+            file_count: walk.files.len(),
+            archive_bytes,
+            excluded: walk.excluded,
+            skipped: walk.skipped,
+            lint,
+            param_sources,
+        },
+        SubmitResponse::SubmittedButUnconfirmed {
+            id,
+            job_id,
+            job_url,
+            error,
+        } => FolderSubmitResponse::SubmittedButUnconfirmed {
+            id,
+            job_id,
+            job_url,
+            error,
+            file_count: walk.files.len(),
+            archive_bytes,
+        },
+        SubmitResponse::Queued { .. } => {
+            return Err("The folder submission was queued instead of sent".to_string())
+        }
+    };
+    Ok(anonymize::outgoing(&app, response))
+}
+
+#[tauri::command]
+pub async fn get_folder_submit_settings(
+    app: tauri::AppHandle,
+) -> Result<FolderSubmitSettings, String> {
+    Ok(load(&app))
+}
+
+/// Save the exclude patterns and warning threshold. A pattern that doesn't
+/// parse is rejected and nothing is saved.
+#[tauri::command]
+pub async fn set_folder_submit_settings(
+    app: tauri::AppHandle,
+    settings: FolderSubmitSettings,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeSubmissionSettings)?;
+
+    let exclude_patterns: Vec<String> = settings
+        .exclude_patterns
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    patterns::compile(&exclude_patterns)?;
+
+    preferences::of(&app).set_many([
+        (EXCLUDE_KEY, json!(exclude_patterns)),
+        (WARN_BYTES_KEY, json!(settings.warn_bytes)),
+    ]);
+    Ok(())
+}
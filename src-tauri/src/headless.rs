@@ -0,0 +1,144 @@
+// Headless command invocation, for scripts and CI.
+//
+// Built only with the `headless-invoke` feature, which release bundles
+// don't enable. Started with `--headless-invoke`, the app hides its window
+// and reads newline-delimited JSON requests from stdin:
+//
+//     {"id": 1, "command": "list_jobs", "args": {}}
+//
+// Each one goes through the same IPC path as a call from the frontend, so
+// permissions, argument parsing and anonymization all apply. Every
+// response and every app event is written to stdout as one JSON line:
+//
+//     {"id": 1, "ok": [...]}
+//     {"id": 2, "error": "Not connected"}
+//     {"event": "download-progress", "payload": {...}}
+//
+// The app exits when stdin closes. Requests go to the demo backend, a local
+// server with a generated fixtures account (see demo_backend for how to log
+// in), unless `--real-api` is also given; then they go to NSG with whatever
+// credentials `load_credentials` or `connect` is given, exactly as in the
+// GUI. Set SHOWCASE_MODE=1 to get anonymized output. The webview still
+// needs a display, so on a Linux CI machine run under xvfb-run.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use tauri::ipc::{CallbackFn, InvokeBody, InvokeResponse, InvokeResponseBody};
+use tauri::webview::InvokeRequest;
+use tauri::{Listener, Manager};
+
+pub const FLAG: &str = "--headless-invoke";
+// Use NSG instead of the demo backend
+pub const REAL_API_FLAG: &str = "--real-api";
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    // Echoed back so responses can be matched to requests
+    #[serde(default)]
+    id: Value,
+    command: String,
+    #[serde(default)]
+    args: Value,
+}
+
+pub fn requested() -> bool {
+    std::env::args().any(|a| a == FLAG)
+}
+
+/// Start the demo backend unless the real API was asked for. Called from
+/// setup in headless mode, before any request is made.
+pub fn select_backend() -> Result<(), String> {
+    if std::env::args().any(|a| a == REAL_API_FLAG) {
+        return Ok(());
+    }
+    crate::demo_backend::start()
+}
+
+fn write_line(value: Value) {
+    let mut out = std::io::stdout().lock();
+    let _ = writeln!(out, "{}", value);
+    let _ = out.flush();
+}
+
+fn response_value(response: InvokeResponse) -> Result<Value, Value> {
+    match response {
+        InvokeResponse::Ok(InvokeResponseBody::Json(json)) => {
+            Ok(serde_json::from_str(&json).unwrap_or(Value::String(json)))
+        }
+        InvokeResponse::Ok(InvokeResponseBody::Raw(bytes)) => Ok(json!(bytes)),
+        InvokeResponse::Err(e) => Err(e.0),
+    }
+}
+
+/// Run one request through the main webview's IPC handler and wait for it
+fn invoke(webview: &tauri::Webview, invoke_key: &str, request: Request) -> Value {
+    let url = match webview.url() {
+        Ok(url) => url,
+        Err(e) => return json!({ "id": request.id, "error": format!("No webview URL: {}", e) }),
+    };
+    let args = if request.args.is_null() {
+        json!({})
+    } else {
+        request.args
+    };
+
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    webview.clone().on_message(
+        InvokeRequest {
+            cmd: request.command,
+            callback: CallbackFn(0),
+            error: CallbackFn(1),
+            url,
+            body: InvokeBody::Json(args),
+            headers: Default::default(),
+            invoke_key: invoke_key.to_string(),
+        },
+        Box::new(move |_webview, _cmd, response, _callback, _error| {
+            let _ = tx.send(response);
+        }),
+    );
+
+    match rx.recv().map(response_value) {
+        Ok(Ok(value)) => json!({ "id": request.id, "ok": value }),
+        Ok(Err(error)) => json!({ "id": request.id, "error": error }),
+        Err(_) => json!({ "id": request.id, "error": "The command never answered" }),
+    }
+}
+
+/// Hide the window, forward events to stdout and serve stdin. Called from
+/// setup in headless mode.
+pub fn start(app: &tauri::AppHandle) -> Result<(), String> {
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "Headless mode needs the main window".to_string())?;
+    let _ = window.hide();
+    let webview = window.as_ref().clone();
+
+    for event in crate::events::ALL {
+        app.listen_any(*event, move |e| {
+            let payload = serde_json::from_str(e.payload()).unwrap_or(Value::Null);
+            write_line(json!({ "event": event, "payload": payload }));
+        });
+    }
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let invoke_key = app.invoke_key().to_string();
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => invoke(&webview, &invoke_key, request),
+                Err(e) => json!({ "id": null, "error": format!("Invalid request: {}", e) }),
+            };
+            write_line(response);
+        }
+        app.exit(0);
+    });
+    Ok(())
+}
@@ -0,0 +1,174 @@
+// Tool health from recent job outcomes.
+//
+// When a tool breaks on the NSG side, every job for it fails the same way
+// until it's fixed. Looking at the last few finished jobs per tool in the
+// cached job list lets the app warn before the next submission. This is
+// advisory only; nothing here ever blocks a submission.
+
+use crate::monitor::parse_date;
+use crate::{listing, AppState, JobSummary};
+use serde::Serialize;
+use tauri::State;
+
+// How many of a tool's most recent finished jobs are looked at
+const RECENT_JOBS: usize = 5;
+// Only jobs submitted this recently count
+const WINDOW_DAYS: i64 = 7;
+// Fewer finished jobs than this say nothing about the tool
+const MIN_JOBS: usize = 3;
+// Warn when more than this share of the recent jobs failed
+const FAILURE_THRESHOLD: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolHealth {
+    pub tool: String,
+    pub recent_jobs: usize,
+    pub failed_jobs: usize,
+    pub window_days: i64,
+    pub warning: Option<String>,
+}
+
+/// How the recent finished jobs of `tool` among `jobs` turned out
+pub fn tool_health(
+    tool: &str,
+    jobs: &[JobSummary],
+    now: chrono::DateTime<chrono::Utc>,
+) -> ToolHealth {
+    let since = now - chrono::Duration::days(WINDOW_DAYS);
+
+    let mut recent: Vec<(chrono::DateTime<chrono::Utc>, bool)> = jobs
+        .iter()
+        .filter(|j| {
+            j.tool
+                .as_deref()
+                .is_some_and(|t| t.eq_ignore_ascii_case(tool))
+        })
+        .filter(|j| j.failed || j.job_stage.as_deref() == Some("COMPLETED"))
+        .filter_map(|j| Some((parse_date(j.date_submitted.as_deref())?, j.failed)))
+        .filter(|(submitted, _)| *submitted >= since)
+        .collect();
+    recent.sort_by_key(|(submitted, _)| std::cmp::Reverse(*submitted));
+    recent.truncate(RECENT_JOBS);
+
+    let failed_jobs = recent.iter().filter(|(_, failed)| *failed).count();
+    let warning = (recent.len() >= MIN_JOBS
+        && failed_jobs as f64 / recent.len() as f64 > FAILURE_THRESHOLD)
+        .then(|| {
+            format!(
+                "{} of your last {} {} jobs failed in the past {} days",
+                failed_jobs,
+                recent.len(),
+                tool,
+                WINDOW_DAYS
+            )
+        });
+
+    ToolHealth {
+        tool: tool.to_string(),
+        recent_jobs: recent.len(),
+        failed_jobs,
+        window_days: WINDOW_DAYS,
+        warning,
+    }
+}
+
+/// Health of `tool` according to the last job listing, or nothing without
+/// one in this session
+pub fn current(app: &tauri::AppHandle, state: &AppState, tool: &str) -> Option<ToolHealth> {
+    let session = state.session.snapshot()?;
+    let jobs = listing::cached(app, session.generation)?;
+    Some(tool_health(tool, &jobs, crate::clock::now()))
+}
+
+/// Check a tool before submitting to it. Without a job list yet there is
+/// no data and no warning.
+#[tauri::command]
+pub async fn get_tool_health(
+    tool: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ToolHealth, String> {
+    Ok(current(&app, &state, &tool).unwrap_or(ToolHealth {
+        tool,
+        recent_jobs: 0,
+        failed_jobs: 0,
+        window_days: WINDOW_DAYS,
+        warning: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> chrono::DateTime<chrono::Utc> {
+        "2026-03-10T12:00:00Z".parse().unwrap()
+    }
+
+    fn job(tool: &str, days_ago: i64, stage: &str, failed: bool) -> JobSummary {
+        JobSummary {
+            tool: Some(tool.to_string()),
+            job_stage: Some(stage.to_string()),
+            failed,
+            date_submitted: Some((now() - chrono::Duration::days(days_ago)).to_rfc3339()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mostly_failing_recent_jobs_warn() {
+        let jobs = [
+            job("NEURON", 1, "COMPLETED", true),
+            job("neuron", 2, "COMPLETED", true),
+            job("NEURON", 3, "COMPLETED", false),
+        ];
+        let health = tool_health("NEURON", &jobs, now());
+        assert_eq!((health.recent_jobs, health.failed_jobs), (3, 2));
+        assert_eq!(
+            health.warning.as_deref(),
+            Some("2 of your last 3 NEURON jobs failed in the past 7 days")
+        );
+    }
+
+    #[test]
+    fn too_few_or_mostly_fine_jobs_do_not_warn() {
+        let few = [
+            job("NEURON", 1, "COMPLETED", true),
+            job("NEURON", 2, "COMPLETED", true),
+        ];
+        assert!(tool_health("NEURON", &few, now()).warning.is_none());
+
+        let fine = [
+            job("NEURON", 1, "COMPLETED", true),
+            job("NEURON", 2, "COMPLETED", false),
+            job("NEURON", 3, "COMPLETED", false),
+            job("NEURON", 4, "COMPLETED", true),
+        ];
+        assert!(tool_health("NEURON", &fine, now()).warning.is_none());
+    }
+
+    #[test]
+    fn only_recent_finished_jobs_of_the_tool_count() {
+        let jobs = [
+            job("NEURON", 1, "COMPLETED", true),
+            job("NEURON", 2, "COMPLETED", true),
+            // Too old, still running, and another tool
+            job("NEURON", 8, "COMPLETED", true),
+            job("NEURON", 1, "QUEUE", false),
+            job("PY_EXPANSE", 1, "COMPLETED", true),
+        ];
+        let health = tool_health("NEURON", &jobs, now());
+        assert_eq!((health.recent_jobs, health.failed_jobs), (2, 2));
+        assert!(health.warning.is_none());
+    }
+
+    #[test]
+    fn only_the_latest_jobs_are_looked_at() {
+        let mut jobs: Vec<JobSummary> = (1..=5)
+            .map(|day| job("NEURON", day, "COMPLETED", false))
+            .collect();
+        jobs.push(job("NEURON", 6, "COMPLETED", true));
+        let health = tool_health("NEURON", &jobs, now());
+        assert_eq!((health.recent_jobs, health.failed_jobs), (RECENT_JOBS, 0));
+    }
+}
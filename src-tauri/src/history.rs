@@ -0,0 +1,273 @@
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, Anonymize};
+use crate::trash::{self, LocalDelete, TrashKind, TrashMark};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const HISTORY_FILE: &str = "download_history.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadHistoryEntry {
+    pub id: String,
+    pub job_id: String,
+    pub job_url: String,
+    pub archive_path: String,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub downloaded_at: String,
+    // Results were deleted on the server after this download was verified
+    #[serde(default)]
+    pub purged_on_server: bool,
+    // Missing in entries from before these were recorded
+    #[serde(default)]
+    pub tool: Option<String>,
+    // Size of the finished zip
+    #[serde(default)]
+    pub archive_bytes: Option<u64>,
+    // Set while the entry is in the trash; such entries are left out of
+    // load()
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<TrashMark>,
+    // Never removed by archive cleanup
+    #[serde(default)]
+    pub pinned: bool,
+    // The archive was gone when cleanup looked for it
+    #[serde(default)]
+    pub archive_missing: bool,
+    // When archive cleanup moved the archive to the OS trash
+    #[serde(default)]
+    pub archive_trashed_at: Option<String>,
+}
+
+impl DownloadHistoryEntry {
+    /// Whether the archive should still be where it was saved
+    pub fn has_archive(&self) -> bool {
+        !self.archive_missing && self.archive_trashed_at.is_none()
+    }
+}
+
+impl Anonymize for DownloadHistoryEntry {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            job_url: anonymize_url(&self.job_url),
+            ..self
+        }
+    }
+}
+
+fn history_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = crate::accounts::user_root(app)?;
+    Ok(dir.join(HISTORY_FILE))
+}
+
+/// Entries not in the trash
+pub fn load(app: &tauri::AppHandle) -> Result<Vec<DownloadHistoryEntry>, String> {
+    let entries: Vec<DownloadHistoryEntry> = crate::state_file::load(&history_path(app)?)?;
+    Ok(entries
+        .into_iter()
+        .filter(|e| e.deleted.is_none())
+        .collect())
+}
+
+fn modify<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut Vec<DownloadHistoryEntry>) -> T,
+) -> Result<T, String> {
+    crate::state_file::modify(&history_path(app)?, "download history", |entries| {
+        Ok(f(entries))
+    })
+}
+
+pub fn append(app: &tauri::AppHandle, entry: DownloadHistoryEntry) -> Result<(), String> {
+    if crate::accounts::is_mismatched() {
+        return Err(
+            "Not recording the download: the history belongs to another account".to_string(),
+        );
+    }
+    append_to(&history_path(app)?, entry)
+}
+
+fn append_to(path: &Path, entry: DownloadHistoryEntry) -> Result<(), String> {
+    crate::state_file::modify(path, "download history", |entries: &mut Vec<_>| {
+        entries.push(entry);
+        Ok(())
+    })
+}
+
+/// Take entries out of the trash. Returns false when `token` no longer
+/// undoes anything.
+pub fn restore(app: &tauri::AppHandle, token: &str) -> Result<bool, String> {
+    modify(app, |entries| {
+        let mut restored = false;
+        for entry in entries.iter_mut() {
+            if entry.deleted.as_ref().is_some_and(|m| m.undoes(token)) {
+                entry.deleted = None;
+                restored = true;
+            }
+        }
+        restored
+    })
+}
+
+/// Pin or unpin an entry, returning whether it exists
+pub fn set_pinned(app: &tauri::AppHandle, id: &str, pinned: bool) -> Result<bool, String> {
+    modify(app, |entries| {
+        let entry = entries
+            .iter_mut()
+            .find(|e| e.deleted.is_none() && e.id == id);
+        entry.map(|e| e.pinned = pinned).is_some()
+    })
+}
+
+/// Record what archive cleanup found: archives already gone and archives
+/// it moved to the OS trash at `trashed_at`, both by path
+pub fn mark_archives(
+    app: &tauri::AppHandle,
+    missing: &[String],
+    trashed: &[String],
+    trashed_at: &str,
+) -> Result<(), String> {
+    if missing.is_empty() && trashed.is_empty() {
+        return Ok(());
+    }
+    modify(app, |entries| {
+        for entry in entries.iter_mut().filter(|e| e.has_archive()) {
+            if missing.contains(&entry.archive_path) {
+                entry.archive_missing = true;
+            } else if trashed.contains(&entry.archive_path) {
+                entry.archive_trashed_at = Some(trashed_at.to_string());
+            }
+        }
+    })
+}
+
+/// Drop trashed entries whose undo window has passed
+pub fn purge_trash(app: &tauri::AppHandle) -> Result<(), String> {
+    let expired = |e: &DownloadHistoryEntry| e.deleted.as_ref().is_some_and(|m| m.is_expired());
+    let entries: Vec<DownloadHistoryEntry> = crate::state_file::load(&history_path(app)?)?;
+    let any_expired = entries.iter().any(expired);
+    if any_expired {
+        modify(app, |entries| entries.retain(|e| !expired(e)))?;
+    }
+    Ok(())
+}
+
+/// Delete the history file, returning whether there was one
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = history_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    crate::paths::remove_file_if_exists(&path)
+}
+
+/// A new history id, unique enough for a local single-user list
+pub fn new_entry_id() -> String {
+    format!(
+        "dl-{}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    )
+}
+
+#[tauri::command]
+pub async fn get_download_history(
+    app: tauri::AppHandle,
+) -> Result<Vec<DownloadHistoryEntry>, String> {
+    let entries = load(&app)?;
+    Ok(anonymize::outgoing(&app, entries))
+}
+
+/// Remove entries from the history, by ID. The archives stay where they
+/// are. Can be undone with the returned token for a while.
+#[tauri::command]
+pub async fn remove_download_history_entries(
+    app: tauri::AppHandle,
+    ids: Vec<String>,
+) -> Result<LocalDelete, String> {
+    let mark = TrashMark::new(TrashKind::DownloadHistory);
+    let removed = modify(&app, |entries| {
+        let mut removed = 0;
+        for entry in entries.iter_mut() {
+            if entry.deleted.is_none() && ids.contains(&entry.id) {
+                entry.deleted = Some(mark.clone());
+                removed += 1;
+            }
+        }
+        removed
+    })?;
+    if removed == 0 {
+        return Err("No such download history entries".to_string());
+    }
+
+    Ok(trash::deleted(
+        &app,
+        TrashKind::DownloadHistory,
+        &mark,
+        removed,
+    ))
+}
+
+/// Pin a download history entry, or unpin it with `pinned: false`. Pinned
+/// archives are never cleaned up.
+#[tauri::command]
+pub async fn pin_history_entry(
+    app: tauri::AppHandle,
+    id: String,
+    pinned: Option<bool>,
+) -> Result<(), String> {
+    if !set_pinned(&app, &id, pinned.unwrap_or(true))? {
+        return Err(format!("No download history entry {}", id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch;
+
+    fn entry(id: &str) -> DownloadHistoryEntry {
+        DownloadHistoryEntry {
+            id: id.to_string(),
+            job_id: format!("NGBW-JOB-{}", id),
+            job_url: String::new(),
+            archive_path: String::new(),
+            file_count: 1,
+            total_bytes: 1,
+            downloaded_at: "2026-01-02T03:04:05+00:00".to_string(),
+            purged_on_server: false,
+            tool: None,
+            archive_bytes: None,
+            deleted: None,
+            pinned: false,
+            archive_missing: false,
+            archive_trashed_at: None,
+        }
+    }
+
+    #[test]
+    fn a_corrupt_history_survives_an_append() {
+        let dir = scratch("history", "corrupt");
+        let path = dir.join(HISTORY_FILE);
+        let corrupt = r#"[{"id": "dl-1", "job_id": "NGBW-JOB-1""#;
+        std::fs::write(&path, corrupt).unwrap();
+
+        append_to(&path, entry("dl-2")).unwrap();
+
+        let entries: Vec<DownloadHistoryEntry> = crate::state_file::load(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, "dl-2");
+        let kept: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().contains(".corrupt-"))
+            .map(|e| std::fs::read_to_string(e.path()).unwrap())
+            .collect();
+        assert_eq!(kept, [corrupt]);
+    }
+
+    #[test]
+    fn an_unparsable_history_is_an_error_not_empty() {
+        let path = scratch("history", "unreadable").join(HISTORY_FILE);
+        std::fs::write(&path, r#"{"schema": 1, "data": {"not": "a list"}}"#).unwrap();
+        assert!(crate::state_file::load::<Vec<DownloadHistoryEntry>>(&path).is_err());
+    }
+}
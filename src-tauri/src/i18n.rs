@@ -0,0 +1,339 @@
+// Localization of backend-generated strings.
+//
+// Every user-facing message is a `Msg` with a stable key and named
+// parameters. The English catalog is generated together with the enum, so a
+// message can't exist without an English text; other locales fall back to
+// English for anything they leave out.
+
+use crate::preferences;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    En,
+    Es,
+}
+
+impl Locale {
+    pub const ALL: &'static [Locale] = &[Locale::En, Locale::Es];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+        }
+    }
+
+    /// Accepts plain codes as well as region tags like "es-MX"
+    pub fn parse(code: &str) -> Option<Locale> {
+        let language = code.split(['-', '_']).next().unwrap_or(code);
+        Locale::ALL
+            .iter()
+            .copied()
+            .find(|l| l.code().eq_ignore_ascii_case(language))
+    }
+}
+
+static LOCALE: Mutex<Locale> = Mutex::new(Locale::En);
+
+macro_rules! messages {
+    ($($variant:ident => $key:literal, $english:literal;)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Msg {
+            $($variant,)*
+        }
+
+        impl Msg {
+            pub fn key(&self) -> &'static str {
+                match self {
+                    $(Msg::$variant => $key,)*
+                }
+            }
+
+            fn english(&self) -> &'static str {
+                match self {
+                    $(Msg::$variant => $english,)*
+                }
+            }
+        }
+    };
+}
+
+messages! {
+    NotConnected => "not_connected", "Not connected";
+    ConnectedAs => "connected_as", "Connected as {username}";
+    ConnectFailed => "connect_failed", "Connection test failed at {phase}: {error}";
+    PhaseBuildingClient => "phase.building_client", "building client";
+    PhaseResolvingHost => "phase.resolving_host", "resolving host";
+    PhaseTlsHandshake => "phase.tls_handshake", "TLS handshake";
+    PhaseAuthenticating => "phase.authenticating", "authenticating";
+    ReadOnlyBlocked => "read_only.blocked", "{action} is disabled in read-only mode";
+    ReadOnlyFromEnvironment => "read_only.from_environment", "Read-only mode is set by {variable} and can't be turned off here";
+    IncorrectPassword => "incorrect_password", "Incorrect password";
+    ActionSubmitJobs => "action.submit_jobs", "Submitting jobs";
+    ActionChangeDownloadFolder => "action.change_download_folder", "Changing the download folder";
+    ActionDeleteAfterDownload => "action.delete_after_download", "Deleting results after download";
+    ActionChangeDownloadSettings => "action.change_download_settings", "Changing download settings";
+    ActionChangeMonitorSettings => "action.change_monitor_settings", "Changing monitor settings";
+    ActionChangeConnectionSettings => "action.change_connection_settings", "Changing connection settings";
+    ActionDeleteSavedCredentials => "action.delete_saved_credentials", "Deleting saved credentials";
+    ActionChangeNotificationSettings => "action.change_notification_settings", "Changing notification settings";
+    ActionChangeSubmissionSettings => "action.change_submission_settings", "Changing submission settings";
+    UnsupportedLocale => "unsupported_locale", "Unsupported locale: {locale}";
+    SessionChanged => "session_changed", "The connection changed while this was running; try again";
+    ActionPurgeAppData => "action.purge_app_data", "Deleting app data";
+    ActionAdoptJobs => "action.adopt_jobs", "Adopting jobs";
+    ActionPurgeJobMetadata => "action.purge_job_metadata", "Cleaning up local job metadata";
+    ActionChangeDataLocation => "action.change_data_location", "Changing where app data is stored";
+    ActionCleanUpArchives => "action.clean_up_archives", "Cleaning up downloaded archives";
+    ActionImportMetadata => "action.import_metadata", "Importing local metadata";
+    ActionCancelJobs => "action.cancel_jobs", "Cancelling jobs";
+    ActionDeleteJobs => "action.delete_jobs", "Deleting jobs";
+    PurgeNotConfirmed => "purge.not_confirmed", "Type {phrase} to confirm deleting all app data";
+    LintInvalidNumber => "lint.invalid_number", "{parameter} must be a positive number, not \"{value}\"";
+    LintRuntimeAboveMax => "lint.runtime_above_max", "{parameter} asks for {hours} hours, more than the {max} hours {tool} allows";
+    LintMultiNodeSingleNodeTool => "lint.nodes_on_single_node_tool", "{parameter} asks for {nodes} nodes, but {tool} runs on a single node";
+    LintCoresAboveResource => "lint.cores_above_resource", "{parameter} asks for {cores} cores per node, but {resource} nodes have {max}";
+    LintRuntimeAboveHistory => "lint.runtime_above_history", "{parameter} asks for {hours} hours, but 90% of your {tool} jobs ran within {p90} hours";
+    StateLocked => "state.locked", "{file} is being changed by another copy of the app; try again";
+    NotifyDownloadComplete => "notify.download_complete", "Download complete";
+    NotifyDownloadBody => "notify.download_body", "{job}: {size}";
+    NotifyNamedJob => "notify.named_job", "{name} ({job_id})";
+    NotifyJobCompleted => "notify.job_completed", "Job completed";
+    NotifyJobFailed => "notify.job_failed", "Job failed";
+    NotifyJobWithTool => "notify.job_with_tool", "{job_id} ({tool})";
+    StageQueue => "stage.queue", "Queued";
+    StageCommitted => "stage.committed", "Prepared";
+    StageSubmitted => "stage.submitted", "Submitted to the cluster";
+    StageRunning => "stage.running", "Running";
+    StageCompleted => "stage.completed", "Completed";
+}
+
+fn spanish(msg: Msg) -> Option<&'static str> {
+    Some(match msg {
+        Msg::NotConnected => "No conectado",
+        Msg::ConnectedAs => "Conectado como {username}",
+        Msg::ConnectFailed => "La prueba de conexión falló durante {phase}: {error}",
+        Msg::PhaseBuildingClient => "la creación del cliente",
+        Msg::PhaseResolvingHost => "la resolución del servidor",
+        Msg::PhaseTlsHandshake => "la negociación TLS",
+        Msg::PhaseAuthenticating => "la autenticación",
+        Msg::ReadOnlyBlocked => "{action}: no está permitido en modo de solo lectura",
+        Msg::ReadOnlyFromEnvironment => {
+            "El modo de solo lectura está definido por {variable} y no se puede desactivar aquí"
+        }
+        Msg::IncorrectPassword => "Contraseña incorrecta",
+        Msg::ActionSubmitJobs => "Enviar trabajos",
+        Msg::ActionChangeDownloadFolder => "Cambiar la carpeta de descargas",
+        Msg::ActionDeleteAfterDownload => "Eliminar resultados tras la descarga",
+        Msg::ActionChangeDownloadSettings => "Cambiar la configuración de descargas",
+        Msg::ActionChangeMonitorSettings => "Cambiar la configuración del monitor",
+        Msg::ActionChangeConnectionSettings => "Cambiar la configuración de conexión",
+        Msg::ActionDeleteSavedCredentials => "Eliminar las credenciales guardadas",
+        Msg::ActionChangeNotificationSettings => "Cambiar la configuración de notificaciones",
+        Msg::ActionChangeSubmissionSettings => "Cambiar la configuración de envíos",
+        Msg::UnsupportedLocale => "Idioma no admitido: {locale}",
+        Msg::SessionChanged => "La conexión cambió mientras se ejecutaba; inténtelo de nuevo",
+        Msg::ActionPurgeAppData => "Eliminar los datos de la aplicación",
+        Msg::ActionAdoptJobs => "Adoptar trabajos",
+        Msg::ActionPurgeJobMetadata => "Limpiar los metadatos locales de trabajos",
+        Msg::ActionChangeDataLocation => "Cambiar dónde se guardan los datos",
+        Msg::ActionCleanUpArchives => "Limpiar los archivos descargados",
+        Msg::ActionImportMetadata => "Importar los metadatos locales",
+        Msg::ActionCancelJobs => "Cancelar trabajos",
+        Msg::ActionDeleteJobs => "Eliminar trabajos",
+        Msg::PurgeNotConfirmed => {
+            "Escriba {phrase} para confirmar la eliminación de todos los datos de la aplicación"
+        }
+        Msg::LintInvalidNumber => "{parameter} debe ser un número positivo, no \"{value}\"",
+        Msg::LintRuntimeAboveMax => {
+            "{parameter} pide {hours} horas, más de las {max} horas que permite {tool}"
+        }
+        Msg::LintMultiNodeSingleNodeTool => {
+            "{parameter} pide {nodes} nodos, pero {tool} se ejecuta en un solo nodo"
+        }
+        Msg::LintCoresAboveResource => {
+            "{parameter} pide {cores} núcleos por nodo, pero los nodos de {resource} tienen {max}"
+        }
+        Msg::LintRuntimeAboveHistory => {
+            "{parameter} pide {hours} horas, pero el 90 % de sus trabajos de {tool} terminaron en {p90} horas"
+        }
+        Msg::StateLocked => {
+            "Otra copia de la aplicación está modificando {file}; inténtelo de nuevo"
+        }
+        Msg::NotifyDownloadComplete => "Descarga completada",
+        Msg::NotifyDownloadBody => "{job}: {size}",
+        Msg::NotifyNamedJob => "{name} ({job_id})",
+        Msg::NotifyJobCompleted => "Trabajo completado",
+        Msg::NotifyJobFailed => "Trabajo fallido",
+        Msg::NotifyJobWithTool => "{job_id} ({tool})",
+        Msg::StageQueue => "En cola",
+        Msg::StageCommitted => "Preparado",
+        Msg::StageSubmitted => "Enviado al clúster",
+        Msg::StageRunning => "En ejecución",
+        Msg::StageCompleted => "Completado",
+    })
+}
+
+/// The label of an NSG job stage in the current locale; a stage NSG added
+/// since is shown as sent
+pub fn stage_label(stage: &str) -> String {
+    let msg = match stage.to_ascii_uppercase().as_str() {
+        "QUEUE" => Msg::StageQueue,
+        "COMMITTED" => Msg::StageCommitted,
+        "SUBMITTED" => Msg::StageSubmitted,
+        "RUNNING" => Msg::StageRunning,
+        "COMPLETED" => Msg::StageCompleted,
+        _ => return stage.to_string(),
+    };
+    t(msg, &[])
+}
+
+fn template(locale: Locale, msg: Msg) -> &'static str {
+    let translated = match locale {
+        Locale::En => None,
+        Locale::Es => spanish(msg),
+    };
+    translated.unwrap_or_else(|| msg.english())
+}
+
+fn fill(template: &str, params: &[(&str, &str)]) -> String {
+    let mut text = template.to_string();
+    for (name, value) in params {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+pub fn locale() -> Locale {
+    *LOCALE.lock().unwrap()
+}
+
+/// The message in the current locale
+pub fn t(msg: Msg, params: &[(&str, &str)]) -> String {
+    fill(template(locale(), msg), params)
+}
+
+/// A message as sent to the frontend: the text in the current locale plus the
+/// key and parameters, for a frontend that would rather localize it itself
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalizedMessage {
+    pub key: &'static str,
+    pub params: BTreeMap<String, String>,
+    pub message: String,
+}
+
+impl LocalizedMessage {
+    pub fn new(msg: Msg, params: &[(&str, &str)]) -> Self {
+        Self {
+            key: msg.key(),
+            params: params
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            message: t(msg, params),
+        }
+    }
+}
+
+/// Back to the default locale, e.g. after the preferences were deleted
+pub fn reset() {
+    *LOCALE.lock().unwrap() = Locale::En;
+}
+
+/// Load the persisted locale at startup
+pub fn load(app: &tauri::AppHandle) {
+    let saved = preferences::of(app)
+        .get("locale")
+        .and_then(|v| v.as_str().and_then(Locale::parse));
+    if let Some(locale) = saved {
+        *LOCALE.lock().unwrap() = locale;
+    }
+}
+
+#[tauri::command]
+pub async fn get_locale() -> Result<String, String> {
+    Ok(locale().code().to_string())
+}
+
+#[tauri::command]
+pub async fn set_locale(app: tauri::AppHandle, locale: String) -> Result<String, String> {
+    let parsed =
+        Locale::parse(&locale).ok_or_else(|| t(Msg::UnsupportedLocale, &[("locale", &locale)]))?;
+
+    let prefs = preferences::of(&app);
+
+    prefs.set("locale", json!(parsed.code()));
+
+    *LOCALE.lock().unwrap() = parsed;
+    Ok(parsed.code().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales_parse_from_codes_and_region_tags() {
+        assert_eq!(Locale::parse("es"), Some(Locale::Es));
+        assert_eq!(Locale::parse("ES-mx"), Some(Locale::Es));
+        assert_eq!(Locale::parse("en_GB"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr"), None);
+        assert_eq!(Locale::parse(""), None);
+    }
+
+    #[test]
+    fn parameters_are_filled_by_name() {
+        assert_eq!(
+            fill(
+                template(Locale::En, Msg::ConnectFailed),
+                &[("phase", "TLS handshake"), ("error", "timed out")]
+            ),
+            "Connection test failed at TLS handshake: timed out"
+        );
+        // Unknown parameters are left alone, missing ones stay as written
+        assert_eq!(fill("{a} and {b}", &[("a", "x"), ("c", "y")]), "x and {b}");
+    }
+
+    #[test]
+    fn spanish_uses_its_own_text() {
+        assert_eq!(
+            fill(
+                template(Locale::Es, Msg::ConnectedAs),
+                &[("username", "ana")]
+            ),
+            "Conectado como ana"
+        );
+        assert_eq!(template(Locale::En, Msg::NotConnected), "Not connected");
+    }
+
+    #[test]
+    fn every_stage_has_a_label_in_every_locale() {
+        for stage in crate::job_query::STAGE_ORDER {
+            assert_ne!(stage_label(stage), *stage);
+        }
+        assert_eq!(stage_label("running"), "Running");
+        assert_eq!(stage_label("ARCHIVED"), "ARCHIVED");
+        for msg in [
+            Msg::StageQueue,
+            Msg::StageCommitted,
+            Msg::StageSubmitted,
+            Msg::StageRunning,
+            Msg::StageCompleted,
+        ] {
+            assert!(spanish(msg).is_some(), "{}", msg.key());
+        }
+    }
+
+    #[test]
+    fn localized_messages_keep_the_key_and_parameters() {
+        let message = LocalizedMessage::new(Msg::UnsupportedLocale, &[("locale", "fr")]);
+        assert_eq!(message.key, "unsupported_locale");
+        assert_eq!(message.params.get("locale").map(String::as_str), Some("fr"));
+        assert!(message.message.contains("fr"));
+    }
+}
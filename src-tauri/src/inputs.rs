@@ -0,0 +1,76 @@
+// Named input files for a submission.
+//
+// Most tools take one archive as `input.infile_`, but some take several
+// inputs, each under its own parameter name (`input.infile_` plus
+// `input.treefile_`, ...). Every binding is checked before any bytes are
+// uploaded, and errors name the binding they are about. Inputs named by the
+// frontend are also checked against path_access before anything else.
+
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// The parameter single-file submissions are sent as. There are no tool
+/// schemas in the app to look a tool's primary input up in, and this is the
+/// one every CIPRES tool accepts.
+pub const PRIMARY_INPUT_PARAM: &str = "input.infile_";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputBinding {
+    pub param_name: String,
+    pub file_path: PathBuf,
+}
+
+impl InputBinding {
+    pub fn primary(file_path: PathBuf) -> Self {
+        Self {
+            param_name: PRIMARY_INPUT_PARAM.to_string(),
+            file_path,
+        }
+    }
+}
+
+/// Check that every input is a path the frontend may read
+pub fn authorize(app: &tauri::AppHandle, inputs: &[InputBinding]) -> Result<(), String> {
+    for input in inputs {
+        crate::path_access::authorize(app, &input.file_path)?;
+    }
+    Ok(())
+}
+
+/// Check that there is at least one input, that parameter names are set and
+/// unique, and that every file can be read and is within `max_bytes`.
+/// Returns the total upload size.
+pub fn validate(inputs: &[InputBinding], max_bytes: u64) -> Result<u64, String> {
+    if inputs.is_empty() {
+        return Err("No input files given".to_string());
+    }
+
+    let mut seen = HashSet::new();
+    let mut total = 0u64;
+    for (i, input) in inputs.iter().enumerate() {
+        let name = input.param_name.trim();
+        if name.is_empty() {
+            return Err(format!(
+                "Input {} ({}) has no parameter name",
+                i + 1,
+                input.file_path.display()
+            ));
+        }
+        if !seen.insert(name) {
+            return Err(format!("Input {} is bound more than once", name));
+        }
+        let size = paths::check_readable_file(&input.file_path, max_bytes)
+            .map_err(|e| format!("Input {}: {}", name, e))?;
+        total = total.saturating_add(size);
+    }
+
+    if total > max_bytes {
+        return Err(format!(
+            "Inputs are {} bytes together, over the {} byte limit",
+            total, max_bytes
+        ));
+    }
+    Ok(total)
+}
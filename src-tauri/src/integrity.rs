@@ -0,0 +1,327 @@
+// Startup check of the JSON state files.
+//
+// A state file that doesn't parse would leave its feature without data.
+// Before anything reads them, each known file is parsed once; one that
+// fails is renamed to `<name>.corrupt-<timestamp>`, so its contents can
+// still be inspected, and the module starts from empty state. A file that
+// gets corrupt while the app runs is quarantined the same way by the next
+// change to it, and reported as a background error. Quarantined copies are
+// deleted after QUARANTINE_DAYS. preferences.json has its own backup-based
+// recovery and is only reported here. Files that parse are then brought to
+// their current schema version by state_version; one written by a newer
+// release is neither quarantined nor upgraded, but left alone and read-only.
+
+use crate::background_errors::{self, ErrorKind};
+use crate::history::DownloadHistoryEntry;
+use crate::job_cache::CachedJobs;
+use crate::local_jobs::LocalJob;
+use crate::projects::ProjectStore;
+use crate::queue::QueuedDownload;
+use crate::state_version;
+use crate::submissions::PendingSubmission;
+use crate::unconfirmed::UnconfirmedSubmission;
+use crate::{
+    history, job_cache, local_jobs, preferences, projects, queue, submissions, unconfirmed,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Emitter;
+
+// Name of the startup check in background errors
+const SOURCE: &str = "state_integrity";
+const QUARANTINE_MARKER: &str = ".corrupt-";
+const QUARANTINE_DAYS: i64 = 30;
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+// Parses a file's contents, given its name, as its module would
+type Check = fn(&str, &[u8]) -> Result<(), String>;
+
+struct StateFile {
+    name: &'static str,
+    check: Check,
+    // Renamed aside when corrupt; false for files with their own recovery
+    quarantine: bool,
+}
+
+fn parses<T: DeserializeOwned>(name: &str, content: &[u8]) -> Result<(), String> {
+    state_version::parse::<T>(name, content).map(|_| ())
+}
+
+const STATE_FILES: &[StateFile] = &[
+    StateFile {
+        name: history::HISTORY_FILE,
+        check: parses::<Vec<DownloadHistoryEntry>>,
+        quarantine: true,
+    },
+    StateFile {
+        name: queue::QUEUE_FILE,
+        check: parses::<Vec<QueuedDownload>>,
+        quarantine: true,
+    },
+    StateFile {
+        name: submissions::SUBMISSIONS_FILE,
+        check: parses::<Vec<PendingSubmission>>,
+        quarantine: true,
+    },
+    StateFile {
+        name: projects::PROJECTS_FILE,
+        check: parses::<ProjectStore>,
+        quarantine: true,
+    },
+    StateFile {
+        name: local_jobs::LOCAL_JOBS_FILE,
+        check: parses::<BTreeMap<String, LocalJob>>,
+        quarantine: true,
+    },
+    StateFile {
+        name: job_cache::JOB_CACHE_FILE,
+        check: parses::<CachedJobs>,
+        quarantine: true,
+    },
+    StateFile {
+        name: unconfirmed::UNCONFIRMED_FILE,
+        check: parses::<Vec<UnconfirmedSubmission>>,
+        quarantine: true,
+    },
+    StateFile {
+        name: preferences::PREFERENCES_FILE,
+        check: parses::<serde_json::Map<String, serde_json::Value>>,
+        quarantine: false,
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedFile {
+    pub name: String,
+    pub quarantined_as: String,
+    pub error: String,
+}
+
+// What was set aside since startup, for get_state_file_report
+static QUARANTINED: Mutex<Vec<QuarantinedFile>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseStatus {
+    Ok,
+    Missing,
+    Corrupt,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateFileStatus {
+    pub name: String,
+    pub size: Option<u64>,
+    pub modified: Option<String>,
+    pub status: ParseStatus,
+    pub error: Option<String>,
+    // Schema version on disk, when it parses; 0 before versioning
+    pub version: Option<u32>,
+    // Written by a newer release, so not written by this one
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateFileReport {
+    pub files: Vec<StateFileStatus>,
+    // Set aside since this startup
+    pub quarantined: Vec<QuarantinedFile>,
+    // Quarantined copies still on disk, from this or earlier startups
+    pub quarantined_copies: Vec<StateFileStatus>,
+}
+
+fn data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    crate::portable::app_data_root(app)
+}
+
+/// Names of the state files checked here
+pub fn state_file_names() -> impl Iterator<Item = &'static str> {
+    STATE_FILES.iter().map(|f| f.name)
+}
+
+fn status_of(path: &Path, check: Option<Check>) -> StateFileStatus {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let metadata = std::fs::metadata(path).ok();
+    let modified = metadata
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339());
+
+    let mut version = None;
+    let (status, error) = match std::fs::read(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (ParseStatus::Missing, None),
+        Err(e) => (ParseStatus::Corrupt, Some(e.to_string())),
+        Ok(content) => {
+            version = state_version::decode(&name, &content)
+                .ok()
+                .map(|d| d.version);
+            match check.map(|check| check(&name, &content)) {
+                Some(Err(e)) => (ParseStatus::Corrupt, Some(e)),
+                _ => (ParseStatus::Ok, None),
+            }
+        }
+    };
+
+    StateFileStatus {
+        name,
+        size: metadata.map(|m| m.len()),
+        modified,
+        status,
+        error,
+        version,
+        read_only: state_version::is_read_only(path),
+    }
+}
+
+/// When a quarantined copy was set aside, from the timestamp in its name
+fn quarantined_at(name: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let (_, stamp) = name.rsplit_once(QUARANTINE_MARKER)?;
+    chrono::NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+fn quarantined_copies(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut copies: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.contains(QUARANTINE_MARKER))
+        })
+        .collect();
+    copies.sort();
+    copies
+}
+
+/// Rename a corrupt state file to its quarantined name next to it
+fn quarantine(path: &Path, error: String) -> Result<QuarantinedFile, String> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stamp = chrono::Utc::now().format(TIMESTAMP_FORMAT);
+    let target = format!("{}{}{}", name, QUARANTINE_MARKER, stamp);
+    std::fs::rename(path, path.with_file_name(&target))
+        .map_err(|e| format!("Failed to quarantine corrupt {}: {}", name, e))?;
+    let quarantined = QuarantinedFile {
+        name,
+        quarantined_as: target,
+        error,
+    };
+    QUARANTINED.lock().unwrap().push(quarantined.clone());
+    Ok(quarantined)
+}
+
+/// Quarantine a state file found corrupt after startup, before it is
+/// written over, and report it
+pub fn quarantine_corrupt(path: &Path, error: String) -> Result<(), String> {
+    let quarantined = quarantine(path, error)?;
+    if let Some(app) = state_version::app() {
+        background_errors::report(
+            app,
+            SOURCE,
+            ErrorKind::Other,
+            format!(
+                "{} was corrupt and was moved to {}: {}",
+                quarantined.name, quarantined.quarantined_as, quarantined.error
+            ),
+        );
+        let _ = app.emit(crate::events::STATE_FILES_REPAIRED, [&quarantined]);
+    }
+    Ok(())
+}
+
+/// Delete quarantined copies older than QUARANTINE_DAYS
+fn sweep_quarantine(dir: &Path) {
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(QUARANTINE_DAYS);
+    for path in quarantined_copies(dir) {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if quarantined_at(name).is_some_and(|at| at < cutoff) {
+            // One that can't be deleted is tried again at the next startup
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Delete every quarantined copy, returning whether there were any
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let copies = quarantined_copies(&data_dir(app)?);
+    for path in &copies {
+        crate::paths::remove_file_if_exists(path)?;
+    }
+    QUARANTINED.lock().unwrap().clear();
+    Ok(!copies.is_empty())
+}
+
+/// Check every state file, quarantining the ones that don't parse. Runs
+/// at startup, before any state module reads its file.
+pub fn check(app: &tauri::AppHandle) {
+    let dir = match data_dir(app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            background_errors::report(app, SOURCE, ErrorKind::Other, e);
+            return;
+        }
+    };
+    sweep_quarantine(&dir);
+
+    let mut quarantined = Vec::new();
+    for file in STATE_FILES.iter().filter(|f| f.quarantine) {
+        let path = dir.join(file.name);
+        let status = status_of(&path, Some(file.check));
+        let newer = status
+            .version
+            .is_some_and(|v| v > state_version::current(file.name));
+        if newer || status.status == ParseStatus::Ok {
+            if let Ok(Err(e)) =
+                std::fs::read(&path).map(|content| state_version::upgrade(&path, &content))
+            {
+                background_errors::report(app, SOURCE, ErrorKind::Other, e);
+            }
+        }
+        if newer || status.status != ParseStatus::Corrupt {
+            continue;
+        }
+
+        match quarantine(&path, status.error.unwrap_or_default()) {
+            Ok(file) => quarantined.push(file),
+            Err(e) => background_errors::report(app, SOURCE, ErrorKind::Other, e),
+        }
+    }
+
+    if !quarantined.is_empty() {
+        let _ = app.emit(crate::events::STATE_FILES_REPAIRED, &quarantined);
+    }
+}
+
+/// Every state file with its size, modification time and whether it parses,
+/// for the diagnostics screen
+#[tauri::command]
+pub async fn get_state_file_report(app: tauri::AppHandle) -> Result<StateFileReport, String> {
+    let dir = data_dir(&app)?;
+    Ok(StateFileReport {
+        files: STATE_FILES
+            .iter()
+            .map(|file| status_of(&dir.join(file.name), Some(file.check)))
+            .collect(),
+        quarantined: QUARANTINED.lock().unwrap().clone(),
+        quarantined_copies: quarantined_copies(&dir)
+            .iter()
+            .map(|path| status_of(path, None))
+            .collect(),
+    })
+}
@@ -0,0 +1,170 @@
+// The job list kept on disk.
+//
+// Every listing is saved to job_cache.json together with the account it
+// belongs to, so a cold start has something to show before NSG answers. The
+// first list_jobs call of a session, made before the session has a listing
+// of its own, returns the saved list at once with every job flagged
+// `stale`. Meanwhile the monitor's first refresh, started as soon as the
+// session begins, replaces it through `jobs-refreshed`; if that fails the
+// stale list stays up and the error goes to the background-error channel.
+// Saving is best effort: the cache only speeds up the first render.
+
+use crate::JobSummary;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const JOB_CACHE_FILE: &str = "job_cache.json";
+
+// Session generation whose first list_jobs call was answered from the cache
+static SERVED: Mutex<Option<u64>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedJobs {
+    pub username: String,
+    pub saved_at: String,
+    // Real IDs
+    pub jobs: Vec<JobSummary>,
+}
+
+fn cache_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = crate::accounts::user_root(app)?;
+    Ok(dir.join(JOB_CACHE_FILE))
+}
+
+fn load(app: &tauri::AppHandle) -> Option<CachedJobs> {
+    crate::state_version::read(&cache_path(app).ok()?)
+        .ok()
+        .flatten()
+}
+
+/// Save a fresh listing of `username`'s jobs
+pub fn store(app: &tauri::AppHandle, username: &str, jobs: &[JobSummary]) -> Result<(), String> {
+    let path = cache_path(app)?;
+    let cache = CachedJobs {
+        username: username.to_string(),
+        saved_at: crate::clock::now().to_rfc3339(),
+        jobs: jobs.to_vec(),
+    };
+    let content = serde_json::to_string(&crate::state_version::wrap(&path, &cache)?)
+        .map_err(|e| format!("Failed to serialize job cache: {}", e))?;
+
+    let _lock = crate::state_file::lock(&path)?;
+    crate::state_file::write_atomic(&path, content.as_bytes())
+}
+
+/// The saved list of `username`'s jobs, as last stored
+pub fn saved(app: &tauri::AppHandle, username: &str) -> Vec<JobSummary> {
+    load(app)
+        .filter(|c| c.username == username)
+        .map(|c| c.jobs)
+        .unwrap_or_default()
+}
+
+/// The saved list, flagged stale, for the first list_jobs call of a
+/// session that has no listing yet. None otherwise, or when the cache
+/// belongs to another account.
+pub fn first_render(
+    app: &tauri::AppHandle,
+    generation: u64,
+    username: &str,
+) -> Option<Vec<JobSummary>> {
+    if crate::listing::has_snapshot(generation) || !claim_first_render(generation) {
+        return None;
+    }
+    stale_jobs(load(app), username)
+}
+
+// Whether this is the first call for `generation`
+fn claim_first_render(generation: u64) -> bool {
+    let mut served = SERVED.lock().unwrap();
+    if *served == Some(generation) {
+        return false;
+    }
+    *served = Some(generation);
+    true
+}
+
+// The jobs of a cache that belongs to `username`, flagged stale
+fn stale_jobs(cache: Option<CachedJobs>, username: &str) -> Option<Vec<JobSummary>> {
+    let cache = cache.filter(|c| c.username == username)?;
+    Some(
+        cache
+            .jobs
+            .into_iter()
+            .map(|job| JobSummary { stale: true, ..job })
+            .collect(),
+    )
+}
+
+/// Delete job_cache.json, returning whether there was one
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = cache_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    crate::paths::remove_file_if_exists(&path)
+}
+
+/// Throw the saved list away and rebuild it from a fresh listing, deriving
+/// projects and expiry again. The fresh list is also published in full.
+#[tauri::command]
+pub async fn rebuild_job_cache(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<crate::listing::JobsUpdate, String> {
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let jobs = tokio::task::spawn_blocking(move || api.list_jobs())
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+        .map_err(|e| format!("Failed to list jobs: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    purge(&app)?;
+    crate::state_version::forget(&cache_path(&app)?);
+    let summaries = crate::listing::summarize(&app, jobs);
+    let update = crate::listing::publish(&app, session.generation, summaries, true);
+    Ok(crate::anonymize::outgoing(&app, update))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(username: &str) -> CachedJobs {
+        CachedJobs {
+            username: username.to_string(),
+            saved_at: "2026-01-01T00:00:00+00:00".to_string(),
+            jobs: vec![JobSummary {
+                job_id: "NGBW-JOB-1".to_string(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn the_saved_list_is_served_stale_to_its_own_account() {
+        let jobs = stale_jobs(Some(cache("alice")), "alice").unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].job_id, "NGBW-JOB-1");
+        assert!(jobs[0].stale);
+
+        assert!(stale_jobs(Some(cache("alice")), "bob").is_none());
+        assert!(stale_jobs(None, "alice").is_none());
+    }
+
+    #[test]
+    fn only_the_first_call_of_a_session_is_served() {
+        assert!(claim_first_render(u64::MAX - 1));
+        assert!(!claim_first_render(u64::MAX - 1));
+        assert!(claim_first_render(u64::MAX));
+    }
+
+    #[test]
+    fn the_stale_flag_is_not_written_for_fresh_jobs() {
+        let fresh = serde_json::to_value(&cache("alice").jobs[0]).unwrap();
+        assert!(fresh.get("stale").is_none());
+        let stale = stale_jobs(Some(cache("alice")), "alice").unwrap();
+        assert_eq!(serde_json::to_value(&stale[0]).unwrap()["stale"], true);
+    }
+}
@@ -0,0 +1,345 @@
+// Windowed job queries.
+//
+// query_jobs returns one window of the job list, filtered and sorted, for
+// a virtualized table that only asks for the rows on screen. It never goes
+// to the server: it reads the last listing of the session, or the on-disk
+// job cache before there is one. The jobs are indexed once per listing with
+// their sort keys worked out up front (dates as epoch seconds, lowercase
+// tool, stage rank), and each sort order is computed once and kept, so a
+// window costs a single pass over the index. Jobs missing the sort field
+// always come last, whichever the direction, and ties go by job ID, so the
+// same query always returns the same rows. `data_version` is the listing's
+// sequence number (0 for the disk cache), the same one `jobs-refreshed` and
+// `jobs-diff` carry: when it changes, visible windows need fetching again.
+
+use crate::anonymize::{self, anonymize_job_id, is_showcase_mode, Anonymize};
+use crate::expiry::{self, ExpirySettings};
+use crate::monitor::parse_date;
+use crate::{clock, AppState, JobSummary};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::State;
+
+// Largest window one call returns
+const MAX_WINDOW: usize = 1000;
+
+// Order of NSG's job stages; anything else sorts after these by name
+pub const STAGE_ORDER: &[&str] = &["QUEUE", "COMMITTED", "SUBMITTED", "RUNNING", "COMPLETED"];
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct QueryWindow {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    DateSubmitted,
+    DateCompleted,
+    ResultsExpireAt,
+    Tool,
+    Stage,
+    JobId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub struct QuerySort {
+    pub field: SortField,
+    pub direction: SortDirection,
+}
+
+impl Default for QuerySort {
+    fn default() -> Self {
+        Self {
+            field: SortField::DateSubmitted,
+            direction: SortDirection::Desc,
+        }
+    }
+}
+
+/// Which jobs a query or list_jobs returns; unset fields match everything
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct JobFilter {
+    // Compared case-insensitively
+    pub tool: Option<String>,
+    // Any of these stages
+    pub stages: Option<Vec<String>>,
+    pub project_id: Option<String>,
+    pub failed: Option<bool>,
+    // Only completed jobs whose results expire within the warning window
+    #[serde(default)]
+    pub expiring_soon: bool,
+    // Part of the job ID (as shown) or tool, case-insensitive
+    pub text: Option<String>,
+}
+
+impl JobFilter {
+    /// Lowercased text `text` is looked for in
+    pub fn search_text(job: &JobSummary) -> String {
+        let job_id = if is_showcase_mode() {
+            anonymize_job_id(&job.job_id)
+        } else {
+            job.job_id.clone()
+        };
+        format!("{} {}", job_id, job.tool.as_deref().unwrap_or("")).to_lowercase()
+    }
+
+    pub fn matches(
+        &self,
+        job: &JobSummary,
+        search_text: &str,
+        now: chrono::DateTime<chrono::Utc>,
+        expiry: ExpirySettings,
+    ) -> bool {
+        let tool_matches = self.tool.as_deref().is_none_or(|wanted| {
+            job.tool
+                .as_deref()
+                .is_some_and(|tool| tool.eq_ignore_ascii_case(wanted))
+        });
+        let stage_matches = self.stages.as_ref().is_none_or(|stages| {
+            job.job_stage
+                .as_deref()
+                .is_some_and(|stage| stages.iter().any(|s| s.eq_ignore_ascii_case(stage)))
+        });
+        let text_matches = self
+            .text
+            .as_deref()
+            .map(|t| t.trim().to_lowercase())
+            .is_none_or(|t| search_text.contains(&t));
+        tool_matches
+            && stage_matches
+            && text_matches
+            && (self.project_id.is_none() || job.project_id == self.project_id)
+            && self.failed.is_none_or(|failed| job.failed == failed)
+            && (!self.expiring_soon || expiry::is_expiring_soon(job, now, expiry))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Time(i64),
+    Text(Box<str>),
+    Stage(usize, Box<str>),
+}
+
+struct Row {
+    job: JobSummary,
+    search_text: String,
+    submitted: Option<i64>,
+    completed: Option<i64>,
+    expires: Option<i64>,
+    tool: Option<Box<str>>,
+    stage: Option<(usize, Box<str>)>,
+}
+
+impl Row {
+    fn new(job: JobSummary) -> Self {
+        let epoch = |d: &Option<String>| parse_date(d.as_deref()).map(|d| d.timestamp());
+        Self {
+            search_text: JobFilter::search_text(&job),
+            submitted: epoch(&job.date_submitted),
+            completed: epoch(&job.date_completed),
+            expires: epoch(&job.results_expire_at),
+            tool: job.tool.as_deref().map(|t| t.to_lowercase().into()),
+            stage: job.job_stage.as_deref().map(|stage| {
+                let rank = STAGE_ORDER
+                    .iter()
+                    .position(|s| s.eq_ignore_ascii_case(stage))
+                    .unwrap_or(STAGE_ORDER.len());
+                (rank, stage.to_lowercase().into())
+            }),
+            job,
+        }
+    }
+
+    fn key(&self, field: SortField) -> Option<SortKey> {
+        match field {
+            SortField::DateSubmitted => self.submitted.map(SortKey::Time),
+            SortField::DateCompleted => self.completed.map(SortKey::Time),
+            SortField::ResultsExpireAt => self.expires.map(SortKey::Time),
+            SortField::Tool => self.tool.clone().map(SortKey::Text),
+            SortField::Stage => self
+                .stage
+                .clone()
+                .map(|(rank, name)| SortKey::Stage(rank, name)),
+            SortField::JobId => Some(SortKey::Text(self.job.job_id.as_str().into())),
+        }
+    }
+}
+
+/// Row positions in `sort` order; missing keys last, ties by job ID
+fn sorted(rows: &[Row], sort: QuerySort) -> Vec<usize> {
+    let keys: Vec<Option<SortKey>> = rows.iter().map(|r| r.key(sort.field)).collect();
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    order.sort_by(|&a, &b| {
+        let by_key = match (&keys[a], &keys[b]) {
+            (Some(ka), Some(kb)) => match sort.direction {
+                SortDirection::Asc => ka.cmp(kb),
+                SortDirection::Desc => kb.cmp(ka),
+            },
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        };
+        by_key.then_with(|| rows[a].job.job_id.cmp(&rows[b].job.job_id))
+    });
+    order
+}
+
+struct Index {
+    generation: u64,
+    version: u64,
+    stale: bool,
+    rows: Vec<Row>,
+    // Row positions in each order asked for so far
+    orders: HashMap<QuerySort, Vec<usize>>,
+}
+
+static INDEX: Mutex<Option<Index>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobIndexStats {
+    pub indexed_jobs: usize,
+    // Sort orders computed and kept
+    pub cached_orders: usize,
+}
+
+pub fn memory_stats() -> JobIndexStats {
+    let index = INDEX.lock().unwrap();
+    JobIndexStats {
+        indexed_jobs: index.as_ref().map_or(0, |i| i.rows.len()),
+        cached_orders: index.as_ref().map_or(0, |i| i.orders.len()),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobWindow {
+    pub offset: usize,
+    // Jobs matching the filter, in all windows together
+    pub total: usize,
+    pub jobs: Vec<JobSummary>,
+    pub data_version: u64,
+    // From the disk cache; the session has no listing yet
+    pub stale: bool,
+}
+
+impl Anonymize for JobWindow {
+    fn anonymize(self) -> Self {
+        Self {
+            jobs: self.jobs.anonymize(),
+            ..self
+        }
+    }
+}
+
+/// Jobs the index is built from: the session's last listing, else the disk
+/// cache, flagged stale
+fn source(app: &tauri::AppHandle, generation: u64, username: &str) -> (u64, bool, Vec<JobSummary>) {
+    if let (Some(seq), Some(jobs)) = (
+        crate::listing::seq(generation),
+        crate::listing::cached(app, generation),
+    ) {
+        return (seq, false, jobs);
+    }
+    let jobs = crate::job_cache::saved(app, username)
+        .into_iter()
+        .map(|job| JobSummary { stale: true, ..job })
+        .collect();
+    (0, true, jobs)
+}
+
+fn query(
+    app: &tauri::AppHandle,
+    generation: u64,
+    username: &str,
+    window: QueryWindow,
+    sort: QuerySort,
+    filter: &JobFilter,
+) -> JobWindow {
+    let mut index = INDEX.lock().unwrap();
+    let version = crate::listing::seq(generation).unwrap_or(0);
+    let current = index
+        .as_ref()
+        .is_some_and(|i| i.generation == generation && i.version == version);
+    if !current {
+        *index = None;
+    }
+    let Index {
+        version,
+        stale,
+        rows,
+        orders,
+        ..
+    } = index.get_or_insert_with(|| {
+        let (version, stale, jobs) = source(app, generation, username);
+        Index {
+            generation,
+            version,
+            stale,
+            rows: jobs.into_iter().map(Row::new).collect(),
+            orders: HashMap::new(),
+        }
+    });
+    let order = orders.entry(sort).or_insert_with(|| sorted(rows, sort));
+
+    let expiry = expiry::load(app);
+    let now = clock::now();
+    let limit = window.limit.min(MAX_WINDOW);
+    let mut total = 0;
+    let mut jobs = Vec::new();
+    for &position in order.iter() {
+        let row = &rows[position];
+        if !filter.matches(&row.job, &row.search_text, now, expiry) {
+            continue;
+        }
+        if total >= window.offset && jobs.len() < limit {
+            jobs.push(row.job.clone());
+        }
+        total += 1;
+    }
+
+    JobWindow {
+        offset: window.offset,
+        total,
+        jobs,
+        data_version: *version,
+        stale: *stale,
+    }
+}
+
+/// One window of the job list, sorted and filtered
+#[tauri::command]
+pub async fn query_jobs(
+    window: QueryWindow,
+    sort: Option<QuerySort>,
+    filter: Option<JobFilter>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<JobWindow, String> {
+    let session = state.session.require()?;
+    let username = session.credentials.username.clone();
+    let filter = filter.unwrap_or_default();
+    let task_app = app.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        query(
+            &task_app,
+            session.generation,
+            &username,
+            window,
+            sort.unwrap_or_default(),
+            &filter,
+        )
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?;
+    Ok(anonymize::outgoing(&app, result))
+}
@@ -0,0 +1,442 @@
+// Single-job HTML reports.
+//
+// export_job_report writes one self-contained HTML file about a job, for
+// mailing to collaborators: its record, the parameters it was submitted
+// with (known only for jobs submitted from this app), the stage timing,
+// the message timeline, the result files and the local tags and note. The
+// page comes from the job_report.html template compiled into the binary
+// and has inline styles, no scripts and no remote assets. Small PNG results
+// are inlined as data URIs, taken from the job's newest downloaded archive
+// when it has one and otherwise fetched from NSG while the total stays
+// under MAX_FETCHED_BYTES. IDs are real except in showcase mode.
+
+use crate::anonymize::{anonymize_job_id, anonymize_url, real_url};
+use crate::api::{JobRecord, NsgApi};
+use crate::job_timing::JobTiming;
+use crate::local_jobs::LocalJob;
+use crate::notify::format_size;
+use crate::AppState;
+use base64::Engine;
+use nsg_cli::models::OutputFile;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+use tauri::State;
+
+const TEMPLATE: &str = include_str!("job_report.html");
+
+// PNGs larger than this are listed but not inlined
+const MAX_INLINE_IMAGE_BYTES: u64 = 1024 * 1024;
+const MAX_INLINE_IMAGES: usize = 12;
+// Bytes fetched from NSG for images not in a downloaded archive
+const MAX_FETCHED_BYTES: u64 = 4 * 1024 * 1024;
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Words that mark a message as the likely reason a job failed
+const FAILURE_WORDS: &[&str] = &["error", "fail", "exceed", "killed", "abort", "denied"];
+
+/// What the report is built from
+struct ReportData {
+    record: JobRecord,
+    // Err when the listing failed; empty for jobs without results yet
+    files: Result<Vec<OutputFile>, String>,
+    // filename -> PNG bytes
+    images: BTreeMap<String, Vec<u8>>,
+    local: Option<LocalJob>,
+    timing: JobTiming,
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn is_png(filename: &str) -> bool {
+    filename.to_lowercase().ends_with(".png")
+}
+
+fn duration(secs: Option<i64>) -> String {
+    let Some(secs) = secs else {
+        return "—".to_string();
+    };
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if h > 0 {
+        format!("{} h {:02} min", h, m)
+    } else if m > 0 {
+        format!("{} min {:02} s", m, s)
+    } else {
+        format!("{} s", s)
+    }
+}
+
+/// Two-column table of (label, already escaped HTML)
+fn fields(rows: &[(&str, String)]) -> String {
+    let mut html = String::from("<table>\n");
+    for (label, value) in rows {
+        html.push_str(&format!(
+            "<tr><th scope=\"row\">{}</th><td>{}</td></tr>\n",
+            escape(label),
+            value
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn text_or_dash(value: Option<&str>) -> String {
+    value
+        .filter(|v| !v.trim().is_empty())
+        .map(escape)
+        .unwrap_or_else(|| "—".to_string())
+}
+
+/// The message most likely to explain a failure: the last one that sounds
+/// like an error, else the last one
+fn failure_message(record: &JobRecord) -> Option<&crate::xml::JobMessage> {
+    let messages = &record.extras.messages;
+    messages
+        .iter()
+        .rev()
+        .find(|m| {
+            let text = m.text.to_lowercase();
+            FAILURE_WORDS.iter().any(|w| text.contains(w))
+        })
+        .or(messages.last())
+}
+
+fn png_data(bytes: Vec<u8>) -> Option<Vec<u8>> {
+    bytes.starts_with(PNG_SIGNATURE).then_some(bytes)
+}
+
+/// Small PNGs of `files` found in the job's newest downloaded archive
+fn archived_images(
+    app: &tauri::AppHandle,
+    job_id: &str,
+    files: &[&OutputFile],
+) -> BTreeMap<String, Vec<u8>> {
+    let mut images = BTreeMap::new();
+    let Ok(mut entries) = crate::history::load(app) else {
+        return images;
+    };
+    entries.retain(|e| e.job_id == job_id);
+    entries.sort_by(|a, b| b.downloaded_at.cmp(&a.downloaded_at));
+    let Some(mut archive) = entries.iter().find_map(|e| {
+        let file = std::fs::File::open(&e.archive_path).ok()?;
+        zip::ZipArchive::new(file).ok()
+    }) else {
+        return images;
+    };
+
+    let renamed = crate::archive::read_job_info(&mut archive)
+        .map(|info| info.renamed)
+        .unwrap_or_default();
+    for file in files {
+        // The archive may have been written with or without the tree, and
+        // under another name when that one was taken
+        let names = renamed.get(&file.filename).cloned().into_iter().chain(
+            [true, false]
+                .into_iter()
+                .filter_map(|tree| crate::download::entry_name(&file.filename, tree).ok()),
+        );
+        for name in names {
+            let Ok(entry) = archive.by_name(&name) else {
+                continue;
+            };
+            let mut bytes = Vec::new();
+            if entry
+                .take(MAX_INLINE_IMAGE_BYTES + 1)
+                .read_to_end(&mut bytes)
+                .is_ok()
+                && bytes.len() as u64 <= MAX_INLINE_IMAGE_BYTES
+            {
+                if let Some(png) = png_data(bytes) {
+                    images.insert(file.filename.clone(), png);
+                }
+            }
+            break;
+        }
+    }
+    images
+}
+
+/// Fetch the record, the result listing and the images. Blocking.
+fn gather(app: &tauri::AppHandle, api: &NsgApi, url: &str) -> Result<ReportData, String> {
+    let record = api
+        .job_record(url)
+        .map_err(|e| format!("Failed to get job status: {}", e))?;
+    let status = &record.status;
+
+    let files = if status.results_uri.is_some() {
+        api.output_files(status)
+            .map_err(|e| format!("Failed to list result files: {}", e))
+    } else {
+        Ok(Vec::new())
+    };
+
+    let mut images = BTreeMap::new();
+    if let Ok(files) = &files {
+        let wanted: Vec<&OutputFile> = files
+            .iter()
+            .filter(|f| is_png(&f.filename) && f.size > 0 && f.size <= MAX_INLINE_IMAGE_BYTES)
+            .take(MAX_INLINE_IMAGES)
+            .collect();
+        images = archived_images(app, &status.job_id, &wanted);
+
+        let mut fetched = 0;
+        for file in wanted {
+            if images.contains_key(&file.filename) || fetched + file.size > MAX_FETCHED_BYTES {
+                continue;
+            }
+            // An image that can't be fetched is just listed
+            if let Some(png) = api.read_range(file, 0, file.size).ok().and_then(png_data) {
+                fetched += file.size;
+                images.insert(file.filename.clone(), png);
+            }
+        }
+    }
+
+    let local = crate::local_jobs::load(app)?.remove(&status.job_id);
+    let sightings = local.as_ref().map(|l| l.stages.clone()).unwrap_or_default();
+    let timing = crate::job_timing::of_record(&record, &sightings);
+
+    Ok(ReportData {
+        record,
+        files,
+        images,
+        local,
+        timing,
+    })
+}
+
+fn render(data: &ReportData) -> String {
+    let status = &data.record.status;
+    let extras = &data.record.extras;
+    let job_id = anonymize_job_id(&status.job_id);
+    let mut body = String::new();
+
+    body.push_str(&format!("<h1>Job {}</h1>\n", escape(&job_id)));
+    body.push_str(&format!(
+        "<p class=\"subtitle\">{} · {}</p>\n",
+        text_or_dash(status.tool_id.as_deref()),
+        escape(&crate::i18n::stage_label(&status.job_stage))
+    ));
+
+    if status.failed {
+        body.push_str("<section class=\"failed\">\n<h2>Failure</h2>\n");
+        match failure_message(&data.record) {
+            Some(message) => body.push_str(&format!(
+                "<p>{}</p>\n<p class=\"estimated\">{} {}</p>\n",
+                escape(&message.text),
+                text_or_dash(message.stage.as_deref()),
+                text_or_dash(message.timestamp.as_deref())
+            )),
+            None => body.push_str("<p>NSG reported the job as failed without a message.</p>\n"),
+        }
+        body.push_str("</section>\n");
+    }
+
+    body.push_str("<h2>Job</h2>\n");
+    body.push_str(&fields(&[
+        ("Job ID", format!("<code>{}</code>", escape(&job_id))),
+        (
+            "URL",
+            format!("<code>{}</code>", escape(&anonymize_url(&status.self_uri))),
+        ),
+        ("Tool", text_or_dash(status.tool_id.as_deref())),
+        ("Tool version", text_or_dash(extras.tool_version.as_deref())),
+        (
+            "Compute resource",
+            text_or_dash(extras.compute_resource.as_deref()),
+        ),
+        (
+            "Stage",
+            escape(&crate::i18n::stage_label(&status.job_stage)),
+        ),
+        (
+            "Failed",
+            if status.failed { "yes" } else { "no" }.to_string(),
+        ),
+        ("Submitted", text_or_dash(status.date_submitted.as_deref())),
+        ("Completed", text_or_dash(status.date_completed.as_deref())),
+        (
+            "Results expire",
+            text_or_dash(extras.results_expire_at.as_deref()),
+        ),
+    ]));
+
+    body.push_str("<h2>Parameters</h2>\n");
+    let params = data.local.as_ref().map(|l| &l.params);
+    match params.filter(|p| !p.is_empty()) {
+        Some(params) => {
+            body.push_str("<table>\n<tr><th>Parameter</th><th>Value</th></tr>\n");
+            for (name, value) in params {
+                body.push_str(&format!(
+                    "<tr><td><code>{}</code></td><td><code>{}</code></td></tr>\n",
+                    escape(name),
+                    escape(value)
+                ));
+            }
+            body.push_str("</table>\n");
+        }
+        None => body.push_str(
+            "<p class=\"empty\">Not known: the job wasn't submitted from this app, and NSG doesn't return parameters.</p>\n",
+        ),
+    }
+    if !extras.metadata.is_empty() {
+        body.push_str("<table>\n<tr><th>Metadata</th><th>Value</th></tr>\n");
+        for (key, value) in &extras.metadata {
+            body.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td></tr>\n",
+                escape(key),
+                escape(value)
+            ));
+        }
+        body.push_str("</table>\n");
+    }
+
+    let timing = &data.timing;
+    body.push_str("<h2>Timing</h2>\n");
+    body.push_str(&fields(&[
+        ("Queue wait", duration(timing.queue_wait_secs)),
+        ("Staging", duration(timing.staging_secs)),
+        ("Execution", duration(timing.execution_secs)),
+        ("Total", duration(timing.total_secs)),
+    ]));
+    if !timing.segments.is_empty() {
+        body.push_str("<table>\n<tr><th>Stage</th><th>Entered</th><th>Duration</th></tr>\n");
+        for segment in &timing.segments {
+            let class = if segment.estimated {
+                " class=\"estimated\""
+            } else {
+                ""
+            };
+            body.push_str(&format!(
+                "<tr{}><td>{}</td><td>{}</td><td class=\"num\">{}</td></tr>\n",
+                class,
+                escape(&segment.stage),
+                escape(&segment.entered_at),
+                duration(segment.duration_secs)
+            ));
+        }
+        body.push_str("</table>\n");
+    }
+    if timing.segments.iter().any(|s| s.estimated) {
+        body.push_str(
+            "<p class=\"estimated\">Stages in italics were timed from the app's polling and are approximate.</p>\n",
+        );
+    }
+
+    body.push_str("<h2>Messages</h2>\n");
+    if extras.messages.is_empty() {
+        body.push_str("<p class=\"empty\">No messages.</p>\n");
+    } else {
+        body.push_str("<table>\n<tr><th>Time</th><th>Stage</th><th>Message</th></tr>\n");
+        for message in &extras.messages {
+            body.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                text_or_dash(message.timestamp.as_deref()),
+                text_or_dash(message.stage.as_deref()),
+                escape(&message.text)
+            ));
+        }
+        body.push_str("</table>\n");
+    }
+
+    body.push_str("<h2>Result files</h2>\n");
+    match &data.files {
+        Err(e) => body.push_str(&format!("<p class=\"empty\">{}</p>\n", escape(e))),
+        Ok(files) if files.is_empty() => body.push_str("<p class=\"empty\">No result files.</p>\n"),
+        Ok(files) => {
+            let total: u64 = files.iter().map(|f| f.size).sum();
+            body.push_str("<table>\n<tr><th>File</th><th>Size</th></tr>\n");
+            for file in files {
+                body.push_str(&format!(
+                    "<tr><td><code>{}</code></td><td class=\"num\">{}</td></tr>\n",
+                    escape(&file.filename),
+                    format_size(file.size)
+                ));
+            }
+            body.push_str(&format!(
+                "<tr><th>{} files</th><th class=\"num\">{}</th></tr>\n</table>\n",
+                files.len(),
+                format_size(total)
+            ));
+        }
+    }
+    for (filename, png) in &data.images {
+        body.push_str(&format!(
+            "<figure><img src=\"data:image/png;base64,{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>\n",
+            base64::engine::general_purpose::STANDARD.encode(png),
+            escape(filename),
+            escape(filename)
+        ));
+    }
+
+    body.push_str("<h2>Notes</h2>\n");
+    let tags = data
+        .local
+        .as_ref()
+        .map(|l| l.tags.as_slice())
+        .unwrap_or(&[]);
+    let note = data.local.as_ref().and_then(|l| l.note.as_deref());
+    if tags.is_empty() && note.is_none() {
+        body.push_str("<p class=\"empty\">No tags or note.</p>\n");
+    }
+    if !tags.is_empty() {
+        body.push_str("<p>");
+        for tag in tags {
+            body.push_str(&format!("<span class=\"tag\">{}</span>", escape(tag)));
+        }
+        body.push_str("</p>\n");
+    }
+    if let Some(note) = note {
+        body.push_str(&format!("<p class=\"note\">{}</p>\n", escape(note)));
+    }
+
+    TEMPLATE
+        .replace("{{title}}", &escape(&format!("NSG job {}", job_id)))
+        .replace(
+            "{{generated}}",
+            &crate::clock::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+        )
+        .replace("{{body}}", &body)
+}
+
+/// Write an HTML report of a job to `path` (".html" is added when it has
+/// no extension), returning the path written
+#[tauri::command]
+pub async fn export_job_report(
+    job_url: String,
+    path: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let url = real_url(&job_url);
+    let worker_app = app.clone();
+    let data = tokio::task::spawn_blocking(move || gather(&worker_app, &api, &url))
+        .await
+        .map_err(|e| format!("Task error: {}", e))??;
+    state.session.ensure_current(session.generation)?;
+
+    let mut path = PathBuf::from(path);
+    if path.extension().is_none() {
+        path.set_extension("html");
+    }
+    let path = crate::path_access::authorize(&app, &path)?;
+    std::fs::write(&path, render(&data))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.display().to_string())
+}
@@ -0,0 +1,319 @@
+// How long a job spent in each stage.
+//
+// Stage boundaries come from the job's messages, which NSG stamps with the
+// stage they were written in, from the submission and completion dates of
+// the job record, and failing those from the stages the app saw while
+// polling (local_jobs.json). A boundary taken from polling is only as
+// precise as the poll interval and its segment is marked `estimated`.
+// Queue wait is the time in NSG's QUEUE stage; execution is the SUBMITTED
+// stage, which includes any wait in the cluster's own queue since NSG
+// doesn't tell the two apart. Staging is the rest up to completion.
+
+use crate::anonymize::{self, anonymize_job_id, real_url, Anonymize};
+use crate::api::JobRecord;
+use crate::clock;
+use crate::local_jobs::StageSighting;
+use crate::monitor::parse_date;
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tauri::State;
+
+const QUEUE_STAGE: &str = "QUEUE";
+const EXECUTION_STAGE: &str = "SUBMITTED";
+const FINAL_STAGE: &str = "COMPLETED";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageSegment {
+    pub stage: String,
+    pub entered_at: String,
+    // None for the last segment of a job that hasn't finished, and for
+    // COMPLETED
+    pub duration_secs: Option<i64>,
+    // A boundary came from local polling rather than the server
+    pub estimated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobTiming {
+    pub job_id: String,
+    pub stage: String,
+    // Oldest first
+    pub segments: Vec<StageSegment>,
+    pub queue_wait_secs: Option<i64>,
+    pub staging_secs: Option<i64>,
+    pub execution_secs: Option<i64>,
+    pub total_secs: Option<i64>,
+    // The job hasn't finished, or a stage's start or end is unknown
+    pub incomplete: bool,
+}
+
+impl Anonymize for JobTiming {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            ..self
+        }
+    }
+}
+
+/// When a stage was first seen, and whether only polling saw it
+struct Entry {
+    at: DateTime<Utc>,
+    estimated: bool,
+}
+
+fn note(entries: &mut BTreeMap<String, Entry>, stage: &str, at: DateTime<Utc>, estimated: bool) {
+    let stage = stage.trim().to_uppercase();
+    if stage.is_empty() {
+        return;
+    }
+    match entries.get_mut(&stage) {
+        // The server's time beats one from polling; otherwise the earliest
+        Some(existing) if existing.estimated && !estimated => {
+            *existing = Entry { at, estimated };
+        }
+        Some(existing) if existing.estimated == estimated && at < existing.at => {
+            existing.at = at;
+        }
+        Some(_) => {}
+        None => {
+            entries.insert(stage, Entry { at, estimated });
+        }
+    }
+}
+
+/// Summed duration of the segments of the picked stages; None when there
+/// are none or one has no end
+fn total(segments: &[StageSegment], pick: impl Fn(&str) -> bool) -> Option<i64> {
+    let mut picked = segments.iter().filter(|s| pick(&s.stage)).peekable();
+    picked.peek()?;
+    picked.map(|s| s.duration_secs).sum()
+}
+
+fn timing(
+    job_id: String,
+    stage: String,
+    failed: bool,
+    date_submitted: Option<&str>,
+    date_completed: Option<&str>,
+    messages: &[(Option<String>, Option<String>)],
+    sightings: &[StageSighting],
+) -> JobTiming {
+    let mut entries = BTreeMap::new();
+    for (timestamp, message_stage) in messages {
+        if let (Some(at), Some(message_stage)) = (parse_date(timestamp.as_deref()), message_stage) {
+            note(&mut entries, message_stage, at, false);
+        }
+    }
+    if let Some(at) = parse_date(date_submitted) {
+        note(&mut entries, QUEUE_STAGE, at, false);
+    }
+    if let Some(at) = parse_date(date_completed) {
+        note(&mut entries, FINAL_STAGE, at, false);
+    }
+    for sighting in sightings {
+        if let Some(at) = parse_date(Some(&sighting.seen_at)) {
+            note(&mut entries, &sighting.stage, at, true);
+        }
+    }
+
+    let mut ordered: Vec<(String, Entry)> = entries.into_iter().collect();
+    ordered.sort_by_key(|(_, entry)| entry.at);
+
+    let finished = failed || stage.eq_ignore_ascii_case(FINAL_STAGE);
+    let segments: Vec<StageSegment> = ordered
+        .iter()
+        .enumerate()
+        .map(|(i, (name, entry))| {
+            let next = ordered.get(i + 1).map(|(_, next)| next);
+            StageSegment {
+                stage: name.clone(),
+                entered_at: entry.at.to_rfc3339(),
+                duration_secs: next.map(|next| clock::between(entry.at, next.at).num_seconds()),
+                estimated: entry.estimated || next.is_some_and(|next| next.estimated),
+            }
+        })
+        .collect();
+
+    let queue_wait_secs = total(&segments, |s| s == QUEUE_STAGE);
+    let execution_secs = total(&segments, |s| s == EXECUTION_STAGE);
+    let staging_secs = total(&segments, |s| {
+        s != QUEUE_STAGE && s != EXECUTION_STAGE && s != FINAL_STAGE
+    });
+    let total_secs = match (ordered.first(), ordered.last()) {
+        (Some((_, first)), Some((_, last))) if finished && ordered.len() > 1 => {
+            Some(clock::between(first.at, last.at).num_seconds())
+        }
+        _ => None,
+    };
+
+    // A failed job may never have reached SUBMITTED or COMPLETED
+    let has = |name: &str| ordered.iter().any(|(s, _)| s == name);
+    let open_segment = segments
+        .iter()
+        .any(|s| s.stage != FINAL_STAGE && s.duration_secs.is_none());
+    let incomplete = !finished
+        || open_segment
+        || !has(QUEUE_STAGE)
+        || (!failed && (!has(EXECUTION_STAGE) || !has(FINAL_STAGE)));
+
+    JobTiming {
+        job_id,
+        stage,
+        segments,
+        queue_wait_secs,
+        staging_secs,
+        execution_secs,
+        total_secs,
+        incomplete,
+    }
+}
+
+/// Timing of a fetched job record, with the stages seen while polling it
+pub fn of_record(record: &JobRecord, sightings: &[StageSighting]) -> JobTiming {
+    let status = &record.status;
+    let messages: Vec<_> = record
+        .extras
+        .messages
+        .iter()
+        .map(|m| (m.timestamp.clone(), m.stage.clone()))
+        .collect();
+    timing(
+        status.job_id.clone(),
+        status.job_stage.clone(),
+        status.failed,
+        status.date_submitted.as_deref(),
+        status.date_completed.as_deref(),
+        &messages,
+        sightings,
+    )
+}
+
+/// Time spent in each stage of a job, with totals for queue wait, staging
+/// and execution
+#[tauri::command]
+pub async fn get_job_timing(
+    job_url: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<JobTiming, String> {
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let url = real_url(&job_url);
+    let record = tokio::task::spawn_blocking(move || api.job_record(&url))
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+        .map_err(|e| format!("Failed to get job status: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    let sightings = crate::local_jobs::load(&app)?
+        .remove(&record.status.job_id)
+        .map(|job| job.stages)
+        .unwrap_or_default();
+    Ok(anonymize::outgoing(&app, of_record(&record, &sightings)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(at: &str, stage: &str) -> (Option<String>, Option<String>) {
+        (Some(at.to_string()), Some(stage.to_string()))
+    }
+
+    #[test]
+    fn a_finished_job_has_every_total() {
+        let timing = timing(
+            "NGBW-JOB-1".to_string(),
+            "COMPLETED".to_string(),
+            false,
+            Some("2026-01-02T10:00:00Z"),
+            Some("2026-01-02T13:00:00Z"),
+            &[
+                message("2026-01-02T10:30:00Z", "SUBMITTED"),
+                message("2026-01-02T12:30:00Z", "LOAD_RESULTS"),
+            ],
+            &[],
+        );
+        assert_eq!(timing.queue_wait_secs, Some(1800));
+        assert_eq!(timing.execution_secs, Some(7200));
+        assert_eq!(timing.staging_secs, Some(1800));
+        assert_eq!(timing.total_secs, Some(3 * 3600));
+        assert!(!timing.incomplete);
+    }
+
+    #[test]
+    fn zone_less_dates_count_real_time_across_daylight_saving() {
+        // Pacific time; the night of 1 November 2026 has 25 hours
+        let timing = timing(
+            "NGBW-JOB-1".to_string(),
+            "COMPLETED".to_string(),
+            false,
+            Some("2026-10-31 23:00:00"),
+            Some("2026-11-01 23:00:00"),
+            &[message("2026-10-31 23:00:00", "SUBMITTED")],
+            &[],
+        );
+        assert_eq!(timing.total_secs, Some(25 * 3600));
+        assert_eq!(timing.execution_secs, Some(25 * 3600));
+    }
+
+    #[test]
+    fn the_server_time_wins_over_a_sighting() {
+        let sightings = [StageSighting {
+            stage: "submitted".to_string(),
+            seen_at: "2026-01-02T11:00:00Z".to_string(),
+        }];
+        let timing = timing(
+            "NGBW-JOB-1".to_string(),
+            "SUBMITTED".to_string(),
+            false,
+            Some("2026-01-02T10:00:00Z"),
+            None,
+            &[message("2026-01-02T10:30:00Z", "SUBMITTED")],
+            &sightings,
+        );
+        assert_eq!(timing.queue_wait_secs, Some(1800));
+        assert!(timing.segments.iter().all(|s| !s.estimated));
+        assert_eq!(timing.total_secs, None);
+        assert!(timing.incomplete);
+    }
+
+    #[test]
+    fn a_sighting_marks_its_segments_estimated() {
+        let sightings = [StageSighting {
+            stage: "SUBMITTED".to_string(),
+            seen_at: "2026-01-02T11:00:00Z".to_string(),
+        }];
+        let timing = timing(
+            "NGBW-JOB-1".to_string(),
+            "SUBMITTED".to_string(),
+            false,
+            Some("2026-01-02T10:00:00Z"),
+            None,
+            &[],
+            &sightings,
+        );
+        assert_eq!(timing.queue_wait_secs, Some(3600));
+        assert!(timing.segments.iter().all(|s| s.estimated));
+    }
+
+    #[test]
+    fn unreadable_dates_are_left_out() {
+        let timing = timing(
+            "NGBW-JOB-1".to_string(),
+            "COMPLETED".to_string(),
+            false,
+            Some("not a date"),
+            Some("2026-01-02T13:00:00Z"),
+            &[(None, Some("SUBMITTED".to_string()))],
+            &[],
+        );
+        assert_eq!(timing.segments.len(), 1);
+        assert_eq!(timing.total_secs, None);
+        assert!(timing.incomplete);
+    }
+}
@@ -0,0 +1,193 @@
+// Credentials in the OS credential store.
+//
+// nsg-cli keeps the password and app key in a plain JSON file. With the
+// `credential_backend` preference set to "keychain" they are kept in the
+// macOS Keychain, the Windows Credential Manager or the Secret Service
+// (GNOME Keyring, KWallet) instead, as one item per account holding the
+// credentials as JSON, through the keyring crate. load_saved is what
+// auto-connect and the login form read, whichever backend is set. In
+// portable mode the OS store is never used, so credentials don't stay
+// behind on the host machine; profile secrets are sealed with the vault
+// passphrase instead.
+
+use crate::credentials_file::LoadedCredentials;
+use crate::i18n::Msg;
+use crate::portable::DataMode;
+use crate::preferences;
+use nsg_cli::Credentials;
+use serde_json::json;
+
+pub const BACKEND_KEY: &str = "credential_backend";
+pub const BACKENDS: &[&str] = &["file", "keychain"];
+
+// Service name of every item the app stores
+const SERVICE: &str = "nsg-gui";
+// Account of the credentials saved without a profile
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+/// Whether credentials are saved in the OS store rather than the file.
+/// Never in portable mode, whatever the preference says.
+pub fn uses_keychain(app: &tauri::AppHandle) -> bool {
+    crate::portable::mode() != DataMode::Portable
+        && preferences::of(app)
+            .get(BACKEND_KEY)
+            .and_then(|v| v.as_str().map(|s| s == "keychain"))
+            .unwrap_or(false)
+}
+
+/// Refuse the OS store in portable mode: the credentials would stay on the
+/// machine the stick was plugged into
+pub fn ensure_available() -> Result<(), String> {
+    if crate::portable::mode() == DataMode::Portable {
+        return Err("The OS credential store isn't used in portable mode".to_string());
+    }
+    Ok(())
+}
+
+// Profile IDs and DEFAULT_ACCOUNT; anything else is a bug
+fn check_account(account: &str) -> Result<(), String> {
+    if account.is_empty()
+        || !account
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("Invalid credential store account: {}", account));
+    }
+    Ok(())
+}
+
+mod store {
+    use super::SERVICE;
+    use keyring::{Entry, Error};
+
+    fn entry(account: &str) -> Result<Entry, String> {
+        Entry::new(SERVICE, account)
+            .map_err(|e| format!("Failed to open the OS credential store: {}", e))
+    }
+
+    pub fn save(account: &str, secret: &str) -> Result<(), String> {
+        entry(account)?
+            .set_password(secret)
+            .map_err(|e| format!("Failed to save to the OS credential store: {}", e))
+    }
+
+    pub fn load(account: &str) -> Result<Option<String>, String> {
+        match entry(account)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!(
+                "Failed to read from the OS credential store: {}",
+                e
+            )),
+        }
+    }
+
+    pub fn delete(account: &str) -> Result<(), String> {
+        match entry(account)?.delete_credential() {
+            Ok(()) | Err(Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!(
+                "Failed to delete from the OS credential store: {}",
+                e
+            )),
+        }
+    }
+}
+
+/// Save credentials under `account` in the OS store
+pub fn save(account: &str, credentials: &Credentials) -> Result<(), String> {
+    check_account(account)?;
+    let secret = serde_json::to_string(credentials)
+        .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+    store::save(account, &secret)
+}
+
+/// The credentials saved under `account`, None when there are none
+pub fn load(account: &str) -> Result<Option<Credentials>, String> {
+    check_account(account)?;
+    let Some(secret) = store::load(account)? else {
+        return Ok(None);
+    };
+    serde_json::from_str(&secret)
+        .map(Some)
+        .map_err(|_| "The credentials in the OS credential store are not valid".to_string())
+}
+
+/// Remove the credentials saved under `account`, if any
+pub fn delete(account: &str) -> Result<(), String> {
+    check_account(account)?;
+    store::delete(account)
+}
+
+fn load_default() -> LoadedCredentials {
+    let path = format!("OS credential store ({})", SERVICE);
+    match load(DEFAULT_ACCOUNT) {
+        Ok(Some(credentials)) => LoadedCredentials::Found { credentials },
+        Ok(None) => LoadedCredentials::Missing { path },
+        Err(error) => LoadedCredentials::Unreadable { path, error },
+    }
+}
+
+/// Remove the app's credentials from the OS store, returning whether there
+/// were any
+pub fn purge() -> Result<bool, String> {
+    let existed = store::load(DEFAULT_ACCOUNT)?.is_some();
+    store::delete(DEFAULT_ACCOUNT)?;
+    Ok(existed)
+}
+
+/// The saved credentials from the configured backend
+pub fn load_saved(app: &tauri::AppHandle) -> LoadedCredentials {
+    if uses_keychain(app) {
+        load_default()
+    } else {
+        crate::credentials_file::load()
+    }
+}
+
+/// Save credentials in the OS store, where load_credentials_secure and,
+/// with the keychain backend, auto-connect read them
+#[tauri::command]
+pub async fn save_credentials_secure(
+    app: tauri::AppHandle,
+    credentials: Credentials,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeConnectionSettings)?;
+    ensure_available()?;
+    tokio::task::spawn_blocking(move || save(DEFAULT_ACCOUNT, &credentials))
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+}
+
+/// The credentials in the OS store, whichever backend is set
+#[tauri::command]
+pub async fn load_credentials_secure() -> Result<LoadedCredentials, String> {
+    tokio::task::spawn_blocking(load_default)
+        .await
+        .map_err(|e| format!("Task error: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_credential_backend(app: tauri::AppHandle) -> Result<String, String> {
+    let backend = if uses_keychain(&app) {
+        "keychain"
+    } else {
+        "file"
+    };
+    Ok(backend.to_string())
+}
+
+/// Where saved credentials are read from: "file" or "keychain". Nothing is
+/// moved; save_credentials_secure fills the keychain. "keychain" is refused
+/// in portable mode.
+#[tauri::command]
+pub async fn set_credential_backend(app: tauri::AppHandle, backend: String) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeConnectionSettings)?;
+    if !BACKENDS.contains(&backend.as_str()) {
+        return Err(format!("Unknown credential backend: {}", backend));
+    }
+    if backend == "keychain" {
+        ensure_available()?;
+    }
+    preferences::of(&app).set(BACKEND_KEY, json!(backend));
+    Ok(())
+}
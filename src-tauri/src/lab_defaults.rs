@@ -0,0 +1,326 @@
+// Submission defaults shared by a lab.
+//
+// A lab can keep a lab_defaults.json on a shared drive with parameter and
+// metadata defaults, for every tool and per tool:
+//
+//     { "metadata": { "statusEmail": "true" },
+//       "tools": { "NEURON_EXPANSE": { "params": { "vparam.runtime_": "2" } } } }
+//
+// The file is found through NSG_GUI_LAB_DEFAULTS, or else the
+// `lab_defaults_path` preference, and read again whenever its modification
+// time changes. A submission's fields are layered app defaults < lab
+// defaults < the user's own settings < explicit arguments, and the response
+// says which layer each value came from. A missing or malformed file is
+// reported once per version through `lab-defaults-invalid` and otherwise
+// ignored, so it never blocks a submission. There are no tool schemas in
+// the app, so only the file's shape is checked, not what a tool accepts.
+
+use crate::email::{self, EMAIL_ADDRESS_FIELD, STATUS_EMAIL_FIELD};
+use crate::i18n::Msg;
+use crate::preferences;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::Emitter;
+
+pub const PATH_KEY: &str = "lab_defaults_path";
+pub const PATH_ENV: &str = "NSG_GUI_LAB_DEFAULTS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamSource {
+    // Built into the app, e.g. completion emails on
+    App,
+    Lab,
+    // The user's settings, e.g. the default notification address
+    User,
+    Explicit,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ToolSection {
+    #[serde(default)]
+    params: BTreeMap<String, Value>,
+    #[serde(default)]
+    metadata: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LabFile {
+    #[serde(default)]
+    params: BTreeMap<String, Value>,
+    #[serde(default)]
+    metadata: BTreeMap<String, Value>,
+    #[serde(default)]
+    tools: BTreeMap<String, ToolSection>,
+}
+
+/// The validated file: form fields for every tool, and per uppercased tool
+#[derive(Debug, Default)]
+struct LabDefaults {
+    common: BTreeMap<String, String>,
+    tools: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+struct Loaded {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    result: Result<Arc<LabDefaults>, String>,
+}
+
+static LOADED: Mutex<Option<Loaded>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LabDefaultsWarning {
+    pub path: String,
+    pub message: String,
+}
+
+/// Final form fields of a submission and where each came from
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Layered {
+    pub params: BTreeMap<String, String>,
+    pub sources: BTreeMap<String, ParamSource>,
+}
+
+impl Layered {
+    fn apply(&mut self, source: ParamSource, fields: BTreeMap<String, String>) {
+        for (name, value) in fields {
+            self.sources.insert(name.clone(), source);
+            self.params.insert(name, value);
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.params.remove(name);
+        self.sources.remove(name);
+    }
+}
+
+/// The configured file, if any: the environment variable wins over the
+/// preference
+pub fn configured_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    let from_env = std::env::var(PATH_ENV).ok();
+    let from_prefs = preferences::of(app)
+        .get(PATH_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
+    from_env
+        .into_iter()
+        .chain(from_prefs)
+        .map(|p| p.trim().to_string())
+        .find(|p| !p.is_empty())
+        .map(PathBuf::from)
+}
+
+fn field_value(name: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(format!("{} must be a string, number or boolean", name)),
+    }
+}
+
+/// Form fields of one section, with metadata keys given their prefix
+fn section_fields(
+    section: &str,
+    params: &BTreeMap<String, Value>,
+    metadata: &BTreeMap<String, Value>,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut fields = BTreeMap::new();
+    for (name, value) in params {
+        let trimmed = name.trim();
+        if trimmed.is_empty() {
+            return Err(format!("{}: a parameter has no name", section));
+        }
+        if trimmed == "tool" || trimmed.starts_with("input.") {
+            return Err(format!(
+                "{}: {} can't have a default; it names the tool or an input file",
+                section, trimmed
+            ));
+        }
+        if trimmed.starts_with("metadata.") {
+            return Err(format!(
+                "{}: put {} under \"metadata\", without the prefix",
+                section, trimmed
+            ));
+        }
+        let label = format!("{}: {}", section, trimmed);
+        fields.insert(trimmed.to_string(), field_value(&label, value)?);
+    }
+    for (key, value) in metadata {
+        let key = key.trim();
+        let key = key.strip_prefix("metadata.").unwrap_or(key);
+        if key.is_empty() {
+            return Err(format!("{}: a metadata entry has no name", section));
+        }
+        let label = format!("{}: metadata {}", section, key);
+        fields.insert(format!("metadata.{}", key), field_value(&label, value)?);
+    }
+    Ok(fields)
+}
+
+fn parse(content: &str) -> Result<LabDefaults, String> {
+    let file: LabFile =
+        serde_json::from_str(content).map_err(|e| format!("Not a valid defaults file: {}", e))?;
+
+    let mut defaults = LabDefaults {
+        common: section_fields("all tools", &file.params, &file.metadata)?,
+        tools: BTreeMap::new(),
+    };
+    for (tool, section) in &file.tools {
+        let tool = tool.trim();
+        if tool.is_empty() {
+            return Err("A tool section has no tool ID".to_string());
+        }
+        let fields = section_fields(tool, &section.params, &section.metadata)?;
+        defaults.tools.insert(tool.to_uppercase(), fields);
+    }
+    Ok(defaults)
+}
+
+/// The configured file's defaults, read again if it changed. None when no
+/// file is configured; Err when it can't be used.
+fn current(app: &tauri::AppHandle) -> Option<(PathBuf, Result<Arc<LabDefaults>, String>)> {
+    let path = configured_path(app)?;
+    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+    let mut loaded = LOADED.lock().unwrap();
+    if let Some(loaded) = loaded
+        .as_ref()
+        .filter(|l| l.path == path && l.modified == modified)
+    {
+        return Some((path, loaded.result.clone()));
+    }
+
+    let result = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+        .and_then(|content| parse(&content))
+        .map(Arc::new);
+    if let Err(message) = &result {
+        let _ = app.emit(
+            crate::events::LAB_DEFAULTS_INVALID,
+            LabDefaultsWarning {
+                path: path.display().to_string(),
+                message: message.clone(),
+            },
+        );
+    }
+    *loaded = Some(Loaded {
+        path: path.clone(),
+        modified,
+        result: result.clone(),
+    });
+    Some((path, result))
+}
+
+fn lab_fields(app: &tauri::AppHandle, tool: &str) -> BTreeMap<String, String> {
+    let Some((_, Ok(defaults))) = current(app) else {
+        return BTreeMap::new();
+    };
+    let mut fields = defaults.common.clone();
+    if let Some(tool_fields) = defaults.tools.get(&tool.trim().to_uppercase()) {
+        fields.extend(tool_fields.clone());
+    }
+    fields
+}
+
+/// Layer a submission's form fields: app defaults < lab defaults < the
+/// user's settings < `explicit` and the `notify` options
+pub fn layer(
+    app: &tauri::AppHandle,
+    tool: &str,
+    explicit: BTreeMap<String, String>,
+    notify: Option<bool>,
+    notify_email: Option<&str>,
+) -> Result<Layered, String> {
+    let mut layered = Layered::default();
+    layered.apply(
+        ParamSource::App,
+        BTreeMap::from([(STATUS_EMAIL_FIELD.to_string(), "true".to_string())]),
+    );
+    layered.apply(ParamSource::Lab, lab_fields(app, tool));
+
+    let explicit_email = notify.is_some() || notify_email.is_some_and(|e| !e.trim().is_empty());
+    if !explicit_email {
+        // Only a stored address is a choice of the user's
+        let fields = email::submission_fields(app, None, None)?;
+        if fields.contains_key(EMAIL_ADDRESS_FIELD) {
+            layered.apply(ParamSource::User, fields);
+        }
+    }
+
+    layered.apply(ParamSource::Explicit, explicit);
+    if explicit_email {
+        layered.apply(
+            ParamSource::Explicit,
+            email::submission_fields(app, notify, notify_email)?,
+        );
+    }
+
+    // An address is meaningless with the email turned off
+    if layered.params.get(STATUS_EMAIL_FIELD).map(String::as_str) == Some("false") {
+        layered.remove(EMAIL_ADDRESS_FIELD);
+    }
+    Ok(layered)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveDefaults {
+    pub tool: String,
+    #[serde(flatten)]
+    pub layered: Layered,
+    // The lab file in use, if one is configured
+    pub lab_file: Option<String>,
+    // Why the lab file was ignored
+    pub lab_error: Option<String>,
+}
+
+/// The lab file in use, or why it's ignored; None when none is configured
+pub fn file_status(app: &tauri::AppHandle) -> Option<Result<String, String>> {
+    let (path, result) = current(app)?;
+    Some(result.map(|_| path.display().to_string()))
+}
+
+/// What a submission of `tool` would get without explicit arguments, for
+/// pre-filling the submit form
+#[tauri::command]
+pub async fn get_effective_defaults(
+    tool: String,
+    app: tauri::AppHandle,
+) -> Result<EffectiveDefaults, String> {
+    let layered = layer(&app, &tool, BTreeMap::new(), None, None)?;
+    let (lab_file, lab_error) = match current(&app) {
+        Some((path, result)) => (Some(path.display().to_string()), result.err()),
+        None => (None, None),
+    };
+    Ok(EffectiveDefaults {
+        tool,
+        layered,
+        lab_file,
+        lab_error,
+    })
+}
+
+#[tauri::command]
+pub async fn get_lab_defaults_path(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    Ok(configured_path(&app).map(|p| p.display().to_string()))
+}
+
+/// Set the lab defaults file; an empty path turns lab defaults off. The
+/// NSG_GUI_LAB_DEFAULTS environment variable still wins when set.
+#[tauri::command]
+pub async fn set_lab_defaults_path(app: tauri::AppHandle, path: String) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeSubmissionSettings)?;
+    let path = path.trim();
+    if !path.is_empty() {
+        crate::path_access::authorize_str(&app, path)?;
+    }
+    preferences::of(&app).set(PATH_KEY, json!(path));
+    Ok(())
+}
@@ -0,0 +1,754 @@
+// Incremental job list updates.
+//
+// After each full poll the job list is compared with the previous one and
+// only the delta goes out, as `jobs-diff`, so the frontend can patch its
+// table in place. The first list of a session, or one the frontend asks for
+// after noticing a gap in the sequence numbers, goes out whole as
+// `jobs-refreshed`. Snapshots and updates hold real job IDs; they are
+// anonymized only on the way out, like every other payload.
+//
+// The snapshot keeps the RESIDENT_JOBS most recently submitted jobs as
+// compact records, with tool names, stages and the like interned, and only
+// a fingerprint of each older one. An older job that changed is compared
+// with its copy in the on-disk job cache, and `cached` reads older jobs from
+// there as well, so what the frontend sees is the same as with everything in
+// memory. A test holds an account of BUDGET_JOBS jobs to MEMORY_BUDGET_BYTES.
+
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, Anonymize};
+use crate::xml::JobExtras;
+use crate::{expiry, projects, AppState, JobSummary};
+use nsg_cli::models::JobSummary as NsgJobSummary;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use tauri::{Manager, State};
+
+// Jobs kept in full in the snapshot; older ones only as a fingerprint
+const RESIDENT_JOBS: usize = 1000;
+// What the snapshot and interner may take, by memory_stats' estimate, for
+// an account of BUDGET_JOBS jobs
+const MEMORY_BUDGET_BYTES: usize = 2 * 1024 * 1024;
+const BUDGET_JOBS: usize = 10_000;
+
+/// A listed job as the snapshot keeps it. Never stale: snapshots come from
+/// fresh listings.
+struct CompactJob {
+    url: Box<str>,
+    tool: Option<Arc<str>>,
+    job_stage: Option<Arc<str>>,
+    failed: bool,
+    date_submitted: Option<Box<str>>,
+    date_completed: Option<Box<str>>,
+    compute_resource: Option<Arc<str>>,
+    tool_version: Option<Arc<str>>,
+    project_id: Option<Arc<str>>,
+    results_expire_at: Option<Box<str>>,
+}
+
+impl CompactJob {
+    fn new(job: &JobSummary, interner: &mut Interner) -> Self {
+        Self {
+            url: job.url.as_str().into(),
+            tool: interner.get(&job.tool),
+            job_stage: interner.get(&job.job_stage),
+            failed: job.failed,
+            date_submitted: job.date_submitted.as_deref().map(Into::into),
+            date_completed: job.date_completed.as_deref().map(Into::into),
+            compute_resource: interner.get(&job.compute_resource),
+            tool_version: interner.get(&job.tool_version),
+            project_id: interner.get(&job.project_id),
+            results_expire_at: job.results_expire_at.as_deref().map(Into::into),
+        }
+    }
+
+    fn expand(&self, job_id: &str) -> JobSummary {
+        let string = |s: &Option<Arc<str>>| s.as_deref().map(str::to_string);
+        let boxed = |s: &Option<Box<str>>| s.as_deref().map(str::to_string);
+        JobSummary {
+            job_id: job_id.to_string(),
+            url: self.url.to_string(),
+            tool: string(&self.tool),
+            job_stage: string(&self.job_stage),
+            failed: self.failed,
+            date_submitted: boxed(&self.date_submitted),
+            date_completed: boxed(&self.date_completed),
+            compute_resource: string(&self.compute_resource),
+            tool_version: string(&self.tool_version),
+            project_id: string(&self.project_id),
+            results_expire_at: boxed(&self.results_expire_at),
+            stale: false,
+        }
+    }
+
+    /// Heap bytes owned by this record alone; interned strings are counted
+    /// by the interner
+    fn heap_bytes(&self) -> usize {
+        let boxed = |s: &Option<Box<str>>| s.as_deref().map_or(0, str::len);
+        self.url.len()
+            + boxed(&self.date_submitted)
+            + boxed(&self.date_completed)
+            + boxed(&self.results_expire_at)
+    }
+}
+
+/// Shared copies of the strings many jobs repeat
+#[derive(Default)]
+struct Interner(BTreeSet<Arc<str>>);
+
+impl Interner {
+    fn get(&mut self, value: &Option<String>) -> Option<Arc<str>> {
+        let value = value.as_deref()?;
+        if let Some(shared) = self.0.get(value) {
+            return Some(shared.clone());
+        }
+        let shared: Arc<str> = Arc::from(value);
+        self.0.insert(shared.clone());
+        Some(shared)
+    }
+
+    /// Forget strings no snapshot uses any more
+    fn sweep(&mut self) {
+        self.0.retain(|s| Arc::strong_count(s) > 1);
+    }
+}
+
+fn fingerprint(job: &JobSummary) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(job)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+struct Snapshot {
+    // Session generation the snapshot was taken in
+    generation: u64,
+    // Account whose on-disk job cache holds the older jobs
+    username: String,
+    seq: u64,
+    // real job ID -> the most recently submitted jobs
+    resident: BTreeMap<String, CompactJob>,
+    // real job ID -> fingerprint of every older job
+    older: BTreeMap<String, u64>,
+}
+
+impl Snapshot {
+    fn new(
+        generation: u64,
+        username: &str,
+        seq: u64,
+        jobs: &BTreeMap<String, JobSummary>,
+        interner: &mut Interner,
+    ) -> Self {
+        let mut newest: Vec<&JobSummary> = jobs.values().collect();
+        newest.sort_by(|a, b| {
+            b.date_submitted
+                .cmp(&a.date_submitted)
+                .then_with(|| a.job_id.cmp(&b.job_id))
+        });
+        let mut snapshot = Self {
+            generation,
+            username: username.to_string(),
+            seq,
+            resident: BTreeMap::new(),
+            older: BTreeMap::new(),
+        };
+        for (rank, job) in newest.into_iter().enumerate() {
+            if rank < RESIDENT_JOBS {
+                snapshot
+                    .resident
+                    .insert(job.job_id.clone(), CompactJob::new(job, interner));
+            } else {
+                snapshot.older.insert(job.job_id.clone(), fingerprint(job));
+            }
+        }
+        snapshot
+    }
+
+    fn contains(&self, job_id: &str) -> bool {
+        self.resident.contains_key(job_id) || self.older.contains_key(job_id)
+    }
+
+    fn job_ids(&self) -> impl Iterator<Item = &String> {
+        self.resident.keys().chain(self.older.keys())
+    }
+
+    /// Older jobs as last saved to the on-disk job cache
+    fn saved_older(&self, app: &tauri::AppHandle) -> BTreeMap<String, JobSummary> {
+        if self.older.is_empty() {
+            return BTreeMap::new();
+        }
+        crate::job_cache::saved(app, &self.username)
+            .into_iter()
+            .filter(|j| self.older.contains_key(&j.job_id))
+            .map(|j| (j.job_id.clone(), j))
+            .collect()
+    }
+}
+
+static SNAPSHOT: Mutex<Option<Snapshot>> = Mutex::new(None);
+static INTERNER: Mutex<Interner> = Mutex::new(Interner(BTreeSet::new()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedJob {
+    pub job: JobSummary,
+    // Names of the JobSummary fields that differ from the previous snapshot
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobsDiff {
+    // One more than the seq of the previous jobs-diff or jobs-refreshed
+    pub seq: u64,
+    pub added: Vec<JobSummary>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ChangedJob>,
+}
+
+impl JobsDiff {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobsUpdate {
+    Full { seq: u64, jobs: Vec<JobSummary> },
+    Diff(JobsDiff),
+    // Nothing changed since the last update; no event was emitted
+    Unchanged { seq: u64 },
+}
+
+impl Anonymize for JobSummary {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            url: anonymize_url(&self.url),
+            ..self
+        }
+    }
+}
+
+impl Anonymize for ChangedJob {
+    fn anonymize(self) -> Self {
+        Self {
+            job: self.job.anonymize(),
+            ..self
+        }
+    }
+}
+
+impl Anonymize for JobsDiff {
+    fn anonymize(self) -> Self {
+        Self {
+            seq: self.seq,
+            added: self.added.anonymize(),
+            removed: self.removed.iter().map(|id| anonymize_job_id(id)).collect(),
+            changed: self.changed.anonymize(),
+        }
+    }
+}
+
+impl Anonymize for JobsUpdate {
+    fn anonymize(self) -> Self {
+        match self {
+            JobsUpdate::Full { seq, jobs } => JobsUpdate::Full {
+                seq,
+                jobs: jobs.anonymize(),
+            },
+            JobsUpdate::Diff(diff) => JobsUpdate::Diff(diff.anonymize()),
+            JobsUpdate::Unchanged { seq } => JobsUpdate::Unchanged { seq },
+        }
+    }
+}
+
+/// Combine the server listing with local project assignments. IDs stay real.
+pub fn summarize(
+    app: &tauri::AppHandle,
+    jobs: Vec<(NsgJobSummary, Option<JobExtras>)>,
+) -> Vec<JobSummary> {
+    // Project assignments are a local extra; don't fail the listing over them
+    let projects = projects::load(app).unwrap_or_default();
+    let expiry = expiry::load(app);
+
+    jobs.into_iter()
+        .map(|(j, extras)| {
+            let extras = extras.unwrap_or_default();
+            let results_expire_at = expiry::expires_at(
+                extras.results_expire_at.as_deref(),
+                j.date_completed.as_deref(),
+                expiry,
+            );
+            JobSummary {
+                project_id: projects.project_of(&j.job_id),
+                job_id: j.job_id,
+                url: j.url,
+                tool: j.tool,
+                job_stage: j.job_stage,
+                failed: j.failed,
+                date_submitted: j.date_submitted,
+                date_completed: j.date_completed,
+                compute_resource: extras.compute_resource,
+                tool_version: extras.tool_version,
+                results_expire_at,
+                stale: false,
+            }
+        })
+        .collect()
+}
+
+fn changed_fields(old: &JobSummary, new: &JobSummary) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    new.iter()
+        .filter(|(field, value)| old.get(*field) != Some(value))
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+/// What changed from `old` to `new`. `saved_older` reads the older jobs'
+/// saved copies, and is only called when one of them changed.
+fn diff(
+    old: &Snapshot,
+    new: &BTreeMap<String, JobSummary>,
+    seq: u64,
+    saved_older: impl FnOnce(&Snapshot) -> BTreeMap<String, JobSummary>,
+) -> JobsDiff {
+    let added = new
+        .iter()
+        .filter(|(id, _)| !old.contains(id))
+        .map(|(_, job)| job.clone())
+        .collect();
+    let removed = old
+        .job_ids()
+        .filter(|id| !new.contains_key(*id))
+        .cloned()
+        .collect();
+
+    let mut changed = Vec::new();
+    let mut changed_older = Vec::new();
+    for (id, job) in new {
+        if let Some(compact) = old.resident.get(id) {
+            let fields = changed_fields(&compact.expand(id), job);
+            if !fields.is_empty() {
+                changed.push(ChangedJob {
+                    job: job.clone(),
+                    fields,
+                });
+            }
+        } else if old.older.get(id).is_some_and(|f| *f != fingerprint(job)) {
+            changed_older.push(job);
+        }
+    }
+    if !changed_older.is_empty() {
+        let saved = saved_older(old);
+        for job in changed_older {
+            // Without a saved copy every field counts as changed
+            let fields = match saved.get(&job.job_id) {
+                Some(previous) => changed_fields(previous, job),
+                None => changed_fields(&JobSummary::default(), job),
+            };
+            changed.push(ChangedJob {
+                job: job.clone(),
+                fields,
+            });
+        }
+        changed.sort_by(|a, b| a.job.job_id.cmp(&b.job.job_id));
+    }
+
+    JobsDiff {
+        seq,
+        added,
+        removed,
+        changed,
+    }
+}
+
+/// Whether this session has had a listing yet
+pub fn has_snapshot(generation: u64) -> bool {
+    SNAPSHOT
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|s| s.generation == generation)
+}
+
+/// Sequence number of the last listing in this session, the one its
+/// `jobs-refreshed` or latest `jobs-diff` carried
+pub fn seq(generation: u64) -> Option<u64> {
+    SNAPSHOT
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|s| s.generation == generation)
+        .map(|s| s.seq)
+}
+
+/// The jobs of the last listing in this session, with real IDs
+pub fn cached(app: &tauri::AppHandle, generation: u64) -> Option<Vec<JobSummary>> {
+    let snapshot = SNAPSHOT.lock().unwrap();
+    let snapshot = snapshot.as_ref().filter(|s| s.generation == generation)?;
+    let mut older = snapshot.saved_older(app);
+    let mut jobs: BTreeMap<String, JobSummary> = snapshot
+        .resident
+        .iter()
+        .map(|(id, job)| (id.clone(), job.expand(id)))
+        .collect();
+    for id in snapshot.older.keys() {
+        if let Some(job) = older.remove(id) {
+            jobs.insert(id.clone(), job);
+        }
+    }
+    Some(jobs.into_values().collect())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSnapshotStats {
+    pub resident_jobs: usize,
+    pub resident_limit: usize,
+    // Kept as a fingerprint, read from the disk cache when needed
+    pub older_jobs: usize,
+    pub interned_strings: usize,
+    // Rough: struct sizes plus owned string bytes
+    pub approx_bytes: usize,
+    // What approx_bytes should stay under for an account of budget_jobs
+    pub budget_bytes: usize,
+    pub budget_jobs: usize,
+}
+
+pub fn memory_stats() -> JobSnapshotStats {
+    let snapshot = SNAPSHOT.lock().unwrap();
+    let interner = INTERNER.lock().unwrap();
+    let interned_bytes: usize = interner
+        .0
+        .iter()
+        .map(|s| s.len() + 2 * std::mem::size_of::<usize>() + std::mem::size_of::<Arc<str>>())
+        .sum();
+    let (resident_jobs, older_jobs, snapshot_bytes) = match snapshot.as_ref() {
+        Some(s) => {
+            let resident: usize = s
+                .resident
+                .iter()
+                .map(|(id, job)| {
+                    id.len() + std::mem::size_of::<(String, CompactJob)>() + job.heap_bytes()
+                })
+                .sum();
+            let older: usize = s
+                .older
+                .keys()
+                .map(|id| id.len() + std::mem::size_of::<(String, u64)>())
+                .sum();
+            (s.resident.len(), s.older.len(), resident + older)
+        }
+        None => (0, 0, 0),
+    };
+    JobSnapshotStats {
+        resident_jobs,
+        resident_limit: RESIDENT_JOBS,
+        older_jobs,
+        interned_strings: interner.0.len(),
+        approx_bytes: snapshot_bytes + interned_bytes,
+        budget_bytes: MEMORY_BUDGET_BYTES,
+        budget_jobs: BUDGET_JOBS,
+    }
+}
+
+/// Record a fresh listing and emit what changed: `jobs-refreshed` with the
+/// whole list when there is no snapshot for this session yet (or `full` is
+/// set), otherwise `jobs-diff` if anything moved. The update returned keeps
+/// the real IDs.
+pub fn publish(
+    app: &tauri::AppHandle,
+    generation: u64,
+    jobs: Vec<JobSummary>,
+    full: bool,
+) -> JobsUpdate {
+    let jobs: BTreeMap<String, JobSummary> =
+        jobs.into_iter().map(|j| (j.job_id.clone(), j)).collect();
+    let session = app
+        .state::<AppState>()
+        .session
+        .snapshot()
+        .filter(|s| s.generation == generation);
+    let username = session
+        .as_ref()
+        .map(|s| s.credentials.username.clone())
+        .unwrap_or_default();
+
+    let mut snapshot = SNAPSHOT.lock().unwrap();
+    let previous = snapshot
+        .as_ref()
+        .filter(|s| s.generation == generation && !full);
+    let seq = snapshot.as_ref().map(|s| s.seq + 1).unwrap_or(1);
+
+    let update = match previous {
+        Some(previous) => {
+            let diff = diff(previous, &jobs, seq, |old| old.saved_older(app));
+            if diff.is_empty() {
+                return JobsUpdate::Unchanged { seq: previous.seq };
+            }
+            JobsUpdate::Diff(diff)
+        }
+        None => JobsUpdate::Full {
+            seq,
+            jobs: jobs.values().cloned().collect(),
+        },
+    };
+
+    {
+        let mut interner = INTERNER.lock().unwrap();
+        *snapshot = Some(Snapshot::new(
+            generation,
+            &username,
+            seq,
+            &jobs,
+            &mut interner,
+        ));
+        interner.sweep();
+    }
+    drop(snapshot);
+
+    // Someone else's metadata is on screen: leave it alone
+    let session = session.filter(|_| !crate::accounts::is_mismatched());
+    if let Some(session) = session {
+        let jobs: Vec<JobSummary> = jobs.into_values().collect();
+        let username = &session.credentials.username;
+        let _ = crate::job_cache::store(app, username, &jobs);
+        let listed = jobs.iter().map(|j| j.job_id.as_str()).collect();
+        if let Err(e) = crate::local_jobs::reconcile(app, username, &listed) {
+            crate::background_errors::report(
+                app,
+                crate::local_jobs::RECORD_SOURCE,
+                crate::background_errors::ErrorKind::Other,
+                format!("Failed to reconcile local jobs: {}", e),
+            );
+        }
+    }
+
+    match &update {
+        JobsUpdate::Full { .. } => {
+            anonymize::emit(app, crate::events::JOBS_REFRESHED, update.clone())
+        }
+        JobsUpdate::Diff(diff) => anonymize::emit(app, crate::events::JOBS_DIFF, diff.clone()),
+        JobsUpdate::Unchanged { .. } => {}
+    }
+    update
+}
+
+/// Poll the job list now and emit the delta against the last snapshot.
+/// `full: true` sends the whole list instead, for a frontend that missed a
+/// sequence number.
+#[tauri::command]
+pub async fn refresh_jobs_diff(
+    full: Option<bool>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<JobsUpdate, String> {
+    let (session, jobs) = state
+        .session
+        .fetch_current(|session| async {
+            let api = state.client.api(session.credentials).await?;
+            tokio::task::spawn_blocking(move || api.list_jobs())
+                .await
+                .map_err(|e| format!("Task error: {}", e))?
+                .map_err(|e| format!("Failed to list jobs: {}", e))
+        })
+        .await?;
+
+    let summaries = summarize(&app, jobs);
+    let update = publish(&app, session.generation, summaries, full.unwrap_or(false));
+    Ok(anonymize::outgoing(&app, update))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryStats {
+    pub job_snapshot: JobSnapshotStats,
+    // query_jobs' index of the same jobs
+    pub job_index: crate::job_query::JobIndexStats,
+    // Monitor refreshes that found the listing unchanged and skipped it
+    pub skipped_unchanged: u64,
+}
+
+/// Rough size of what the app holds in memory about jobs, for diagnostics
+#[tauri::command]
+pub async fn get_memory_stats() -> Result<MemoryStats, String> {
+    Ok(MemoryStats {
+        job_snapshot: memory_stats(),
+        job_index: crate::job_query::memory_stats(),
+        skipped_unchanged: crate::monitor::skipped_unchanged(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str, day: u32, stage: &str) -> JobSummary {
+        JobSummary {
+            job_id: id.to_string(),
+            url: format!("https://example.org/job/alice/{}", id),
+            tool: Some("NEURON".to_string()),
+            job_stage: Some(stage.to_string()),
+            date_submitted: Some(format!("2026-01-{:02}T00:00:00+00:00", day)),
+            ..Default::default()
+        }
+    }
+
+    fn by_id(jobs: impl IntoIterator<Item = JobSummary>) -> BTreeMap<String, JobSummary> {
+        jobs.into_iter().map(|j| (j.job_id.clone(), j)).collect()
+    }
+
+    #[test]
+    fn compact_records_expand_to_the_same_job() {
+        let original = JobSummary {
+            compute_resource: Some("Expanse".to_string()),
+            project_id: Some("p1".to_string()),
+            results_expire_at: Some("2026-02-01T00:00:00+00:00".to_string()),
+            failed: true,
+            ..job("NGBW-JOB-1", 1, "COMPLETED")
+        };
+        let compact = CompactJob::new(&original, &mut Interner::default());
+        assert_eq!(compact.expand("NGBW-JOB-1"), original);
+    }
+
+    #[test]
+    fn repeated_strings_are_shared_and_swept_when_unused() {
+        let mut interner = Interner::default();
+        let a = CompactJob::new(&job("a", 1, "QUEUE"), &mut interner);
+        let b = CompactJob::new(&job("b", 2, "QUEUE"), &mut interner);
+        assert!(Arc::ptr_eq(
+            a.tool.as_ref().unwrap(),
+            b.tool.as_ref().unwrap()
+        ));
+        assert_eq!(interner.0.len(), 2);
+
+        drop((a, b));
+        interner.sweep();
+        assert!(interner.0.is_empty());
+    }
+
+    #[test]
+    fn only_the_newest_jobs_are_resident() {
+        let jobs = by_id((0..RESIDENT_JOBS + 2).map(|i| {
+            let mut job = job(&format!("NGBW-JOB-{:05}", i), 1, "COMPLETED");
+            job.date_submitted = Some(format!("{:05}", i));
+            job
+        }));
+        let snapshot = Snapshot::new(1, "alice", 0, &jobs, &mut Interner::default());
+        assert_eq!(snapshot.resident.len(), RESIDENT_JOBS);
+        assert_eq!(snapshot.older.len(), 2);
+        assert!(snapshot.older.contains_key("NGBW-JOB-00000"));
+        assert!(snapshot.older.contains_key("NGBW-JOB-00001"));
+        assert!(snapshot.contains("NGBW-JOB-00000"));
+        assert!(snapshot.contains("NGBW-JOB-01001"));
+        assert_eq!(snapshot.job_ids().count(), RESIDENT_JOBS + 2);
+    }
+
+    #[test]
+    fn a_diff_lists_added_removed_and_changed_fields() {
+        let before = by_id([job("a", 1, "QUEUE"), job("b", 2, "QUEUE")]);
+        let snapshot = Snapshot::new(1, "alice", 0, &before, &mut Interner::default());
+        let mut after = by_id([job("a", 1, "COMPLETED"), job("c", 3, "QUEUE")]);
+        after.get_mut("a").unwrap().failed = true;
+
+        let changes = diff(&snapshot, &after, 1, |_| panic!("no older jobs"));
+        assert_eq!(changes.seq, 1);
+        assert_eq!(changes.added.len(), 1);
+        assert_eq!(changes.added[0].job_id, "c");
+        assert_eq!(changes.removed, ["b"]);
+        assert_eq!(changes.changed.len(), 1);
+        assert_eq!(changes.changed[0].job.job_id, "a");
+        assert_eq!(changes.changed[0].fields, ["failed", "job_stage"]);
+    }
+
+    #[test]
+    fn a_changed_older_job_is_compared_with_its_saved_copy() {
+        let mut before: Vec<JobSummary> = (0..RESIDENT_JOBS)
+            .map(|i| job(&format!("new-{:04}", i), 20, "QUEUE"))
+            .collect();
+        before.push(job("old", 1, "QUEUE"));
+        let before = by_id(before);
+        let snapshot = Snapshot::new(1, "alice", 0, &before, &mut Interner::default());
+        assert!(snapshot.older.contains_key("old"));
+
+        let mut after = before.clone();
+        after.get_mut("old").unwrap().job_stage = Some("COMPLETED".to_string());
+        let saved = before.clone();
+        let changes = diff(&snapshot, &after, 2, |old| {
+            old.older
+                .keys()
+                .map(|id| (id.clone(), saved[id].clone()))
+                .collect()
+        });
+        assert_eq!(changes.changed.len(), 1);
+        assert_eq!(changes.changed[0].fields, ["job_stage"]);
+
+        // Without a saved copy every field present counts as changed
+        let changes = diff(&snapshot, &after, 3, |_| BTreeMap::new());
+        assert!(changes.changed[0].fields.len() > 1);
+    }
+
+    #[test]
+    fn a_large_account_stays_within_the_memory_budget() {
+        let tools = [
+            "NEURON_EXPANSE",
+            "PY_EXPANSE",
+            "MATLAB_EXPANSE",
+            "EEGLAB_EXPANSE",
+        ];
+        let jobs = by_id((0..BUDGET_JOBS).map(|i| {
+            let id = format!("NGBW-JOB-{}-{:032X}", tools[i % tools.len()], i);
+            JobSummary {
+                url: format!("{}/job/alice/{}", crate::api::NSG_BASE_URL, id),
+                tool: Some(tools[i % tools.len()].to_string()),
+                job_stage: Some("COMPLETED".to_string()),
+                date_submitted: Some(format!(
+                    "2025-{:02}-01T00:00:{:02}+00:00",
+                    i % 12 + 1,
+                    i % 60
+                )),
+                date_completed: Some(format!(
+                    "2025-{:02}-02T00:00:{:02}+00:00",
+                    i % 12 + 1,
+                    i % 60
+                )),
+                compute_resource: Some("Expanse".to_string()),
+                tool_version: Some("8.2".to_string()),
+                results_expire_at: Some("2026-01-01T00:00:00+00:00".to_string()),
+                job_id: id,
+                ..Default::default()
+            }
+        }));
+
+        // The statics publish fills; no other test uses them
+        let previous = {
+            let mut interner = INTERNER.lock().unwrap();
+            let snapshot = Snapshot::new(1, "alice", 1, &jobs, &mut interner);
+            SNAPSHOT.lock().unwrap().replace(snapshot)
+        };
+        let stats = memory_stats();
+        *SNAPSHOT.lock().unwrap() = previous;
+        INTERNER.lock().unwrap().sweep();
+
+        assert_eq!(stats.resident_jobs + stats.older_jobs, BUDGET_JOBS);
+        assert!(
+            stats.approx_bytes < stats.budget_bytes,
+            "{} bytes for {} jobs, over the {} byte budget",
+            stats.approx_bytes,
+            BUDGET_JOBS,
+            stats.budget_bytes
+        );
+    }
+
+    #[test]
+    fn an_unchanged_listing_is_an_empty_diff() {
+        let jobs = by_id([job("a", 1, "QUEUE")]);
+        let snapshot = Snapshot::new(1, "alice", 0, &jobs, &mut Interner::default());
+        assert!(diff(&snapshot, &jobs, 1, |_| BTreeMap::new()).is_empty());
+    }
+}
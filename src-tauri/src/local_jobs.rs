@@ -0,0 +1,535 @@
+// Jobs known to this machine.
+//
+// local_jobs.json holds, per real job ID, where the job came from, its tags
+// and note, and the stages the app has seen it in. Jobs submitted from the
+// app are added when they're submitted; jobs submitted elsewhere (nsg_cli,
+// the REST API) show up in the listing without an entry until adopted.
+// Adopting is idempotent: tags are merged, a note replaces the old one only
+// when given, and a stage is recorded only when it differs from the last.
+// Cleared tags and notes stay in the entry for a while, for undo.
+//
+// A job deleted on the server (through the portal, or by NSG's retention
+// policy) drops out of the listing. Its entry is kept and marked
+// `server_deleted`, `jobs-removed-on-server` names it once, and it stays
+// until purge_server_deleted_metadata removes it deliberately; its project
+// assignment and download history survive too.
+
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, Anonymize};
+use crate::i18n::Msg;
+use crate::trash::{self, LocalDelete, TrashKind, TrashMark, Trashed};
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use tauri::State;
+
+pub const LOCAL_JOBS_FILE: &str = "local_jobs.json";
+// Name in background errors of keeping submitted jobs' local records
+pub const RECORD_SOURCE: &str = "job_records";
+
+// An entry this new may be for a job the listing doesn't show yet
+const LISTING_GRACE_MINUTES: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobOrigin {
+    Submitted,
+    Adopted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageSighting {
+    pub stage: String,
+    pub seen_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalJob {
+    pub job_id: String,
+    pub job_url: String,
+    pub tool: Option<String>,
+    pub origin: JobOrigin,
+    pub added_at: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    // When tags and note last changed, for merging metadata from another
+    // device; None in entries from before these were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags_modified_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note_modified_at: Option<String>,
+    // Form fields the app submitted the job with, other than metadata.*;
+    // NSG doesn't return them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    // Oldest first
+    #[serde(default)]
+    pub stages: Vec<StageSighting>,
+    // The job was missing from the server listing at server_deleted_at
+    #[serde(default)]
+    pub server_deleted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_deleted_at: Option<String>,
+    // What clear_job_annotations removed, until its undo window passes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleared_annotations: Option<Trashed<Annotations>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotations {
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+}
+
+impl Anonymize for LocalJob {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            job_url: anonymize_url(&self.job_url),
+            ..self
+        }
+    }
+}
+
+/// Payload of `jobs-removed-on-server`
+#[derive(Debug, Clone, Serialize)]
+pub struct JobsRemovedOnServer {
+    pub job_ids: Vec<String>,
+    pub detected_at: String,
+}
+
+impl Anonymize for JobsRemovedOnServer {
+    fn anonymize(self) -> Self {
+        Self {
+            job_ids: self.job_ids.iter().map(|id| anonymize_job_id(id)).collect(),
+            ..self
+        }
+    }
+}
+
+impl LocalJob {
+    fn new(job_id: &str, job_url: &str, tool: Option<String>, origin: JobOrigin) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            job_url: job_url.to_string(),
+            tool,
+            origin,
+            added_at: chrono::Utc::now().to_rfc3339(),
+            tags: Vec::new(),
+            note: None,
+            tags_modified_at: None,
+            note_modified_at: None,
+            params: BTreeMap::new(),
+            stages: Vec::new(),
+            server_deleted: false,
+            server_deleted_at: None,
+            cleared_annotations: None,
+        }
+    }
+
+    fn see_stage(&mut self, stage: &str) {
+        if self.stages.last().is_some_and(|s| s.stage == stage) {
+            return;
+        }
+        self.stages.push(StageSighting {
+            stage: stage.to_string(),
+            seen_at: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+}
+
+pub fn local_jobs_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = crate::accounts::user_root(app)?;
+    Ok(dir.join(LOCAL_JOBS_FILE))
+}
+
+pub fn load(app: &tauri::AppHandle) -> Result<BTreeMap<String, LocalJob>, String> {
+    crate::state_file::load(&local_jobs_path(app)?)
+}
+
+/// Delete local_jobs.json, returning whether there was one
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = local_jobs_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    crate::paths::remove_file_if_exists(&path)
+}
+
+fn modify<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut BTreeMap<String, LocalJob>) -> T,
+) -> Result<T, String> {
+    crate::state_file::modify(&local_jobs_path(app)?, "local jobs", |store| Ok(f(store)))
+}
+
+/// Record a job the app just submitted
+pub fn record_submission(
+    app: &tauri::AppHandle,
+    job_id: &str,
+    job_url: &str,
+    tool: &str,
+    params: &BTreeMap<String, String>,
+    stage: &str,
+) -> Result<(), String> {
+    modify(app, |store| {
+        store
+            .entry(job_id.to_string())
+            .or_insert_with(|| LocalJob {
+                params: params
+                    .iter()
+                    .filter(|(name, _)| !name.starts_with("metadata."))
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect(),
+                ..LocalJob::new(
+                    job_id,
+                    job_url,
+                    Some(tool.to_string()),
+                    JobOrigin::Submitted,
+                )
+            })
+            .see_stage(stage);
+    })
+}
+
+/// Record a stage the monitor saw, for jobs that have an entry
+pub fn record_stage(app: &tauri::AppHandle, job_id: &str, stage: &str) -> Result<(), String> {
+    let known = load(app)?.contains_key(job_id);
+    if !known {
+        return Ok(());
+    }
+    modify(app, |store| {
+        if let Some(job) = store.get_mut(job_id) {
+            job.see_stage(stage);
+        }
+    })
+}
+
+/// Whether the entry is for a job of `username`, going by its URL
+fn belongs_to(job: &LocalJob, username: &str) -> bool {
+    job.job_url.contains(&format!("/job/{}/", username))
+}
+
+/// Compare the entries of `username`'s jobs with a full server listing:
+/// mark the ones missing from it as deleted on the server and unmark the
+/// ones listed again. Emits `jobs-removed-on-server` for newly missing
+/// jobs.
+pub fn reconcile(
+    app: &tauri::AppHandle,
+    username: &str,
+    listed: &BTreeSet<&str>,
+) -> Result<(), String> {
+    let now = chrono::Utc::now();
+    let grace_start = now - chrono::Duration::minutes(LISTING_GRACE_MINUTES);
+    let missing = |job: &LocalJob| {
+        belongs_to(job, username)
+            && !listed.contains(job.job_id.as_str())
+            && crate::monitor::parse_date(Some(&job.added_at)).is_some_and(|at| at < grace_start)
+    };
+    let needs_change = |job: &LocalJob| job.server_deleted != missing(job);
+    if !load(app)?.values().any(needs_change) {
+        return Ok(());
+    }
+
+    let detected_at = now.to_rfc3339();
+    let removed = modify(app, |store| {
+        let mut removed = Vec::new();
+        for job in store.values_mut().filter(|job| needs_change(job)) {
+            job.server_deleted = !job.server_deleted;
+            job.server_deleted_at = job.server_deleted.then(|| detected_at.clone());
+            if job.server_deleted {
+                removed.push(job.job_id.clone());
+            }
+        }
+        removed
+    })?;
+
+    if !removed.is_empty() {
+        anonymize::emit(
+            app,
+            crate::events::JOBS_REMOVED_ON_SERVER,
+            JobsRemovedOnServer {
+                job_ids: removed,
+                detected_at,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Put back cleared tags and notes, keeping any added since. Returns false
+/// when `token` no longer undoes anything.
+pub fn restore_annotations(app: &tauri::AppHandle, token: &str) -> Result<bool, String> {
+    modify(app, |store| {
+        let mut restored = false;
+        for job in store.values_mut() {
+            if !job
+                .cleared_annotations
+                .as_ref()
+                .is_some_and(|t| t.mark.undoes(token))
+            {
+                continue;
+            }
+            let Some(cleared) = job.cleared_annotations.take().map(|t| t.item) else {
+                continue;
+            };
+            for tag in cleared.tags {
+                if !job.tags.contains(&tag) {
+                    job.tags.push(tag);
+                }
+            }
+            job.note = job.note.take().or(cleared.note);
+            let now = chrono::Utc::now().to_rfc3339();
+            job.tags_modified_at = Some(now.clone());
+            job.note_modified_at = Some(now);
+            restored = true;
+        }
+        restored
+    })
+}
+
+/// Drop cleared annotations whose undo window has passed
+pub fn purge_trash(app: &tauri::AppHandle) -> Result<(), String> {
+    let expired = |job: &LocalJob| {
+        job.cleared_annotations
+            .as_ref()
+            .is_some_and(|t| t.mark.is_expired())
+    };
+    if !load(app)?.values().any(expired) {
+        return Ok(());
+    }
+    modify(app, |store| {
+        for job in store.values_mut() {
+            if expired(job) {
+                job.cleared_annotations = None;
+            }
+        }
+    })
+}
+
+/// What's known about a job on the server, for adopting it
+struct ServerJob {
+    job_id: String,
+    job_url: String,
+    tool: Option<String>,
+    stage: Option<String>,
+    terminal: bool,
+}
+
+/// Add or update the entries of jobs, returning them with real IDs
+fn adopt(
+    app: &tauri::AppHandle,
+    jobs: &[ServerJob],
+    tags: &[String],
+    note: Option<&str>,
+) -> Result<Vec<LocalJob>, String> {
+    modify(app, |store| {
+        jobs.iter()
+            .map(|job| adopt_one(store, job, tags, note))
+            .collect()
+    })
+}
+
+fn adopt_one(
+    store: &mut BTreeMap<String, LocalJob>,
+    job: &ServerJob,
+    tags: &[String],
+    note: Option<&str>,
+) -> LocalJob {
+    let entry = store.entry(job.job_id.clone()).or_insert_with(|| {
+        LocalJob::new(
+            &job.job_id,
+            &job.job_url,
+            job.tool.clone(),
+            JobOrigin::Adopted,
+        )
+    });
+    let now = chrono::Utc::now().to_rfc3339();
+    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if !entry.tags.iter().any(|t| t == tag) {
+            entry.tags.push(tag.to_string());
+            entry.tags_modified_at = Some(now.clone());
+        }
+    }
+    if let Some(note) = note {
+        let note = Some(note.to_string()).filter(|n| !n.trim().is_empty());
+        if entry.note != note {
+            entry.note = note;
+            entry.note_modified_at = Some(now);
+        }
+    }
+    if let Some(stage) = &job.stage {
+        entry.see_stage(stage);
+    }
+    entry.clone()
+}
+
+fn watch(state: &AppState, generation: u64, job: &ServerJob) {
+    if !job.terminal && state.session.is_current(generation) {
+        state.monitor.nudge(&job.job_url, &job.job_id);
+    }
+}
+
+/// Take a job submitted outside the app into local metadata. Running again
+/// on the same job only adds what's new.
+#[tauri::command]
+pub async fn adopt_job(
+    job_url: String,
+    project_id: Option<String>,
+    tags: Option<Vec<String>>,
+    note: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<LocalJob>, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionAdoptJobs)?;
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let url = anonymize::real_url(&job_url);
+    let fetch_url = url.clone();
+    let record = tokio::task::spawn_blocking(move || api.job_record(&fetch_url))
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+        .map_err(|e| format!("Failed to get job status: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    let status = record.status;
+    let job = ServerJob {
+        terminal: status.failed || status.job_stage == "COMPLETED",
+        job_id: status.job_id,
+        job_url: url,
+        tool: status.tool_id,
+        stage: Some(status.job_stage),
+    };
+    if let Some(project_id) = &project_id {
+        crate::projects::assign(&app, &job.job_id, Some(project_id))?;
+    }
+    let local = adopt(
+        &app,
+        std::slice::from_ref(&job),
+        &tags.unwrap_or_default(),
+        note.as_deref(),
+    )?;
+    watch(&state, session.generation, &job);
+
+    Ok(anonymize::outgoing(&app, local.into_iter().next()))
+}
+
+/// Adopt every job on the server that has no local entry yet, returning
+/// how many were adopted
+#[tauri::command]
+pub async fn adopt_untracked_jobs(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionAdoptJobs)?;
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let jobs = tokio::task::spawn_blocking(move || api.list_jobs())
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+        .map_err(|e| format!("Failed to list jobs: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    let known = load(&app)?;
+    let untracked: Vec<ServerJob> = jobs
+        .into_iter()
+        .map(|(j, _)| ServerJob {
+            terminal: j.failed || j.job_stage.as_deref() == Some("COMPLETED"),
+            job_id: j.job_id,
+            job_url: j.url,
+            tool: j.tool,
+            stage: j.job_stage,
+        })
+        .filter(|j| !known.contains_key(&j.job_id))
+        .collect();
+
+    adopt(&app, &untracked, &[], None)?;
+    for job in &untracked {
+        watch(&state, session.generation, job);
+    }
+    Ok(untracked.len())
+}
+
+/// Remove a job's tags and note. Can be undone with the returned token for
+/// a while.
+#[tauri::command]
+pub async fn clear_job_annotations(
+    job_id: String,
+    app: tauri::AppHandle,
+) -> Result<LocalDelete, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionAdoptJobs)?;
+    let job_id = anonymize::real_job_id(&job_id);
+
+    let mark = TrashMark::new(TrashKind::Annotations);
+    let cleared = modify(&app, |store| {
+        let job = store.get_mut(&job_id)?;
+        if job.tags.is_empty() && job.note.is_none() {
+            return None;
+        }
+        let item = Annotations {
+            tags: std::mem::take(&mut job.tags),
+            note: job.note.take(),
+        };
+        let count = item.tags.len() + usize::from(item.note.is_some());
+        let now = chrono::Utc::now().to_rfc3339();
+        job.tags_modified_at = Some(now.clone());
+        job.note_modified_at = Some(now);
+        job.cleared_annotations = Some(Trashed {
+            mark: mark.clone(),
+            item,
+        });
+        Some(count)
+    })?;
+    let count = cleared.ok_or_else(|| "This job has no tags or note to clear".to_string())?;
+
+    Ok(trash::deleted(&app, TrashKind::Annotations, &mark, count))
+}
+
+/// Local job entries. `server_deleted` picks only the jobs deleted on the
+/// server (true) or only the others (false); all when omitted.
+#[tauri::command]
+pub async fn list_local_jobs(
+    server_deleted: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<Vec<LocalJob>, String> {
+    let jobs = load(&app)?
+        .into_values()
+        .filter(|job| server_deleted.is_none_or(|wanted| job.server_deleted == wanted))
+        .collect();
+    Ok(anonymize::outgoing(&app, jobs))
+}
+
+/// Remove the entries, and project assignments, of jobs deleted on the
+/// server at least `older_than_days` ago. Download history is kept.
+/// Returns how many entries were removed.
+#[tauri::command]
+pub async fn purge_server_deleted_metadata(
+    older_than_days: u32,
+    app: tauri::AppHandle,
+) -> Result<usize, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionPurgeJobMetadata)?;
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(i64::from(older_than_days));
+    let purged = modify(&app, |store| {
+        let purged: Vec<String> = store
+            .values()
+            .filter(|job| {
+                job.server_deleted
+                    && crate::monitor::parse_date(job.server_deleted_at.as_deref())
+                        .is_none_or(|at| at <= cutoff)
+            })
+            .map(|job| job.job_id.clone())
+            .collect();
+        for job_id in &purged {
+            store.remove(job_id);
+        }
+        purged
+    })?;
+
+    for job_id in &purged {
+        crate::projects::assign(&app, job_id, None)?;
+    }
+    Ok(purged.len())
+}
@@ -1,24 +1,121 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use nsg_cli::{Credentials, NsgClient};
+mod about;
+mod accessibility;
+mod accounts;
+mod activity;
+mod activity_feed;
+mod announcements;
+mod anonymize;
+mod api;
+mod archive;
+mod archive_cleanup;
+mod audit_log;
+mod background_errors;
+mod checkpoint;
+mod checksum;
+mod clock;
+mod compression;
+mod crash;
+mod credentials_file;
+#[cfg(feature = "headless-invoke")]
+mod demo_backend;
+mod download;
+mod dropped_paths;
+mod dry_run;
+mod email;
+mod events;
+mod expiry;
+#[cfg(any(test, feature = "fixtures"))]
+mod fixtures;
+mod folder_submit;
+#[cfg(feature = "headless-invoke")]
+mod headless;
+mod health;
+mod history;
+mod i18n;
+mod inputs;
+mod integrity;
+mod job_cache;
+mod job_query;
+mod job_report;
+mod job_timing;
+mod keychain;
+mod lab_defaults;
+mod listing;
+mod local_jobs;
+mod metadata_sync;
+mod mode;
+mod monitor;
+mod notify;
+mod param_lint;
+mod path_access;
+mod paths;
+mod patterns;
+mod portable;
+mod preference_types;
+mod preferences;
+mod prepared;
+mod profiles;
+mod progress;
+mod projects;
+mod purge;
+mod queue;
+mod receipt;
+mod resume;
+mod rules;
+mod scheduler;
+mod secret_scan;
+mod session;
+mod session_status;
+mod size_stats;
+mod staging;
+mod state_file;
+mod state_version;
+mod streams;
+mod submissions;
+mod sweep;
+#[cfg(test)]
+mod test_util;
+mod throttle;
+mod thumbnails;
+mod tools;
+mod trash;
+mod unconfirmed;
+mod vault;
+mod verify;
+mod window_geometry;
+mod windows;
+mod xml;
+
+use activity_feed::{Activity, ActivityKind, ActivityStatus};
+use anonymize::{anonymize_job_id, anonymize_url, is_showcase_mode, Anonymize};
+use i18n::Msg;
+use inputs::InputBinding;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::fs::File;
-use std::io::Write;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
-use std::sync::Mutex;
-use tauri::{Emitter, State, WebviewWindow};
-use tauri_plugin_store::StoreExt;
-use zip::write::FileOptions;
-use zip::ZipWriter;
+use tauri::{State, WebviewWindow};
 
-// Application state - store credentials instead of client
+// Application state: the session, the shared HTTP client and what the
+// commands keep between calls
 struct AppState {
-    credentials: Mutex<Option<Credentials>>,
+    session: session::SessionState,
+    preferences: preferences::Preferences,
+    monitor: monitor::Monitor,
+    // Open result file streams of the preview pane
+    streams: streams::Streams,
+    // What the status bar shows
+    status: session_status::StatusTracker,
+    // Submissions resolved for review, awaiting submit_prepared
+    plans: prepared::Plans,
+    // The HTTP client every NSG request goes through
+    client: api::ApiClient,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 struct JobSummary {
     job_id: String,
     url: String,
@@ -27,6 +124,14 @@ struct JobSummary {
     failed: bool,
     date_submitted: Option<String>,
     date_completed: Option<String>,
+    compute_resource: Option<String>,
+    tool_version: Option<String>,
+    project_id: Option<String>,
+    // Reported by NSG, or estimated from date_completed
+    results_expire_at: Option<String>,
+    // From the disk cache, until the first listing of the session arrives
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    stale: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,327 +142,716 @@ struct JobDetails {
     date_submitted: Option<String>,
     self_uri: String,
     results_uri: Option<String>,
+    compute_resource: Option<String>,
+    tool_version: Option<String>,
+    // None when the job record doesn't say
+    notification_requested: Option<bool>,
+    notification_email: Option<String>,
+    // Best-effort, from the job messages
+    scheduler_info: Option<scheduler::SchedulerInfo>,
+    queue_note: Option<String>,
+    results_expire_at: Option<String>,
 }
 
-// Showcase mode utilities
-fn is_showcase_mode() -> bool {
-    std::env::var("SHOWCASE_MODE").unwrap_or_default() == "1"
-}
-
-fn anonymize_username(username: &str) -> String {
-    if is_showcase_mode() {
-        "demo_user".to_string()
-    } else {
-        username.to_string()
+impl Anonymize for JobDetails {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            self_uri: anonymize_url(&self.self_uri),
+            results_uri: self.results_uri.map(|u| anonymize_url(&u)),
+            ..self
+        }
     }
 }
 
-fn anonymize_job_id(job_id: &str) -> String {
-    if is_showcase_mode() {
-        // Generate consistent but unique fake ID based on hash of original
-        let prefix = "NGBW-JOB-";
-
-        // Simple hash of the job_id string to generate a unique suffix
-        let mut hash: u64 = 0;
-        for byte in job_id.bytes() {
-            hash = hash.wrapping_mul(31).wrapping_add(byte as u64);
-        }
-
-        // Convert hash to base36 string (using 0-9, A-Z)
-        let chars = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
-        let mut suffix = String::new();
-        let mut h = hash;
-        for _ in 0..12 {
-            suffix.push(chars.chars().nth((h % 36) as usize).unwrap());
-            h /= 36;
-        }
-
-        format!("{}{}", prefix, suffix)
-    } else {
-        job_id.to_string()
-    }
+pub const DEFAULT_MAX_SUBMIT_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Optional parts of a submission
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct SubmitOptions {
+    // Extra form fields sent with the job
+    params: BTreeMap<String, String>,
+    // Store the submission for later instead of failing when offline
+    queue_if_offline: bool,
+    project_id: Option<String>,
+    // false turns off NSG's completion email for this job
+    notify: Option<bool>,
+    // Overrides the default notification address
+    notify_email: Option<String>,
+    // Submit even though the password or app key was found in an input
+    ignore_secret_scan: bool,
 }
 
-fn anonymize_url(url: &str) -> String {
-    if is_showcase_mode() {
-        // Replace username and job_id in URL
-        // NSG URLs format: https://nsgr.sdsc.edu:8443/cipresrest/v1/job/USERNAME/JOBID
-        let parts: Vec<&str> = url.split('/').collect();
-        if parts.len() >= 2 {
-            let mut new_parts: Vec<String> = parts.iter().map(|s| s.to_string()).collect();
-            // Replace second-to-last segment (username) with "demo_user"
-            if parts.len() >= 2 {
-                new_parts[parts.len() - 2] = "demo_user".to_string();
-            }
-            // Replace last segment (job ID) with anonymized version
-            if let Some(job_id) = parts.last() {
-                new_parts[parts.len() - 1] = anonymize_job_id(job_id);
-            }
-            return new_parts.join("/");
-        }
-        url.to_string()
-    } else {
-        url.to_string()
-    }
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum SubmitResponse {
+    Submitted {
+        job_id: String,
+        // Advisory, e.g. most recent jobs for this tool failed
+        warning: Option<String>,
+        // Set when the submission receipt couldn't be written
+        receipt_warning: Option<String>,
+        // Resource requests that look like mistakes
+        lint: Vec<param_lint::LintFinding>,
+        // Which layer each submitted field came from
+        param_sources: BTreeMap<String, lab_defaults::ParamSource>,
+    },
+    // Couldn't reach the server; will be sent when it's back
+    Queued {
+        id: String,
+    },
+    // The server took the job but its answer couldn't be read; the job is
+    // confirmed in the background
+    SubmittedButUnconfirmed {
+        id: String,
+        job_id: Option<String>,
+        job_url: Option<String>,
+        error: String,
+    },
 }
 
-#[allow(dead_code)]
-fn anonymize_app_key(key: &str) -> String {
-    if is_showcase_mode() {
-        "DEMO-APP-KEY-".to_string() + &"X".repeat(32)
-    } else {
-        key.to_string()
+impl Anonymize for SubmitResponse {
+    fn anonymize(self) -> Self {
+        match self {
+            SubmitResponse::Submitted {
+                job_id,
+                warning,
+                receipt_warning,
+                lint,
+                param_sources,
+            } => SubmitResponse::Submitted {
+                job_id: anonymize_job_id(&job_id),
+                warning,
+                receipt_warning,
+                lint,
+                param_sources,
+            },
+            SubmitResponse::SubmittedButUnconfirmed {
+                id,
+                job_id,
+                job_url,
+                error,
+            } => SubmitResponse::SubmittedButUnconfirmed {
+                id,
+                job_id: job_id.map(|id| anonymize_job_id(&id)),
+                job_url: job_url.map(|url| anonymize_url(&url)),
+                error,
+            },
+            queued => queued,
+        }
     }
 }
 
 // Tauri Commands
 
 #[tauri::command]
-async fn load_credentials() -> Result<Option<Credentials>, String> {
+async fn load_credentials(
+    app: tauri::AppHandle,
+) -> Result<credentials_file::LoadedCredentials, String> {
     // Always return real credentials - they're needed for authentication
     // Anonymization only happens in display strings, not in credentials used for API calls
-    match Credentials::load() {
-        Ok(creds) => Ok(Some(creds)),
-        Err(_) => Ok(None),
+    tokio::task::spawn_blocking(move || keychain::load_saved(&app))
+        .await
+        .map_err(|e| format!("Task error: {}", e))
+}
+
+#[tauri::command]
+async fn list_jobs(
+    project_id: Option<String>,
+    // Only completed jobs whose results expire within the warning window
+    expiring_soon: Option<bool>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<JobSummary>, String> {
+    // The cached listing on the first render of a session, else a fresh one
+    let (session, (jobs, fetched)) = state
+        .session
+        .fetch_current(|session| {
+            let (app, client) = (&app, &state.client);
+            async move {
+                let username = &session.credentials.username;
+                if let Some(jobs) = job_cache::first_render(app, session.generation, username) {
+                    return Ok((jobs, false));
+                }
+                let api = client.api(session.credentials).await?;
+                let jobs = tokio::task::spawn_blocking(move || api.list_jobs())
+                    .await
+                    .map_err(|e| format!("Task error: {}", e))?
+                    .map_err(|e| format!("Failed to list jobs: {}", e))?;
+                Ok((listing::summarize(app, jobs), true))
+            }
+        })
+        .await?;
+    if fetched {
+        let _ = job_cache::store(&app, &session.credentials.username, &jobs);
     }
+
+    let filter = job_query::JobFilter {
+        project_id,
+        expiring_soon: expiring_soon.unwrap_or(false),
+        ..Default::default()
+    };
+    let expiry = expiry::load(&app);
+    let now = clock::now();
+    let jobs = jobs
+        .into_iter()
+        .filter(|j| filter.matches(j, "", now, expiry))
+        .collect::<Vec<_>>();
+    Ok(anonymize::outgoing(&app, jobs))
 }
 
 #[tauri::command]
-async fn connect(
-    username: String,
-    password: String,
-    app_key: String,
+async fn get_job_status(
+    job_url: String,
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<String, String> {
-    let creds = Credentials {
-        username: username.clone(),
-        password,
-        app_key,
+) -> Result<JobDetails, String> {
+    let job_url = anonymize::real_url(&job_url);
+    let (_, record) = state
+        .session
+        .fetch_current(|session| async {
+            let api = state.client.api(session.credentials).await?;
+            tokio::task::spawn_blocking(move || api.job_record(&job_url))
+                .await
+                .map_err(|e| format!("Task error: {}", e))?
+                .map_err(|e| format!("Failed to get job status: {}", e))
+        })
+        .await?;
+    let status = record.status;
+    let queue = scheduler::queue_details(&record.extras);
+    let results_expire_at = expiry::expires_at(
+        record.extras.results_expire_at.as_deref(),
+        status.date_completed.as_deref(),
+        expiry::load(&app),
+    );
+
+    let details = JobDetails {
+        job_id: status.job_id,
+        job_stage: status.job_stage,
+        failed: status.failed,
+        date_submitted: status.date_submitted,
+        self_uri: status.self_uri,
+        results_uri: status.results_uri,
+        compute_resource: record.extras.compute_resource,
+        tool_version: record.extras.tool_version,
+        notification_requested: record.extras.status_email,
+        notification_email: record.extras.email_address,
+        scheduler_info: queue.scheduler_info,
+        queue_note: queue.queue_note,
+        results_expire_at,
     };
+    Ok(anonymize::outgoing(&app, details))
+}
 
-    // Test connection by creating client
-    let test_creds = creds.clone();
-    tokio::task::spawn_blocking(move || {
-        let client = NsgClient::new(test_creds)?;
-        client.test_connection()
-    })
-    .await
-    .map_err(|e| format!("Task error: {}", e))?
-    .map_err(|e| format!("Connection test failed: {}", e))?;
+/// Cancel a job that is queued or running, then refresh the job list
+#[tauri::command]
+async fn cancel_job(
+    job_url: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    mode::ensure_writable(&app, Msg::ActionCancelJobs)?;
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let job_url = anonymize::real_url(&job_url);
+    tokio::task::spawn_blocking(move || api.cancel_job(&job_url))
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+        .map_err(|e| format!("Failed to cancel job: {}", e))?;
+    if state.session.is_current(session.generation) {
+        state.monitor.refresh_now();
+    }
+    Ok(())
+}
 
-    // Store credentials in state
-    *state.credentials.lock().unwrap() = Some(creds);
+/// Payload of `job-deleted`
+#[derive(Debug, Clone, Serialize)]
+struct JobDeleted {
+    job_id: String,
+    job_url: String,
+}
 
-    Ok(format!("Connected as {}", anonymize_username(&username)))
+impl Anonymize for JobDeleted {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            job_url: anonymize_url(&self.job_url),
+        }
+    }
 }
 
+/// Delete a finished job and its results from NSG. A job that is still
+/// queued or running is refused unless `force` is set, which cancels it.
 #[tauri::command]
-async fn list_jobs(state: State<'_, AppState>) -> Result<Vec<JobSummary>, String> {
-    let creds = state
-        .credentials
-        .lock()
-        .unwrap()
-        .clone()
-        .ok_or("Not connected")?;
-
-    let jobs = tokio::task::spawn_blocking(move || {
-        let client = NsgClient::new(creds)?;
-        client.list_jobs()
+async fn delete_job(
+    job_url: String,
+    force: Option<bool>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    mode::ensure_writable(&app, Msg::ActionDeleteJobs)?;
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let force = force.unwrap_or(false);
+    let job_url = anonymize::real_url(&job_url);
+    let url = job_url.clone();
+    let job_id = tokio::task::spawn_blocking(move || {
+        let status = api
+            .job_record(&url)
+            .map_err(|e| format!("Failed to get job status: {}", e))?
+            .status;
+        let finished = status.failed || status.job_stage == "COMPLETED";
+        if !finished && !force {
+            return Err(format!(
+                "{} is still {}; delete it with force to cancel it",
+                anonymize_job_id(&status.job_id),
+                status.job_stage
+            ));
+        }
+        api.delete_job(&url)
+            .map_err(|e| format!("Failed to delete job: {}", e))?;
+        Ok(status.job_id)
     })
     .await
-    .map_err(|e| format!("Task error: {}", e))?
-    .map_err(|e| format!("Failed to list jobs: {}", e))?;
+    .map_err(|e| format!("Task error: {}", e))??;
 
-    Ok(jobs
-        .into_iter()
-        .map(|j| JobSummary {
-            job_id: anonymize_job_id(&j.job_id),
-            url: anonymize_url(&j.url),
-            tool: j.tool,
-            job_stage: j.job_stage,
-            failed: j.failed,
-            date_submitted: j.date_submitted,
-            date_completed: j.date_completed,
-        })
-        .collect())
+    if state.session.is_current(session.generation) {
+        state.monitor.refresh_now();
+    }
+    anonymize::emit(
+        &app,
+        crate::events::JOB_DELETED,
+        JobDeleted { job_id, job_url },
+    );
+    Ok(())
 }
 
-#[tauri::command]
-async fn get_job_status(job_url: String, state: State<'_, AppState>) -> Result<JobDetails, String> {
-    let creds = state
-        .credentials
-        .lock()
-        .unwrap()
-        .clone()
-        .ok_or("Not connected")?;
-
-    let status = tokio::task::spawn_blocking(move || {
-        let client = NsgClient::new(creds)?;
-        client.get_job_status(&job_url)
-    })
-    .await
-    .map_err(|e| format!("Task error: {}", e))?
-    .map_err(|e| format!("Failed to get job status: {}", e))?;
-
-    Ok(JobDetails {
-        job_id: anonymize_job_id(&status.job_id),
-        job_stage: status.job_stage,
-        failed: status.failed,
-        date_submitted: status.date_submitted,
-        self_uri: anonymize_url(&status.self_uri),
-        results_uri: status.results_uri.map(|u| anonymize_url(&u)),
-    })
+/// Add tool parameters, given by vparam name (`runtime_`,
+/// `number_nodes_`), to the submission's form fields
+fn with_parameters(
+    options: Option<SubmitOptions>,
+    parameters: Option<HashMap<String, String>>,
+) -> Result<Option<SubmitOptions>, String> {
+    let Some(parameters) = parameters.filter(|p| !p.is_empty()) else {
+        return Ok(options);
+    };
+    let mut options = options.unwrap_or_default();
+    for (name, value) in parameters {
+        let field = sweep::field_name(name.trim());
+        match options.params.get(&field) {
+            Some(existing) if *existing != value => {
+                return Err(format!(
+                    "{} is set to both \"{}\" and \"{}\"",
+                    field, existing, value
+                ));
+            }
+            _ => {
+                options.params.insert(field, value);
+            }
+        }
+    }
+    Ok(Some(options))
 }
 
+/// Submit a single job archive as the tool's primary input
 #[tauri::command]
 async fn submit_job(
     file_path: String,
     tool: String,
+    options: Option<SubmitOptions>,
+    // Tool parameters by vparam name, e.g. runtime_
+    parameters: Option<HashMap<String, String>>,
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<String, String> {
-    let creds = state
-        .credentials
-        .lock()
-        .unwrap()
-        .clone()
-        .ok_or("Not connected")?;
-
-    let path = PathBuf::from(file_path);
-    let status = tokio::task::spawn_blocking(move || {
-        let client = NsgClient::new(creds)?;
-        client.submit_job(&path, &tool)
-    })
-    .await
-    .map_err(|e| format!("Task error: {}", e))?
-    .map_err(|e| format!("Failed to submit job: {}", e))?;
-
-    Ok(status.job_id)
+) -> Result<SubmitResponse, String> {
+    let options = with_parameters(options, parameters)?;
+    let inputs = vec![InputBinding::primary(PathBuf::from(file_path))];
+    inputs::authorize(&app, &inputs)?;
+    submit(inputs, tool, options, app, state).await
 }
 
+/// Submit a job whose tool takes several named inputs
 #[tauri::command]
-async fn download_results(
-    job_url: String,
-    output_dir: String,
+async fn submit_job_inputs(
+    inputs: Vec<InputBinding>,
+    tool: String,
+    options: Option<SubmitOptions>,
+    parameters: Option<HashMap<String, String>>,
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
-    window: WebviewWindow,
-) -> Result<String, String> {
-    let creds = state
-        .credentials
-        .lock()
-        .unwrap()
-        .clone()
-        .ok_or("Not connected")?;
-
-    // Extract job ID from URL (e.g., "https://example.com/jobs/NGBW-JOB-123" -> "NGBW-JOB-123")
-    let job_id = job_url
-        .split('/')
-        .last()
-        .ok_or("Invalid job URL")?
-        .to_string();
-
-    tokio::task::spawn_blocking(move || {
-        // Create temporary directory for downloads
-        let temp_dir = std::env::temp_dir().join(format!("nsg_download_{}", job_id));
-        std::fs::create_dir_all(&temp_dir)
-            .map_err(|e| format!("Failed to create temp dir: {}", e))?;
-
-        // Download files to temp directory with progress callback
-        let client =
-            NsgClient::new(creds).map_err(|e| format!("Failed to create client: {}", e))?;
-
-        let window_clone = window.clone();
-        let files = client
-            .download_results(&job_url, &temp_dir, |filename, downloaded, total| {
-                // Emit progress event to frontend
-                let _ = window_clone.emit(
-                    "download-progress",
-                    json!({
-                        "filename": filename,
-                        "downloaded": downloaded,
-                        "total": total,
-                    }),
-                );
-            })
-            .map_err(|e| format!("Failed to download results: {}", e))?;
-
-        // Create output zip file
-        let zip_filename = format!("nsg_results_{}.zip", job_id);
-        let zip_path = PathBuf::from(&output_dir).join(&zip_filename);
-
-        // Ensure output directory exists
-        if let Some(parent) = zip_path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create output dir: {}", e))?;
+) -> Result<SubmitResponse, String> {
+    let options = with_parameters(options, parameters)?;
+    let inputs = inputs
+        .into_iter()
+        .map(|input| InputBinding {
+            param_name: input.param_name.trim().to_string(),
+            ..input
+        })
+        .collect::<Vec<_>>();
+    inputs::authorize(&app, &inputs)?;
+    submit(inputs, tool, options, app, state).await
+}
+
+async fn submit(
+    inputs: Vec<InputBinding>,
+    tool: String,
+    options: Option<SubmitOptions>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SubmitResponse, String> {
+    let response = submit_with(inputs, tool, options, None, false, &app, &state).await?;
+    Ok(anonymize::outgoing(&app, response))
+}
+
+/// Submit with real IDs in the response. `temporary_inputs` marks inputs
+/// deleted right after, which are never queued or given a receipt.
+async fn submit_with(
+    inputs: Vec<InputBinding>,
+    tool: String,
+    options: Option<SubmitOptions>,
+    progress: Option<api::UploadProgress>,
+    temporary_inputs: bool,
+    app: &tauri::AppHandle,
+    state: &AppState,
+) -> Result<SubmitResponse, String> {
+    mode::ensure_writable(app, Msg::ActionSubmitJobs)?;
+
+    let session = state.session.require()?;
+
+    // Fail now, with the actual reason, rather than mid-upload or after
+    // sitting in the offline queue
+    let upload_bytes = inputs::validate(&inputs, max_submit_bytes(app))?;
+
+    let SubmitOptions {
+        params,
+        queue_if_offline,
+        project_id,
+        notify,
+        notify_email,
+        ignore_secret_scan,
+    } = options.unwrap_or_default();
+    let queue_if_offline = queue_if_offline && !temporary_inputs;
+
+    if !ignore_secret_scan && secret_scan::enabled(app) {
+        let scan_inputs = inputs.clone();
+        let scan_creds = session.credentials.clone();
+        tokio::task::spawn_blocking(move || secret_scan::check(&scan_inputs, &scan_creds))
+            .await
+            .map_err(|e| format!("Task error: {}", e))??;
+    }
+    let lab_defaults::Layered {
+        params,
+        sources: param_sources,
+    } = lab_defaults::layer(app, &tool, params, notify, notify_email.as_deref())?;
+    let lint = param_lint::lint(app, &tool, &params)?;
+
+    let submission = ResolvedSubmission {
+        inputs,
+        tool,
+        params,
+        param_sources,
+        lint,
+        project_id,
+        upload_bytes,
+    };
+    send_resolved(
+        submission,
+        session,
+        queue_if_offline,
+        progress,
+        temporary_inputs,
+        app,
+        state,
+    )
+    .await
+}
+
+/// A submission whose fields are final
+struct ResolvedSubmission {
+    inputs: Vec<InputBinding>,
+    tool: String,
+    params: BTreeMap<String, String>,
+    param_sources: BTreeMap<String, lab_defaults::ParamSource>,
+    lint: Vec<param_lint::LintFinding>,
+    project_id: Option<String>,
+    upload_bytes: u64,
+}
+
+/// Send a resolved submission as it is, or queue it when offline, and
+/// record the job locally. Real IDs in the response.
+async fn send_resolved(
+    submission: ResolvedSubmission,
+    session: session::SessionSnapshot,
+    queue_if_offline: bool,
+    progress: Option<api::UploadProgress>,
+    temporary_inputs: bool,
+    app: &tauri::AppHandle,
+    state: &AppState,
+) -> Result<SubmitResponse, String> {
+    let ResolvedSubmission {
+        inputs,
+        tool,
+        params,
+        param_sources,
+        lint,
+        project_id,
+        upload_bytes,
+    } = submission;
+
+    // Known to be offline: don't wait for the request to time out
+    if queue_if_offline && state.monitor.is_offline() {
+        let summary = format!("{} job queued until the connection is back", tool);
+        let id = submissions::enqueue(app, inputs, tool, params, project_id, None)?;
+        activity_feed::record(
+            app,
+            None,
+            Activity::new(
+                ActivityKind::QueuedSubmission,
+                ActivityStatus::Info,
+                summary,
+            ),
+        );
+        return Ok(SubmitResponse::Queued { id });
+    }
+
+    // Held until this function returns, so the same job can't be sent twice
+    // at once
+    let _sending = unconfirmed::begin(app, &tool, &params, &inputs)?;
+
+    let activity_key = activity_feed::unique_key("submit");
+    activity_feed::record(
+        app,
+        Some(activity_key.clone()),
+        Activity::new(
+            ActivityKind::Submission,
+            ActivityStatus::Running,
+            format!("Submitting {} job", tool),
+        ),
+    );
+    // The feed follows the upload too, whether or not the caller does
+    let progress: Option<api::UploadProgress> = if upload_bytes > 0 {
+        let feed_app = app.clone();
+        let feed_key = activity_key.clone();
+        Some(std::sync::Arc::new(move |sent: u64| {
+            activity_feed::progress(&feed_app, &feed_key, sent as f64 / upload_bytes as f64);
+            if let Some(progress) = &progress {
+                progress(sent);
+            }
+        }))
+    } else {
+        progress
+    };
+
+    let submit_inputs = inputs.clone();
+    let submit_tool = tool.clone();
+    let submit_params = params.clone();
+    let creds = session.credentials;
+    let username = creds.username.clone();
+    let started = std::time::Instant::now();
+    let result = async {
+        let api = state.client.api(creds).await?;
+        tokio::task::spawn_blocking(move || {
+            api.submit_job_with_progress(
+                &submit_inputs,
+                &submit_tool,
+                &submit_params,
+                progress.as_ref(),
+            )
+        })
+        .await
+        .map_err(|e| format!("Task error: {}", e))
+    }
+    .await;
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            activity_feed::finish(
+                app,
+                &activity_key,
+                Activity::new(
+                    ActivityKind::Submission,
+                    ActivityStatus::Failed,
+                    "Submission failed",
+                )
+                .detail(e.clone()),
+            );
+            return Err(e);
         }
+    };
+    if result.is_ok() {
+        dry_run::record_upload(app, upload_bytes, started.elapsed());
+    }
 
-        // Create zip archive
-        let zip_file =
-            File::create(&zip_path).map_err(|e| format!("Failed to create zip file: {}", e))?;
-        let mut zip = ZipWriter::new(zip_file);
-        let options = FileOptions::<()>::default()
-            .compression_method(zip::CompressionMethod::Deflated)
-            .unix_permissions(0o755);
-
-        // Add each downloaded file to the zip
-        for file_info in files {
-            let file_path = &file_info.path;
-            let file_name = file_path
-                .file_name()
-                .ok_or("Invalid file path")?
-                .to_string_lossy();
-
-            // Read file contents
-            let contents = std::fs::read(file_path)
-                .map_err(|e| format!("Failed to read file {}: {}", file_name, e))?;
-
-            // Add to zip
-            zip.start_file(file_name.to_string(), options)
-                .map_err(|e| format!("Failed to add file to zip: {}", e))?;
-            zip.write_all(&contents)
-                .map_err(|e| format!("Failed to write file to zip: {}", e))?;
+    let status = match result {
+        Ok(api::Submitted::Confirmed(status)) => status,
+        Ok(api::Submitted::Unconfirmed(unread)) => {
+            let entry =
+                unconfirmed::record(app, &username, unread, &tool, &params, &inputs, project_id)?;
+            let mut activity = Activity::new(
+                ActivityKind::Submission,
+                ActivityStatus::Info,
+                format!("{} job submitted but not confirmed yet", tool),
+            )
+            .detail(entry.error.clone());
+            if let Some(job_id) = &entry.job_id {
+                activity = activity.job(job_id);
+            }
+            activity_feed::finish(app, &activity_key, activity);
+            return Ok(SubmitResponse::SubmittedButUnconfirmed {
+                id: entry.id,
+                job_id: entry.job_id,
+                job_url: entry.job_url,
+                error: entry.error,
+            });
+        }
+        Err(e) if queue_if_offline && api::is_network_error(&e) => {
+            activity_feed::finish(
+                app,
+                &activity_key,
+                Activity::new(
+                    ActivityKind::Submission,
+                    ActivityStatus::Info,
+                    format!("{} job queued after the connection dropped", tool),
+                )
+                .detail(e.to_string()),
+            );
+            let id =
+                submissions::enqueue(app, inputs, tool, params, project_id, Some(e.to_string()))?;
+            return Ok(SubmitResponse::Queued { id });
+        }
+        Err(e) => {
+            let e = format!("Failed to submit job: {}", e);
+            activity_feed::finish(
+                app,
+                &activity_key,
+                Activity::new(
+                    ActivityKind::Submission,
+                    ActivityStatus::Failed,
+                    "Submission failed",
+                )
+                .detail(e.clone()),
+            );
+            return Err(e);
         }
+    };
+    activity_feed::finish(
+        app,
+        &activity_key,
+        Activity::new(
+            ActivityKind::Submission,
+            ActivityStatus::Succeeded,
+            format!("Submitted {} job", tool),
+        )
+        .job(&status.job_id),
+    );
+
+    // The job exists either way, so it is still reported, but the monitor
+    // now belongs to another account
+    if state.session.is_current(session.generation) {
+        state.monitor.nudge(&status.self_uri, &status.job_id);
+    }
+    if let Err(e) = local_jobs::record_submission(
+        app,
+        &status.job_id,
+        &status.self_uri,
+        &tool,
+        &params,
+        &status.job_stage,
+    ) {
+        background_errors::report(
+            app,
+            local_jobs::RECORD_SOURCE,
+            background_errors::ErrorKind::Other,
+            format!("Failed to record submitted job: {}", e),
+        );
+    }
+    if let Some(project_id) = &project_id {
+        // The job exists now; a failed local assignment shouldn't hide that
+        if let Err(e) = projects::assign(app, &status.job_id, Some(project_id)) {
+            background_errors::report(
+                app,
+                local_jobs::RECORD_SOURCE,
+                background_errors::ErrorKind::Other,
+                format!("Failed to assign job to project: {}", e),
+            );
+        }
+    }
 
-        // Finalize zip
-        zip.finish()
-            .map_err(|e| format!("Failed to finalize zip: {}", e))?;
+    let receipt_warning = if receipt::is_enabled(app) && !temporary_inputs {
+        let job_id = status.job_id.clone();
+        let job_url = status.self_uri.clone();
+        let receipt_tool = tool.clone();
+        tokio::task::spawn_blocking(move || {
+            receipt::write(&inputs, &job_id, &job_url, &receipt_tool, &params)
+        })
+        .await
+        .unwrap_or_else(|e| Some(format!("Task error: {}", e)))
+    } else {
+        None
+    };
 
-        // Clean up temp directory
-        std::fs::remove_dir_all(&temp_dir)
-            .map_err(|e| format!("Failed to clean up temp dir: {}", e))?;
+    Ok(SubmitResponse::Submitted {
+        job_id: status.job_id,
+        warning: health::current(app, state, &tool).and_then(|h| h.warning),
+        receipt_warning,
+        lint,
+        param_sources,
+    })
+}
 
-        // Emit completion event
-        let _ = window.emit("download-complete", json!({}));
+pub fn max_submit_bytes(app: &tauri::AppHandle) -> u64 {
+    preferences::of(app)
+        .get("max_submit_bytes")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_MAX_SUBMIT_BYTES)
+}
 
-        Ok(zip_path.to_string_lossy().to_string())
-    })
-    .await
-    .map_err(|e| format!("Task error: {}", e))?
+#[tauri::command]
+async fn get_max_submit_bytes(app: tauri::AppHandle) -> Result<u64, String> {
+    Ok(max_submit_bytes(&app))
 }
 
 #[tauri::command]
-async fn get_download_dir(app: tauri::AppHandle) -> Result<String, String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+async fn set_max_submit_bytes(app: tauri::AppHandle, bytes: u64) -> Result<(), String> {
+    mode::ensure_writable(&app, Msg::ActionChangeSubmissionSettings)?;
+    if bytes == 0 {
+        return Err("The submission size limit must be greater than zero".to_string());
+    }
+
+    preferences::of(&app).set("max_submit_bytes", json!(bytes));
+    Ok(())
+}
+
+/// The global download folder: the user's choice, or ~/Downloads
+fn default_download_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let prefs = preferences::of(app);
 
     // Check if user has set a custom download directory
-    if let Some(custom_dir) = store.get("download_dir") {
+    if let Some(custom_dir) = prefs.get("download_dir") {
         if let Some(dir_str) = custom_dir.as_str() {
-            return Ok(dir_str.to_string());
+            return Ok(PathBuf::from(dir_str));
         }
     }
 
     // Default to ~/Downloads
-    let home = std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .map_err(|_| "Could not determine home directory".to_string())?;
+    let home = paths::home_dir().ok_or("Could not determine home directory")?;
 
-    let download_dir = PathBuf::from(home).join("Downloads");
-    Ok(download_dir.to_string_lossy().to_string())
+    Ok(home.join("Downloads"))
+}
+
+#[tauri::command]
+async fn get_download_dir(app: tauri::AppHandle) -> Result<String, String> {
+    paths::to_ipc_string(&default_download_dir(&app)?)
 }
 
 #[tauri::command]
 async fn set_download_dir(app: tauri::AppHandle, dir: String) -> Result<(), String> {
+    mode::ensure_writable(&app, Msg::ActionChangeDownloadFolder)?;
+    path_access::authorize_str(&app, &dir)?;
+
     // Validate that the directory exists
     let path = PathBuf::from(&dir);
     if !path.exists() {
@@ -367,46 +861,36 @@ async fn set_download_dir(app: tauri::AppHandle, dir: String) -> Result<(), Stri
         return Err(format!("Path is not a directory: {}", dir));
     }
 
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    store.set("download_dir", json!(dir));
-    store
-        .save()
-        .map_err(|e| format!("Failed to save store: {}", e))?;
+    prefs.set("download_dir", json!(dir));
 
     Ok(())
 }
 
 #[tauri::command]
 async fn get_credentials_location() -> Result<String, String> {
-    Ok(Credentials::credentials_location())
+    paths::to_ipc_string(&portable::credentials_path())
 }
 
 #[tauri::command]
 async fn zoom_in(app: tauri::AppHandle, window: WebviewWindow) -> Result<f64, String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
     // Get current zoom level
-    let current_zoom = store
+    let current_zoom = prefs
         .get("zoom_level")
         .and_then(|v| v.as_f64())
         .unwrap_or(1.0);
 
     let new_zoom = (current_zoom + 0.1).min(3.0); // Max 300%
 
-    // Save to store (auto-persists)
-    store.set("zoom_level", json!(new_zoom));
-    store
-        .save()
-        .map_err(|e| format!("Failed to save store: {}", e))?;
+    // Saved in the background
+    prefs.set("zoom_level", json!(new_zoom));
 
     // Apply to window
     window
-        .eval(&format!("document.body.style.zoom = '{}'", new_zoom))
+        .eval(format!("document.body.style.zoom = '{}'", new_zoom))
         .map_err(|e| format!("Failed to set zoom: {}", e))?;
 
     Ok(new_zoom)
@@ -414,24 +898,19 @@ async fn zoom_in(app: tauri::AppHandle, window: WebviewWindow) -> Result<f64, St
 
 #[tauri::command]
 async fn zoom_out(app: tauri::AppHandle, window: WebviewWindow) -> Result<f64, String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    let current_zoom = store
+    let current_zoom = prefs
         .get("zoom_level")
         .and_then(|v| v.as_f64())
         .unwrap_or(1.0);
 
     let new_zoom = (current_zoom - 0.1).max(0.5); // Min 50%
 
-    store.set("zoom_level", json!(new_zoom));
-    store
-        .save()
-        .map_err(|e| format!("Failed to save store: {}", e))?;
+    prefs.set("zoom_level", json!(new_zoom));
 
     window
-        .eval(&format!("document.body.style.zoom = '{}'", new_zoom))
+        .eval(format!("document.body.style.zoom = '{}'", new_zoom))
         .map_err(|e| format!("Failed to set zoom: {}", e))?;
 
     Ok(new_zoom)
@@ -439,14 +918,9 @@ async fn zoom_out(app: tauri::AppHandle, window: WebviewWindow) -> Result<f64, S
 
 #[tauri::command]
 async fn reset_zoom(app: tauri::AppHandle, window: WebviewWindow) -> Result<f64, String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    store.set("zoom_level", json!(1.0));
-    store
-        .save()
-        .map_err(|e| format!("Failed to save store: {}", e))?;
+    prefs.set("zoom_level", json!(1.0));
 
     window
         .eval("document.body.style.zoom = '1.0'")
@@ -457,11 +931,9 @@ async fn reset_zoom(app: tauri::AppHandle, window: WebviewWindow) -> Result<f64,
 
 #[tauri::command]
 async fn get_zoom(app: tauri::AppHandle) -> Result<f64, String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    let zoom = store
+    let zoom = prefs
         .get("zoom_level")
         .and_then(|v| v.as_f64())
         .unwrap_or(1.0);
@@ -469,13 +941,15 @@ async fn get_zoom(app: tauri::AppHandle) -> Result<f64, String> {
     Ok(zoom)
 }
 
+// "high-contrast" is chosen explicitly; "system" may still pick it up from
+// get_accessibility_hints
+const THEMES: &[&str] = &["light", "dark", "system", "high-contrast"];
+
 #[tauri::command]
 async fn get_theme(app: tauri::AppHandle) -> Result<String, String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    let theme = store
+    let theme = prefs
         .get("theme")
         .and_then(|v| v.as_str().map(|s| s.to_string()))
         .unwrap_or_else(|| "system".to_string());
@@ -485,14 +959,16 @@ async fn get_theme(app: tauri::AppHandle) -> Result<String, String> {
 
 #[tauri::command]
 async fn set_theme(app: tauri::AppHandle, theme: String) -> Result<(), String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    if !THEMES.contains(&theme.as_str()) {
+        return Err(format!(
+            "Unknown theme {}; expected one of {}",
+            theme,
+            THEMES.join(", ")
+        ));
+    }
+    let prefs = preferences::of(&app);
 
-    store.set("theme", json!(theme));
-    store
-        .save()
-        .map_err(|e| format!("Failed to save store: {}", e))?;
+    prefs.set("theme", json!(theme));
 
     Ok(())
 }
@@ -504,11 +980,9 @@ fn get_showcase_mode() -> bool {
 
 #[tauri::command]
 async fn get_auto_refresh(app: tauri::AppHandle) -> Result<bool, String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    let auto_refresh = store
+    let auto_refresh = prefs
         .get("auto_refresh")
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
@@ -518,25 +992,18 @@ async fn get_auto_refresh(app: tauri::AppHandle) -> Result<bool, String> {
 
 #[tauri::command]
 async fn set_auto_refresh(app: tauri::AppHandle, enabled: bool) -> Result<(), String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    store.set("auto_refresh", json!(enabled));
-    store
-        .save()
-        .map_err(|e| format!("Failed to save store: {}", e))?;
+    prefs.set("auto_refresh", json!(enabled));
 
     Ok(())
 }
 
 #[tauri::command]
 async fn get_auto_refresh_interval(app: tauri::AppHandle) -> Result<u64, String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    let interval = store
+    let interval = prefs
         .get("auto_refresh_interval")
         .and_then(|v| v.as_u64())
         .unwrap_or(30);
@@ -546,38 +1013,197 @@ async fn get_auto_refresh_interval(app: tauri::AppHandle) -> Result<u64, String>
 
 #[tauri::command]
 async fn set_auto_refresh_interval(app: tauri::AppHandle, interval: u64) -> Result<(), String> {
-    let store = app
-        .store("preferences.json")
-        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let prefs = preferences::of(&app);
 
-    store.set("auto_refresh_interval", json!(interval));
-    store
-        .save()
-        .map_err(|e| format!("Failed to save store: {}", e))?;
+    prefs.set("auto_refresh_interval", json!(interval));
 
     Ok(())
 }
 
 fn main() {
+    crash::install();
+    portable::init();
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_window_state::Builder::default().build())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_notification::init())
         .manage(AppState {
-            credentials: Mutex::new(None),
+            session: session::SessionState::default(),
+            monitor: monitor::Monitor::new(),
+            preferences: preferences::Preferences::default(),
+            streams: streams::Streams::default(),
+            status: session_status::StatusTracker::default(),
+            plans: prepared::Plans::default(),
+            client: api::ApiClient::default(),
+        })
+        .on_webview_event(|webview, event| {
+            // Dropped paths count as picked, like a dialog's
+            if let tauri::WebviewEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event {
+                for path in paths {
+                    if let Err(e) = path_access::register_picked_path(path) {
+                        background_errors::report(
+                            tauri::Manager::app_handle(webview),
+                            "dropped_paths",
+                            background_errors::ErrorKind::Other,
+                            e,
+                        );
+                    }
+                }
+            }
+        })
+        .setup(|app| {
+            // Before anything can make a request
+            #[cfg(feature = "headless-invoke")]
+            if headless::requested() {
+                headless::select_backend()?;
+            }
+            crash::attach(app.handle().clone());
+            crash::check(app.handle());
+            preferences::load(app.handle());
+            portable::check(app.handle());
+            state_version::init(app.handle().clone());
+            integrity::check(app.handle());
+            activity::init(app.handle().clone());
+            clock::init(app.handle().clone());
+            i18n::load(app.handle());
+            throttle::load(app.handle());
+            queue::load(app.handle());
+            staging::sweep_orphans(app.handle());
+            monitor::start(app.handle().clone());
+            submissions::start(app.handle().clone());
+            unconfirmed::start(app.handle().clone());
+            trash::start(app.handle().clone());
+            archive_cleanup::start(app.handle().clone());
+            accessibility::start(app.handle().clone());
+            window_geometry::check(app.handle());
+            #[cfg(feature = "headless-invoke")]
+            if headless::requested() {
+                headless::start(app.handle())?;
+            }
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             load_credentials,
-            connect,
+            keychain::save_credentials_secure,
+            keychain::load_credentials_secure,
+            keychain::get_credential_backend,
+            keychain::set_credential_backend,
+            profiles::list_profiles,
+            profiles::add_profile,
+            profiles::switch_profile,
+            profiles::delete_profile,
+            vault::get_vault_status,
+            vault::create_vault,
+            vault::unlock_vault,
+            vault::lock_vault,
+            path_access::pick_path,
+            accessibility::get_accessibility_hints,
+            dropped_paths::inspect_dropped_paths,
+            metadata_sync::export_local_metadata,
+            metadata_sync::import_local_metadata,
+            #[cfg(feature = "fixtures")]
+            fixtures::export_fixture_account,
+            archive_cleanup::get_archive_cleanup_settings,
+            archive_cleanup::set_archive_cleanup_settings,
+            archive_cleanup::run_archive_cleanup,
+            history::pin_history_entry,
+            activity_feed::get_activity_feed,
+            activity::get_network_activity,
+            clock::get_clock_skew,
+            crash::get_previous_crash,
+            crash::dismiss_previous_crash,
+            crash::open_crash_log,
+            crash::trigger_test_panic,
+            session::connect,
+            session::auto_connect,
+            session::get_auto_connect,
+            session::validate_app_key_format,
+            session::set_auto_connect,
+            session::delete_saved_credentials,
+            session::disconnect,
+            accounts::get_account_status,
+            accounts::resolve_account_mismatch,
+            purge::purge_app_data,
             list_jobs,
             get_job_status,
+            cancel_job,
+            tools::list_tools,
+            delete_job,
             submit_job,
-            download_results,
+            submit_job_inputs,
+            prepared::prepare_submission,
+            prepared::submit_prepared,
+            checkpoint::download_latest_matching,
+            session_status::get_session_status,
+            background_errors::get_active_background_errors,
+            portable::get_data_location_conflict,
+            portable::resolve_data_location_conflict,
+            checkpoint::get_checkpoint_patterns,
+            checkpoint::set_checkpoint_pattern,
+            folder_submit::submit_folder,
+            sweep::submit_sweep,
+            folder_submit::get_folder_submit_settings,
+            folder_submit::set_folder_submit_settings,
+            job_timing::get_job_timing,
+            job_report::export_job_report,
+            thumbnails::list_image_results,
+            thumbnails::get_result_thumbnail,
+            thumbnails::get_thumbnail_cache_limit,
+            thumbnails::set_thumbnail_cache_limit,
+            lab_defaults::get_effective_defaults,
+            lab_defaults::get_lab_defaults_path,
+            lab_defaults::set_lab_defaults_path,
+            secret_scan::get_secret_scan_enabled,
+            secret_scan::set_secret_scan_enabled,
+            dry_run::dry_run_submit,
+            health::get_tool_health,
+            expiry::get_expiry_settings,
+            expiry::set_expiry_settings,
+            download::download_results,
+            download::list_result_files,
+            download::frontend_ready,
+            checksum::verify_archive,
+            verify::verify_against_server,
+            archive::list_archive_contents,
+            archive::extract_archive_entry,
+            queue::resume_pending_downloads,
+            queue::discard_pending_downloads,
+            download::get_max_download_warn_bytes,
+            download::set_max_download_warn_bytes,
+            size_stats::get_download_size_stats,
+            trash::undo_local_delete,
+            history::remove_download_history_entries,
+            local_jobs::clear_job_annotations,
+            staging::get_staging_dir,
+            staging::set_staging_dir,
+            notify::download_notification_clicked,
+            announcements::get_service_announcements,
+            announcements::dismiss_announcement,
+            announcements::get_announcements_url,
+            announcements::set_announcements_url,
+            notify::get_notification_settings,
+            notify::set_notification_settings,
+            size_stats::get_adaptive_download_warning,
+            size_stats::set_adaptive_download_warning,
+            download::get_delete_after_download,
+            download::set_delete_after_download,
+            download::get_settle_delay_secs,
+            download::set_settle_delay_secs,
+            download::get_download_concurrency,
+            download::set_download_concurrency,
+            throttle::get_download_rate_limit,
+            throttle::set_download_rate_limit,
+            history::get_download_history,
+            monitor::get_monitor_settings,
+            monitor::set_monitor_settings,
+            anonymize::export_anonymization_map,
             get_download_dir,
             set_download_dir,
             get_credentials_location,
+            credentials_file::check_credentials_file,
+            credentials_file::fix_credentials_permissions,
             zoom_in,
             zoom_out,
             reset_zoom,
@@ -589,7 +1215,60 @@ fn main() {
             set_auto_refresh,
             get_auto_refresh_interval,
             set_auto_refresh_interval,
+            about::get_license_report,
+            about::get_app_info,
+            mode::get_app_mode,
+            mode::set_read_only,
+            i18n::get_locale,
+            submissions::list_pending_submissions,
+            email::get_default_notification_email,
+            email::set_default_notification_email,
+            projects::list_projects,
+            projects::create_project,
+            projects::rename_project,
+            projects::delete_project,
+            projects::assign_job_to_project,
+            local_jobs::adopt_job,
+            local_jobs::adopt_untracked_jobs,
+            local_jobs::list_local_jobs,
+            local_jobs::purge_server_deleted_metadata,
+            streams::open_result_stream,
+            streams::read_stream_chunk,
+            streams::search_stream,
+            streams::close_result_stream,
+            submissions::cancel_pending_submission,
+            unconfirmed::list_unconfirmed_submissions,
+            unconfirmed::dismiss_unconfirmed_submission,
+            i18n::set_locale,
+            windows::open_job_window,
+            window_geometry::reset_window_geometry,
+            preferences::flush_preferences,
+            preferences::get_preference_repairs,
+            integrity::get_state_file_report,
+            job_query::query_jobs,
+            listing::refresh_jobs_diff,
+            job_cache::rebuild_job_cache,
+            listing::get_memory_stats,
+            compression::get_zip_compression,
+            compression::set_zip_compression,
+            patterns::get_download_patterns,
+            patterns::set_download_patterns,
+            patterns::preview_download_selection,
+            rules::get_download_rules,
+            rules::set_download_rules,
+            rules::get_last_output_dir,
+            get_max_submit_bytes,
+            set_max_submit_bytes,
+            receipt::get_write_submission_receipt,
+            receipt::set_write_submission_receipt,
+            receipt::read_submission_receipt,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Nothing is left to tell about a failure
+                let _ = preferences::of(app).flush();
+            }
+        });
 }
@@ -0,0 +1,477 @@
+// Carrying local metadata between devices.
+//
+// Tags, notes and projects live only on the machine they were made on.
+// export_local_metadata writes them to one bundle file, which can sit in a
+// synced folder, and import_local_metadata brings a bundle in on another
+// machine. Importing never removes a job or a project. What happens when
+// both sides have something for the same job depends on the strategy:
+// `theirs` takes the bundle's tags, note and project, `mine` keeps this
+// machine's and only adds what it lacks, and `merge` unions the tags, keeps
+// whichever note was changed last (going by note_modified_at; a note with
+// no time loses to one with a time, and a tie keeps ours) and keeps our
+// project for a job assigned differently on both. In every case, stage
+// sightings are combined, and projects are matched by name and created when
+// missing. Before anything is written, local_jobs.json and projects.json
+// are copied to backups/metadata-<time>/. Both files are then written with
+// their locks held, and if the second write fails the first is put back. A
+// bundle from another NSG account is refused unless `force` is set.
+
+use crate::anonymize::{self, anonymize_job_id, Anonymize};
+use crate::i18n::Msg;
+use crate::local_jobs::{self, LocalJob, StageSighting};
+use crate::projects::{self, Project, ProjectStore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const BUNDLE_FORMAT: &str = "nsg-gui-local-metadata";
+const BUNDLE_VERSION: u32 = 1;
+const BACKUP_DIR: &str = "backups";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataBundle {
+    pub format: String,
+    pub version: u32,
+    // Owner of the metadata; None when exported before anyone connected
+    pub username: Option<String>,
+    pub exported_at: String,
+    // By real job ID
+    pub jobs: BTreeMap<String, LocalJob>,
+    pub projects: Vec<Project>,
+    // Real job ID -> project name, since project IDs differ per machine
+    pub assignments: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStrategy {
+    Theirs,
+    Mine,
+    Merge,
+}
+
+/// What an import changed for one job
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobImportChange {
+    pub job_id: String,
+    // The job had no entry here before
+    pub added: bool,
+    pub tags_added: Vec<String>,
+    pub tags_removed: Vec<String>,
+    pub note_changed: bool,
+    // Name of the project it was put in
+    pub project: Option<String>,
+    // Where both sides differed and one was kept
+    pub conflicts: Vec<String>,
+}
+
+impl Anonymize for JobImportChange {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetadataImportReport {
+    pub strategy: ImportStrategy,
+    pub bundle_username: Option<String>,
+    pub bundle_exported_at: String,
+    // Copies of the files as they were before the import
+    pub backup_dir: String,
+    // Only the jobs that changed
+    pub jobs: Vec<JobImportChange>,
+    pub projects_created: Vec<String>,
+    pub unchanged_jobs: usize,
+}
+
+impl Anonymize for MetadataImportReport {
+    fn anonymize(self) -> Self {
+        Self {
+            bundle_username: self
+                .bundle_username
+                .map(|u| anonymize::anonymize_username(&u)),
+            jobs: self.jobs.anonymize(),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetadataExport {
+    pub path: String,
+    pub jobs: usize,
+    pub projects: usize,
+}
+
+fn bundle(app: &tauri::AppHandle) -> Result<MetadataBundle, String> {
+    let jobs = local_jobs::load(app)?
+        .into_iter()
+        .map(|(id, job)| {
+            // Undo state stays on this machine
+            let job = LocalJob {
+                cleared_annotations: None,
+                ..job
+            };
+            (id, job)
+        })
+        .collect();
+    let store = projects::load(app)?;
+    let names: BTreeMap<&str, &str> = store
+        .projects
+        .iter()
+        .map(|p| (p.id.as_str(), p.name.as_str()))
+        .collect();
+    let assignments = store
+        .assignments
+        .iter()
+        .filter_map(|(job, project)| Some((job.clone(), names.get(project.as_str())?.to_string())))
+        .collect();
+    Ok(MetadataBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        username: crate::accounts::active_username(app),
+        exported_at: crate::clock::now().to_rfc3339(),
+        jobs,
+        projects: store.projects,
+        assignments,
+    })
+}
+
+fn read_bundle(path: &Path) -> Result<MetadataBundle, String> {
+    let content = std::fs::read(path).map_err(|e| format!("Failed to read the bundle: {}", e))?;
+    let bundle: MetadataBundle =
+        serde_json::from_slice(&content).map_err(|e| format!("Not a metadata bundle: {}", e))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err("Not a metadata bundle".to_string());
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(format!(
+            "The bundle is version {}, from a newer version of the app (this one reads up to {})",
+            bundle.version, BUNDLE_VERSION
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Whether `a` is a later time than `b`; a missing time is the earliest
+fn newer(a: Option<&str>, b: Option<&str>) -> bool {
+    let parse = |t: Option<&str>| t.and_then(crate::clock::parse_server_time);
+    match (parse(a), parse(b)) {
+        (Some(a), Some(b)) => a > b,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+fn merge_stages(mine: &mut Vec<StageSighting>, theirs: &[StageSighting]) {
+    for sighting in theirs {
+        let known = mine
+            .iter()
+            .any(|s| s.stage == sighting.stage && s.seen_at == sighting.seen_at);
+        if !known {
+            mine.push(sighting.clone());
+        }
+    }
+    mine.sort_by_key(|s| crate::clock::parse_server_time(&s.seen_at));
+}
+
+/// Bring `theirs` into `mine`, recording what changed in `change`
+fn merge_job(
+    mine: &mut LocalJob,
+    theirs: &LocalJob,
+    strategy: ImportStrategy,
+    change: &mut JobImportChange,
+) {
+    let before_tags = mine.tags.clone();
+    let tags_differ = {
+        let (mut a, mut b) = (mine.tags.clone(), theirs.tags.clone());
+        a.sort();
+        b.sort();
+        a != b
+    };
+    match strategy {
+        ImportStrategy::Theirs if tags_differ => {
+            mine.tags = theirs.tags.clone();
+            mine.tags_modified_at = theirs.tags_modified_at.clone();
+        }
+        ImportStrategy::Mine if tags_differ && !mine.tags.is_empty() => {
+            change
+                .conflicts
+                .push("tags: kept this device's".to_string());
+        }
+        ImportStrategy::Mine | ImportStrategy::Merge => {
+            for tag in &theirs.tags {
+                if !mine.tags.contains(tag) {
+                    mine.tags.push(tag.clone());
+                }
+            }
+            if mine.tags != before_tags
+                && newer(
+                    theirs.tags_modified_at.as_deref(),
+                    mine.tags_modified_at.as_deref(),
+                )
+            {
+                mine.tags_modified_at = theirs.tags_modified_at.clone();
+            }
+        }
+        ImportStrategy::Theirs => {}
+    }
+    change.tags_added = mine
+        .tags
+        .iter()
+        .filter(|t| !before_tags.contains(t))
+        .cloned()
+        .collect();
+    change.tags_removed = before_tags
+        .iter()
+        .filter(|t| !mine.tags.contains(t))
+        .cloned()
+        .collect();
+
+    if mine.note != theirs.note && theirs.note.is_some() {
+        let take = match strategy {
+            ImportStrategy::Theirs => true,
+            ImportStrategy::Mine => mine.note.is_none(),
+            ImportStrategy::Merge => {
+                mine.note.is_none()
+                    || newer(
+                        theirs.note_modified_at.as_deref(),
+                        mine.note_modified_at.as_deref(),
+                    )
+            }
+        };
+        if mine.note.is_some() {
+            change.conflicts.push(if take {
+                "note: took the bundle's".to_string()
+            } else {
+                "note: kept this device's".to_string()
+            });
+        }
+        if take {
+            mine.note = theirs.note.clone();
+            mine.note_modified_at = theirs.note_modified_at.clone();
+            change.note_changed = true;
+        }
+    }
+
+    merge_stages(&mut mine.stages, &theirs.stages);
+    if mine.params.is_empty() {
+        mine.params = theirs.params.clone();
+    }
+    if mine.tool.is_none() {
+        mine.tool = theirs.tool.clone();
+    }
+}
+
+/// The project named `name`, created when there isn't one
+fn project_id(store: &mut ProjectStore, name: &str, created: &mut Vec<String>) -> String {
+    if let Some(project) = store.named(name) {
+        return project.id.clone();
+    }
+    let mut project = Project::new(name.trim().to_string());
+    while store.projects.iter().any(|p| p.id == project.id) {
+        project.id.push('x');
+    }
+    created.push(project.name.clone());
+    let id = project.id.clone();
+    store.projects.push(project);
+    id
+}
+
+/// Apply `bundle` to the stores, returning the per-job changes
+fn apply(
+    jobs: &mut BTreeMap<String, LocalJob>,
+    store: &mut ProjectStore,
+    bundle: &MetadataBundle,
+    strategy: ImportStrategy,
+    projects_created: &mut Vec<String>,
+) -> (Vec<JobImportChange>, usize) {
+    // Every project in the bundle exists here afterwards, even if empty
+    for project in &bundle.projects {
+        project_id(store, &project.name, projects_created);
+    }
+
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+    let job_ids: std::collections::BTreeSet<&String> = bundle
+        .jobs
+        .keys()
+        .chain(bundle.assignments.keys())
+        .collect();
+    for job_id in job_ids {
+        let mut change = JobImportChange {
+            job_id: job_id.clone(),
+            ..Default::default()
+        };
+        if let Some(theirs) = bundle.jobs.get(job_id) {
+            match jobs.get_mut(job_id) {
+                Some(mine) => merge_job(mine, theirs, strategy, &mut change),
+                None => {
+                    jobs.insert(
+                        job_id.clone(),
+                        LocalJob {
+                            cleared_annotations: None,
+                            ..theirs.clone()
+                        },
+                    );
+                    change.added = true;
+                    change.tags_added = theirs.tags.clone();
+                    change.note_changed = theirs.note.is_some();
+                }
+            }
+        }
+
+        if let Some(name) = bundle.assignments.get(job_id) {
+            let theirs = project_id(store, name, projects_created);
+            match store.assignments.get(job_id) {
+                Some(mine) if *mine == theirs => {}
+                Some(_) if strategy != ImportStrategy::Theirs => {
+                    change
+                        .conflicts
+                        .push("project: kept this device's".to_string());
+                }
+                _ => {
+                    store.assignments.insert(job_id.clone(), theirs);
+                    change.project = Some(name.clone());
+                }
+            }
+        }
+
+        let changed = change.added
+            || !change.tags_added.is_empty()
+            || !change.tags_removed.is_empty()
+            || change.note_changed
+            || change.project.is_some()
+            || !change.conflicts.is_empty();
+        if changed {
+            changes.push(change);
+        } else {
+            unchanged += 1;
+        }
+    }
+    (changes, unchanged)
+}
+
+/// Copy `files` into a new folder under backups/, returning it
+fn backup(app: &tauri::AppHandle, files: &[&Path]) -> Result<PathBuf, String> {
+    let dir = crate::accounts::user_root(app)?
+        .join(BACKUP_DIR)
+        .join(format!(
+            "metadata-{}",
+            crate::clock::now().format("%Y%m%dT%H%M%S%.3f")
+        ));
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create the backup: {}", e))?;
+    for file in files.iter().filter(|f| f.exists()) {
+        let name = file.file_name().ok_or("State file has no name")?;
+        std::fs::copy(file, dir.join(name))
+            .map_err(|e| format!("Failed to back up {}: {}", file.display(), e))?;
+    }
+    Ok(dir)
+}
+
+fn import(
+    app: &tauri::AppHandle,
+    bundle: &MetadataBundle,
+    strategy: ImportStrategy,
+) -> Result<MetadataImportReport, String> {
+    let jobs_path = local_jobs::local_jobs_path(app)?;
+    let projects_path = projects::projects_path(app)?;
+    let _jobs_lock = crate::state_file::lock(&jobs_path)?;
+    let _projects_lock = crate::state_file::lock(&projects_path)?;
+
+    let mut jobs: BTreeMap<String, LocalJob> = crate::state_file::read(&jobs_path)?;
+    let mut store: ProjectStore = crate::state_file::read(&projects_path)?;
+    let mut projects_created = Vec::new();
+    let (changes, unchanged) = apply(
+        &mut jobs,
+        &mut store,
+        bundle,
+        strategy,
+        &mut projects_created,
+    );
+
+    // Both serialized before either is written
+    let jobs_content =
+        serde_json::to_string_pretty(&crate::state_version::wrap(&jobs_path, &jobs)?)
+            .map_err(|e| format!("Failed to serialize local jobs: {}", e))?;
+    let projects_content =
+        serde_json::to_string_pretty(&crate::state_version::wrap(&projects_path, &store)?)
+            .map_err(|e| format!("Failed to serialize projects: {}", e))?;
+
+    let backup_dir = backup(app, &[&jobs_path, &projects_path])?;
+    crate::state_file::write_atomic(&jobs_path, jobs_content.as_bytes())?;
+    if let Err(e) = crate::state_file::write_atomic(&projects_path, projects_content.as_bytes()) {
+        let saved = backup_dir.join(local_jobs::LOCAL_JOBS_FILE);
+        let restored = match std::fs::read(&saved) {
+            Ok(content) => crate::state_file::write_atomic(&jobs_path, &content),
+            Err(_) => crate::paths::remove_file_if_exists(&jobs_path).map(|_| ()),
+        };
+        if let Err(restore_error) = restored {
+            return Err(format!(
+                "{}; undoing the import also failed: {}",
+                e, restore_error
+            ));
+        }
+        return Err(e);
+    }
+
+    Ok(MetadataImportReport {
+        strategy,
+        bundle_username: bundle.username.clone(),
+        bundle_exported_at: bundle.exported_at.clone(),
+        backup_dir: crate::paths::to_ipc_string(&backup_dir)?,
+        jobs: changes,
+        projects_created,
+        unchanged_jobs: unchanged,
+    })
+}
+
+/// Write this machine's tags, notes and projects to `path`
+#[tauri::command]
+pub async fn export_local_metadata(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<MetadataExport, String> {
+    let path = crate::path_access::authorize_str(&app, &path)?;
+    let bundle = bundle(&app)?;
+    let content = serde_json::to_vec_pretty(&bundle)
+        .map_err(|e| format!("Failed to serialize the metadata bundle: {}", e))?;
+    crate::state_file::write_atomic(&path, &content)?;
+    Ok(MetadataExport {
+        path: crate::paths::to_ipc_string(&path)?,
+        jobs: bundle.jobs.len(),
+        projects: bundle.projects.len(),
+    })
+}
+
+/// Bring in a bundle written by export_local_metadata on another device
+#[tauri::command]
+pub async fn import_local_metadata(
+    app: tauri::AppHandle,
+    path: String,
+    strategy: ImportStrategy,
+    force: Option<bool>,
+) -> Result<MetadataImportReport, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionImportMetadata)?;
+    let path = crate::path_access::authorize_str(&app, &path)?;
+    let bundle = read_bundle(&path)?;
+    let active = crate::accounts::active_username(&app);
+    if let (Some(theirs), Some(mine)) = (&bundle.username, &active) {
+        if theirs != mine && !force.unwrap_or(false) {
+            return Err(format!(
+                "The bundle belongs to {}, not {}; import it with force to use it anyway",
+                anonymize::anonymize_username(theirs),
+                anonymize::anonymize_username(mine)
+            ));
+        }
+    }
+
+    let import_app = app.clone();
+    let report = tokio::task::spawn_blocking(move || import(&import_app, &bundle, strategy))
+        .await
+        .map_err(|e| format!("Task error: {}", e))??;
+    Ok(anonymize::outgoing(&app, report))
+}
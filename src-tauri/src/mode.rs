@@ -0,0 +1,136 @@
+// Read-only mode for shared displays.
+//
+// Enforced here in the backend: mutating commands call `ensure_writable`
+// first, so hiding buttons in the UI is a convenience, not the protection.
+
+use crate::anonymize::is_showcase_mode;
+use crate::i18n::{t, Msg};
+use crate::preferences;
+use crate::AppState;
+use serde::Serialize;
+use serde_json::json;
+use tauri::{Emitter, State};
+
+/// Prefix of the error returned by blocked commands, so the frontend can
+/// tell it apart from ordinary failures
+pub const READ_ONLY_ERROR: &str = "ReadOnly";
+
+const READ_ONLY_ENV: &str = "NSG_GUI_READONLY";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadOnlySource {
+    Environment,
+    Preference,
+    // Connected as someone other than the owner of the metadata on screen
+    AccountMismatch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppMode {
+    pub read_only: bool,
+    // What turned read-only mode on; the environment can't be undone from
+    // inside the app
+    pub read_only_source: Option<ReadOnlySource>,
+    pub showcase: bool,
+}
+
+fn env_read_only() -> bool {
+    std::env::var(READ_ONLY_ENV).unwrap_or_default() == "1"
+}
+
+fn pref_read_only(app: &tauri::AppHandle) -> bool {
+    preferences::of(app)
+        .get("read_only")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn read_only_source(app: &tauri::AppHandle) -> Option<ReadOnlySource> {
+    if env_read_only() {
+        Some(ReadOnlySource::Environment)
+    } else if pref_read_only(app) {
+        Some(ReadOnlySource::Preference)
+    } else if crate::accounts::is_mismatched() {
+        Some(ReadOnlySource::AccountMismatch)
+    } else {
+        None
+    }
+}
+
+pub fn is_read_only(app: &tauri::AppHandle) -> bool {
+    read_only_source(app).is_some()
+}
+
+/// Fail with a `ReadOnly` error if mutating operations are disabled
+pub fn ensure_writable(app: &tauri::AppHandle, action: Msg) -> Result<(), String> {
+    if is_read_only(app) {
+        return Err(format!(
+            "{}: {}",
+            READ_ONLY_ERROR,
+            t(Msg::ReadOnlyBlocked, &[("action", &t(action, &[]))])
+        ));
+    }
+    Ok(())
+}
+
+fn app_mode(app: &tauri::AppHandle) -> AppMode {
+    let source = read_only_source(app);
+    AppMode {
+        read_only: source.is_some(),
+        read_only_source: source,
+        showcase: is_showcase_mode(),
+    }
+}
+
+/// Emit `app-mode-changed` after something other than set_read_only changed
+/// the mode
+pub fn changed(app: &tauri::AppHandle) {
+    let _ = app.emit(crate::events::APP_MODE_CHANGED, app_mode(app));
+}
+
+#[tauri::command]
+pub async fn get_app_mode(app: tauri::AppHandle) -> Result<AppMode, String> {
+    Ok(app_mode(&app))
+}
+
+/// Turn read-only mode on or off. Turning it off requires the account
+/// password whenever one is known, from the session or the saved credentials.
+#[tauri::command]
+pub async fn set_read_only(
+    enabled: bool,
+    password: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<AppMode, String> {
+    if !enabled {
+        if env_read_only() {
+            return Err(t(
+                Msg::ReadOnlyFromEnvironment,
+                &[("variable", READ_ONLY_ENV)],
+            ));
+        }
+
+        let stored_password = state.session.credentials().map(|c| c.password).or_else(|| {
+            match crate::keychain::load_saved(&app) {
+                crate::credentials_file::LoadedCredentials::Found { credentials } => {
+                    Some(credentials.password)
+                }
+                _ => None,
+            }
+        });
+        if let Some(stored) = stored_password {
+            if password.as_deref() != Some(stored.as_str()) {
+                return Err(t(Msg::IncorrectPassword, &[]));
+            }
+        }
+    }
+
+    let prefs = preferences::of(&app);
+
+    prefs.set("read_only", json!(enabled));
+
+    let mode = app_mode(&app);
+    let _ = app.emit(crate::events::APP_MODE_CHANGED, mode.clone());
+    Ok(mode)
+}
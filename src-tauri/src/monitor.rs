@@ -0,0 +1,752 @@
+// Background job monitor.
+//
+// Once connected, the job list is refreshed every `poll_interval_secs`, and
+// in adaptive mode jobs submitted in the last few minutes are polled on a
+// faster cycle, since that's when their stages change quickly. Stage changes
+// are emitted as `job-status-changed` events.
+//
+// Most full refreshes find nothing new, so the listing is fetched as a
+// conditional request (ETag, Last-Modified) and compared by hash for a
+// server that ignores those. When it hasn't changed, only the jobs that
+// hadn't finished have their records fetched again, and if none of those
+// moved either, the listing isn't republished at all. The refresh after an
+// action taken in the app, like a submit or a delete, always fetches
+// everything so the action shows up right away.
+
+use crate::activity_feed::{self, Activity, ActivityKind, ActivityStatus};
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, Anonymize};
+use crate::api::{is_network_error, ListingVersion, NsgApi};
+use crate::background_errors::{self, ErrorKind};
+use crate::scheduler::{self, QueueDetails, SchedulerInfo};
+use crate::session::SessionSnapshot;
+use crate::xml::JobExtras;
+use crate::AppState;
+use crate::{clock, preferences};
+use nsg_cli::models::JobSummary as NsgJobSummary;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, State};
+use tokio::sync::Notify;
+
+pub const MIN_POLL_INTERVAL_SECS: u64 = 15;
+pub const MAX_POLL_INTERVAL_SECS: u64 = 3600;
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 60;
+
+// Jobs younger than this are polled every FAST_POLL_INTERVAL in adaptive mode
+const FAST_POLL_WINDOW: chrono::Duration = chrono::Duration::minutes(15);
+const FAST_POLL_INTERVAL: Duration = Duration::from_secs(20);
+
+// How often the loop wakes up to check for due work
+const TICK: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorSettings {
+    pub poll_interval_secs: u64,
+    pub adaptive: bool,
+}
+
+impl Default for MonitorSettings {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+            adaptive: true,
+        }
+    }
+}
+
+impl MonitorSettings {
+    fn validate(&self) -> Result<(), String> {
+        if !(MIN_POLL_INTERVAL_SECS..=MAX_POLL_INTERVAL_SECS).contains(&self.poll_interval_secs) {
+            return Err(format!(
+                "Poll interval must be between {} and {} seconds",
+                MIN_POLL_INTERVAL_SECS, MAX_POLL_INTERVAL_SECS
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatusChange {
+    pub job_id: String,
+    pub url: String,
+    pub previous_stage: Option<String>,
+    pub job_stage: Option<String>,
+    pub failed: bool,
+    pub scheduler_info: Option<SchedulerInfo>,
+    pub queue_note: Option<String>,
+}
+
+/// One sighting of a job, from the list refresh or a single-job poll
+struct Observation<'a> {
+    stage: Option<String>,
+    failed: bool,
+    date_submitted: Option<&'a str>,
+    // None when the job's details couldn't be fetched; keeps what's known
+    queue: Option<QueueDetails>,
+}
+
+struct TrackedJob {
+    job_id: String,
+    stage: Option<String>,
+    failed: bool,
+    queue: QueueDetails,
+    submitted_at: Option<chrono::DateTime<chrono::Utc>>,
+    next_poll: Instant,
+}
+
+impl TrackedJob {
+    fn is_terminal(&self) -> bool {
+        self.failed || self.stage.as_deref() == Some("COMPLETED")
+    }
+
+    fn is_recent(&self) -> bool {
+        self.submitted_at
+            .map(|t| clock::since(t) < FAST_POLL_WINDOW)
+            .unwrap_or(false)
+    }
+}
+
+/// The last full listing, to compare the next one against
+#[derive(Clone)]
+struct LastListing {
+    version: ListingVersion,
+    jobs: Vec<(NsgJobSummary, Option<JobExtras>)>,
+}
+
+// Full refreshes that found nothing changed and published nothing
+static SKIPPED_UNCHANGED: AtomicU64 = AtomicU64::new(0);
+
+pub fn skipped_unchanged() -> u64 {
+    SKIPPED_UNCHANGED.load(Ordering::Relaxed)
+}
+
+#[derive(Default)]
+struct MonitorInner {
+    settings: MonitorSettings,
+    // Keyed by real job URL
+    jobs: HashMap<String, TrackedJob>,
+    // None until the first refresh after connecting
+    next_full_refresh: Option<Instant>,
+    // Whether the last full refresh couldn't reach the server
+    offline: bool,
+    listing: Option<LastListing>,
+    // Set after an action in the app; the next refresh fetches everything
+    expect_change: bool,
+}
+
+pub struct Monitor {
+    inner: Mutex<MonitorInner>,
+    wake: Notify,
+}
+
+pub fn parse_date(date: Option<&str>) -> Option<chrono::DateTime<chrono::Utc>> {
+    date.and_then(clock::parse_server_time)
+}
+
+impl Monitor {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(MonitorInner::default()),
+            wake: Notify::new(),
+        }
+    }
+
+    pub fn settings(&self) -> MonitorSettings {
+        self.inner.lock().unwrap().settings.clone()
+    }
+
+    pub fn set_settings(&self, settings: MonitorSettings) {
+        let mut inner = self.inner.lock().unwrap();
+        // A shorter interval should take effect now, not after the old one
+        let sooner = Instant::now() + Duration::from_secs(settings.poll_interval_secs);
+        if inner.next_full_refresh.is_some_and(|t| t > sooner) {
+            inner.next_full_refresh = Some(sooner);
+        }
+        inner.settings = settings;
+        drop(inner);
+        self.wake.notify_one();
+    }
+
+    /// Poll a just-submitted job right away and keep it on the fast cycle
+    pub fn nudge(&self, job_url: &str, job_id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        let job = inner
+            .jobs
+            .entry(job_url.to_string())
+            .or_insert_with(|| TrackedJob {
+                job_id: job_id.to_string(),
+                stage: None,
+                failed: false,
+                queue: QueueDetails::default(),
+                submitted_at: Some(clock::now()),
+                next_poll: Instant::now(),
+            });
+        job.next_poll = Instant::now();
+        inner.expect_change = true;
+        drop(inner);
+        self.wake.notify_one();
+    }
+
+    /// Make the next full refresh fetch everything, after something done in
+    /// the app changed the job list
+    pub fn expect_change(&self) {
+        self.inner.lock().unwrap().expect_change = true;
+    }
+
+    /// expect_change, with the refresh run now rather than when it's due
+    pub fn refresh_now(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.expect_change = true;
+        inner.next_full_refresh = None;
+        drop(inner);
+        self.wake.notify_one();
+    }
+
+    /// The listing to compare the next refresh against, None when it has
+    /// to fetch everything
+    fn last_listing(&self) -> Option<LastListing> {
+        let mut inner = self.inner.lock().unwrap();
+        if std::mem::take(&mut inner.expect_change) {
+            return None;
+        }
+        inner.listing.clone()
+    }
+
+    fn set_listing(&self, listing: LastListing) {
+        self.inner.lock().unwrap().listing = Some(listing);
+    }
+
+    /// Forget everything, e.g. after the credentials changed. A new session
+    /// gets its first listing right away instead of on the next tick.
+    pub fn reset(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.jobs.clear();
+        inner.next_full_refresh = None;
+        inner.offline = false;
+        inner.listing = None;
+        inner.expect_change = false;
+        drop(inner);
+        self.wake.notify_one();
+    }
+
+    /// Tracked jobs that haven't finished
+    pub fn running_jobs(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner.jobs.values().filter(|j| !j.is_terminal()).count()
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.inner.lock().unwrap().offline
+    }
+
+    /// Record whether the server was reachable, returning true on a change
+    fn set_offline(&self, offline: bool) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        std::mem::replace(&mut inner.offline, offline) != offline
+    }
+
+    /// Record the latest known state of a job, returning the change if its
+    /// stage or queue details moved. The first sighting of a job is not a
+    /// change.
+    fn update(
+        &self,
+        job_url: &str,
+        job_id: &str,
+        observation: Observation,
+    ) -> Option<JobStatusChange> {
+        let Observation {
+            stage,
+            failed,
+            date_submitted,
+            queue,
+        } = observation;
+        let mut inner = self.inner.lock().unwrap();
+        let adaptive = inner.settings.adaptive;
+        let next_full = inner.next_full_refresh;
+
+        let job = inner
+            .jobs
+            .entry(job_url.to_string())
+            .or_insert_with(|| TrackedJob {
+                job_id: job_id.to_string(),
+                stage: stage.clone(),
+                failed,
+                queue: queue.clone().unwrap_or_default(),
+                submitted_at: None,
+                next_poll: Instant::now(),
+            });
+
+        if let Some(submitted) = parse_date(date_submitted) {
+            job.submitted_at = Some(submitted);
+        }
+
+        let queue_changed = queue.as_ref().is_some_and(|q| *q != job.queue);
+        let change = if job.stage != stage || job.failed != failed || queue_changed {
+            let previous_stage = std::mem::replace(&mut job.stage, stage.clone());
+            job.failed = failed;
+            if let Some(queue) = queue {
+                job.queue = queue;
+            }
+            Some(JobStatusChange {
+                job_id: job.job_id.clone(),
+                url: job_url.to_string(),
+                previous_stage,
+                job_stage: stage,
+                failed,
+                scheduler_info: job.queue.scheduler_info.clone(),
+                queue_note: job.queue.queue_note.clone(),
+            })
+        } else {
+            None
+        };
+
+        job.next_poll = if adaptive && job.is_recent() {
+            Instant::now() + FAST_POLL_INTERVAL
+        } else {
+            next_full.unwrap_or_else(Instant::now)
+        };
+
+        change
+    }
+
+    /// Jobs whose individual poll is due: nudged jobs not seen yet, plus (in
+    /// adaptive mode) recent jobs on the fast cycle
+    fn due_fast_polls(&self) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let adaptive = inner.settings.adaptive;
+
+        let now = Instant::now();
+        inner
+            .jobs
+            .iter()
+            .filter(|(_, job)| !job.is_terminal() && job.next_poll <= now)
+            .filter(|(_, job)| job.stage.is_none() || (adaptive && job.is_recent()))
+            .map(|(url, _)| url.clone())
+            .collect()
+    }
+
+    /// Whether a full list refresh is due, scheduling the next one if so
+    fn take_full_refresh(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        if inner.next_full_refresh.is_some_and(|t| t > now) {
+            return false;
+        }
+        inner.next_full_refresh =
+            Some(now + Duration::from_secs(inner.settings.poll_interval_secs));
+        true
+    }
+}
+
+impl Anonymize for JobStatusChange {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            url: anonymize_url(&self.url),
+            ..self
+        }
+    }
+}
+
+fn emit_change(app: &tauri::AppHandle, change: JobStatusChange) {
+    let stage = change
+        .job_stage
+        .as_ref()
+        .filter(|_| !crate::accounts::is_mismatched());
+    if let Some(stage) = stage {
+        if let Err(e) = crate::local_jobs::record_stage(app, &change.job_id, stage) {
+            background_errors::report(
+                app,
+                crate::local_jobs::RECORD_SOURCE,
+                ErrorKind::Other,
+                format!("Failed to record job stage: {}", e),
+            );
+        }
+    }
+    let finished = change
+        .job_stage
+        .as_deref()
+        .is_some_and(|s| s.eq_ignore_ascii_case("COMPLETED"));
+    if stage.is_some() && change.previous_stage != change.job_stage && finished {
+        let activity = if change.failed {
+            Activity::new(
+                ActivityKind::JobStatus,
+                ActivityStatus::Failed,
+                "Job failed",
+            )
+        } else {
+            Activity::new(
+                ActivityKind::JobStatus,
+                ActivityStatus::Succeeded,
+                "Job completed",
+            )
+        };
+        activity_feed::record(app, None, activity.job(&change.job_id));
+        let tool = crate::local_jobs::load(app)
+            .ok()
+            .and_then(|jobs| jobs.get(&change.job_id).and_then(|j| j.tool.clone()));
+        crate::notify::job_finished(app, &change.job_id, tool.as_deref(), change.failed);
+    }
+    anonymize::emit(app, crate::events::JOB_STATUS_CHANGED, change);
+    crate::session_status::changed(app);
+}
+
+/// What a full refresh fetched
+struct Refresh {
+    listing: LastListing,
+    // Jobs whose record was fetched: all of them, unless the listing was
+    // unchanged
+    fetched: Vec<(NsgJobSummary, Option<JobExtras>)>,
+    // Neither the listing nor any record fetched differed from last time
+    unchanged: bool,
+}
+
+/// Whether a job's record can still change; one that couldn't be fetched
+/// is tried again
+fn is_open(job: &NsgJobSummary, extras: &Option<JobExtras>) -> bool {
+    extras.is_none() || !(job.failed || job.job_stage.as_deref() == Some("COMPLETED"))
+}
+
+fn same_record(
+    (a, a_extras): &(NsgJobSummary, Option<JobExtras>),
+    (b, b_extras): &(NsgJobSummary, Option<JobExtras>),
+) -> bool {
+    let extras = match (a_extras, b_extras) {
+        (Some(a), Some(b)) => {
+            a.compute_resource == b.compute_resource
+                && a.tool_version == b.tool_version
+                && a.results_expire_at == b.results_expire_at
+                && scheduler::queue_details(a) == scheduler::queue_details(b)
+        }
+        (None, None) => true,
+        _ => false,
+    };
+    extras
+        && a.job_stage == b.job_stage
+        && a.failed == b.failed
+        && a.tool == b.tool
+        && a.date_submitted == b.date_submitted
+        && a.date_completed == b.date_completed
+}
+
+fn refresh(api: &NsgApi, previous: Option<LastListing>) -> anyhow::Result<Refresh> {
+    let version = previous.as_ref().map(|p| &p.version);
+    if let Some((basic, version)) = api.job_listing(version)? {
+        let jobs = api.job_records(basic);
+        return Ok(Refresh {
+            listing: LastListing {
+                version,
+                jobs: jobs.clone(),
+            },
+            fetched: jobs,
+            unchanged: false,
+        });
+    }
+
+    // Only reported unchanged when there was a previous listing
+    let Some(listing) = previous else {
+        anyhow::bail!("Failed to list jobs: the server sent no listing");
+    };
+    Ok(recheck_open(listing, |jobs| api.job_records(jobs)))
+}
+
+/// Fetch the records of the jobs in an unchanged listing that haven't
+/// finished, with `fetch_records`, and fold them in
+fn recheck_open(
+    mut listing: LastListing,
+    fetch_records: impl FnOnce(Vec<NsgJobSummary>) -> Vec<(NsgJobSummary, Option<JobExtras>)>,
+) -> Refresh {
+    let open: Vec<usize> = (0..listing.jobs.len())
+        .filter(|&i| is_open(&listing.jobs[i].0, &listing.jobs[i].1))
+        .collect();
+    let fetched = fetch_records(open.iter().map(|&i| listing.jobs[i].0.clone()).collect());
+    let mut unchanged = true;
+    for (&i, job) in open.iter().zip(&fetched) {
+        unchanged &= same_record(&listing.jobs[i], job);
+        listing.jobs[i] = job.clone();
+    }
+    Refresh {
+        listing,
+        fetched,
+        unchanged,
+    }
+}
+
+/// One round of polling. Results that arrive after the session changed are
+/// dropped: they belong to the previous account, and the monitor was reset.
+async fn cycle(app: &tauri::AppHandle, session: SessionSnapshot) {
+    let state = app.state::<AppState>();
+    let monitor = &state.monitor;
+    let api = match state.client.api(session.credentials).await {
+        Ok(api) => api,
+        Err(e) => {
+            background_errors::report(app, "job_monitor", ErrorKind::Other, e);
+            return;
+        }
+    };
+
+    if monitor.take_full_refresh() {
+        let previous = monitor.last_listing();
+        let jobs = tokio::task::spawn_blocking(move || refresh(&api, previous)).await;
+        if !state.session.is_current(session.generation) {
+            return;
+        }
+
+        match &jobs {
+            Ok(Ok(_)) => background_errors::resolved(app, "job_monitor"),
+            Ok(Err(e)) => background_errors::report_error(app, "job_monitor", e),
+            Err(e) => background_errors::report(
+                app,
+                "job_monitor",
+                ErrorKind::Other,
+                format!("Task error: {}", e),
+            ),
+        }
+        let offline = matches!(&jobs, Ok(Err(e)) if is_network_error(e));
+        if monitor.set_offline(offline) {
+            let _ = app.emit(
+                crate::events::CONNECTIVITY_CHANGED,
+                json!({ "online": !offline }),
+            );
+            crate::session_status::changed(app);
+        }
+        if !offline {
+            crate::announcements::check(app).await;
+        }
+
+        if let Ok(Ok(refresh)) = jobs {
+            for (job, extras) in &refresh.fetched {
+                let observation = Observation {
+                    stage: job.job_stage.clone(),
+                    failed: job.failed,
+                    date_submitted: job.date_submitted.as_deref(),
+                    queue: extras.as_ref().map(scheduler::queue_details),
+                };
+                if let Some(change) = monitor.update(&job.url, &job.job_id, observation) {
+                    emit_change(app, change);
+                }
+            }
+            if refresh.unchanged {
+                SKIPPED_UNCHANGED.fetch_add(1, Ordering::Relaxed);
+                // Expiry warnings go by the clock, not by what changed
+                if let Some(summaries) = crate::listing::cached(app, session.generation) {
+                    crate::expiry::check(app, session.generation, &summaries);
+                }
+            } else {
+                let summaries = crate::listing::summarize(app, refresh.listing.jobs.clone());
+                crate::expiry::check(app, session.generation, &summaries);
+                crate::listing::publish(app, session.generation, summaries, false);
+            }
+            monitor.set_listing(refresh.listing);
+            crate::session_status::refreshed(app, session.generation);
+        }
+        return;
+    }
+
+    for job_url in monitor.due_fast_polls() {
+        let poll_api = api.clone();
+        let url = job_url.clone();
+        let record = tokio::task::spawn_blocking(move || poll_api.job_record(&url)).await;
+        if !state.session.is_current(session.generation) {
+            return;
+        }
+        let record = match record {
+            Ok(Ok(record)) => {
+                background_errors::resolved(app, "job_poll");
+                Some(record)
+            }
+            Ok(Err(e)) => {
+                background_errors::report_error(app, "job_poll", &e);
+                None
+            }
+            Err(_) => None,
+        };
+        if let Some(record) = record {
+            let status = record.status;
+            let observation = Observation {
+                stage: Some(status.job_stage),
+                failed: status.failed,
+                date_submitted: status.date_submitted.as_deref(),
+                queue: Some(scheduler::queue_details(&record.extras)),
+            };
+            if let Some(change) = monitor.update(&job_url, &status.job_id, observation) {
+                emit_change(app, change);
+            }
+        }
+    }
+}
+
+/// Start the monitor loop. It idles while disconnected.
+pub fn start(app: tauri::AppHandle) {
+    app.state::<AppState>()
+        .monitor
+        .set_settings(load_settings(&app));
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let session = app.state::<AppState>().session.snapshot();
+            if let Some(session) = session {
+                cycle(&app, session).await;
+            }
+
+            let monitor = &app.state::<AppState>().monitor;
+            tokio::select! {
+                _ = tokio::time::sleep(TICK) => {}
+                _ = monitor.wake.notified() => {}
+            }
+        }
+    });
+}
+
+fn load_settings(app: &tauri::AppHandle) -> MonitorSettings {
+    let prefs = preferences::of(app);
+
+    let defaults = MonitorSettings::default();
+    MonitorSettings {
+        poll_interval_secs: prefs
+            .get("poll_interval_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(defaults.poll_interval_secs)
+            .clamp(MIN_POLL_INTERVAL_SECS, MAX_POLL_INTERVAL_SECS),
+        adaptive: prefs
+            .get("adaptive_polling")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(defaults.adaptive),
+    }
+}
+
+#[tauri::command]
+pub async fn get_monitor_settings(state: State<'_, AppState>) -> Result<MonitorSettings, String> {
+    Ok(state.monitor.settings())
+}
+
+#[tauri::command]
+pub async fn set_monitor_settings(
+    settings: MonitorSettings,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, crate::i18n::Msg::ActionChangeMonitorSettings)?;
+    settings.validate()?;
+
+    preferences::of(&app).set_many([
+        ("poll_interval_secs", json!(settings.poll_interval_secs)),
+        ("adaptive_polling", json!(settings.adaptive)),
+    ]);
+
+    state.monitor.set_settings(settings.clone());
+    let _ = app.emit(
+        crate::events::MONITOR_STATE_CHANGED,
+        json!({ "settings": settings }),
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str, stage: &str) -> (NsgJobSummary, Option<JobExtras>) {
+        (
+            NsgJobSummary {
+                job_id: id.to_string(),
+                url: format!("https://example.org/job/alice/{}", id),
+                tool: Some("NEURON".to_string()),
+                job_stage: Some(stage.to_string()),
+                failed: false,
+                date_submitted: None,
+                date_completed: None,
+            },
+            Some(JobExtras::default()),
+        )
+    }
+
+    fn listing(jobs: Vec<(NsgJobSummary, Option<JobExtras>)>) -> LastListing {
+        LastListing {
+            version: ListingVersion {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+                body_hash: [1; 32],
+            },
+            jobs,
+        }
+    }
+
+    #[test]
+    fn finished_jobs_with_a_record_are_closed() {
+        assert!(!is_open(&job("a", "COMPLETED").0, &job("a", "COMPLETED").1));
+        let (mut failed, extras) = job("b", "QUEUE");
+        failed.failed = true;
+        assert!(!is_open(&failed, &extras));
+        assert!(is_open(&job("c", "QUEUE").0, &job("c", "QUEUE").1));
+        // A record that couldn't be fetched is tried again
+        assert!(is_open(&job("d", "COMPLETED").0, &None));
+    }
+
+    #[test]
+    fn records_differ_on_stage_extras_and_queue_details() {
+        let a = job("a", "QUEUE");
+        assert!(same_record(&a, &a.clone()));
+        assert!(!same_record(&a, &job("a", "RUNNING")));
+        assert!(!same_record(&a, &(a.0.clone(), None)));
+
+        let mut moved = a.clone();
+        moved
+            .1
+            .as_mut()
+            .unwrap()
+            .messages
+            .push(crate::xml::JobMessage {
+                stage: Some("QUEUE".to_string()),
+                text: "Submitted to Expanse".to_string(),
+                ..Default::default()
+            });
+        assert!(!same_record(&a, &moved));
+    }
+
+    #[test]
+    fn only_open_jobs_are_fetched_again() {
+        let previous = listing(vec![job("done", "COMPLETED"), job("waiting", "QUEUE")]);
+        let refresh = recheck_open(previous, |jobs| {
+            let ids: Vec<&str> = jobs.iter().map(|j| j.job_id.as_str()).collect();
+            assert_eq!(ids, ["waiting"]);
+            vec![job("waiting", "QUEUE")]
+        });
+        assert!(refresh.unchanged);
+        assert_eq!(refresh.fetched.len(), 1);
+        assert_eq!(refresh.listing.jobs.len(), 2);
+    }
+
+    #[test]
+    fn an_open_job_that_moved_is_a_change() {
+        let previous = listing(vec![job("done", "COMPLETED"), job("waiting", "QUEUE")]);
+        let refresh = recheck_open(previous, |_| vec![job("waiting", "RUNNING")]);
+        assert!(!refresh.unchanged);
+        assert_eq!(
+            refresh.listing.jobs[1].0.job_stage.as_deref(),
+            Some("RUNNING")
+        );
+        assert_eq!(refresh.listing.version.etag.as_deref(), Some("\"v1\""));
+    }
+
+    #[test]
+    fn an_action_in_the_app_forces_a_full_listing_once() {
+        let monitor = Monitor::new();
+        assert!(monitor.last_listing().is_none());
+        monitor.set_listing(listing(vec![job("a", "QUEUE")]));
+        assert!(monitor.last_listing().is_some());
+
+        monitor.expect_change();
+        assert!(monitor.last_listing().is_none());
+        assert!(monitor.last_listing().is_some());
+
+        monitor.reset();
+        assert!(monitor.last_listing().is_none());
+    }
+}
@@ -0,0 +1,388 @@
+// Download and job notifications.
+//
+// `download-complete` only reaches a window someone is looking at. When the
+// main window is unfocused, minimized or hidden as a download finishes, a
+// `download-notification` event carries the text of a desktop notification
+// for the frontend to show; while the window has focus nothing extra is
+// sent. Clicking the notification calls `download_notification_clicked`,
+// which brings the window back and, per `notification_click_action`,
+// either emits `reveal-download` or opens the folder holding the zip.
+//
+// A job the monitor sees finish gets an OS notification from the backend
+// itself, so it shows even while the webview is suspended in the
+// background. It goes through tauri-plugin-notification and is skipped,
+// like the download one, while the window is in use. Both follow
+// `notifications_enabled`, and neither is shown during the quiet hours in
+// `notification_quiet_hours`, a local-time window that may run past
+// midnight; the download-complete event still reaches the app then.
+
+use crate::anonymize::{self, anonymize_job_id, Anonymize};
+use crate::history;
+use crate::i18n::{t, Msg};
+use crate::preferences;
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use tauri::{Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+pub const ENABLED_KEY: &str = "notifications_enabled";
+pub const CLICK_ACTION_KEY: &str = "notification_click_action";
+pub const CLICK_ACTIONS: &[&str] = &["reveal_in_app", "open_folder"];
+pub const QUIET_HOURS_KEY: &str = "notification_quiet_hours";
+
+const MAIN_WINDOW: &str = "main";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadNotification {
+    pub history_id: String,
+    pub job_id: String,
+    pub title: String,
+    pub body: String,
+}
+
+impl Anonymize for DownloadNotification {
+    fn anonymize(self) -> Self {
+        let job_id = anonymize_job_id(&self.job_id);
+        Self {
+            body: self.body.replace(&self.job_id, &job_id),
+            job_id,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    // One of CLICK_ACTIONS
+    pub click_action: String,
+    // None notifies at any time of day
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// When no OS notification is shown, in local time as "HH:MM". A start
+/// after the end spans midnight; the end itself is outside.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()
+}
+
+impl QuietHours {
+    fn bounds(&self) -> Option<(NaiveTime, NaiveTime)> {
+        Some((parse_time(&self.start)?, parse_time(&self.end)?))
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        let Some((start, end)) = self.bounds() else {
+            return false;
+        };
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RevealDownload {
+    history_id: String,
+}
+
+pub fn is_enabled(app: &tauri::AppHandle) -> bool {
+    preferences::of(app)
+        .get(ENABLED_KEY)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
+
+fn quiet_hours(app: &tauri::AppHandle) -> Option<QuietHours> {
+    preferences::of(app)
+        .get(QUIET_HOURS_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+}
+
+fn in_quiet_hours(app: &tauri::AppHandle) -> bool {
+    let now = crate::clock::now().with_timezone(&chrono::Local).time();
+    quiet_hours(app).is_some_and(|quiet| quiet.contains(now))
+}
+
+/// Whether a finished download or job gets a notification now
+fn should_notify(app: &tauri::AppHandle) -> bool {
+    is_enabled(app) && !window_attended(app) && !in_quiet_hours(app)
+}
+
+fn click_action(app: &tauri::AppHandle) -> String {
+    preferences::of(app)
+        .get(CLICK_ACTION_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| CLICK_ACTIONS[0].to_string())
+}
+
+/// Whether someone is looking at the main window right now
+fn window_attended(app: &tauri::AppHandle) -> bool {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return false;
+    };
+    window.is_visible().unwrap_or(true)
+        && !window.is_minimized().unwrap_or(false)
+        && window.is_focused().unwrap_or(true)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Send a notification for a finished download, unless the window is in
+/// use, notifications are off or it's quiet hours. `job_name` is the job's
+/// client name, if it was given one.
+pub fn download_complete(
+    app: &tauri::AppHandle,
+    history_id: &str,
+    job_id: &str,
+    job_name: Option<&str>,
+    archive_bytes: u64,
+) {
+    if !should_notify(app) {
+        return;
+    }
+
+    anonymize::emit(
+        app,
+        crate::events::DOWNLOAD_NOTIFICATION,
+        download_notification(history_id, job_id, job_name, archive_bytes),
+    );
+}
+
+fn download_notification(
+    history_id: &str,
+    job_id: &str,
+    job_name: Option<&str>,
+    archive_bytes: u64,
+) -> DownloadNotification {
+    let job = match job_name.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(name) => t(Msg::NotifyNamedJob, &[("name", name), ("job_id", job_id)]),
+        None => job_id.to_string(),
+    };
+    DownloadNotification {
+        history_id: history_id.to_string(),
+        job_id: job_id.to_string(),
+        title: t(Msg::NotifyDownloadComplete, &[]),
+        body: t(
+            Msg::NotifyDownloadBody,
+            &[("job", &job), ("size", &format_size(archive_bytes))],
+        ),
+    }
+}
+
+/// Show an OS notification. Best effort: a refused notification is only
+/// lost.
+fn show_native(app: &tauri::AppHandle, title: &str, body: &str) {
+    let _ = app.notification().builder().title(title).body(body).show();
+}
+
+/// Notify that a job finished, unless the window is in use, notifications
+/// are off or it's quiet hours
+pub fn job_finished(app: &tauri::AppHandle, job_id: &str, tool: Option<&str>, failed: bool) {
+    if !should_notify(app) {
+        return;
+    }
+    let (title, body) = job_finished_text(&anonymize_job_id(job_id), tool, failed);
+    show_native(app, &title, &body);
+}
+
+/// Title and body of a finished job's notification
+fn job_finished_text(job_id: &str, tool: Option<&str>, failed: bool) -> (String, String) {
+    let title = if failed {
+        t(Msg::NotifyJobFailed, &[])
+    } else {
+        t(Msg::NotifyJobCompleted, &[])
+    };
+    let body = match tool {
+        Some(tool) => t(
+            Msg::NotifyJobWithTool,
+            &[("job_id", job_id), ("tool", tool)],
+        ),
+        None => job_id.to_string(),
+    };
+    (title, body)
+}
+
+/// Open the system file manager on the folder holding `path`, selecting
+/// the file where the platform allows it
+pub fn open_folder(path: &Path) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = std::process::Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    };
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = std::process::Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = {
+        let mut command = std::process::Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(path));
+        command
+    };
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to open the file manager: {}", e))?;
+    // Reap it once it exits so it doesn't linger as a zombie
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Handle a click on a download notification
+#[tauri::command]
+pub async fn download_notification_clicked(
+    history_id: String,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+
+    if click_action(&app) == "open_folder" {
+        let entry = history::load(&app)?
+            .into_iter()
+            .find(|e| e.id == history_id)
+            .ok_or_else(|| format!("No download history entry {}", history_id))?;
+        return open_folder(Path::new(&entry.archive_path));
+    }
+
+    let _ = app.emit(
+        crate::events::REVEAL_DOWNLOAD,
+        RevealDownload { history_id },
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_notification_settings(
+    app: tauri::AppHandle,
+) -> Result<NotificationSettings, String> {
+    Ok(NotificationSettings {
+        enabled: is_enabled(&app),
+        click_action: click_action(&app),
+        quiet_hours: quiet_hours(&app),
+    })
+}
+
+#[tauri::command]
+pub async fn set_notification_settings(
+    app: tauri::AppHandle,
+    settings: NotificationSettings,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeNotificationSettings)?;
+    if !CLICK_ACTIONS.contains(&settings.click_action.as_str()) {
+        return Err(format!(
+            "Unknown notification click action {} (expected one of {})",
+            settings.click_action,
+            CLICK_ACTIONS.join(", ")
+        ));
+    }
+    if let Some(quiet) = &settings.quiet_hours {
+        if quiet.bounds().is_none() {
+            return Err(format!(
+                "Quiet hours need times as HH:MM, not {} to {}",
+                quiet.start, quiet.end
+            ));
+        }
+    }
+
+    preferences::of(&app).set_many([
+        (ENABLED_KEY, json!(settings.enabled)),
+        (CLICK_ACTION_KEY, json!(settings.click_action)),
+        (QUIET_HOURS_KEY, json!(settings.quiet_hours)),
+    ]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet(start: &str, end: &str) -> QuietHours {
+        QuietHours {
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        parse_time(time).unwrap()
+    }
+
+    #[test]
+    fn daytime_window() {
+        let hours = quiet("12:00", "14:30");
+        assert!(hours.contains(at("12:00")));
+        assert!(hours.contains(at("14:29")));
+        assert!(!hours.contains(at("14:30")));
+        assert!(!hours.contains(at("11:59")));
+    }
+
+    #[test]
+    fn window_past_midnight() {
+        let hours = quiet("22:00", "07:00");
+        assert!(hours.contains(at("23:15")));
+        assert!(hours.contains(at("00:00")));
+        assert!(hours.contains(at("06:59")));
+        assert!(!hours.contains(at("07:00")));
+        assert!(!hours.contains(at("21:59")));
+    }
+
+    #[test]
+    fn notification_texts_name_the_job() {
+        let named = download_notification("h1", "NGBW-JOB-1", Some(" relax "), 2048);
+        assert_eq!(named.title, "Download complete");
+        assert_eq!(named.body, "relax (NGBW-JOB-1): 2.0 KB");
+        let unnamed = download_notification("h1", "NGBW-JOB-1", Some(""), 10);
+        assert_eq!(unnamed.body, "NGBW-JOB-1: 10 B");
+
+        assert_eq!(
+            job_finished_text("NGBW-JOB-1", Some("PY_EXPANSE"), true),
+            (
+                "Job failed".to_string(),
+                "NGBW-JOB-1 (PY_EXPANSE)".to_string()
+            )
+        );
+        assert_eq!(
+            job_finished_text("NGBW-JOB-1", None, false).0,
+            "Job completed"
+        );
+    }
+
+    #[test]
+    fn unreadable_or_empty_windows_are_never_quiet() {
+        assert!(!quiet("late", "07:00").contains(at("03:00")));
+        assert!(!quiet("25:00", "07:00").contains(at("03:00")));
+        assert!(!quiet("09:00", "09:00").contains(at("09:00")));
+    }
+}
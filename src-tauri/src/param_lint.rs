@@ -0,0 +1,439 @@
+// Linting the resource requests of a submission.
+//
+// NSG takes the runtime, node count and cores per node as vparams whose
+// names vary a little between tools (`vparam.runtime_`,
+// `vparam.number_nodes_`, ...). Each rule in RULES looks at those values
+// and may report a finding; the limits they compare against live in the
+// TOOL_LIMITS and RESOURCES tables, and the user's own history comes from
+// the stages the app saw in local_jobs.json. Findings are advice:
+// dry_run_submit lists them and submit_job returns them with the job. Only
+// a value that isn't a positive number at all fails the submission.
+
+use crate::i18n::{LocalizedMessage, Msg};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+const RUNTIME_NAMES: &[&str] = &["runtime", "walltime", "run_time", "wall_time"];
+const NODE_NAMES: &[&str] = &["number_nodes", "num_nodes", "nodes"];
+const CORE_NAMES: &[&str] = &[
+    "number_cores",
+    "cores_per_node",
+    "tasks_per_node",
+    "ntasks_per_node",
+    "ppn",
+];
+
+// Fewer finished runs than this say nothing about the usual runtime
+const MIN_HISTORY_RUNS: usize = 5;
+// How far above the historical p90 a runtime request is worth a note
+const HISTORY_FACTOR: f64 = 4.0;
+
+struct ToolLimits {
+    tool: &'static str,
+    max_runtime_hours: Option<f64>,
+    single_node: bool,
+}
+
+// From the NSG tool pages; tools not listed aren't checked against limits
+const TOOL_LIMITS: &[ToolLimits] = &[
+    ToolLimits {
+        tool: "NEURON_EXPANSE",
+        max_runtime_hours: Some(48.0),
+        single_node: false,
+    },
+    ToolLimits {
+        tool: "PY_EXPANSE",
+        max_runtime_hours: Some(48.0),
+        single_node: false,
+    },
+    ToolLimits {
+        tool: "MATLAB_EXPANSE",
+        max_runtime_hours: Some(48.0),
+        single_node: true,
+    },
+];
+
+// Tool ID suffix -> resource name and cores per node
+const RESOURCES: &[(&str, &str, u32)] = &[
+    ("_EXPANSE", "Expanse", 128),
+    ("_ANVIL", "Anvil", 128),
+    ("_BRIDGES2", "Bridges-2", 128),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintSeverity {
+    Info,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LintFinding {
+    pub rule: &'static str,
+    pub severity: LintSeverity,
+    // The form field the finding is about, e.g. "vparam.runtime_"
+    pub parameter: String,
+    // key, params and the localized message
+    #[serde(flatten)]
+    pub message: LocalizedMessage,
+}
+
+/// The resource requests of a submission, as the rules see them
+struct Request<'a> {
+    tool: &'a str,
+    limits: Option<&'static ToolLimits>,
+    // (parameter, value)
+    runtime_hours: Option<(&'a str, f64)>,
+    nodes: Option<(&'a str, f64)>,
+    cores_per_node: Option<(&'a str, f64)>,
+    // 90th percentile of the tool's past runtimes on this machine
+    history_p90_hours: Option<f64>,
+}
+
+struct Rule {
+    id: &'static str,
+    check: fn(&Request) -> Option<(LintSeverity, String, LocalizedMessage)>,
+}
+
+fn number(value: f64) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    rounded.to_string()
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        id: "runtime_above_tool_max",
+        check: |r| {
+            let (parameter, hours) = r.runtime_hours?;
+            let max = r.limits?.max_runtime_hours?;
+            (hours > max).then(|| {
+                (
+                    LintSeverity::Warning,
+                    parameter.to_string(),
+                    LocalizedMessage::new(
+                        Msg::LintRuntimeAboveMax,
+                        &[
+                            ("parameter", parameter),
+                            ("hours", &number(hours)),
+                            ("max", &number(max)),
+                            ("tool", r.tool),
+                        ],
+                    ),
+                )
+            })
+        },
+    },
+    Rule {
+        id: "nodes_on_single_node_tool",
+        check: |r| {
+            let (parameter, nodes) = r.nodes?;
+            (r.limits?.single_node && nodes > 1.0).then(|| {
+                (
+                    LintSeverity::Warning,
+                    parameter.to_string(),
+                    LocalizedMessage::new(
+                        Msg::LintMultiNodeSingleNodeTool,
+                        &[
+                            ("parameter", parameter),
+                            ("nodes", &number(nodes)),
+                            ("tool", r.tool),
+                        ],
+                    ),
+                )
+            })
+        },
+    },
+    Rule {
+        id: "cores_above_resource",
+        check: |r| {
+            let (parameter, cores) = r.cores_per_node?;
+            let (_, resource, max) = RESOURCES
+                .iter()
+                .find(|(suffix, _, _)| r.tool.to_uppercase().ends_with(suffix))?;
+            (cores > f64::from(*max)).then(|| {
+                (
+                    LintSeverity::Warning,
+                    parameter.to_string(),
+                    LocalizedMessage::new(
+                        Msg::LintCoresAboveResource,
+                        &[
+                            ("parameter", parameter),
+                            ("cores", &number(cores)),
+                            ("resource", resource),
+                            ("max", &max.to_string()),
+                        ],
+                    ),
+                )
+            })
+        },
+    },
+    Rule {
+        id: "runtime_above_history",
+        check: |r| {
+            let (parameter, hours) = r.runtime_hours?;
+            let p90 = r.history_p90_hours?;
+            (hours > p90 * HISTORY_FACTOR && hours - p90 >= 1.0).then(|| {
+                (
+                    LintSeverity::Info,
+                    parameter.to_string(),
+                    LocalizedMessage::new(
+                        Msg::LintRuntimeAboveHistory,
+                        &[
+                            ("parameter", parameter),
+                            ("hours", &number(hours)),
+                            ("p90", &number(p90)),
+                            ("tool", r.tool),
+                        ],
+                    ),
+                )
+            })
+        },
+    },
+];
+
+/// Normalized vparam name: "vparam.number_nodes_" -> "number_nodes"
+fn base_name(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_prefix("vparam.").unwrap_or(&name);
+    name.trim_end_matches('_').to_string()
+}
+
+fn positive(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n > 0.0)
+}
+
+/// Hours, written as a number or as H:MM[:SS]
+fn hours(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if !value.contains(':') {
+        return positive(value);
+    }
+    let mut total = 0.0;
+    for (part, scale) in value.split(':').zip([1.0, 60.0, 3600.0]) {
+        total += part.parse::<u32>().ok()? as f64 / scale;
+    }
+    (value.split(':').count() <= 3 && total > 0.0).then_some(total)
+}
+
+/// The first parameter named like one of `names`, parsed. A value that
+/// doesn't parse is an error.
+fn find<'a>(
+    params: &'a BTreeMap<String, String>,
+    names: &[&str],
+    parse: fn(&str) -> Option<f64>,
+) -> Result<Option<(&'a str, f64)>, String> {
+    let Some((name, value)) = params
+        .iter()
+        .find(|(name, _)| names.contains(&base_name(name).as_str()))
+    else {
+        return Ok(None);
+    };
+    match parse(value) {
+        Some(n) => Ok(Some((name.as_str(), n))),
+        None => Err(crate::i18n::t(
+            Msg::LintInvalidNumber,
+            &[("parameter", name), ("value", value)],
+        )),
+    }
+}
+
+/// 90th percentile of how long finished jobs of `tool` spent in SUBMITTED,
+/// from the stages seen while polling
+fn history_p90_hours(app: &tauri::AppHandle, tool: &str) -> Option<f64> {
+    let jobs = crate::local_jobs::load(app).ok()?;
+    let hours: Vec<f64> = jobs
+        .values()
+        .filter(|job| job.tool.as_deref() == Some(tool))
+        .filter_map(|job| {
+            let i = job.stages.iter().position(|s| s.stage == "SUBMITTED")?;
+            let start = crate::monitor::parse_date(Some(&job.stages[i].seen_at))?;
+            let end = crate::monitor::parse_date(Some(&job.stages.get(i + 1)?.seen_at))?;
+            Some(crate::clock::between(start, end).num_seconds() as f64 / 3600.0)
+        })
+        .collect();
+    p90(hours)
+}
+
+/// 90th percentile of `hours`, None with fewer than MIN_HISTORY_RUNS
+fn p90(mut hours: Vec<f64>) -> Option<f64> {
+    if hours.len() < MIN_HISTORY_RUNS {
+        return None;
+    }
+    hours.sort_by(|a, b| a.total_cmp(b));
+    let index = ((hours.len() as f64) * 0.9).ceil() as usize - 1;
+    hours.get(index.min(hours.len() - 1)).copied()
+}
+
+/// The resource requests in a submission's parameters, without history
+fn request<'a>(tool: &'a str, params: &'a BTreeMap<String, String>) -> Result<Request<'a>, String> {
+    Ok(Request {
+        tool,
+        limits: TOOL_LIMITS
+            .iter()
+            .find(|l| l.tool.eq_ignore_ascii_case(tool)),
+        runtime_hours: find(params, RUNTIME_NAMES, hours)?,
+        nodes: find(params, NODE_NAMES, positive)?,
+        cores_per_node: find(params, CORE_NAMES, positive)?,
+        history_p90_hours: None,
+    })
+}
+
+fn run(request: &Request) -> Vec<LintFinding> {
+    RULES
+        .iter()
+        .filter_map(|rule| {
+            let (severity, parameter, message) = (rule.check)(request)?;
+            Some(LintFinding {
+                rule: rule.id,
+                severity,
+                parameter,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Run every rule over a submission's parameters. Errors only for values
+/// that aren't positive numbers.
+pub fn lint(
+    app: &tauri::AppHandle,
+    tool: &str,
+    params: &BTreeMap<String, String>,
+) -> Result<Vec<LintFinding>, String> {
+    let mut request = request(tool, params)?;
+    request.history_p90_hours = history_p90_hours(app, tool);
+    Ok(run(&request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn rules_hit(tool: &str, pairs: &[(&str, &str)], p90: Option<f64>) -> Vec<&'static str> {
+        let params = params(pairs);
+        let mut request = request(tool, &params).unwrap();
+        request.history_p90_hours = p90;
+        run(&request).into_iter().map(|f| f.rule).collect()
+    }
+
+    #[test]
+    fn a_runtime_over_the_tool_maximum_is_a_warning() {
+        let params = params(&[("vparam.runtime_", "72")]);
+        let findings = run(&request("NEURON_EXPANSE", &params).unwrap());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, "runtime_above_tool_max");
+        assert_eq!(findings[0].severity, LintSeverity::Warning);
+        assert_eq!(findings[0].parameter, "vparam.runtime_");
+        assert_eq!(findings[0].message.params["max"], "48");
+
+        assert!(rules_hit("NEURON_EXPANSE", &[("vparam.runtime_", "48")], None).is_empty());
+        // Tools without known limits aren't checked against them
+        assert!(rules_hit("SOMETHING_ELSE", &[("vparam.runtime_", "500")], None).is_empty());
+    }
+
+    #[test]
+    fn several_nodes_on_a_single_node_tool_are_a_warning() {
+        assert_eq!(
+            rules_hit("MATLAB_EXPANSE", &[("vparam.number_nodes_", "2")], None),
+            ["nodes_on_single_node_tool"]
+        );
+        assert!(rules_hit("MATLAB_EXPANSE", &[("vparam.number_nodes_", "1")], None).is_empty());
+        assert!(rules_hit("NEURON_EXPANSE", &[("vparam.number_nodes_", "4")], None).is_empty());
+    }
+
+    #[test]
+    fn cores_beyond_the_resource_are_a_warning() {
+        assert_eq!(
+            rules_hit("py_anvil", &[("vparam.ppn_", "256")], None),
+            ["cores_above_resource"]
+        );
+        assert!(rules_hit("PY_ANVIL", &[("vparam.ppn_", "128")], None).is_empty());
+    }
+
+    #[test]
+    fn a_runtime_far_above_history_is_a_note() {
+        let hit = rules_hit("SOMETHING_ELSE", &[("vparam.runtime_", "10")], Some(2.0));
+        assert_eq!(hit, ["runtime_above_history"]);
+        let params = params(&[("vparam.runtime_", "10")]);
+        let mut request = request("SOMETHING_ELSE", &params).unwrap();
+        request.history_p90_hours = Some(2.0);
+        assert_eq!(run(&request)[0].severity, LintSeverity::Info);
+
+        assert!(rules_hit("SOMETHING_ELSE", &[("vparam.runtime_", "6")], Some(2.0)).is_empty());
+        // Tiny runtimes aren't worth a note even when far above the p90
+        assert!(rules_hit("SOMETHING_ELSE", &[("vparam.runtime_", "0.5")], Some(0.1)).is_empty());
+        assert!(rules_hit("SOMETHING_ELSE", &[("vparam.runtime_", "10")], None).is_empty());
+    }
+
+    #[test]
+    fn a_value_that_is_not_a_positive_number_fails() {
+        for value in ["abc", "-2", "0", "NaN", "inf"] {
+            let params = params(&[("vparam.number_nodes_", value)]);
+            assert!(request("NEURON_EXPANSE", &params).is_err(), "{}", value);
+        }
+        let params = params(&[("vparam.runtime_", "1:xx")]);
+        assert!(request("NEURON_EXPANSE", &params).is_err());
+    }
+
+    #[test]
+    fn runtimes_are_read_as_hours_or_clock_time() {
+        assert_eq!(hours("1.5"), Some(1.5));
+        assert_eq!(hours("01:30"), Some(1.5));
+        assert_eq!(hours("0:30:36"), Some(0.51));
+        assert_eq!(hours("0:00"), None);
+        assert_eq!(hours("1:2:3:4"), None);
+        assert_eq!(hours("-1"), None);
+    }
+
+    #[test]
+    fn parameter_names_are_matched_loosely() {
+        assert_eq!(base_name("vparam.number_nodes_"), "number_nodes");
+        assert_eq!(base_name(" VPARAM.RunTime_ "), "runtime");
+        assert_eq!(base_name("walltime"), "walltime");
+
+        let params = params(&[("vparam.Wall_Time_", "2"), ("vparam.ppn_", "16")]);
+        let request = request("PY_EXPANSE", &params).unwrap();
+        assert_eq!(request.runtime_hours, Some(("vparam.Wall_Time_", 2.0)));
+        assert_eq!(request.cores_per_node, Some(("vparam.ppn_", 16.0)));
+        assert_eq!(request.nodes, None);
+    }
+
+    #[test]
+    fn the_history_needs_enough_runs() {
+        assert_eq!(p90(vec![1.0; MIN_HISTORY_RUNS - 1]), None);
+        let runs: Vec<f64> = (1..=10).rev().map(f64::from).collect();
+        assert_eq!(p90(runs), Some(9.0));
+        assert_eq!(p90(vec![2.0, 1.0, 5.0, 3.0, 4.0]), Some(5.0));
+    }
+
+    #[test]
+    fn findings_carry_their_message_key_and_parameters() {
+        let params = params(&[("vparam.number_nodes_", "3")]);
+        let findings = run(&request("MATLAB_EXPANSE", &params).unwrap());
+        let value = serde_json::to_value(&findings[0]).unwrap();
+        assert_eq!(value["rule"], "nodes_on_single_node_tool");
+        assert_eq!(value["severity"], "warning");
+        assert_eq!(value["key"], "lint.nodes_on_single_node_tool");
+        assert_eq!(value["params"]["nodes"], "3");
+        assert_eq!(value["params"]["tool"], "MATLAB_EXPANSE");
+    }
+
+    #[test]
+    fn an_invalid_value_names_the_parameter() {
+        let params = params(&[("vparam.runtime_", "soon")]);
+        let error = request("PY_EXPANSE", &params).err().unwrap();
+        assert!(error.contains("vparam.runtime_"), "{}", error);
+        assert!(error.contains("soon"), "{}", error);
+    }
+}
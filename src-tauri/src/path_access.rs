@@ -0,0 +1,294 @@
+// Which paths the frontend may use.
+//
+// Every command that reads or writes a path handed over from the webview
+// runs it through `authorize` first. The path is resolved the way the OS
+// would use it, symlinks and `..` included, and must then lie inside one of
+// the allowed roots: the home folder, the download folder, the staging
+// folders, the folders downloads were remembered to or are sent to by a
+// download rule, or something the user picked in a file dialog this session.
+// Dialogs are opened through pick_path rather than the dialog plugin's own
+// JavaScript API so that the backend sees the result directly; a path the
+// webview merely claims was picked doesn't count. A path that doesn't exist
+// yet (an export target, a download folder to be created) is resolved up to
+// its closest existing ancestor, and the rest may only name plain
+// components. Relative paths, drive-relative Windows paths like `C:foo`,
+// device paths like `\\.\PhysicalDrive0` and `..` in a verbatim `\\?\`
+// path are refused outright; UNC shares are allowed when picked.
+
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::sync::Mutex;
+use tauri_plugin_dialog::DialogExt;
+
+// Older picks are forgotten first
+const MAX_PICKED: usize = 200;
+
+// Resolved paths picked in a dialog this session
+static PICKED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// A path the frontend isn't allowed to use
+#[derive(Debug, Clone)]
+pub struct PathNotAllowed {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl std::fmt::Display for PathNotAllowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Path not allowed: {} ({})",
+            self.path.display(),
+            self.reason
+        )
+    }
+}
+
+impl From<PathNotAllowed> for String {
+    fn from(e: PathNotAllowed) -> Self {
+        e.to_string()
+    }
+}
+
+/// Resolve `path` to the one the OS would actually use. The part that
+/// exists is canonicalized; anything after it must be plain names.
+fn resolve(path: &Path) -> Result<PathBuf, String> {
+    if path.as_os_str().is_empty() {
+        return Err("the path is empty".to_string());
+    }
+    if let Some(Component::Prefix(prefix)) = path.components().next() {
+        if matches!(prefix.kind(), Prefix::DeviceNS(_)) {
+            return Err("device paths aren't accepted".to_string());
+        }
+        // Windows takes these as written, so `..` wouldn't mean what
+        // canonicalize makes of it
+        if prefix.kind().is_verbatim()
+            && path
+                .components()
+                .any(|c| matches!(c, Component::ParentDir | Component::CurDir))
+        {
+            return Err("`\\\\?\\` paths may not contain `.` or `..`".to_string());
+        }
+    }
+    if !path.is_absolute() {
+        return Err("relative paths aren't accepted".to_string());
+    }
+
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or("no part of the path exists")?;
+    let rest = path
+        .strip_prefix(existing)
+        .map_err(|_| "the path can't be resolved".to_string())?;
+    if !rest.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err("the part that doesn't exist yet may not contain `..`".to_string());
+    }
+    let existing =
+        std::fs::canonicalize(existing).map_err(|e| format!("it can't be resolved: {}", e))?;
+    Ok(existing.join(rest))
+}
+
+fn roots(app: &tauri::AppHandle) -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    roots.extend(crate::paths::home_dir());
+    roots.extend(crate::default_download_dir(app).ok());
+    roots.extend(crate::staging::roots(app));
+    roots.extend(crate::rules::persisted_dirs(app));
+    allowed_roots(roots)
+}
+
+// The roots that resolve, as resolved
+fn allowed_roots(roots: Vec<PathBuf>) -> Vec<PathBuf> {
+    roots.into_iter().filter_map(|r| resolve(&r).ok()).collect()
+}
+
+fn was_picked(resolved: &Path) -> bool {
+    PICKED
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|picked| resolved == picked || (picked.is_dir() && resolved.starts_with(picked)))
+}
+
+/// Check that the frontend may use `path`, returning it resolved
+pub fn authorize(app: &tauri::AppHandle, path: &Path) -> Result<PathBuf, PathNotAllowed> {
+    let refuse = |reason: String| PathNotAllowed {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let resolved = resolve(path).map_err(refuse)?;
+    if was_picked(&resolved) || roots(app).iter().any(|root| resolved.starts_with(root)) {
+        return Ok(resolved);
+    }
+    Err(refuse(
+        "it is outside the home, download, staging, remembered and rule folders and wasn't \
+         picked in a dialog"
+            .to_string(),
+    ))
+}
+
+/// `authorize` for a path given as a string
+pub fn authorize_str(app: &tauri::AppHandle, path: &str) -> Result<PathBuf, PathNotAllowed> {
+    authorize(app, Path::new(path))
+}
+
+/// Allow `path`, and everything inside it if it is a folder, for the rest
+/// of the session
+pub fn register_picked_path(path: &Path) -> Result<(), String> {
+    let resolved =
+        resolve(path).map_err(|reason| format!("Can't use {}: {}", path.display(), reason))?;
+    let mut picked = PICKED.lock().unwrap();
+    picked.retain(|p| *p != resolved);
+    picked.push(resolved);
+    let excess = picked.len().saturating_sub(MAX_PICKED);
+    picked.drain(..excess);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PickKind {
+    File,
+    Folder,
+    // A file to write; it needn't exist
+    Save,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PickFilter {
+    pub name: String,
+    pub extensions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PickOptions {
+    pub title: Option<String>,
+    pub default_path: Option<String>,
+    // Suggested name for `save`
+    pub file_name: Option<String>,
+    #[serde(default)]
+    pub filters: Vec<PickFilter>,
+}
+
+/// Show a file dialog and allow whatever is picked. None when cancelled.
+#[tauri::command]
+pub async fn pick_path(
+    app: tauri::AppHandle,
+    kind: PickKind,
+    options: Option<PickOptions>,
+) -> Result<Option<String>, String> {
+    let options = options.unwrap_or_default();
+    let mut dialog = app.dialog().file();
+    if let Some(title) = options.title {
+        dialog = dialog.set_title(title);
+    }
+    if let Some(dir) = options.default_path {
+        dialog = dialog.set_directory(dir);
+    }
+    if let Some(name) = options.file_name {
+        dialog = dialog.set_file_name(name);
+    }
+    for filter in &options.filters {
+        let extensions: Vec<&str> = filter.extensions.iter().map(|e| e.as_str()).collect();
+        dialog = dialog.add_filter(filter.name.clone(), &extensions);
+    }
+
+    let picked = tokio::task::spawn_blocking(move || match kind {
+        PickKind::File => dialog.blocking_pick_file(),
+        PickKind::Folder => dialog.blocking_pick_folder(),
+        PickKind::Save => dialog.blocking_save_file(),
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?;
+
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| format!("Failed to read the picked path: {}", e))?;
+    register_picked_path(&path)?;
+    crate::paths::to_ipc_string(&path).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Canonical, as resolve returns paths
+    fn scratch(name: &str) -> PathBuf {
+        std::fs::canonicalize(crate::test_util::scratch("path-access", name)).unwrap()
+    }
+
+    #[test]
+    fn relative_and_empty_paths_are_refused() {
+        assert!(resolve(Path::new("")).is_err());
+        assert!(resolve(Path::new("relative/path")).is_err());
+    }
+
+    #[test]
+    fn a_missing_tail_may_only_name_plain_components() {
+        let dir = scratch("tail");
+        assert_eq!(
+            resolve(&dir.join("new/file.txt")).unwrap(),
+            dir.join("new/file.txt")
+        );
+        assert!(resolve(&dir.join("new/../../escape")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_resolved() {
+        let dir = scratch("link");
+        std::fs::create_dir(dir.join("target")).unwrap();
+        std::os::unix::fs::symlink(dir.join("target"), dir.join("link")).unwrap();
+        assert_eq!(
+            resolve(&dir.join("link/out.txt")).unwrap(),
+            dir.join("target/out.txt")
+        );
+    }
+
+    #[test]
+    fn a_persisted_folder_is_a_root_once_it_resolves() {
+        let dir = scratch("persisted");
+        let remembered = dir.join("remembered");
+        std::fs::create_dir(&remembered).unwrap();
+        let roots = allowed_roots(vec![remembered.clone(), PathBuf::from("relative")]);
+        assert_eq!(roots, vec![remembered.clone()]);
+
+        let inside = resolve(&remembered.join("job-1/out.txt")).unwrap();
+        assert!(roots.iter().any(|root| inside.starts_with(root)));
+        let outside = resolve(&dir.join("elsewhere")).unwrap();
+        assert!(!roots.iter().any(|root| outside.starts_with(root)));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn drive_relative_device_and_verbatim_dot_paths_are_refused() {
+        assert!(resolve(Path::new(r"C:foo")).is_err());
+        assert!(resolve(Path::new(r"\\.\PhysicalDrive0")).is_err());
+        assert!(resolve(Path::new(r"\\?\C:\..")).is_err());
+        assert!(resolve(Path::new(r"\\?\C:\Windows\..\Users")).is_err());
+    }
+
+    // Through the administrative share of the drive the temp dir is on
+    #[cfg(windows)]
+    #[test]
+    fn a_unc_share_is_allowed_only_once_picked() {
+        let dir = scratch("unc");
+        let local = dir.to_string_lossy();
+        let local = local.strip_prefix(r"\\?\").unwrap_or(&local);
+        let (drive, rest) = local.split_once(r":\").unwrap();
+        let share = PathBuf::from(format!(r"\\localhost\{}$\{}", drive, rest));
+
+        let resolved = resolve(&share.join("out.txt")).unwrap();
+        assert!(!was_picked(&resolved));
+        // The same folder as a root doesn't cover it under its UNC name
+        let roots = allowed_roots(vec![dir.clone()]);
+        assert!(!roots.iter().any(|root| resolved.starts_with(root)));
+
+        register_picked_path(&share).unwrap();
+        assert!(was_picked(&resolved));
+    }
+}
@@ -0,0 +1,121 @@
+// Path handling at the frontend boundary.
+//
+// Paths stay PathBuf/OsStr internally and are only turned into strings when
+// they are handed back over IPC, which needs UTF-8. A path that isn't valid
+// UTF-8 is reported instead of being mangled by a lossy conversion.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Convert a path for returning to the frontend
+pub fn to_ipc_string(path: &Path) -> Result<String, String> {
+    path.to_str().map(|s| s.to_string()).ok_or_else(|| {
+        format!(
+            "Path is not valid UTF-8 and can't be shown: {}",
+            path.display()
+        )
+    })
+}
+
+/// The user's home directory, without requiring it to be valid UTF-8
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Check that `path` is a regular file we can open, no larger than
+/// `max_bytes`, returning its size. The error names the path and what
+/// exactly is wrong, rather than failing later mid-upload.
+pub fn check_readable_file(path: &Path, max_bytes: u64) -> Result<u64, String> {
+    let describe = |e: std::io::Error| match e.kind() {
+        ErrorKind::NotFound => format!("File not found: {}", path.display()),
+        ErrorKind::PermissionDenied => format!("Permission denied: {}", path.display()),
+        _ => format!("Can't read {}: {}", path.display(), e),
+    };
+
+    let metadata = std::fs::metadata(path).map_err(describe)?;
+    if metadata.is_dir() {
+        return Err(format!("Is a directory: {}", path.display()));
+    }
+    if !metadata.is_file() {
+        return Err(format!("Not a regular file: {}", path.display()));
+    }
+    if metadata.len() > max_bytes {
+        return Err(format!(
+            "{} is {} bytes, over the {} byte limit",
+            path.display(),
+            metadata.len(),
+            max_bytes
+        ));
+    }
+
+    // Permissions and dead network mounts only show up on open
+    std::fs::File::open(path).map_err(describe)?;
+    Ok(metadata.len())
+}
+
+/// Delete a file, returning whether there was one to delete
+pub fn remove_file_if_exists(path: &Path) -> Result<bool, String> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!("Failed to delete {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch;
+
+    #[test]
+    fn non_ascii_paths_round_trip() {
+        let dir = scratch("paths", "unicode");
+        let path = dir.join("résultats 結果").join("sortie ü.txt");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"ok").unwrap();
+
+        let shown = to_ipc_string(&path).unwrap();
+        assert_eq!(PathBuf::from(&shown), path);
+        assert_eq!(std::fs::read(&shown).unwrap(), b"ok");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_are_usable_but_not_shown() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = scratch("paths", "non-utf8");
+        let path = dir.join(OsStr::from_bytes(b"caf\xe9.txt"));
+        std::fs::write(&path, b"ok").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"ok");
+
+        let error = to_ipc_string(&path).unwrap_err();
+        assert!(error.contains("not valid UTF-8"));
+    }
+
+    #[test]
+    fn a_readable_file_reports_its_size() {
+        let dir = scratch("paths", "readable");
+        let path = dir.join("input.zip");
+        std::fs::write(&path, b"12345").unwrap();
+        assert_eq!(check_readable_file(&path, 5), Ok(5));
+    }
+
+    #[test]
+    fn unusable_inputs_say_what_is_wrong() {
+        let dir = scratch("paths", "unusable");
+        let missing = check_readable_file(&dir.join("missing.zip"), 100).unwrap_err();
+        assert!(missing.starts_with("File not found"), "{}", missing);
+        let folder = check_readable_file(&dir, 100).unwrap_err();
+        assert!(folder.starts_with("Is a directory"), "{}", folder);
+
+        let big = dir.join("big.zip");
+        std::fs::write(&big, b"123456").unwrap();
+        let error = check_readable_file(&big, 5).unwrap_err();
+        assert!(error.contains("over the 5 byte limit"), "{}", error);
+    }
+}
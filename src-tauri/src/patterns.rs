@@ -0,0 +1,194 @@
+// Include/exclude patterns for result downloads.
+//
+// Glob patterns kept in preferences decide which result files every
+// download fetches: a file matching an exclude pattern is skipped, and a
+// non-empty include list lets through only the files matching one of its
+// patterns. Patterns are matched against the server's file name, with `*`
+// also crossing `/`, so "*.chk" and "scratch/**" both do what they look like.
+
+use crate::download::ResultFile;
+use crate::i18n::Msg;
+use crate::{preferences, AppState};
+use globset::{Glob, GlobMatcher};
+use nsg_cli::models::OutputFile;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::State;
+
+const EXCLUDE_KEY: &str = "download_exclude_patterns";
+const INCLUDE_KEY: &str = "download_include_patterns";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DownloadPatterns {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// A result file left out of a download, and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExcludedFile {
+    pub filename: String,
+    pub size: u64,
+    // The exclude pattern it matched; None when no include pattern matched,
+    // or the download was of picked files and this one wasn't picked
+    pub pattern: Option<String>,
+}
+
+struct Compiled {
+    include: Vec<(String, GlobMatcher)>,
+    exclude: Vec<(String, GlobMatcher)>,
+}
+
+pub fn compile(patterns: &[String]) -> Result<Vec<(String, GlobMatcher)>, String> {
+    patterns
+        .iter()
+        .map(|p| {
+            Glob::new(p)
+                .map(|glob| (p.clone(), glob.compile_matcher()))
+                .map_err(|e| format!("Invalid pattern \"{}\": {}", p, e))
+        })
+        .collect()
+}
+
+impl Compiled {
+    fn new(patterns: &DownloadPatterns) -> Result<Self, String> {
+        Ok(Self {
+            include: compile(&patterns.include)?,
+            exclude: compile(&patterns.exclude)?,
+        })
+    }
+
+    /// None if the file is downloaded, otherwise the exclude pattern that
+    /// matched (None inside when it's missing from the include list)
+    fn exclusion(&self, filename: &str) -> Option<Option<String>> {
+        let name = filename.replace('\\', "/");
+        if let Some((pattern, _)) = self.exclude.iter().find(|(_, m)| m.is_match(&name)) {
+            return Some(Some(pattern.clone()));
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|(_, m)| m.is_match(&name)) {
+            return Some(None);
+        }
+        None
+    }
+}
+
+fn load(app: &tauri::AppHandle) -> DownloadPatterns {
+    let prefs = preferences::of(app);
+    let list = |key: &str| -> Vec<String> {
+        prefs
+            .get(key)
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default()
+    };
+    DownloadPatterns {
+        include: list(INCLUDE_KEY),
+        exclude: list(EXCLUDE_KEY),
+    }
+}
+
+/// Split a results listing into the files to download and the ones the
+/// patterns leave out
+pub fn select(
+    app: &tauri::AppHandle,
+    listing: Vec<OutputFile>,
+) -> Result<(Vec<OutputFile>, Vec<ExcludedFile>), String> {
+    split(&load(app), listing)
+}
+
+/// `select` with the given patterns instead of the saved ones
+pub fn split(
+    patterns: &DownloadPatterns,
+    listing: Vec<OutputFile>,
+) -> Result<(Vec<OutputFile>, Vec<ExcludedFile>), String> {
+    let compiled = Compiled::new(patterns)?;
+
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+    for file in listing {
+        match compiled.exclusion(&file.filename) {
+            None => included.push(file),
+            Some(pattern) => excluded.push(ExcludedFile {
+                filename: file.filename,
+                size: file.size,
+                pattern,
+            }),
+        }
+    }
+    Ok((included, excluded))
+}
+
+#[tauri::command]
+pub async fn get_download_patterns(app: tauri::AppHandle) -> Result<DownloadPatterns, String> {
+    Ok(load(&app))
+}
+
+/// Replace both pattern lists. A pattern that doesn't parse is rejected
+/// with the parser's message and nothing is saved.
+#[tauri::command]
+pub async fn set_download_patterns(
+    app: tauri::AppHandle,
+    patterns: DownloadPatterns,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+
+    let clean = |list: Vec<String>| -> Vec<String> {
+        list.into_iter()
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect()
+    };
+    let patterns = DownloadPatterns {
+        include: clean(patterns.include),
+        exclude: clean(patterns.exclude),
+    };
+    Compiled::new(&patterns)?;
+
+    preferences::of(&app).set_many([
+        (INCLUDE_KEY, json!(patterns.include)),
+        (EXCLUDE_KEY, json!(patterns.exclude)),
+    ]);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadSelection {
+    pub included: Vec<ResultFile>,
+    pub excluded: Vec<ExcludedFile>,
+    pub total_bytes: u64,
+}
+
+/// What a download of this job would fetch under the current patterns
+#[tauri::command]
+pub async fn preview_download_selection(
+    job_url: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<DownloadSelection, String> {
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let job_url = crate::anonymize::real_url(&job_url);
+    let listing = tokio::task::spawn_blocking(move || {
+        let record = api.job_record(&job_url)?;
+        api.output_files(&record.status)
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+    .map_err(|e| format!("Failed to list results: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    let (included, excluded) = select(&app, listing)?;
+    Ok(DownloadSelection {
+        total_bytes: included.iter().map(|f| f.size).sum(),
+        included: included
+            .into_iter()
+            .map(|f| ResultFile {
+                filename: f.filename,
+                size: f.size,
+            })
+            .collect(),
+        excluded,
+    })
+}
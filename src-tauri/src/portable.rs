@@ -0,0 +1,233 @@
+// Portable mode.
+//
+// On lab machines where the OS app-data folder isn't writable the app can
+// run from a USB stick with everything in a `data/` folder next to the
+// executable. It's on when a `portable.flag` file sits beside the
+// executable or the app is started with `--portable`; the mode is decided
+// once, in main(), before anything is read. Every state file goes through
+// `app_data_root`, and the credentials file through `credentials_path`:
+// ~/.nsg/credentials.json normally, data/credentials.json when portable.
+//
+// Switching modes never merges or drops data. When the location not in use
+// also holds app data, `data-location-conflict` is emitted at startup and
+// the user picks: keep using the active data, or copy the other data over
+// when the active location has none yet, then restart.
+
+use crate::i18n::Msg;
+use crate::preferences;
+use nsg_cli::Credentials;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::{Emitter, Manager};
+
+pub const FLAG_ARG: &str = "--portable";
+const FLAG_FILE: &str = "portable.flag";
+const DATA_DIR: &str = "data";
+const CREDENTIALS_FILE: &str = "credentials.json";
+// The other location whose conflict the user chose to ignore
+const DISMISSED_KEY: &str = "data_location_conflict_dismissed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataMode {
+    Installed,
+    Portable,
+}
+
+impl DataMode {
+    fn other(self) -> Self {
+        match self {
+            DataMode::Installed => DataMode::Portable,
+            DataMode::Portable => DataMode::Installed,
+        }
+    }
+}
+
+static MODE: OnceLock<DataMode> = OnceLock::new();
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()?
+        .parent()
+        .map(|dir| dir.to_path_buf())
+}
+
+fn portable_root() -> Option<PathBuf> {
+    exe_dir().map(|dir| dir.join(DATA_DIR))
+}
+
+/// Decide the mode from the flag file and arguments. Called first thing in
+/// main().
+pub fn init() {
+    let flagged = exe_dir().is_some_and(|dir| dir.join(FLAG_FILE).is_file());
+    let mode = if flagged || std::env::args().any(|a| a == FLAG_ARG) {
+        DataMode::Portable
+    } else {
+        DataMode::Installed
+    };
+    let _ = MODE.set(mode);
+}
+
+pub fn mode() -> DataMode {
+    MODE.get().copied().unwrap_or(DataMode::Installed)
+}
+
+fn installed_root(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data dir: {}", e))
+}
+
+fn root_for(app: &tauri::AppHandle, mode: DataMode) -> Result<PathBuf, String> {
+    match mode {
+        DataMode::Installed => installed_root(app),
+        DataMode::Portable => {
+            portable_root().ok_or_else(|| "Can't find the executable's folder".to_string())
+        }
+    }
+}
+
+/// The folder all state files live in
+pub fn app_data_root(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    root_for(app, mode())
+}
+
+fn credentials_path_for(mode: DataMode) -> Option<PathBuf> {
+    match mode {
+        DataMode::Installed => Some(PathBuf::from(Credentials::credentials_location())),
+        DataMode::Portable => portable_root().map(|root| root.join(CREDENTIALS_FILE)),
+    }
+}
+
+/// Where the saved credentials are read from
+pub fn credentials_path() -> PathBuf {
+    credentials_path_for(mode()).unwrap_or_else(|| PathBuf::from(DATA_DIR).join(CREDENTIALS_FILE))
+}
+
+/// Whether a folder holds app data: preferences or any state file
+fn has_data(dir: &Path) -> bool {
+    std::iter::once(preferences::PREFERENCES_FILE)
+        .chain(crate::integrity::state_file_names())
+        .any(|name| dir.join(name).is_file())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataLocationConflict {
+    pub mode: DataMode,
+    pub active_root: String,
+    pub other_root: String,
+    // Copying is only offered into an empty location
+    pub active_has_data: bool,
+}
+
+fn find_conflict(app: &tauri::AppHandle) -> Option<DataLocationConflict> {
+    let mode = mode();
+    let active = app_data_root(app).ok()?;
+    let other = root_for(app, mode.other()).ok()?;
+    if active == other || !has_data(&other) {
+        return None;
+    }
+
+    let other_root = other.display().to_string();
+    let dismissed = preferences::of(app)
+        .get(DISMISSED_KEY)
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
+    if dismissed.as_deref() == Some(other_root.as_str()) {
+        return None;
+    }
+    Some(DataLocationConflict {
+        mode,
+        active_has_data: has_data(&active),
+        active_root: active.display().to_string(),
+        other_root,
+    })
+}
+
+/// Emit `data-location-conflict` if the unused location has data. Called
+/// at startup, after preferences are loaded.
+pub fn check(app: &tauri::AppHandle) {
+    if let Some(conflict) = find_conflict(app) {
+        let _ = app.emit(crate::events::DATA_LOCATION_CONFLICT, conflict);
+    }
+}
+
+/// The conflict found at startup, for a frontend that missed the event
+#[tauri::command]
+pub async fn get_data_location_conflict(
+    app: tauri::AppHandle,
+) -> Result<Option<DataLocationConflict>, String> {
+    Ok(find_conflict(&app))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictChoice {
+    // Go on with the active data and stop asking about this location
+    KeepActive,
+    // Copy the other data into the empty active location and restart
+    CopyOther,
+}
+
+fn copy_files(from: &Path, to: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {}", to.display(), e))?;
+    let entries =
+        std::fs::read_dir(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?;
+    for entry in entries.flatten() {
+        // Credentials are copied to their own location, not the data folder
+        if !entry.path().is_file() || entry.file_name() == CREDENTIALS_FILE {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        std::fs::copy(entry.path(), &target)
+            .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+    }
+    Ok(())
+}
+
+/// Answer a `data-location-conflict`. Copying never overwrites: it's
+/// refused when the active location already has data.
+#[tauri::command]
+pub async fn resolve_data_location_conflict(
+    app: tauri::AppHandle,
+    choice: ConflictChoice,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDataLocation)?;
+    let conflict = find_conflict(&app).ok_or("There is no data location conflict")?;
+
+    match choice {
+        ConflictChoice::KeepActive => {
+            preferences::of(&app).set(DISMISSED_KEY, json!(conflict.other_root));
+            Ok(())
+        }
+        ConflictChoice::CopyOther => {
+            if conflict.active_has_data {
+                return Err(format!(
+                    "{} already has app data; copying would mix two datasets",
+                    conflict.active_root
+                ));
+            }
+            copy_files(
+                Path::new(&conflict.other_root),
+                Path::new(&conflict.active_root),
+            )?;
+
+            if let (Some(from), Some(to)) = (
+                credentials_path_for(conflict.mode.other()),
+                credentials_path_for(conflict.mode),
+            ) {
+                if from.is_file() && !to.exists() {
+                    if let Some(parent) = to.parent() {
+                        let _ = std::fs::create_dir_all(parent);
+                    }
+                    std::fs::copy(&from, &to)
+                        .map_err(|e| format!("Failed to copy saved credentials: {}", e))?;
+                }
+            }
+
+            // Everything in memory came from the old, empty location
+            app.restart()
+        }
+    }
+}
@@ -0,0 +1,355 @@
+// Expected types of the known preference keys.
+//
+// preferences.json can be edited by hand, so a key may hold a value of the
+// wrong type ("1.2" for a zoom level) or out of range. Each known key is
+// checked when the file is loaded: a value that converts cleanly (a numeric
+// string, a number out of range) is replaced by the converted value,
+// anything else is dropped so the key's default applies. Unknown keys are
+// left alone.
+
+use crate::compression::ZipCompression;
+use crate::expiry::MAX_DAYS;
+use crate::monitor::{MAX_POLL_INTERVAL_SECS, MIN_POLL_INTERVAL_SECS};
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Bool,
+    Number { min: f64, max: f64 },
+    Integer { min: u64, max: u64 },
+    Text,
+    OneOf(&'static [&'static str]),
+    List,
+    Map,
+}
+
+const KNOWN: &[(&str, Kind)] = &[
+    ("zoom_level", Kind::Number { min: 0.5, max: 3.0 }),
+    ("theme", Kind::OneOf(crate::THEMES)),
+    ("locale", Kind::OneOf(&["en", "es"])),
+    ("auto_refresh", Kind::Bool),
+    (
+        "auto_refresh_interval",
+        Kind::Integer {
+            min: 1,
+            max: 86_400,
+        },
+    ),
+    ("auto_connect", Kind::Bool),
+    ("credential_backend", Kind::OneOf(crate::keychain::BACKENDS)),
+    ("read_only", Kind::Bool),
+    ("download_dir", Kind::Text),
+    ("staging_dir", Kind::Text),
+    ("download_rules", Kind::List),
+    ("job_output_dirs", Kind::Map),
+    ("project_output_dirs", Kind::Map),
+    ("checkpoint_patterns", Kind::Map),
+    ("download_include_patterns", Kind::List),
+    ("zip_compression", Kind::OneOf(ZipCompression::ALL)),
+    ("download_exclude_patterns", Kind::List),
+    (
+        "download_rate_limit_kbps",
+        Kind::Integer {
+            min: 0,
+            max: u64::MAX,
+        },
+    ),
+    ("delete_after_download", Kind::Bool),
+    ("settle_delay_secs", Kind::Integer { min: 0, max: 600 }),
+    ("download_concurrency", Kind::Integer { min: 1, max: 8 }),
+    ("adaptive_download_warning", Kind::Bool),
+    ("notifications_enabled", Kind::Bool),
+    ("announcements_url", Kind::Text),
+    ("dismissed_announcements", Kind::List),
+    ("data_location_conflict_dismissed", Kind::Text),
+    (
+        "notification_click_action",
+        Kind::OneOf(crate::notify::CLICK_ACTIONS),
+    ),
+    ("notification_quiet_hours", Kind::Map),
+    (
+        "max_download_warn_bytes",
+        Kind::Integer {
+            min: 0,
+            max: u64::MAX,
+        },
+    ),
+    (
+        "max_submit_bytes",
+        Kind::Integer {
+            min: 1,
+            max: u64::MAX,
+        },
+    ),
+    ("notification_email", Kind::Text),
+    ("write_submission_receipt", Kind::Bool),
+    ("submit_exclude_patterns", Kind::List),
+    ("secret_scan_enabled", Kind::Bool),
+    ("lab_defaults_path", Kind::Text),
+    (
+        "thumbnail_cache_bytes",
+        Kind::Integer {
+            min: 0,
+            max: u64::MAX,
+        },
+    ),
+    (
+        "submit_zip_warn_bytes",
+        Kind::Integer {
+            min: 0,
+            max: u64::MAX,
+        },
+    ),
+    (
+        "poll_interval_secs",
+        Kind::Integer {
+            min: MIN_POLL_INTERVAL_SECS,
+            max: MAX_POLL_INTERVAL_SECS,
+        },
+    ),
+    ("adaptive_polling", Kind::Bool),
+    (
+        "results_retention_days",
+        Kind::Integer {
+            min: 1,
+            max: MAX_DAYS,
+        },
+    ),
+    (
+        "expiry_warning_days",
+        Kind::Integer {
+            min: 1,
+            max: MAX_DAYS,
+        },
+    ),
+    (
+        "sweep_max_consecutive_failures",
+        Kind::Integer { min: 1, max: 1000 },
+    ),
+    (
+        "upload_bytes_per_sec",
+        Kind::Number {
+            min: f64::MIN_POSITIVE,
+            max: f64::MAX,
+        },
+    ),
+    ("auto_cleanup_enabled", Kind::Bool),
+    (
+        "keep_archives_days",
+        Kind::Integer {
+            min: 0,
+            max: u64::MAX,
+        },
+    ),
+    (
+        "keep_archives_max_gb",
+        Kind::Number {
+            min: 0.0,
+            max: f64::MAX,
+        },
+    ),
+];
+
+/// A value that had to be fixed while loading
+#[derive(Debug, Clone, Serialize)]
+pub struct PreferenceRepair {
+    pub key: String,
+    pub found: Value,
+    // None when the value was dropped in favor of the default
+    pub repaired: Option<Value>,
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// The value to keep for a key of this kind: `value` itself if it's fine,
+/// a converted value, or None to drop it
+fn conform(kind: Kind, value: &Value) -> Option<Value> {
+    match kind {
+        Kind::Bool => match value {
+            Value::Bool(_) => Some(value.clone()),
+            Value::Number(n) => match n.as_u64() {
+                Some(0) => Some(Value::Bool(false)),
+                Some(1) => Some(Value::Bool(true)),
+                _ => None,
+            },
+            Value::String(s) => parse_bool(s).map(Value::Bool),
+            _ => None,
+        },
+        Kind::Number { min, max } => {
+            let n = match value {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse::<f64>().ok(),
+                _ => None,
+            }
+            .filter(|n| n.is_finite())?;
+            if value.is_number() && (min..=max).contains(&n) {
+                // Keeps 1 as 1 rather than turning it into 1.0
+                return Some(value.clone());
+            }
+            Number::from_f64(n.clamp(min, max)).map(Value::Number)
+        }
+        Kind::Integer { min, max } => {
+            let n = match value {
+                Value::Number(n) => n.as_u64().or_else(|| {
+                    n.as_f64()
+                        .filter(|f| f.is_finite() && *f >= 0.0)
+                        .map(|f| f.round() as u64)
+                }),
+                Value::String(s) => s.trim().parse::<u64>().ok(),
+                _ => None,
+            }?;
+            Some(Value::from(n.clamp(min, max)))
+        }
+        Kind::Text => match value {
+            Value::String(_) => Some(value.clone()),
+            Value::Number(n) => Some(Value::String(n.to_string())),
+            Value::Bool(b) => Some(Value::String(b.to_string())),
+            _ => None,
+        },
+        Kind::OneOf(allowed) => {
+            let text = value.as_str()?.trim().to_lowercase();
+            allowed
+                .contains(&text.as_str())
+                .then_some(Value::String(text))
+        }
+        Kind::List => value.is_array().then(|| value.clone()),
+        Kind::Map => value.is_object().then(|| value.clone()),
+    }
+}
+
+/// Fix the known keys in `values` in place, returning what was changed
+pub fn repair(values: &mut Map<String, Value>) -> Vec<PreferenceRepair> {
+    let mut repairs = Vec::new();
+    for (key, kind) in KNOWN {
+        let Some(found) = values.get(*key) else {
+            continue;
+        };
+        let conformed = conform(*kind, found);
+        if conformed.as_ref() == Some(found) {
+            continue;
+        }
+
+        let found = found.clone();
+        match &conformed {
+            Some(value) => values.insert(key.to_string(), value.clone()),
+            None => values.remove(*key),
+        };
+        repairs.push(PreferenceRepair {
+            key: key.to_string(),
+            found,
+            repaired: conformed,
+        });
+    }
+    repairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn values_of_the_right_type_are_kept_as_they_are() {
+        assert_eq!(conform(Kind::Bool, &json!(true)), Some(json!(true)));
+        let zoom = Kind::Number { min: 0.5, max: 3.0 };
+        assert_eq!(conform(zoom, &json!(1)), Some(json!(1)));
+        assert_eq!(conform(Kind::Text, &json!("x")), Some(json!("x")));
+        assert_eq!(conform(Kind::List, &json!([1])), Some(json!([1])));
+        assert_eq!(conform(Kind::Map, &json!({"a": 1})), Some(json!({"a": 1})));
+    }
+
+    #[test]
+    fn clean_conversions_are_applied() {
+        assert_eq!(conform(Kind::Bool, &json!("Yes")), Some(json!(true)));
+        assert_eq!(conform(Kind::Bool, &json!(0)), Some(json!(false)));
+        let zoom = Kind::Number { min: 0.5, max: 3.0 };
+        assert_eq!(conform(zoom, &json!("1.2")), Some(json!(1.2)));
+        assert_eq!(conform(zoom, &json!(9)), Some(json!(3.0)));
+        let workers = Kind::Integer { min: 1, max: 8 };
+        assert_eq!(conform(workers, &json!(" 4 ")), Some(json!(4)));
+        assert_eq!(conform(workers, &json!(2.6)), Some(json!(3)));
+        assert_eq!(conform(workers, &json!(0)), Some(json!(1)));
+        assert_eq!(conform(Kind::Text, &json!(5)), Some(json!("5")));
+        assert_eq!(
+            conform(Kind::OneOf(&["en", "es"]), &json!(" ES ")),
+            Some(json!("es"))
+        );
+    }
+
+    #[test]
+    fn anything_else_is_dropped() {
+        assert_eq!(conform(Kind::Bool, &json!(2)), None);
+        assert_eq!(conform(Kind::Bool, &json!("maybe")), None);
+        let zoom = Kind::Number { min: 0.5, max: 3.0 };
+        assert_eq!(conform(zoom, &json!("big")), None);
+        let workers = Kind::Integer { min: 1, max: 8 };
+        assert_eq!(conform(workers, &json!(-3)), None);
+        assert_eq!(conform(workers, &json!("-3")), None);
+        assert_eq!(conform(Kind::Text, &json!(null)), None);
+        assert_eq!(conform(Kind::OneOf(&["en", "es"]), &json!("fr")), None);
+        assert_eq!(conform(Kind::List, &json!({})), None);
+        assert_eq!(conform(Kind::Map, &json!([])), None);
+    }
+
+    #[test]
+    fn every_known_key_is_listed_once() {
+        let mut keys: Vec<&str> = KNOWN.iter().map(|(key, _)| *key).collect();
+        let count = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), count);
+    }
+
+    #[test]
+    fn repair_fixes_known_keys_and_reports_each_change() {
+        let mut values = json!({
+            "zoom_level": "1.5",
+            "auto_refresh": "maybe",
+            "theme": 7,
+            "download_concurrency": 4,
+            "unknown_key": "left alone",
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        let repairs = repair(&mut values);
+        let fixed: Vec<(&str, Option<&Value>)> = repairs
+            .iter()
+            .map(|r| (r.key.as_str(), r.repaired.as_ref()))
+            .collect();
+        assert_eq!(
+            fixed,
+            [
+                ("zoom_level", Some(&json!(1.5))),
+                ("theme", None),
+                ("auto_refresh", None),
+            ]
+        );
+        assert_eq!(repairs[0].found, json!("1.5"));
+
+        assert_eq!(values["zoom_level"], json!(1.5));
+        assert!(!values.contains_key("auto_refresh"));
+        assert!(!values.contains_key("theme"));
+        assert_eq!(values["download_concurrency"], json!(4));
+        assert_eq!(values["unknown_key"], json!("left alone"));
+    }
+
+    #[test]
+    fn repair_leaves_good_values_alone() {
+        let mut values = json!({ "zoom_level": 1, "locale": "es", "download_rules": [] })
+            .as_object()
+            .unwrap()
+            .clone();
+        let before = values.clone();
+        assert!(repair(&mut values).is_empty());
+        assert_eq!(values, before);
+    }
+}
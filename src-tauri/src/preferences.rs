@@ -0,0 +1,242 @@
+// Preferences, kept in memory and written to preferences.json.
+//
+// Setters only update memory and wake the writer, which saves once things
+// have been quiet for SAVE_DEBOUNCE, so a burst of zoom ticks costs one
+// write. Saves go to a temporary file that is renamed over the real one, so
+// a crash mid-save leaves the previous file intact, and the previous good
+// file is kept as preferences.json.bak to recover from if it's corrupt anyway.
+// Values of the wrong type are fixed on load, see preference_types.
+
+use crate::background_errors::{self, ErrorKind};
+use crate::preference_types::{self, PreferenceRepair};
+use crate::AppState;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use tokio::sync::Notify;
+
+pub const PREFERENCES_FILE: &str = "preferences.json";
+const BACKUP_FILE: &str = "preferences.json.bak";
+// Name of loading and saving in background errors
+const SOURCE: &str = "preferences";
+
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+struct PreferencesState {
+    values: Map<String, Value>,
+    // None until load() has resolved the app data dir
+    path: Option<PathBuf>,
+    dirty: bool,
+    // What load() had to fix
+    repairs: Vec<PreferenceRepair>,
+}
+
+#[derive(Default)]
+pub struct Preferences {
+    state: Mutex<PreferencesState>,
+    // Held for a whole save, so two saves never interleave on disk
+    save_lock: Mutex<()>,
+    wake: Notify,
+}
+
+impl Preferences {
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.state.lock().unwrap().values.get(key).cloned()
+    }
+
+    pub fn set(&self, key: &str, value: Value) {
+        self.set_many([(key, value)]);
+    }
+
+    /// Set several keys at once; they are always saved together
+    pub fn set_many<'a>(&self, entries: impl IntoIterator<Item = (&'a str, Value)>) {
+        let mut state = self.state.lock().unwrap();
+        for (key, value) in entries {
+            state.values.insert(key.to_string(), value);
+        }
+        state.dirty = true;
+        drop(state);
+        self.wake.notify_one();
+    }
+
+    /// Write pending changes now
+    pub fn flush(&self) -> Result<(), String> {
+        let _save = self.save_lock.lock().unwrap();
+
+        let (path, content) = {
+            let mut state = self.state.lock().unwrap();
+            let Some(path) = state.path.clone() else {
+                return Ok(());
+            };
+            if !state.dirty {
+                return Ok(());
+            }
+            let content = serde_json::to_vec_pretty(&state.values)
+                .map_err(|e| format!("Failed to serialize preferences: {}", e))?;
+            state.dirty = false;
+            (path, content)
+        };
+
+        write_with_backup(&path, &content).inspect_err(|_| {
+            // Try again with the next save
+            self.state.lock().unwrap().dirty = true;
+        })
+    }
+
+    /// Forget every preference and delete the files, the backup included,
+    /// returning whether preferences.json existed. Nothing is written back
+    /// until the next change.
+    pub fn purge(&self) -> Result<bool, String> {
+        let _save = self.save_lock.lock().unwrap();
+
+        let path = {
+            let mut state = self.state.lock().unwrap();
+            state.values.clear();
+            state.dirty = false;
+            state.repairs.clear();
+            state.path.clone()
+        };
+        let Some(path) = path else {
+            return Ok(false);
+        };
+
+        let dir = path
+            .parent()
+            .ok_or_else(|| format!("Invalid preferences path: {}", path.display()))?;
+        crate::paths::remove_file_if_exists(&dir.join(format!("{}.tmp", PREFERENCES_FILE)))?;
+        crate::paths::remove_file_if_exists(&dir.join(BACKUP_FILE))?;
+        crate::paths::remove_file_if_exists(&path)
+    }
+}
+
+fn parse(path: &Path) -> Option<Map<String, Value>> {
+    let content = std::fs::read(path).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+// Only a file that still parses is worth keeping as the backup; after a
+// recovery the current file is the corrupt one and the backup must survive.
+// Another instance writing at the same time waits for the lock.
+fn write_with_backup(path: &Path, content: &[u8]) -> Result<(), String> {
+    let _lock = crate::state_file::lock(path)?;
+    if parse(path).is_some() {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::copy(path, dir.join(BACKUP_FILE));
+        }
+    }
+    crate::state_file::write_atomic(path, content)
+}
+
+/// The app's preferences
+pub fn of(app: &tauri::AppHandle) -> &Preferences {
+    &app.state::<AppState>().inner().preferences
+}
+
+/// Read preferences.json, falling back to the backup if it is unreadable,
+/// and start the background writer. Called first thing at startup.
+pub fn load(app: &tauri::AppHandle) {
+    let preferences = of(app);
+
+    let dir = match crate::portable::app_data_root(app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            background_errors::report(
+                app,
+                SOURCE,
+                ErrorKind::Other,
+                format!(
+                    "Failed to resolve app data dir, preferences won't be saved: {}",
+                    e
+                ),
+            );
+            return;
+        }
+    };
+    let path = dir.join(PREFERENCES_FILE);
+
+    let (mut values, recovered) = match parse(&path) {
+        Some(values) => (values, false),
+        None if !path.exists() => (Map::new(), false),
+        None => match parse(&dir.join(BACKUP_FILE)) {
+            Some(values) => {
+                background_errors::report(
+                    app,
+                    SOURCE,
+                    ErrorKind::Other,
+                    "preferences.json is corrupt, restored the last good copy".to_string(),
+                );
+                (values, true)
+            }
+            None => {
+                background_errors::report(
+                    app,
+                    SOURCE,
+                    ErrorKind::Other,
+                    "preferences.json is corrupt and there is no usable backup".to_string(),
+                );
+                (Map::new(), false)
+            }
+        },
+    };
+
+    let repairs = preference_types::repair(&mut values);
+    let rewrite = recovered || !repairs.is_empty();
+
+    {
+        let mut state = preferences.state.lock().unwrap();
+        state.values = values;
+        state.path = Some(path);
+        // Replace the corrupt file with the recovered or repaired values
+        state.dirty = rewrite;
+        state.repairs = repairs.clone();
+    }
+    if !repairs.is_empty() {
+        let keys: Vec<&str> = repairs.iter().map(|r| r.key.as_str()).collect();
+        let _ = app.emit(
+            crate::events::PREFERENCES_REPAIRED,
+            serde_json::json!({ "keys": keys, "repairs": repairs }),
+        );
+    }
+
+    let writer_app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            of(&writer_app).wake.notified().await;
+            // Hold off while changes keep coming
+            while tokio::time::timeout(SAVE_DEBOUNCE, of(&writer_app).wake.notified())
+                .await
+                .is_ok()
+            {}
+
+            let flush_app = writer_app.clone();
+            let result = tokio::task::spawn_blocking(move || of(&flush_app).flush()).await;
+            if let Ok(Err(e)) = result {
+                background_errors::report(&writer_app, SOURCE, ErrorKind::Other, e);
+            }
+        }
+    });
+    if rewrite {
+        preferences.wake.notify_one();
+    }
+}
+
+/// Write pending preference changes immediately instead of after the
+/// debounce
+#[tauri::command]
+pub async fn flush_preferences(app: tauri::AppHandle) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || of(&app).flush())
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+}
+
+/// The values load() had to fix, for a frontend that started listening for
+/// `preferences-repaired` too late
+#[tauri::command]
+pub async fn get_preference_repairs(
+    app: tauri::AppHandle,
+) -> Result<Vec<PreferenceRepair>, String> {
+    Ok(of(&app).state.lock().unwrap().repairs.clone())
+}
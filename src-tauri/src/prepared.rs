@@ -0,0 +1,232 @@
+// Reviewed submissions.
+//
+// prepare_submission resolves a submission the way submit_job would (lab
+// and user defaults layered in, lint run, inputs checked) and returns it for
+// review together with a plan ID: every field with the layer it came from,
+// each input's size and SHA-256, and the upload estimate. submit_prepared
+// then sends exactly that plan. Plans live in memory for PLAN_TTL, belong to
+// the session they were made in and are used once; an input whose contents
+// changed since the review fails the submission rather than sending
+// something nobody looked at.
+
+use crate::i18n::Msg;
+use crate::inputs::{self, InputBinding};
+use crate::lab_defaults::{self, ParamSource};
+use crate::param_lint::{self, LintFinding};
+use crate::{anonymize, checksum, dry_run, secret_scan, session, AppState, SubmitResponse};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+const PLAN_TTL: Duration = Duration::from_secs(5 * 60);
+
+struct Plan {
+    generation: u64,
+    created: Instant,
+    submission: crate::ResolvedSubmission,
+    // SHA-256 of each input, in input order
+    hashes: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct Plans {
+    pending: Mutex<HashMap<String, Plan>>,
+}
+
+impl Plans {
+    fn sweep(pending: &mut HashMap<String, Plan>) {
+        pending.retain(|_, plan| plan.created.elapsed() < PLAN_TTL);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewedField {
+    pub value: String,
+    pub source: ParamSource,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewedInput {
+    pub param_name: String,
+    pub file_path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmissionReview {
+    pub plan_id: String,
+    // Seconds until the plan can no longer be submitted
+    pub expires_in_secs: u64,
+    pub tool: String,
+    pub params: BTreeMap<String, ReviewedField>,
+    // Without the "metadata." prefix
+    pub metadata: BTreeMap<String, ReviewedField>,
+    pub inputs: Vec<ReviewedInput>,
+    pub upload_bytes: u64,
+    // None until an upload has been timed
+    pub estimated_upload_secs: Option<u64>,
+    pub lint: Vec<LintFinding>,
+}
+
+async fn hash_inputs(inputs: &[InputBinding]) -> Result<Vec<String>, String> {
+    let paths: Vec<_> = inputs.iter().map(|i| i.file_path.clone()).collect();
+    tokio::task::spawn_blocking(move || {
+        paths
+            .iter()
+            .map(|path| checksum::file_sha256(path))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+}
+
+/// Resolve a submission for review without sending anything. `metadata`
+/// keys are given without the "metadata." prefix.
+#[tauri::command]
+pub async fn prepare_submission(
+    inputs: Vec<InputBinding>,
+    tool: String,
+    params: Option<BTreeMap<String, String>>,
+    metadata: Option<BTreeMap<String, String>>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SubmissionReview, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionSubmitJobs)?;
+    let session = state.session.require()?;
+
+    let inputs: Vec<InputBinding> = inputs
+        .into_iter()
+        .map(|input| InputBinding {
+            param_name: input.param_name.trim().to_string(),
+            ..input
+        })
+        .collect();
+    inputs::authorize(&app, &inputs)?;
+    let upload_bytes = inputs::validate(&inputs, crate::max_submit_bytes(&app))?;
+
+    if secret_scan::enabled(&app) {
+        let scan_inputs = inputs.clone();
+        let scan_creds = session.credentials.clone();
+        tokio::task::spawn_blocking(move || secret_scan::check(&scan_inputs, &scan_creds))
+            .await
+            .map_err(|e| format!("Task error: {}", e))??;
+    }
+
+    let mut explicit = params.unwrap_or_default();
+    for (key, value) in metadata.unwrap_or_default() {
+        let key = key.trim();
+        let key = key.strip_prefix("metadata.").unwrap_or(key);
+        explicit.insert(format!("metadata.{}", key), value);
+    }
+    let lab_defaults::Layered {
+        params,
+        sources: param_sources,
+    } = lab_defaults::layer(&app, &tool, explicit, None, None)?;
+    let lint = param_lint::lint(&app, &tool, &params)?;
+    let hashes = hash_inputs(&inputs).await?;
+
+    let mut review_params = BTreeMap::new();
+    let mut review_metadata = BTreeMap::new();
+    for (name, value) in &params {
+        let field = ReviewedField {
+            value: value.clone(),
+            source: param_sources
+                .get(name)
+                .copied()
+                .unwrap_or(ParamSource::Explicit),
+        };
+        match name.strip_prefix("metadata.") {
+            Some(key) => review_metadata.insert(key.to_string(), field),
+            None => review_params.insert(name.clone(), field),
+        };
+    }
+    let review_inputs = inputs
+        .iter()
+        .zip(&hashes)
+        .map(|(input, hash)| ReviewedInput {
+            param_name: input.param_name.clone(),
+            file_path: input.file_path.display().to_string(),
+            size: std::fs::metadata(&input.file_path)
+                .map(|m| m.len())
+                .unwrap_or(0),
+            sha256: hash.clone(),
+        })
+        .collect();
+
+    let plan_id = format!(
+        "plan-{}",
+        crate::clock::now()
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+    );
+    let review = SubmissionReview {
+        plan_id: plan_id.clone(),
+        expires_in_secs: PLAN_TTL.as_secs(),
+        tool: tool.clone(),
+        params: review_params,
+        metadata: review_metadata,
+        inputs: review_inputs,
+        upload_bytes,
+        estimated_upload_secs: dry_run::estimated_upload_secs(&app, upload_bytes),
+        lint: lint.clone(),
+    };
+
+    let plan = Plan {
+        generation: session.generation,
+        created: Instant::now(),
+        submission: crate::ResolvedSubmission {
+            inputs,
+            tool,
+            params,
+            param_sources,
+            lint,
+            project_id: None,
+            upload_bytes,
+        },
+        hashes,
+    };
+    let mut pending = state.plans.pending.lock().unwrap();
+    Plans::sweep(&mut pending);
+    pending.insert(plan_id, plan);
+    Ok(review)
+}
+
+/// Send a plan from prepare_submission exactly as it was reviewed
+#[tauri::command]
+pub async fn submit_prepared(
+    plan_id: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SubmitResponse, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionSubmitJobs)?;
+
+    let plan = {
+        let mut pending = state.plans.pending.lock().unwrap();
+        Plans::sweep(&mut pending);
+        pending.remove(&plan_id)
+    }
+    .ok_or_else(|| format!("Plan {} has expired or was already submitted", plan_id))?;
+
+    let session: session::SessionSnapshot = state.session.require()?;
+    if session.generation != plan.generation {
+        return Err("The plan was prepared in an earlier session; prepare it again".to_string());
+    }
+
+    let hashes = hash_inputs(&plan.submission.inputs).await?;
+    for ((input, reviewed), current) in plan.submission.inputs.iter().zip(&plan.hashes).zip(&hashes)
+    {
+        if reviewed != current {
+            return Err(format!(
+                "{} changed since the submission was reviewed; prepare it again",
+                input.file_path.display()
+            ));
+        }
+    }
+
+    let response =
+        crate::send_resolved(plan.submission, session, false, None, false, &app, &state).await?;
+    Ok(anonymize::outgoing(&app, response))
+}
@@ -0,0 +1,400 @@
+// Saved credential profiles.
+//
+// Someone with a lab account and a personal one keeps both as profiles
+// and moves between them with switch_profile, which connects with the
+// profile's credentials the way the login form does and, when the
+// username differs, switches to that account's state namespace right away
+// instead of raising `account-mismatch`: picking the profile is the answer.
+// profiles.json in the app data folder holds the names, usernames and which
+// profile is active. A profile's password and app key go into the OS
+// credential store when it was added with the keychain backend. In portable
+// mode, where the OS store is off, they are sealed with the vault
+// passphrase and stored in profiles.json; otherwise they go into
+// profiles.json as they are, and the file is kept owner-only on Unix like
+// the credentials file. Reads and writes go through state_file, so a
+// profiles.json that doesn't parse is an error when read and quarantined by
+// the next change instead of lost to the rewrite.
+
+use crate::accounts::MismatchAction;
+use crate::anonymize::{self, anonymize_username, Anonymize};
+use crate::i18n::Msg;
+use crate::keychain;
+use crate::vault::{self, Sealed};
+use crate::AppState;
+use nsg_cli::Credentials;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::State;
+
+pub const PROFILES_FILE: &str = "profiles.json";
+// Where earlier releases set a corrupt profiles file aside
+const BACKUP_FILE: &str = "profiles.json.bak";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Secret {
+    password: String,
+    app_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Profile {
+    id: String,
+    name: String,
+    username: String,
+    created_at: String,
+    // None when the secret is in the OS credential store or sealed
+    #[serde(default)]
+    secret: Option<Secret>,
+    // The secret as JSON, sealed with the vault passphrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed: Option<Sealed>,
+}
+
+impl Profile {
+    fn in_keychain(&self) -> bool {
+        self.secret.is_none() && self.sealed.is_none()
+    }
+
+    // Seal a plain secret, bound to this profile
+    fn seal(&mut self) -> Result<(), String> {
+        if let Some(secret) = self.secret.take() {
+            let json = serde_json::to_string(&secret)
+                .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+            match vault::seal(&self.id, &json) {
+                Ok(sealed) => self.sealed = Some(sealed),
+                Err(e) => {
+                    self.secret = Some(secret);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct ProfilesFile {
+    #[serde(default)]
+    profiles: Vec<Profile>,
+    // Profile last switched to
+    #[serde(default)]
+    active: Option<String>,
+}
+
+/// A profile as shown in the app, without its secret
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileInfo {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    pub created_at: String,
+    // The password and app key are in the OS credential store
+    pub keychain: bool,
+    pub active: bool,
+}
+
+impl Anonymize for ProfileInfo {
+    fn anonymize(self) -> Self {
+        Self {
+            username: anonymize_username(&self.username),
+            ..self
+        }
+    }
+}
+
+fn profiles_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::portable::app_data_root(app)?.join(PROFILES_FILE))
+}
+
+fn modify<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut ProfilesFile) -> Result<T, String>,
+) -> Result<T, String> {
+    let path = profiles_path(app)?;
+    let result = crate::state_file::modify(&path, "profiles", f)?;
+    #[cfg(unix)]
+    crate::credentials_file::set_owner_only(&path)?;
+    Ok(result)
+}
+
+fn load(app: &tauri::AppHandle) -> Result<ProfilesFile, String> {
+    crate::state_file::load(&profiles_path(app)?)
+}
+
+// Account of a profile's item in the OS credential store
+fn keychain_account(id: &str) -> String {
+    format!("profile-{}", id)
+}
+
+fn info(profile: &Profile, active: Option<&str>) -> ProfileInfo {
+    ProfileInfo {
+        id: profile.id.clone(),
+        name: profile.name.clone(),
+        username: profile.username.clone(),
+        created_at: profile.created_at.clone(),
+        keychain: profile.in_keychain(),
+        active: active == Some(profile.id.as_str()),
+    }
+}
+
+fn credentials(profile: &Profile) -> Result<Credentials, String> {
+    let opened;
+    let secret = match (&profile.secret, &profile.sealed) {
+        (Some(secret), _) => Some(secret),
+        (None, Some(sealed)) => {
+            opened = serde_json::from_str::<Secret>(&vault::open(&profile.id, sealed)?).map_err(
+                |_| {
+                    format!(
+                        "The sealed credentials of profile {} are not valid",
+                        profile.name
+                    )
+                },
+            )?;
+            Some(&opened)
+        }
+        (None, None) => None,
+    };
+    match secret {
+        Some(secret) => Ok(Credentials {
+            username: profile.username.clone(),
+            password: secret.password.clone(),
+            app_key: secret.app_key.clone(),
+        }),
+        None => keychain::load(&keychain_account(&profile.id))?.ok_or_else(|| {
+            format!(
+                "The credentials of profile {} are missing from the OS credential store",
+                profile.name
+            )
+        }),
+    }
+}
+
+/// Delete the profiles file, its backup and the profiles' OS credential
+/// store items, returning whether there was a file
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = profiles_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    // A corrupt file can't say which store items are the profiles'
+    let mut failed = Vec::new();
+    let file: ProfilesFile = crate::state_file::read(&path).unwrap_or_default();
+    for profile in file.profiles {
+        if profile.in_keychain() {
+            if let Err(e) = keychain::delete(&keychain_account(&profile.id)) {
+                failed.push(format!("{} ({})", profile.name, e));
+            }
+        }
+    }
+    crate::paths::remove_file_if_exists(&path.with_file_name(BACKUP_FILE))?;
+    let existed = crate::paths::remove_file_if_exists(&path)?;
+    if !failed.is_empty() {
+        return Err(format!(
+            "Failed to delete the credentials of profiles {}",
+            failed.join(", ")
+        ));
+    }
+    Ok(existed)
+}
+
+#[tauri::command]
+pub async fn list_profiles(app: tauri::AppHandle) -> Result<Vec<ProfileInfo>, String> {
+    let file = load(&app)?;
+    let profiles = file
+        .profiles
+        .iter()
+        .map(|p| info(p, file.active.as_deref()))
+        .collect::<Vec<_>>();
+    Ok(anonymize::outgoing(&app, profiles))
+}
+
+/// Save credentials as a named profile. `keychain` defaults to the
+/// credential_backend preference. In portable mode the vault must be
+/// unlocked.
+#[tauri::command]
+pub async fn add_profile(
+    name: String,
+    username: String,
+    password: String,
+    app_key: String,
+    keychain: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<ProfileInfo, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeConnectionSettings)?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("A profile needs a name".to_string());
+    }
+    if username.trim().is_empty() {
+        return Err("A profile needs a username".to_string());
+    }
+    if load(&app)?
+        .profiles
+        .iter()
+        .any(|p| p.name.eq_ignore_ascii_case(&name))
+    {
+        return Err(format!("There is already a profile named {}", name));
+    }
+
+    let id = format!(
+        "{:x}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    );
+    let in_keychain = keychain.unwrap_or_else(|| keychain::uses_keychain(&app));
+    let secret = if in_keychain {
+        keychain::ensure_available()?;
+        let credentials = Credentials {
+            username: username.clone(),
+            password,
+            app_key,
+        };
+        let account = keychain_account(&id);
+        tokio::task::spawn_blocking(move || keychain::save(&account, &credentials))
+            .await
+            .map_err(|e| format!("Task error: {}", e))??;
+        None
+    } else {
+        Some(Secret { password, app_key })
+    };
+    let mut profile = Profile {
+        id,
+        name,
+        username,
+        created_at: crate::clock::now().to_rfc3339(),
+        secret,
+        sealed: None,
+    };
+    // Never as plain text on a stick
+    if vault::in_use() {
+        profile.seal()?;
+    }
+
+    let stored = profile.clone();
+    modify(&app, |file| {
+        file.profiles.push(stored);
+        Ok(())
+    })?;
+    Ok(anonymize::outgoing(&app, info(&profile, None)))
+}
+
+/// Seal the plain secrets of every profile, once the vault is unlocked
+pub fn seal_plain_secrets(app: &tauri::AppHandle) -> Result<(), String> {
+    if !vault::in_use() || load(app)?.profiles.iter().all(|p| p.secret.is_none()) {
+        return Ok(());
+    }
+    modify(app, |file| {
+        file.profiles.iter_mut().try_for_each(Profile::seal)
+    })
+}
+
+/// Connect with a profile's credentials, replacing the current session, and
+/// move to its account's state
+#[tauri::command]
+pub async fn switch_profile(
+    id: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ProfileInfo, String> {
+    let Some(profile) = load(&app)?.profiles.into_iter().find(|p| p.id == id) else {
+        return Err(format!("No profile {}", id));
+    };
+    let lookup = profile.clone();
+    let creds = tokio::task::spawn_blocking(move || credentials(&lookup))
+        .await
+        .map_err(|e| format!("Task error: {}", e))??;
+
+    crate::session::connect(
+        creds.username,
+        creds.password,
+        creds.app_key,
+        app.clone(),
+        state.clone(),
+    )
+    .await
+    .map_err(|e| e.message.message)?;
+
+    if crate::accounts::is_mismatched() {
+        crate::accounts::resolve_account_mismatch(MismatchAction::Switch, app.clone(), state)
+            .await?;
+    }
+    modify(&app, |file| {
+        file.active = Some(id.clone());
+        Ok(())
+    })?;
+    Ok(anonymize::outgoing(&app, info(&profile, Some(&id))))
+}
+
+/// Forget a profile and its saved credentials. The session stays connected
+/// if it was started from it.
+#[tauri::command]
+pub async fn delete_profile(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeConnectionSettings)?;
+    let removed = modify(&app, |file| {
+        let Some(at) = file.profiles.iter().position(|p| p.id == id) else {
+            return Err(format!("No profile {}", id));
+        };
+        if file.active.as_deref() == Some(id.as_str()) {
+            file.active = None;
+        }
+        Ok(file.profiles.remove(at))
+    })?;
+
+    if removed.in_keychain() {
+        let account = keychain_account(&removed.id);
+        tokio::task::spawn_blocking(move || keychain::delete(&account))
+            .await
+            .map_err(|e| format!("Task error: {}", e))??;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch;
+
+    #[test]
+    fn a_missing_file_has_no_profiles() {
+        let path = scratch("profiles", "missing").join(PROFILES_FILE);
+        let file: ProfilesFile = crate::state_file::load(&path).unwrap();
+        assert!(file.profiles.is_empty());
+    }
+
+    #[test]
+    fn a_corrupt_file_is_an_error() {
+        let path = scratch("profiles", "corrupt").join(PROFILES_FILE);
+        std::fs::write(&path, "{\"profiles\": [").unwrap();
+        assert!(crate::state_file::load::<ProfilesFile>(&path).is_err());
+    }
+
+    #[test]
+    fn the_file_is_written_unversioned() {
+        let path = scratch("profiles", "plain").join(PROFILES_FILE);
+        crate::state_file::modify(&path, "profiles", |_: &mut ProfilesFile| Ok(())).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert!(written.get("profiles").is_some());
+    }
+
+    #[test]
+    fn a_sealed_profile_keeps_no_plain_secret() {
+        crate::vault::unlock_for_tests();
+        let mut profile = Profile {
+            id: "p1".to_string(),
+            name: "Lab".to_string(),
+            username: "alice".to_string(),
+            created_at: String::new(),
+            secret: Some(Secret {
+                password: "hunter2".to_string(),
+                app_key: "NSG_GUI-key".to_string(),
+            }),
+            sealed: None,
+        };
+        profile.seal().unwrap();
+        let json = serde_json::to_string(&profile).unwrap();
+        assert!(!json.contains("hunter2") && !json.contains("NSG_GUI-key"));
+        assert!(!profile.in_keychain());
+
+        let credentials = credentials(&profile).unwrap();
+        assert_eq!(credentials.password, "hunter2");
+        assert_eq!(credentials.app_key, "NSG_GUI-key");
+    }
+}
@@ -0,0 +1,90 @@
+// Progress event throttling.
+//
+// Transfers call back for every chunk, which on a fast link is thousands of
+// events a second, each one serialized and pushed through IPC into the
+// webview. A Gate lets one through per INTERVAL per transfer; updates in
+// between are coalesced, since every event carries the running totals and
+// the newest one says everything the skipped ones did. The last update is
+// always let through, so the UI ends on the exact final byte count.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// About ten events a second per transfer
+pub const INTERVAL: Duration = Duration::from_millis(100);
+
+/// Decides which updates of one transfer are emitted
+pub struct Gate {
+    last: Mutex<Option<Instant>>,
+}
+
+impl Gate {
+    pub fn new() -> Self {
+        Self {
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Whether to emit this update: the first one, the final one, and then
+    /// at most one per INTERVAL
+    pub fn due(&self, final_update: bool) -> bool {
+        let mut last = self.last.lock().unwrap();
+        if final_update || last.is_none_or(|t| t.elapsed() >= INTERVAL) {
+            *last = Some(Instant::now());
+            return true;
+        }
+        false
+    }
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_update_is_let_through_and_the_next_ones_wait() {
+        let gate = Gate::new();
+        assert!(gate.due(false));
+        assert!(!gate.due(false));
+        assert!(!gate.due(false));
+    }
+
+    #[test]
+    fn the_final_update_is_always_let_through() {
+        let gate = Gate::new();
+        assert!(gate.due(false));
+        assert!(gate.due(true));
+        assert!(gate.due(true));
+    }
+
+    #[test]
+    fn an_update_is_let_through_again_after_the_interval() {
+        let gate = Gate::new();
+        assert!(gate.due(false));
+        std::thread::sleep(INTERVAL + Duration::from_millis(10));
+        assert!(gate.due(false));
+        assert!(!gate.due(false));
+    }
+
+    #[test]
+    fn the_last_emitted_count_is_exact_despite_coalescing() {
+        let gate = Gate::new();
+        let total: u64 = 10_000_019;
+        let mut sent = 0;
+        let mut emitted = Vec::new();
+        while sent < total {
+            sent = (sent + 4096).min(total);
+            if gate.due(sent == total) {
+                emitted.push(sent);
+            }
+        }
+        assert!(emitted.len() < 10);
+        assert_eq!(emitted.last(), Some(&total));
+    }
+}
@@ -0,0 +1,279 @@
+// Local projects for grouping jobs.
+//
+// Projects exist only on this machine: projects.json holds the projects and
+// a job ID -> project ID association. Deleting a project only removes the
+// association, never a job, and can be undone for a while (see trash.rs).
+
+use crate::anonymize::real_job_id;
+use crate::trash::{self, LocalDelete, TrashKind, TrashMark, Trashed};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub const PROJECTS_FILE: &str = "projects.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProjectStore {
+    #[serde(default)]
+    pub projects: Vec<Project>,
+    // real job ID -> project ID
+    #[serde(default)]
+    pub assignments: BTreeMap<String, String>,
+    #[serde(default)]
+    pub trash: Vec<Trashed<DeletedProject>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedProject {
+    pub project: Project,
+    // The jobs that were in it, by real ID
+    pub jobs: Vec<String>,
+    // Where those jobs went; None when they became unassigned
+    pub reassigned_to: Option<String>,
+}
+
+impl Project {
+    pub fn new(name: String) -> Self {
+        Self {
+            id: format!(
+                "proj-{}",
+                chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+            ),
+            name,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+impl ProjectStore {
+    pub fn project_of(&self, job_id: &str) -> Option<String> {
+        self.assignments.get(job_id).cloned()
+    }
+
+    fn exists(&self, project_id: &str) -> bool {
+        self.projects.iter().any(|p| p.id == project_id)
+    }
+
+    /// The project named `name`, ignoring case
+    pub fn named(&self, name: &str) -> Option<&Project> {
+        self.projects
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    fn validate_name(&self, name: &str, except_id: Option<&str>) -> Result<String, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Project name can't be empty".to_string());
+        }
+        let taken = self
+            .projects
+            .iter()
+            .any(|p| Some(p.id.as_str()) != except_id && p.name.eq_ignore_ascii_case(name));
+        if taken {
+            return Err(format!("A project named \"{}\" already exists", name));
+        }
+        Ok(name.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectSummary {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub job_count: usize,
+}
+
+pub fn projects_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = crate::accounts::user_root(app)?;
+    Ok(dir.join(PROJECTS_FILE))
+}
+
+pub fn load(app: &tauri::AppHandle) -> Result<ProjectStore, String> {
+    crate::state_file::load(&projects_path(app)?)
+}
+
+/// Delete projects.json, returning whether there was one
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = projects_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    crate::paths::remove_file_if_exists(&path)
+}
+
+fn modify<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut ProjectStore) -> Result<T, String>,
+) -> Result<T, String> {
+    crate::state_file::modify(&projects_path(app)?, "projects", f)
+}
+
+/// Associate a job (by real ID) with a project, or clear its project
+pub fn assign(
+    app: &tauri::AppHandle,
+    job_id: &str,
+    project_id: Option<&str>,
+) -> Result<(), String> {
+    modify(app, |store| {
+        match project_id {
+            Some(project_id) => {
+                if !store.exists(project_id) {
+                    return Err(format!("No project with id {}", project_id));
+                }
+                store
+                    .assignments
+                    .insert(job_id.to_string(), project_id.to_string());
+            }
+            None => {
+                store.assignments.remove(job_id);
+            }
+        }
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub async fn list_projects(app: tauri::AppHandle) -> Result<Vec<ProjectSummary>, String> {
+    let store = load(&app)?;
+    Ok(store
+        .projects
+        .iter()
+        .map(|p| ProjectSummary {
+            id: p.id.clone(),
+            name: p.name.clone(),
+            created_at: p.created_at.clone(),
+            job_count: store.assignments.values().filter(|id| **id == p.id).count(),
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn create_project(app: tauri::AppHandle, name: String) -> Result<Project, String> {
+    create(&app, &name)
+}
+
+pub fn create(app: &tauri::AppHandle, name: &str) -> Result<Project, String> {
+    modify(app, |store| {
+        let name = store.validate_name(name, None)?;
+        let project = Project::new(name);
+        store.projects.push(project.clone());
+        Ok(project)
+    })
+}
+
+#[tauri::command]
+pub async fn rename_project(
+    app: tauri::AppHandle,
+    id: String,
+    name: String,
+) -> Result<Project, String> {
+    modify(&app, |store| {
+        let name = store.validate_name(&name, Some(&id))?;
+        let project = store
+            .projects
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| format!("No project with id {}", id))?;
+        project.name = name;
+        Ok(project.clone())
+    })
+}
+
+/// Put a deleted project back, with the jobs that haven't been moved since.
+/// Returns false when `token` no longer undoes anything.
+pub fn restore(app: &tauri::AppHandle, token: &str) -> Result<bool, String> {
+    modify(app, |store| {
+        let Some(deleted) = trash::take(&mut store.trash, token) else {
+            return Ok(false);
+        };
+        store.validate_name(&deleted.project.name, None)?;
+
+        for job_id in deleted.jobs {
+            if store.assignments.get(&job_id) == deleted.reassigned_to.as_ref() {
+                store.assignments.insert(job_id, deleted.project.id.clone());
+            }
+        }
+        store.projects.push(deleted.project);
+        Ok(true)
+    })
+}
+
+/// Drop deleted projects whose undo window has passed
+pub fn purge_trash(app: &tauri::AppHandle) -> Result<(), String> {
+    if !load(app)?.trash.iter().any(|t| t.mark.is_expired()) {
+        return Ok(());
+    }
+    modify(app, |store| {
+        store.trash.retain(|t| !t.mark.is_expired());
+        Ok(())
+    })
+}
+
+/// Delete a project. Its jobs move to `reassign_to`, or become unassigned.
+/// Can be undone with the returned token for a while.
+#[tauri::command]
+pub async fn delete_project(
+    app: tauri::AppHandle,
+    id: String,
+    reassign_to: Option<String>,
+) -> Result<LocalDelete, String> {
+    let mark = TrashMark::new(TrashKind::Project);
+    modify(&app, |store| {
+        if !store.exists(&id) {
+            return Err(format!("No project with id {}", id));
+        }
+        if let Some(target) = &reassign_to {
+            if *target == id || !store.exists(target) {
+                return Err(format!("Can't reassign jobs to project {}", target));
+            }
+        }
+
+        let jobs: Vec<String> = store
+            .assignments
+            .iter()
+            .filter(|(_, project_id)| **project_id == id)
+            .map(|(job_id, _)| job_id.clone())
+            .collect();
+        let index = store.projects.iter().position(|p| p.id == id);
+        if let Some(project) = index.map(|i| store.projects.remove(i)) {
+            store.trash.push(Trashed {
+                mark: mark.clone(),
+                item: DeletedProject {
+                    project,
+                    jobs,
+                    reassigned_to: reassign_to.clone(),
+                },
+            });
+        }
+        match &reassign_to {
+            Some(target) => {
+                for project_id in store.assignments.values_mut() {
+                    if *project_id == id {
+                        *project_id = target.clone();
+                    }
+                }
+            }
+            None => store.assignments.retain(|_, project_id| *project_id != id),
+        }
+        Ok(())
+    })?;
+
+    Ok(trash::deleted(&app, TrashKind::Project, &mark, 1))
+}
+
+/// Put a job into a project; `project_id: None` removes it from its project
+#[tauri::command]
+pub async fn assign_job_to_project(
+    app: tauri::AppHandle,
+    job_id: String,
+    project_id: Option<String>,
+) -> Result<(), String> {
+    assign(&app, &real_job_id(&job_id), project_id.as_deref())
+}
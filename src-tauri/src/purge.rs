@@ -0,0 +1,126 @@
+// Deleting everything the app has stored.
+//
+// purge_app_data removes the app's own state files and the saved
+// credentials, then resets what is held in memory, as if the app had just
+// been installed. Downloaded result archives live in the user's folders and
+// are never touched. Each item is reported separately so one failure doesn't
+// hide what did get removed.
+
+use crate::i18n::{t, Msg};
+use crate::monitor::MonitorSettings;
+use crate::{
+    history, i18n, integrity, job_cache, local_jobs, paths, preferences, projects, queue, staging,
+    submissions, throttle, thumbnails, AppState,
+};
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+
+/// What the user has to type to confirm
+pub const PURGE_PHRASE: &str = "DELETE EVERYTHING";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PurgeOutcome {
+    Removed,
+    NotPresent,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeItem {
+    pub item: &'static str,
+    pub outcome: PurgeOutcome,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeReport {
+    pub items: Vec<PurgeItem>,
+}
+
+impl PurgeReport {
+    fn record(&mut self, item: &'static str, result: Result<bool, String>) {
+        let (outcome, error) = match result {
+            Ok(true) => (PurgeOutcome::Removed, None),
+            Ok(false) => (PurgeOutcome::NotPresent, None),
+            Err(e) => (PurgeOutcome::Failed, Some(e)),
+        };
+        self.items.push(PurgeItem {
+            item,
+            outcome,
+            error,
+        });
+    }
+}
+
+/// Delete all app data and saved credentials. Only runs when
+/// `confirm_phrase` is exactly PURGE_PHRASE. Emits `app-data-purged` with
+/// the report so the frontend can go back to the first-run screen.
+#[tauri::command]
+pub async fn purge_app_data(
+    app: tauri::AppHandle,
+    confirm_phrase: String,
+) -> Result<PurgeReport, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionPurgeAppData)?;
+    if confirm_phrase != PURGE_PHRASE {
+        return Err(t(Msg::PurgeNotConfirmed, &[("phrase", PURGE_PHRASE)]));
+    }
+
+    // Read before the preferences naming the staging folder are deleted
+    let staging_roots = staging::roots(&app);
+    let uses_keychain = crate::keychain::uses_keychain(&app);
+    let purge_app = app.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        let app = purge_app;
+        let mut report = PurgeReport { items: Vec::new() };
+
+        // First, so the rest clears the app data folder's own state files
+        report.record("other_accounts", crate::accounts::purge(&app));
+        report.record("preferences", preferences::of(&app).purge());
+        report.record("download_history", history::purge(&app));
+        report.record("download_queue", queue::purge(&app));
+        report.record("queued_submissions", submissions::purge(&app));
+        report.record("unconfirmed_submissions", crate::unconfirmed::purge(&app));
+        report.record("projects", projects::purge(&app));
+        report.record("local_jobs", local_jobs::purge(&app));
+        report.record("job_cache", job_cache::purge(&app));
+        report.record("thumbnails", thumbnails::purge(&app));
+        report.record("crash_logs", crate::crash::purge(&app));
+        report.record("quarantined_state_files", integrity::purge(&app));
+        report.record("activity_feed", crate::activity_feed::purge(&app));
+        report.record("audit_log", crate::audit_log::purge(&app));
+        report.record(
+            "download_staging",
+            staging::remove_download_dirs(&staging_roots, |_| false, Duration::ZERO),
+        );
+        report.record(
+            "credentials_file",
+            paths::remove_file_if_exists(&crate::portable::credentials_path()),
+        );
+        report.record("profiles", crate::profiles::purge(&app));
+        report.record("vault", crate::vault::purge(&app));
+        if uses_keychain {
+            report.record("keychain_credentials", crate::keychain::purge());
+        }
+
+        report
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?;
+
+    // Back to first-run state in memory too
+    let state = app.state::<AppState>();
+    state.session.replace(None);
+    state.monitor.reset();
+    state.streams.clear();
+    crate::state_version::forget_all();
+    crate::session_status::changed(&app);
+    crate::background_errors::resolve_all(&app);
+    state.monitor.set_settings(MonitorSettings::default());
+    i18n::reset();
+    throttle::set_rate_limit_kbps(0);
+
+    let _ = app.emit(crate::events::APP_DATA_PURGED, &report);
+    Ok(report)
+}
@@ -0,0 +1,261 @@
+// Persisted download queue.
+//
+// Every download is recorded in download_queue.json while it runs and
+// removed once it finishes, so entries still in the file at startup were
+// interrupted by a quit or crash. They are offered back to the user through
+// `pending-downloads-found`, never resumed without consent.
+
+use crate::anonymize::{self, anonymize_url, Anonymize};
+use crate::download::{self, DownloadRequest};
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{Manager, State};
+
+pub const QUEUE_FILE: &str = "download_queue.json";
+
+// IDs of the entries left over from the previous run, until the user
+// resumes or discards them
+static PENDING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueStatus {
+    // Still running, or interrupted when found at startup
+    Downloading,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedDownload {
+    pub id: String,
+    #[serde(flatten)]
+    pub request: DownloadRequest,
+    pub status: QueueStatus,
+    #[serde(default)]
+    pub error: Option<String>,
+    pub added_at: String,
+}
+
+/// A leftover entry as offered to the user
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingDownload {
+    pub id: String,
+    pub job_url: String,
+    // None when the download rules pick the folder
+    pub output_dir: Option<String>,
+    pub delete_after_download: bool,
+    pub status: QueueStatus,
+    pub error: Option<String>,
+    pub added_at: String,
+    // The output directory is gone; resuming would recreate it elsewhere
+    // than the user expects, so these are skipped
+    pub output_dir_missing: bool,
+}
+
+impl Anonymize for PendingDownload {
+    fn anonymize(self) -> Self {
+        Self {
+            job_url: anonymize_url(&self.job_url),
+            ..self
+        }
+    }
+}
+
+fn queue_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = crate::accounts::user_root(app)?;
+    Ok(dir.join(QUEUE_FILE))
+}
+
+fn load_entries(app: &tauri::AppHandle) -> Result<Vec<QueuedDownload>, String> {
+    crate::state_file::load(&queue_path(app)?)
+}
+
+fn modify<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut Vec<QueuedDownload>) -> T,
+) -> Result<T, String> {
+    crate::state_file::modify(&queue_path(app)?, "download queue", |entries| {
+        Ok(f(entries))
+    })
+}
+
+/// Delete the queue file and forget the downloads left over from the
+/// previous run, returning whether there was a file
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = queue_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    *PENDING.lock().unwrap() = Some(HashSet::new());
+    crate::paths::remove_file_if_exists(&path)
+}
+
+/// Record a download that is about to start, returning its queue ID
+pub fn add(app: &tauri::AppHandle, request: &DownloadRequest) -> Result<String, String> {
+    let id = format!(
+        "q-{}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    );
+    let entry = QueuedDownload {
+        id: id.clone(),
+        request: request.clone(),
+        status: QueueStatus::Downloading,
+        error: None,
+        added_at: chrono::Utc::now().to_rfc3339(),
+    };
+    modify(app, |entries| entries.push(entry))?;
+    Ok(id)
+}
+
+pub fn set_status(
+    app: &tauri::AppHandle,
+    id: &str,
+    status: QueueStatus,
+    error: Option<String>,
+) -> Result<(), String> {
+    modify(app, |entries| {
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            entry.status = status;
+            entry.error = error;
+        }
+    })
+}
+
+pub fn remove(app: &tauri::AppHandle, id: &str) -> Result<(), String> {
+    modify(app, |entries| entries.retain(|e| e.id != id))
+}
+
+/// Remember what the previous run left behind. Called once at startup,
+/// before anything new is queued.
+pub fn load(app: &tauri::AppHandle) {
+    let entries = load_entries(app).unwrap_or_default();
+    *PENDING.lock().unwrap() = Some(entries.into_iter().map(|e| e.id).collect());
+}
+
+/// Job IDs of every queued download, finished or not
+pub fn queued_job_ids(app: &tauri::AppHandle) -> Vec<String> {
+    load_entries(app)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|e| {
+            e.request
+                .job_url
+                .rsplit('/')
+                .next()
+                .map(|id| id.to_string())
+        })
+        .collect()
+}
+
+fn pending_entries(app: &tauri::AppHandle) -> Result<Vec<QueuedDownload>, String> {
+    let pending = PENDING.lock().unwrap().clone().unwrap_or_default();
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(load_entries(app)?
+        .into_iter()
+        .filter(|e| pending.contains(&e.id))
+        .collect())
+}
+
+/// Emit `pending-downloads-found` if the previous run left downloads behind
+/// that the user hasn't acted on yet
+pub fn announce_pending(app: &tauri::AppHandle) {
+    let entries = match pending_entries(app) {
+        Ok(entries) if !entries.is_empty() => entries,
+        _ => return,
+    };
+
+    let pending: Vec<PendingDownload> = entries
+        .into_iter()
+        .map(|e| PendingDownload {
+            id: e.id,
+            job_url: e.request.job_url,
+            output_dir_missing: e
+                .request
+                .output_dir
+                .as_deref()
+                .is_some_and(|dir| !Path::new(dir).is_dir()),
+            output_dir: e.request.output_dir,
+            delete_after_download: e.request.delete_after_download,
+            status: e.status,
+            error: e.error,
+            added_at: e.added_at,
+        })
+        .collect();
+
+    anonymize::emit(app, crate::events::PENDING_DOWNLOADS_FOUND, pending);
+}
+
+/// Resume the downloads left over from the previous run, one after another in
+/// the background. Entries whose output directory is missing stay queued.
+/// Returns the number of downloads started.
+#[tauri::command]
+pub async fn resume_pending_downloads(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let session = state.session.require()?;
+
+    let entries: Vec<QueuedDownload> = pending_entries(&app)?
+        .into_iter()
+        .filter(|e| {
+            e.request
+                .output_dir
+                .as_deref()
+                .is_none_or(|dir| Path::new(dir).is_dir())
+        })
+        .collect();
+
+    if let Some(pending) = PENDING.lock().unwrap().as_mut() {
+        for entry in &entries {
+            pending.remove(&entry.id);
+        }
+    }
+
+    let count = entries.len();
+    let read_only = crate::mode::is_read_only(&app);
+    tauri::async_runtime::spawn_blocking(move || {
+        for entry in entries {
+            // Signed in elsewhere meanwhile: leave the rest queued
+            if !app
+                .state::<AppState>()
+                .session
+                .is_current(session.generation)
+            {
+                break;
+            }
+            // Resuming is the user's go-ahead, so skip the size confirmation
+            let request = DownloadRequest {
+                confirmed: true,
+                delete_after_download: entry.request.delete_after_download && !read_only,
+                ..entry.request
+            };
+            let _ = remove(&app, &entry.id);
+            let _ = download::run_queued(&app, session.credentials.clone(), request);
+        }
+    });
+
+    Ok(count)
+}
+
+/// Drop the downloads left over from the previous run, along with any
+/// partially downloaded files
+#[tauri::command]
+pub async fn discard_pending_downloads(app: tauri::AppHandle) -> Result<usize, String> {
+    let entries = pending_entries(&app)?;
+    let ids: HashSet<String> = entries.iter().map(|e| e.id.clone()).collect();
+
+    modify(&app, |queue| queue.retain(|e| !ids.contains(&e.id)))?;
+    *PENDING.lock().unwrap() = Some(HashSet::new());
+
+    for entry in &entries {
+        for temp_dir in download::temp_dirs_for(&app, &entry.request.job_url) {
+            let _ = std::fs::remove_dir_all(temp_dir);
+        }
+    }
+
+    Ok(entries.len())
+}
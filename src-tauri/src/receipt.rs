@@ -0,0 +1,159 @@
+// Submission receipts.
+//
+// With the `write_submission_receipt` preference on, each input of a
+// submitted job gets a `<input>.nsg-receipt.json` beside it recording the
+// job, what it was submitted with and the input's SHA-256, so a local file
+// can later be matched to its NSG job. Receipts hold real job IDs, as lab
+// records, and never credentials. A receipt that can't be written (say, a
+// read-only folder) never fails the submission.
+
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, Anonymize};
+use crate::i18n::Msg;
+use crate::inputs::InputBinding;
+use crate::{checksum, preferences};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const RECEIPT_KEY: &str = "write_submission_receipt";
+const RECEIPT_SUFFIX: &str = ".nsg-receipt.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmissionReceipt {
+    pub job_id: String,
+    pub job_url: String,
+    pub tool: String,
+    // Form fields other than metadata.*
+    pub params: BTreeMap<String, String>,
+    // metadata.* fields, without the prefix
+    pub metadata: BTreeMap<String, String>,
+    pub submitted_at: String,
+    // The input this receipt sits next to
+    pub param_name: String,
+    pub input_file: String,
+    pub input_sha256: String,
+}
+
+impl Anonymize for SubmissionReceipt {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            job_url: anonymize_url(&self.job_url),
+            ..self
+        }
+    }
+}
+
+pub fn is_enabled(app: &tauri::AppHandle) -> bool {
+    preferences::of(app)
+        .get(RECEIPT_KEY)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn receipt_path(input: &Path) -> PathBuf {
+    let mut name = input.as_os_str().to_os_string();
+    name.push(RECEIPT_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Write a receipt next to every input of a submitted job. Returns a
+/// warning naming the receipts that couldn't be written, if any.
+pub fn write(
+    inputs: &[InputBinding],
+    job_id: &str,
+    job_url: &str,
+    tool: &str,
+    params: &BTreeMap<String, String>,
+) -> Option<String> {
+    let (metadata, params): (BTreeMap<_, _>, BTreeMap<_, _>) = params
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .partition(|(k, _)| k.starts_with("metadata."));
+    let metadata = metadata
+        .into_iter()
+        .map(|(k, v)| (k["metadata.".len()..].to_string(), v))
+        .collect();
+    let submitted_at = chrono::Utc::now().to_rfc3339();
+
+    let mut receipt = SubmissionReceipt {
+        job_id: job_id.to_string(),
+        job_url: job_url.to_string(),
+        tool: tool.to_string(),
+        params,
+        metadata,
+        submitted_at,
+        param_name: String::new(),
+        input_file: String::new(),
+        input_sha256: String::new(),
+    };
+
+    let mut failures = Vec::new();
+    for input in inputs {
+        let path = receipt_path(&input.file_path);
+        let result = checksum::file_sha256(&input.file_path).and_then(|hash| {
+            receipt.param_name = input.param_name.clone();
+            receipt.input_file = input
+                .file_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            receipt.input_sha256 = hash;
+            let content = serde_json::to_string_pretty(&receipt)
+                .map_err(|e| format!("Failed to serialize receipt: {}", e))?;
+            std::fs::write(&path, content)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+        });
+        if let Err(e) = result {
+            failures.push(e);
+        }
+    }
+
+    (!failures.is_empty()).then(|| {
+        format!(
+            "The job was submitted, but its receipt could not be written: {}",
+            failures.join(", ")
+        )
+    })
+}
+
+/// Read the receipt of an input file. `path` may be the input itself or
+/// its receipt.
+#[tauri::command]
+pub async fn read_submission_receipt(
+    path: String,
+    app: tauri::AppHandle,
+) -> Result<Option<SubmissionReceipt>, String> {
+    let path = crate::path_access::authorize_str(&app, &path)?;
+    let path = if path.to_string_lossy().ends_with(RECEIPT_SUFFIX) {
+        path
+    } else {
+        receipt_path(&path)
+    };
+
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let receipt: SubmissionReceipt = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(anonymize::outgoing(&app, Some(receipt)))
+}
+
+#[tauri::command]
+pub async fn get_write_submission_receipt(app: tauri::AppHandle) -> Result<bool, String> {
+    Ok(is_enabled(&app))
+}
+
+#[tauri::command]
+pub async fn set_write_submission_receipt(
+    app: tauri::AppHandle,
+    enabled: bool,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeSubmissionSettings)?;
+
+    preferences::of(&app).set(RECEIPT_KEY, json!(enabled));
+    Ok(())
+}
@@ -0,0 +1,113 @@
+// Resumable result downloads.
+//
+// Result files are fetched into the job's staging folder, which is kept when
+// a download fails. OFFSETS_FILE next to them records, per entry, the size
+// the listing advertised and how many bytes are on disk. The next attempt,
+// whether a retry of the same call or a new download_results, cuts each
+// partial file back to its recorded offset and continues from there with a
+// Range request (see NsgApi::download_file). A file whose advertised size
+// changed, or that has no record, starts over, so bytes of an old version
+// are never joined to a new one. Offsets are saved at most once per
+// SAVE_INTERVAL while a file streams, and always when it stops.
+
+use nsg_cli::models::OutputFile;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const OFFSETS_FILE: &str = ".nsg_offsets.json";
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Partial {
+    // Advertised by the listing when the file was started
+    size: u64,
+    // Bytes known to be on disk
+    offset: u64,
+}
+
+/// Byte offsets of the files in one staging folder
+pub struct Offsets {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<String, Partial>>,
+    last_saved: Mutex<Option<Instant>>,
+}
+
+impl Offsets {
+    /// The offsets recorded in `temp_dir`, none if there are no readable ones
+    pub fn open(temp_dir: &Path) -> Self {
+        let path = temp_dir.join(OFFSETS_FILE);
+        let entries = std::fs::read(&path)
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            entries: Mutex::new(entries),
+            last_saved: Mutex::new(None),
+        }
+    }
+
+    /// Get `dest` ready to fetch `file` into, keeping only the bytes that
+    /// are recorded for the same version of it. Returns the offset the
+    /// download continues from.
+    pub fn prepare(&self, name: &str, file: &OutputFile, dest: &Path) -> Result<u64, String> {
+        let on_disk = std::fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+        let keep = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(name)
+            .filter(|p| p.size == file.size)
+            .map_or(0, |p| p.offset.min(on_disk));
+
+        if keep == 0 {
+            crate::paths::remove_file_if_exists(dest)?;
+        } else if on_disk > keep {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(dest)
+                .and_then(|f| f.set_len(keep))
+                .map_err(|e| format!("Failed to trim partial {}: {}", file.filename, e))?;
+        }
+
+        self.entries.lock().unwrap().insert(
+            name.to_string(),
+            Partial {
+                size: file.size,
+                offset: keep,
+            },
+        );
+        self.save();
+        Ok(keep)
+    }
+
+    /// Note that `offset` bytes of `name` are on disk, saving when
+    /// SAVE_INTERVAL has passed or `stopped` says the transfer ended
+    pub fn record(&self, name: &str, offset: u64, stopped: bool) {
+        if let Some(partial) = self.entries.lock().unwrap().get_mut(name) {
+            partial.offset = offset;
+        }
+        let due = stopped
+            || self
+                .last_saved
+                .lock()
+                .unwrap()
+                .is_none_or(|t| t.elapsed() >= SAVE_INTERVAL);
+        if due {
+            self.save();
+        }
+    }
+
+    // Best effort: without the file the next attempt starts the files over
+    fn save(&self) {
+        *self.last_saved.lock().unwrap() = Some(Instant::now());
+        let Ok(content) = serde_json::to_vec(&*self.entries.lock().unwrap()) else {
+            return;
+        };
+        let _ = crate::state_file::write_atomic(&self.path, &content);
+    }
+}
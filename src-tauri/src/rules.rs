@@ -0,0 +1,446 @@
+// Download folder rules.
+//
+// A download that wasn't given a folder goes to the most specific one
+// known: the folder last chosen for that job, then the one last chosen for
+// its project, then the first rule whose tool and/or project match, then
+// the global download folder. Rule targets may use `{tool}` and `{project}`,
+// which become subfolders created on demand, and a leading `~`. Remembered
+// folders that no longer exist are skipped and forgotten.
+
+use crate::anonymize::real_job_id;
+use crate::i18n::Msg;
+use crate::{paths, preferences};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+
+const RULES_KEY: &str = "download_rules";
+pub const JOB_DIRS_KEY: &str = "job_output_dirs";
+pub const PROJECT_DIRS_KEY: &str = "project_output_dirs";
+
+// Stands in for {project} when the job isn't in one
+const NO_PROJECT: &str = "Unassigned";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadRule {
+    // Tool ID, compared case-insensitively; None matches any tool
+    #[serde(default)]
+    pub tool: Option<String>,
+    // Local project ID; None matches any project
+    #[serde(default)]
+    pub project_id: Option<String>,
+    pub directory: String,
+}
+
+impl DownloadRule {
+    fn matches(&self, tool: Option<&str>, project_id: Option<&str>) -> bool {
+        let tool_matches = match (&self.tool, tool) {
+            (None, _) => true,
+            (Some(wanted), Some(tool)) => wanted.eq_ignore_ascii_case(tool),
+            (Some(_), None) => false,
+        };
+        let project_matches = match &self.project_id {
+            None => true,
+            Some(wanted) => Some(wanted.as_str()) == project_id,
+        };
+        tool_matches && project_matches
+    }
+}
+
+pub fn load(app: &tauri::AppHandle) -> Vec<DownloadRule> {
+    preferences::of(app)
+        .get(RULES_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// A placeholder value as a single, harmless path component
+fn path_component(value: &str) -> String {
+    let cleaned: String = value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_control() || "/\\:*?\"<>|".contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    match cleaned.trim_matches('.') {
+        "" => "_".to_string(),
+        _ => cleaned,
+    }
+}
+
+fn expand_home(directory: &str) -> Result<PathBuf, String> {
+    match directory
+        .strip_prefix("~/")
+        .or_else(|| (directory == "~").then_some(""))
+    {
+        Some(rest) => Ok(paths::home_dir()
+            .ok_or("Could not determine home directory")?
+            .join(rest)),
+        None => Ok(PathBuf::from(directory)),
+    }
+}
+
+fn expand(directory: &str, tool: &str, project: &str) -> Result<PathBuf, String> {
+    expand_home(
+        &directory
+            .replace("{tool}", &path_component(tool))
+            .replace("{project}", &path_component(project)),
+    )
+}
+
+/// Check that `dir`, or its closest existing ancestor if it doesn't exist
+/// yet, is a folder we can write to
+fn check_writable(dir: &Path) -> Result<(), String> {
+    let existing = dir
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| format!("No existing parent folder for {}", dir.display()))?;
+    if !existing.is_dir() {
+        return Err(format!("Not a folder: {}", existing.display()));
+    }
+
+    let probe = existing.join(format!(".nsg-gui-write-test-{}", std::process::id()));
+    std::fs::File::create(&probe)
+        .map_err(|e| format!("Can't write to {}: {}", existing.display(), e))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// The part of a rule target that is the same for every job, up to the
+/// folder holding the first placeholder
+fn fixed_part(directory: &str) -> Result<PathBuf, String> {
+    let fixed = match directory.find('{') {
+        Some(i) => directory[..i].trim_end_matches(|c| c != '/' && c != '\\'),
+        None => directory,
+    };
+    expand_home(fixed)
+}
+
+fn validate(
+    app: &tauri::AppHandle,
+    rule: &DownloadRule,
+    projects: &crate::projects::ProjectStore,
+) -> Result<(), String> {
+    if rule.tool.is_none() && rule.project_id.is_none() {
+        return Err(format!(
+            "The rule for {} matches every download; set the download folder instead",
+            rule.directory
+        ));
+    }
+    if rule.directory.trim().is_empty() {
+        return Err("A rule needs a target folder".to_string());
+    }
+    if let Some(project_id) = &rule.project_id {
+        if !projects.projects.iter().any(|p| p.id == *project_id) {
+            return Err(format!("No project with id {}", project_id));
+        }
+    }
+
+    let fixed = fixed_part(&rule.directory)?;
+    if !fixed.is_absolute() {
+        return Err(format!("Rule folder must be absolute: {}", rule.directory));
+    }
+    // Placeholders become single plain names, so they stay inside this
+    crate::path_access::authorize(app, &fixed)?;
+    check_writable(&fixed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirSource {
+    // Given with the download request
+    Explicit,
+    Job,
+    Project,
+    Rule,
+    Default,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepOutcome {
+    Used,
+    NotSet,
+    // Remembered, but the folder is gone; it was forgotten
+    Missing,
+}
+
+/// One level of the folder lookup, most specific first
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolutionStep {
+    pub source: DirSource,
+    pub directory: Option<String>,
+    pub outcome: StepOutcome,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedDir {
+    pub directory: PathBuf,
+    pub source: DirSource,
+    // Every level looked at, ending with the one used
+    pub chain: Vec<ResolutionStep>,
+}
+
+fn remembered(app: &tauri::AppHandle, key: &str, id: &str) -> Option<String> {
+    preferences::of(app)
+        .get(key)?
+        .get(id)?
+        .as_str()
+        .map(|s| s.to_string())
+}
+
+fn set_remembered(app: &tauri::AppHandle, key: &str, id: &str, dir: Option<&str>) {
+    let prefs = preferences::of(app);
+    let mut dirs = match prefs.get(key) {
+        Some(Value::Object(dirs)) => dirs,
+        _ => Map::new(),
+    };
+    match dir {
+        Some(dir) => dirs.insert(id.to_string(), json!(dir)),
+        None => dirs.remove(id),
+    };
+    prefs.set(key, Value::Object(dirs));
+}
+
+// The folders in a remembered-folder map
+fn remembered_dirs(dirs: Option<Value>) -> Vec<PathBuf> {
+    match dirs {
+        Some(Value::Object(dirs)) => dirs
+            .values()
+            .filter_map(|dir| dir.as_str().map(PathBuf::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// The fixed part of each rule target that is an absolute folder
+fn rule_dirs(rules: &[DownloadRule]) -> Vec<PathBuf> {
+    rules
+        .iter()
+        .filter_map(|rule| fixed_part(&rule.directory).ok())
+        .filter(|dir| dir.is_absolute())
+        .collect()
+}
+
+/// Every folder downloads are sent to without asking again: the remembered
+/// job and project folders and the rule targets
+pub fn persisted_dirs(app: &tauri::AppHandle) -> Vec<PathBuf> {
+    let prefs = preferences::of(app);
+    let mut dirs = remembered_dirs(prefs.get(JOB_DIRS_KEY));
+    dirs.extend(remembered_dirs(prefs.get(PROJECT_DIRS_KEY)));
+    dirs.extend(rule_dirs(&load(app)));
+    dirs
+}
+
+/// Remember a folder the user picked for a job, for that job and for its
+/// project if it has one
+pub fn remember(app: &tauri::AppHandle, job_id: &str, dir: &Path) {
+    let dir = dir.to_string_lossy();
+    set_remembered(app, JOB_DIRS_KEY, job_id, Some(&dir));
+    let project_id = crate::projects::load(app)
+        .ok()
+        .and_then(|store| store.project_of(job_id));
+    if let Some(project_id) = project_id {
+        set_remembered(app, PROJECT_DIRS_KEY, &project_id, Some(&dir));
+    }
+}
+
+fn finish(mut chain: Vec<ResolutionStep>, source: DirSource, directory: PathBuf) -> ResolvedDir {
+    chain.push(ResolutionStep {
+        source,
+        directory: Some(directory.to_string_lossy().to_string()),
+        outcome: StepOutcome::Used,
+    });
+    ResolvedDir {
+        directory,
+        source,
+        chain,
+    }
+}
+
+/// Look up the folder for a download of this job without creating it.
+/// `explicit` is the folder given with the request, if any.
+pub fn lookup(
+    app: &tauri::AppHandle,
+    explicit: Option<&str>,
+    tool: Option<&str>,
+    job_id: &str,
+) -> Result<ResolvedDir, String> {
+    let mut chain = Vec::new();
+    if let Some(dir) = explicit {
+        return Ok(finish(chain, DirSource::Explicit, PathBuf::from(dir)));
+    }
+
+    let store = crate::projects::load(app).unwrap_or_default();
+    let project_id = store.project_of(job_id);
+
+    for (source, key, id) in [
+        (DirSource::Job, JOB_DIRS_KEY, Some(job_id.to_string())),
+        (DirSource::Project, PROJECT_DIRS_KEY, project_id.clone()),
+    ] {
+        let dir = id.as_deref().and_then(|id| remembered(app, key, id));
+        match (dir, id) {
+            (Some(dir), _) if Path::new(&dir).is_dir() => {
+                return Ok(finish(chain, source, PathBuf::from(dir)));
+            }
+            (Some(dir), Some(id)) => {
+                set_remembered(app, key, &id, None);
+                chain.push(ResolutionStep {
+                    source,
+                    directory: Some(dir),
+                    outcome: StepOutcome::Missing,
+                });
+            }
+            _ => chain.push(ResolutionStep {
+                source,
+                directory: None,
+                outcome: StepOutcome::NotSet,
+            }),
+        }
+    }
+
+    let project_name = project_id
+        .as_deref()
+        .and_then(|id| store.projects.iter().find(|p| p.id == id))
+        .map(|p| p.name.clone());
+    let rule = load(app)
+        .into_iter()
+        .find(|rule| rule.matches(tool, project_id.as_deref()));
+    match rule {
+        Some(rule) => {
+            let dir = expand(
+                &rule.directory,
+                tool.unwrap_or("unknown-tool"),
+                project_name.as_deref().unwrap_or(NO_PROJECT),
+            )?;
+            Ok(finish(chain, DirSource::Rule, dir))
+        }
+        None => {
+            chain.push(ResolutionStep {
+                source: DirSource::Rule,
+                directory: None,
+                outcome: StepOutcome::NotSet,
+            });
+            let dir = crate::default_download_dir(app)?;
+            Ok(finish(chain, DirSource::Default, dir))
+        }
+    }
+}
+
+/// The folder a download of this job should go to, created if needed.
+/// `tool` and the job's project come from the job record and projects.json.
+pub fn resolve(
+    app: &tauri::AppHandle,
+    explicit: Option<&str>,
+    tool: Option<&str>,
+    job_id: &str,
+) -> Result<ResolvedDir, String> {
+    let resolved = lookup(app, explicit, tool, job_id)?;
+    std::fs::create_dir_all(&resolved.directory)
+        .map_err(|e| format!("Failed to create {}: {}", resolved.directory.display(), e))?;
+    Ok(resolved)
+}
+
+/// The folder to pre-fill the download dialog with for this job
+#[tauri::command]
+pub async fn get_last_output_dir(
+    job_id: String,
+    tool: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<ResolvedDir, String> {
+    lookup(&app, None, tool.as_deref(), &real_job_id(&job_id))
+}
+
+#[tauri::command]
+pub async fn get_download_rules(app: tauri::AppHandle) -> Result<Vec<DownloadRule>, String> {
+    Ok(load(&app))
+}
+
+/// Replace the rule list. Rules are checked in order; each target must be
+/// an absolute folder we can create and write to.
+#[tauri::command]
+pub async fn set_download_rules(
+    app: tauri::AppHandle,
+    rules: Vec<DownloadRule>,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+
+    let rules: Vec<DownloadRule> = rules
+        .into_iter()
+        .map(|rule| DownloadRule {
+            tool: rule
+                .tool
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty()),
+            project_id: rule.project_id.filter(|p| !p.trim().is_empty()),
+            directory: rule.directory.trim().to_string(),
+        })
+        .collect();
+    let projects = crate::projects::load(&app)?;
+    for rule in &rules {
+        validate(&app, rule, &projects)?;
+    }
+
+    preferences::of(&app).set(RULES_KEY, json!(rules));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(directory: &str) -> DownloadRule {
+        DownloadRule {
+            tool: Some("NEURON".to_string()),
+            project_id: None,
+            directory: directory.to_string(),
+        }
+    }
+
+    #[test]
+    fn the_fixed_part_stops_before_the_first_placeholder() {
+        assert_eq!(
+            fixed_part("/data/{project}/{tool}").unwrap(),
+            PathBuf::from("/data/")
+        );
+        assert_eq!(
+            fixed_part("/data/run-{tool}/out").unwrap(),
+            PathBuf::from("/data/")
+        );
+        assert_eq!(
+            fixed_part("/data/plain").unwrap(),
+            PathBuf::from("/data/plain")
+        );
+    }
+
+    #[test]
+    fn rule_dirs_skip_relative_targets() {
+        let rules = [rule("/data/{tool}"), rule("relative/{tool}"), rule("")];
+        assert_eq!(rule_dirs(&rules), vec![PathBuf::from("/data/")]);
+    }
+
+    #[test]
+    fn remembered_dirs_are_the_string_values() {
+        let dirs = json!({ "job-1": "/a", "job-2": "/b", "job-3": 7 });
+        let mut found = remembered_dirs(Some(dirs));
+        found.sort();
+        assert_eq!(found, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        assert!(remembered_dirs(Some(json!(["/a"]))).is_empty());
+        assert!(remembered_dirs(None).is_empty());
+    }
+
+    #[test]
+    fn placeholders_become_single_names() {
+        assert_eq!(path_component("../x"), ".._x");
+        assert_eq!(path_component(".."), "_");
+        assert_eq!(
+            expand("/data/{project}", "NEURON", "a/b").unwrap(),
+            PathBuf::from("/data/a_b")
+        );
+    }
+}
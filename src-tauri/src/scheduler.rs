@@ -0,0 +1,266 @@
+// Scheduler details for queued jobs.
+//
+// NSG mentions where a job was queued and what it asked for only in its
+// free-text job messages, and their wording differs between tools and
+// resources ("Submitted to Expanse as job 123", "nodes=2 ppn=24",
+// "runtime: 0.5 hours", ...). Extraction is keyword-based and best-effort:
+// anything not recognized is left as None rather than guessed.
+
+use crate::xml::{JobExtras, JobMessage};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchedulerInfo {
+    pub resource: Option<String>,
+    pub requested_nodes: Option<u32>,
+    pub requested_cores: Option<u32>,
+    // As written by the server, e.g. "0.5 hours" or "01:00:00"
+    pub requested_runtime: Option<String>,
+}
+
+/// What is known about a job's place in the queue
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueDetails {
+    pub scheduler_info: Option<SchedulerInfo>,
+    // Text of the latest queue-related message
+    pub queue_note: Option<String>,
+}
+
+// Lowercase token -> display name
+const KNOWN_RESOURCES: &[(&str, &str)] = &[
+    ("expanse", "Expanse"),
+    ("comet", "Comet"),
+    ("stampede", "Stampede"),
+    ("stampede2", "Stampede2"),
+    ("stampede3", "Stampede3"),
+    ("bridges", "Bridges"),
+    ("bridges2", "Bridges-2"),
+    ("bridges-2", "Bridges-2"),
+    ("anvil", "Anvil"),
+    ("delta", "Delta"),
+    ("frontera", "Frontera"),
+    ("aws", "AWS"),
+];
+
+const NODE_KEYWORDS: &[&str] = &["node", "nodes", "number_nodes", "num_nodes"];
+const CORE_KEYWORDS: &[&str] = &[
+    "core",
+    "cores",
+    "cpus",
+    "ppn",
+    "ntasks",
+    "tasks",
+    "processors",
+    "number_cores",
+    "tasks_per_node",
+];
+const RUNTIME_KEYWORDS: &[&str] = &["runtime", "walltime", "run_time", "wall_time"];
+const RUNTIME_UNITS: &[&str] = &[
+    "h", "hr", "hrs", "hour", "hours", "m", "min", "mins", "minute", "minutes",
+];
+
+/// Split a message into lowercase words, with `key=value` split in two and
+/// trailing punctuation dropped
+fn tokens(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| c.is_whitespace() || ",;()[]'\"".contains(c))
+        .flat_map(|word| word.split('='))
+        .map(|word| {
+            word.trim_start_matches('-')
+                .trim_end_matches(['.', ':'])
+                .to_string()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// A count written next to one of `keywords`: "nodes=2", "2 nodes",
+/// "nodes: 2"
+fn count_near(tokens: &[String], keywords: &[&str]) -> Option<u32> {
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        if !keywords.contains(&token.as_str()) {
+            return None;
+        }
+        let after = tokens.get(i + 1).and_then(|t| t.parse().ok());
+        let before = i.checked_sub(1).and_then(|j| tokens[j].parse().ok());
+        after.or(before).filter(|n| *n > 0)
+    })
+}
+
+fn is_duration(token: &str) -> bool {
+    !token.is_empty()
+        && token.chars().any(|c| c.is_ascii_digit())
+        && token
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == ':')
+}
+
+/// A runtime after a runtime keyword ("runtime 0.5 hours", "walltime=01:00:00"),
+/// or after "run time"/"wall time"
+fn runtime_near(tokens: &[String]) -> Option<String> {
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        let keyword = RUNTIME_KEYWORDS.contains(&token.as_str())
+            || (token == "time"
+                && i.checked_sub(1)
+                    .is_some_and(|j| tokens[j] == "run" || tokens[j] == "wall"));
+        if !keyword {
+            return None;
+        }
+        let value = tokens[i + 1..]
+            .iter()
+            .take(2)
+            .position(|t| is_duration(t))
+            .map(|offset| i + 1 + offset)?;
+        let unit = tokens
+            .get(value + 1)
+            .filter(|u| RUNTIME_UNITS.contains(&u.as_str()));
+        Some(match unit {
+            Some(unit) => format!("{} {}", tokens[value], unit),
+            None => tokens[value].clone(),
+        })
+    })
+}
+
+fn resource_in(tokens: &[String]) -> Option<String> {
+    tokens.iter().find_map(|token| {
+        KNOWN_RESOURCES
+            .iter()
+            .find(|(name, _)| *name == token)
+            .map(|(_, display)| display.to_string())
+    })
+}
+
+fn is_queue_message(message: &JobMessage) -> bool {
+    message
+        .stage
+        .as_deref()
+        .is_some_and(|s| s.eq_ignore_ascii_case("QUEUE"))
+        || message.text.to_lowercase().contains("queue")
+}
+
+/// Pull scheduler details out of a job's messages. Later messages win, so
+/// a resubmission to another resource is reflected.
+pub fn queue_details(extras: &JobExtras) -> QueueDetails {
+    let mut info = SchedulerInfo {
+        resource: extras.compute_resource.clone(),
+        ..Default::default()
+    };
+
+    for message in &extras.messages {
+        let tokens = tokens(&message.text);
+        if extras.compute_resource.is_none() {
+            if let Some(resource) = resource_in(&tokens) {
+                info.resource = Some(resource);
+            }
+        }
+        if let Some(nodes) = count_near(&tokens, NODE_KEYWORDS) {
+            info.requested_nodes = Some(nodes);
+        }
+        if let Some(cores) = count_near(&tokens, CORE_KEYWORDS) {
+            info.requested_cores = Some(cores);
+        }
+        if let Some(runtime) = runtime_near(&tokens) {
+            info.requested_runtime = Some(runtime);
+        }
+    }
+
+    let queue_note = extras
+        .messages
+        .iter()
+        .rev()
+        .find(|m| is_queue_message(m))
+        .map(|m| m.text.trim().to_string())
+        .filter(|text| !text.is_empty());
+
+    QueueDetails {
+        scheduler_info: (info != SchedulerInfo::default()).then_some(info),
+        queue_note,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extras(messages: &[(&str, &str)]) -> JobExtras {
+        JobExtras {
+            messages: messages
+                .iter()
+                .map(|(stage, text)| JobMessage {
+                    stage: (!stage.is_empty()).then(|| stage.to_string()),
+                    text: text.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn details_come_from_the_messages() {
+        let details = queue_details(&extras(&[
+            ("QUEUE", "Submitted to Expanse as job 4242."),
+            ("", "Requested nodes=2 ppn=24, walltime=01:30:00"),
+        ]));
+        assert_eq!(
+            details.scheduler_info,
+            Some(SchedulerInfo {
+                resource: Some("Expanse".to_string()),
+                requested_nodes: Some(2),
+                requested_cores: Some(24),
+                requested_runtime: Some("01:30:00".to_string()),
+            })
+        );
+        assert_eq!(
+            details.queue_note.as_deref(),
+            Some("Submitted to Expanse as job 4242.")
+        );
+    }
+
+    #[test]
+    fn counts_may_come_before_the_keyword_and_runtimes_keep_their_unit() {
+        let details = queue_details(&extras(&[("", "Running on 4 nodes, run time: 0.5 hours")]));
+        let info = details.scheduler_info.unwrap();
+        assert_eq!(info.requested_nodes, Some(4));
+        assert_eq!(info.requested_runtime.as_deref(), Some("0.5 hours"));
+        assert_eq!(info.requested_cores, None);
+    }
+
+    #[test]
+    fn later_messages_win_but_the_record_resource_is_kept() {
+        let mut job = extras(&[
+            ("", "Submitted to Comet with nodes=1"),
+            ("", "Resubmitted to Anvil with nodes=3"),
+        ]);
+        let info = queue_details(&job).scheduler_info.unwrap();
+        assert_eq!(info.resource.as_deref(), Some("Anvil"));
+        assert_eq!(info.requested_nodes, Some(3));
+
+        job.compute_resource = Some("EXPANSE".to_string());
+        let info = queue_details(&job).scheduler_info.unwrap();
+        assert_eq!(info.resource.as_deref(), Some("EXPANSE"));
+    }
+
+    #[test]
+    fn nothing_recognized_is_none() {
+        let details = queue_details(&extras(&[("", "Job created"), ("", "0 nodes")]));
+        assert_eq!(details, QueueDetails::default());
+        assert_eq!(
+            queue_details(&JobExtras::default()),
+            QueueDetails::default()
+        );
+    }
+
+    #[test]
+    fn the_note_is_the_latest_queue_message() {
+        let details = queue_details(&extras(&[
+            ("QUEUE", "Waiting in queue"),
+            ("", "Still in the queue, position 3"),
+            ("COMMANDRENDERING", "Rendering command"),
+        ]));
+        assert_eq!(
+            details.queue_note.as_deref(),
+            Some("Still in the queue, position 3")
+        );
+    }
+}
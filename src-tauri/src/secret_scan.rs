@@ -0,0 +1,269 @@
+// Looking for the NSG password and app key in submitted files.
+//
+// Whatever is uploaded sits in NSG's storage, so a script with the password
+// pasted into a comment leaks it. Before a submission each input is read,
+// and for zip inputs each entry, and the text ones are searched for the
+// session's password and app key. A hit blocks the submission with the file
+// and line; the secret itself is never echoed. Binary files and entries are
+// skipped, and only the first MAX_SCAN_BYTES of each are read, so a scan
+// stays quick even for big archives. On by default; `ignore_secret_scan` in
+// the submit options skips it for one submission.
+
+use crate::i18n::Msg;
+use crate::inputs::InputBinding;
+use crate::preferences;
+use nsg_cli::Credentials;
+use serde_json::json;
+use std::io::Read;
+use std::path::Path;
+
+pub const ENABLED_KEY: &str = "secret_scan_enabled";
+
+// Bytes read from each file or zip entry
+const MAX_SCAN_BYTES: u64 = 4 * 1024 * 1024;
+// Zip entries looked at per archive
+const MAX_ZIP_ENTRIES: usize = 10_000;
+// A NUL byte in this prefix marks the content as binary
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+// Shorter secrets match too much by chance to be worth blocking on
+const MIN_SECRET_LEN: usize = 6;
+
+pub fn enabled(app: &tauri::AppHandle) -> bool {
+    preferences::of(app)
+        .get(ENABLED_KEY)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
+
+/// Where a secret was found
+struct Finding {
+    secret: &'static str,
+    // The input file, plus the entry for a hit inside a zip
+    file: String,
+    line: usize,
+}
+
+impl Finding {
+    fn message(&self) -> String {
+        format!(
+            "Your NSG {} appears in {} on line {}. Remove it before submitting, or set ignore_secret_scan if this is a false positive.",
+            self.secret, self.file, self.line
+        )
+    }
+}
+
+/// Line number of the first occurrence of a secret in text content; None
+/// for binary content
+fn find_in(content: &[u8], secrets: &[(&'static str, &str)]) -> Option<(&'static str, usize)> {
+    let sniff = &content[..content.len().min(BINARY_SNIFF_BYTES)];
+    if sniff.contains(&0) {
+        return None;
+    }
+
+    secrets
+        .iter()
+        .filter_map(|(name, secret)| {
+            let offset = content
+                .windows(secret.len())
+                .position(|w| w == secret.as_bytes())?;
+            let line = content[..offset].iter().filter(|b| **b == b'\n').count() + 1;
+            Some((*name, line))
+        })
+        .min_by_key(|(_, line)| *line)
+}
+
+fn read_prefix(reader: impl Read) -> std::io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    reader.take(MAX_SCAN_BYTES).read_to_end(&mut content)?;
+    Ok(content)
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+fn scan_file(path: &Path, secrets: &[(&'static str, &str)]) -> Result<Option<Finding>, String> {
+    let display = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+    let open =
+        || std::fs::File::open(path).map_err(|e| format!("Failed to read {}: {}", display, e));
+
+    // An input that isn't a readable zip is scanned as a plain file
+    let archive = if is_zip(path) {
+        zip::ZipArchive::new(open()?).ok()
+    } else {
+        None
+    };
+    let Some(mut archive) = archive else {
+        let content =
+            read_prefix(open()?).map_err(|e| format!("Failed to read {}: {}", display, e))?;
+        return Ok(find_in(&content, secrets).map(|(secret, line)| Finding {
+            secret,
+            file: display,
+            line,
+        }));
+    };
+
+    for i in 0..archive.len().min(MAX_ZIP_ENTRIES) {
+        // Encrypted or unsupported entries can't be looked into
+        let Ok(entry) = archive.by_index(i) else {
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let Ok(content) = read_prefix(entry) else {
+            continue;
+        };
+        if let Some((secret, line)) = find_in(&content, secrets) {
+            return Ok(Some(Finding {
+                secret,
+                file: format!("{} ({})", name, display),
+                line,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Refuse inputs that contain the password or app key, naming the file and
+/// line. Blocking; call off the async runtime.
+pub fn check(inputs: &[InputBinding], credentials: &Credentials) -> Result<(), String> {
+    let secrets: Vec<(&'static str, &str)> = [
+        ("password", credentials.password.as_str()),
+        ("app key", credentials.app_key.as_str()),
+    ]
+    .into_iter()
+    .filter(|(_, secret)| secret.len() >= MIN_SECRET_LEN)
+    .collect();
+    if secrets.is_empty() {
+        return Ok(());
+    }
+
+    for input in inputs {
+        if let Some(finding) = scan_file(&input.file_path, &secrets)? {
+            return Err(finding.message());
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_secret_scan_enabled(app: tauri::AppHandle) -> Result<bool, String> {
+    Ok(enabled(&app))
+}
+
+#[tauri::command]
+pub async fn set_secret_scan_enabled(app: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeSubmissionSettings)?;
+    preferences::of(&app).set(ENABLED_KEY, json!(enabled));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    const PASSWORD: &str = "correct-horse-battery";
+    const APP_KEY: &str = "NSG_GUI-0123456789abcdef";
+
+    fn credentials(password: &str) -> Credentials {
+        Credentials {
+            username: "alice".to_string(),
+            password: password.to_string(),
+            app_key: APP_KEY.to_string(),
+        }
+    }
+
+    fn input(path: PathBuf) -> Vec<InputBinding> {
+        vec![InputBinding::primary(path)]
+    }
+
+    fn zip_with(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, content) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn the_first_hit_is_named_by_line() {
+        let content = format!("a\nkey = {}\npw = {}\n", APP_KEY, PASSWORD);
+        let secrets = [("password", PASSWORD), ("app key", APP_KEY)];
+        assert_eq!(find_in(content.as_bytes(), &secrets), Some(("app key", 2)));
+        assert_eq!(find_in(b"nothing here", &secrets), None);
+    }
+
+    #[test]
+    fn binary_content_is_skipped() {
+        let mut content = b"\0\x01\x02".to_vec();
+        content.extend_from_slice(PASSWORD.as_bytes());
+        assert_eq!(find_in(&content, &[("password", PASSWORD)]), None);
+    }
+
+    #[test]
+    fn a_secret_in_a_plain_input_blocks_without_being_echoed() {
+        let path = scratch("secret-scan", "plain").join("run.py");
+        std::fs::write(&path, format!("# login\n# {}\n", PASSWORD)).unwrap();
+        let error = check(&input(path), &credentials(PASSWORD)).unwrap_err();
+        assert!(
+            error.contains("password appears in run.py on line 2"),
+            "{}",
+            error
+        );
+        assert!(!error.contains(PASSWORD));
+    }
+
+    #[test]
+    fn zip_entries_are_scanned_one_by_one() {
+        let path = scratch("secret-scan", "zip").join("input.zip");
+        zip_with(
+            &path,
+            &[
+                ("model/clean.hoc", b"create soma\n"),
+                ("model/init.py", format!("\n\n{}\n", APP_KEY).as_bytes()),
+            ],
+        );
+        let error = check(&input(path), &credentials(PASSWORD)).unwrap_err();
+        assert!(
+            error.contains("app key appears in model/init.py (input.zip) on line 3"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn clean_inputs_and_short_secrets_pass() {
+        let dir = scratch("secret-scan", "clean");
+        let path = dir.join("input.zip");
+        zip_with(&path, &[("model/init.py", b"print('hi')\n")]);
+        assert!(check(&input(path), &credentials(PASSWORD)).is_ok());
+
+        // Too short to block on, even though it's there
+        let short = dir.join("short.txt");
+        std::fs::write(&short, "pw\n").unwrap();
+        let credentials = Credentials {
+            app_key: "k".to_string(),
+            ..credentials("pw")
+        };
+        assert!(check(&input(short), &credentials).is_ok());
+    }
+
+    #[test]
+    fn a_zip_that_does_not_open_is_scanned_as_a_file() {
+        let path = scratch("secret-scan", "fake-zip").join("notes.zip");
+        std::fs::write(&path, PASSWORD).unwrap();
+        let error = check(&input(path), &credentials(PASSWORD)).unwrap_err();
+        assert!(error.contains("notes.zip on line 1"), "{}", error);
+    }
+}
@@ -0,0 +1,622 @@
+use crate::anonymize::anonymize_username;
+use crate::api::{
+    app_key_problems, AppKeyProblem, ConnectPhase, CredentialCulprit, NsgApi, ProbeError,
+};
+use crate::credentials_file::LoadedCredentials;
+use crate::i18n::{t, LocalizedMessage, Msg};
+use crate::preferences;
+use crate::AppState;
+use nsg_cli::Credentials;
+use serde::Serialize;
+use serde_json::json;
+use std::future::Future;
+use std::sync::Mutex;
+use tauri::{Emitter, Manager, State};
+
+/// Prefix of the error returned when the credentials changed while a command
+/// was running, so the frontend can drop the result instead of showing it
+pub const SESSION_CHANGED_ERROR: &str = "SessionChanged";
+
+#[derive(Default)]
+struct SessionInner {
+    credentials: Option<Credentials>,
+    generation: u64,
+    // Ticket of the newest connect, disconnect or other change started
+    latest_change: u64,
+}
+
+/// The current credentials plus a generation number that moves on every
+/// change. Work that outlives an await snapshots both and checks the
+/// generation before using its result, so a connect or disconnect in the
+/// meantime can't leak one account's data into another's session.
+///
+/// A connect takes a ticket with begin_change before probing the server
+/// and stores its credentials with commit, which refuses once a newer
+/// change has begun. Whatever the user did last wins, however long each
+/// probe takes.
+#[derive(Default)]
+pub struct SessionState {
+    inner: Mutex<SessionInner>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionSnapshot {
+    pub credentials: Credentials,
+    pub generation: u64,
+}
+
+impl SessionState {
+    pub fn credentials(&self) -> Option<Credentials> {
+        self.inner.lock().unwrap().credentials.clone()
+    }
+
+    pub fn snapshot(&self) -> Option<SessionSnapshot> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .credentials
+            .clone()
+            .map(|credentials| SessionSnapshot {
+                credentials,
+                generation: inner.generation,
+            })
+    }
+
+    /// The snapshot, or a `NotConnected` error
+    pub fn require(&self) -> Result<SessionSnapshot, String> {
+        self.snapshot().ok_or_else(|| t(Msg::NotConnected, &[]))
+    }
+
+    /// Start a change of credentials that will be committed later,
+    /// superseding every change begun before it
+    pub fn begin_change(&self) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        inner.latest_change += 1;
+        inner.latest_change
+    }
+
+    /// Store the credentials of the change with `ticket`, invalidating all
+    /// earlier snapshots. Returns false, changing nothing, when a newer
+    /// change has begun since.
+    pub fn commit(&self, ticket: u64, credentials: Option<Credentials>) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.latest_change != ticket {
+            return false;
+        }
+        inner.credentials = credentials;
+        inner.generation += 1;
+        true
+    }
+
+    /// Whether no change has begun since the one with `ticket`
+    pub fn is_latest_change(&self, ticket: u64) -> bool {
+        self.inner.lock().unwrap().latest_change == ticket
+    }
+
+    /// Swap the credentials right away, invalidating all earlier snapshots
+    /// and superseding any connect still in progress
+    pub fn replace(&self, credentials: Option<Credentials>) {
+        let ticket = self.begin_change();
+        self.commit(ticket, credentials);
+    }
+
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.inner.lock().unwrap().generation == generation
+    }
+
+    /// Fail with a `SessionChanged` error if the credentials changed since
+    /// the snapshot with this generation was taken
+    pub fn ensure_current(&self, generation: u64) -> Result<(), String> {
+        if !self.is_current(generation) {
+            return Err(format!(
+                "{}: {}",
+                SESSION_CHANGED_ERROR,
+                t(Msg::SessionChanged, &[])
+            ));
+        }
+        Ok(())
+    }
+
+    /// Snapshot the session, run `fetch` for it and return its result along
+    /// with the snapshot, or a `SessionChanged` error if the credentials
+    /// changed while the fetch was in flight
+    pub async fn fetch_current<T, F, Fut>(&self, fetch: F) -> Result<(SessionSnapshot, T), String>
+    where
+        F: FnOnce(SessionSnapshot) -> Fut,
+        Fut: Future<Output = Result<T, String>>,
+    {
+        let snapshot = self.require()?;
+        let value = fetch(snapshot.clone()).await?;
+        self.ensure_current(snapshot.generation)?;
+        Ok((snapshot, value))
+    }
+}
+
+/// A failed connect, tagged with the phase it got stuck in, or a connect
+/// superseded by a later connect or disconnect, which says nothing about
+/// its credentials
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectError {
+    // None when superseded
+    phase: Option<ConnectPhase>,
+    // Set when the credentials were rejected
+    culprit: Option<CredentialCulprit>,
+    superseded: bool,
+    // key, params and the localized message
+    #[serde(flatten)]
+    pub message: LocalizedMessage,
+}
+
+impl ConnectError {
+    fn superseded() -> Self {
+        Self {
+            phase: None,
+            culprit: None,
+            superseded: true,
+            message: LocalizedMessage::new(Msg::SessionChanged, &[]),
+        }
+    }
+}
+
+/// Run the staged probe, emitting `connect-progress` as each phase begins
+async fn probe(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    creds: Credentials,
+) -> Result<(), (ConnectPhase, ProbeError)> {
+    let _ = app.emit(
+        crate::events::CONNECT_PROGRESS,
+        json!({ "phase": ConnectPhase::BuildingClient }),
+    );
+    let client = state.client.client().await.map_err(|e| {
+        (
+            ConnectPhase::BuildingClient,
+            ProbeError::Other(e.to_string()),
+        )
+    })?;
+    let progress_app = app.clone();
+    tokio::task::spawn_blocking(move || {
+        NsgApi::connect_staged(client, creds, |phase| {
+            let _ = progress_app.emit(crate::events::CONNECT_PROGRESS, json!({ "phase": phase }));
+        })
+        .map(|_| ())
+    })
+    .await
+    .unwrap_or_else(|e| {
+        Err((
+            ConnectPhase::BuildingClient,
+            ProbeError::Other(format!("Task error: {}", e)),
+        ))
+    })
+}
+
+#[tauri::command]
+pub async fn connect(
+    username: String,
+    password: String,
+    app_key: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<String, ConnectError> {
+    let creds = Credentials {
+        username: username.clone(),
+        password,
+        app_key,
+    };
+
+    let ticket = state.session.begin_change();
+    probe(&app, &state, creds.clone())
+        .await
+        .map_err(|(phase, e)| ConnectError {
+            phase: Some(phase),
+            culprit: match e {
+                ProbeError::Unauthorized(_, culprit) => Some(culprit),
+                _ => None,
+            },
+            superseded: false,
+            message: LocalizedMessage::new(
+                Msg::ConnectFailed,
+                &[("phase", &phase.label()), ("error", &e.to_string())],
+            ),
+        })?;
+
+    // Store credentials in state, unless a later connect or disconnect
+    // began while this one was probing
+    if !state.session.commit(ticket, Some(creds)) {
+        return Err(ConnectError::superseded());
+    }
+    crate::accounts::on_connect(&app, &username);
+    state.monitor.reset();
+    crate::session_status::changed(&app);
+    crate::background_errors::resolve_all(&app);
+    crate::email::check_stored_default(&app);
+
+    Ok(t(
+        Msg::ConnectedAs,
+        &[("username", &anonymize_username(&username))],
+    ))
+}
+
+/// Forget the session's credentials. The saved credentials file is kept.
+#[tauri::command]
+pub async fn disconnect(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    state.session.replace(None);
+    crate::accounts::on_disconnect(&app);
+    state.monitor.reset();
+    state.streams.clear();
+    crate::session_status::changed(&app);
+    crate::background_errors::resolve_all(&app);
+    let _ = app.emit(crate::events::DISCONNECTED, ());
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoConnectStart {
+    // false when auto-connect is disabled or no credentials are saved
+    attempting: bool,
+    // Saved username, for pre-filling the login form
+    username: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoConnectFailure {
+    // The server rejected the saved credentials - offer to delete them
+    BadCredentials,
+    // The server could not be reached - offer to retry
+    Network,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoConnectFailed {
+    username: String,
+    reason: AutoConnectFailure,
+    phase: ConnectPhase,
+    message: String,
+}
+
+fn auto_connect_enabled(app: &tauri::AppHandle) -> Result<bool, String> {
+    let prefs = preferences::of(app);
+
+    // Defaults to on; it only does anything when credentials are saved
+    Ok(prefs
+        .get("auto_connect")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true))
+}
+
+/// Connect in the background with the saved credentials.
+///
+/// Returns immediately; the outcome arrives as a `connected` or
+/// `auto-connect-failed` event. The frontend calls this once on mount, after
+/// its listeners are registered.
+#[tauri::command]
+pub async fn auto_connect(app: tauri::AppHandle) -> Result<AutoConnectStart, String> {
+    let loaded = {
+        let app = app.clone();
+        tokio::task::spawn_blocking(move || crate::keychain::load_saved(&app))
+            .await
+            .map_err(|e| format!("Task error: {}", e))?
+    };
+    let creds = match loaded {
+        LoadedCredentials::Found { credentials } => credentials,
+        _ => {
+            return Ok(AutoConnectStart {
+                attempting: false,
+                username: None,
+            })
+        }
+    };
+
+    let username = creds.username.clone();
+    if !auto_connect_enabled(&app)? {
+        return Ok(AutoConnectStart {
+            attempting: false,
+            username: Some(username),
+        });
+    }
+
+    let task_app = app.clone();
+    let ticket = app.state::<AppState>().session.begin_change();
+    tauri::async_runtime::spawn(async move {
+        let probed = probe(&task_app, &task_app.state::<AppState>(), creds.clone()).await;
+        match probed {
+            Ok(()) => {
+                let state = task_app.state::<AppState>();
+                // A connect or disconnect by the user since then wins
+                if !state.session.commit(ticket, Some(creds.clone())) {
+                    return;
+                }
+                crate::accounts::on_connect(&task_app, &creds.username);
+                state.monitor.reset();
+                crate::session_status::changed(&task_app);
+                crate::background_errors::resolve_all(&task_app);
+                crate::email::check_stored_default(&task_app);
+
+                let username = anonymize_username(&creds.username);
+                let _ = task_app.emit(
+                    crate::events::CONNECTED,
+                    json!({
+                        "username": username,
+                        "message": t(Msg::ConnectedAs, &[("username", &username)]),
+                    }),
+                );
+            }
+            Err((phase, e)) => {
+                // Nothing to report once the user connected or disconnected
+                if !task_app
+                    .state::<AppState>()
+                    .session
+                    .is_latest_change(ticket)
+                {
+                    return;
+                }
+                let reason = match e {
+                    ProbeError::Unauthorized(_, _) => AutoConnectFailure::BadCredentials,
+                    ProbeError::Network(_) => AutoConnectFailure::Network,
+                    ProbeError::Other(_) => AutoConnectFailure::Other,
+                };
+
+                let _ = task_app.emit(
+                    crate::events::AUTO_CONNECT_FAILED,
+                    AutoConnectFailed {
+                        username: creds.username.clone(),
+                        reason,
+                        phase,
+                        message: e.to_string(),
+                    },
+                );
+            }
+        }
+    });
+
+    Ok(AutoConnectStart {
+        attempting: true,
+        username: Some(username),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppKeyFormat {
+    valid: bool,
+    problems: Vec<AppKeyProblem>,
+}
+
+/// Catch obvious paste errors in the app key field before connecting. Pass
+/// the password to also catch it being pasted as the key.
+#[tauri::command]
+pub async fn validate_app_key_format(
+    key: String,
+    password: Option<String>,
+) -> Result<AppKeyFormat, String> {
+    let problems = app_key_problems(&key, password.as_deref());
+    Ok(AppKeyFormat {
+        valid: problems.is_empty(),
+        problems,
+    })
+}
+
+#[tauri::command]
+pub async fn get_auto_connect(app: tauri::AppHandle) -> Result<bool, String> {
+    auto_connect_enabled(&app)
+}
+
+#[tauri::command]
+pub async fn set_auto_connect(app: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeConnectionSettings)?;
+
+    let prefs = preferences::of(&app);
+
+    prefs.set("auto_connect", json!(enabled));
+
+    Ok(())
+}
+
+/// Remove the saved credentials file, e.g. after auto-connect reported them
+/// as rejected by the server
+#[tauri::command]
+pub async fn delete_saved_credentials(app: tauri::AppHandle) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionDeleteSavedCredentials)?;
+
+    if crate::keychain::uses_keychain(&app) {
+        return tokio::task::spawn_blocking(|| {
+            crate::keychain::delete(crate::keychain::DEFAULT_ACCOUNT)
+        })
+        .await
+        .map_err(|e| format!("Task error: {}", e))?;
+    }
+
+    let path = crate::portable::credentials_path();
+    if !path.exists() {
+        return Ok(());
+    }
+
+    std::fs::remove_file(&path).map_err(|e| format!("Failed to delete credentials file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn creds(username: &str) -> Credentials {
+        Credentials {
+            username: username.to_string(),
+            password: "secret".to_string(),
+            app_key: "key".to_string(),
+        }
+    }
+
+    // A listing through fetch_current whose fetch returns the snapshot's
+    // account's jobs after running `meanwhile`. Returns the jobs with the
+    // account committed when the check passed.
+    fn list_jobs(
+        session: &SessionState,
+        meanwhile: impl FnOnce(),
+    ) -> Result<(Vec<String>, Option<String>), String> {
+        let (_, jobs) =
+            tauri::async_runtime::block_on(session.fetch_current(|snapshot| async move {
+                meanwhile();
+                Ok(vec![format!("job-of-{}", snapshot.credentials.username)])
+            }))?;
+        let committed = session.credentials().map(|c| c.username);
+        Ok((jobs, committed))
+    }
+
+    #[test]
+    fn a_superseded_connect_blames_no_phase_or_credential() {
+        let error = serde_json::to_value(ConnectError::superseded()).unwrap();
+        assert_eq!(error["superseded"], true);
+        assert!(error["phase"].is_null());
+        assert!(error["culprit"].is_null());
+    }
+
+    #[test]
+    fn a_later_change_supersedes_a_pending_connect() {
+        let session = SessionState::default();
+        let auto_connect = session.begin_change();
+        let manual = session.begin_change();
+        assert!(session.commit(manual, Some(creds("manual"))));
+        assert!(!session.commit(auto_connect, Some(creds("auto"))));
+        assert_eq!(session.credentials().unwrap().username, "manual");
+    }
+
+    #[test]
+    fn a_disconnect_during_a_connect_wins() {
+        let session = SessionState::default();
+        let connect = session.begin_change();
+        session.replace(None);
+        assert!(!session.commit(connect, Some(creds("late"))));
+        assert!(session.credentials().is_none());
+        assert!(!session.is_latest_change(connect));
+    }
+
+    #[test]
+    fn an_uncontested_connect_commits() {
+        let session = SessionState::default();
+        let before = session.snapshot().map(|s| s.generation);
+        let connect = session.begin_change();
+        assert!(session.is_latest_change(connect));
+        assert!(session.commit(connect, Some(creds("alice"))));
+        let snapshot = session.require().unwrap();
+        assert_eq!(snapshot.credentials.username, "alice");
+        assert_ne!(Some(snapshot.generation), before);
+    }
+
+    #[test]
+    fn a_change_invalidates_earlier_snapshots() {
+        let session = SessionState::default();
+        session.replace(Some(creds("alice")));
+        let snapshot = session.require().unwrap();
+        session.replace(Some(creds("bob")));
+        let error = session.ensure_current(snapshot.generation).unwrap_err();
+        assert!(error.starts_with(SESSION_CHANGED_ERROR));
+    }
+
+    #[test]
+    fn a_listing_overtaken_by_a_switch_is_dropped() {
+        let session = SessionState::default();
+        session.replace(Some(creds("alice")));
+        for round in 0..10 {
+            let snapshot = session.require().unwrap();
+            // The slow request; every other round the user switches
+            // accounts or disconnects while it is in flight
+            if round % 2 == 1 {
+                if round % 4 == 1 {
+                    session.replace(Some(creds("bob")));
+                } else {
+                    session.replace(None);
+                    session.replace(Some(creds("alice")));
+                }
+            }
+            let result = session.ensure_current(snapshot.generation);
+            assert_eq!(result.is_ok(), round % 2 == 0, "round {}", round);
+        }
+    }
+
+    #[test]
+    fn a_listing_only_returns_the_committed_accounts_jobs() {
+        let switches: [&dyn Fn(&SessionState); 4] = [
+            &|_| {},
+            &|s| s.replace(Some(creds("bob"))),
+            &|s| s.replace(None),
+            &|s| {
+                s.replace(None);
+                s.replace(Some(creds("alice")));
+            },
+        ];
+        for (i, switch) in switches.iter().enumerate() {
+            let session = SessionState::default();
+            session.replace(Some(creds("alice")));
+            let result = list_jobs(&session, || switch(&session));
+            match result {
+                Ok((jobs, committed)) => {
+                    assert_eq!(i, 0, "case {} returned {:?}", i, jobs);
+                    assert_eq!(jobs, vec![format!("job-of-{}", committed.unwrap())]);
+                }
+                Err(e) => assert!(e.starts_with(SESSION_CHANGED_ERROR), "case {}", i),
+            }
+        }
+    }
+
+    #[test]
+    fn slow_listings_never_return_another_accounts_jobs() {
+        let session = Arc::new(SessionState::default());
+        session.replace(Some(creds("alice")));
+        // Held by the toggler while it switches and by the lister from the
+        // check until it has read the committed account
+        let switching = Arc::new(Mutex::new(()));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let toggler = {
+            let session = session.clone();
+            let switching = switching.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut round = 0;
+                while !done.load(Ordering::SeqCst) {
+                    let user = if round % 2 == 0 { "bob" } else { "alice" };
+                    {
+                        let _switching = switching.lock().unwrap();
+                        session.replace(Some(creds(user)));
+                    }
+                    std::thread::sleep(Duration::from_micros(150));
+                    round += 1;
+                }
+            })
+        };
+
+        let mut dropped = 0;
+        for round in 0..5000 {
+            if round >= 300 && dropped > 0 {
+                break;
+            }
+            let switching = &switching;
+            let fetch = session.fetch_current(|snapshot| async move {
+                let jobs = vec![format!("job-of-{}", snapshot.credentials.username)];
+                std::thread::sleep(Duration::from_micros(100));
+                Ok((jobs, switching.lock().unwrap()))
+            });
+            let result = tauri::async_runtime::block_on(fetch)
+                .map(|(_, (jobs, _switching))| (jobs, session.credentials().map(|c| c.username)));
+            match result {
+                Ok((jobs, committed)) => {
+                    assert_eq!(jobs, vec![format!("job-of-{}", committed.unwrap())]);
+                }
+                Err(_) => dropped += 1,
+            }
+        }
+        done.store(true, Ordering::SeqCst);
+        toggler.join().unwrap();
+        // Switches did land mid-fetch, so the check was exercised
+        assert!(dropped > 0);
+
+        // Without changes in flight the listing goes through
+        session.replace(Some(creds("carol")));
+        let (jobs, committed) = list_jobs(&session, || {}).unwrap();
+        assert_eq!(committed.as_deref(), Some("carol"));
+        assert_eq!(jobs, ["job-of-carol"]);
+    }
+}
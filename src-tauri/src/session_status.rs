@@ -0,0 +1,172 @@
+// The status line.
+//
+// One summary of the session for the always-visible status bar: who is
+// connected, how many jobs are running and downloads active, when the job
+// list was last refreshed and how NSG is doing. It's put together from the
+// session, the monitor, the running downloads and the announcements feed
+// whenever one of them calls `changed`, and goes out as `session-status`
+// when it differs from the last one sent, at most once per EMIT_INTERVAL;
+// a change inside the interval is sent when it ends.
+
+use crate::announcements::Severity;
+use crate::anonymize::{self, anonymize_username, Anonymize};
+use crate::AppState;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+const EMIT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceHealth {
+    Disconnected,
+    // Connected, but the job list hasn't been fetched yet
+    Unknown,
+    Online,
+    Offline,
+}
+
+/// Payload of `session-status`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionStatus {
+    pub username: Option<String>,
+    // Jobs the monitor tracks that haven't finished
+    pub jobs_running: usize,
+    pub downloads_active: usize,
+    // RFC 3339; None before the first refresh of the session
+    pub last_refresh_at: Option<String>,
+    pub health: ServiceHealth,
+    // Most severe announcement in effect that isn't dismissed
+    pub announcement: Option<Severity>,
+}
+
+impl Anonymize for SessionStatus {
+    fn anonymize(self) -> Self {
+        Self {
+            username: self.username.as_deref().map(anonymize_username),
+            ..self
+        }
+    }
+}
+
+#[derive(Default)]
+struct TrackerInner {
+    // Session generation and time of the last successful job list refresh
+    last_refresh: Option<(u64, String)>,
+    sent: Option<SessionStatus>,
+    sent_at: Option<Instant>,
+    // An emit is already waiting for the interval to end
+    scheduled: bool,
+}
+
+#[derive(Default)]
+pub struct StatusTracker {
+    inner: Mutex<TrackerInner>,
+}
+
+fn current(app: &tauri::AppHandle, last_refresh: Option<&(u64, String)>) -> SessionStatus {
+    let state = app.state::<AppState>();
+    let session = state.session.snapshot();
+    let last_refresh_at = session.as_ref().and_then(|s| {
+        last_refresh
+            .filter(|(generation, _)| *generation == s.generation)
+            .map(|(_, at)| at.clone())
+    });
+    let health = match &session {
+        None => ServiceHealth::Disconnected,
+        Some(_) if state.monitor.is_offline() => ServiceHealth::Offline,
+        Some(_) if last_refresh_at.is_some() => ServiceHealth::Online,
+        Some(_) => ServiceHealth::Unknown,
+    };
+
+    SessionStatus {
+        username: session.map(|s| s.credentials.username),
+        jobs_running: state.monitor.running_jobs(),
+        downloads_active: crate::download::active_downloads(),
+        last_refresh_at,
+        health,
+        announcement: crate::announcements::highest_severity(app),
+    }
+}
+
+/// Send the status if it differs from the last one sent
+fn send(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let last_refresh = state.status.inner.lock().unwrap().last_refresh.clone();
+    let status = current(app, last_refresh.as_ref());
+
+    let mut inner = state.status.inner.lock().unwrap();
+    if inner.sent.as_ref() == Some(&status) {
+        return;
+    }
+    inner.sent = Some(status.clone());
+    inner.sent_at = Some(Instant::now());
+    drop(inner);
+    anonymize::emit(app, crate::events::SESSION_STATUS, status);
+}
+
+/// Something the status is made of changed
+pub fn changed(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let wait = {
+        let mut inner = state.status.inner.lock().unwrap();
+        if inner.scheduled {
+            return;
+        }
+        let wait = inner
+            .sent_at
+            .map(|t| EMIT_INTERVAL.saturating_sub(t.elapsed()))
+            .unwrap_or_default();
+        if !wait.is_zero() {
+            inner.scheduled = true;
+        }
+        wait
+    };
+
+    if wait.is_zero() {
+        send(app);
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(wait).await;
+        app.state::<AppState>()
+            .status
+            .inner
+            .lock()
+            .unwrap()
+            .scheduled = false;
+        send(&app);
+    });
+}
+
+/// The job list was fetched. Called by the monitor.
+pub fn refreshed(app: &tauri::AppHandle, generation: u64) {
+    let at = crate::clock::now().to_rfc3339();
+    app.state::<AppState>()
+        .status
+        .inner
+        .lock()
+        .unwrap()
+        .last_refresh = Some((generation, at));
+    changed(app);
+}
+
+/// The status right now, for the first render
+#[tauri::command]
+pub async fn get_session_status(app: tauri::AppHandle) -> Result<SessionStatus, String> {
+    let last_refresh = app
+        .state::<AppState>()
+        .status
+        .inner
+        .lock()
+        .unwrap()
+        .last_refresh
+        .clone();
+    Ok(anonymize::outgoing(
+        &app,
+        current(&app, last_refresh.as_ref()),
+    ))
+}
@@ -0,0 +1,139 @@
+// Per-tool download size statistics.
+//
+// One fixed size warning doesn't fit every tool: phylogenetics results are
+// a few megabytes, NEURON runs routinely produce gigabytes. Every completed
+// download records its tool and final archive size in the history. Once a
+// tool has MIN_SAMPLES of them, downloads only ask for confirmation above
+// ADAPTIVE_FACTOR times that tool's 90th percentile, and never below
+// ADAPTIVE_FLOOR_BYTES. Until then, or with `adaptive_download_warning` off,
+// the fixed `max_download_warn_bytes` applies.
+
+use crate::history::{self, DownloadHistoryEntry};
+use crate::i18n::Msg;
+use crate::preferences;
+use serde::Serialize;
+use serde_json::json;
+
+pub const ADAPTIVE_KEY: &str = "adaptive_download_warning";
+
+const MIN_SAMPLES: usize = 5;
+const ADAPTIVE_FACTOR: u64 = 3;
+const ADAPTIVE_FLOOR_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeStats {
+    pub tool: String,
+    pub samples: usize,
+    // None without any samples
+    pub median_bytes: Option<u64>,
+    pub p90_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdSource {
+    Fixed,
+    Adaptive,
+}
+
+/// The size above which a download needs confirmation, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct WarnThreshold {
+    pub bytes: u64,
+    pub source: ThresholdSource,
+    pub reason: String,
+}
+
+/// Nearest-rank percentile of sorted, non-empty `values`
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+pub fn stats(history: &[DownloadHistoryEntry], tool: &str) -> SizeStats {
+    let mut sizes: Vec<u64> = history
+        .iter()
+        .filter(|e| {
+            e.tool
+                .as_deref()
+                .is_some_and(|t| t.eq_ignore_ascii_case(tool))
+        })
+        .filter_map(|e| e.archive_bytes)
+        .collect();
+    sizes.sort_unstable();
+
+    SizeStats {
+        tool: tool.to_string(),
+        samples: sizes.len(),
+        median_bytes: (!sizes.is_empty()).then(|| percentile(&sizes, 50)),
+        p90_bytes: (!sizes.is_empty()).then(|| percentile(&sizes, 90)),
+    }
+}
+
+pub fn is_adaptive(app: &tauri::AppHandle) -> bool {
+    preferences::of(app)
+        .get(ADAPTIVE_KEY)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
+
+/// The threshold for a download of `tool`'s results. `fixed_bytes` is the
+/// max_download_warn_bytes preference; 0 turns the warning off entirely.
+pub fn threshold(app: &tauri::AppHandle, tool: Option<&str>, fixed_bytes: u64) -> WarnThreshold {
+    let fixed = |reason: String| WarnThreshold {
+        bytes: fixed_bytes,
+        source: ThresholdSource::Fixed,
+        reason,
+    };
+
+    if fixed_bytes == 0 {
+        return fixed("The size warning is turned off".to_string());
+    }
+    if !is_adaptive(app) {
+        return fixed("Adaptive size warnings are turned off".to_string());
+    }
+    let Some(tool) = tool else {
+        return fixed("The job's tool is unknown".to_string());
+    };
+
+    let history = history::load(app).unwrap_or_default();
+    let stats = stats(&history, tool);
+    match stats.p90_bytes {
+        Some(p90) if stats.samples >= MIN_SAMPLES => WarnThreshold {
+            bytes: (p90 * ADAPTIVE_FACTOR).max(ADAPTIVE_FLOOR_BYTES),
+            source: ThresholdSource::Adaptive,
+            reason: format!(
+                "{}× the 90th percentile ({} bytes) of your {} {} downloads, at least {} bytes",
+                ADAPTIVE_FACTOR, p90, stats.samples, tool, ADAPTIVE_FLOOR_BYTES
+            ),
+        },
+        _ => fixed(format!(
+            "Only {} earlier {} downloads; {} are needed for an adaptive threshold",
+            stats.samples, tool, MIN_SAMPLES
+        )),
+    }
+}
+
+#[tauri::command]
+pub async fn get_download_size_stats(
+    tool: String,
+    app: tauri::AppHandle,
+) -> Result<SizeStats, String> {
+    Ok(stats(&history::load(&app)?, &tool))
+}
+
+#[tauri::command]
+pub async fn get_adaptive_download_warning(app: tauri::AppHandle) -> Result<bool, String> {
+    Ok(is_adaptive(&app))
+}
+
+#[tauri::command]
+pub async fn set_adaptive_download_warning(
+    app: tauri::AppHandle,
+    enabled: bool,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+
+    preferences::of(&app).set(ADAPTIVE_KEY, json!(enabled));
+    Ok(())
+}
@@ -0,0 +1,162 @@
+// Where temporary files go.
+//
+// Downloads collect their files in an `nsg_download_<job>` folder before
+// zipping them, which for multi-gigabyte results can fill a small system
+// disk. The `staging_dir` preference moves that to another volume; unset,
+// or when the folder disappeared, the system temp directory is used. Every
+// temporary artifact goes through `staging_path`. The folder is chosen
+// when a transfer starts, so changing the setting never moves a running
+// one; sweeps look in both places for leftovers from before a change.
+
+use crate::i18n::Msg;
+use crate::{paths, preferences};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const STAGING_KEY: &str = "staging_dir";
+pub const DOWNLOAD_PREFIX: &str = "nsg_download_";
+
+// Leftovers younger than this may belong to another running instance
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+fn configured(app: &tauri::AppHandle) -> Option<PathBuf> {
+    preferences::of(app)
+        .get(STAGING_KEY)
+        .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The folder temporary files go into right now
+pub fn staging_root(app: &tauri::AppHandle) -> PathBuf {
+    configured(app)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Path of a temporary file or folder named `name`
+pub fn staging_path(app: &tauri::AppHandle, name: &str) -> PathBuf {
+    staging_root(app).join(name)
+}
+
+/// Every folder temporary files may have been left in: the configured one
+/// and the system temp directory
+pub fn roots(app: &tauri::AppHandle) -> Vec<PathBuf> {
+    let mut roots = vec![std::env::temp_dir()];
+    if let Some(dir) = configured(app).filter(|dir| dir.is_dir()) {
+        if !roots.contains(&dir) {
+            roots.push(dir);
+        }
+    }
+    roots
+}
+
+/// The staging folders a job's download may have used
+pub fn download_dirs(app: &tauri::AppHandle, job_id: &str) -> Vec<PathBuf> {
+    roots(app)
+        .into_iter()
+        .map(|root| root.join(format!("{}{}", DOWNLOAD_PREFIX, job_id)))
+        .collect()
+}
+
+/// Check that a folder exists and files can be created in it
+fn validate(dir: &Path) -> Result<(), String> {
+    if !dir.is_absolute() {
+        return Err(format!("Not an absolute path: {}", dir.display()));
+    }
+    if !dir.is_dir() {
+        return Err(format!("Not a folder: {}", dir.display()));
+    }
+
+    let probe = dir.join(format!(".nsg_gui_write_test_{}", std::process::id()));
+    std::fs::write(&probe, b"").map_err(|e| format!("Can't write to {}: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Delete download staging folders in `roots`. Folders whose job `keep`
+/// returns true for, or that changed within `min_age`, are left alone.
+/// Returns whether anything was deleted.
+pub fn remove_download_dirs(
+    roots: &[PathBuf],
+    keep: impl Fn(&str) -> bool,
+    min_age: Duration,
+) -> Result<bool, String> {
+    let mut removed = false;
+    let mut errors = Vec::new();
+    for root in roots {
+        let Ok(entries) = std::fs::read_dir(root) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(job_id) = name.to_str().and_then(|n| n.strip_prefix(DOWNLOAD_PREFIX)) else {
+                continue;
+            };
+            if keep(job_id) || !entry.path().is_dir() {
+                continue;
+            }
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.elapsed().ok())
+                .unwrap_or_default();
+            if age < min_age {
+                continue;
+            }
+            match std::fs::remove_dir_all(entry.path()) {
+                Ok(()) => removed = true,
+                Err(e) => errors.push(format!("{}: {}", entry.path().display(), e)),
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(format!("Failed to delete {}", errors.join(", ")));
+    }
+    Ok(removed)
+}
+
+/// Delete staging folders no queued download can resume from. Called once
+/// at startup, after the queue is loaded.
+pub fn sweep_orphans(app: &tauri::AppHandle) {
+    let queued = crate::queue::queued_job_ids(app);
+    if let Err(e) = remove_download_dirs(
+        &roots(app),
+        |job_id| queued.iter().any(|id| id == job_id),
+        ORPHAN_MIN_AGE,
+    ) {
+        crate::background_errors::report(
+            app,
+            "staging_sweep",
+            crate::background_errors::ErrorKind::Other,
+            format!("Failed to clean up staging folders: {}", e),
+        );
+    }
+}
+
+/// The configured staging folder; None when the system temp directory is
+/// used
+#[tauri::command]
+pub async fn get_staging_dir(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    configured(&app)
+        .map(|dir| paths::to_ipc_string(&dir))
+        .transpose()
+}
+
+/// Set the staging folder; None goes back to the system temp directory.
+/// Downloads already running keep the folder they started with.
+#[tauri::command]
+pub async fn set_staging_dir(app: tauri::AppHandle, dir: Option<String>) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+
+    let dir = dir.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+    if let Some(dir) = &dir {
+        crate::path_access::authorize_str(&app, dir)?;
+        validate(Path::new(dir))?;
+    }
+    preferences::of(&app).set(STAGING_KEY, json!(dir.unwrap_or_default()));
+    Ok(())
+}
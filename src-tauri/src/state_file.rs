@@ -0,0 +1,278 @@
+// Locking and writing the JSON state files.
+//
+// Two copies of the app can run at once (portable and installed, or with
+// the single-instance check off), and both read, change and write back the
+// same files. Every read-modify-write of a state file holds an advisory
+// lock on `<file>.lock` next to it, which other threads and other processes
+// wait for, up to LOCK_TIMEOUT; after that the operation fails with a
+// STATE_LOCKED_ERROR instead of hanging. The lock lives in its own file
+// because writes go to a temporary file that is renamed over the real one,
+// which replaces the file a lock on it would be held on.
+//
+// The state modules go through load and modify, which take the lock, run
+// the file through state_version and write it back atomically. A file that
+// doesn't parse is an error for load; modify quarantines it first, as the
+// startup integrity pass does, so the change doesn't write over what it held.
+
+use crate::i18n::{t, Msg};
+use fs2::FileExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Prefix of the error returned when another instance held a state file
+/// for too long, so the frontend can offer to retry
+pub const STATE_LOCKED_ERROR: &str = "StateLocked";
+
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Held while a state file is read and written; unlocks when dropped
+pub struct StateFileLock {
+    file: File,
+}
+
+impl Drop for StateFileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Lock a state file against other threads and other instances of the app
+pub fn lock(path: &Path) -> Result<StateFileLock, String> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| format!("Invalid state file path: {}", path.display()))?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create app data dir: {}", e))?;
+
+    let lock_path = dir.join(format!("{}.lock", file_name(path)));
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("Failed to open {}: {}", lock_path.display(), e))?;
+
+    // Each open file has its own lock, so threads of this process wait for
+    // each other too
+    let started = Instant::now();
+    while file.try_lock_exclusive().is_err() {
+        if started.elapsed() >= LOCK_TIMEOUT {
+            return Err(format!(
+                "{}: {}",
+                STATE_LOCKED_ERROR,
+                t(Msg::StateLocked, &[("file", &file_name(path))])
+            ));
+        }
+        std::thread::sleep(RETRY_INTERVAL);
+    }
+    Ok(StateFileLock { file })
+}
+
+/// Replace a state file's contents through a temporary file, so a crash
+/// mid-write leaves the old contents. Call with the file's lock held.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let name = file_name(path);
+    let dir = path
+        .parent()
+        .ok_or_else(|| format!("Invalid state file path: {}", path.display()))?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create app data dir: {}", e))?;
+
+    let temp = dir.join(format!("{}.tmp", name));
+    let mut file = File::create(&temp).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    file.write_all(content)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    drop(file);
+
+    std::fs::rename(&temp, path).map_err(|e| format!("Failed to save {}: {}", name, e))
+}
+
+/// A state file's contents, the default when it is missing. Fails when it
+/// is there but doesn't parse. Call with the file's lock held.
+pub fn read<S: Default + DeserializeOwned>(path: &Path) -> Result<S, String> {
+    crate::state_version::read(path)
+        .map(Option::unwrap_or_default)
+        .map_err(|e| format!("{} is corrupt: {}", file_name(path), e))
+}
+
+/// Read a state file under its lock
+pub fn load<S: Default + DeserializeOwned>(path: &Path) -> Result<S, String> {
+    let _lock = lock(path)?;
+    read(path)
+}
+
+/// Change a state file under its lock, writing it back unless `f` fails.
+/// `what` names its contents in errors.
+pub fn modify<S, T>(
+    path: &Path,
+    what: &str,
+    f: impl FnOnce(&mut S) -> Result<T, String>,
+) -> Result<T, String>
+where
+    S: Default + Serialize + DeserializeOwned,
+{
+    let _lock = lock(path)?;
+    let mut state = match read(path) {
+        Ok(state) => state,
+        // Written by a newer build, which wrap won't write over anyway
+        Err(e) if crate::state_version::is_read_only(path) => return Err(e),
+        Err(e) => {
+            crate::integrity::quarantine_corrupt(path, e)?;
+            S::default()
+        }
+    };
+    let result = f(&mut state)?;
+
+    let content = serde_json::to_vec_pretty(&crate::state_version::wrap(path, &state)?)
+        .map_err(|e| format!("Failed to serialize {}: {}", what, e))?;
+    write_atomic(path, &content)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch;
+    use std::sync::mpsc;
+
+    #[test]
+    fn a_second_lock_waits_for_the_first() {
+        let path = scratch("state-file", "wait").join("jobs.json");
+        let held = lock(&path).unwrap();
+
+        let (sent, received) = mpsc::channel();
+        let waiter_path = path.clone();
+        let waiter = std::thread::spawn(move || {
+            let started = Instant::now();
+            let _lock = lock(&waiter_path).unwrap();
+            sent.send(started.elapsed()).unwrap();
+        });
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(received.try_recv().is_err());
+
+        drop(held);
+        let waited = received.recv().unwrap();
+        assert!(waited >= Duration::from_millis(200));
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        const UPDATES: u64 = 50;
+        let path = scratch("state-file", "concurrent").join("counter.json");
+
+        let workers: Vec<_> = (0..2)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..UPDATES {
+                        modify(&path, "counter", |count: &mut u64| {
+                            *count += 1;
+                            Ok(())
+                        })
+                        .unwrap();
+                        // The lock isn't fair; without a pause one worker
+                        // can starve the other past LOCK_TIMEOUT
+                        std::thread::sleep(RETRY_INTERVAL);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(load::<u64>(&path).unwrap(), 2 * UPDATES);
+    }
+
+    #[test]
+    fn a_missing_file_is_the_default() {
+        let path = scratch("state-file", "missing").join("jobs.json");
+        assert_eq!(load::<Vec<u32>>(&path).unwrap(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn a_corrupt_file_is_set_aside_before_a_change() {
+        let dir = scratch("state-file", "corrupt");
+        let path = dir.join("jobs.json");
+        std::fs::write(&path, "[1, 2").unwrap();
+        assert!(load::<Vec<u32>>(&path).is_err());
+
+        modify(&path, "jobs", |jobs: &mut Vec<u32>| {
+            jobs.push(3);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(load::<Vec<u32>>(&path).unwrap(), [3]);
+        let copies: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| {
+                e.file_name()
+                    .to_string_lossy()
+                    .starts_with("jobs.json.corrupt-")
+            })
+            .collect();
+        assert_eq!(copies.len(), 1);
+        assert_eq!(std::fs::read_to_string(copies[0].path()).unwrap(), "[1, 2");
+    }
+
+    #[test]
+    fn a_failed_change_writes_nothing() {
+        let path = scratch("state-file", "refused").join("jobs.json");
+        modify(&path, "jobs", |jobs: &mut Vec<u32>| {
+            jobs.push(1);
+            Err::<(), _>("no".to_string())
+        })
+        .unwrap_err();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn a_lock_held_too_long_fails_as_state_locked() {
+        let path = scratch("state-file", "timeout").join("jobs.json");
+        let _held = lock(&path).unwrap();
+        let error = lock(&path).err().unwrap();
+        assert!(error.starts_with(STATE_LOCKED_ERROR), "{}", error);
+    }
+
+    #[test]
+    fn the_lock_is_a_separate_file() {
+        let dir = scratch("state-file", "lockfile");
+        let path = dir.join("jobs.json");
+        let _held = lock(&path).unwrap();
+        assert!(dir.join("jobs.json.lock").exists());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn write_atomic_replaces_the_contents() {
+        let dir = scratch("state-file", "replace");
+        let path = dir.join("nested").join("jobs.json");
+        write_atomic(&path, b"[1]").unwrap();
+        write_atomic(&path, b"[1, 2]").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"[1, 2]");
+        assert!(!dir.join("nested").join("jobs.json.tmp").exists());
+    }
+
+    #[test]
+    fn a_failed_write_keeps_the_old_contents() {
+        let dir = scratch("state-file", "failed");
+        let path = dir.join("jobs.json");
+        write_atomic(&path, b"old").unwrap();
+        // The temporary file can't be created where a folder is in the way
+        std::fs::create_dir_all(dir.join("jobs.json.tmp")).unwrap();
+        assert!(write_atomic(&path, b"new").is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+    }
+}
@@ -0,0 +1,349 @@
+// Schema versions of the JSON state files.
+//
+// Each state file is written as `{"schema": N, "data": ...}`. A file from
+// before versioning, just the data, is version 0. Reading a file runs it
+// through its migrations, one per version step, up to the version this
+// build writes; the startup integrity pass also writes the upgraded file
+// back. A file with a newer version than this build knows, left by a later
+// release before a downgrade, is read as well as it can be (fields this
+// build doesn't know are ignored) but never written: `wrap` refuses it, so
+// the newer data survives, and `state-version-mismatch` is emitted once.
+// Going back to the release that wrote it picks it up where it was.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::Emitter;
+
+use crate::{history, job_cache, local_jobs, projects, queue, submissions, unconfirmed};
+
+// Upgrades the data of one version to the next
+type Migration = fn(Value) -> Result<Value, String>;
+
+// Migrations of each versioned file, from version 0 upwards; the number of
+// migrations is the version written
+const SCHEMAS: &[(&str, &[Migration])] = &[
+    (history::HISTORY_FILE, &[unversioned]),
+    (queue::QUEUE_FILE, &[unversioned]),
+    (submissions::SUBMISSIONS_FILE, &[unversioned]),
+    (projects::PROJECTS_FILE, &[unversioned]),
+    (local_jobs::LOCAL_JOBS_FILE, &[unversioned]),
+    (job_cache::JOB_CACHE_FILE, &[unversioned]),
+    (unconfirmed::UNCONFIRMED_FILE, &[unversioned]),
+];
+
+/// Version 0 to 1: the data is unchanged, only wrapped
+fn unversioned(data: Value) -> Result<Value, String> {
+    Ok(data)
+}
+
+/// Payload of `state-version-mismatch`
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionMismatch {
+    pub file: String,
+    pub version: u32,
+    pub supported: u32,
+}
+
+// Files found with a newer version, kept read-only
+static NEWER: Mutex<BTreeMap<PathBuf, VersionMismatch>> = Mutex::new(BTreeMap::new());
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    schema: u32,
+    data: T,
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+}
+
+fn migrations(name: &str) -> Option<&'static [Migration]> {
+    SCHEMAS
+        .iter()
+        .find(|(file, _)| *file == name)
+        .map(|(_, migrations)| *migrations)
+}
+
+/// The version `name` is written with; 0 for files that aren't versioned
+pub fn current(name: &str) -> u32 {
+    migrations(name).map_or(0, |m| m.len() as u32)
+}
+
+/// A file's contents split into its version and data
+fn split(content: &[u8]) -> Result<(u32, Value), String> {
+    let mut value: Value = serde_json::from_slice(content).map_err(|e| e.to_string())?;
+    let schema = match &value {
+        Value::Object(map) if map.len() == 2 && map.contains_key("data") => map
+            .get("schema")
+            .and_then(|s| s.as_u64())
+            .and_then(|s| u32::try_from(s).ok()),
+        _ => None,
+    };
+    match schema {
+        Some(schema) => Ok((schema, value["data"].take())),
+        None => Ok((0, value)),
+    }
+}
+
+/// What a versioned file holds
+pub struct Decoded {
+    pub version: u32,
+    // Migrated to the current version, unless newer
+    pub data: Value,
+}
+
+/// Read `content` of the file `name`, migrating it to the current version
+pub fn decode(name: &str, content: &[u8]) -> Result<Decoded, String> {
+    let (version, mut data) = split(content)?;
+    if let Some(migrations) = migrations(name) {
+        for (step, migrate) in migrations.iter().enumerate().skip(version as usize) {
+            data = migrate(data).map_err(|e| {
+                format!("Failed to upgrade {} to version {}: {}", name, step + 1, e)
+            })?;
+        }
+    }
+    Ok(Decoded { version, data })
+}
+
+/// Parse `content` of the file `name` as `T`, whatever its version
+pub fn parse<T: DeserializeOwned>(name: &str, content: &[u8]) -> Result<T, String> {
+    let decoded = decode(name, content)?;
+    serde_json::from_value(decoded.data).map_err(|e| e.to_string())
+}
+
+/// Remember that `path` is newer than this build and keep it read-only,
+/// emitting `state-version-mismatch` the first time
+fn note_newer(path: &Path, version: u32) {
+    let name = file_name(path);
+    let mismatch = VersionMismatch {
+        file: name.to_string(),
+        version,
+        supported: current(name),
+    };
+    let first = NEWER
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), mismatch.clone())
+        .is_none();
+    if first {
+        if let Some(app) = APP.get() {
+            let _ = app.emit(crate::events::STATE_VERSION_MISMATCH, mismatch);
+        }
+    }
+}
+
+/// Read and parse a state file; None when it is missing. Fails when it
+/// can't be read, doesn't parse or a migration fails.
+pub fn read<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    let name = file_name(path);
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", name, e)),
+    };
+    let decoded = decode(name, &content)?;
+    if decoded.version > current(name) {
+        note_newer(path, decoded.version);
+    }
+    serde_json::from_value(decoded.data)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+// What wrap returns: files without migrations stay plain data
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Wrapped<'a, T> {
+    Versioned(Versioned<&'a T>),
+    Plain(&'a T),
+}
+
+/// `data` ready to be written to `path` at the current version. Fails for a
+/// file kept read-only because a newer build wrote it.
+pub fn wrap<'a, T: Serialize>(path: &Path, data: &'a T) -> Result<impl Serialize + 'a, String> {
+    if let Some(mismatch) = NEWER.lock().unwrap().get(path) {
+        return Err(format!(
+            "{} was written by a newer version of the app (version {}, this one reads up to {}) and is kept read-only",
+            mismatch.file, mismatch.version, mismatch.supported
+        ));
+    }
+    let name = file_name(path);
+    if migrations(name).is_none() {
+        return Ok(Wrapped::Plain(data));
+    }
+    Ok(Wrapped::Versioned(Versioned {
+        schema: current(name),
+        data,
+    }))
+}
+
+/// Forget that `path` was newer, after it was deleted
+pub fn forget(path: &Path) {
+    NEWER.lock().unwrap().remove(path);
+}
+
+/// Forget every newer file, after the app's data was purged
+pub fn forget_all() {
+    NEWER.lock().unwrap().clear();
+}
+
+/// Whether `path` is kept read-only
+pub fn is_read_only(path: &Path) -> bool {
+    NEWER.lock().unwrap().contains_key(path)
+}
+
+/// How the startup pass left a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upgrade {
+    Current,
+    Migrated { from: u32 },
+    Newer { version: u32 },
+}
+
+/// Bring the file at `path` to the current version on disk. Call with
+/// content that is known to parse.
+pub fn upgrade(path: &Path, content: &[u8]) -> Result<Upgrade, String> {
+    let name = file_name(path);
+    let decoded = decode(name, content)?;
+    let current = current(name);
+    if decoded.version > current {
+        note_newer(path, decoded.version);
+        return Ok(Upgrade::Newer {
+            version: decoded.version,
+        });
+    }
+    if decoded.version == current || migrations(name).is_none() {
+        return Ok(Upgrade::Current);
+    }
+
+    let _lock = crate::state_file::lock(path)?;
+    let content = serde_json::to_vec_pretty(&wrap(path, &decoded.data)?)
+        .map_err(|e| format!("Failed to serialize {}: {}", name, e))?;
+    crate::state_file::write_atomic(path, &content)?;
+    Ok(Upgrade::Migrated {
+        from: decoded.version,
+    })
+}
+
+/// Let mismatches be emitted. Called once at startup, before the
+/// integrity pass.
+pub fn init(app: tauri::AppHandle) {
+    let _ = APP.set(app);
+}
+
+/// The handle given to init, for reporting from code that only has a path
+pub fn app() -> Option<&'static tauri::AppHandle> {
+    APP.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch;
+
+    // download_history.json as written before versioning
+    const HISTORY_V0: &str = r#"[
+  {
+    "id": "dl-1",
+    "job_id": "NGBW-JOB-RAXML-1",
+    "job_url": "https://nsgr.sdsc.edu:8443/cipresrest/v1/job/user/NGBW-JOB-RAXML-1",
+    "archive_path": "/home/user/nsg/NGBW-JOB-RAXML-1.zip",
+    "file_count": 3,
+    "total_bytes": 1024,
+    "downloaded_at": "2026-01-02T03:04:05+00:00"
+  }
+]"#;
+
+    // projects.json as written before versioning
+    const PROJECTS_V0: &str = r#"{
+  "projects": [
+    { "id": "proj-1", "name": "Cortex", "created_at": "2026-01-02T03:04:05+00:00" }
+  ],
+  "assignments": { "NGBW-JOB-RAXML-1": "proj-1" }
+}"#;
+
+    #[test]
+    fn unversioned_files_are_version_zero() {
+        let decoded = decode(history::HISTORY_FILE, HISTORY_V0.as_bytes()).unwrap();
+        assert_eq!(decoded.version, 0);
+        assert!(decoded.data.is_array());
+        // An object that merely has a data field is still data
+        let (version, data) = split(br#"{"data": 1, "other": 2}"#).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(data["other"], 2);
+    }
+
+    #[test]
+    fn old_history_round_trips_through_the_current_version() {
+        let path = scratch("state-version", "history").join(history::HISTORY_FILE);
+        let entries: Vec<history::DownloadHistoryEntry> =
+            parse(history::HISTORY_FILE, HISTORY_V0.as_bytes()).unwrap();
+        assert_eq!(entries[0].job_id, "NGBW-JOB-RAXML-1");
+        assert_eq!(entries[0].tool, None);
+
+        let written = serde_json::to_vec(&wrap(&path, &entries).unwrap()).unwrap();
+        let decoded = decode(history::HISTORY_FILE, &written).unwrap();
+        assert_eq!(decoded.version, current(history::HISTORY_FILE));
+        let reread: Vec<history::DownloadHistoryEntry> =
+            serde_json::from_value(decoded.data).unwrap();
+        assert_eq!(reread.len(), 1);
+        assert_eq!(reread[0].archive_path, entries[0].archive_path);
+        assert_eq!(reread[0].total_bytes, 1024);
+    }
+
+    #[test]
+    fn the_startup_pass_writes_old_files_back_upgraded() {
+        let path = scratch("state-version", "upgrade").join(projects::PROJECTS_FILE);
+        std::fs::write(&path, PROJECTS_V0).unwrap();
+        assert_eq!(
+            upgrade(&path, PROJECTS_V0.as_bytes()).unwrap(),
+            Upgrade::Migrated { from: 0 }
+        );
+
+        let content = std::fs::read(&path).unwrap();
+        assert_eq!(split(&content).unwrap().0, current(projects::PROJECTS_FILE));
+        assert_eq!(upgrade(&path, &content).unwrap(), Upgrade::Current);
+        let store: projects::ProjectStore = read(&path).unwrap().unwrap();
+        assert_eq!(store.projects[0].name, "Cortex");
+        assert_eq!(store.assignments["NGBW-JOB-RAXML-1"], "proj-1");
+    }
+
+    #[test]
+    fn a_newer_file_is_read_but_never_written() {
+        let path = scratch("state-version", "newer").join(queue::QUEUE_FILE);
+        let newer = current(queue::QUEUE_FILE) + 1;
+        let content = format!(r#"{{"schema": {}, "data": []}}"#, newer);
+        std::fs::write(&path, &content).unwrap();
+
+        assert_eq!(
+            upgrade(&path, content.as_bytes()).unwrap(),
+            Upgrade::Newer { version: newer }
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+        assert!(is_read_only(&path));
+        let entries: Vec<Value> = read(&path).unwrap().unwrap();
+        assert!(entries.is_empty());
+        assert!(wrap(&path, &entries).is_err());
+
+        forget(&path);
+        assert!(!is_read_only(&path));
+        assert!(wrap(&path, &entries).is_ok());
+    }
+
+    #[test]
+    fn files_without_migrations_are_not_versioned() {
+        assert_eq!(current("preferences.json"), 0);
+        let path = scratch("state-version", "plain").join("preferences.json");
+        assert_eq!(upgrade(&path, b"{}").unwrap(), Upgrade::Current);
+        assert!(!path.exists());
+        let written = serde_json::to_string(&wrap(&path, &vec![1]).unwrap()).unwrap();
+        assert_eq!(written, "[1]");
+    }
+}
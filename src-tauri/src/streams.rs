@@ -0,0 +1,219 @@
+// Windowed reading of large result files.
+//
+// A multi-hundred-megabyte log can't be previewed by downloading it. A
+// stream handle remembers which result file is open; every read and search
+// fetches only the bytes it needs with HTTP Range requests. Handles belong
+// to the session they were opened in, are dropped after IDLE_TIMEOUT
+// without use, and at most MAX_STREAMS are open at once.
+
+use crate::AppState;
+use nsg_cli::models::OutputFile;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const MAX_STREAMS: usize = 8;
+// Largest window a single read_stream_chunk may ask for
+const MAX_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+// search_stream reads this much per request
+const SEARCH_WINDOW_BYTES: u64 = 1024 * 1024;
+const MAX_PATTERN_BYTES: usize = 1024;
+
+struct ResultStream {
+    generation: u64,
+    file: OutputFile,
+    last_used: Instant,
+}
+
+#[derive(Default)]
+pub struct Streams {
+    open: Mutex<HashMap<u64, ResultStream>>,
+    next_handle: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamInfo {
+    pub handle: u64,
+    pub filename: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamChunk {
+    pub offset: u64,
+    // Bytes actually read; less than asked for at the end of the file
+    pub length: u64,
+    // Invalid UTF-8, including characters cut at the window edges, is
+    // replaced with U+FFFD
+    pub text: String,
+    pub eof: bool,
+}
+
+impl Streams {
+    /// Drop handles that sat idle too long or belong to another session
+    fn sweep(open: &mut HashMap<u64, ResultStream>, generation: u64) {
+        open.retain(|_, s| s.generation == generation && s.last_used.elapsed() < IDLE_TIMEOUT);
+    }
+
+    fn insert(&self, generation: u64, file: OutputFile) -> Result<StreamInfo, String> {
+        let mut open = self.open.lock().unwrap();
+        Self::sweep(&mut open, generation);
+        if open.len() >= MAX_STREAMS {
+            return Err(format!(
+                "Too many open result streams (at most {}); close one first",
+                MAX_STREAMS
+            ));
+        }
+
+        let handle = self.next_handle.fetch_add(1, Ordering::SeqCst) + 1;
+        let info = StreamInfo {
+            handle,
+            filename: file.filename.clone(),
+            size: file.size,
+        };
+        open.insert(
+            handle,
+            ResultStream {
+                generation,
+                file,
+                last_used: Instant::now(),
+            },
+        );
+        Ok(info)
+    }
+
+    /// The file behind a handle, marking it as used
+    fn file(&self, handle: u64, generation: u64) -> Result<OutputFile, String> {
+        let mut open = self.open.lock().unwrap();
+        Self::sweep(&mut open, generation);
+        let stream = open
+            .get_mut(&handle)
+            .ok_or_else(|| format!("Result stream {} is not open", handle))?;
+        stream.last_used = Instant::now();
+        Ok(stream.file.clone())
+    }
+
+    fn close(&self, handle: u64) -> bool {
+        self.open.lock().unwrap().remove(&handle).is_some()
+    }
+
+    /// Close every handle, e.g. on disconnect
+    pub fn clear(&self) {
+        self.open.lock().unwrap().clear();
+    }
+}
+
+/// Open a result file of a job for windowed reading
+#[tauri::command]
+pub async fn open_result_stream(
+    job_url: String,
+    filename: String,
+    state: State<'_, AppState>,
+) -> Result<StreamInfo, String> {
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let job_url = crate::anonymize::real_url(&job_url);
+    let listing = tokio::task::spawn_blocking(move || {
+        let record = api.job_record(&job_url)?;
+        api.output_files(&record.status)
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+    .map_err(|e| format!("Failed to list results: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    let file = listing
+        .into_iter()
+        .find(|f| f.filename == filename)
+        .ok_or_else(|| format!("No result file named {}", filename))?;
+    state.streams.insert(session.generation, file)
+}
+
+/// Read `length` bytes (at most 4 MB) starting at `offset`
+#[tauri::command]
+pub async fn read_stream_chunk(
+    handle: u64,
+    offset: u64,
+    length: u64,
+    state: State<'_, AppState>,
+) -> Result<StreamChunk, String> {
+    let session = state.session.require()?;
+    let file = state.streams.file(handle, session.generation)?;
+    let length = length.min(MAX_CHUNK_BYTES);
+
+    let api = state.client.api(session.credentials).await?;
+    let size = file.size;
+    let data = tokio::task::spawn_blocking(move || api.read_range(&file, offset, length))
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+        .map_err(|e| format!("Failed to read result file: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    let read = data.len() as u64;
+    Ok(StreamChunk {
+        offset,
+        length: read,
+        text: String::from_utf8_lossy(&data).into_owned(),
+        eof: offset + read >= size,
+    })
+}
+
+/// Offset of the next occurrence of `pattern` at or after `from_offset`,
+/// or None if it doesn't occur again. Reads the file forward in 1 MB
+/// windows, so only the part up to the match is fetched.
+#[tauri::command]
+pub async fn search_stream(
+    handle: u64,
+    pattern: String,
+    from_offset: u64,
+    state: State<'_, AppState>,
+) -> Result<Option<u64>, String> {
+    if pattern.is_empty() {
+        return Err("Search pattern can't be empty".to_string());
+    }
+    if pattern.len() > MAX_PATTERN_BYTES {
+        return Err(format!(
+            "Search pattern is too long (at most {} bytes)",
+            MAX_PATTERN_BYTES
+        ));
+    }
+    let session = state.session.require()?;
+    let file = state.streams.file(handle, session.generation)?;
+
+    let api = state.client.api(session.credentials).await?;
+    let found = tokio::task::spawn_blocking(move || -> anyhow::Result<Option<u64>> {
+        let needle = pattern.as_bytes();
+        // Windows overlap so a match across a boundary isn't missed
+        let overlap = needle.len() as u64 - 1;
+
+        let mut offset = from_offset;
+        while offset < file.size {
+            let window = api.read_range(&file, offset, SEARCH_WINDOW_BYTES)?;
+            if let Some(i) = window.windows(needle.len()).position(|w| w == needle) {
+                return Ok(Some(offset + i as u64));
+            }
+            if (window.len() as u64) < SEARCH_WINDOW_BYTES {
+                break;
+            }
+            offset += window.len() as u64 - overlap;
+        }
+        Ok(None)
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+    .map_err(|e| format!("Failed to search result file: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    Ok(found)
+}
+
+/// Close a stream handle, returning whether it was open
+#[tauri::command]
+pub async fn close_result_stream(handle: u64, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.streams.close(handle))
+}
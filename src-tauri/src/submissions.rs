@@ -0,0 +1,352 @@
+// Submissions queued while offline.
+//
+// A submission that can't reach the server is stored in
+// pending_submissions.json, referencing its input files by path rather than
+// copying them. A background worker sends the queue once the monitor sees the
+// server again, one entry at a time; an entry that fails for any reason other
+// than the network is dropped with a `queued-submission-failed` event so it
+// doesn't block the rest. One the server took without a readable answer is
+// handed to unconfirmed, which confirms it later.
+
+use crate::activity_feed::{self, Activity, ActivityKind, ActivityStatus};
+use crate::anonymize::{self, anonymize_job_id, Anonymize};
+use crate::api::{is_network_error, Submitted};
+use crate::background_errors::{self, ErrorKind};
+use crate::inputs::{validate, InputBinding};
+use crate::session::SessionSnapshot;
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+
+pub const SUBMISSIONS_FILE: &str = "pending_submissions.json";
+
+// How often the worker checks whether the queue can be sent
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSubmission {
+    pub id: String,
+    #[serde(default)]
+    pub inputs: Vec<InputBinding>,
+    // Single archive of entries queued before inputs existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<PathBuf>,
+    pub tool: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+    // Project to put the job in once it exists
+    #[serde(default)]
+    pub project_id: Option<String>,
+    pub queued_at: String,
+    // Why the last attempt didn't go through
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+impl PendingSubmission {
+    fn all_inputs(&self) -> Vec<InputBinding> {
+        let mut inputs = self.inputs.clone();
+        inputs.extend(self.file_path.clone().map(InputBinding::primary));
+        inputs
+    }
+}
+
+fn submissions_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = crate::accounts::user_root(app)?;
+    Ok(dir.join(SUBMISSIONS_FILE))
+}
+
+fn modify<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut Vec<PendingSubmission>) -> T,
+) -> Result<T, String> {
+    crate::state_file::modify(&submissions_path(app)?, "pending submissions", |entries| {
+        Ok(f(entries))
+    })
+}
+
+fn load(app: &tauri::AppHandle) -> Result<Vec<PendingSubmission>, String> {
+    crate::state_file::load(&submissions_path(app)?)
+}
+
+/// Delete the queued submissions file, returning whether there was one
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = submissions_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    crate::paths::remove_file_if_exists(&path)
+}
+
+/// Queue a submission for later, returning its ID
+pub fn enqueue(
+    app: &tauri::AppHandle,
+    inputs: Vec<InputBinding>,
+    tool: String,
+    params: BTreeMap<String, String>,
+    project_id: Option<String>,
+    reason: Option<String>,
+) -> Result<String, String> {
+    let id = format!(
+        "sub-{}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    );
+    let entry = PendingSubmission {
+        id: id.clone(),
+        inputs,
+        file_path: None,
+        tool,
+        params,
+        project_id,
+        queued_at: chrono::Utc::now().to_rfc3339(),
+        last_error: reason,
+    };
+    modify(app, |entries| entries.push(entry))?;
+    Ok(id)
+}
+
+enum Attempt {
+    Sent(String),
+    // Accepted, but the response couldn't be read
+    Unconfirmed(String),
+    // Still offline; keep this and everything after it for the next pass
+    Offline(String),
+    Failed(String),
+}
+
+fn attempt(
+    app: &tauri::AppHandle,
+    session: &SessionSnapshot,
+    entry: &PendingSubmission,
+) -> Attempt {
+    // The files may have moved or changed since it was queued
+    let inputs = entry.all_inputs();
+    if let Err(e) = validate(&inputs, crate::max_submit_bytes(app)) {
+        return Attempt::Failed(e);
+    }
+
+    let api = match app
+        .state::<AppState>()
+        .client
+        .blocking_api(session.credentials.clone())
+    {
+        Ok(api) => api,
+        Err(e) => return Attempt::Failed(e),
+    };
+    let result = api.submit_job(&inputs, &entry.tool, &entry.params);
+    match result {
+        // Taken off the queue; the confirmation worker follows it from here
+        Ok(Submitted::Unconfirmed(unread)) => match crate::unconfirmed::record(
+            app,
+            &session.credentials.username,
+            unread,
+            &entry.tool,
+            &entry.params,
+            &inputs,
+            entry.project_id.clone(),
+        ) {
+            Ok(unconfirmed) => Attempt::Unconfirmed(unconfirmed.error),
+            Err(e) => Attempt::Failed(e),
+        },
+        Ok(Submitted::Confirmed(status)) => {
+            let state = app.state::<AppState>();
+            if state.session.is_current(session.generation) {
+                state.monitor.nudge(&status.self_uri, &status.job_id);
+            }
+            if let Some(project_id) = &entry.project_id {
+                if let Err(e) = crate::projects::assign(app, &status.job_id, Some(project_id)) {
+                    background_errors::report(
+                        app,
+                        crate::local_jobs::RECORD_SOURCE,
+                        ErrorKind::Other,
+                        format!("Failed to assign job to project: {}", e),
+                    );
+                }
+            }
+            if let Err(e) = crate::local_jobs::record_submission(
+                app,
+                &status.job_id,
+                &status.self_uri,
+                &entry.tool,
+                &entry.params,
+                &status.job_stage,
+            ) {
+                background_errors::report(
+                    app,
+                    crate::local_jobs::RECORD_SOURCE,
+                    ErrorKind::Other,
+                    format!("Failed to record submitted job: {}", e),
+                );
+            }
+            // Nobody is waiting on a response to carry the warning
+            if crate::receipt::is_enabled(app) {
+                if let Some(warning) = crate::receipt::write(
+                    &inputs,
+                    &status.job_id,
+                    &status.self_uri,
+                    &entry.tool,
+                    &entry.params,
+                ) {
+                    background_errors::report(
+                        app,
+                        crate::local_jobs::RECORD_SOURCE,
+                        ErrorKind::Other,
+                        warning,
+                    );
+                }
+            }
+            Attempt::Sent(status.job_id)
+        }
+        Err(e) if is_network_error(&e) => Attempt::Offline(e.to_string()),
+        Err(e) => Attempt::Failed(e.to_string()),
+    }
+}
+
+/// Payload of `queued-submission-sent`
+#[derive(Debug, Clone, Serialize)]
+struct SubmissionSent {
+    id: String,
+    job_id: String,
+}
+
+impl Anonymize for SubmissionSent {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            ..self
+        }
+    }
+}
+
+// Name of the worker in background errors
+const SOURCE: &str = "submission_queue";
+
+/// Try to send everything in the queue, in order
+fn send_pending(app: &tauri::AppHandle, session: SessionSnapshot) {
+    let entries = match load(app) {
+        Ok(entries) => entries,
+        Err(e) => {
+            background_errors::report(app, SOURCE, ErrorKind::Other, e);
+            return;
+        }
+    };
+
+    for entry in entries {
+        // The rest goes out with whatever session is current next pass
+        if !app
+            .state::<AppState>()
+            .session
+            .is_current(session.generation)
+        {
+            return;
+        }
+
+        match attempt(app, &session, &entry) {
+            Attempt::Sent(job_id) => {
+                background_errors::resolved(app, SOURCE);
+                activity_feed::record(
+                    app,
+                    None,
+                    Activity::new(
+                        ActivityKind::QueuedSubmission,
+                        ActivityStatus::Succeeded,
+                        format!("Queued {} job sent", entry.tool),
+                    )
+                    .job(&job_id),
+                );
+                let _ = modify(app, |queue| queue.retain(|e| e.id != entry.id));
+                anonymize::emit(
+                    app,
+                    crate::events::QUEUED_SUBMISSION_SENT,
+                    SubmissionSent {
+                        id: entry.id,
+                        job_id,
+                    },
+                );
+            }
+            Attempt::Unconfirmed(error) => {
+                background_errors::resolved(app, SOURCE);
+                activity_feed::record(
+                    app,
+                    None,
+                    Activity::new(
+                        ActivityKind::QueuedSubmission,
+                        ActivityStatus::Info,
+                        format!("Queued {} job sent but not confirmed yet", entry.tool),
+                    )
+                    .detail(error),
+                );
+                let _ = modify(app, |queue| queue.retain(|e| e.id != entry.id));
+            }
+            Attempt::Offline(error) => {
+                background_errors::report(app, SOURCE, ErrorKind::Network, error.clone());
+                let _ = modify(app, |queue| {
+                    if let Some(e) = queue.iter_mut().find(|e| e.id == entry.id) {
+                        e.last_error = Some(error);
+                    }
+                });
+                return;
+            }
+            Attempt::Failed(error) => {
+                let _ = modify(app, |queue| queue.retain(|e| e.id != entry.id));
+                activity_feed::record(
+                    app,
+                    None,
+                    Activity::new(
+                        ActivityKind::QueuedSubmission,
+                        ActivityStatus::Failed,
+                        format!("Queued {} job was refused", entry.tool),
+                    )
+                    .detail(error.clone()),
+                );
+                let _ = app.emit(
+                    crate::events::QUEUED_SUBMISSION_FAILED,
+                    json!({ "id": entry.id, "tool": entry.tool, "error": error }),
+                );
+            }
+        }
+    }
+}
+
+/// Start the worker that sends queued submissions once back online
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(RETRY_INTERVAL).await;
+
+            let state = app.state::<AppState>();
+            let Some(session) = state.session.snapshot() else {
+                continue;
+            };
+            if state.monitor.is_offline() || crate::mode::is_read_only(&app) {
+                continue;
+            }
+
+            let worker_app = app.clone();
+            let _ = tokio::task::spawn_blocking(move || send_pending(&worker_app, session)).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn list_pending_submissions(
+    app: tauri::AppHandle,
+) -> Result<Vec<PendingSubmission>, String> {
+    load(&app)
+}
+
+#[tauri::command]
+pub async fn cancel_pending_submission(app: tauri::AppHandle, id: String) -> Result<(), String> {
+    let removed = modify(&app, |entries| {
+        let before = entries.len();
+        entries.retain(|e| e.id != id);
+        entries.len() != before
+    })?;
+
+    if !removed {
+        return Err(format!("No pending submission with id {}", id));
+    }
+    Ok(())
+}
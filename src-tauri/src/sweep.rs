@@ -0,0 +1,303 @@
+// Parameter sweeps.
+//
+// submit_sweep submits the same input once per entry of a sweep, each
+// entry's values sent as vparams on top of the shared options and lab
+// defaults, and each job named from a template such as "gNa-{gna}_gK-{gk}".
+// All of a sweep's jobs go into a new local project. A failed entry is
+// recorded and the sweep moves on, unless the last
+// `sweep_max_consecutive_failures` entries all failed, which usually means
+// something systemic (an expired session, a rejected tool) and stops it.
+// `sweep-progress` follows each entry, and the sweep's definition and
+// outcome are written, after every entry, to `<input>.sweep-<id>.json`
+// beside the input.
+
+use crate::anonymize::{self, anonymize_job_id, Anonymize};
+use crate::i18n::Msg;
+use crate::inputs::InputBinding;
+use crate::{preferences, AppState, SubmitOptions, SubmitResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+pub const MAX_FAILURES_KEY: &str = "sweep_max_consecutive_failures";
+const DEFAULT_MAX_FAILURES: u64 = 3;
+
+// Form field NSG reads a job's name from
+const JOB_NAME_PARAM: &str = "metadata.clientJobName";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    Submitted,
+    Failed,
+    // Not tried, after the sweep stopped
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepEntry {
+    pub index: usize,
+    // As given, before the vparam. prefix
+    pub params: BTreeMap<String, String>,
+    pub job_name: String,
+    pub status: EntryStatus,
+    pub job_id: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Anonymize for SweepEntry {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: self.job_id.map(|id| anonymize_job_id(&id)),
+            ..self
+        }
+    }
+}
+
+/// The sweep manifest, and what submit_sweep returns
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepManifest {
+    pub sweep_id: String,
+    pub created_at: String,
+    pub tool: String,
+    pub base_input: String,
+    pub name_template: String,
+    // Sent with every entry
+    pub shared_params: BTreeMap<String, String>,
+    pub project_id: String,
+    pub project_name: String,
+    pub entries: Vec<SweepEntry>,
+    pub stopped_early: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepResult {
+    #[serde(flatten)]
+    pub manifest: SweepManifest,
+    pub manifest_path: String,
+    // Set when the manifest couldn't be written
+    pub manifest_warning: Option<String>,
+}
+
+impl Anonymize for SweepResult {
+    fn anonymize(self) -> Self {
+        Self {
+            manifest: SweepManifest {
+                entries: self.manifest.entries.anonymize(),
+                ..self.manifest
+            },
+            ..self
+        }
+    }
+}
+
+/// Payload of `sweep-progress`
+#[derive(Debug, Clone, Serialize)]
+struct SweepProgress {
+    sweep_id: String,
+    total: usize,
+    entry: SweepEntry,
+}
+
+impl Anonymize for SweepProgress {
+    fn anonymize(self) -> Self {
+        Self {
+            entry: self.entry.anonymize(),
+            ..self
+        }
+    }
+}
+
+fn max_failures(app: &tauri::AppHandle) -> u64 {
+    preferences::of(app)
+        .get(MAX_FAILURES_KEY)
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_MAX_FAILURES)
+}
+
+/// Fill `{name}` placeholders from `values`; an unknown name is an error
+fn job_name(template: &str, values: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|i| start + i)
+            .ok_or_else(|| format!("Unclosed {{ in name template {}", template))?;
+        let key = &rest[start + 1..end];
+        let value = values
+            .get(key)
+            .ok_or_else(|| format!("The name template uses {{{}}}, which isn't swept", key))?;
+        name.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    name.push_str(rest);
+    Ok(name)
+}
+
+/// Field name of a tool parameter: a vparam unless it names its own prefix
+pub fn field_name(key: &str) -> String {
+    if key.contains('.') {
+        key.to_string()
+    } else {
+        format!("vparam.{}", key)
+    }
+}
+
+fn manifest_path(input: &Path, sweep_id: &str) -> PathBuf {
+    let mut name = input.as_os_str().to_os_string();
+    name.push(format!(".{}.json", sweep_id));
+    PathBuf::from(name)
+}
+
+fn write_manifest(path: &Path, manifest: &SweepManifest) -> Result<(), String> {
+    let content = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize sweep manifest: {}", e))?;
+    crate::state_file::write_atomic(path, &content)
+}
+
+/// Submit `base_input` once per entry of `sweep` into a new project,
+/// returning each entry's job ID or error
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn submit_sweep(
+    base_input: String,
+    tool: String,
+    sweep: Vec<BTreeMap<String, String>>,
+    name_template: String,
+    options: Option<SubmitOptions>,
+    project_name: Option<String>,
+    max_consecutive_failures: Option<u64>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SweepResult, String> {
+    crate::mode::ensure_writable(&app, Msg::ActionSubmitJobs)?;
+    state.session.require()?;
+    if sweep.is_empty() {
+        return Err("The sweep has no entries".to_string());
+    }
+    let base_input = crate::path_access::authorize_str(&app, &base_input)?;
+    let names = sweep
+        .iter()
+        .map(|values| job_name(&name_template, values))
+        .collect::<Result<Vec<_>, _>>()?;
+    let max_failures = max_consecutive_failures
+        .unwrap_or_else(|| max_failures(&app))
+        .max(1);
+
+    let now = crate::clock::now();
+    let sweep_id = format!("sweep-{}", now.format("%Y%m%dT%H%M%S"));
+    let project_name = project_name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("{} sweep {}", tool, now.format("%Y-%m-%d %H:%M:%S")));
+    let project = crate::projects::create(&app, &project_name)?;
+
+    let options = options.unwrap_or_default();
+    let mut manifest = SweepManifest {
+        sweep_id: sweep_id.clone(),
+        created_at: now.to_rfc3339(),
+        tool: tool.clone(),
+        base_input: crate::paths::to_ipc_string(&base_input)?,
+        name_template,
+        shared_params: options.params.clone(),
+        project_id: project.id.clone(),
+        project_name: project.name,
+        entries: sweep
+            .into_iter()
+            .zip(names)
+            .enumerate()
+            .map(|(index, (params, job_name))| SweepEntry {
+                index,
+                params,
+                job_name,
+                status: EntryStatus::Skipped,
+                job_id: None,
+                error: None,
+            })
+            .collect(),
+        stopped_early: false,
+    };
+    let path = manifest_path(&base_input, &sweep_id);
+    let mut manifest_warning = None;
+    let total = manifest.entries.len();
+
+    let mut consecutive_failures = 0;
+    for i in 0..total {
+        let entry = &manifest.entries[i];
+        let mut params = options.params.clone();
+        for (key, value) in &entry.params {
+            params.insert(field_name(key), value.clone());
+        }
+        params.insert(JOB_NAME_PARAM.to_string(), entry.job_name.clone());
+        let entry_options = SubmitOptions {
+            params,
+            queue_if_offline: false,
+            project_id: Some(project.id.clone()),
+            ..options.clone()
+        };
+
+        let inputs = vec![InputBinding::primary(base_input.clone())];
+        let result = crate::submit_with(
+            inputs,
+            tool.clone(),
+            Some(entry_options),
+            None,
+            false,
+            &app,
+            &state,
+        )
+        .await;
+        let entry = &mut manifest.entries[i];
+        match result {
+            Ok(SubmitResponse::Submitted { job_id, .. }) => {
+                entry.status = EntryStatus::Submitted;
+                entry.job_id = Some(job_id);
+                consecutive_failures = 0;
+            }
+            // The job exists; its ID is filled in if the response had one
+            Ok(SubmitResponse::SubmittedButUnconfirmed { job_id, error, .. }) => {
+                entry.status = EntryStatus::Submitted;
+                entry.job_id = job_id;
+                entry.error = Some(error);
+                consecutive_failures = 0;
+            }
+            Ok(SubmitResponse::Queued { .. }) => {
+                entry.status = EntryStatus::Failed;
+                entry.error = Some("The entry was queued instead of sent".to_string());
+                consecutive_failures += 1;
+            }
+            Err(e) => {
+                entry.status = EntryStatus::Failed;
+                entry.error = Some(e);
+                consecutive_failures += 1;
+            }
+        }
+        anonymize::emit(
+            &app,
+            crate::events::SWEEP_PROGRESS,
+            SweepProgress {
+                sweep_id: sweep_id.clone(),
+                total,
+                entry: entry.clone(),
+            },
+        );
+
+        if consecutive_failures >= max_failures && i + 1 < total {
+            manifest.stopped_early = true;
+        }
+        manifest_warning = write_manifest(&path, &manifest).err();
+        if manifest.stopped_early {
+            break;
+        }
+    }
+
+    let result = SweepResult {
+        manifest,
+        manifest_path: crate::paths::to_ipc_string(&path)?,
+        manifest_warning,
+    };
+    Ok(anonymize::outgoing(&app, result))
+}
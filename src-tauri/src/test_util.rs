@@ -0,0 +1,17 @@
+// Helpers shared by the unit tests.
+
+use std::path::PathBuf;
+
+/// An empty folder for test `name` of module `prefix` under the system temp
+/// dir, separate for each test process
+pub fn scratch(prefix: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "nsg-gui-{}-{}-{}",
+        prefix,
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
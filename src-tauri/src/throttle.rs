@@ -0,0 +1,97 @@
+// Download bandwidth limit.
+//
+// A single token bucket shared by every transfer, so concurrent files
+// together stay under the configured rate. The limit is read on every
+// acquire, which makes changes apply to running downloads immediately.
+
+use crate::preferences;
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Kilobits per second; 0 = unlimited
+static RATE_LIMIT_KBPS: AtomicU64 = AtomicU64::new(0);
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+static BUCKET: Mutex<Option<Bucket>> = Mutex::new(None);
+
+// Sleep in short slices so a raised or removed limit is picked up quickly
+const MAX_SLEEP: Duration = Duration::from_millis(250);
+
+fn bytes_per_sec() -> Option<f64> {
+    match RATE_LIMIT_KBPS.load(Ordering::Relaxed) {
+        0 => None,
+        kbps => Some(kbps as f64 * 1000.0 / 8.0),
+    }
+}
+
+pub fn set_rate_limit_kbps(kbps: u64) {
+    RATE_LIMIT_KBPS.store(kbps, Ordering::Relaxed);
+}
+
+pub fn rate_limit_kbps() -> u64 {
+    RATE_LIMIT_KBPS.load(Ordering::Relaxed)
+}
+
+/// Block until `bytes` may be transferred under the current limit
+pub fn acquire(bytes: usize) {
+    let mut needed = bytes as f64;
+
+    while let Some(rate) = bytes_per_sec() {
+        let wait = {
+            let mut guard = BUCKET.lock().unwrap();
+            let bucket = guard.get_or_insert_with(|| Bucket {
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            });
+
+            // Refill, keeping at most one second of burst
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+            bucket.last_refill = now;
+
+            let take = needed.min(bucket.tokens);
+            bucket.tokens -= take;
+            needed -= take;
+            if needed <= 0.0 {
+                return;
+            }
+
+            Duration::from_secs_f64(needed / rate).min(MAX_SLEEP)
+        };
+
+        std::thread::sleep(wait);
+    }
+}
+
+/// Load the persisted limit at startup
+pub fn load(app: &tauri::AppHandle) {
+    let kbps = preferences::of(app)
+        .get("download_rate_limit_kbps")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    set_rate_limit_kbps(kbps);
+}
+
+#[tauri::command]
+pub async fn get_download_rate_limit() -> Result<u64, String> {
+    Ok(rate_limit_kbps())
+}
+
+#[tauri::command]
+pub async fn set_download_rate_limit(app: tauri::AppHandle, kbps: u64) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, crate::i18n::Msg::ActionChangeDownloadSettings)?;
+
+    let prefs = preferences::of(&app);
+
+    prefs.set("download_rate_limit_kbps", json!(kbps));
+
+    set_rate_limit_kbps(kbps);
+    Ok(())
+}
@@ -0,0 +1,430 @@
+// Thumbnails of image results, for the job gallery.
+//
+// get_result_thumbnail fetches one result image, at most MAX_SOURCE_BYTES,
+// scales it down to `max_dim` on the long edge and keeps the thumbnail in
+// the app data `thumbnails` folder, named after a hash of the job ID, file
+// name and size asked for, plus the original dimensions. Reading a cached
+// thumbnail touches its modification time, so once the folder grows past
+// the `thumbnail_cache_bytes` preference the least recently used ones are
+// removed first. PNG, JPEG, GIF, BMP, TIFF and WebP are decoded with the
+// image crate and thumbnails are always PNG; SVG and files too big to fetch
+// come back as `unsupported` or `too_large` instead of being downloaded.
+
+use crate::anonymize::real_url;
+use crate::api::NsgApi;
+use crate::i18n::Msg;
+use crate::{preferences, AppState};
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use nsg_cli::models::OutputFile;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tauri::State;
+
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+pub const CACHE_BYTES_KEY: &str = "thumbnail_cache_bytes";
+const DEFAULT_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "tif", "tiff", "webp", "svg",
+];
+// The formats a thumbnail can be made of
+const DECODABLE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "tif", "tiff", "webp"];
+
+// Largest result image fetched for a thumbnail
+const MAX_SOURCE_BYTES: u64 = 20 * 1024 * 1024;
+// Largest image decoded, whatever its file size
+const MAX_PIXELS: u64 = 50_000_000;
+const MIN_DIM: u32 = 16;
+const MAX_DIM: u32 = 2048;
+
+// Serializes eviction with the writes it races
+static CACHE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageResult {
+    pub filename: String,
+    pub size: u64,
+    // get_result_thumbnail can make a thumbnail of it
+    pub thumbnail: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ThumbnailResponse {
+    Ready {
+        // Base64 PNG
+        data: String,
+        width: u32,
+        height: u32,
+        original_width: u32,
+        original_height: u32,
+        cached: bool,
+    },
+    // Not an image, or a format thumbnails aren't made of
+    Unsupported {
+        filename: String,
+    },
+    TooLarge {
+        filename: String,
+        size: u64,
+        max_bytes: u64,
+    },
+}
+
+fn extension(filename: &str) -> String {
+    Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn is_image(filename: &str) -> bool {
+    IMAGE_EXTENSIONS.contains(&extension(filename).as_str())
+}
+
+fn is_decodable(filename: &str) -> bool {
+    DECODABLE_EXTENSIONS.contains(&extension(filename).as_str())
+}
+
+pub fn cache_limit(app: &tauri::AppHandle) -> u64 {
+    preferences::of(app)
+        .get(CACHE_BYTES_KEY)
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_CACHE_BYTES)
+}
+
+fn cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::portable::app_data_root(app)?.join(THUMBNAIL_DIR))
+}
+
+fn cache_key(job_id: &str, filename: &str, max_dim: u32) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(job_id.as_bytes());
+    hasher.update([0]);
+    hasher.update(filename.as_bytes());
+    hasher.update([0]);
+    hasher.update(max_dim.to_le_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A cached thumbnail: `<key>-<width>x<height>.png`, with the original
+/// dimensions
+fn cached(dir: &Path, key: &str) -> Option<(PathBuf, u32, u32)> {
+    let prefix = format!("{}-", key);
+    std::fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let dims = name.strip_prefix(&prefix)?.strip_suffix(".png")?;
+        let (w, h) = dims.split_once('x')?;
+        Some((entry.path(), w.parse().ok()?, h.parse().ok()?))
+    })
+}
+
+/// Remove the least recently used thumbnails until the folder fits `limit`
+fn evict(dir: &Path, limit: u64) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            let modified = meta.modified().ok()?;
+            meta.is_file().then(|| (modified, meta.len(), entry.path()))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort();
+    for (_, len, path) in files {
+        if total <= limit {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            total -= len;
+        }
+    }
+}
+
+/// Decode an image in any of the supported formats, told apart by its
+/// content rather than its name
+fn decode(bytes: &[u8]) -> Result<DynamicImage, String> {
+    let reader = || {
+        ImageReader::new(std::io::Cursor::new(bytes))
+            .with_guessed_format()
+            .map_err(|e| format!("Not a readable image: {}", e))
+    };
+    let (width, height) = reader()?
+        .into_dimensions()
+        .map_err(|e| format!("Not a readable image: {}", e))?;
+    if u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return Err(format!(
+            "The image is too large to decode ({}x{})",
+            width, height
+        ));
+    }
+    reader()?
+        .decode()
+        .map_err(|e| format!("Not a readable image: {}", e))
+}
+
+/// Scale an image down to fit `max_dim`; smaller images are kept
+fn downscale(image: DynamicImage, max_dim: u32) -> DynamicImage {
+    if image.width().max(image.height()) <= max_dim {
+        return image;
+    }
+    image.resize(max_dim, max_dim, FilterType::Triangle)
+}
+
+fn encode(image: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+    Ok(png.into_inner())
+}
+
+/// The whole of a result file: one Range request, or a plain download for
+/// servers that don't do ranges
+fn fetch(api: &NsgApi, file: &OutputFile, dir: &Path, key: &str) -> Result<Vec<u8>, String> {
+    if let Ok(bytes) = api.read_range(file, 0, file.size) {
+        return Ok(bytes);
+    }
+    let part = dir.join(format!("{}.part", key));
+    let _ = std::fs::remove_file(&part);
+    let result = api
+        .download_file(file, &part, |_, _| {})
+        .map_err(|e| format!("Failed to download {}: {}", file.filename, e))
+        .and_then(|_| {
+            std::fs::read(&part).map_err(|e| format!("Failed to read {}: {}", file.filename, e))
+        });
+    let _ = std::fs::remove_file(&part);
+    result
+}
+
+/// PNG dimensions from the header alone
+fn png_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    ImageReader::with_format(std::io::Cursor::new(bytes), ImageFormat::Png)
+        .into_dimensions()
+        .ok()
+}
+
+fn ready(png: Vec<u8>, original: (u32, u32), cached: bool) -> Option<ThumbnailResponse> {
+    let (width, height) = png_size(&png)?;
+    Some(ThumbnailResponse::Ready {
+        data: base64::engine::general_purpose::STANDARD.encode(png),
+        width,
+        height,
+        original_width: original.0,
+        original_height: original.1,
+        cached,
+    })
+}
+
+fn read_cached(dir: &Path, key: &str) -> Option<ThumbnailResponse> {
+    let (path, original_width, original_height) = cached(dir, key)?;
+    let png = std::fs::read(&path).ok()?;
+    // Reading counts as use for eviction
+    if let Ok(file) = std::fs::File::options().append(true).open(&path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+    ready(png, (original_width, original_height), true)
+}
+
+fn list_images(api: &NsgApi, job_url: &str) -> anyhow::Result<(String, Vec<OutputFile>)> {
+    let record = api.job_record(job_url)?;
+    let files = api
+        .output_files(&record.status)?
+        .into_iter()
+        .filter(|f| is_image(&f.filename))
+        .collect();
+    Ok((record.status.job_id, files))
+}
+
+/// The image files among a job's results
+#[tauri::command]
+pub async fn list_image_results(
+    job_url: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<ImageResult>, String> {
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let url = real_url(&job_url);
+    let (_, files) = tokio::task::spawn_blocking(move || list_images(&api, &url))
+        .await
+        .map_err(|e| format!("Task error: {}", e))?
+        .map_err(|e| format!("Failed to list results: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    Ok(files
+        .into_iter()
+        .map(|f| ImageResult {
+            thumbnail: is_decodable(&f.filename) && f.size <= MAX_SOURCE_BYTES,
+            filename: f.filename,
+            size: f.size,
+        })
+        .collect())
+}
+
+/// A thumbnail of a result image, at most `max_dim` pixels on the long
+/// edge, from the cache when it's there
+#[tauri::command]
+pub async fn get_result_thumbnail(
+    job_url: String,
+    filename: String,
+    max_dim: u32,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ThumbnailResponse, String> {
+    if !(MIN_DIM..=MAX_DIM).contains(&max_dim) {
+        return Err(format!(
+            "Thumbnail size must be between {} and {} pixels",
+            MIN_DIM, MAX_DIM
+        ));
+    }
+    if !is_decodable(&filename) {
+        return Ok(ThumbnailResponse::Unsupported { filename });
+    }
+    let session = state.session.require()?;
+    let dir = cache_dir(&app)?;
+    let limit = cache_limit(&app);
+
+    let url = real_url(&job_url);
+    // Job URLs end in the job ID, so a cached thumbnail needs no request
+    let job_id = url.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+    let key = cache_key(job_id, &filename, max_dim);
+    let hit_dir = dir.clone();
+    let hit_key = key.clone();
+    let hit = tokio::task::spawn_blocking(move || read_cached(&hit_dir, &hit_key))
+        .await
+        .map_err(|e| format!("Task error: {}", e))?;
+    if let Some(response) = hit {
+        return Ok(response);
+    }
+
+    let api = state.client.api(session.credentials).await?;
+    let response = tokio::task::spawn_blocking(move || -> Result<ThumbnailResponse, String> {
+        let (_, files) =
+            list_images(&api, &url).map_err(|e| format!("Failed to list results: {}", e))?;
+
+        let file = files
+            .into_iter()
+            .find(|f| f.filename == filename)
+            .ok_or_else(|| format!("No result file named {}", filename))?;
+        if file.size > MAX_SOURCE_BYTES {
+            return Ok(ThumbnailResponse::TooLarge {
+                filename,
+                size: file.size,
+                max_bytes: MAX_SOURCE_BYTES,
+            });
+        }
+
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create thumbnail cache: {}", e))?;
+        let bytes = fetch(&api, &file, &dir, &key)?;
+        let image = decode(&bytes)?;
+        let (original_width, original_height) = (image.width(), image.height());
+        let png = encode(&downscale(image, max_dim))?;
+
+        {
+            let _guard = CACHE_LOCK.lock().unwrap();
+            let path = dir.join(format!(
+                "{}-{}x{}.png",
+                key, original_width, original_height
+            ));
+            // A cache that can't be written only costs a refetch
+            let _ = crate::state_file::write_atomic(&path, &png);
+            evict(&dir, limit);
+        }
+
+        ready(png, (original_width, original_height), false)
+            .ok_or_else(|| "Failed to encode thumbnail".to_string())
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))??;
+    state.session.ensure_current(session.generation)?;
+
+    Ok(response)
+}
+
+/// Delete the thumbnail cache, returning whether there was one
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let dir = cache_dir(app)?;
+    let _guard = CACHE_LOCK.lock().unwrap();
+    if !dir.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_dir_all(&dir)
+        .map(|_| true)
+        .map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))
+}
+
+#[tauri::command]
+pub async fn get_thumbnail_cache_limit(app: tauri::AppHandle) -> Result<u64, String> {
+    Ok(cache_limit(&app))
+}
+
+/// Set the most disk space cached thumbnails may take; 0 keeps none
+#[tauri::command]
+pub async fn set_thumbnail_cache_limit(app: tauri::AppHandle, bytes: u64) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeDownloadSettings)?;
+    preferences::of(&app).set(CACHE_BYTES_KEY, json!(bytes));
+
+    let dir = cache_dir(&app)?;
+    let _guard = CACHE_LOCK.lock().unwrap();
+    evict(&dir, bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(format: ImageFormat, width: u32, height: u32) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            width,
+            height,
+            image::Rgb([200, 40, 40]),
+        ));
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn jpeg_and_tiff_results_get_thumbnails() {
+        for format in [ImageFormat::Jpeg, ImageFormat::Tiff, ImageFormat::Png] {
+            let image = decode(&encoded(format, 400, 100)).unwrap();
+            let png = encode(&downscale(image, 64)).unwrap();
+            assert_eq!(png_size(&png), Some((64, 16)), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn small_images_keep_their_size() {
+        let image = decode(&encoded(ImageFormat::Jpeg, 20, 30)).unwrap();
+        assert_eq!(downscale(image, 64).width(), 20);
+    }
+
+    #[test]
+    fn garbage_is_not_decoded() {
+        assert!(decode(b"not an image").is_err());
+    }
+
+    #[test]
+    fn svg_is_listed_but_not_decoded() {
+        assert!(is_image("plot.SVG"));
+        assert!(!is_decodable("plot.svg"));
+        assert!(is_decodable("scan.TIF"));
+    }
+}
@@ -0,0 +1,236 @@
+// Tool catalog.
+//
+// NSG's /tool listing gives each tool's ID and usually a name, but nothing
+// about what it is for or whether it runs on GPU nodes. KNOWN_TOOLS fills
+// that in for the tools the app has been used with. list_tools returns every
+// tool the server lists, described from KNOWN_TOOLS where possible, and falls
+// back to KNOWN_TOOLS alone when there is no session or the server can't be
+// reached, so the submit form always has something to offer.
+
+use crate::xml::ListedTool;
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+// Tool ID -> name, description, runs on GPU nodes
+const KNOWN_TOOLS: &[(&str, &str, &str, bool)] = &[
+    (
+        "AMICA_EXPANSE",
+        "AMICA",
+        "Adaptive mixture ICA of EEG data",
+        false,
+    ),
+    (
+        "BLUEPYOPT_EXPANSE",
+        "BluePyOpt",
+        "Parameter optimization of neuron models",
+        false,
+    ),
+    (
+        "BLUEPYOPT_EXPANSE1143",
+        "BluePyOpt 1.14.3",
+        "Parameter optimization of neuron models, pinned to 1.14.3",
+        false,
+    ),
+    (
+        "CORENEURON_EXPANSE",
+        "CoreNEURON",
+        "NEURON models on the optimized CoreNEURON engine",
+        false,
+    ),
+    (
+        "EEGLAB_EXPANSE",
+        "EEGLAB",
+        "EEGLAB scripts in MATLAB",
+        false,
+    ),
+    (
+        "GPU_PY_EXPANSE",
+        "Python (GPU)",
+        "Python scripts on a GPU node",
+        true,
+    ),
+    (
+        "HNN_EXPANSE",
+        "HNN",
+        "Human Neocortical Neurosolver simulations",
+        false,
+    ),
+    (
+        "HNN_GUI_EXPANSE",
+        "HNN (GUI parameters)",
+        "HNN simulations from parameter files saved by the HNN GUI",
+        false,
+    ),
+    ("MATLAB_EXPANSE", "MATLAB", "MATLAB scripts", false),
+    (
+        "MRTRIX_EXPANSE",
+        "MRtrix3",
+        "Diffusion MRI processing with MRtrix3",
+        false,
+    ),
+    (
+        "NEMAR_EXPANSE",
+        "NEMAR",
+        "Processing of NEMAR EEG, MEG and iEEG datasets",
+        false,
+    ),
+    (
+        "NEURON_EXPANSE",
+        "NEURON",
+        "NEURON simulations from hoc or Python",
+        false,
+    ),
+    (
+        "NIC_CONVERTER_EXPANSE",
+        "NIC converter",
+        "Conversion of neuroimaging data between formats",
+        false,
+    ),
+    (
+        "NIC_CORRELATOR_EXPANSE",
+        "NIC correlator",
+        "Correlation analysis of neuroimaging data",
+        false,
+    ),
+    (
+        "NIC_TDA_EXPANSE",
+        "NIC TDA",
+        "Topological data analysis of neuroimaging data",
+        false,
+    ),
+    (
+        "OSBv2_EXPANSE_0_7_3",
+        "OSBv2 0.7.3",
+        "Open Source Brain v2 models, NetPyNE and NEURON",
+        false,
+    ),
+    (
+        "PYTORCH_PY_EXPANSE",
+        "PyTorch",
+        "Python scripts using PyTorch on a GPU node",
+        true,
+    ),
+    (
+        "PY_CRI",
+        "Python (CRI)",
+        "Python scripts on the CRI cluster",
+        false,
+    ),
+    ("PY_EXPANSE", "Python", "Python scripts", false),
+    (
+        "SINGULARITY_PGENESIS24_EXPANSE",
+        "PGENESIS 2.4",
+        "Parallel GENESIS simulations in a Singularity container",
+        false,
+    ),
+    (
+        "SPIKEINTERFACE_EXPANSE",
+        "SpikeInterface",
+        "Spike sorting with SpikeInterface",
+        false,
+    ),
+    (
+        "TENSORFLOW_PY_EXPANSE",
+        "TensorFlow",
+        "Python scripts using TensorFlow on a GPU node",
+        true,
+    ),
+    (
+        "TENSORFLOW_PY_NSGOSG",
+        "TensorFlow (OSG)",
+        "Python scripts using TensorFlow on the Open Science Grid",
+        false,
+    ),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInfo {
+    pub tool_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub gpu: bool,
+    // Offered by the server; false for every tool in a built-in list
+    pub listed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolList {
+    // By tool ID
+    pub tools: Vec<ToolInfo>,
+    // True when the tools came from the server rather than KNOWN_TOOLS
+    pub from_server: bool,
+    // Why the server's list couldn't be used
+    pub error: Option<String>,
+}
+
+fn known(tool_id: &str) -> Option<&'static (&'static str, &'static str, &'static str, bool)> {
+    KNOWN_TOOLS.iter().find(|(id, ..)| *id == tool_id)
+}
+
+fn describe(listed: ListedTool) -> ToolInfo {
+    match known(&listed.tool_id) {
+        Some((_, name, description, gpu)) => ToolInfo {
+            name: listed.tool_name.unwrap_or_else(|| name.to_string()),
+            description: Some(description.to_string()),
+            gpu: *gpu,
+            listed: true,
+            tool_id: listed.tool_id,
+        },
+        None => ToolInfo {
+            name: listed.tool_name.unwrap_or_else(|| listed.tool_id.clone()),
+            description: None,
+            // Unknown tools follow NSG's naming
+            gpu: listed.tool_id.starts_with("GPU_"),
+            listed: true,
+            tool_id: listed.tool_id,
+        },
+    }
+}
+
+fn built_in(error: Option<String>) -> ToolList {
+    ToolList {
+        tools: KNOWN_TOOLS
+            .iter()
+            .map(|(id, name, description, gpu)| ToolInfo {
+                tool_id: id.to_string(),
+                name: name.to_string(),
+                description: Some(description.to_string()),
+                gpu: *gpu,
+                listed: false,
+            })
+            .collect(),
+        from_server: false,
+        error,
+    }
+}
+
+/// The tools to offer for submission
+#[tauri::command]
+pub async fn list_tools(state: State<'_, AppState>) -> Result<ToolList, String> {
+    let Some(session) = state.session.snapshot() else {
+        return Ok(built_in(None));
+    };
+
+    let api = state.client.api(session.credentials).await?;
+    let listed = tokio::task::spawn_blocking(move || api.tools())
+        .await
+        .map_err(|e| format!("Task error: {}", e))?;
+    let listed = match listed {
+        Ok(listed) if !listed.is_empty() => listed,
+        Ok(_) => return Ok(built_in(Some("The server listed no tools".to_string()))),
+        Err(e) => return Ok(built_in(Some(format!("Failed to list tools: {}", e)))),
+    };
+    crate::dropped_paths::remember_catalog(
+        &listed.iter().map(|t| t.tool_id.clone()).collect::<Vec<_>>(),
+    );
+
+    let mut tools: Vec<ToolInfo> = listed.into_iter().map(describe).collect();
+    tools.sort_by(|a, b| a.tool_id.cmp(&b.tool_id));
+    tools.dedup_by(|a, b| a.tool_id == b.tool_id);
+    Ok(ToolList {
+        tools,
+        from_server: true,
+        error: None,
+    })
+}
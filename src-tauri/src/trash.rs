@@ -0,0 +1,266 @@
+// Undo for destructive local operations.
+//
+// Deleting a project, removing download history entries and clearing a
+// job's tags and note don't destroy anything at first: the state file keeps
+// the records in its trash, marked with an undo token and the time of the
+// delete, and `local-delete` is emitted with the token. `undo_local_delete`
+// puts them back within UNDO_WINDOW_SECS. After that a sweep, every
+// SWEEP_INTERVAL, drops them for good. The token's prefix names the state
+// file holding the records. Deletes on the server are not covered and keep
+// their own confirmation.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::Emitter;
+
+pub const UNDO_WINDOW_SECS: i64 = 10 * 60;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Project,
+    DownloadHistory,
+    Annotations,
+}
+
+impl TrashKind {
+    const ALL: &'static [TrashKind] = &[
+        TrashKind::Project,
+        TrashKind::DownloadHistory,
+        TrashKind::Annotations,
+    ];
+
+    fn prefix(self) -> &'static str {
+        match self {
+            TrashKind::Project => "project",
+            TrashKind::DownloadHistory => "history",
+            TrashKind::Annotations => "annotations",
+        }
+    }
+
+    fn of_token(token: &str) -> Option<Self> {
+        let (prefix, _) = token.split_once(':')?;
+        Self::ALL.iter().copied().find(|k| k.prefix() == prefix)
+    }
+}
+
+/// When and under which token records were deleted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashMark {
+    pub token: String,
+    pub deleted_at: String,
+}
+
+impl TrashMark {
+    pub fn new(kind: TrashKind) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            token: format!(
+                "{}:{}",
+                kind.prefix(),
+                now.timestamp_nanos_opt().unwrap_or(0)
+            ),
+            deleted_at: now.to_rfc3339(),
+        }
+    }
+
+    fn expires_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let deleted = chrono::DateTime::parse_from_rfc3339(&self.deleted_at).ok()?;
+        Some(deleted.with_timezone(&chrono::Utc) + chrono::Duration::seconds(UNDO_WINDOW_SECS))
+    }
+
+    /// Past the undo window; an unreadable time counts as expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at()
+            .is_none_or(|expires| expires <= chrono::Utc::now())
+    }
+
+    /// Whether `token` can still undo this delete
+    pub fn undoes(&self, token: &str) -> bool {
+        self.token == token && !self.is_expired()
+    }
+}
+
+/// Records kept in a state file's trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trashed<T> {
+    #[serde(flatten)]
+    pub mark: TrashMark,
+    pub item: T,
+}
+
+/// Take the records `token` still undoes out of `trash`
+pub fn take<T>(trash: &mut Vec<Trashed<T>>, token: &str) -> Option<T> {
+    let i = trash.iter().position(|t| t.mark.undoes(token))?;
+    Some(trash.remove(i).item)
+}
+
+/// Sent as `local-delete` and returned by the deleting command
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalDelete {
+    pub token: String,
+    pub kind: TrashKind,
+    // How many records were deleted
+    pub count: usize,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LocalDeleteUndone {
+    token: String,
+    kind: TrashKind,
+}
+
+/// Announce a delete that can be undone
+pub fn deleted(
+    app: &tauri::AppHandle,
+    kind: TrashKind,
+    mark: &TrashMark,
+    count: usize,
+) -> LocalDelete {
+    let event = LocalDelete {
+        token: mark.token.clone(),
+        kind,
+        count,
+        expires_at: mark
+            .expires_at()
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default(),
+    };
+    let _ = app.emit(crate::events::LOCAL_DELETE, event.clone());
+    event
+}
+
+fn sweep(app: &tauri::AppHandle) {
+    let results = [
+        crate::projects::purge_trash(app),
+        crate::history::purge_trash(app),
+        crate::local_jobs::purge_trash(app),
+    ];
+    let errors: Vec<String> = results.into_iter().filter_map(Result::err).collect();
+    if errors.is_empty() {
+        crate::background_errors::resolved(app, "trash_sweep");
+        return;
+    }
+    crate::background_errors::report(
+        app,
+        "trash_sweep",
+        crate::background_errors::ErrorKind::Other,
+        format!("Failed to empty the trash: {}", errors.join("; ")),
+    );
+}
+
+/// Start the sweep that empties expired trash. Called once at startup.
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let sweep_app = app.clone();
+            let _ = tokio::task::spawn_blocking(move || sweep(&sweep_app)).await;
+            tokio::time::sleep(SWEEP_INTERVAL).await;
+        }
+    });
+}
+
+/// Restore what a delete removed, if its undo window hasn't passed
+#[tauri::command]
+pub async fn undo_local_delete(app: tauri::AppHandle, token: String) -> Result<(), String> {
+    let kind =
+        TrashKind::of_token(&token).ok_or_else(|| format!("Invalid undo token {}", token))?;
+    let restored = match kind {
+        TrashKind::Project => crate::projects::restore(&app, &token)?,
+        TrashKind::DownloadHistory => crate::history::restore(&app, &token)?,
+        TrashKind::Annotations => crate::local_jobs::restore_annotations(&app, &token)?,
+    };
+    if !restored {
+        return Err(
+            "Nothing to undo: it was already undone or the undo window has passed".to_string(),
+        );
+    }
+
+    let _ = app.emit(
+        crate::events::LOCAL_DELETE_UNDONE,
+        LocalDeleteUndone { token, kind },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A mark for a delete `ago` in the past
+    fn mark(token: &str, ago: chrono::Duration) -> TrashMark {
+        TrashMark {
+            token: token.to_string(),
+            deleted_at: (chrono::Utc::now() - ago).to_rfc3339(),
+        }
+    }
+
+    fn trashed(token: &str, ago: chrono::Duration, item: &'static str) -> Trashed<&'static str> {
+        Trashed {
+            mark: mark(token, ago),
+            item,
+        }
+    }
+
+    #[test]
+    fn only_the_right_token_undoes_within_the_window() {
+        let recent = mark("project:1", chrono::Duration::seconds(30));
+        assert!(recent.undoes("project:1"));
+        assert!(!recent.undoes("project:2"));
+        assert!(!recent.undoes("history:1"));
+
+        let expired = mark("project:1", chrono::Duration::seconds(UNDO_WINDOW_SECS + 1));
+        assert!(expired.is_expired());
+        assert!(!expired.undoes("project:1"));
+
+        let unreadable = TrashMark {
+            token: "project:1".to_string(),
+            deleted_at: "yesterday".to_string(),
+        };
+        assert!(!unreadable.undoes("project:1"));
+    }
+
+    #[test]
+    fn take_removes_only_the_matching_entry() {
+        let mut trash = vec![
+            trashed("project:1", chrono::Duration::seconds(5), "a"),
+            trashed("project:2", chrono::Duration::seconds(5), "b"),
+            trashed("project:3", chrono::Duration::seconds(5), "c"),
+        ];
+        assert_eq!(take(&mut trash, "project:2"), Some("b"));
+        let left: Vec<_> = trash.iter().map(|t| t.item).collect();
+        assert_eq!(left, ["a", "c"]);
+
+        assert_eq!(take(&mut trash, "project:2"), None);
+        assert_eq!(take(&mut trash, "project:9"), None);
+        assert_eq!(trash.len(), 2);
+    }
+
+    #[test]
+    fn take_leaves_expired_entries_for_the_sweep() {
+        let mut trash = vec![trashed(
+            "history:1",
+            chrono::Duration::seconds(UNDO_WINDOW_SECS + 1),
+            "old",
+        )];
+        assert_eq!(take(&mut trash, "history:1"), None);
+        assert_eq!(trash.len(), 1);
+    }
+
+    #[test]
+    fn tokens_name_their_kind() {
+        for kind in TrashKind::ALL {
+            assert_eq!(
+                TrashKind::of_token(&TrashMark::new(*kind).token),
+                Some(*kind)
+            );
+        }
+        assert_eq!(TrashKind::of_token("tags:1"), None);
+        assert_eq!(TrashKind::of_token("project"), None);
+        assert_eq!(TrashKind::of_token(":1"), None);
+        assert_eq!(TrashKind::of_token(""), None);
+        assert_eq!(TrashKind::of_token("Project:1"), None);
+    }
+}
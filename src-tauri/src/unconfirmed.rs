@@ -0,0 +1,506 @@
+// Submissions the server accepted without a readable answer.
+//
+// Now and then NSG takes a job but answers with something nsg-cli can't
+// parse. The job most likely exists, so instead of failing the submission,
+// the raw response and whatever handle could be picked out of it are kept
+// in unconfirmed_submissions.json and the caller gets
+// `submitted_but_unconfirmed`. A worker fetches the status of each one that
+// has a handle every RETRY_INTERVAL; once that works the job is recorded
+// like any other submission and `submission-confirmed` is sent. One without
+// any handle stays until the user dismisses it, after checking the job list.
+// While a submission is being sent, and for as long as it is unconfirmed,
+// the same tool, fields and inputs can't be submitted again, so a user left
+// unsure doesn't create the job twice.
+
+use crate::activity_feed::{self, Activity, ActivityKind, ActivityStatus};
+use crate::anonymize::{self, anonymize_job_id, anonymize_url, is_showcase_mode, Anonymize};
+use crate::api::UnreadSubmission;
+use crate::background_errors::{self, ErrorKind};
+use crate::inputs::InputBinding;
+use crate::session::SessionSnapshot;
+use crate::xml::RecoveredHandle;
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::Manager;
+
+pub const UNCONFIRMED_FILE: &str = "unconfirmed_submissions.json";
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(20);
+// Longer responses are cut here; the start is what identifies the job
+const MAX_RAW_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnconfirmedSubmission {
+    pub id: String,
+    pub tool: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+    #[serde(default)]
+    pub inputs: Vec<InputBinding>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    pub submitted_at: String,
+    // The response as received, up to MAX_RAW_BYTES
+    pub raw_response: String,
+    // Why it couldn't be read
+    pub error: String,
+    // Recovered from the response; the URL is built from the ID when only
+    // that was found
+    pub job_id: Option<String>,
+    pub job_url: Option<String>,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+impl Anonymize for UnconfirmedSubmission {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: self.job_id.map(|id| anonymize_job_id(&id)),
+            job_url: self.job_url.map(|url| anonymize_url(&url)),
+            // Names the user and the job
+            raw_response: if is_showcase_mode() {
+                String::new()
+            } else {
+                self.raw_response
+            },
+            ..self
+        }
+    }
+}
+
+/// Payload of `submission-confirmed`
+#[derive(Debug, Clone, Serialize)]
+struct SubmissionConfirmed {
+    id: String,
+    job_id: String,
+    job_url: String,
+    tool: String,
+}
+
+impl Anonymize for SubmissionConfirmed {
+    fn anonymize(self) -> Self {
+        Self {
+            job_id: anonymize_job_id(&self.job_id),
+            job_url: anonymize_url(&self.job_url),
+            ..self
+        }
+    }
+}
+
+fn unconfirmed_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::accounts::user_root(app)?.join(UNCONFIRMED_FILE))
+}
+
+fn modify<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut Vec<UnconfirmedSubmission>) -> T,
+) -> Result<T, String> {
+    crate::state_file::modify(
+        &unconfirmed_path(app)?,
+        "unconfirmed submissions",
+        |entries| Ok(f(entries)),
+    )
+}
+
+fn load(app: &tauri::AppHandle) -> Result<Vec<UnconfirmedSubmission>, String> {
+    crate::state_file::load(&unconfirmed_path(app)?)
+}
+
+/// Delete the unconfirmed submissions file, returning whether there was one
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = unconfirmed_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    crate::paths::remove_file_if_exists(&path)
+}
+
+/// Identifies a submission by what it sends
+fn fingerprint(tool: &str, params: &BTreeMap<String, String>, inputs: &[InputBinding]) -> String {
+    let mut files: Vec<(&str, &Path)> = inputs
+        .iter()
+        .map(|i| (i.param_name.as_str(), i.file_path.as_path()))
+        .collect();
+    files.sort();
+    serde_json::to_string(&(tool, params, files)).unwrap_or_default()
+}
+
+// Fingerprints of the submissions being sent right now
+static SENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Marks a submission as being sent until dropped
+pub struct Sending(String);
+
+impl Drop for Sending {
+    fn drop(&mut self) {
+        let mut sending = SENDING.lock().unwrap();
+        if let Some(at) = sending.iter().position(|f| *f == self.0) {
+            sending.remove(at);
+        }
+    }
+}
+
+/// Claim a submission for sending, refusing one that is already being sent
+/// or is still unconfirmed
+pub fn begin(
+    app: &tauri::AppHandle,
+    tool: &str,
+    params: &BTreeMap<String, String>,
+    inputs: &[InputBinding],
+) -> Result<Sending, String> {
+    let key = fingerprint(tool, params, inputs);
+    check_unconfirmed(&load(app)?, &key, tool)?;
+    claim(key, tool)
+}
+
+/// Refuse a submission matching `key` that is still unconfirmed
+fn check_unconfirmed(
+    entries: &[UnconfirmedSubmission],
+    key: &str,
+    tool: &str,
+) -> Result<(), String> {
+    let Some(entry) = entries
+        .iter()
+        .find(|e| fingerprint(&e.tool, &e.params, &e.inputs) == key)
+    else {
+        return Ok(());
+    };
+    let job = entry
+        .job_id
+        .as_ref()
+        .map(|id| format!(" as {}", anonymize_job_id(id)))
+        .unwrap_or_default();
+    Err(format!(
+        "The same {} job was submitted{} at {} and hasn't been confirmed yet; \
+         check the job list or dismiss it before submitting again",
+        tool, job, entry.submitted_at
+    ))
+}
+
+/// Mark the submission `key` as being sent, unless it already is
+fn claim(key: String, tool: &str) -> Result<Sending, String> {
+    let mut sending = SENDING.lock().unwrap();
+    if sending.contains(&key) {
+        return Err(format!("The same {} job is already being submitted", tool));
+    }
+    sending.push(key.clone());
+    Ok(Sending(key))
+}
+
+/// The status URL of a recovered handle: the one in the response, or else
+/// the one built from the job ID
+fn job_url(handle: &RecoveredHandle, username: &str) -> Option<String> {
+    handle.self_uri.clone().or_else(|| {
+        handle
+            .job_id
+            .as_ref()
+            .map(|id| format!("{}/job/{}/{}", crate::api::base_url(), username, id))
+    })
+}
+
+fn truncate(mut raw: String) -> String {
+    if raw.len() > MAX_RAW_BYTES {
+        let mut end = MAX_RAW_BYTES;
+        while !raw.is_char_boundary(end) {
+            end -= 1;
+        }
+        raw.truncate(end);
+    }
+    raw
+}
+
+/// Keep an accepted submission whose response couldn't be read, for the
+/// worker to confirm
+pub fn record(
+    app: &tauri::AppHandle,
+    username: &str,
+    unread: UnreadSubmission,
+    tool: &str,
+    params: &BTreeMap<String, String>,
+    inputs: &[InputBinding],
+    project_id: Option<String>,
+) -> Result<UnconfirmedSubmission, String> {
+    let UnreadSubmission { raw, handle, error } = unread;
+    let job_url = job_url(&handle, username);
+    let entry = UnconfirmedSubmission {
+        id: format!(
+            "unconfirmed-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+        ),
+        tool: tool.to_string(),
+        params: params.clone(),
+        inputs: inputs.to_vec(),
+        project_id,
+        submitted_at: crate::clock::now().to_rfc3339(),
+        raw_response: truncate(raw),
+        error,
+        job_id: handle.job_id,
+        job_url,
+        attempts: 0,
+        last_error: None,
+    };
+    let stored = entry.clone();
+    modify(app, |entries| entries.push(stored))?;
+    Ok(entry)
+}
+
+// Name of the worker in background errors
+const SOURCE: &str = "submission_confirmation";
+
+/// Record a confirmed job the way a normal submission does
+fn adopt(app: &tauri::AppHandle, session: &SessionSnapshot, entry: &UnconfirmedSubmission) {
+    let Some(job_url) = &entry.job_url else {
+        return;
+    };
+    let api = match app
+        .state::<AppState>()
+        .client
+        .blocking_api(session.credentials.clone())
+    {
+        Ok(api) => api,
+        Err(e) => {
+            background_errors::report(app, SOURCE, ErrorKind::Other, e);
+            return;
+        }
+    };
+    let record = match api.job_record(job_url) {
+        Ok(record) => record,
+        Err(e) => {
+            let error = e.to_string();
+            if crate::api::is_network_error(&e) {
+                background_errors::report(app, SOURCE, ErrorKind::Network, error.clone());
+            }
+            let _ = modify(app, |entries| {
+                if let Some(e) = entries.iter_mut().find(|e| e.id == entry.id) {
+                    e.attempts += 1;
+                    e.last_error = Some(error);
+                }
+            });
+            return;
+        }
+    };
+    background_errors::resolved(app, SOURCE);
+    let status = record.status;
+
+    let state = app.state::<AppState>();
+    if state.session.is_current(session.generation) {
+        state.monitor.nudge(&status.self_uri, &status.job_id);
+    }
+    if let Err(e) = crate::local_jobs::record_submission(
+        app,
+        &status.job_id,
+        &status.self_uri,
+        &entry.tool,
+        &entry.params,
+        &status.job_stage,
+    ) {
+        background_errors::report(
+            app,
+            crate::local_jobs::RECORD_SOURCE,
+            ErrorKind::Other,
+            format!("Failed to record submitted job: {}", e),
+        );
+    }
+    if let Some(project_id) = &entry.project_id {
+        if let Err(e) = crate::projects::assign(app, &status.job_id, Some(project_id)) {
+            background_errors::report(
+                app,
+                crate::local_jobs::RECORD_SOURCE,
+                ErrorKind::Other,
+                format!("Failed to assign job to project: {}", e),
+            );
+        }
+    }
+    let _ = modify(app, |entries| entries.retain(|e| e.id != entry.id));
+
+    activity_feed::record(
+        app,
+        None,
+        Activity::new(
+            ActivityKind::Submission,
+            ActivityStatus::Succeeded,
+            format!("{} job confirmed", entry.tool),
+        )
+        .job(&status.job_id),
+    );
+    anonymize::emit(
+        app,
+        crate::events::SUBMISSION_CONFIRMED,
+        SubmissionConfirmed {
+            id: entry.id.clone(),
+            job_id: status.job_id,
+            job_url: status.self_uri,
+            tool: entry.tool.clone(),
+        },
+    );
+}
+
+fn confirm_all(app: &tauri::AppHandle, session: SessionSnapshot) {
+    let entries = match load(app) {
+        Ok(entries) => entries,
+        Err(e) => {
+            background_errors::report(app, SOURCE, ErrorKind::Other, e);
+            return;
+        }
+    };
+    for entry in entries.iter().filter(|e| e.job_url.is_some()) {
+        if !app
+            .state::<AppState>()
+            .session
+            .is_current(session.generation)
+        {
+            return;
+        }
+        adopt(app, &session, entry);
+    }
+}
+
+/// Start the worker that confirms unconfirmed submissions
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(RETRY_INTERVAL).await;
+
+            let state = app.state::<AppState>();
+            let Some(session) = state.session.snapshot() else {
+                continue;
+            };
+            if state.monitor.is_offline() {
+                continue;
+            }
+
+            let worker_app = app.clone();
+            let _ = tokio::task::spawn_blocking(move || confirm_all(&worker_app, session)).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn list_unconfirmed_submissions(
+    app: tauri::AppHandle,
+) -> Result<Vec<UnconfirmedSubmission>, String> {
+    Ok(anonymize::outgoing(&app, load(&app)?))
+}
+
+/// Stop tracking an unconfirmed submission, e.g. once it has been found in
+/// the job list or is known not to exist
+#[tauri::command]
+pub async fn dismiss_unconfirmed_submission(
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, crate::i18n::Msg::ActionSubmitJobs)?;
+    let removed = modify(&app, |entries| {
+        let before = entries.len();
+        entries.retain(|e| e.id != id);
+        before != entries.len()
+    })?;
+    if !removed {
+        return Err(format!("No unconfirmed submission {}", id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(value: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([("runtime_".to_string(), value.to_string())])
+    }
+
+    fn inputs(names: &[&str]) -> Vec<InputBinding> {
+        names
+            .iter()
+            .map(|name| InputBinding {
+                param_name: format!("input.{}", name),
+                file_path: PathBuf::from(format!("/data/{}.zip", name)),
+            })
+            .collect()
+    }
+
+    fn entry(tool: &str, runtime: &str, files: &[&str]) -> UnconfirmedSubmission {
+        UnconfirmedSubmission {
+            id: "unconfirmed-1".to_string(),
+            tool: tool.to_string(),
+            params: params(runtime),
+            inputs: inputs(files),
+            project_id: None,
+            submitted_at: "2026-01-02T03:04:05+00:00".to_string(),
+            raw_response: "<jobstatus>".to_string(),
+            error: "unexpected end of file".to_string(),
+            job_id: Some("NGBW-JOB-PY_EXPANSE-1A2B".to_string()),
+            job_url: None,
+            attempts: 0,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn an_unconfirmed_submission_blocks_the_same_one_again() {
+        let entries = [entry("PY_EXPANSE", "1.0", &["a", "b"])];
+        // Inputs in another order are the same submission
+        let same = fingerprint("PY_EXPANSE", &params("1.0"), &inputs(&["b", "a"]));
+        let error = check_unconfirmed(&entries, &same, "PY_EXPANSE").unwrap_err();
+        assert!(error.contains("NGBW-JOB-PY_EXPANSE-1A2B"), "{}", error);
+        assert!(error.contains("hasn't been confirmed"), "{}", error);
+    }
+
+    #[test]
+    fn a_different_submission_goes_through() {
+        let entries = [entry("PY_EXPANSE", "1.0", &["a"])];
+        for key in [
+            fingerprint("PY_EXPANSE", &params("2.0"), &inputs(&["a"])),
+            fingerprint("PY_EXPANSE", &params("1.0"), &inputs(&["c"])),
+            fingerprint("NEURON_EXPANSE", &params("1.0"), &inputs(&["a"])),
+        ] {
+            assert!(check_unconfirmed(&entries, &key, "PY_EXPANSE").is_ok());
+        }
+        assert!(check_unconfirmed(&[], "anything", "PY_EXPANSE").is_ok());
+    }
+
+    #[test]
+    fn a_submission_being_sent_is_claimed_once() {
+        let key = fingerprint("CLAIM_TEST", &params("1.0"), &[]);
+        let sending = claim(key.clone(), "CLAIM_TEST").unwrap();
+        assert!(claim(key.clone(), "CLAIM_TEST").is_err());
+        drop(sending);
+        assert!(claim(key, "CLAIM_TEST").is_ok());
+    }
+
+    #[test]
+    fn the_url_from_the_response_is_preferred_to_one_built_from_the_id() {
+        let built = RecoveredHandle {
+            job_id: Some("NGBW-JOB-1".to_string()),
+            self_uri: None,
+        };
+        assert_eq!(
+            job_url(&built, "alice").as_deref(),
+            Some(format!("{}/job/alice/NGBW-JOB-1", crate::api::base_url()).as_str())
+        );
+        let listed = RecoveredHandle {
+            self_uri: Some("https://nsg.example/job/alice/NGBW-JOB-1".to_string()),
+            ..built
+        };
+        assert_eq!(
+            job_url(&listed, "alice").as_deref(),
+            Some("https://nsg.example/job/alice/NGBW-JOB-1")
+        );
+        assert_eq!(job_url(&RecoveredHandle::default(), "alice"), None);
+    }
+
+    #[test]
+    fn a_cut_off_response_falls_back_to_the_built_url() {
+        let handle = crate::xml::recover_job_handle(
+            "<jobstatus><jobHandle>NGBW-JOB-1</jobHandle><selfUri><url>https://nsgr.sd",
+        );
+        assert_eq!(
+            job_url(&handle, "alice"),
+            Some(format!("{}/job/alice/NGBW-JOB-1", crate::api::base_url()))
+        );
+    }
+}
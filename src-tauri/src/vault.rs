@@ -0,0 +1,282 @@
+// Passphrase-encrypted secrets, the credential backend of portable mode.
+//
+// The OS credential store is off in portable mode, so profile passwords and
+// app keys would sit in profiles.json on the stick as plain text. They are
+// sealed instead with AES-256-GCM under a key derived from a passphrase the
+// user picks (PBKDF2-HMAC-SHA256 with a random salt), each bound to the
+// profile it belongs to. vault.json next to the other data holds the salt
+// and a sealed check value that tells a wrong passphrase apart, never the
+// key itself. The key is kept in memory from unlock_vault until lock_vault
+// or exit. Plain secrets left from before are sealed at the first unlock.
+
+use crate::i18n::Msg;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const VAULT_FILE: &str = "vault.json";
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const MIN_PASSPHRASE_CHARS: usize = 8;
+// What the check value seals, and the context it's bound to
+const CHECK_TEXT: &str = "nsg-gui vault";
+const CHECK_CONTEXT: &str = "check";
+
+static KEY: Mutex<Option<[u8; KEY_LEN]>> = Mutex::new(None);
+
+/// A value sealed under the vault key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sealed {
+    nonce: String,
+    data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultFile {
+    salt: String,
+    iterations: u32,
+    check: Sealed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    // A passphrase has been set
+    pub exists: bool,
+    pub unlocked: bool,
+}
+
+fn vault_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(crate::portable::app_data_root(app)?.join(VAULT_FILE))
+}
+
+fn derive(passphrase: &str, salt: &[u8], iterations: u32) -> Result<[u8; KEY_LEN], String> {
+    let iterations =
+        NonZeroU32::new(iterations).ok_or_else(|| "The vault file is not valid".to_string())?;
+    let mut key = [0; KEY_LEN];
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    Ok(key)
+}
+
+fn aead_key(key: &[u8; KEY_LEN]) -> LessSafeKey {
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).expect("AES-256 key length"))
+}
+
+fn seal_with(key: &[u8; KEY_LEN], context: &str, plain: &str) -> Result<Sealed, String> {
+    let mut nonce = [0; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| "Failed to generate a nonce".to_string())?;
+    let mut data = plain.as_bytes().to_vec();
+    aead_key(key)
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(context.as_bytes()),
+            &mut data,
+        )
+        .map_err(|_| "Failed to encrypt the secret".to_string())?;
+    Ok(Sealed {
+        nonce: BASE64.encode(nonce),
+        data: BASE64.encode(data),
+    })
+}
+
+// None when the key is wrong or the value was tampered with
+fn open_with(key: &[u8; KEY_LEN], context: &str, sealed: &Sealed) -> Option<String> {
+    let nonce: [u8; NONCE_LEN] = BASE64.decode(&sealed.nonce).ok()?.try_into().ok()?;
+    let mut data = BASE64.decode(&sealed.data).ok()?;
+    let plain = aead_key(key)
+        .open_in_place(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(context.as_bytes()),
+            &mut data,
+        )
+        .ok()?;
+    String::from_utf8(plain.to_vec()).ok()
+}
+
+fn create(passphrase: &str, iterations: u32) -> Result<([u8; KEY_LEN], VaultFile), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(format!(
+            "The passphrase needs at least {} characters",
+            MIN_PASSPHRASE_CHARS
+        ));
+    }
+    let mut salt = [0; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| "Failed to generate a salt".to_string())?;
+    let key = derive(passphrase, &salt, iterations)?;
+    let vault = VaultFile {
+        salt: BASE64.encode(salt),
+        iterations,
+        check: seal_with(&key, CHECK_CONTEXT, CHECK_TEXT)?,
+    };
+    Ok((key, vault))
+}
+
+fn unlock(passphrase: &str, vault: &VaultFile) -> Result<[u8; KEY_LEN], String> {
+    let salt = BASE64
+        .decode(&vault.salt)
+        .map_err(|_| "The vault file is not valid".to_string())?;
+    let key = derive(passphrase, &salt, vault.iterations)?;
+    match open_with(&key, CHECK_CONTEXT, &vault.check) {
+        Some(check) if check == CHECK_TEXT => Ok(key),
+        _ => Err("Wrong passphrase".to_string()),
+    }
+}
+
+/// Whether secrets are sealed here rather than kept in the OS store or as
+/// plain text
+pub fn in_use() -> bool {
+    crate::portable::mode() == crate::portable::DataMode::Portable
+}
+
+/// Seal `plain` under the unlocked key, bound to `context`
+pub fn seal(context: &str, plain: &str) -> Result<Sealed, String> {
+    let key = KEY
+        .lock()
+        .unwrap()
+        .ok_or_else(|| "Unlock the vault with its passphrase to save secrets".to_string())?;
+    seal_with(&key, context, plain)
+}
+
+/// Open a value sealed for `context`
+pub fn open(context: &str, sealed: &Sealed) -> Result<String, String> {
+    let key = KEY
+        .lock()
+        .unwrap()
+        .ok_or_else(|| "Unlock the vault with its passphrase to use saved secrets".to_string())?;
+    open_with(&key, context, sealed).ok_or_else(|| "A sealed secret is not valid".to_string())
+}
+
+/// Delete vault.json and forget the key, returning whether there was a file
+pub fn purge(app: &tauri::AppHandle) -> Result<bool, String> {
+    let path = vault_path(app)?;
+    let _lock = crate::state_file::lock(&path)?;
+    *KEY.lock().unwrap() = None;
+    crate::paths::remove_file_if_exists(&path)
+}
+
+fn load(path: &Path) -> Result<Option<VaultFile>, String> {
+    crate::state_file::load(path)
+}
+
+#[tauri::command]
+pub async fn get_vault_status(app: tauri::AppHandle) -> Result<VaultStatus, String> {
+    Ok(VaultStatus {
+        exists: load(&vault_path(&app)?)?.is_some(),
+        unlocked: KEY.lock().unwrap().is_some(),
+    })
+}
+
+/// Set the passphrase secrets are sealed with, unlocking the vault
+#[tauri::command]
+pub async fn create_vault(app: tauri::AppHandle, passphrase: String) -> Result<(), String> {
+    crate::mode::ensure_writable(&app, Msg::ActionChangeConnectionSettings)?;
+    let path = vault_path(&app)?;
+    let key = tokio::task::spawn_blocking(move || {
+        crate::state_file::modify(&path, "vault", |vault: &mut Option<VaultFile>| {
+            if vault.is_some() {
+                return Err("A vault passphrase is already set".to_string());
+            }
+            let (key, created) = create(&passphrase, PBKDF2_ITERATIONS)?;
+            *vault = Some(created);
+            Ok(key)
+        })
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))??;
+    *KEY.lock().unwrap() = Some(key);
+    crate::profiles::seal_plain_secrets(&app)
+}
+
+/// Unlock the vault for this run, sealing any plain secrets left over
+#[tauri::command]
+pub async fn unlock_vault(app: tauri::AppHandle, passphrase: String) -> Result<(), String> {
+    let path = vault_path(&app)?;
+    let key = tokio::task::spawn_blocking(move || {
+        let vault = load(&path)?.ok_or_else(|| "No vault passphrase is set".to_string())?;
+        unlock(&passphrase, &vault)
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))??;
+    *KEY.lock().unwrap() = Some(key);
+    if crate::mode::ensure_writable(&app, Msg::ActionChangeConnectionSettings).is_ok() {
+        crate::profiles::seal_plain_secrets(&app)?;
+    }
+    Ok(())
+}
+
+/// Forget the key until the next unlock
+#[tauri::command]
+pub async fn lock_vault() -> Result<(), String> {
+    *KEY.lock().unwrap() = None;
+    Ok(())
+}
+
+/// Unlock with a throwaway passphrase, for tests of what is sealed
+#[cfg(test)]
+pub fn unlock_for_tests() {
+    let (key, _) = create("test passphrase", 1).unwrap();
+    *KEY.lock().unwrap() = Some(key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fast enough for a debug build; the real count only costs time
+    const ITERATIONS: u32 = 1_000;
+
+    #[test]
+    fn the_right_passphrase_opens_what_was_sealed() {
+        let (key, vault) = create("correct horse", ITERATIONS).unwrap();
+        let sealed = seal_with(&key, "profile-1", "hunter2").unwrap();
+        assert!(!sealed.data.contains("hunter2"));
+
+        let unlocked = unlock("correct horse", &vault).unwrap();
+        assert_eq!(
+            open_with(&unlocked, "profile-1", &sealed).as_deref(),
+            Some("hunter2")
+        );
+    }
+
+    #[test]
+    fn a_wrong_passphrase_is_refused() {
+        let (_, vault) = create("correct horse", ITERATIONS).unwrap();
+        assert!(unlock("wrong horse", &vault).is_err());
+    }
+
+    #[test]
+    fn a_secret_only_opens_for_its_own_context() {
+        let (key, _) = create("correct horse", ITERATIONS).unwrap();
+        let sealed = seal_with(&key, "profile-1", "hunter2").unwrap();
+        assert_eq!(open_with(&key, "profile-2", &sealed), None);
+    }
+
+    #[test]
+    fn each_seal_uses_a_fresh_nonce() {
+        let (key, _) = create("correct horse", ITERATIONS).unwrap();
+        let first = seal_with(&key, "profile-1", "hunter2").unwrap();
+        let second = seal_with(&key, "profile-1", "hunter2").unwrap();
+        assert_ne!(first.nonce, second.nonce);
+        assert_ne!(first.data, second.data);
+    }
+
+    #[test]
+    fn a_short_passphrase_is_refused() {
+        assert!(create("short", ITERATIONS).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+// Checking a downloaded archive against the server, long after the fact.
+//
+// A local copy is compared with the job's current results listing: every
+// file gets a verdict, and files on only one side are listed. Sizes come
+// from the listing; hashes are recomputed and compared with the archive's
+// checksums.sha256 where it has one. Once NSG has purged the job, the
+// archive's job_info.json is the only record of what was downloaded, and
+// it's used instead, which the result says.
+
+use crate::anonymize;
+use crate::api::is_network_error;
+use crate::archive::{open_archive, read_job_info};
+use crate::checksum::{self, CHECKSUMS_ENTRY};
+use crate::download::{entry_name, JOB_INFO_ENTRY};
+use crate::AppState;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use tauri::State;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceSource {
+    Server,
+    // job_info.json in the archive, after the server purged the job
+    Manifest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileVerdict {
+    Match,
+    SizeMismatch,
+    // Size is right but the content doesn't match checksums.sha256
+    HashMismatch,
+    // In the reference but not in the archive: added later or never
+    // downloaded
+    OnlyOnServer,
+    // Left out on purpose by the download patterns
+    Excluded,
+    OnlyInArchive,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileCheck {
+    pub name: String,
+    pub verdict: FileVerdict,
+    pub reference_size: Option<u64>,
+    pub archive_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    // Every file matches; excluded files don't count against it
+    Verified,
+    // Files are missing on one side, but nothing present differs
+    Incomplete,
+    // At least one file differs in size or content
+    Mismatched,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerVerification {
+    pub reference: ReferenceSource,
+    // Why the manifest was used instead of the server
+    pub fallback_reason: Option<String>,
+    // Whether hashes were checked; false without checksums.sha256
+    pub hashes_checked: bool,
+    pub files: Vec<FileCheck>,
+    pub status: VerificationStatus,
+}
+
+struct ArchiveFile {
+    size: u64,
+    // None when the manifest doesn't list the file
+    hash_ok: Option<bool>,
+}
+
+/// Sizes and hash results of the result entries of an archive
+fn archive_files(
+    archive: &mut zip::ZipArchive<std::fs::File>,
+) -> Result<(BTreeMap<String, ArchiveFile>, bool), String> {
+    let manifest = checksum::read_manifest(archive).ok();
+
+    let mut files = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read archive entry: {}", e))?;
+        let name = entry.name().to_string();
+        if entry.is_dir() || name == CHECKSUMS_ENTRY || name == JOB_INFO_ENTRY {
+            continue;
+        }
+
+        let expected = manifest.as_ref().and_then(|m| m.get(&name));
+        let (size, hash_ok) = match expected {
+            Some(expected) => {
+                let (size, hash) = checksum::copy_hashed(&mut entry, &mut std::io::sink())
+                    .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+                (size, Some(&hash == expected))
+            }
+            None => (entry.size(), None),
+        };
+        files.insert(name, ArchiveFile { size, hash_ok });
+    }
+    Ok((files, manifest.is_some()))
+}
+
+fn compare(
+    reference: &BTreeMap<String, u64>,
+    excluded: &BTreeSet<String>,
+    archive: &BTreeMap<String, ArchiveFile>,
+) -> Vec<FileCheck> {
+    let names: BTreeSet<&String> = reference.keys().chain(archive.keys()).collect();
+    names
+        .into_iter()
+        .map(|name| {
+            let reference_size = reference.get(name).copied();
+            let local = archive.get(name);
+            let verdict = match (reference_size, local) {
+                (Some(expected), Some(local)) if expected != local.size => {
+                    FileVerdict::SizeMismatch
+                }
+                (Some(_), Some(local)) if local.hash_ok == Some(false) => FileVerdict::HashMismatch,
+                (Some(_), Some(_)) => FileVerdict::Match,
+                (Some(_), None) if excluded.contains(name) => FileVerdict::Excluded,
+                (Some(_), None) => FileVerdict::OnlyOnServer,
+                (None, _) => FileVerdict::OnlyInArchive,
+            };
+            FileCheck {
+                name: name.clone(),
+                verdict,
+                reference_size,
+                archive_size: local.map(|f| f.size),
+            }
+        })
+        .collect()
+}
+
+fn overall(files: &[FileCheck]) -> VerificationStatus {
+    let verdicts = || files.iter().map(|f| f.verdict);
+    if verdicts().any(|v| matches!(v, FileVerdict::SizeMismatch | FileVerdict::HashMismatch)) {
+        VerificationStatus::Mismatched
+    } else if verdicts().all(|v| matches!(v, FileVerdict::Match | FileVerdict::Excluded)) {
+        VerificationStatus::Verified
+    } else {
+        VerificationStatus::Incomplete
+    }
+}
+
+/// Compare a downloaded archive with the job's results on the server, or
+/// with the archive's own job_info.json once the server has purged them
+#[tauri::command]
+pub async fn verify_against_server(
+    job_url: String,
+    archive_path: PathBuf,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ServerVerification, String> {
+    let archive_path = crate::path_access::authorize(&app, &archive_path)?;
+    let session = state.session.require()?;
+
+    let api = state.client.api(session.credentials).await?;
+    let url = anonymize::real_url(&job_url);
+    let listing = tokio::task::spawn_blocking(move || {
+        let record = api.job_record(&url)?;
+        api.output_files(&record.status)
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?;
+    state.session.ensure_current(session.generation)?;
+
+    // Only an unreachable server is an error; any other failure means the
+    // results are gone
+    let listing = match listing {
+        Ok(listing) => Ok(listing),
+        Err(e) if is_network_error(&e) => {
+            return Err(format!("Failed to reach NSG: {}", e));
+        }
+        Err(e) => Err(e.to_string()),
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let mut archive = open_archive(&archive_path)?;
+        let job_info = read_job_info(&mut archive);
+        let (files, hashes_checked) = archive_files(&mut archive)?;
+
+        // Name the reference files the way the archive named its entries, so
+        // an archive with the tree preserved is checked for structure too
+        let tree = job_info.as_ref().is_some_and(|info| info.preserve_tree);
+        let renamed = job_info.as_ref().map(|info| &info.renamed);
+        let local_names = |names: Vec<(String, u64)>| -> BTreeMap<String, u64> {
+            names
+                .into_iter()
+                .filter_map(|(name, size)| {
+                    let local = match renamed.and_then(|r| r.get(&name)) {
+                        Some(local) => local.clone(),
+                        None => entry_name(&name, tree).ok()?,
+                    };
+                    Some((local, size))
+                })
+                .collect()
+        };
+        let excluded: BTreeSet<String> = job_info
+            .iter()
+            .flat_map(|info| &info.excluded)
+            .filter_map(|f| entry_name(&f.filename, tree).ok())
+            .collect();
+
+        let (reference, source, fallback_reason) = match listing {
+            Ok(listing) => (
+                local_names(listing.into_iter().map(|f| (f.filename, f.size)).collect()),
+                ReferenceSource::Server,
+                None,
+            ),
+            Err(error) => {
+                let info = job_info.as_ref().ok_or_else(|| {
+                    format!(
+                        "The results are no longer on the server ({}) and the archive has no readable {}",
+                        error, JOB_INFO_ENTRY
+                    )
+                })?;
+                (
+                    local_names(
+                        info.files
+                            .iter()
+                            .map(|f| (f.filename.clone(), f.size))
+                            .collect(),
+                    ),
+                    ReferenceSource::Manifest,
+                    Some(format!(
+                        "The results are no longer on the server ({}); compared with {} instead",
+                        error, JOB_INFO_ENTRY
+                    )),
+                )
+            }
+        };
+
+        let checks = compare(&reference, &excluded, &files);
+        Ok(ServerVerification {
+            reference: source,
+            fallback_reason,
+            hashes_checked,
+            status: overall(&checks),
+            files: checks,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task error: {}", e))?
+}
@@ -0,0 +1,291 @@
+// Keeping windows on screen.
+//
+// tauri-plugin-window-state puts each window back where it was, which can
+// be larger than the screen it is on now (a 1366x768 laptop after a big
+// external display) or on a monitor that is no longer attached. After the
+// plugin has restored a window, sanitize finds the monitor that holds the
+// restored top-left corner, falling back to the primary monitor when none
+// does, shrinks the window to that monitor's work area, moves it so that
+// every edge is on screen, and sets a minimum size that still fits there.
+// Maximized and fullscreen windows are left to the OS. What was changed is
+// kept for get_app_info, so a bug report shows the geometry the app ended
+// up with and why.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{Manager, Monitor, PhysicalPosition, PhysicalSize, WebviewWindow};
+
+// Logical pixels; lowered to the work area on screens smaller than this
+const MIN_SIZE: (f64, f64) = (800.0, 560.0);
+// Share of the work area a reset window may take at most
+const RESET_FILL: f64 = 0.9;
+const MAIN_WINDOW: &str = "main";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        let (left, top) = (self.x as i64, self.y as i64);
+        let (x, y) = (x as i64, y as i64);
+        x >= left && x < left + self.width as i64 && y >= top && y < top + self.height as i64
+    }
+}
+
+/// A window's geometry in physical pixels, for bug reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub label: String,
+    pub outer: Rect,
+    pub scale_factor: f64,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    pub monitor: Option<String>,
+    pub work_area: Option<Rect>,
+    // Corrections made after the geometry was restored, if any
+    pub corrections: Vec<String>,
+}
+
+// Corrections made per window label at startup
+static CORRECTIONS: Mutex<Vec<(String, Vec<String>)>> = Mutex::new(Vec::new());
+
+fn work_area(monitor: &Monitor) -> Rect {
+    let area = monitor.work_area();
+    Rect {
+        x: area.position.x,
+        y: area.position.y,
+        width: area.size.width,
+        height: area.size.height,
+    }
+}
+
+fn bounds(monitor: &Monitor) -> Rect {
+    let (position, size) = (monitor.position(), monitor.size());
+    Rect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    }
+}
+
+/// The monitor holding (x, y) and true, or the primary one and false when
+/// no attached monitor does
+fn monitor_at(window: &WebviewWindow, x: i32, y: i32) -> Option<(Monitor, bool)> {
+    let monitors = window.available_monitors().unwrap_or_default();
+    if let Some(monitor) = monitors.iter().find(|m| bounds(m).contains(x, y)) {
+        return Some((monitor.clone(), true));
+    }
+    let primary = window.primary_monitor().ok().flatten();
+    primary
+        .or_else(|| monitors.into_iter().next())
+        .map(|m| (m, false))
+}
+
+/// Minimum size in physical pixels, no larger than `area`
+fn min_size(area: &Rect, scale_factor: f64) -> PhysicalSize<u32> {
+    PhysicalSize::new(
+        ((MIN_SIZE.0 * scale_factor) as u32).min(area.width),
+        ((MIN_SIZE.1 * scale_factor) as u32).min(area.height),
+    )
+}
+
+/// Start of a span of `len` inside [start, start + available), as near
+/// `at` as possible
+fn clamp_axis(at: i32, len: u32, start: i32, available: u32) -> i32 {
+    let last = start as i64 + available as i64 - len as i64;
+    (at as i64).clamp(start as i64, last.max(start as i64)) as i32
+}
+
+/// Resize to `outer`, given in outer pixels, through the inner size the
+/// window API takes
+fn set_outer_size(window: &WebviewWindow, outer: PhysicalSize<u32>) -> Result<(), String> {
+    let (current_outer, inner) = match (window.outer_size(), window.inner_size()) {
+        (Ok(o), Ok(i)) => (o, i),
+        _ => (outer, outer),
+    };
+    let frame_w = current_outer.width.saturating_sub(inner.width);
+    let frame_h = current_outer.height.saturating_sub(inner.height);
+    window
+        .set_size(PhysicalSize::new(
+            outer.width.saturating_sub(frame_w),
+            outer.height.saturating_sub(frame_h),
+        ))
+        .map_err(|e| format!("Failed to resize the window: {}", e))
+}
+
+/// Fit a restored window onto the screen it is on, or the primary screen
+/// when it was on one that's gone
+pub fn sanitize(window: &WebviewWindow) {
+    let corrections = match fit(window) {
+        Ok(corrections) => corrections,
+        Err(e) => {
+            crate::background_errors::report(
+                window.app_handle(),
+                "window_geometry",
+                crate::background_errors::ErrorKind::Other,
+                format!(
+                    "Failed to check the {} window's geometry: {}",
+                    window.label(),
+                    e
+                ),
+            );
+            return;
+        }
+    };
+    let mut all = CORRECTIONS.lock().unwrap();
+    all.retain(|(label, _)| label != window.label());
+    all.push((window.label().to_string(), corrections));
+}
+
+fn fit(window: &WebviewWindow) -> Result<Vec<String>, String> {
+    let mut corrections = Vec::new();
+    if window.is_minimized().unwrap_or(false) {
+        return Ok(corrections);
+    }
+    let position = window.outer_position().map_err(|e| e.to_string())?;
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+    let Some((monitor, contains)) = monitor_at(window, position.x, position.y) else {
+        return Ok(corrections);
+    };
+    let area = work_area(&monitor);
+    let min = min_size(&area, monitor.scale_factor());
+    window
+        .set_min_size(Some(min))
+        .map_err(|e| format!("Failed to set the minimum size: {}", e))?;
+    if window.is_maximized().unwrap_or(false) || window.is_fullscreen().unwrap_or(false) {
+        return Ok(corrections);
+    }
+
+    let width = size.width.clamp(min.width, area.width);
+    let height = size.height.clamp(min.height, area.height);
+    if (width, height) != (size.width, size.height) {
+        corrections.push(format!(
+            "resized from {}x{} to {}x{}",
+            size.width, size.height, width, height
+        ));
+        set_outer_size(window, PhysicalSize::new(width, height))?;
+    }
+
+    let (x, y) = if contains {
+        (
+            clamp_axis(position.x, width, area.x, area.width),
+            clamp_axis(position.y, height, area.y, area.height),
+        )
+    } else {
+        corrections.push("its monitor is no longer attached".to_string());
+        centered(&area, width, height)
+    };
+    if (x, y) != (position.x, position.y) {
+        corrections.push(format!(
+            "moved from ({}, {}) to ({}, {})",
+            position.x, position.y, x, y
+        ));
+        window
+            .set_position(PhysicalPosition::new(x, y))
+            .map_err(|e| format!("Failed to move the window: {}", e))?;
+    }
+    Ok(corrections)
+}
+
+fn centered(area: &Rect, width: u32, height: u32) -> (i32, i32) {
+    (
+        area.x + (area.width.saturating_sub(width) / 2) as i32,
+        area.y + (area.height.saturating_sub(height) / 2) as i32,
+    )
+}
+
+/// The current geometry of a window
+pub fn geometry(window: &WebviewWindow) -> Option<WindowGeometry> {
+    let position = window.outer_position().ok()?;
+    let size = window.outer_size().ok()?;
+    let monitor = window.current_monitor().ok().flatten();
+    let corrections = CORRECTIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(label, _)| label == window.label())
+        .map(|(_, c)| c.clone())
+        .unwrap_or_default();
+    Some(WindowGeometry {
+        label: window.label().to_string(),
+        outer: Rect {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        },
+        scale_factor: window.scale_factor().unwrap_or(1.0),
+        maximized: window.is_maximized().unwrap_or(false),
+        fullscreen: window.is_fullscreen().unwrap_or(false),
+        monitor: monitor.as_ref().and_then(|m| m.name().cloned()),
+        work_area: monitor.as_ref().map(work_area),
+        corrections,
+    })
+}
+
+/// Fit the main window once the window-state plugin has restored it.
+/// Called once at startup.
+pub fn check(app: &tauri::AppHandle) {
+    use tauri::Manager;
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        sanitize(&window);
+    }
+}
+
+/// Put the main window back at its default size, centered on the primary
+/// monitor, for a window that ended up somewhere it can't be reached
+#[tauri::command]
+pub async fn reset_window_geometry(app: tauri::AppHandle) -> Result<WindowGeometry, String> {
+    use tauri::Manager;
+    let window = app
+        .get_webview_window(MAIN_WINDOW)
+        .ok_or("The main window isn't open")?;
+    let _ = window.set_fullscreen(false);
+    let _ = window.unmaximize();
+    let _ = window.unminimize();
+
+    let monitor = window
+        .primary_monitor()
+        .ok()
+        .flatten()
+        .or_else(|| window.current_monitor().ok().flatten())
+        .ok_or("No monitor found")?;
+    let area = work_area(&monitor);
+    let scale_factor = monitor.scale_factor();
+    let (default_width, default_height) = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == MAIN_WINDOW)
+        .map_or((1600.0, 1300.0), |w| (w.width, w.height));
+    let min = min_size(&area, scale_factor);
+    let fill = |default: f64, available: u32, min: u32| {
+        ((default * scale_factor) as u32)
+            .min((available as f64 * RESET_FILL) as u32)
+            .max(min)
+    };
+    let width = fill(default_width, area.width, min.width);
+    let height = fill(default_height, area.height, min.height);
+    window
+        .set_min_size(Some(min))
+        .map_err(|e| format!("Failed to set the minimum size: {}", e))?;
+    set_outer_size(&window, PhysicalSize::new(width, height))?;
+    let (x, y) = centered(&area, width, height);
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .map_err(|e| format!("Failed to move the window: {}", e))?;
+    let _ = window.set_focus();
+
+    CORRECTIONS
+        .lock()
+        .unwrap()
+        .retain(|(label, _)| label != MAIN_WINDOW);
+    geometry(&window).ok_or_else(|| "Failed to read the window's geometry".to_string())
+}
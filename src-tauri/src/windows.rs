@@ -0,0 +1,91 @@
+// Secondary windows.
+//
+// Job windows load the same frontend as the main window and share all
+// backend state; closing one only drops its webview. Events are emitted
+// app-wide, so every window sees job and download updates and filters them
+// by job ID itself.
+
+use crate::preferences;
+use serde_json::json;
+use tauri::{Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+
+const JOB_WINDOW_PREFIX: &str = "job-";
+
+/// Window label for a job. Labels only allow a small charset, so anything
+/// else in the job ID is replaced.
+fn job_window_label(job_url: &str) -> String {
+    let job_id = job_url.rsplit('/').next().unwrap_or(job_url);
+    let sanitized: String = job_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", JOB_WINDOW_PREFIX, sanitized)
+}
+
+/// Open a job in its own window, or focus the window already showing it.
+///
+/// The job URL and the persisted zoom/theme are handed to the page through
+/// `window.__NSG_JOB_CONTEXT__` before any of its scripts run.
+#[tauri::command]
+pub async fn open_job_window(app: tauri::AppHandle, job_url: String) -> Result<String, String> {
+    let label = job_window_label(&job_url);
+
+    if let Some(existing) = app.get_webview_window(&label) {
+        let _ = existing.unminimize();
+        existing
+            .set_focus()
+            .map_err(|e| format!("Failed to focus window: {}", e))?;
+        return Ok(label);
+    }
+
+    let prefs = preferences::of(&app);
+    let zoom = prefs
+        .get("zoom_level")
+        .and_then(|v| v.as_f64())
+        .unwrap_or(1.0);
+    let theme = prefs
+        .get("theme")
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| "system".to_string());
+
+    let context = json!({
+        "job_url": job_url,
+        "zoom": zoom,
+        "theme": theme,
+    });
+    let init_script = format!(
+        "window.__NSG_JOB_CONTEXT__ = {context};
+         document.addEventListener('DOMContentLoaded', () => {{
+           document.body.style.zoom = '{zoom}';
+         }});"
+    );
+
+    let native_theme = match theme.as_str() {
+        "dark" => Some(tauri::Theme::Dark),
+        "light" => Some(tauri::Theme::Light),
+        _ => None,
+    };
+
+    let job_id = job_url.rsplit('/').next().unwrap_or(&job_url);
+    let window = WebviewWindowBuilder::new(&app, &label, WebviewUrl::default())
+        .title(format!("NSG GUI - {}", job_id))
+        .inner_size(900.0, 800.0)
+        .resizable(true)
+        .theme(native_theme)
+        .initialization_script(&init_script)
+        .build()
+        .map_err(|e| format!("Failed to open job window: {}", e))?;
+    crate::window_geometry::sanitize(&window);
+
+    // Also available as an event for pages that listen instead of reading
+    // the injected global
+    let _ = window.emit_to(&label, crate::events::JOB_CONTEXT, context);
+
+    Ok(label)
+}
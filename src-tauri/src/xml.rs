@@ -0,0 +1,539 @@
+// Parsing of CIPRES job XML fields that nsg-cli's models don't capture.
+// Everything here is best-effort: older job records simply lack some fields.
+
+use crate::monitor::parse_date;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct JobExtras {
+    pub compute_resource: Option<String>,
+    pub tool_version: Option<String>,
+    // Whether NSG was asked to email on completion, and where to
+    pub status_email: Option<bool>,
+    pub email_address: Option<String>,
+    // When NSG will delete the results, if the record says
+    pub results_expire_at: Option<String>,
+    // <metadata><entry><key/><value/></entry></metadata>
+    pub metadata: BTreeMap<String, String>,
+    // <messages><message>, oldest first
+    pub messages: Vec<JobMessage>,
+    // <workingDirUri><url>, set while the job runs
+    pub working_dir_uri: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JobMessage {
+    pub timestamp: Option<String>,
+    pub stage: Option<String>,
+    pub text: String,
+}
+
+const COMPUTE_RESOURCE_KEYS: &[&str] = &["computeresource", "compute_resource", "resource"];
+const TOOL_VERSION_KEYS: &[&str] = &["toolversion", "tool_version"];
+const STATUS_EMAIL_KEYS: &[&str] = &["statusemail", "metadata.statusemail"];
+const EMAIL_ADDRESS_KEYS: &[&str] = &["emailaddress", "metadata.emailaddress"];
+// Per-file modification times in a <jobfile>, where the server sends one
+const FILE_DATE_KEYS: &[&str] = &["datemodified", "lastmodified", "modified", "datecreated"];
+const EXPIRES_KEYS: &[&str] = &[
+    "dateexpires",
+    "expirationdate",
+    "expiration_date",
+    "resultsexpire",
+    "date_expires",
+];
+
+fn lookup(fields: &BTreeMap<String, String>, keys: &[&str]) -> Option<String> {
+    fields
+        .iter()
+        .find(|(k, v)| keys.contains(&k.to_lowercase().as_str()) && !v.is_empty())
+        .map(|(_, v)| v.clone())
+}
+
+/// Extract extra fields from a `<jobstatus>` document
+pub fn parse_job_extras(xml: &str) -> JobExtras {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut metadata = BTreeMap::new();
+    // Plain elements anywhere outside metadata, e.g. <toolVersion>
+    let mut elements = BTreeMap::new();
+
+    let mut messages = Vec::new();
+
+    let mut current_tag = String::new();
+    let mut in_metadata = false;
+    let mut current_key: Option<String> = None;
+    let mut current_value: Option<String> = None;
+    let mut current_message: Option<JobMessage> = None;
+    let mut in_working_dir = false;
+    let mut working_dir_uri = None;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                current_tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                match current_tag.as_str() {
+                    "metadata" => in_metadata = true,
+                    "entry" if in_metadata => {
+                        current_key = None;
+                        current_value = None;
+                    }
+                    "message" => current_message = Some(JobMessage::default()),
+                    "workingDirUri" => in_working_dir = true,
+                    _ => {}
+                }
+            }
+            Ok(Event::End(e)) => {
+                let tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                match tag.as_str() {
+                    "metadata" => in_metadata = false,
+                    "entry" if in_metadata => {
+                        if let Some(key) = current_key.take() {
+                            metadata.insert(key, current_value.take().unwrap_or_default());
+                        }
+                    }
+                    "message" => messages.extend(current_message.take()),
+                    "workingDirUri" => in_working_dir = false,
+                    _ => {}
+                }
+                current_tag.clear();
+            }
+            Ok(Event::Text(e)) => {
+                let text = reader
+                    .decoder()
+                    .decode(e.as_ref())
+                    .map(|s| s.to_string())
+                    .unwrap_or_default();
+                if let Some(message) = current_message.as_mut() {
+                    match current_tag.as_str() {
+                        "timestamp" => message.timestamp = Some(text),
+                        "stage" => message.stage = Some(text),
+                        "text" => message.text = text,
+                        _ => {}
+                    }
+                    buf.clear();
+                    continue;
+                }
+                if in_working_dir && current_tag == "url" {
+                    working_dir_uri = Some(text);
+                    buf.clear();
+                    continue;
+                }
+                match current_tag.as_str() {
+                    "key" if in_metadata => current_key = Some(text),
+                    "value" if in_metadata => current_value = Some(text),
+                    tag if !in_metadata && !tag.is_empty() => {
+                        elements.entry(tag.to_string()).or_insert(text);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    JobExtras {
+        compute_resource: lookup(&elements, COMPUTE_RESOURCE_KEYS)
+            .or_else(|| lookup(&metadata, COMPUTE_RESOURCE_KEYS)),
+        tool_version: lookup(&elements, TOOL_VERSION_KEYS)
+            .or_else(|| lookup(&metadata, TOOL_VERSION_KEYS)),
+        status_email: lookup(&metadata, STATUS_EMAIL_KEYS).map(|v| v.eq_ignore_ascii_case("true")),
+        email_address: lookup(&metadata, EMAIL_ADDRESS_KEYS),
+        results_expire_at: lookup(&elements, EXPIRES_KEYS)
+            .or_else(|| lookup(&metadata, EXPIRES_KEYS))
+            .and_then(|d| parse_date(Some(&d)))
+            .map(|d| d.to_rfc3339()),
+        metadata,
+        messages,
+        working_dir_uri,
+    }
+}
+
+/// Modification time (RFC 3339) of each file in a results or working
+/// directory listing, by file name. Files without one are left out.
+pub fn parse_file_dates(xml: &str) -> BTreeMap<String, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut dates = BTreeMap::new();
+    let mut current_tag = String::new();
+    let mut in_jobfile = false;
+    let mut filename: Option<String> = None;
+    let mut fields = BTreeMap::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                current_tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                if current_tag == "jobfile" {
+                    in_jobfile = true;
+                    filename = None;
+                    fields.clear();
+                }
+            }
+            Ok(Event::End(e)) => {
+                if e.name().as_ref() == b"jobfile" {
+                    in_jobfile = false;
+                    let date = lookup(&fields, FILE_DATE_KEYS)
+                        .and_then(|d| parse_date(Some(&d)))
+                        .map(|d| d.to_rfc3339());
+                    if let (Some(name), Some(date)) = (filename.take(), date) {
+                        dates.insert(name, date);
+                    }
+                }
+                current_tag.clear();
+            }
+            Ok(Event::Text(e)) if in_jobfile => {
+                let text = reader
+                    .decoder()
+                    .decode(e.as_ref())
+                    .map(|s| s.to_string())
+                    .unwrap_or_default();
+                match current_tag.as_str() {
+                    "filename" => filename = Some(text),
+                    "" => {}
+                    tag => {
+                        fields.insert(tag.to_string(), text);
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    dates
+}
+
+/// What a results listing says about being one page of several
+#[derive(Debug, Clone, Default)]
+pub struct ListingPaging {
+    // The next page, from <next>, <nextPage> or a <link rel="next">
+    pub next_url: Option<String>,
+    // Files in the whole listing, where the server says
+    pub total: Option<usize>,
+    // The server flagged this page as cut short
+    pub truncated: bool,
+}
+
+const NEXT_PAGE_TAGS: &[&str] = &["next", "nextpage", "next_page", "nexturi"];
+const TOTAL_KEYS: &[&str] = &["total", "totalcount", "total_count", "filecount", "count"];
+
+/// Paging hints of a results listing, none of which NSG is documented to
+/// send; outside <jobfile> elements only
+pub fn parse_listing_paging(xml: &str) -> ListingPaging {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut paging = ListingPaging::default();
+    let mut in_jobfile = false;
+    let mut in_next = false;
+    let mut current_tag = String::new();
+
+    let attribute_hints = |paging: &mut ListingPaging, e: &quick_xml::events::BytesStart| {
+        let mut rel_next = false;
+        let mut href = None;
+        for attr in e.attributes().flatten() {
+            let key = String::from_utf8_lossy(attr.key.as_ref()).to_lowercase();
+            let value = attr
+                .unescape_value()
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            match key.as_str() {
+                "rel" => rel_next = value.eq_ignore_ascii_case("next"),
+                "href" => href = Some(value),
+                "truncated" => paging.truncated |= value.eq_ignore_ascii_case("true"),
+                key if TOTAL_KEYS.contains(&key) => {
+                    if let Ok(total) = value.parse() {
+                        paging.total = Some(total);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if rel_next && paging.next_url.is_none() {
+            paging.next_url = href.filter(|h| !h.is_empty());
+        }
+    };
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                current_tag = String::from_utf8_lossy(e.name().as_ref()).to_lowercase();
+                if current_tag == "jobfile" {
+                    in_jobfile = true;
+                } else if !in_jobfile {
+                    in_next |= NEXT_PAGE_TAGS.contains(&current_tag.as_str());
+                    attribute_hints(&mut paging, &e);
+                }
+            }
+            Ok(Event::Empty(e)) if !in_jobfile => attribute_hints(&mut paging, &e),
+            Ok(Event::End(e)) => {
+                let tag = String::from_utf8_lossy(e.name().as_ref()).to_lowercase();
+                if tag == "jobfile" {
+                    in_jobfile = false;
+                } else if NEXT_PAGE_TAGS.contains(&tag.as_str()) {
+                    in_next = false;
+                }
+                current_tag.clear();
+            }
+            Ok(Event::Text(e)) if !in_jobfile => {
+                let text = reader
+                    .decoder()
+                    .decode(e.as_ref())
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_default();
+                if text.is_empty() {
+                    // Nothing to read
+                } else if in_next && paging.next_url.is_none() {
+                    paging.next_url = Some(text);
+                } else if current_tag == "truncated" {
+                    paging.truncated |= text.eq_ignore_ascii_case("true");
+                } else if TOTAL_KEYS.contains(&current_tag.as_str()) {
+                    if let Ok(total) = text.parse() {
+                        paging.total = Some(total);
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    paging
+}
+
+/// One `<tool>` of a `<toolList>` document
+#[derive(Debug, Clone, Default)]
+pub struct ListedTool {
+    pub tool_id: String,
+    pub tool_name: Option<String>,
+}
+
+/// Every tool in a `<toolList>` document, with its `<toolName>` when given
+pub fn parse_tool_list(xml: &str) -> Vec<ListedTool> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut tools: Vec<ListedTool> = Vec::new();
+    let mut current_tag = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => current_tag = e.name().as_ref().to_vec(),
+            Ok(Event::End(_)) => current_tag.clear(),
+            Ok(Event::Text(e)) => {
+                let Ok(text) = reader.decoder().decode(e.as_ref()) else {
+                    buf.clear();
+                    continue;
+                };
+                match current_tag.as_slice() {
+                    b"toolId" => tools.push(ListedTool {
+                        tool_id: text.to_string(),
+                        tool_name: None,
+                    }),
+                    // Follows its tool's ID
+                    b"toolName" => {
+                        if let Some(tool) = tools.last_mut() {
+                            tool.tool_name = Some(text.to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    tools
+}
+
+/// The `<toolId>` of every tool in a `<toolList>` document
+pub fn parse_tool_ids(xml: &str) -> Vec<String> {
+    parse_tool_list(xml)
+        .into_iter()
+        .map(|tool| tool.tool_id)
+        .collect()
+}
+
+/// What a submission response says about the job it created, whatever
+/// could be found in it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveredHandle {
+    pub job_id: Option<String>,
+    pub self_uri: Option<String>,
+}
+
+/// Text of the first `<tag>` after `from`, if it has any and is closed; a
+/// body cut off inside the element would give only part of it
+fn element_text<'a>(body: &'a str, tag: &str, from: usize) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = body[from..].find(&open)? + from + open.len();
+    let end = start + body[start..].find(&close)?;
+    let text = body[start..end].trim();
+    (!text.is_empty() && !text.contains('<')).then_some(text)
+}
+
+/// The job handle and status URL in a submission response that nsg-cli
+/// couldn't parse. Searched for as plain text rather than parsed, since the
+/// document may be cut off or malformed; a bare NGBW-JOB- identifier
+/// anywhere in the body is taken when there is no `<jobHandle>`, unless the
+/// body ends in it and it may be cut short.
+pub fn recover_job_handle(body: &str) -> RecoveredHandle {
+    let job_id = element_text(body, "jobHandle", 0)
+        .map(str::to_string)
+        .or_else(|| {
+            let start = body.find("NGBW-JOB-")?;
+            let id: String = body[start..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+                .collect();
+            let ended = start + id.len() < body.len();
+            (ended && id.len() > "NGBW-JOB-".len()).then_some(id)
+        });
+    let self_uri = body
+        .find("<selfUri>")
+        .and_then(|at| element_text(body, "url", at))
+        .map(str::to_string);
+    RecoveredHandle { job_id, self_uri }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_messages_are_kept_in_order() {
+        let extras = parse_job_extras(
+            "<jobstatus><messages>\
+             <message><timestamp>2026-01-02T03:04:05-08:00</timestamp><stage>QUEUE</stage>\
+             <text>Submitted to Expanse as job 42</text></message>\
+             <message><stage>COMPLETED</stage><text>Done</text></message>\
+             </messages></jobstatus>",
+        );
+        assert_eq!(extras.messages.len(), 2);
+        assert_eq!(
+            extras.messages[0].timestamp.as_deref(),
+            Some("2026-01-02T03:04:05-08:00")
+        );
+        assert_eq!(extras.messages[0].stage.as_deref(), Some("QUEUE"));
+        assert_eq!(extras.messages[0].text, "Submitted to Expanse as job 42");
+        assert_eq!(extras.messages[1].text, "Done");
+    }
+
+    #[test]
+    fn a_single_page_listing_has_no_paging() {
+        let paging = parse_listing_paging(
+            "<results><jobfiles><jobfile><filename>next</filename>\
+             <length>4</length></jobfile></jobfiles></results>",
+        );
+        assert_eq!(paging.next_url, None);
+        assert_eq!(paging.total, None);
+        assert!(!paging.truncated);
+    }
+
+    #[test]
+    fn paging_hints_are_read_from_elements_and_attributes() {
+        let paging = parse_listing_paging(
+            "<results truncated=\"true\"><nextPage>https://example.org/p2</nextPage>\
+             <totalCount>12</totalCount><jobfiles/></results>",
+        );
+        assert_eq!(paging.next_url.as_deref(), Some("https://example.org/p2"));
+        assert_eq!(paging.total, Some(12));
+        assert!(paging.truncated);
+
+        let paging = parse_listing_paging(
+            "<results count=\"3\"><link rel=\"self\" href=\"p1\"/>\
+             <link rel=\"next\" href=\"p2\"/><truncated>false</truncated></results>",
+        );
+        assert_eq!(paging.next_url.as_deref(), Some("p2"));
+        assert_eq!(paging.total, Some(3));
+        assert!(!paging.truncated);
+    }
+
+    #[test]
+    fn hints_inside_a_jobfile_are_ignored() {
+        let paging = parse_listing_paging(
+            "<results><jobfile><next>p2</next><count>9</count>\
+             <truncated>true</truncated></jobfile></results>",
+        );
+        assert_eq!(paging.next_url, None);
+        assert_eq!(paging.total, None);
+        assert!(!paging.truncated);
+    }
+
+    const URL: &str = "https://nsgr.sdsc.edu:8443/cipresrest/v1/job/alice/NGBW-JOB-PY_EXPANSE-1A2B";
+
+    #[test]
+    fn a_whole_response_gives_the_handle_and_url() {
+        let body = format!(
+            "<jobstatus><selfUri><url>{}</url><title>NGBW-JOB-PY_EXPANSE-1A2B</title></selfUri>\
+             <jobHandle>NGBW-JOB-PY_EXPANSE-1A2B</jobHandle><jobStage>QUEUE</jobStage>",
+            URL
+        );
+        assert_eq!(
+            recover_job_handle(&body),
+            RecoveredHandle {
+                job_id: Some("NGBW-JOB-PY_EXPANSE-1A2B".to_string()),
+                self_uri: Some(URL.to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn a_response_cut_off_inside_an_element_gives_nothing_of_it() {
+        let body = "<jobstatus><jobHandle>NGBW-JOB-PY_EXPANSE-1A2B</jobHandle>\
+                    <selfUri><url>https://nsgr.sdsc.edu:8443/cipresrest/v1/jo";
+        let handle = recover_job_handle(body);
+        assert_eq!(handle.job_id.as_deref(), Some("NGBW-JOB-PY_EXPANSE-1A2B"));
+        assert_eq!(handle.self_uri, None);
+
+        // Cut inside the handle itself: the partial ID isn't taken either
+        let handle = recover_job_handle("<jobstatus><jobHandle>NGBW-JOB-PY_EXP");
+        assert_eq!(handle, RecoveredHandle::default());
+    }
+
+    #[test]
+    fn malformed_elements_are_skipped() {
+        for body in [
+            "<jobHandle></jobHandle><selfUri><url>  </url></selfUri>",
+            "<jobHandle><b>x</b></jobHandle><selfUri><url><x/></url></selfUri>",
+            "<selfUri></selfUri>",
+            "",
+            "Internal Server Error",
+        ] {
+            assert_eq!(
+                recover_job_handle(body),
+                RecoveredHandle::default(),
+                "{}",
+                body
+            );
+        }
+        // A <url> that isn't in <selfUri> isn't the job's
+        let handle = recover_job_handle("<resultsUri><url>https://x</url></resultsUri>");
+        assert_eq!(handle.self_uri, None);
+    }
+
+    #[test]
+    fn a_bare_job_id_is_taken_without_a_handle() {
+        let handle = recover_job_handle("Job NGBW-JOB-PY_EXPANSE-1A2B was created.");
+        assert_eq!(handle.job_id.as_deref(), Some("NGBW-JOB-PY_EXPANSE-1A2B"));
+        assert_eq!(handle.self_uri, None);
+        // Just the prefix, or one that runs to the end of the body
+        assert_eq!(recover_job_handle("NGBW-JOB- ").job_id, None);
+        assert_eq!(recover_job_handle("created NGBW-JOB-PY_EX").job_id, None);
+    }
+}